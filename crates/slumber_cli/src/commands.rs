@@ -1,6 +1,18 @@
+pub mod baseline;
 pub mod collections;
+pub mod db;
+pub mod export;
 pub mod generate;
 pub mod history;
 pub mod import;
+pub mod lint;
+pub mod listen;
+pub mod proxy;
+pub mod report;
 pub mod request;
+pub mod run;
+pub mod schema;
+pub mod secrets;
+pub mod serve;
 pub mod show;
+pub mod test;
@@ -0,0 +1,134 @@
+use crate::{util::prompt_passphrase, GlobalArgs, Subcommand};
+use anyhow::{anyhow, bail};
+use clap::Parser;
+use slumber_core::{
+    collection::{CollectionFile, ProfileId, RecipeId},
+    db::{CollectionDatabase, Database},
+    http::{baseline, Exchange, RequestId},
+};
+use std::process::ExitCode;
+
+/// Pin a response as a recipe's baseline, and check other responses against
+/// it
+///
+/// Use the `baseline_ignore` field of a recipe to exclude volatile values
+/// (timestamps, IDs, etc.) from the comparison.
+#[derive(Clone, Debug, Parser)]
+pub struct BaselineCommand {
+    #[command(subcommand)]
+    subcommand: BaselineSubcommand,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+enum BaselineSubcommand {
+    /// Pin a response from history as the baseline for a recipe/profile
+    Pin {
+        #[command(flatten)]
+        target: Target,
+    },
+    /// Compare a response from history to the pinned baseline. Exits with
+    /// status 1 if they differ
+    Check {
+        #[command(flatten)]
+        target: Target,
+    },
+}
+
+/// Arguments to select a request from history
+#[derive(Clone, Debug, Parser)]
+struct Target {
+    /// Recipe to pin/check
+    recipe: RecipeId,
+
+    /// Profile to pin/check. If omitted, use requests with no profile
+    #[clap(long = "profile", short)]
+    profile: Option<ProfileId>,
+
+    /// ID of the request to use [default: most recent request for the
+    /// recipe/profile]
+    #[clap(long)]
+    request: Option<RequestId>,
+}
+
+impl Subcommand for BaselineCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(None, global.file)?;
+        let database = Database::load(prompt_passphrase)?
+            .into_collection(&collection_path)?;
+
+        match self.subcommand {
+            BaselineSubcommand::Pin { target } => {
+                let exchange = target.load(&database)?;
+                database.set_baseline(&exchange)?;
+                eprintln!(
+                    "Pinned request `{}` as baseline for `{}`",
+                    exchange.id, target.recipe
+                );
+                Ok(ExitCode::SUCCESS)
+            }
+            BaselineSubcommand::Check { target } => {
+                let exchange = target.load(&database)?;
+                let baseline = database
+                    .get_baseline(target.profile.as_ref(), &target.recipe)?
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "No baseline pinned for `{}`; pin one with \
+                            `slumber baseline pin`",
+                            target.recipe
+                        )
+                    })?;
+                let collection =
+                    CollectionFile::load(collection_path).await?.collection;
+                let ignore = collection
+                    .recipes
+                    .get_recipe(&target.recipe)
+                    .map(|recipe| recipe.baseline_ignore.as_slice())
+                    .unwrap_or_default();
+
+                let differences = baseline::compare(
+                    &baseline.response,
+                    &exchange.response,
+                    ignore,
+                );
+                if differences.is_empty() {
+                    eprintln!("Response matches baseline");
+                    Ok(ExitCode::SUCCESS)
+                } else {
+                    for difference in differences {
+                        println!("{difference}");
+                    }
+                    Ok(ExitCode::FAILURE)
+                }
+            }
+        }
+    }
+}
+
+impl Target {
+    /// Load the selected request from history
+    fn load(&self, database: &CollectionDatabase) -> anyhow::Result<Exchange> {
+        let exchange = if let Some(request_id) = self.request {
+            let exchange = database
+                .get_request(request_id)?
+                .ok_or_else(|| anyhow!("Request `{request_id}` not found"))?;
+            let request = &exchange.request;
+            if request.recipe_id != self.recipe
+                || request.profile_id != self.profile
+            {
+                bail!(
+                    "Request `{request_id}` is not for recipe `{}` and the \
+                    given profile",
+                    self.recipe
+                );
+            }
+            exchange
+        } else {
+            database
+                .get_latest_request(self.profile.as_ref(), &self.recipe)?
+                .ok_or_else(|| {
+                    anyhow!("No requests in history for `{}`", self.recipe)
+                })?
+        };
+        Ok(exchange)
+    }
+}
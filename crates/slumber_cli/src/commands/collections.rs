@@ -1,7 +1,12 @@
-use crate::{GlobalArgs, Subcommand};
+use crate::{util::prompt_passphrase, GlobalArgs, Subcommand};
+use anyhow::Context;
 use clap::Parser;
-use slumber_core::db::Database;
-use std::{path::PathBuf, process::ExitCode};
+use itertools::Itertools;
+use slumber_core::{
+    collection::{self, CollectionFile, RecipeId, ReferenceIssue},
+    db::Database,
+};
+use std::{fs, path::PathBuf, process::ExitCode};
 
 /// View and modify request collection metadata
 #[derive(Clone, Debug, Parser)]
@@ -25,11 +30,55 @@ enum CollectionsSubcommand {
         /// The path the collection to migrate *into*
         to: PathBuf,
     },
+    /// Show changes to the collection since the last git commit.
+    ///
+    /// Imported files are included. Changes are shown per template/field
+    /// rather than per line, so formatting changes are ignored.
+    Diff,
+    /// Report breaking changes between two versions of a collection.
+    ///
+    /// Lists recipes, profiles, profile fields, and chains that were removed
+    /// or renamed, which could break scripts or templates that refer to them
+    /// by ID. Items are reported as renamed if an added item has identical
+    /// content. Exits with status 1 if there are any breaking changes.
+    Compare {
+        /// Path to the old version of the collection
+        old: PathBuf,
+        /// Path to the new version of the collection
+        new: PathBuf,
+    },
+    /// Rename a recipe, updating every chain that refers to it.
+    ///
+    /// The collection file and any files it imports are edited in place,
+    /// retaining comments and formatting. Request history and baselines for
+    /// the recipe are moved to the new ID, so they aren't orphaned by the
+    /// rename.
+    RenameRecipe {
+        /// Current ID of the recipe
+        old: RecipeId,
+        /// New ID for the recipe
+        new: RecipeId,
+    },
+    /// List every key available in templates, as JSON.
+    ///
+    /// Includes profile fields, chains, fake data, and every environment
+    /// variable referenced in the collection. Intended for editor completion.
+    Keys {
+        /// Mark keys as `used` only if they're referenced by this recipe
+        #[clap(long)]
+        recipe: Option<RecipeId>,
+    },
+    /// Report unused and dangling references in the collection.
+    ///
+    /// Lists chains and profile fields that aren't used by any template, and
+    /// template keys that refer to fields or chains that don't exist. Exits
+    /// with status 1 if there are any dangling references.
+    References,
 }
 
 impl Subcommand for CollectionsCommand {
-    async fn execute(self, _global: GlobalArgs) -> anyhow::Result<ExitCode> {
-        let database = Database::load()?;
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let database = Database::load(prompt_passphrase)?;
         match self.subcommand {
             CollectionsSubcommand::List => {
                 for path in database.collections()? {
@@ -40,6 +89,72 @@ impl Subcommand for CollectionsCommand {
                 database.merge_collections(&from, &to)?;
                 println!("Migrated {} into {}", from.display(), to.display());
             }
+            CollectionsSubcommand::Diff => {
+                let path = CollectionFile::try_path(None, global.file)?;
+                // Imported files are compared too, so compare the full
+                // collection as of HEAD
+                let before =
+                    collection::load_git_head(&path).await.with_context(
+                        || format!("Error loading {path:?} from git HEAD"),
+                    )?;
+                let after = CollectionFile::load(path).await?.collection;
+                let changes = before.diff(&after)?;
+                if changes.is_empty() {
+                    eprintln!("No changes since HEAD");
+                }
+                for change in changes {
+                    println!("{change}");
+                }
+            }
+            CollectionsSubcommand::Compare { old, new } => {
+                let old = CollectionFile::load(old).await?.collection;
+                let new = CollectionFile::load(new).await?.collection;
+                let changes = old.breaking_changes(&new)?;
+                if changes.is_empty() {
+                    eprintln!("No breaking changes");
+                    return Ok(ExitCode::SUCCESS);
+                }
+                for change in changes {
+                    println!("{change}");
+                }
+                return Ok(ExitCode::FAILURE);
+            }
+            CollectionsSubcommand::RenameRecipe { old, new } => {
+                let path = CollectionFile::try_path(None, global.file)?;
+                let renamed = collection::rename_recipe(&path, &old, &new)?;
+                for (path, source) in &renamed {
+                    fs::write(path, source)
+                        .with_context(|| format!("Error writing {path:?}"))?;
+                }
+                let moved = database
+                    .into_collection(&path)?
+                    .rename_recipe(&old, &new)?;
+                println!(
+                    "Renamed recipe `{old}` to `{new}` in {}; \
+                    moved {moved} request(s) in history",
+                    renamed.iter().map(|(path, _)| path.display()).format(", ")
+                );
+            }
+            CollectionsSubcommand::Keys { recipe } => {
+                let path = CollectionFile::try_path(None, global.file)?;
+                let collection = CollectionFile::load(path).await?.collection;
+                let keys = collection.template_keys(recipe.as_ref())?;
+                println!("{}", serde_json::to_string_pretty(&keys)?);
+            }
+            CollectionsSubcommand::References => {
+                let path = CollectionFile::try_path(None, global.file)?;
+                let collection = CollectionFile::load(path).await?.collection;
+                let issues = collection.reference_issues()?;
+                if issues.is_empty() {
+                    eprintln!("No unused or dangling references");
+                }
+                for issue in &issues {
+                    println!("{issue}");
+                }
+                if issues.iter().any(ReferenceIssue::is_error) {
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
         }
         Ok(ExitCode::SUCCESS)
     }
@@ -0,0 +1,86 @@
+use crate::{
+    util::{prompt_passphrase, PASSPHRASE_VARIABLE},
+    GlobalArgs, Subcommand,
+};
+use anyhow::Context;
+use clap::Parser;
+use dialoguer::Password;
+use slumber_core::db::{Database, HistoryKey};
+use std::{env, path::PathBuf, process::ExitCode};
+
+/// Manage the local database of history and persisted state
+#[derive(Clone, Debug, Parser)]
+pub struct DbCommand {
+    #[command(subcommand)]
+    subcommand: DbSubcommand,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+enum DbSubcommand {
+    /// Delete persisted UI state, such as the selected profile and recipe.
+    ///
+    /// Request history is not affected.
+    ClearUiState {
+        /// Only clear state for the collection at this path. If omitted,
+        /// clear state for all collections
+        #[clap(long)]
+        collection: Option<PathBuf>,
+    },
+
+    /// Encrypt request history at rest.
+    ///
+    /// Request and response headers and bodies are encrypted, for all
+    /// existing history and all history stored from now on. By default the
+    /// key is stored in the OS keychain. Close other Slumber sessions first.
+    Encrypt {
+        /// Protect the key with a passphrase instead of the OS keychain. The
+        /// passphrase must be entered every time Slumber starts, or set in
+        /// the `SLUMBER_HISTORY_PASSPHRASE` environment variable
+        #[clap(long)]
+        passphrase: bool,
+    },
+
+    /// Decrypt request history, and stop encrypting new history
+    Decrypt,
+}
+
+impl Subcommand for DbCommand {
+    async fn execute(self, _global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let database = Database::load(prompt_passphrase)?;
+        match self.subcommand {
+            DbSubcommand::ClearUiState { collection } => {
+                let deleted = database.clear_ui_state(collection.as_deref())?;
+                println!("Cleared {deleted} UI state value(s)");
+            }
+            DbSubcommand::Encrypt { passphrase } => {
+                let key = if passphrase {
+                    HistoryKey::Passphrase(Self::new_passphrase()?)
+                } else {
+                    HistoryKey::Keychain
+                };
+                database.encrypt_history(key)?;
+                println!("History is now encrypted");
+            }
+            DbSubcommand::Decrypt => {
+                database.decrypt_history()?;
+                println!("History is no longer encrypted");
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+impl DbCommand {
+    /// Get a passphrase for newly encrypted history, from the environment or
+    /// by asking twice
+    fn new_passphrase() -> anyhow::Result<String> {
+        if let Ok(passphrase) = env::var(PASSPHRASE_VARIABLE) {
+            return Ok(passphrase);
+        }
+        Password::new()
+            .with_prompt("New history passphrase")
+            .with_confirmation("Confirm passphrase", "Passphrases don't match")
+            .interact()
+            .context("Error reading passphrase")
+    }
+}
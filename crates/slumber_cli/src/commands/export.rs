@@ -0,0 +1,98 @@
+use crate::{GlobalArgs, Subcommand};
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use slumber_core::collection::{CollectionFile, ProfileId};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::PathBuf,
+    process::ExitCode,
+};
+
+/// Convert the current Slumber collection to an external format
+#[derive(Clone, Debug, Parser)]
+pub struct ExportCommand {
+    /// Output format
+    format: Format,
+    /// Directory to write the exported files to
+    #[clap(long, short, default_value = ".")]
+    output_dir: PathBuf,
+    /// Name of the exported collection [default: name of the directory
+    /// containing the collection file]
+    #[clap(long)]
+    name: Option<String>,
+    /// Profile whose fields become file variables, for `http` [default: the
+    /// first profile in the collection]
+    #[clap(long = "profile", short)]
+    profile: Option<ProfileId>,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Format {
+    /// Postman v2.1 collection, plus an environment for each profile
+    Postman,
+    /// VS Code REST Client file (`.http`), with the fields of a single
+    /// profile as file variables
+    Http,
+}
+
+impl Subcommand for ExportCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let path = CollectionFile::try_path(None, global.file)?;
+        let name = self.name.clone().unwrap_or_else(|| {
+            path.canonicalize()
+                .ok()
+                .and_then(|path| {
+                    Some(path.parent()?.file_name()?.to_string_lossy().into())
+                })
+                .unwrap_or_else(|| "Slumber".into())
+        });
+        let collection = CollectionFile::load(path).await?.collection;
+
+        match self.format {
+            Format::Postman => {
+                let export = collection.to_postman(&name);
+                self.write(
+                    &format!("{name}.postman_collection.json"),
+                    &export.collection,
+                )?;
+                for environment in &export.environments {
+                    self.write(
+                        &format!("{}.postman_environment.json", environment.id),
+                        environment,
+                    )?;
+                }
+            }
+            Format::Http => {
+                let profile_id = self
+                    .profile
+                    .as_ref()
+                    .or_else(|| collection.profiles.keys().next());
+                let export = collection.to_rest_client(profile_id)?;
+                let path = self.output_dir.join(format!("{name}.http"));
+                fs::write(&path, export)
+                    .with_context(|| format!("Error writing to {path:?}"))?;
+                eprintln!("Wrote {}", path.display());
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+impl ExportCommand {
+    /// Write a JSON file to the output directory
+    fn write(
+        &self,
+        file_name: &str,
+        value: &impl Serialize,
+    ) -> anyhow::Result<()> {
+        let path = self.output_dir.join(file_name);
+        let file = File::create(&path)
+            .with_context(|| format!("Error creating file {path:?}"))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), value)
+            .with_context(|| format!("Error writing to {path:?}"))?;
+        eprintln!("Wrote {}", path.display());
+        Ok(())
+    }
+}
@@ -24,7 +24,7 @@ pub enum GenerateFormat {
 
 impl Subcommand for GenerateCommand {
     async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
-        let (_, ticket) = self
+        let (_, _, ticket) = self
             .build_request
             // User has to explicitly opt into executing triggered requests
             .build_request(global, self.execute_triggers)
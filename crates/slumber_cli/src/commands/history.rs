@@ -1,14 +1,27 @@
-use crate::{util::HeaderDisplay, GlobalArgs, Subcommand};
-use anyhow::anyhow;
-use clap::Parser;
+use crate::{
+    util::{prompt_passphrase, HeaderDisplay},
+    GlobalArgs, Subcommand,
+};
+use anyhow::{anyhow, Context};
+use clap::{Parser, ValueEnum};
 use dialoguer::console::Style;
+use itertools::Itertools;
 use slumber_core::{
     collection::{CollectionFile, ProfileId, RecipeId},
-    db::Database,
-    http::{Exchange, ExchangeSummary, RequestId},
+    db::{Database, FailureSummary, SearchMatch},
+    http::{
+        blob::BlobStore, export, failure::FailureKind, Exchange,
+        ExchangeSummary, RequestId,
+    },
     util::{format_byte_size, format_duration, format_time, MaybeStr},
 };
-use std::process::ExitCode;
+use std::{
+    cmp::Reverse,
+    fs::File,
+    io::{self, BufReader, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
 use tracing::warn;
 
 /// View request collection history (unstable)
@@ -33,8 +46,91 @@ enum HistorySubcommand {
         profile: Option<ProfileId>,
     },
 
+    /// List requests for a recipe/profile combination that failed without
+    /// getting a response
+    Failures {
+        /// Recipe to query for
+        recipe: RecipeId,
+
+        /// Profile to query for. If omitted, query for requests with no
+        /// profile
+        #[clap(long = "profile", short)]
+        profile: Option<ProfileId>,
+
+        /// Only show failures of this kind: build, dns, connect, tls, timeout,
+        /// cancelled, or other
+        #[clap(long, short)]
+        kind: Option<FailureKind>,
+    },
+
+    /// Summarize the outcomes of all requests for a recipe/profile
+    /// combination: how many got each status code, and how many failed for
+    /// each reason
+    Stats {
+        /// Recipe to query for
+        recipe: RecipeId,
+
+        /// Profile to query for. If omitted, query for requests with no
+        /// profile
+        #[clap(long = "profile", short)]
+        profile: Option<ProfileId>,
+
+        /// Only count failures of this kind: build, dns, connect, tls,
+        /// timeout, cancelled, or other. Responses are omitted
+        #[clap(long, short)]
+        kind: Option<FailureKind>,
+    },
+
     /// Print an entire request/response by ID
     Get { request: RequestId },
+
+    /// Search request URLs, notes, headers, and bodies (and response headers
+    /// and bodies) across all recipes and profiles. Each word in the query must
+    /// appear somewhere in the request. Most recent matches are shown first.
+    Search {
+        query: String,
+
+        /// Maximum number of matches to show
+        #[clap(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Export request history, e.g. to move it to another machine or attach
+    /// it to a bug report
+    Export {
+        /// Output format
+        #[clap(long, short, default_value = "har")]
+        format: Format,
+
+        /// Only export requests for this recipe
+        #[clap(long, short)]
+        recipe: Option<RecipeId>,
+
+        /// Destination file [default: stdout]
+        output_file: Option<PathBuf>,
+    },
+
+    /// Import request history from a file generated by `history export`.
+    /// Requests that are already in history are skipped.
+    Import {
+        /// Input format
+        #[clap(long, short, default_value = "har")]
+        format: Format,
+
+        /// File to import
+        input_file: PathBuf,
+    },
+}
+
+/// Format for exported history
+#[derive(Copy, Clone, Debug, ValueEnum)]
+#[allow(rustdoc::bare_urls)]
+enum Format {
+    /// HTTP Archive, readable by browser dev tools and other HTTP tools
+    /// http://www.softwareishard.com/blog/har-12-spec/
+    Har,
+    /// JSON Lines, with one request per line
+    Jsonl,
 }
 
 impl Subcommand for HistoryCommand {
@@ -44,7 +140,8 @@ impl Subcommand for HistoryCommand {
             it may change or be removed at any time"
         );
         let collection_path = CollectionFile::try_path(None, global.file)?;
-        let database = Database::load()?.into_collection(&collection_path)?;
+        let database = Database::load(prompt_passphrase)?
+            .into_collection(&collection_path)?;
 
         match self.subcommand {
             HistorySubcommand::List { recipe, profile } => {
@@ -52,12 +149,74 @@ impl Subcommand for HistoryCommand {
                     database.get_all_requests(profile.as_ref(), &recipe)?;
                 Self::print_list(exchanges);
             }
+            HistorySubcommand::Failures {
+                recipe,
+                profile,
+                kind,
+            } => {
+                let failures =
+                    database.get_failures(profile.as_ref(), &recipe, kind)?;
+                Self::print_failures(failures);
+            }
+            HistorySubcommand::Stats {
+                recipe,
+                profile,
+                kind,
+            } => {
+                let exchanges = if kind.is_some() {
+                    vec![]
+                } else {
+                    database.get_all_requests(profile.as_ref(), &recipe)?
+                };
+                let failures =
+                    database.get_failures(profile.as_ref(), &recipe, kind)?;
+                Self::print_stats(&exchanges, &failures);
+            }
             HistorySubcommand::Get { request } => {
                 let exchange = database
                     .get_request(request)?
                     .ok_or_else(|| anyhow!("Request `{request}` not found"))?;
                 Self::print_detail(exchange);
             }
+            HistorySubcommand::Search { query, limit } => {
+                let matches = database.search_requests(&query, limit)?;
+                Self::print_matches(matches);
+            }
+            HistorySubcommand::Export {
+                format,
+                recipe,
+                output_file,
+            } => {
+                let exchanges = database.get_all_exchanges(recipe.as_ref())?;
+                let writer: Box<dyn Write> = match output_file {
+                    Some(output_file) => Box::new(
+                        File::create(&output_file).context(format!(
+                            "Error opening history output file \
+                            {output_file:?}"
+                        ))?,
+                    ),
+                    None => Box::new(io::stdout()),
+                };
+                match format {
+                    Format::Har => export::write_har(&exchanges, writer)?,
+                    Format::Jsonl => export::write_jsonl(&exchanges, writer)?,
+                }
+                eprintln!("Exported {} requests", exchanges.len());
+            }
+            HistorySubcommand::Import { format, input_file } => {
+                let reader = BufReader::new(File::open(&input_file).context(
+                    format!("Error opening history input file {input_file:?}"),
+                )?);
+                let exchanges = match format {
+                    Format::Har => export::read_har(reader)?,
+                    Format::Jsonl => export::read_jsonl(reader)?,
+                };
+                let imported = database.import_exchanges(&exchanges)?;
+                eprintln!(
+                    "Imported {imported} requests ({} already in history)",
+                    exchanges.len() - imported
+                );
+            }
         }
         Ok(ExitCode::SUCCESS)
     }
@@ -66,12 +225,88 @@ impl Subcommand for HistoryCommand {
 impl HistoryCommand {
     fn print_list(exchanges: Vec<ExchangeSummary>) {
         for exchange in exchanges {
-            println!(
+            print!(
                 "{} {} {}",
                 exchange.id,
                 exchange.status,
                 format_time(&exchange.start_time)
             );
+            if let Some(trigger) = &exchange.trigger {
+                print!(" (triggered by {trigger})");
+            }
+            if let Some(note) = &exchange.note {
+                print!(" {note}");
+            }
+            println!();
+        }
+    }
+
+    fn print_failures(failures: Vec<FailureSummary>) {
+        for failure in failures {
+            print!(
+                "{} {} {}",
+                failure.id,
+                failure.kind,
+                format_time(&failure.start_time)
+            );
+            if let (Some(method), Some(url)) = (&failure.method, &failure.url) {
+                print!(" {method} {url}");
+            }
+            println!();
+            println!("    {}", failure.message);
+        }
+    }
+
+    fn print_stats(exchanges: &[ExchangeSummary], failures: &[FailureSummary]) {
+        let header_style = Style::new().bold().underlined();
+        let total = exchanges.len() + failures.len();
+        let percent = |count: usize| count as f64 / total as f64 * 100.0;
+        println!("{total} requests");
+
+        if !exchanges.is_empty() {
+            println!("{}", header_style.apply_to("STATUS"));
+            let statuses = exchanges
+                .iter()
+                .counts_by(|exchange| exchange.status)
+                .into_iter()
+                .sorted();
+            for (status, count) in statuses {
+                println!("{status}: {count} ({:.1}%)", percent(count));
+            }
+        }
+
+        if !failures.is_empty() {
+            println!("{}", header_style.apply_to("FAILURES"));
+            let kinds = failures
+                .iter()
+                .counts_by(|failure| failure.kind)
+                .into_iter()
+                .sorted_by_key(|(_, count)| Reverse(*count));
+            for (kind, count) in kinds {
+                println!(
+                    "{}: {count} ({:.1}%)",
+                    kind.description(),
+                    percent(count)
+                );
+            }
+        }
+    }
+
+    fn print_matches(matches: Vec<SearchMatch>) {
+        let snippet_style = Style::new().dim();
+        for search_match in matches {
+            print!(
+                "{} {} {} {}",
+                search_match.id,
+                search_match.status,
+                format_time(&search_match.start_time),
+                search_match.recipe_id,
+            );
+            if let Some(profile_id) = &search_match.profile_id {
+                print!(" ({profile_id})");
+            }
+            println!(" {} {}", search_match.method, search_match.url);
+            println!("    {}", snippet_style.apply_to(&search_match.snippet));
         }
     }
 
@@ -84,6 +319,9 @@ impl HistoryCommand {
         println!("{}", header_style.apply_to("REQUEST"));
         println!("{} {}", subheader_style.apply_to("URL:"), request.url);
         println!("{} {}", subheader_style.apply_to("Method:"), request.method);
+        if let Some(note) = &request.note {
+            println!("{} {note}", subheader_style.apply_to("Note:"));
+        }
         print!(
             "{}\n{}",
             subheader_style.apply_to("Headers"),
@@ -111,6 +349,25 @@ impl HistoryCommand {
             subheader_style.apply_to("Duration:"),
             format_duration(&exchange.duration())
         );
+        if let Some(profile_id) = &request.profile_id {
+            println!("{} {profile_id}", subheader_style.apply_to("Profile:"));
+        }
+        let environment = &request.environment;
+        if let Some(trigger) = &environment.trigger {
+            println!("{} {trigger}", subheader_style.apply_to("Triggered By:"));
+        }
+        if let Some(git_revision) = &environment.git_revision {
+            println!(
+                "{} {git_revision}",
+                subheader_style.apply_to("Git Revision:")
+            );
+        }
+        if !environment.profile_data.is_empty() {
+            println!("{}", subheader_style.apply_to("Profile Data"));
+            for (field, value) in &environment.profile_data {
+                println!("{}: {value}", subheader_style.apply_to(field));
+            }
+        }
         println!();
 
         // Response
@@ -132,5 +389,13 @@ impl HistoryCommand {
             format_byte_size(response.body.size()),
             MaybeStr(response.body.bytes())
         );
+        // Streamed bodies are too large to print, so only the preview is shown
+        if let Some(blob) = response.body.blob() {
+            println!(
+                "\n(Showing the first {}. The full body is in {})",
+                format_byte_size(response.body.bytes().len()),
+                BlobStore::global().path(blob).display()
+            );
+        }
     }
 }
@@ -1,11 +1,14 @@
-use crate::{GlobalArgs, Subcommand};
-use anyhow::Context;
+use crate::{util::prompt_passphrase, GlobalArgs, Subcommand};
+use anyhow::{bail, Context};
 use clap::{Parser, ValueEnum};
-use slumber_core::collection::Collection;
+use slumber_core::{
+    collection::{self, Collection, CollectionFile, HarImport, RecipeId},
+    db::Database,
+};
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{self, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::ExitCode,
 };
 
@@ -14,10 +17,19 @@ use std::{
 pub struct ImportCommand {
     /// Input format
     format: Format,
-    /// Collection to import
+    /// Collection to import. For `curl`, a file containing the command, or
+    /// `-` to read it from stdin
     input_file: PathBuf,
-    /// Destination for the new slumber collection file [default: stdout]
+    /// Destination for the new slumber collection file [default: stdout].
+    /// Not used for `curl`, which adds a recipe to the current collection
     output_file: Option<PathBuf>,
+    /// What to generate from a HAR file
+    #[clap(long, value_enum, default_value_t = HarMode::Recipes)]
+    mode: HarMode,
+    /// ID for the recipe imported from a curl command [default: generated
+    /// from the method and URL]
+    #[clap(long)]
+    id: Option<RecipeId>,
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
@@ -28,33 +40,128 @@ enum Format {
     /// OpenAPI v3.0 (JSON or YAML) v3.1 not supported but may work
     /// https://spec.openapis.org/oas/v3.0.3
     Openapi,
+    /// A single curl command, added as a recipe to the current collection
+    Curl,
+    /// HTTP Archive, e.g. exported from a browser's dev tools
+    Har,
+    /// VS Code REST Client file (`.http` or `.rest`)
+    Http,
+}
+
+/// What to do with the requests in a HAR file
+#[derive(Copy, Clone, Debug, Default, PartialEq, ValueEnum)]
+enum HarMode {
+    /// Generate a recipe for each endpoint
+    #[default]
+    Recipes,
+    /// Insert the requests and responses into request history
+    History,
+    /// Generate recipes and insert into history. Requires an output file
+    Both,
 }
 
 impl Subcommand for ImportCommand {
-    async fn execute(self, _global: GlobalArgs) -> anyhow::Result<ExitCode> {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
         // Load the input
         let collection = match self.format {
             Format::Insomnia => Collection::from_insomnia(&self.input_file)?,
             Format::Openapi => Collection::from_openapi(&self.input_file)?,
+            Format::Http => Collection::from_rest_client(&self.input_file)?,
+            Format::Curl => return self.import_curl(global).await,
+            Format::Har => return self.import_har(global),
         };
+        write_collection(self.output_file.as_deref(), &collection)?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
 
-        // Write the output
-        let mut writer: Box<dyn Write> = match self.output_file {
-            Some(output_file) => Box::new(
-                File::options()
-                    .create(true)
-                    .truncate(true)
-                    .write(true)
-                    .open(&output_file)
-                    .context(format!(
-                        "Error opening collection output file \
-                        {output_file:?}"
-                    ))?,
-            ),
-            None => Box::new(io::stdout()),
+impl ImportCommand {
+    /// A curl command is only a single recipe, so rather than generating a
+    /// new collection, add it to the existing one
+    async fn import_curl(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        if self.output_file.is_some() {
+            bail!(
+                "curl commands are imported into the current collection file; \
+                use `--file` to select a different one"
+            );
+        }
+        let command = if self.input_file == Path::new("-") {
+            io::read_to_string(io::stdin()).context("Error reading stdin")?
+        } else {
+            fs::read_to_string(&self.input_file).with_context(|| {
+                format!("Error reading {:?}", self.input_file)
+            })?
         };
-        serde_yaml::to_writer(&mut writer, &collection)?;
 
+        let path = CollectionFile::try_path(None, global.file)?;
+        let source = fs::read_to_string(&path)
+            .with_context(|| format!("Error reading {path:?}"))?;
+        // Recipes can come from imported files, so check against all of them
+        let loaded = CollectionFile::load(path.clone()).await?.collection;
+        let (source, id) =
+            collection::import_curl(&source, &loaded, &command, self.id)?;
+        fs::write(&path, source)
+            .with_context(|| format!("Error writing {path:?}"))?;
+        println!("Added recipe `{id}` to {}", path.display());
         Ok(ExitCode::SUCCESS)
     }
+
+    /// A HAR file can generate recipes, seed request history so chains have
+    /// real responses to work with, or both
+    fn import_har(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let import = HarImport::load(&self.input_file)?;
+
+        if self.mode != HarMode::History {
+            if self.mode == HarMode::Both && self.output_file.is_none() {
+                bail!(
+                    "An output file is required to generate recipes and \
+                    history together"
+                );
+            }
+            write_collection(
+                self.output_file.as_deref(),
+                &import.to_collection()?,
+            )?;
+        }
+
+        if self.mode != HarMode::Recipes {
+            // History is stored per collection. If we just generated one,
+            // that's where it belongs, otherwise use the current collection
+            let collection_path = match self.output_file {
+                Some(output_file) if self.mode == HarMode::Both => output_file,
+                _ => CollectionFile::try_path(None, global.file)?,
+            };
+            let database = Database::load(prompt_passphrase)?
+                .into_collection(&collection_path)?;
+            let imported = database.import_exchanges(&import.exchanges)?;
+            eprintln!(
+                "Imported {imported} requests ({} already in history)",
+                import.exchanges.len() - imported
+            );
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Write a generated collection to a file, or stdout if no file is given
+fn write_collection(
+    output_file: Option<&Path>,
+    collection: &Collection,
+) -> anyhow::Result<()> {
+    let mut writer: Box<dyn Write> = match output_file {
+        Some(output_file) => Box::new(
+            File::options()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(output_file)
+                .context(format!(
+                    "Error opening collection output file \
+                    {output_file:?}"
+                ))?,
+        ),
+        None => Box::new(io::stdout()),
+    };
+    serde_yaml::to_writer(&mut writer, collection)?;
+    Ok(())
 }
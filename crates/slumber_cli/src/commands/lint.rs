@@ -0,0 +1,69 @@
+use crate::{GlobalArgs, Subcommand};
+use clap::Parser;
+use slumber_core::collection::{self, CollectionFile, Severity};
+use std::{env, process::ExitCode};
+
+/// Check the collection for mistakes, without sending any requests
+///
+/// Reports problems that will break requests, such as templates that refer to
+/// unknown fields or chains, `!request` chains that refer to unknown recipes,
+/// and files that fail to load. Also warns about likely mistakes: fields that
+/// are missing from some profiles, recipes that no profile can build,
+/// deprecated syntax, and unused chains and fields. Each problem is printed
+/// with the file and line it was found on. Exits with status 1 if there are
+/// any errors, for use in CI.
+#[derive(Clone, Debug, Parser)]
+pub struct LintCommand {
+    /// Exit with status 1 if there are any warnings, not just errors
+    #[clap(long)]
+    strict: bool,
+    /// Print diagnostics as a JSON array
+    #[clap(long)]
+    json: bool,
+}
+
+impl Subcommand for LintCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let path = CollectionFile::try_path(None, global.file)?;
+        let mut diagnostics = collection::lint(&path);
+
+        // Show paths relative to the current directory, like a compiler
+        if let Ok(current_dir) = env::current_dir() {
+            let current_dir = current_dir.canonicalize().unwrap_or(current_dir);
+            for diagnostic in &mut diagnostics {
+                if let Ok(relative) = diagnostic.file.strip_prefix(&current_dir)
+                {
+                    diagnostic.file = relative.to_owned();
+                }
+            }
+        }
+
+        let count = |severity| {
+            diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == severity)
+                .count()
+        };
+        let errors = count(Severity::Error);
+        let warnings = count(Severity::Warning);
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&diagnostics)?);
+        } else {
+            for diagnostic in &diagnostics {
+                println!("{diagnostic}");
+            }
+            if diagnostics.is_empty() {
+                eprintln!("No problems found");
+            } else {
+                eprintln!("{errors} error(s), {warnings} warning(s)");
+            }
+        }
+
+        Ok(if errors > 0 || (self.strict && warnings > 0) {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+}
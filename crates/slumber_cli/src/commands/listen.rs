@@ -0,0 +1,84 @@
+use crate::{
+    util::{prompt_passphrase, HeaderDisplay},
+    GlobalArgs, Subcommand,
+};
+use anyhow::Context;
+use clap::Parser;
+use dialoguer::console::Style;
+use slumber_core::{
+    collection::CollectionFile,
+    db::Database,
+    http::{CapturedRequest, Listener},
+    util::{format_time, MaybeStr},
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    process::ExitCode,
+};
+
+/// Listen for incoming HTTP requests, e.g. webhooks or OAuth redirects
+///
+/// Every received request is printed, and saved so it can be viewed later in
+/// the TUI. Runs until killed.
+#[derive(Clone, Debug, Parser)]
+pub struct ListenCommand {
+    /// Port to listen on
+    port: u16,
+
+    /// Address to bind to. Use `0.0.0.0` to accept requests from other hosts
+    #[clap(long, default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    host: IpAddr,
+
+    /// HTTP status code to respond to every request with
+    #[clap(long, default_value_t = 200)]
+    status: u16,
+
+    /// Print request headers
+    #[clap(long)]
+    headers: bool,
+
+    /// Do not print request bodies
+    #[clap(long)]
+    no_body: bool,
+}
+
+impl Subcommand for ListenCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(None, global.file)?;
+        let database = Database::load(prompt_passphrase)?
+            .into_collection(&collection_path)?;
+        let status = self.status.try_into().with_context(|| {
+            format!("Invalid HTTP status code `{}`", self.status)
+        })?;
+
+        let listener =
+            Listener::bind(SocketAddr::new(self.host, self.port), status)
+                .await?;
+        eprintln!("Listening on http://{}", listener.local_addr()?);
+        listener
+            .run(database, move |request| {
+                Self::print_request(request, self.headers, !self.no_body)
+            })
+            .await;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+impl ListenCommand {
+    fn print_request(request: &CapturedRequest, headers: bool, body: bool) {
+        let header_style = Style::new().bold();
+        println!(
+            "{} {}",
+            format_time(&request.time),
+            header_style
+                .apply_to(format!("{} {}", request.method, request.url)),
+        );
+        if headers {
+            print!("{}", HeaderDisplay(&request.headers));
+        }
+        if body && !request.body.is_empty() {
+            println!("{}", MaybeStr(&request.body));
+        }
+        println!();
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{util::prompt_passphrase, GlobalArgs, Subcommand};
+use anyhow::anyhow;
+use clap::Parser;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use slumber_core::{
+    collection::{CollectionFile, RecipeId, RecipeNode},
+    db::Database,
+    http::{Exchange, Proxy, RequestId, PROXY_RECIPE_ID},
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    process::ExitCode,
+};
+
+/// Record traffic from other HTTP clients (browsers, apps, etc.)
+///
+/// Point a client's HTTP proxy setting at Slumber, and every plain HTTP
+/// exchange will be saved to history. HTTPS traffic is passed through, but
+/// not recorded.
+#[derive(Clone, Debug, Parser)]
+pub struct ProxyCommand {
+    #[command(subcommand)]
+    subcommand: ProxySubcommand,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+enum ProxySubcommand {
+    /// Start the proxy. Runs until killed
+    Start {
+        /// Port to listen on
+        port: u16,
+        /// Address to bind to. Use `0.0.0.0` to accept traffic from other
+        /// hosts
+        #[clap(long, default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+        host: IpAddr,
+    },
+    /// Print a recorded request as a recipe, to be pasted into the `requests`
+    /// section of your collection
+    Promote {
+        /// ID of the recorded request, as printed by `slumber proxy start`
+        request: RequestId,
+        /// ID for the new recipe [default: derived from the method and path]
+        #[clap(long)]
+        id: Option<RecipeId>,
+    },
+}
+
+impl Subcommand for ProxyCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(None, global.file)?;
+        let database = Database::load(prompt_passphrase)?
+            .into_collection(&collection_path)?;
+
+        match self.subcommand {
+            ProxySubcommand::Start { port, host } => {
+                let proxy = Proxy::bind(SocketAddr::new(host, port)).await?;
+                eprintln!("Proxy listening on http://{}", proxy.local_addr()?);
+                proxy.run(database, Self::print_exchange).await;
+            }
+            ProxySubcommand::Promote { request, id } => {
+                let exchange = database
+                    .get_request(request)?
+                    .filter(|exchange| {
+                        exchange.request.recipe_id.as_str() == PROXY_RECIPE_ID
+                    })
+                    .ok_or_else(|| {
+                        anyhow!("Proxied request `{request}` not found")
+                    })?;
+                let id = id.unwrap_or_else(|| default_recipe_id(&exchange));
+                let recipe = exchange.request.to_recipe(id.clone())?;
+                let requests: IndexMap<RecipeId, RecipeNode> =
+                    [(id, recipe.into())].into_iter().collect();
+                print!("{}", serde_yaml::to_string(&requests)?);
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+impl ProxyCommand {
+    fn print_exchange(exchange: &Exchange) {
+        println!(
+            "{} {} {} {}",
+            exchange.id,
+            exchange.response.status.as_u16(),
+            exchange.request.method,
+            exchange.request.url,
+        );
+    }
+}
+
+/// Generate a recipe ID from a request, e.g. `GET /api/users` becomes
+/// `get_api_users`
+fn default_recipe_id(exchange: &Exchange) -> RecipeId {
+    let request = &exchange.request;
+    let path = request.url.path();
+    let id = format!("{}_{}", request.method, path)
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .join("_");
+    id.into()
+}
@@ -0,0 +1,281 @@
+//! Checking responses and reporting results. This is shared by `slumber run`
+//! and `slumber test`, so both check and report the same way.
+
+use itertools::Itertools;
+use slumber_core::{collection::Recipe, http::Exchange};
+use std::{iter, process::ExitCode};
+
+/// Check an exchange against each of the recipe's assertions, in order
+pub fn check_assertions(
+    recipe: &Recipe,
+    exchange: &Exchange,
+) -> Vec<AssertionResult> {
+    recipe
+        .assertions
+        .iter()
+        .map(|assertion| AssertionResult {
+            assertion: assertion.to_string(),
+            failure: assertion
+                .check(exchange)
+                .err()
+                .map(|failure| failure.to_string()),
+        })
+        .collect()
+}
+
+/// The result of checking a single assertion
+#[derive(Debug, PartialEq)]
+pub struct AssertionResult {
+    /// Description of the assertion
+    pub assertion: String,
+    /// Explanation of the failure, if it failed
+    pub failure: Option<String>,
+}
+
+impl AssertionResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// The result of running a single recipe or test, for the summary table
+#[derive(Debug)]
+pub struct Outcome<'a> {
+    /// Recipe ID or test name
+    pub name: &'a str,
+    /// Row of the `--data` file this was run for, if any
+    pub row: Option<usize>,
+    pub result: OutcomeResult,
+    /// Status code and duration in milliseconds, if a response was received
+    pub response: Option<(u16, i64)>,
+}
+
+impl<'a> Outcome<'a> {
+    pub fn success(&self) -> bool {
+        matches!(self.result, OutcomeResult::Passed)
+    }
+}
+
+#[derive(Debug)]
+pub enum OutcomeResult {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+impl OutcomeResult {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Passed => "PASS",
+            Self::Failed => "FAIL",
+            Self::Skipped => "SKIP",
+        }
+    }
+}
+
+/// Print a table with one row per outcome, followed by pass/fail counts.
+/// `name_header` is the header of the column of recipe IDs or test names.
+pub fn print_summary(name_header: &str, outcomes: &[Outcome]) {
+    for line in summary_lines(name_header, outcomes) {
+        println!("{line}");
+    }
+}
+
+/// Get the exit code for a set of outcomes: success only if every one passed
+pub fn exit_code(outcomes: &[Outcome]) -> ExitCode {
+    if outcomes.iter().all(Outcome::success) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Build the lines for [print_summary]. If a data file was used, the first
+/// column is the data row each recipe was run for. The status and time
+/// columns are only included if any outcome got a response, so they're
+/// omitted for tests that don't send requests.
+fn summary_lines(name_header: &str, outcomes: &[Outcome]) -> Vec<String> {
+    let has_rows = outcomes.iter().any(|outcome| outcome.row.is_some());
+    let has_responses =
+        outcomes.iter().any(|outcome| outcome.response.is_some());
+    let rows: Vec<Vec<String>> = outcomes
+        .iter()
+        .map(|outcome| {
+            let response = has_responses.then(|| match &outcome.response {
+                Some((status, duration_ms)) => {
+                    [status.to_string(), format!("{duration_ms}ms")]
+                }
+                None => ["-".into(), "-".into()],
+            });
+            // Either every outcome has a row or none do
+            let row = outcome.row.map(|row| row.to_string());
+            row.into_iter()
+                .chain([outcome.name.to_owned()])
+                .chain(response.into_iter().flatten())
+                .chain([outcome.result.label().to_owned()])
+                .collect()
+        })
+        .collect();
+    let header: Vec<String> = has_rows
+        .then_some("ROW")
+        .into_iter()
+        .chain([name_header])
+        .chain(
+            has_responses
+                .then_some(["STATUS", "TIME"])
+                .into_iter()
+                .flatten(),
+        )
+        .chain(["RESULT"])
+        .map(String::from)
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            iter::once(&header)
+                .chain(&rows)
+                .map(|row| row[i].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let count = |result: fn(&OutcomeResult) -> bool| {
+        outcomes
+            .iter()
+            .filter(|outcome| result(&outcome.result))
+            .count()
+    };
+    let passed = count(|result| matches!(result, OutcomeResult::Passed));
+    let failed = count(|result| matches!(result, OutcomeResult::Failed));
+    let skipped = count(|result| matches!(result, OutcomeResult::Skipped));
+    let mut counts = format!("{passed} passed, {failed} failed");
+    if skipped > 0 {
+        counts.push_str(&format!(", {skipped} skipped"));
+    }
+
+    iter::once(&header)
+        .chain(&rows)
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .join("  ");
+            line.trim_end().to_owned()
+        })
+        .chain([counts])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use reqwest::StatusCode;
+    use rstest::rstest;
+    use slumber_core::{
+        collection::Assertion,
+        http::{RequestRecord, ResponseRecord},
+        test_util::Factory,
+    };
+
+    #[test]
+    fn test_check_assertions() {
+        let recipe = Recipe {
+            assertions: vec![
+                Assertion::Status(404),
+                Assertion::Status(200),
+                Assertion::Header {
+                    name: "x-missing".into(),
+                    matches: None,
+                },
+            ],
+            ..Recipe::factory(())
+        };
+        let exchange = Exchange::factory((
+            RequestRecord::factory(()),
+            ResponseRecord::factory(StatusCode::NOT_FOUND),
+        ));
+        let results = check_assertions(&recipe, &exchange);
+        assert_eq!(
+            results
+                .iter()
+                .map(AssertionResult::passed)
+                .collect::<Vec<_>>(),
+            [true, false, false]
+        );
+        assert_eq!(results[0].assertion, "status 404");
+        assert!(results[1].failure.is_some());
+    }
+
+    fn outcome(
+        name: &str,
+        row: Option<usize>,
+        result: OutcomeResult,
+        response: Option<(u16, i64)>,
+    ) -> Outcome<'_> {
+        Outcome {
+            name,
+            row,
+            result,
+            response,
+        }
+    }
+
+    #[rstest]
+    #[case::responses(
+        vec![
+            outcome("login", None, OutcomeResult::Passed, Some((200, 142))),
+            outcome("get_user", None, OutcomeResult::Failed, None),
+            outcome("logout", None, OutcomeResult::Skipped, None),
+        ],
+        &[
+            "RECIPE    STATUS  TIME   RESULT",
+            "login     200     142ms  PASS",
+            "get_user  -       -      FAIL",
+            "logout    -       -      SKIP",
+            "1 passed, 1 failed, 1 skipped",
+        ],
+    )]
+    #[case::rows(
+        vec![
+            outcome("login", Some(1), OutcomeResult::Passed, Some((200, 5))),
+            outcome("login", Some(2), OutcomeResult::Failed, Some((401, 35))),
+        ],
+        &[
+            "ROW  RECIPE  STATUS  TIME  RESULT",
+            "1    login   200     5ms   PASS",
+            "2    login   401     35ms  FAIL",
+            "1 passed, 1 failed",
+        ],
+    )]
+    // Without any responses, status and time are left out
+    #[case::no_responses(
+        vec![
+            outcome("login", None, OutcomeResult::Passed, None),
+            outcome("get_user", None, OutcomeResult::Failed, None),
+        ],
+        &[
+            "RECIPE    RESULT",
+            "login     PASS",
+            "get_user  FAIL",
+            "1 passed, 1 failed",
+        ],
+    )]
+    fn test_summary_lines(
+        #[case] outcomes: Vec<Outcome>,
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(summary_lines("RECIPE", &outcomes), expected);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let passed = outcome("a", None, OutcomeResult::Passed, None);
+        let skipped = outcome("b", None, OutcomeResult::Skipped, None);
+        // ExitCode doesn't implement PartialEq
+        let debug = |code: ExitCode| format!("{code:?}");
+        assert_eq!(debug(exit_code(&[passed])), debug(ExitCode::SUCCESS));
+        assert_eq!(debug(exit_code(&[skipped])), debug(ExitCode::FAILURE));
+    }
+}
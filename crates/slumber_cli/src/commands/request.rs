@@ -1,22 +1,38 @@
-use crate::{util::HeaderDisplay, GlobalArgs, Subcommand};
-use anyhow::{anyhow, Context};
-use clap::Parser;
-use dialoguer::{Input, Password, Select as DialoguerSelect};
+use crate::{
+    util::{prompt_passphrase, HeaderDisplay},
+    GlobalArgs, Subcommand,
+};
+use anyhow::{anyhow, bail, Context};
+use clap::{Parser, ValueEnum};
+use dialoguer::{console::Style, Input, Password, Select as DialoguerSelect};
 use indexmap::IndexMap;
 use itertools::Itertools;
+use rand::{seq::SliceRandom, Rng};
 use slumber_config::Config;
 use slumber_core::{
-    collection::{CollectionFile, ProfileId, RecipeId},
+    collection::{
+        parse_duration, Collection, CollectionFile, Method, ProfileId,
+        RecipeBody, RecipeFields, RecipeId, Workspace,
+    },
     db::{CollectionDatabase, Database},
-    http::{BuildOptions, HttpEngine, RequestSeed, RequestTicket},
-    template::{Prompt, Prompter, Select, TemplateContext, TemplateError},
-    util::ResultTraced,
+    http::{
+        blob::BlobStore, contract::Contract, export, load::run_concurrent,
+        BuildOptions, Exchange, HttpEngine, RequestSeed, RequestTicket,
+    },
+    template::{
+        Prompt, Prompter, Select, Template, TemplateContext, TemplateError,
+    },
+    util::{format_byte_size, format_duration, ResultTraced},
 };
 use std::{
     error::Error,
-    io::{self, Write},
+    fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
     process::ExitCode,
     str::FromStr,
+    sync::Arc,
+    time::Duration,
 };
 use tracing::warn;
 
@@ -24,6 +40,10 @@ use tracing::warn;
 /// an error status code
 const HTTP_ERROR_EXIT_CODE: u8 = 2;
 
+/// Exit code to return when `check_contract` flag is set and the response
+/// violates the OpenAPI spec
+const CONTRACT_ERROR_EXIT_CODE: u8 = 3;
+
 /// Execute a single request, and print its response
 #[derive(Clone, Debug, Parser)]
 #[clap(aliases=&["req", "rq"])]
@@ -43,15 +63,101 @@ pub struct RequestCommand {
     #[clap(long)]
     no_body: bool,
 
+    /// Print only one part of the response to stdout, for use in scripts.
+    /// `json` prints the entire exchange (status, headers, body, and timing)
+    /// as a JSON object. Replaces `--status`, `--headers`, and `--no-body`
+    #[clap(
+        long,
+        conflicts_with_all = ["status", "headers", "no_body", "dry_run"],
+    )]
+    output: Option<OutputFormat>,
+
+    /// Write the response body to this file instead of stdout. The body is
+    /// written byte-for-byte, so this is safe for binary content. If the path
+    /// is a directory, the file name is taken from the response's
+    /// `Content-Disposition` header, or guessed from its `Content-Type`
+    #[clap(long, conflicts_with_all = ["no_body", "output", "dry_run"])]
+    output_file: Option<PathBuf>,
+
     /// Set process exit code based on HTTP response status. If the status is
     /// <400, exit code is 0. If it's >=400, exit code is 2.
     #[clap(long)]
     exit_status: bool,
 
+    /// Set process exit code based on HTTP response status. If the status is
+    /// 2xx, exit code is 0. Otherwise, exit code is 2. This is stricter than
+    /// `--exit-status`, which allows redirects
+    #[clap(long)]
+    exit_code: bool,
+
+    /// Validate the response against the OpenAPI spec linked by the
+    /// collection's `openapi` field. Violations are printed to stderr, and the
+    /// exit code is 3 if there are any.
+    #[clap(long)]
+    check_contract: bool,
+
     /// Just print the generated request, instead of sending it. Triggered
     /// sub-requests will also not be executed.
     #[clap(long)]
     dry_run: bool,
+
+    /// Send the request this many times and print a summary of the results,
+    /// instead of the response. Each copy is rendered separately, but chains
+    /// are only computed once
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = [
+            "status", "headers", "output", "output_file", "check_contract",
+            "dry_run",
+        ],
+    )]
+    repeat: Option<u32>,
+
+    /// Maximum number of requests to have in flight at once, with `--repeat`
+    #[clap(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+    )]
+    concurrency: u32,
+
+    #[clap(flatten)]
+    chaos: ChaosArgs,
+}
+
+/// What to print for a response with `--output`
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum OutputFormat {
+    /// The status code, e.g. `200`
+    Status,
+    /// Response headers, one per line
+    Headers,
+    /// The raw response body
+    Body,
+    /// The request and response, as a JSON object. This is the same format as
+    /// `slumber history export --format jsonl`
+    Json,
+}
+
+/// Fault injection, to exercise how scripts handle a slow or flaky API
+#[derive(Clone, Debug, Parser)]
+pub struct ChaosArgs {
+    /// Wait before sending the request. Accepts a fixed duration (`500ms`) or
+    /// a range to pick from at random (`100ms..2s`)
+    #[clap(long)]
+    delay: Option<Delay>,
+
+    /// Percent chance (0-100) that the request is dropped instead of sent. A
+    /// dropped request fails the same way a network error would.
+    #[clap(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    drop: Option<u8>,
+
+    /// Send requests in a random order. With `--repeat`, the copies are sent
+    /// in random order; with `slumber run`, the recipes in each run (or each
+    /// row of `--data`) are shuffled. Has no effect on a single request
+    #[clap(long)]
+    shuffle: bool,
 }
 
 /// A helper for any subcommand that needs to build requests. This handles
@@ -59,11 +165,94 @@ pub struct RequestCommand {
 #[derive(Clone, Debug, Parser)]
 pub struct BuildRequestCommand {
     /// ID of the recipe to render into a request
-    recipe_id: RecipeId,
+    #[clap(required_unless_present_any = ["workspace", "url"])]
+    recipe_id: Option<RecipeId>,
+
+    /// Recipe from a workspace member, as `<member>:<recipe>`. Use this in
+    /// place of the recipe ID to select a collection from the
+    /// slumber-workspace.yml in the current or an ancestor directory
+    #[clap(long, conflicts_with = "recipe_id")]
+    workspace: Option<WorkspaceRecipe>,
+
+    #[clap(flatten)]
+    ad_hoc: AdHocArgs,
+
+    #[clap(flatten)]
+    recipe_overrides: RecipeOverrideArgs,
+
+    #[clap(flatten)]
+    pub render: RenderArgs,
+}
+
+/// Define a one-off request on the command line, in place of a recipe. The URL
+/// is a template, rendered with the selected profile like a recipe would be.
+/// Headers, query parameters, and a body can be added with [RecipeOverrideArgs].
+#[derive(Clone, Debug, Default, Parser)]
+struct AdHocArgs {
+    /// URL to send a one-off request to, instead of building a recipe from
+    /// the collection. History for these requests is stored under the recipe
+    /// ID `_scratch`
+    #[clap(long, conflicts_with_all = ["recipe_id", "workspace"])]
+    url: Option<String>,
+
+    /// HTTP method for `--url` [default: GET]
+    #[clap(
+        long,
+        requires = "url",
+        value_parser = |s: &str| Method::try_from(s.to_owned()),
+    )]
+    method: Option<Method>,
+}
+
+/// Modify the built request for a single invocation, without editing the
+/// collection. Headers, query parameters, and the body are templates; the body
+/// file is not.
+#[derive(Clone, Debug, Default, Parser)]
+struct RecipeOverrideArgs {
+    /// Set a header, as `name: value`. Replaces any header of the same name in
+    /// the recipe, or adds it if there isn't one. An empty value (`name:`)
+    /// removes the header. Can be given multiple times
+    #[clap(long = "header", id = "header", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// Set a query parameter, as `name=value`. Replaces all parameters of the
+    /// same name in the recipe. Repeat a name to pass multiple values
+    #[clap(long = "query", value_parser = parse_key_val::<String, String>)]
+    query: Vec<(String, String)>,
+
+    /// Replace the request body
+    #[clap(long)]
+    body: Option<String>,
 
+    /// Replace the request body with the contents of a file. The contents are
+    /// sent as-is, *not* rendered as a template
+    #[clap(long, conflicts_with = "body")]
+    body_file: Option<PathBuf>,
+
+    /// Give a value to one of the recipe's parameters, as `name=value`.
+    /// Parameters that aren't given use their default. Can be given multiple
+    /// times
+    #[clap(long = "param", value_parser = parse_key_val::<String, String>)]
+    parameters: Vec<(String, String)>,
+}
+
+/// [RecipeOverrideArgs] with templates parsed and the body file loaded, so
+/// they can be applied to multiple builds
+#[derive(Clone, Debug, Default)]
+struct RecipeOverrides {
+    headers: Vec<(String, Option<Template>)>,
+    query: Vec<(String, Template)>,
+    body: Option<Template>,
+    parameters: IndexMap<String, String>,
+}
+
+/// Args that control how templates are rendered, independent of which recipe
+/// is being built
+#[derive(Clone, Debug, Parser)]
+pub struct RenderArgs {
     /// ID of the profile to pull template values from
     #[clap(long = "profile", short)]
-    profile: Option<ProfileId>,
+    pub profile: Option<ProfileId>,
 
     /// List of key=value template field overrides
     #[clap(
@@ -71,12 +260,25 @@ pub struct BuildRequestCommand {
         short = 'o',
         value_parser = parse_key_val::<String, String>,
     )]
-    overrides: Vec<(String, String)>,
+    pub overrides: Vec<(String, String)>,
+
+    /// Seed for `fake.*` template values, to generate the same values every
+    /// time. Overrides the profile's `fake_seed` field
+    #[clap(long)]
+    fake_seed: Option<u64>,
+
+    /// Clear all cached chain values before rendering, so they're recomputed
+    #[clap(long)]
+    pub clear_cache: bool,
 }
 
 impl Subcommand for RequestCommand {
     async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
-        let (database, ticket) = self
+        if let Some(repeat) = self.repeat {
+            return self.execute_repeated(global, repeat as usize).await;
+        }
+
+        let (database, collection, ticket) = self
             .build_request
             // Don't execute sub-requests in a dry run
             .build_request(global, !self.dry_run)
@@ -104,30 +306,80 @@ impl Subcommand for RequestCommand {
             }
 
             // Run the request
-            let exchange = ticket.send(&database).await?;
+            self.chaos.apply().await?;
+            // Show download progress if the body isn't going to the
+            // terminal. Progress is only reported for slow downloads
+            let show_progress =
+                self.output_file.is_some() && io::stderr().is_terminal();
+            let mut showed_progress = false;
+            let exchange = ticket
+                .send_with_progress(&database, |progress| {
+                    if show_progress {
+                        eprint!("\rDownloading {:<24}", progress.to_string());
+                        showed_progress = true;
+                    }
+                })
+                .await?;
+            if showed_progress {
+                eprintln!();
+            }
             let status = exchange.response.status;
 
             // Print stuff!
-            if self.status {
-                eprintln!("{}", status.as_u16());
-            }
-            if self.headers {
-                eprintln!("{}", HeaderDisplay(&exchange.response.headers));
+            match self.output {
+                None => {
+                    if self.status {
+                        eprintln!("{}", status.as_u16());
+                    }
+                    if self.headers {
+                        eprintln!(
+                            "{}",
+                            HeaderDisplay(&exchange.response.headers)
+                        );
+                    }
+                    if let Some(full_size) = exchange.response.body.full_size()
+                    {
+                        eprintln!(
+                            "Body truncated to {} of {} (see `max_body_size`)",
+                            format_byte_size(exchange.response.body.size()),
+                            format_byte_size(full_size as usize),
+                        );
+                    }
+                    match &self.output_file {
+                        Some(path) => {
+                            let path = write_body_file(&exchange, path)?;
+                            eprintln!("Saved body to {}", path.display());
+                        }
+                        None if !self.no_body => write_body(&exchange)?,
+                        None => {}
+                    }
+                }
+                Some(OutputFormat::Status) => println!("{}", status.as_u16()),
+                Some(OutputFormat::Headers) => {
+                    print!("{}", HeaderDisplay(&exchange.response.headers))
+                }
+                Some(OutputFormat::Body) => write_body(&exchange)?,
+                Some(OutputFormat::Json) => {
+                    export::write_json(&exchange, io::stdout())?
+                }
             }
-            if !self.no_body {
-                // If body is not UTF-8, write the raw bytes instead (e.g if
-                // downloading an image)
-                let body = &exchange.response.body;
-                if let Some(text) = body.text() {
-                    print!("{}", text);
-                } else {
-                    io::stdout()
-                        .write(body.bytes())
-                        .context("Error writing to stdout")?;
+
+            if self.check_contract {
+                let path = collection.openapi.as_deref().ok_or_else(|| {
+                    anyhow!("Collection has no `openapi` spec to check against")
+                })?;
+                let violations = Contract::load(path)?.validate(&exchange);
+                for violation in &violations {
+                    eprintln!("{violation}");
+                }
+                if !violations.is_empty() {
+                    return Ok(ExitCode::from(CONTRACT_ERROR_EXIT_CODE));
                 }
             }
 
-            if self.exit_status && status.as_u16() >= 400 {
+            let is_error = (self.exit_code && !status.is_success())
+                || (self.exit_status && status.as_u16() >= 400);
+            if is_error {
                 Ok(ExitCode::from(HTTP_ERROR_EXIT_CODE))
             } else {
                 Ok(ExitCode::SUCCESS)
@@ -136,10 +388,128 @@ impl Subcommand for RequestCommand {
     }
 }
 
+impl RequestCommand {
+    /// Send many copies of the request concurrently, then print the
+    /// distribution of status codes and latencies
+    async fn execute_repeated(
+        self,
+        global: GlobalArgs,
+        repeat: usize,
+    ) -> anyhow::Result<ExitCode> {
+        let (database, _, mut tickets) = self
+            .build_request
+            .build_requests(global, true, repeat)
+            .await?;
+        self.chaos.shuffle(&mut tickets);
+        let requests = tickets.into_iter().map(|ticket| async {
+            self.chaos.apply().await?;
+            ticket.send(&database).await.map_err(|error| error.error)
+        });
+        let report = run_concurrent(requests, self.concurrency as usize).await;
+
+        let header_style = Style::new().bold().underlined();
+        println!(
+            "Sent {} requests in {} ({:.1} requests/s)",
+            report.total(),
+            format_duration(&report.elapsed),
+            report.requests_per_second()
+        );
+        println!();
+        println!("{}", header_style.apply_to("STATUS"));
+        for (status, count) in &report.statuses {
+            println!("{status}: {count}");
+        }
+        for (error, count) in &report.errors {
+            println!("Error: {error}: {count}");
+        }
+        if let (Some(min), Some(max)) =
+            (report.percentile(0), report.percentile(100))
+        {
+            println!();
+            println!("{}", header_style.apply_to("LATENCY"));
+            println!("min: {}", format_duration(&min));
+            for percentile in [50, 90, 99] {
+                // Percentiles exist if min/max do
+                let latency = report.percentile(percentile).unwrap_or(max);
+                println!("p{percentile}: {}", format_duration(&latency));
+            }
+            println!("max: {}", format_duration(&max));
+        }
+
+        let has_error_status = report
+            .statuses
+            .keys()
+            .any(|status| status.is_client_error() || status.is_server_error());
+        if report.error_count() > 0 {
+            Ok(ExitCode::FAILURE)
+        } else if self.exit_status && has_error_status {
+            Ok(ExitCode::from(HTTP_ERROR_EXIT_CODE))
+        } else {
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
 impl BuildRequestCommand {
-    /// Render the request specified by the user. This returns the HTTP engine
-    /// too so it can be re-used if necessary (iff `trigger_dependencies` is
-    /// enabled).
+    /// Build a specific recipe from the collection in the current directory
+    pub fn new(recipe_id: RecipeId, render: RenderArgs) -> Self {
+        Self {
+            recipe_id: Some(recipe_id),
+            workspace: None,
+            ad_hoc: AdHocArgs::default(),
+            recipe_overrides: RecipeOverrideArgs::default(),
+            render,
+        }
+    }
+
+    /// ID of the recipe to build, from either the positional argument or
+    /// `--workspace`
+    pub fn recipe_id(&self) -> anyhow::Result<&RecipeId> {
+        match (&self.workspace, &self.recipe_id) {
+            (Some(WorkspaceRecipe { recipe_id, .. }), _)
+            | (None, Some(recipe_id)) => Ok(recipe_id),
+            // Clap requires one or the other
+            (None, None) => bail!("Recipe ID or `--workspace` is required"),
+        }
+    }
+
+    /// Build a different recipe from the same collection, with the same
+    /// options
+    pub fn with_recipe_id(&self, recipe_id: RecipeId) -> Self {
+        let mut new = self.clone();
+        match &mut new.workspace {
+            Some(workspace) => workspace.recipe_id = recipe_id,
+            None => new.recipe_id = Some(recipe_id),
+        }
+        new
+    }
+
+    /// Path to the collection file to build from. This is the workspace
+    /// member's collection if `--workspace` was given, otherwise the `--file`
+    /// argument or the default collection path
+    pub fn collection_path(
+        &self,
+        file: Option<PathBuf>,
+    ) -> anyhow::Result<PathBuf> {
+        match &self.workspace {
+            Some(WorkspaceRecipe { member, .. }) => {
+                if file.is_some() {
+                    bail!("`--file` cannot be used with `--workspace`");
+                }
+                let workspace = Workspace::detect(None)?.ok_or_else(|| {
+                    anyhow!(
+                        "No workspace file found in current or ancestor \
+                        directories"
+                    )
+                })?;
+                workspace.member_path(member)
+            }
+            None => CollectionFile::try_path(None, file),
+        }
+    }
+
+    /// Render the request specified by the user. This returns the database and
+    /// collection too, so they can be re-used after the request is sent.
     ///
     /// `trigger_dependencies` controls whether chained requests can be executed
     /// if their triggers apply.
@@ -147,16 +517,46 @@ impl BuildRequestCommand {
         self,
         global: GlobalArgs,
         trigger_dependencies: bool,
-    ) -> anyhow::Result<(CollectionDatabase, RequestTicket)> {
-        let collection_path = CollectionFile::try_path(None, global.file)?;
-        let database = Database::load()?.into_collection(&collection_path)?;
+    ) -> anyhow::Result<(CollectionDatabase, Arc<Collection>, RequestTicket)>
+    {
+        let (database, collection, mut tickets) =
+            self.build_requests(global, trigger_dependencies, 1).await?;
+        // We asked for exactly one
+        let ticket = tickets.pop().expect("No request built");
+        Ok((database, collection, ticket))
+    }
+
+    /// Render the request specified by the user `count` times. Each request is
+    /// rendered separately, so dynamic values such as `uuid()` differ between
+    /// them, but chains are only computed once and shared by all of them.
+    pub async fn build_requests(
+        self,
+        global: GlobalArgs,
+        trigger_dependencies: bool,
+        count: usize,
+    ) -> anyhow::Result<(CollectionDatabase, Arc<Collection>, Vec<RequestTicket>)>
+    {
+        let collection_path = self.collection_path(global.file)?;
+        let ad_hoc = self.ad_hoc.fields();
+        let recipe_overrides = self.recipe_overrides.resolve()?;
+        let recipe_id = match ad_hoc {
+            Some(_) => RecipeId::scratch(),
+            None => self.recipe_id()?.clone(),
+        };
+        let render = self.render;
+        let database = Database::load(prompt_passphrase)?
+            .into_collection(&collection_path)?;
+        if render.clear_cache {
+            database.clear_chain_cache()?;
+        }
         let collection_file = CollectionFile::load(collection_path).await?;
         let collection = collection_file.collection;
-        let config = Config::load()?;
-        let http_engine = HttpEngine::new(&config.http);
+        let mut config = Config::load()?;
+        config.http.offline |= global.offline;
+        let http_engine = HttpEngine::new(&config.http)?;
 
         // Validate profile ID, so we can provide a good error if it's invalid
-        if let Some(profile_id) = &self.profile {
+        if let Some(profile_id) = &render.profile {
             collection.profiles.get(profile_id).ok_or_else(|| {
                 anyhow!(
                     "No profile with ID `{profile_id}`; options are: {}",
@@ -166,10 +566,10 @@ impl BuildRequestCommand {
         }
 
         // Build the request
-        let overrides: IndexMap<_, _> = self.overrides.into_iter().collect();
+        let overrides: IndexMap<_, _> = render.overrides.into_iter().collect();
         let template_context = TemplateContext {
-            selected_profile: self.profile.clone(),
-            collection,
+            selected_profile: render.profile.clone(),
+            collection: Arc::clone(&collection),
             // Passing the HTTP engine is how we tell the template renderer that
             // it's ok to execute subrequests during render
             http_engine: if trigger_dependencies {
@@ -180,11 +580,186 @@ impl BuildRequestCommand {
             database: database.clone(),
             overrides,
             prompter: Box::new(CliPrompter),
+            fake_seed: render.fake_seed,
+            offline: config.http.offline,
             state: Default::default(),
         };
-        let seed = RequestSeed::new(self.recipe_id, BuildOptions::default());
-        let request = http_engine.build(seed, &template_context).await?;
-        Ok((database, request))
+        let mut tickets = Vec::with_capacity(count);
+        for _ in 0..count {
+            let options = recipe_overrides.build_options();
+            let seed = match &ad_hoc {
+                Some(fields) => RequestSeed {
+                    options,
+                    ..RequestSeed::scratch(fields.to_recipe(recipe_id.clone())?)
+                },
+                None => RequestSeed::new(recipe_id.clone(), options),
+            };
+            tickets.push(http_engine.build(seed, &template_context).await?);
+        }
+        Ok((database, collection, tickets))
+    }
+}
+
+impl AdHocArgs {
+    /// Get the recipe fields defined by the args. `None` if `--url` wasn't
+    /// given, meaning a recipe from the collection should be used instead
+    fn fields(&self) -> Option<RecipeFields> {
+        self.url.as_ref().map(|url| RecipeFields {
+            name: None,
+            method: self.method.unwrap_or(Method::Get),
+            url: url.clone(),
+            headers: IndexMap::new(),
+            body: None,
+        })
+    }
+}
+
+impl RecipeOverrideArgs {
+    /// Parse templates and load the body file
+    fn resolve(&self) -> anyhow::Result<RecipeOverrides> {
+        fn parse(template: &str, field: &str) -> anyhow::Result<Template> {
+            template
+                .parse()
+                .with_context(|| format!("Invalid template for {field}"))
+        }
+
+        let headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let template = if value.is_empty() {
+                    None
+                } else {
+                    Some(parse(value, &format!("header `{name}`"))?)
+                };
+                Ok((name.clone(), template))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let query = self
+            .query
+            .iter()
+            .map(|(name, value)| {
+                let template =
+                    parse(value, &format!("query parameter `{name}`"))?;
+                Ok((name.clone(), template))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let body = match (&self.body, &self.body_file) {
+            (Some(body), _) => Some(parse(body, "body")?),
+            (None, Some(path)) => {
+                let body = fs::read_to_string(path).with_context(|| {
+                    format!("Error reading body from `{}`", path.display())
+                })?;
+                Some(Template::raw(body))
+            }
+            (None, None) => None,
+        };
+        Ok(RecipeOverrides {
+            headers,
+            query,
+            body,
+            parameters: self.parameters.iter().cloned().collect(),
+        })
+    }
+}
+
+impl RecipeOverrides {
+    /// Get build options that apply these overrides
+    fn build_options(&self) -> BuildOptions {
+        BuildOptions {
+            extra_headers: self.headers.clone(),
+            extra_query_parameters: self.query.clone(),
+            body: self.body.clone().map(|body| RecipeBody::Raw {
+                body,
+                content_type: None,
+            }),
+            parameters: self.parameters.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl ChaosArgs {
+    /// Apply the delay and drop the request if enabled. Return an error if the
+    /// request should be dropped
+    pub async fn apply(&self) -> anyhow::Result<()> {
+        if let Some(delay) = &self.delay {
+            tokio::time::sleep(delay.pick()).await;
+        }
+        if let Some(percent) = self.drop {
+            if rand::thread_rng().gen_range(0..100) < percent {
+                bail!("Request dropped by `--drop {percent}`");
+            }
+        }
+        Ok(())
+    }
+
+    /// Randomize the order of a batch of requests, if enabled
+    pub fn shuffle<T>(&self, items: &mut [T]) {
+        if self.shuffle {
+            items.shuffle(&mut rand::thread_rng());
+        }
+    }
+}
+
+/// A fixed delay, or a range to pick one from at random
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Delay {
+    min: Duration,
+    max: Duration,
+}
+
+impl Delay {
+    /// Get a delay duration. Millisecond precision is plenty here
+    fn pick(&self) -> Duration {
+        let millis = rand::thread_rng()
+            .gen_range(self.min.as_millis()..=self.max.as_millis());
+        Duration::from_millis(u64::try_from(millis).unwrap_or(u64::MAX))
+    }
+}
+
+impl FromStr for Delay {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (min, max) = match s.split_once("..") {
+            Some((min, max)) => (parse_duration(min)?, parse_duration(max)?),
+            None => {
+                let delay = parse_duration(s)?;
+                (delay, delay)
+            }
+        };
+        if min > max {
+            bail!("Invalid delay range `{s}`, minimum is greater than maximum");
+        }
+        Ok(Self { min, max })
+    }
+}
+
+/// A recipe within a workspace member, parsed from `<member>:<recipe>`
+#[derive(Clone, Debug)]
+struct WorkspaceRecipe {
+    member: String,
+    recipe_id: RecipeId,
+}
+
+impl FromStr for WorkspaceRecipe {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((member, recipe_id))
+                if !member.is_empty() && !recipe_id.is_empty() =>
+            {
+                Ok(Self {
+                    member: member.to_owned(),
+                    recipe_id: recipe_id.to_owned().into(),
+                })
+            }
+            _ => bail!(
+                "Invalid workspace recipe `{s}`, must be `<member>:<recipe>`"
+            ),
+        }
     }
 }
 
@@ -243,7 +818,7 @@ impl Prompter for CliPrompter {
 }
 
 /// Parse a single key=value pair for an argument
-fn parse_key_val<T, U>(
+pub fn parse_key_val<T, U>(
     s: &str,
 ) -> Result<(T, U), Box<dyn Error + Send + Sync + 'static>>
 where
@@ -257,3 +832,174 @@ where
         .ok_or_else(|| format!("invalid key=value: no \"=\" found in `{s}`"))?;
     Ok((key.parse()?, value.parse()?))
 }
+
+/// Write a response body to stdout. If the body is not UTF-8, write the raw
+/// bytes instead (e.g if downloading an image)
+fn write_body(exchange: &Exchange) -> anyhow::Result<()> {
+    let body = &exchange.response.body;
+    if let Some(blob) = body.blob() {
+        // Streamed bodies are too large to load, so copy straight from disk
+        io::copy(&mut BlobStore::global().open(blob)?, &mut io::stdout())
+            .context("Error writing to stdout")?;
+    } else if let Some(text) = body.text() {
+        print!("{}", text);
+    } else {
+        io::stdout()
+            .write(body.bytes())
+            .context("Error writing to stdout")?;
+    }
+    Ok(())
+}
+
+/// Write the raw response body to a file, returning the path that was written.
+/// If the given path is a directory, the file name is taken from the response.
+/// A file name chosen by the server will never overwrite an existing file.
+fn write_body_file(
+    exchange: &Exchange,
+    path: &Path,
+) -> anyhow::Result<PathBuf> {
+    let (path, overwrite) = if path.is_dir() {
+        let file_name = exchange.response.file_name().ok_or_else(|| {
+            anyhow!(
+                "Response doesn't suggest a file name; pass a file path to \
+                `--output-file` instead of a directory"
+            )
+        })?;
+        (path.join(file_name), false)
+    } else {
+        (path.to_owned(), true)
+    };
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(overwrite)
+        .create_new(!overwrite)
+        .open(&path)
+        .with_context(|| format!("Error opening {path:?}"))?;
+    match exchange.response.body.blob() {
+        Some(blob) => io::copy(&mut BlobStore::global().open(blob)?, &mut file)
+            .map(|_| ()),
+        None => file.write_all(exchange.response.body.bytes()),
+    }
+    .with_context(|| format!("Error writing body to {path:?}"))?;
+    Ok(path)
+}
+
+/// Parse a `name: value` header for an argument
+fn parse_header(
+    s: &str,
+) -> Result<(String, String), Box<dyn Error + Send + Sync + 'static>> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("invalid header: no \":\" found in `{s}`"))?;
+    Ok((name.trim().to_owned(), value.trim().to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use slumber_core::assert_err;
+
+    #[rstest]
+    #[case::millis("500ms", 500, 500)]
+    #[case::seconds("2s", 2000, 2000)]
+    #[case::minutes("1m", 60_000, 60_000)]
+    #[case::hours("1h", 3_600_000, 3_600_000)]
+    #[case::range("100ms..2s", 100, 2000)]
+    #[case::range_equal("1s..1000ms", 1000, 1000)]
+    fn test_parse_delay(
+        #[case] input: &str,
+        #[case] expected_min: u64,
+        #[case] expected_max: u64,
+    ) {
+        assert_eq!(
+            input.parse::<Delay>().unwrap(),
+            Delay {
+                min: Duration::from_millis(expected_min),
+                max: Duration::from_millis(expected_max),
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::missing_unit("500", "Missing unit in duration `500`")]
+    #[case::missing_unit_range("100..200ms", "Missing unit in duration `100`")]
+    #[case::unknown_unit("5w", "Unknown duration unit `w`")]
+    #[case::no_quantity("ms", "Invalid duration `ms`")]
+    #[case::negative("-5ms", "Invalid duration `-5ms`")]
+    #[case::overflow(
+        "99999999999999999999ms",
+        "Invalid duration `99999999999999999999ms`"
+    )]
+    #[case::overflow_minutes(
+        "18446744073709551615m",
+        "Duration `18446744073709551615m` is too large"
+    )]
+    #[case::empty("", "Invalid duration ``")]
+    #[case::min_greater(
+        "2s..1s",
+        "Invalid delay range `2s..1s`, minimum is greater than maximum"
+    )]
+    fn test_parse_delay_error(
+        #[case] input: &str,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(input.parse::<Delay>(), expected_error);
+    }
+
+    fn chaos(args: &[&str]) -> ChaosArgs {
+        ChaosArgs::try_parse_from(["slumber"].iter().chain(args)).unwrap()
+    }
+
+    /// Dropping is all or nothing at the ends of the range, and disabled
+    /// chaos does nothing
+    #[rstest]
+    #[case::disabled(&[], true)]
+    #[case::never(&["--drop", "0"], true)]
+    #[case::always(&["--drop", "100"], false)]
+    #[tokio::test]
+    async fn test_chaos_drop(#[case] args: &[&str], #[case] expected_ok: bool) {
+        let result = chaos(args).apply().await;
+        if expected_ok {
+            result.unwrap();
+        } else {
+            assert_err!(result, "Request dropped by `--drop 100`");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chaos_delay() {
+        let start = std::time::Instant::now();
+        chaos(&["--delay", "20ms"]).apply().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    /// Shuffling keeps every item, and is skipped unless enabled
+    #[test]
+    fn test_chaos_shuffle() {
+        let mut items: Vec<u32> = (0..20).collect();
+        chaos(&[]).shuffle(&mut items);
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+
+        chaos(&["--shuffle"]).shuffle(&mut items);
+        items.sort_unstable();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+    }
+
+    /// Picked delays are always within the range
+    #[test]
+    fn test_pick_delay() {
+        let delay: Delay = "10ms..20ms".parse().unwrap();
+        for _ in 0..100 {
+            let picked = delay.pick();
+            assert!(
+                (delay.min..=delay.max).contains(&picked),
+                "{picked:?} out of range"
+            );
+        }
+        let delay: Delay = "15ms".parse().unwrap();
+        assert_eq!(delay.pick(), Duration::from_millis(15));
+    }
+}
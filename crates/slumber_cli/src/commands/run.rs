@@ -0,0 +1,633 @@
+use crate::{
+    commands::{
+        report::{self, Outcome, OutcomeResult},
+        request::{parse_key_val, BuildRequestCommand, ChaosArgs},
+    },
+    GlobalArgs, Subcommand,
+};
+use anyhow::{anyhow, bail, Context};
+use clap::Parser;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde::Serialize;
+use slumber_core::{
+    collection::{Collection, CollectionFile, ProfileId, RecipeId, RecipeNode},
+    http::{contract::Contract, Exchange, RequestId},
+    util::format_byte_size,
+};
+use std::{
+    ffi::OsStr,
+    fmt::Display,
+    fs, iter,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+/// Send requests without printing the responses, reporting progress as it goes
+///
+/// Pass multiple recipe IDs to run them one after another, in order. A folder
+/// ID runs every recipe within it. Each response is stored in history before
+/// the next recipe is built, so chains can pass values from one recipe to the
+/// next.
+///
+/// With `--data`, every recipe is run once for each row of a CSV or JSONL file,
+/// and the row's columns are available to templates as `{{data.<column>}}`.
+///
+/// With `--events-json`, progress is printed as line-delimited JSON events,
+/// for consumption by other programs.
+#[derive(Clone, Debug, Parser)]
+pub struct RunCommand {
+    #[clap(flatten)]
+    build_request: BuildRequestCommand,
+
+    /// Additional recipes or folders to run after the first, in order
+    #[clap(value_name = "RECIPE_ID")]
+    more_recipe_ids: Vec<RecipeId>,
+
+    /// Print progress as line-delimited JSON objects, instead of plain text.
+    /// Each object has an `event` field denoting its type
+    #[clap(long)]
+    events_json: bool,
+
+    /// Validate the response against the OpenAPI spec linked by the
+    /// collection's `openapi` field. Each violation is reported as a failed
+    /// assertion
+    #[clap(long)]
+    check_contract: bool,
+
+    /// Keep running the remaining recipes after one fails. By default, they
+    /// are skipped
+    #[clap(long)]
+    continue_on_error: bool,
+
+    /// Use a different profile for one recipe, as `<recipe>=<profile>`.
+    /// Overrides `--profile` for that recipe. Can be given multiple times
+    #[clap(
+        long = "recipe-profile",
+        value_name = "RECIPE=PROFILE",
+        value_parser = parse_key_val::<String, String>,
+    )]
+    recipe_profiles: Vec<(String, String)>,
+
+    /// Run the recipes once for each row of this file, exposing each column as
+    /// `{{data.<column>}}`. `.jsonl`/`.ndjson` files have one JSON object per
+    /// line; any other file is read as CSV with a header row
+    #[clap(long, value_name = "PATH")]
+    data: Option<PathBuf>,
+
+    #[clap(flatten)]
+    chaos: ChaosArgs,
+}
+
+impl Subcommand for RunCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let recipe_ids = self.recipe_ids(&global).await?;
+        let recipe_profiles: IndexMap<RecipeId, ProfileId> = self
+            .recipe_profiles
+            .iter()
+            .map(|(recipe_id, profile_id)| {
+                (recipe_id.clone().into(), profile_id.clone().into())
+            })
+            .collect();
+        if let Some(recipe_id) =
+            recipe_profiles.keys().find(|id| !recipe_ids.contains(id))
+        {
+            bail!("`--recipe-profile` given for `{recipe_id}`, which isn't being run");
+        }
+
+        // Without a data file, run everything once with no row
+        let rows: Vec<Option<DataRow>> = match &self.data {
+            Some(path) => load_data(path)?.into_iter().map(Some).collect(),
+            None => vec![None],
+        };
+
+        let mut outcomes: Vec<Outcome> =
+            Vec::with_capacity(rows.len() * recipe_ids.len());
+        for (row_index, row) in rows.iter().enumerate() {
+            // Data rows are numbered from 1. These aren't line numbers: the
+            // CSV header and blank JSONL lines aren't counted
+            let row_number = row.as_ref().map(|_| row_index + 1);
+            if let Some(row) = row_number {
+                self.emit(RunEvent::RowStarted { row });
+            }
+            // Each row is independent, so a failure only skips the rest of
+            // its own row
+            let row_start = outcomes.len();
+            let mut row_recipe_ids = recipe_ids.iter().collect_vec();
+            self.chaos.shuffle(&mut row_recipe_ids);
+            for (i, recipe_id) in row_recipe_ids.into_iter().enumerate() {
+                let failed = outcomes[row_start..]
+                    .iter()
+                    .any(|outcome| !outcome.success());
+                if failed && !self.continue_on_error {
+                    self.emit(RunEvent::Skipped { recipe: recipe_id });
+                    outcomes.push(Outcome {
+                        name: recipe_id,
+                        row: row_number,
+                        result: OutcomeResult::Skipped,
+                        response: None,
+                    });
+                    continue;
+                }
+
+                let mut build_request =
+                    self.build_request.with_recipe_id(recipe_id.clone());
+                if let Some(profile_id) = recipe_profiles.get(recipe_id) {
+                    build_request.render.profile = Some(profile_id.clone());
+                }
+                if let Some(row) = row {
+                    build_request.render.overrides.extend(row_overrides(row));
+                }
+                // Only clear the cache once, so chains can be shared between
+                // recipes
+                build_request.render.clear_cache &= row_index == 0 && i == 0;
+                let outcome =
+                    self.run(build_request, global.clone(), recipe_id).await;
+                outcomes.push(Outcome {
+                    row: row_number,
+                    ..outcome
+                });
+            }
+        }
+
+        // A summary is only useful for humans, and only if there are multiple
+        // recipes. JSON consumers can build their own from the events
+        if !self.events_json && outcomes.len() > 1 {
+            println!();
+            report::print_summary("RECIPE", &outcomes);
+        }
+        let success = outcomes.iter().all(Outcome::success);
+        self.emit(RunEvent::Finished { success });
+        Ok(report::exit_code(&outcomes))
+    }
+}
+
+impl RunCommand {
+    /// Get the ordered list of recipes to run, with folders expanded to the
+    /// recipes within them
+    async fn recipe_ids(
+        &self,
+        global: &GlobalArgs,
+    ) -> anyhow::Result<Vec<RecipeId>> {
+        let node_ids = iter::once(self.build_request.recipe_id()?)
+            .chain(&self.more_recipe_ids);
+        // If the collection fails to load, pass the IDs through as-is. The
+        // error will show up again when building the first request, where it
+        // gets reported as an event
+        let collection =
+            match self.build_request.collection_path(global.file.clone()) {
+                Ok(path) => CollectionFile::load(path)
+                    .await
+                    .ok()
+                    .map(|file| file.collection),
+                Err(_) => None,
+            };
+        let Some(collection) = collection else {
+            return Ok(node_ids.cloned().collect());
+        };
+
+        let mut recipe_ids = Vec::new();
+        for node_id in node_ids {
+            if let Some(RecipeNode::Folder(_)) = collection.recipes.get(node_id)
+            {
+                let recipes =
+                    collection.recipes.try_get_recipes_under(node_id)?;
+                if recipes.is_empty() {
+                    bail!("Folder `{node_id}` has no recipes");
+                }
+                recipe_ids.extend(recipes.into_iter().map(|r| r.id.clone()));
+            } else {
+                // Unknown IDs will fail to build, and get reported then
+                recipe_ids.push(node_id.clone());
+            }
+        }
+        Ok(recipe_ids)
+    }
+
+    /// Build and send a single request, emitting events along the way
+    async fn run<'a>(
+        &self,
+        build_request: BuildRequestCommand,
+        global: GlobalArgs,
+        recipe: &'a RecipeId,
+    ) -> Outcome<'a> {
+        let failed = |response| Outcome {
+            name: recipe,
+            row: None,
+            result: OutcomeResult::Failed,
+            response,
+        };
+
+        self.emit(RunEvent::BuildStarted { recipe });
+        let (database, collection, ticket) =
+            match build_request.build_request(global, true).await {
+                Ok(built) => built,
+                Err(error) => {
+                    self.emit(RunEvent::BuildFailed {
+                        recipe,
+                        error: format!("{error:#}"),
+                    });
+                    return failed(None);
+                }
+            };
+
+        let record = ticket.record();
+        let id = record.id;
+        self.emit(RunEvent::RequestSent {
+            recipe,
+            id,
+            method: record.method.to_string(),
+            url: record.url.to_string(),
+        });
+        let result = async {
+            self.chaos.apply().await?;
+            ticket.send(&database).await.map_err(|error| error.error)
+        }
+        .await;
+        let exchange = match result {
+            Ok(exchange) => exchange,
+            Err(error) => {
+                self.emit(RunEvent::RequestFailed {
+                    recipe,
+                    id,
+                    error: format!("{error:#}"),
+                });
+                return failed(None);
+            }
+        };
+        self.emit(RunEvent::ResponseReceived {
+            recipe,
+            id,
+            status: exchange.response.status.as_u16(),
+            duration_ms: exchange.duration().num_milliseconds(),
+            size: exchange.response.body.size(),
+        });
+
+        let assertions_passed =
+            self.check_assertions(recipe, &collection, &exchange);
+        let contract_passed = !self.check_contract
+            || self.check_contract(recipe, &collection, &exchange);
+        let response = Some((
+            exchange.response.status.as_u16(),
+            exchange.duration().num_milliseconds(),
+        ));
+        if assertions_passed && contract_passed {
+            Outcome {
+                name: recipe,
+                row: None,
+                result: OutcomeResult::Passed,
+                response,
+            }
+        } else {
+            failed(response)
+        }
+    }
+
+    /// Check the exchange against the recipe's assertions, emitting an event
+    /// for each one
+    fn check_assertions(
+        &self,
+        recipe_id: &RecipeId,
+        collection: &Collection,
+        exchange: &Exchange,
+    ) -> bool {
+        let Some(recipe) = collection.recipes.get_recipe(recipe_id) else {
+            return true;
+        };
+        let mut success = true;
+        for result in report::check_assertions(recipe, exchange) {
+            success &= result.passed();
+            self.emit(RunEvent::Assertion {
+                recipe: recipe_id,
+                passed: result.passed(),
+                assertion: result.assertion,
+                message: result.failure,
+            });
+        }
+        success
+    }
+
+    /// Validate the exchange against the collection's OpenAPI spec, emitting
+    /// an assertion event for each violation. If there are none, emit a single
+    /// passing assertion.
+    fn check_contract(
+        &self,
+        recipe: &RecipeId,
+        collection: &Collection,
+        exchange: &Exchange,
+    ) -> bool {
+        let violations = collection
+            .openapi
+            .as_deref()
+            .ok_or_else(|| {
+                anyhow!("Collection has no `openapi` spec to check against")
+            })
+            .and_then(Contract::load)
+            .map(|contract| contract.validate(exchange));
+        let messages = match violations {
+            Ok(violations) => {
+                violations.iter().map(ToString::to_string).collect()
+            }
+            // Report a broken spec as a failure rather than aborting, so
+            // consumers still get a well-formed event stream
+            Err(error) => vec![format!("{error:#}")],
+        };
+
+        if messages.is_empty() {
+            self.emit(RunEvent::Assertion {
+                recipe,
+                assertion: "contract".into(),
+                passed: true,
+                message: None,
+            });
+            true
+        } else {
+            for message in messages {
+                self.emit(RunEvent::Assertion {
+                    recipe,
+                    assertion: "contract".into(),
+                    passed: false,
+                    message: Some(message),
+                });
+            }
+            false
+        }
+    }
+
+    /// Print an event to stdout, as JSON or plain text
+    fn emit(&self, event: RunEvent) {
+        if self.events_json {
+            // Serialization can't fail; every field is a plain value
+            println!("{}", serde_json::to_string(&event).unwrap());
+        } else {
+            println!("{event}");
+        }
+    }
+}
+
+/// A step in the progress of a run. With `--events-json`, each event is
+/// serialized to one line, tagged by its type in the `event` field. This
+/// format is consumed by other programs, so changes must be backward
+/// compatible.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum RunEvent<'a> {
+    /// Starting a row of the `--data` file. Events up to the next row (or the
+    /// end) belong to this row. Data rows are numbered from 1, not counting
+    /// the CSV header or blank lines
+    RowStarted { row: usize },
+    /// Recipe wasn't run because an earlier recipe failed
+    Skipped { recipe: &'a RecipeId },
+    /// Request is being rendered, including any triggered sub-requests
+    BuildStarted { recipe: &'a RecipeId },
+    /// Rendering the request failed
+    BuildFailed { recipe: &'a RecipeId, error: String },
+    /// Request was built and is being sent
+    RequestSent {
+        recipe: &'a RecipeId,
+        id: RequestId,
+        method: String,
+        url: String,
+    },
+    /// Request failed to send or receive, e.g. due to a network error
+    RequestFailed {
+        recipe: &'a RecipeId,
+        id: RequestId,
+        error: String,
+    },
+    /// Response was received. A 4xx/5xx status is *not* a failure on its own
+    ResponseReceived {
+        recipe: &'a RecipeId,
+        id: RequestId,
+        status: u16,
+        duration_ms: i64,
+        /// Body size, in bytes
+        size: usize,
+    },
+    /// Result of checking a single expectation against the response
+    Assertion {
+        recipe: &'a RecipeId,
+        /// Description of the assertion, or `contract` for OpenAPI checks
+        assertion: String,
+        passed: bool,
+        /// Explanation of the failure, if it failed
+        message: Option<String>,
+    },
+    /// Run is complete. Always the last event
+    Finished { success: bool },
+}
+
+impl<'a> Display for RunEvent<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RowStarted { row } => write!(f, "Row {row}"),
+            Self::Skipped { recipe } => write!(f, "Skipping `{recipe}`"),
+            Self::BuildStarted { recipe } => write!(f, "Building `{recipe}`"),
+            Self::BuildFailed { error, .. } => {
+                write!(f, "Error building request: {error}")
+            }
+            Self::RequestSent { method, url, .. } => {
+                write!(f, "Sending {method} {url}")
+            }
+            Self::RequestFailed { error, .. } => {
+                write!(f, "Error sending request: {error}")
+            }
+            Self::ResponseReceived {
+                status,
+                duration_ms,
+                size,
+                ..
+            } => write!(
+                f,
+                "Received {status} in {duration_ms}ms ({})",
+                format_byte_size(*size)
+            ),
+            Self::Assertion {
+                assertion,
+                passed: true,
+                ..
+            } => write!(f, "PASS {assertion}"),
+            Self::Assertion {
+                assertion, message, ..
+            } => {
+                write!(f, "FAIL {assertion}")?;
+                if let Some(message) = message {
+                    write!(f, ": {message}")?;
+                }
+                Ok(())
+            }
+            Self::Finished { success: true } => write!(f, "Success"),
+            Self::Finished { success: false } => write!(f, "Failed"),
+        }
+    }
+}
+
+/// One row of a `--data` file, mapping each column name to its value
+type DataRow = IndexMap<String, String>;
+
+/// Get template overrides that expose a data row as `{{data.<column>}}`
+fn row_overrides(row: &DataRow) -> impl '_ + Iterator<Item = (String, String)> {
+    row.iter()
+        .map(|(column, value)| (format!("data.{column}"), value.clone()))
+}
+
+/// Load all rows from a data file. JSONL files (`.jsonl` or `.ndjson`) have
+/// one JSON object per line. Anything else is parsed as CSV, with column names
+/// taken from the header row.
+fn load_data(path: &Path) -> anyhow::Result<Vec<DataRow>> {
+    let context = || format!("Error loading data from `{}`", path.display());
+    let is_jsonl = matches!(
+        path.extension().and_then(OsStr::to_str),
+        Some("jsonl" | "ndjson")
+    );
+
+    let rows: Vec<DataRow> = if is_jsonl {
+        let content = fs::read_to_string(path).with_context(context)?;
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let object: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_str(line)
+                        .with_context(|| format!("Line {}", i + 1))?;
+                Ok(object
+                    .into_iter()
+                    .map(|(column, value)| {
+                        // Strings are inserted as-is. Anything else is
+                        // inserted as JSON, so numbers and objects still work
+                        let value = match value {
+                            serde_json::Value::String(value) => value,
+                            value => value.to_string(),
+                        };
+                        (column, value)
+                    })
+                    .collect())
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(context)?
+    } else {
+        let mut reader = csv::Reader::from_path(path).with_context(context)?;
+        let headers = reader.headers().with_context(context)?.clone();
+        reader
+            .records()
+            .map(|record| {
+                let record = record?;
+                Ok(headers
+                    .iter()
+                    .zip(&record)
+                    .map(|(column, value)| {
+                        (column.to_owned(), value.to_owned())
+                    })
+                    .collect())
+            })
+            .collect::<Result<Vec<_>, csv::Error>>()
+            .with_context(context)?
+    };
+
+    if rows.is_empty() {
+        bail!("Data file `{}` has no rows", path.display());
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use slumber_core::{
+        assert_err,
+        test_util::{temp_dir, TempDir},
+    };
+
+    fn row(columns: &[(&str, &str)]) -> DataRow {
+        columns
+            .iter()
+            .map(|(column, value)| ((*column).to_owned(), (*value).to_owned()))
+            .collect()
+    }
+
+    /// Write a data file and load it
+    fn load(
+        temp_dir: &TempDir,
+        file_name: &str,
+        content: &str,
+    ) -> anyhow::Result<Vec<DataRow>> {
+        let path = temp_dir.join(file_name);
+        fs::write(&path, content).unwrap();
+        load_data(&path)
+    }
+
+    #[rstest]
+    fn test_load_csv(temp_dir: TempDir) {
+        let rows =
+            load(&temp_dir, "data.csv", "id,name\n1,Alice\n2,\"Bob, Jr.\"\n")
+                .unwrap();
+        assert_eq!(
+            rows,
+            [
+                row(&[("id", "1"), ("name", "Alice")]),
+                row(&[("id", "2"), ("name", "Bob, Jr.")]),
+            ]
+        );
+    }
+
+    /// Blank lines are skipped, and anything other than a string is inserted
+    /// as JSON
+    #[rstest]
+    fn test_load_jsonl(temp_dir: TempDir) {
+        let rows = load(
+            &temp_dir,
+            "data.jsonl",
+            "{\"id\": 1, \"name\": \"Alice\"}\n\n  \n\
+            {\"id\": 2, \"admin\": true, \"tags\": [\"a\"], \"extra\": null}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            [
+                row(&[("id", "1"), ("name", "Alice")]),
+                row(&[
+                    ("id", "2"),
+                    ("admin", "true"),
+                    ("tags", "[\"a\"]"),
+                    ("extra", "null")
+                ]),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::csv_header_only("data.csv", "id,name\n", "has no rows")]
+    #[case::csv_empty("data.csv", "", "has no rows")]
+    #[case::csv_ragged(
+        "data.csv",
+        "id,name\n1,Alice\n2\n",
+        "found record with 1 fields, but the previous record has 2 fields"
+    )]
+    #[case::jsonl_empty("data.ndjson", "\n\n", "has no rows")]
+    // Errors report the line in the file, counting blank lines
+    #[case::jsonl_not_object("data.jsonl", "{\"id\": 1}\n\n[1, 2]\n", "Line 3")]
+    #[case::jsonl_invalid("data.jsonl", "{\"id\": \n", "Line 1")]
+    fn test_load_data_error(
+        temp_dir: TempDir,
+        #[case] file_name: &str,
+        #[case] content: &str,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(load(&temp_dir, file_name, content), expected_error);
+    }
+
+    #[test]
+    fn test_row_overrides() {
+        assert_eq!(
+            row_overrides(&row(&[("id", "1"), ("name", "")]))
+                .collect::<Vec<_>>(),
+            [
+                ("data.id".to_owned(), "1".to_owned()),
+                ("data.name".to_owned(), "".to_owned()),
+            ]
+        );
+    }
+}
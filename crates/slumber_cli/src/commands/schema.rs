@@ -0,0 +1,19 @@
+use crate::{GlobalArgs, Subcommand};
+use clap::Parser;
+use slumber_core::collection;
+use std::process::ExitCode;
+
+/// Print a JSON Schema for the collection file format
+///
+/// Point your editor's YAML language server at the schema to get
+/// autocomplete and validation while editing your collection file.
+#[derive(Clone, Debug, Parser)]
+pub struct SchemaCommand {}
+
+impl Subcommand for SchemaCommand {
+    async fn execute(self, _global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let schema = collection::json_schema();
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        Ok(ExitCode::SUCCESS)
+    }
+}
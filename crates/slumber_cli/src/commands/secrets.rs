@@ -0,0 +1,74 @@
+use crate::{GlobalArgs, Subcommand};
+use clap::Parser;
+use dialoguer::Password;
+use slumber_core::util::secrets;
+use std::process::ExitCode;
+
+/// Manage secrets in the OS secret store (keychain).
+///
+/// Secrets are stored by service and user name. Use the `!keyring` chain
+/// source to access them from a collection.
+#[derive(Clone, Debug, Parser)]
+pub struct SecretsCommand {
+    #[command(subcommand)]
+    subcommand: SecretsSubcommand,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+enum SecretsSubcommand {
+    /// Create or update a secret
+    Set {
+        /// Service name to store the secret under
+        service: String,
+        /// User name to store the secret under
+        user: String,
+        /// Secret value. If omitted, you'll be prompted for it. Passing the
+        /// value here will leave it in your shell history!
+        #[clap(long)]
+        value: Option<String>,
+    },
+    /// Print a secret
+    Get {
+        /// Service name the secret is stored under
+        service: String,
+        /// User name the secret is stored under
+        user: String,
+    },
+    /// Delete a secret
+    #[command(visible_alias = "rm")]
+    Delete {
+        /// Service name the secret is stored under
+        service: String,
+        /// User name the secret is stored under
+        user: String,
+    },
+}
+
+impl Subcommand for SecretsCommand {
+    async fn execute(self, _global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        match self.subcommand {
+            SecretsSubcommand::Set {
+                service,
+                user,
+                value,
+            } => {
+                let value = match value {
+                    Some(value) => value,
+                    None => Password::new()
+                        .with_prompt(format!("Secret for {user}@{service}"))
+                        .interact()?,
+                };
+                secrets::set(&service, &user, &value).await?;
+                eprintln!("Saved secret for user `{user}` in `{service}`");
+            }
+            SecretsSubcommand::Get { service, user } => {
+                println!("{}", secrets::get(&service, &user).await?);
+            }
+            SecretsSubcommand::Delete { service, user } => {
+                secrets::delete(&service, &user).await?;
+                eprintln!("Deleted secret for user `{user}` in `{service}`");
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{util::prompt_passphrase, GlobalArgs, Subcommand};
+use anyhow::bail;
+use clap::Parser;
+use itertools::Itertools;
+use slumber_core::{
+    collection::{CollectionFile, ProfileId},
+    db::Database,
+    http::{MockServer, MockedRequest},
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    process::ExitCode,
+};
+
+/// Serve mock responses for the recipes in the collection
+///
+/// Each incoming request is matched to a recipe by method and URL path, and
+/// answered with the recipe's most recent response from history. Recipes that
+/// have never been sent use their `example` response. Runs until killed.
+#[derive(Clone, Debug, Parser)]
+pub struct ServeCommand {
+    /// Port to listen on
+    port: u16,
+
+    /// Address to bind to. Use `0.0.0.0` to accept requests from other hosts
+    #[clap(long, default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    host: IpAddr,
+
+    /// Profile to load responses from [default: the first profile in the
+    /// collection]
+    #[clap(long = "profile", short)]
+    profile: Option<ProfileId>,
+}
+
+impl Subcommand for ServeCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(None, global.file)?;
+        let database = Database::load(prompt_passphrase)?
+            .into_collection(&collection_path)?;
+        let collection =
+            CollectionFile::load(collection_path).await?.collection;
+
+        let profile_id = match self.profile {
+            Some(profile_id) => {
+                if !collection.profiles.contains_key(&profile_id) {
+                    bail!(
+                        "No profile with ID `{profile_id}`; options are: {}",
+                        collection.profiles.keys().format(", ")
+                    );
+                }
+                Some(profile_id)
+            }
+            None => collection.profiles.keys().next().cloned(),
+        };
+
+        let server = MockServer::bind(
+            SocketAddr::new(self.host, self.port),
+            &collection,
+        )
+        .await?;
+        eprintln!("Serving mock responses on http://{}", server.local_addr()?);
+        server.run(database, profile_id, Self::print_request).await;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+impl ServeCommand {
+    fn print_request(request: &MockedRequest) {
+        println!(
+            "{} {} {} {}",
+            request.status.as_u16(),
+            request.method,
+            request.url,
+            request
+                .recipe_id
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_else(|| "-".into()),
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{
+    commands::{
+        report::{self, Outcome, OutcomeResult},
+        request::{BuildRequestCommand, RenderArgs},
+    },
+    GlobalArgs, Subcommand,
+};
+use anyhow::bail;
+use clap::Parser;
+use slumber_core::collection::{Collection, CollectionFile, Recipe, RecipeId};
+use std::{process::ExitCode, sync::Arc};
+
+/// Send requests and check their responses against the recipes' assertions
+///
+/// Each recipe is built, sent, and checked against its `assertions`. A recipe
+/// fails if it can't be built or sent, or if any assertion fails. Exits with
+/// status 1 if any recipe fails, for use in CI.
+///
+/// With `--templates`, run the collection's `template_tests` instead. These
+/// render templates against fixture responses, so no requests are sent.
+#[derive(Clone, Debug, Parser)]
+pub struct TestCommand {
+    /// Recipe or folder to test. A recipe is tested even if it has no
+    /// assertions; for a folder, every recipe within it that has assertions
+    /// is tested. [default: all recipes with assertions]
+    #[clap(conflicts_with = "templates")]
+    node_id: Option<RecipeId>,
+
+    /// Run the collection's template tests instead of sending requests.
+    /// Build options such as `--profile` are ignored; each test defines its
+    /// own inputs
+    #[clap(long)]
+    templates: bool,
+
+    #[clap(flatten)]
+    render: RenderArgs,
+}
+
+impl Subcommand for TestCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(None, global.file)?;
+        let collection = CollectionFile::load(collection_path.clone())
+            .await?
+            .collection;
+        if self.templates {
+            return run_template_tests(collection).await;
+        }
+
+        let recipes: Vec<&Recipe> = match &self.node_id {
+            // A recipe named directly is always tested
+            Some(id) => match collection.recipes.get_recipe(id) {
+                Some(recipe) => vec![recipe],
+                None => with_assertions(
+                    collection.recipes.try_get_recipes_under(id)?,
+                ),
+            },
+            None => with_assertions(
+                collection
+                    .recipes
+                    .recipe_ids()
+                    .filter_map(|id| collection.recipes.get_recipe(id)),
+            ),
+        };
+        if recipes.is_empty() {
+            bail!("No recipes with assertions to test");
+        }
+
+        let mut outcomes = Vec::with_capacity(recipes.len());
+        for (i, recipe) in recipes.iter().enumerate() {
+            let mut render = self.render.clone();
+            // Only clear the cache once, so chains can be shared between
+            // recipes
+            render.clear_cache &= i == 0;
+            let (failures, response) = test_recipe(
+                GlobalArgs {
+                    file: Some(collection_path.clone()),
+                    offline: global.offline,
+                },
+                recipe,
+                render,
+            )
+            .await;
+            outcomes.push(print_result(&recipe.id, &failures, response));
+        }
+
+        println!();
+        report::print_summary("RECIPE", &outcomes);
+        Ok(report::exit_code(&outcomes))
+    }
+}
+
+/// Run every template test in the collection
+async fn run_template_tests(
+    collection: Arc<Collection>,
+) -> anyhow::Result<ExitCode> {
+    if collection.template_tests.is_empty() {
+        bail!("No template tests in collection");
+    }
+
+    let mut outcomes = Vec::with_capacity(collection.template_tests.len());
+    for (name, test) in &collection.template_tests {
+        let failures = match test.run(Arc::clone(&collection)).await {
+            Ok(()) => vec![],
+            Err(failure) => vec![failure.to_string()],
+        };
+        outcomes.push(print_result(name, &failures, None));
+    }
+
+    println!();
+    report::print_summary("TEST", &outcomes);
+    Ok(report::exit_code(&outcomes))
+}
+
+/// Print whether a recipe or test passed, with a line for each failure, and
+/// get its outcome for the summary
+fn print_result<'a>(
+    name: &'a str,
+    failures: &[String],
+    response: Option<(u16, i64)>,
+) -> Outcome<'a> {
+    let result = if failures.is_empty() {
+        OutcomeResult::Passed
+    } else {
+        OutcomeResult::Failed
+    };
+    println!("{} {name}", result.label());
+    for failure in failures {
+        println!("  {failure}");
+    }
+    Outcome {
+        name,
+        row: None,
+        result,
+        response,
+    }
+}
+
+/// Build and send a recipe, then check its assertions. Return a message for
+/// each failure, and the status and duration of the response if one was
+/// received
+async fn test_recipe(
+    global: GlobalArgs,
+    recipe: &Recipe,
+    render: RenderArgs,
+) -> (Vec<String>, Option<(u16, i64)>) {
+    let built = BuildRequestCommand::new(recipe.id.clone(), render)
+        .build_request(global, true)
+        .await;
+    let (database, _, ticket) = match built {
+        Ok(built) => built,
+        Err(error) => {
+            return (vec![format!("Error building request: {error:#}")], None)
+        }
+    };
+    let exchange = match ticket.send(&database).await {
+        Ok(exchange) => exchange,
+        Err(error) => {
+            return (
+                vec![format!("Error sending request: {:#}", error.error)],
+                None,
+            )
+        }
+    };
+    let failures = report::check_assertions(recipe, &exchange)
+        .into_iter()
+        .filter_map(|result| result.failure)
+        .collect();
+    let response = (
+        exchange.response.status.as_u16(),
+        exchange.duration().num_milliseconds(),
+    );
+    (failures, Some(response))
+}
+
+/// Filter out recipes without assertions
+fn with_assertions<'a>(
+    recipes: impl IntoIterator<Item = &'a Recipe>,
+) -> Vec<&'a Recipe> {
+    recipes
+        .into_iter()
+        .filter(|recipe| !recipe.assertions.is_empty())
+        .collect()
+}
@@ -11,13 +11,18 @@ mod commands;
 mod util;
 
 use crate::commands::{
-    collections::CollectionsCommand, generate::GenerateCommand,
-    history::HistoryCommand, import::ImportCommand, request::RequestCommand,
-    show::ShowCommand,
+    baseline::BaselineCommand, collections::CollectionsCommand, db::DbCommand,
+    export::ExportCommand, generate::GenerateCommand, history::HistoryCommand,
+    import::ImportCommand, lint::LintCommand, listen::ListenCommand,
+    proxy::ProxyCommand, request::RequestCommand, run::RunCommand,
+    schema::SchemaCommand, secrets::SecretsCommand, serve::ServeCommand,
+    show::ShowCommand, test::TestCommand,
 };
 use clap::Parser;
 use std::{path::PathBuf, process::ExitCode};
 
+pub use util::prompt_passphrase;
+
 #[derive(Debug, Parser)]
 #[clap(
     author,
@@ -41,23 +46,38 @@ impl Args {
 }
 
 /// Arguments that are available to all subcommands and the TUI
-#[derive(Debug, Parser)]
+#[derive(Clone, Debug, Parser)]
 pub struct GlobalArgs {
     /// Collection file, which defines profiles, recipes, etc. If omitted,
     /// check the current and all parent directories for the following files
     /// (in this order): slumber.yml, slumber.yaml, .slumber.yml, .slumber.yaml
     #[clap(long, short)]
     pub file: Option<PathBuf>,
+    /// Don't send any HTTP requests. Each request gets its recipe's `example`
+    /// response instead. Overrides the `offline` config field
+    #[clap(long)]
+    pub offline: bool,
 }
 
 /// A CLI subcommand
 #[derive(Clone, Debug, clap::Subcommand)]
 pub enum CliCommand {
     Request(RequestCommand),
+    Run(RunCommand),
+    Test(TestCommand),
+    Lint(LintCommand),
+    Schema(SchemaCommand),
     Generate(GenerateCommand),
     Import(ImportCommand),
+    Export(ExportCommand),
     Collections(CollectionsCommand),
+    Db(DbCommand),
+    Baseline(BaselineCommand),
     History(HistoryCommand),
+    Listen(ListenCommand),
+    Proxy(ProxyCommand),
+    Serve(ServeCommand),
+    Secrets(SecretsCommand),
     Show(ShowCommand),
 }
 
@@ -67,9 +87,20 @@ impl CliCommand {
         match self {
             Self::Generate(command) => command.execute(global).await,
             Self::Request(command) => command.execute(global).await,
+            Self::Run(command) => command.execute(global).await,
+            Self::Test(command) => command.execute(global).await,
+            Self::Lint(command) => command.execute(global).await,
+            Self::Schema(command) => command.execute(global).await,
             Self::Import(command) => command.execute(global).await,
+            Self::Export(command) => command.execute(global).await,
             Self::Collections(command) => command.execute(global).await,
+            Self::Db(command) => command.execute(global).await,
+            Self::Baseline(command) => command.execute(global).await,
             Self::History(command) => command.execute(global).await,
+            Self::Listen(command) => command.execute(global).await,
+            Self::Proxy(command) => command.execute(global).await,
+            Self::Serve(command) => command.execute(global).await,
+            Self::Secrets(command) => command.execute(global).await,
             Self::Show(command) => command.execute(global).await,
         }
     }
@@ -1,7 +1,15 @@
-use dialoguer::console::Style;
+use anyhow::Context;
+use dialoguer::{console::Style, Password};
 use reqwest::header::HeaderMap;
 use slumber_core::util::MaybeStr;
-use std::fmt::{self, Display, Formatter};
+use std::{
+    env,
+    fmt::{self, Display, Formatter},
+};
+
+/// Environment variable that can hold the history passphrase, to skip the
+/// prompt (e.g. in scripts)
+pub const PASSPHRASE_VARIABLE: &str = "SLUMBER_HISTORY_PASSPHRASE";
 
 /// Wrapper making it easy to print a header map
 pub struct HeaderDisplay<'a>(pub &'a HeaderMap);
@@ -20,3 +28,20 @@ impl<'a> Display for HeaderDisplay<'a> {
         Ok(())
     }
 }
+
+/// Prompt the user for the history passphrase, unless it's provided via the
+/// environment. Pass this to [Database::load](slumber_core::db::Database::load)
+pub fn prompt_passphrase() -> anyhow::Result<String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_VARIABLE) {
+        return Ok(passphrase);
+    }
+    Password::new()
+        .with_prompt("History passphrase")
+        .interact()
+        .with_context(|| {
+            format!(
+                "History is encrypted with a passphrase. Run interactively to \
+                enter it, or set `{PASSPHRASE_VARIABLE}`"
+            )
+        })
+}
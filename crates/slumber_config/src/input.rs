@@ -137,6 +137,12 @@ pub enum Action {
     /// Do a thing, e.g. submit in a text prompt. Alternatively, send a request
     #[display("Send Request/Submit")]
     Submit,
+    /// Send a request with a note attached, to label it in history
+    #[display("Send With Note")]
+    SendWithNote,
+    /// Cancel the selected request, if it's still building or loading
+    #[display("Cancel Request")]
+    CancelRequest,
     /// Toggle checkbox and similar components on/off
     Toggle,
     /// Close the current modal/dialog/etc.
@@ -146,12 +152,32 @@ pub enum Action {
     Edit,
     /// Browse request history
     History,
+    /// List requests sent this session, to switch between concurrent ones
+    #[display("In-Flight Requests")]
+    InFlight,
+    /// List the environment variables used by the collection, and their
+    /// values
+    #[display("Environment Variables")]
+    EnvironmentVariables,
     /// Start a search/filter operation
     #[display("Search/Filter")]
     Search,
+    /// Bookmark the top visible line of a response body, or remove the
+    /// bookmark
+    #[display("Toggle Bookmark")]
+    ToggleBookmark,
+    /// Jump to the next bookmark in a response body
+    #[display("Next Bookmark")]
+    NextBookmark,
+    /// Jump to the previous bookmark in a response body
+    #[display("Prev Bookmark")]
+    PreviousBookmark,
     /// Force a collection reload (typically it's automatic)
     #[display("Reload Collection")]
     ReloadCollection,
+    /// Switch to another collection in the workspace
+    #[display("Switch Collection")]
+    SwitchCollection,
     /// Embiggen a pane
     Fullscreen,
     /// Open the actions modal
@@ -17,7 +17,7 @@ pub use theme::Theme;
 
 use anyhow::Context;
 use indexmap::IndexMap;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
 use slumber_core::{
     http::HttpEngineConfig,
     util::{parse_yaml, DataDirectory, ResultTraced},
@@ -41,6 +41,11 @@ pub struct Config {
     /// Should templates be rendered inline in the UI, or should we show the
     /// raw text?
     pub preview_templates: bool,
+    /// Response bodies over this size (in bytes) are truncated in the TUI,
+    /// until the user chooses to load the full body. Rendering a huge body
+    /// can freeze the UI. Must be greater than zero.
+    #[serde(deserialize_with = "deserialize_nonzero")]
+    pub truncate_body_size: usize,
     /// Overrides for default key bindings
     pub input_bindings: IndexMap<Action, InputBinding>,
     /// Visual configuration for the TUI (e.g. colors)
@@ -90,9 +95,40 @@ impl Default for Config {
             editor: None,
             http: HttpEngineConfig::default(),
             preview_templates: true,
+            truncate_body_size: 200 * 1000, // 200KB
             input_bindings: Default::default(),
             theme: Default::default(),
             debug: false,
         }
     }
 }
+
+/// Deserialize a size that can't be zero, e.g. because it's used as a divisor
+fn deserialize_nonzero<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<usize, D::Error> {
+    let size = usize::deserialize(deserializer)?;
+    if size == 0 {
+        Err(D::Error::custom("must be greater than zero"))
+    } else {
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slumber_core::assert_err;
+
+    /// A truncation size of zero would make zero-size pages in the TUI
+    #[test]
+    fn test_truncate_body_size_zero() {
+        assert_err!(
+            parse_yaml::<Config>("truncate_body_size: 0".as_bytes()),
+            "must be greater than zero"
+        );
+        let config =
+            parse_yaml::<Config>("truncate_body_size: 1".as_bytes()).unwrap();
+        assert_eq!(config.truncate_body_size, 1);
+    }
+}
@@ -2,22 +2,53 @@
 //! possible
 
 mod cereal;
+mod compare;
+mod curl;
+mod defaults;
+mod diff;
+mod edit;
+mod har;
+mod imports;
+mod inheritance;
 mod insomnia;
+mod keys;
+mod lint;
 mod models;
-mod openapi;
+pub(crate) mod openapi;
+mod postman;
 mod recipe_tree;
+mod references;
+mod rename;
+mod rest_client;
+mod schema;
+mod spans;
+mod workspace;
 
-pub use cereal::HasId;
+pub use cereal::{parse_duration, HasId};
+pub use compare::{BreakingChange, ItemKind};
+pub use diff::CollectionChange;
+pub use edit::{
+    add_profile, add_recipe, clone_profile, edit_recipe, import_curl,
+    RecipeFields,
+};
+pub use har::HarImport;
+pub use imports::load_git_head;
+pub use keys::{TemplateKeyInfo, TemplateKeyKind};
+pub use lint::{find_item, lint, Diagnostic, Severity};
 pub use models::*;
+pub use postman::{PostmanCollection, PostmanEnvironment, PostmanExport};
 pub use recipe_tree::*;
+pub use references::{ReferenceIssue, ReferenceLocation, TemplateReference};
+pub use rename::rename_recipe;
+pub use schema::json_schema;
+pub use workspace::{Workspace, WorkspaceMember};
 
-use crate::util::{parse_yaml, ResultTraced};
+use crate::util::ResultTraced;
 use anyhow::{anyhow, Context};
 use itertools::Itertools;
 use std::{
     env,
     fmt::Debug,
-    fs::File,
     future::Future,
     path::{Path, PathBuf},
     sync::Arc,
@@ -93,7 +124,7 @@ impl CollectionFile {
         };
         override_path
             .map(|override_path| dir.join(override_path))
-            .or_else(|| detect_path(&dir)).ok_or_else(|| {
+            .or_else(|| detect_path(&dir, CONFIG_FILES)).ok_or_else(|| {
                 anyhow!("No collection file found in current or ancestor directories")
             })
     }
@@ -110,48 +141,43 @@ impl crate::test_util::Factory<Collection> for CollectionFile {
     }
 }
 
-/// Search the current directory for a config file matching one of the known
-/// file names, and return it if found
-fn detect_path(dir: &Path) -> Option<PathBuf> {
-    /// Search a directory and its parents for the collection file. Return None
-    /// only if we got through the whole tree and couldn't find it
-    fn search_all(dir: &Path) -> Option<PathBuf> {
-        search(dir).or_else(|| {
-            let parent = dir.parent()?;
-            search_all(parent)
-        })
-    }
-
-    /// Search a single directory for a collection file
-    fn search(dir: &Path) -> Option<PathBuf> {
-        trace!("Scanning for collection file in {dir:?}");
+/// Search the given directory and its ancestors for a file matching one of the
+/// given names, and return it if found
+fn detect_path(dir: &Path, file_names: &[&str]) -> Option<PathBuf> {
+    // Walk *up* the tree until we've hit the root
+    search_dir(dir, file_names).or_else(|| {
+        let parent = dir.parent()?;
+        detect_path(parent, file_names)
+    })
+}
 
-        let paths = CONFIG_FILES
-            .iter()
-            .map(|file| dir.join(file))
-            // This could be async but I'm being lazy and skipping it for now,
-            // since we only do this at startup anyway (mid-process reloading
-            // reuses the detected path so we don't re-detect)
-            .filter(|p| p.exists())
-            .collect_vec();
-        match paths.as_slice() {
-            [] => None,
-            [first, rest @ ..] => {
-                if !rest.is_empty() {
-                    warn!(
-                        "Multiple collection files detected. {first:?} will be \
-                            used and the following will be ignored: {rest:?}"
-                    );
-                }
+/// Search a single directory for a file matching one of the given names. If
+/// multiple match, the earliest name wins
+fn search_dir(dir: &Path, file_names: &[&str]) -> Option<PathBuf> {
+    trace!("Scanning for {file_names:?} in {dir:?}");
 
-                trace!("Found collection file at {first:?}");
-                Some(first.to_path_buf())
+    let paths = file_names
+        .iter()
+        .map(|file| dir.join(file))
+        // This could be async but I'm being lazy and skipping it for now,
+        // since we only do this at startup anyway (mid-process reloading
+        // reuses the detected path so we don't re-detect)
+        .filter(|p| p.exists())
+        .collect_vec();
+    match paths.as_slice() {
+        [] => None,
+        [first, rest @ ..] => {
+            if !rest.is_empty() {
+                warn!(
+                    "Multiple files detected. {first:?} will be used and the \
+                    following will be ignored: {rest:?}"
+                );
             }
+
+            trace!("Found file at {first:?}");
+            Some(first.to_path_buf())
         }
     }
-
-    // Walk *up* the tree until we've hit the root
-    search_all(dir)
 }
 
 /// Load a collection from the given file. Takes an owned path because it
@@ -165,9 +191,7 @@ async fn load_collection(path: PathBuf) -> anyhow::Result<Collection> {
     // tokio::fs for this but that just uses std::fs underneath anyway.
     let result =
         task::spawn_blocking::<_, anyhow::Result<Collection>>(move || {
-            let file = File::open(path)?;
-            let collection = parse_yaml(&file)?;
-            Ok(collection)
+            imports::load_with_imports(&path)
         })
         .await;
 
@@ -187,15 +211,21 @@ mod tests {
     use super::*;
     use crate::{
         assert_err,
-        http::content_type::ContentType,
+        http::{
+            content_type::ContentType,
+            query::{CaptureGroup, RegexSelector, Selector},
+        },
         test_util::{by_id, temp_dir, test_data_dir, TempDir},
     };
-    use indexmap::indexmap;
+    use indexmap::{indexmap, IndexMap};
     use pretty_assertions::assert_eq;
     use rstest::rstest;
     use serde::de::IgnoredAny;
     use serde_json::json;
-    use std::{fs, time::Duration};
+    use std::{
+        fs::{self, File},
+        time::Duration,
+    };
 
     /// Test various cases of try_path
     #[rstest]
@@ -252,24 +282,42 @@ mod tests {
             .unwrap()
             .collection;
         let expected = Collection {
+            imports: Vec::new(),
             profiles: by_id([
                 Profile {
                     id: "profile1".into(),
                     name: Some("Profile 1".into()),
+                    extends: None,
                     data: indexmap! {
                         "user_guid".into() => "abc123".into(),
                         "username".into() => "xX{{chains.username}}Xx".into(),
                         "host".into() => "https://httpbin.org".into(),
 
                     },
+                    certificate: None,
+                    tunnel: Some(SshTunnel {
+                        host: "{{username}}@bastion.example.com".into(),
+                        jump: Some("jump.example.com".into()),
+                        local_port: 8080,
+                        remote_host: Some("api.internal".into()),
+                        remote_port: 80,
+                    }),
+                    fake_seed: Some(42),
                 },
                 Profile {
                     id: "profile2".into(),
                     name: Some("Profile 2".into()),
+                    extends: None,
                     data: indexmap! {
                         "host".into() => "https://httpbin.org".into(),
 
                     },
+                    certificate: Some(ClientCertificate::Pkcs12 {
+                        path: "./client.p12".into(),
+                        password: Some("{{chains.password}}".into()),
+                    }),
+                    tunnel: None,
+                    fake_seed: None,
                 },
             ]),
             chains: by_id([
@@ -280,6 +328,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: Some(Duration::from_secs(300)),
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "command_stdin".into(),
@@ -291,6 +342,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: true,
+                    example: None,
                 },
                 Chain {
                     id: "command_trim_none".into(),
@@ -299,6 +353,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "command_trim_start".into(),
@@ -307,6 +364,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::Start,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "command_trim_end".into(),
@@ -315,6 +375,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::End,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "command_trim_both".into(),
@@ -323,6 +386,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::Both,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "prompt_sensitive".into(),
@@ -334,6 +400,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "prompt_default".into(),
@@ -345,6 +414,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "file".into(),
@@ -355,6 +427,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "file_content_type".into(),
@@ -365,6 +440,191 @@ mod tests {
                     selector: None,
                     content_type: Some(ContentType::Json),
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "env_file".into(),
+                    source: ChainSource::EnvFile {
+                        path: "./.env".into(),
+                        variable: "API_TOKEN".into(),
+                    },
+                    sensitive: true,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "aws_access_key_id".into(),
+                    source: ChainSource::AwsCredentials {
+                        profile: None,
+                        field: AwsCredentialField::AccessKeyId,
+                    },
+                    sensitive: false,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "aws_session_token".into(),
+                    source: ChainSource::AwsCredentials {
+                        profile: Some("{{aws_profile}}".into()),
+                        field: AwsCredentialField::SessionToken,
+                    },
+                    sensitive: true,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "gcp_access_token".into(),
+                    source: ChainSource::GcpToken {
+                        token_type: GcpTokenType::AccessToken,
+                        audience: None,
+                    },
+                    sensitive: true,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "gcp_id_token".into(),
+                    source: ChainSource::GcpToken {
+                        token_type: GcpTokenType::IdToken,
+                        audience: Some("{{host}}".into()),
+                    },
+                    sensitive: true,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "azure_token".into(),
+                    source: ChainSource::AzureToken {
+                        scope: Some("api://my-app/.default".into()),
+                        tenant: None,
+                    },
+                    sensitive: true,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "consul".into(),
+                    source: ChainSource::Consul {
+                        service: "api".into(),
+                        tag: Some("v2".into()),
+                        address: None,
+                    },
+                    sensitive: false,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "keyring".into(),
+                    source: ChainSource::Keyring {
+                        service: "slumber".into(),
+                        user: "{{username}}".into(),
+                    },
+                    sensitive: true,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "kubernetes_secret".into(),
+                    source: ChainSource::Kubernetes {
+                        kind: KubernetesResourceKind::Secret,
+                        name: "api-credentials".into(),
+                        key: "token".into(),
+                        namespace: None,
+                        context: None,
+                    },
+                    sensitive: false,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "kubernetes_config_map".into(),
+                    source: ChainSource::Kubernetes {
+                        kind: KubernetesResourceKind::ConfigMap,
+                        name: "api-config".into(),
+                        key: "host".into(),
+                        namespace: Some("staging".into()),
+                        context: Some("{{kube_context}}".into()),
+                    },
+                    sensitive: false,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "rotate".into(),
+                    source: ChainSource::Rotate {
+                        values: vec!["key1".into(), "{{username}}".into()],
+                        file: Some("./api_keys.txt".into()),
+                    },
+                    sensitive: true,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "random".into(),
+                    source: ChainSource::Random {
+                        values: vec![
+                            RandomChoice::Value("active".into()),
+                            RandomChoice::Weighted {
+                                value: "suspended".into(),
+                                weight: 3,
+                            },
+                        ],
+                        file: Some("./statuses.txt".into()),
+                        seed: Some(42),
+                    },
+                    sensitive: false,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "request_selector".into(),
@@ -377,6 +637,42 @@ mod tests {
                     selector: Some("$.data".parse().unwrap()),
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "request_selector_regex".into(),
+                    source: ChainSource::Request {
+                        recipe: "login".into(),
+                        trigger: ChainRequestTrigger::Never,
+                        section: ChainRequestSection::Body,
+                    },
+                    sensitive: false,
+                    selector: Some(Selector::Regex(RegexSelector {
+                        pattern: r"session=(?<session>\w+)".parse().unwrap(),
+                        group: Some(CaptureGroup::Name("session".into())),
+                    })),
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "request_selector_xpath".into(),
+                    source: ChainSource::Request {
+                        recipe: "login".into(),
+                        trigger: ChainRequestTrigger::Never,
+                        section: ChainRequestSection::Body,
+                    },
+                    sensitive: false,
+                    selector: Some("//user/@id".parse().unwrap()),
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "request_trigger_never".into(),
@@ -389,6 +685,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "request_trigger_no_history".into(),
@@ -401,6 +700,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "request_trigger_expire".into(),
@@ -415,6 +717,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "request_trigger_always".into(),
@@ -427,6 +732,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "request_section_body".into(),
@@ -439,6 +747,9 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
                 Chain {
                     id: "request_section_header".into(),
@@ -453,6 +764,24 @@ mod tests {
                     selector: None,
                     content_type: None,
                     trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
+                },
+                Chain {
+                    id: "request_section_status".into(),
+                    source: ChainSource::Request {
+                        recipe: "login".into(),
+                        trigger: ChainRequestTrigger::Never,
+                        section: ChainRequestSection::Status,
+                    },
+                    sensitive: false,
+                    selector: None,
+                    content_type: None,
+                    trim: ChainOutputTrim::None,
+                    cache: None,
+                    exclusive: false,
+                    example: None,
                 },
             ]),
             recipes: by_id([
@@ -469,6 +798,14 @@ mod tests {
                         content_type: None,
                     }),
                     authentication: None,
+                    signature: None,
+                    certificate: None,
+                    danger_skip_tls_verify: false,
+                    baseline_ignore: vec![],
+                    assertions: vec![],
+                    display_query: None,
+                    example: None,
+                    parameters: IndexMap::new(),
                     query: vec![
                         ("sudo".into(), "yes_please".into()),
                         ("fast".into(), "no_thanks".into()),
@@ -488,6 +825,14 @@ mod tests {
                             url: "{{host}}/anything/{{user_guid}}".into(),
                             body: None,
                             authentication: None,
+                            signature: None,
+                            certificate: None,
+                            danger_skip_tls_verify: true,
+                            baseline_ignore: vec![],
+                            assertions: vec![],
+                            display_query: None,
+                            example: None,
+                            parameters: IndexMap::new(),
                             query: vec![
                                 ("value".into(), "{{field1}}".into()),
                                 ("value".into(), "{{field2}}".into()),
@@ -507,6 +852,49 @@ mod tests {
                             authentication: Some(Authentication::Bearer(
                                 "{{chains.auth_token}}".into(),
                             )),
+                            signature: Some(BodySignature::Stripe {
+                                secret: "{{chains.webhook_secret}}".into(),
+                                timestamp: None,
+                            }),
+                            certificate: Some(ClientCertificate::Pem {
+                                certificate: "./client.pem".into(),
+                                key: Some("./client.key".into()),
+                            }),
+                            danger_skip_tls_verify: false,
+                            baseline_ignore: vec!["$.headers.Date"
+                                .parse()
+                                .unwrap()],
+                            assertions: vec![
+                                Assertion::Status(200),
+                                Assertion::Header {
+                                    name: "content-type".into(),
+                                    matches: Some(
+                                        "^application/json".parse().unwrap(),
+                                    ),
+                                },
+                                Assertion::JsonPath {
+                                    query: "$.json.username".parse().unwrap(),
+                                    equals: Some("new username".into()),
+                                    contains: None,
+                                },
+                                Assertion::Latency(Duration::from_millis(500)),
+                            ],
+                            display_query: Some("$.json".parse().unwrap()),
+                            example: None,
+                            parameters: indexmap! {
+                                "notify".into() => RecipeParameter {
+                                    kind: ParameterType::Boolean,
+                                    default: Some("false".into()),
+                                    description: None,
+                                },
+                                "reason".into() => RecipeParameter {
+                                    kind: ParameterType::String,
+                                    default: Some("".into()),
+                                    description: Some(
+                                        "Why the user was modified".into(),
+                                    ),
+                                },
+                            },
                             query: vec![],
                             headers: indexmap! {
                                 "Accept".into() => "application/json".into(),
@@ -527,6 +915,14 @@ mod tests {
                                 username: "{{username}}".into(),
                                 password: Some("{{password}}".into()),
                             }),
+                            signature: None,
+                            certificate: None,
+                            danger_skip_tls_verify: false,
+                            baseline_ignore: vec![],
+                            assertions: vec![],
+                            display_query: None,
+                            example: None,
+                            parameters: IndexMap::new(),
                             query: vec![],
                             headers: indexmap! {
                                 "Accept".into() => "application/json".into(),
@@ -541,16 +937,49 @@ mod tests {
                             body: Some(RecipeBody::FormUrlencoded(indexmap! {
                                 "username".into() => "new username".into()
                             })),
-                            authentication: None,
+                            authentication: Some(Authentication::AwsSigv4 {
+                                region: "us-east-1".into(),
+                                service: "execute-api".into(),
+                                access_key_id: None,
+                                secret_access_key: None,
+                                session_token: None,
+                                profile: Some("{{aws_profile}}".into()),
+                            }),
+                            signature: None,
+                            certificate: None,
+                            danger_skip_tls_verify: false,
+                            baseline_ignore: vec![],
+                            assertions: vec![],
+                            display_query: None,
+                            example: None,
+                            parameters: IndexMap::new(),
                             query: vec![],
                             headers: indexmap! {
                                 "Accept".into() => "application/json".into(),
                             },
                         }),
                     ]),
+                    defaults: RecipeDefaults {
+                        headers: indexmap! {
+                            "X-Users".into() => "true".into(),
+                        },
+                        ..RecipeDefaults::default()
+                    },
                 }),
             ])
             .into(),
+            defaults: RecipeDefaults {
+                base_url: Some("{{host}}".into()),
+                authentication: Some(Authentication::Bearer(
+                    "{{chains.auth_token}}".into(),
+                )),
+                headers: indexmap! {
+                    "User-Agent".into() => "slumber".into(),
+                },
+            },
+            openapi: Some("./openapi.yml".into()),
+            template_tests: IndexMap::new(),
+            environment: IndexMap::new(),
             _ignore: IgnoredAny,
         };
         assert_eq!(*loaded, expected);
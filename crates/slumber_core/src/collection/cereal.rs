@@ -8,7 +8,8 @@ use crate::{
     http::content_type::ContentType,
     template::Template,
 };
-use anyhow::Context;
+use anyhow::{anyhow, bail, Context};
+use itertools::Itertools;
 use serde::{
     de::{
         self, EnumAccess, Error as _, MapAccess, SeqAccess, VariantAccess,
@@ -17,7 +18,9 @@ use serde::{
     ser::Error as _,
     Deserialize, Deserializer, Serialize, Serializer,
 };
-use std::{fmt::Display, hash::Hash, str::FromStr};
+use std::{fmt::Display, hash::Hash, str::FromStr, time::Duration};
+use strum::{EnumIter, EnumString, IntoEnumIterator};
+use winnow::{ascii::digit1, token::take_while, PResult, Parser};
 
 /// A type that has an `id` field. This is ripe for a derive macro, maybe a fun
 /// project some day?
@@ -115,6 +118,22 @@ where
     s.parse().map_err(D::Error::custom)
 }
 
+/// Serialize query parameters as a sequence of `key=value`. A map can't be used
+/// because parameters can be repeated
+pub fn serialize_query_parameters<S>(
+    query: &[(String, Template)],
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(
+        query
+            .iter()
+            .map(|(param, value)| format!("{param}={}", value.display())),
+    )
+}
+
 /// Deserialize query parameters from either a sequence of `key=value` or a
 /// map of `key: value`
 pub fn deserialize_query_parameters<'de, D>(
@@ -334,37 +353,86 @@ impl<'de> Deserialize<'de> for RecipeBody {
     }
 }
 
-/// Serialize/deserialize a duration with unit shorthand. This does *not* handle
-/// subsecond precision. Supported units are:
+/// A unit for [parse_duration]
+#[derive(Debug, derive_more::Display, EnumIter, EnumString)]
+enum DurationUnit {
+    #[display("ms")]
+    #[strum(serialize = "ms")]
+    Millisecond,
+    #[display("s")]
+    #[strum(serialize = "s")]
+    Second,
+    #[display("m")]
+    #[strum(serialize = "m")]
+    Minute,
+    #[display("h")]
+    #[strum(serialize = "h")]
+    Hour,
+    #[display("d")]
+    #[strum(serialize = "d")]
+    Day,
+}
+
+/// Parse a duration with unit shorthand, in the format `<quantity><unit>`.
+/// Supported units are:
+/// - ms
 /// - s
 /// - m
 /// - h
 /// - d
 ///
-/// Examples: `30s`, `5m`, `12h`, `3d`
+/// Examples: `500ms`, `30s`, `5m`, `12h`, `3d`
+pub fn parse_duration(input: &str) -> anyhow::Result<Duration> {
+    fn quantity(input: &mut &str) -> PResult<u64> {
+        digit1.parse_to().parse_next(input)
+    }
+
+    fn unit<'a>(input: &mut &'a str) -> PResult<&'a str> {
+        take_while(0.., char::is_alphabetic).parse_next(input)
+    }
+
+    let units = || {
+        DurationUnit::iter()
+            .format_with(", ", |unit, f| f(&format_args!("`{unit}`")))
+    };
+
+    let (quantity, unit) = (quantity, unit)
+        .parse(input)
+        // The format is so simple there isn't much value in spitting out a
+        // specific parsing error, just use a canned one
+        .map_err(|_| {
+            anyhow!(
+                "Invalid duration `{input}`, must be `<quantity><unit>` \
+                (e.g. `12d`)"
+            )
+        })?;
+    if unit.is_empty() {
+        bail!(
+            "Missing unit in duration `{input}`; must be one of {} \
+            (e.g. `{input}s`)",
+            units()
+        );
+    }
+    let unit: DurationUnit = unit.parse().map_err(|_| {
+        anyhow!("Unknown duration unit `{unit}`; must be one of {}", units())
+    })?;
+
+    let seconds = |factor: u64| quantity.checked_mul(factor);
+    let duration = match unit {
+        DurationUnit::Millisecond => Some(Duration::from_millis(quantity)),
+        DurationUnit::Second => Some(Duration::from_secs(quantity)),
+        DurationUnit::Minute => seconds(60).map(Duration::from_secs),
+        DurationUnit::Hour => seconds(60 * 60).map(Duration::from_secs),
+        DurationUnit::Day => seconds(60 * 60 * 24).map(Duration::from_secs),
+    };
+    duration.ok_or_else(|| anyhow!("Duration `{input}` is too large"))
+}
+
+/// Serialize/deserialize a duration with unit shorthand. This does *not* handle
+/// subsecond precision. See [parse_duration] for the format.
 pub mod serde_duration {
-    use derive_more::Display;
-    use itertools::Itertools;
     use serde::{de::Error, Deserialize, Deserializer, Serializer};
     use std::time::Duration;
-    use strum::{EnumIter, EnumString, IntoEnumIterator};
-    use winnow::{ascii::digit1, token::take_while, PResult, Parser};
-
-    #[derive(Debug, Display, EnumIter, EnumString)]
-    enum Unit {
-        #[display("s")]
-        #[strum(serialize = "s")]
-        Second,
-        #[display("m")]
-        #[strum(serialize = "m")]
-        Minute,
-        #[display("h")]
-        #[strum(serialize = "h")]
-        Hour,
-        #[display("d")]
-        #[strum(serialize = "d")]
-        Day,
-    }
 
     pub fn serialize<S>(
         duration: &Duration,
@@ -373,48 +441,58 @@ pub mod serde_duration {
     where
         S: Serializer,
     {
-        // Always serialize as seconds, because it's easiest. Sub-second
-        // precision is lost
-        S::serialize_str(serializer, &format!("{}s", duration.as_secs()))
+        // Serialize as seconds when possible, because it's easiest to read.
+        // Fall back to milliseconds to retain sub-second precision. Anything
+        // below a millisecond is lost
+        let s = if duration.subsec_millis() == 0 {
+            format!("{}s", duration.as_secs())
+        } else {
+            format!("{}ms", duration.as_millis())
+        };
+        S::serialize_str(serializer, &s)
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
     where
         D: Deserializer<'de>,
     {
-        fn quantity(input: &mut &str) -> PResult<u64> {
-            digit1.parse_to().parse_next(input)
-        }
+        let input = String::deserialize(deserializer)?;
+        super::parse_duration(&input).map_err(D::Error::custom)
+    }
+}
 
-        fn unit<'a>(input: &mut &'a str) -> PResult<&'a str> {
-            take_while(1.., char::is_alphabetic).parse_next(input)
+/// Serialize/deserialize an optional duration. See [serde_duration] for the
+/// format
+pub mod serde_duration_option {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match duration {
+            Some(duration) => {
+                super::serde_duration::serialize(duration, serializer)
+            }
+            None => serializer.serialize_none(),
         }
+    }
 
-        let input = String::deserialize(deserializer)?;
-        let (quantity, unit) = (quantity, unit)
-            .parse(&input)
-            // The format is so simple there isn't much value in spitting out a
-            // specific parsing error, just use a canned one
-            .map_err(|_| {
-                D::Error::custom(
-                    "Invalid duration, must be `<quantity><unit>` (e.g. `12d`)",
-                )
-            })?;
-
-        let unit = unit.parse().map_err(|_| {
-            D::Error::custom(format!(
-                "Unknown duration unit `{unit}`; must be one of {}",
-                Unit::iter()
-                    .format_with(", ", |unit, f| f(&format_args!("`{unit}`")))
-            ))
-        })?;
-        let seconds = match unit {
-            Unit::Second => quantity,
-            Unit::Minute => quantity * 60,
-            Unit::Hour => quantity * 60 * 60,
-            Unit::Day => quantity * 60 * 60 * 24,
-        };
-        Ok(Duration::from_secs(seconds))
+    pub fn deserialize<'de, D>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Wrap(#[serde(with = "super::serde_duration")] Duration);
+
+        let duration = Option::<Wrap>::deserialize(deserializer)?;
+        Ok(duration.map(|Wrap(duration)| duration))
     }
 }
 
@@ -596,6 +674,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serialize_query_parameters() {
+        #[derive(Debug, PartialEq, Serialize)]
+        #[serde(transparent)]
+        struct Wrap(
+            #[serde(serialize_with = "serialize_query_parameters")]
+            Vec<(String, Template)>,
+        );
+
+        assert_ser_tokens(
+            &Wrap(vec![
+                ("param".into(), "{{value}}".into()),
+                ("param".into(), "value".into()),
+            ]),
+            &[
+                Token::Seq { len: Some(2) },
+                Token::Str("param={{value}}"),
+                Token::Str("param=value"),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
     /// A wrapper that forces serde_test to use our custom serialize/deserialize
     /// functions
     #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -605,9 +706,10 @@ mod tests {
     #[rstest]
     #[case::seconds_short(Duration::from_secs(3), "3s")]
     #[case::seconds_long(Duration::from_secs(3000), "3000s")]
-    // Subsecond precision is lost
-    #[case::seconds_subsecond_lost(Duration::from_millis(400), "0s")]
-    #[case::seconds_subsecond_round_down(Duration::from_millis(1999), "1s")]
+    #[case::milliseconds(Duration::from_millis(400), "400ms")]
+    #[case::milliseconds_long(Duration::from_millis(1999), "1999ms")]
+    // Sub-millisecond precision is lost
+    #[case::submillisecond_lost(Duration::from_micros(1500), "1ms")]
     fn test_serialize_duration(
         #[case] duration: Duration,
         #[case] expected: &'static str,
@@ -616,6 +718,8 @@ mod tests {
     }
 
     #[rstest]
+    #[case::milliseconds("500ms", Duration::from_millis(500))]
+    #[case::milliseconds_long("1500ms", Duration::from_millis(1500))]
     #[case::seconds_zero("0s", Duration::from_secs(0))]
     #[case::seconds_short("1s", Duration::from_secs(1))]
     #[case::seconds_longer("100s", Duration::from_secs(100))]
@@ -632,29 +736,44 @@ mod tests {
     #[rstest]
     #[case::negative(
         "-1s",
-        "Invalid duration, must be `<quantity><unit>` (e.g. `12d`)"
+        "Invalid duration `-1s`, must be `<quantity><unit>` (e.g. `12d`)"
     )]
-    #[case::whitespace(
-        " 1s ",
-        "Invalid duration, must be `<quantity><unit>` (e.g. `12d`)"
+    #[case::whitespace(" 1s ", "Invalid duration ` 1s `")]
+    #[case::trailing_whitespace("1s ", "Invalid duration `1s `")]
+    #[case::decimal("3.5s", "Invalid duration `3.5s`")]
+    #[case::empty("", "Invalid duration ``")]
+    #[case::quantity_overflow(
+        "99999999999999999999ms",
+        "Invalid duration `99999999999999999999ms`"
     )]
-    #[case::trailing_whitespace(
-        "1s ",
-        "Invalid duration, must be `<quantity><unit>` (e.g. `12d`)"
-    )]
-    #[case::decimal(
-        "3.5s",
-        "Invalid duration, must be `<quantity><unit>` (e.g. `12d`)"
+    #[case::missing_unit(
+        "500",
+        "Missing unit in duration `500`; must be one of \
+        `ms`, `s`, `m`, `h`, `d` (e.g. `500s`)"
     )]
     #[case::invalid_unit(
         "3hr",
-        "Unknown duration unit `hr`; must be one of `s`, `m`, `h`, `d`"
+        "Unknown duration unit `hr`; must be one of `ms`, `s`, `m`, `h`, `d`"
     )]
-    fn test_deserialize_duration_error(
-        #[case] s: &'static str,
-        #[case] error: &str,
-    ) {
-        assert_de_tokens_error::<WrapDuration>(&[Token::Str(s)], error)
+    #[case::minutes_overflow(
+        "18446744073709551615m",
+        "Duration `18446744073709551615m` is too large"
+    )]
+    #[case::days_overflow(
+        "999999999999999d",
+        "Duration `999999999999999d` is too large"
+    )]
+    fn test_parse_duration_error(#[case] s: &str, #[case] error: &str) {
+        assert_err!(parse_duration(s), error);
+    }
+
+    /// Parse errors are passed through deserialization
+    #[test]
+    fn test_deserialize_duration_error() {
+        assert_de_tokens_error::<WrapDuration>(
+            &[Token::Str("3hr")],
+            "Unknown duration unit `hr`; must be one of `ms`, `s`, `m`, `h`, `d`",
+        )
     }
 
     /// Build a YAML mapping
@@ -0,0 +1,314 @@
+//! Detect changes between two versions of a collection that could break
+//! anything referring to it by ID, e.g. scripts that run recipes from the CLI
+
+use crate::collection::{Collection, ProfileId};
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_yaml::Value;
+use std::fmt::{self, Display, Formatter};
+
+/// A change that removes something that could be referenced by ID. Additions
+/// and changes to content aren't breaking, so they aren't reported.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BreakingChange {
+    Removed {
+        kind: ItemKind,
+        id: String,
+    },
+    /// An item was removed and another was added with identical content, so
+    /// it was most likely renamed
+    Renamed {
+        kind: ItemKind,
+        from: String,
+        to: String,
+    },
+}
+
+/// The type of item affected by a [BreakingChange]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ItemKind {
+    Recipe,
+    Profile,
+    /// Field within a profile. Fields are compared per profile, because
+    /// removing a field from one profile can break requests under it
+    Field {
+        profile: ProfileId,
+    },
+    Chain,
+}
+
+impl Collection {
+    /// Get every breaking change from this collection to a newer version.
+    /// Recipes, profiles, profile fields, and chains are matched by ID. Items
+    /// missing from the new version are reported as renamed if an added item
+    /// has identical content, otherwise as removed.
+    pub fn breaking_changes(
+        &self,
+        new: &Self,
+    ) -> anyhow::Result<Vec<BreakingChange>> {
+        let mut changes = Vec::new();
+
+        let recipes = |collection: &Self| {
+            serialize_items(collection.recipes.recipe_ids().filter_map(|id| {
+                Some((id.to_string(), collection.recipes.get_recipe(id)?))
+            }))
+        };
+        compare_items(
+            ItemKind::Recipe,
+            recipes(self)?,
+            recipes(new)?,
+            &mut changes,
+        );
+
+        let profiles = |collection: &Self| {
+            serialize_items(
+                collection
+                    .profiles
+                    .iter()
+                    .map(|(id, profile)| (id.to_string(), profile)),
+            )
+        };
+        let profile_pairs = compare_items(
+            ItemKind::Profile,
+            profiles(self)?,
+            profiles(new)?,
+            &mut changes,
+        );
+        // Fields are only compared within profiles that still exist. If the
+        // profile was removed, its fields went with it
+        for (old_id, new_id) in profile_pairs {
+            let fields = |collection: &Self, id: &str| {
+                serialize_items(
+                    collection
+                        .profiles
+                        .get(&ProfileId::from(id.to_owned()))
+                        .into_iter()
+                        .flat_map(|profile| &profile.data)
+                        .map(|(field, template)| (field.clone(), template)),
+                )
+            };
+            compare_items(
+                ItemKind::Field {
+                    profile: new_id.clone().into(),
+                },
+                fields(self, &old_id)?,
+                fields(new, &new_id)?,
+                &mut changes,
+            );
+        }
+
+        let chains = |collection: &Self| {
+            serialize_items(
+                collection
+                    .chains
+                    .iter()
+                    .map(|(id, chain)| (id.to_string(), chain)),
+            )
+        };
+        compare_items(
+            ItemKind::Chain,
+            chains(self)?,
+            chains(new)?,
+            &mut changes,
+        );
+
+        Ok(changes)
+    }
+}
+
+/// Serialize items so their content can be compared. IDs are skipped during
+/// serialization, so two items with different IDs can still be equal.
+fn serialize_items<'a, T: 'a + Serialize>(
+    items: impl IntoIterator<Item = (String, &'a T)>,
+) -> anyhow::Result<IndexMap<String, Value>> {
+    items
+        .into_iter()
+        .map(|(id, item)| Ok((id, serde_yaml::to_value(item)?)))
+        .collect()
+}
+
+/// Report every item in the old set that's missing from the new one. Return
+/// the ID of each old item that's still present, paired with its ID in the new
+/// set (which differs if it was renamed).
+fn compare_items(
+    kind: ItemKind,
+    old: IndexMap<String, Value>,
+    new: IndexMap<String, Value>,
+    changes: &mut Vec<BreakingChange>,
+) -> Vec<(String, String)> {
+    // Only added items are candidates for renames. Each can only be the
+    // target of one rename
+    let mut added: IndexMap<&String, &Value> = new
+        .iter()
+        .filter(|(id, _)| !old.contains_key(*id))
+        .collect();
+
+    let mut pairs = Vec::new();
+    for (id, value) in &old {
+        if new.contains_key(id) {
+            pairs.push((id.clone(), id.clone()));
+            continue;
+        }
+
+        let renamed_to = added
+            .iter()
+            .find(|(_, added_value)| **added_value == value)
+            .map(|(new_id, _)| (*new_id).clone());
+        match renamed_to {
+            Some(to) => {
+                added.shift_remove(&to);
+                changes.push(BreakingChange::Renamed {
+                    kind: kind.clone(),
+                    from: id.clone(),
+                    to: to.clone(),
+                });
+                pairs.push((id.clone(), to));
+            }
+            None => changes.push(BreakingChange::Removed {
+                kind: kind.clone(),
+                id: id.clone(),
+            }),
+        }
+    }
+    pairs
+}
+
+impl Display for BreakingChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Removed {
+                kind: ItemKind::Field { profile },
+                id,
+            } => write!(f, "- Field `{id}` removed from profile `{profile}`"),
+            Self::Removed { kind, id } => write!(f, "- {kind} `{id}` removed"),
+            Self::Renamed {
+                kind: ItemKind::Field { profile },
+                from,
+                to,
+            } => write!(
+                f,
+                "~ Field `{from}` renamed to `{to}` in profile `{profile}`"
+            ),
+            Self::Renamed { kind, from, to } => {
+                write!(f, "~ {kind} `{from}` renamed to `{to}`")
+            }
+        }
+    }
+}
+
+impl Display for ItemKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Recipe => write!(f, "Recipe"),
+            Self::Profile => write!(f, "Profile"),
+            Self::Field { .. } => write!(f, "Field"),
+            Self::Chain => write!(f, "Chain"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_yaml;
+    use rstest::rstest;
+
+    fn collection(yaml: &str) -> Collection {
+        parse_yaml(yaml.as_bytes()).unwrap()
+    }
+
+    #[rstest]
+    #[case::unchanged(
+        "requests: {login: !request {method: POST, url: '{{host}}/login'}}",
+        "requests: {login: !request {method: POST, url: '{{host}}/login'}}",
+        &[]
+    )]
+    #[case::content_changed(
+        "requests: {login: !request {method: POST, url: '{{host}}/login'}}",
+        "requests: {login: !request {method: PUT, url: '{{host}}/login'}}",
+        &[]
+    )]
+    #[case::added(
+        "requests: {login: !request {method: POST, url: u}}",
+        "requests: {
+            login: !request {method: POST, url: u},
+            logout: !request {method: POST, url: u},
+        }",
+        &[]
+    )]
+    #[case::recipe_removed(
+        "requests: {
+            login: !request {method: POST, url: u},
+            logout: !request {method: POST, url: u2},
+        }",
+        "requests: {login: !request {method: POST, url: u}}",
+        &["- Recipe `logout` removed"]
+    )]
+    #[case::recipe_renamed(
+        "requests: {login: !request {method: POST, url: u}}",
+        "requests: {sign_in: !request {method: POST, url: u}}",
+        &["~ Recipe `login` renamed to `sign_in`"]
+    )]
+    #[case::recipe_moved_to_folder(
+        "requests: {login: !request {method: POST, url: u}}",
+        "requests: {auth: !folder {requests: {
+            login: !request {method: POST, url: u},
+        }}}",
+        &[]
+    )]
+    #[case::recipe_replaced(
+        "requests: {login: !request {method: POST, url: u}}",
+        "requests: {sign_in: !request {method: PUT, url: u}}",
+        &["- Recipe `login` removed"]
+    )]
+    #[case::profile_renamed(
+        "profiles: {dev: {data: {host: h}}}",
+        "profiles: {local: {data: {host: h}}}",
+        &["~ Profile `dev` renamed to `local`"]
+    )]
+    #[case::profile_removed(
+        "profiles: {dev: {data: {host: h}}, prod: {data: {host: p}}}",
+        "profiles: {dev: {data: {host: h}}}",
+        &["- Profile `prod` removed"]
+    )]
+    #[case::field_removed(
+        "profiles: {dev: {data: {host: h, token: t}}}",
+        "profiles: {dev: {data: {host: h}}}",
+        &["- Field `token` removed from profile `dev`"]
+    )]
+    #[case::field_renamed(
+        "profiles: {dev: {data: {host: h}}}",
+        "profiles: {dev: {data: {base_url: h}}}",
+        &["~ Field `host` renamed to `base_url` in profile `dev`"]
+    )]
+    #[case::chain_removed(
+        "chains: {token: {source: !command {command: [echo]}}}",
+        "chains: {}",
+        &["- Chain `token` removed"]
+    )]
+    #[case::chain_renamed(
+        "chains: {token: {source: !command {command: [echo]}}}",
+        "chains: {auth_token: {source: !command {command: [echo]}}}",
+        &["~ Chain `token` renamed to `auth_token`"]
+    )]
+    // An added item can only be the target of one rename
+    #[case::rename_once(
+        "chains: {
+            a: {source: !command {command: [echo]}},
+            b: {source: !command {command: [echo]}},
+        }",
+        "chains: {c: {source: !command {command: [echo]}}}",
+        &["~ Chain `a` renamed to `c`", "- Chain `b` removed"]
+    )]
+    fn test_breaking_changes(
+        #[case] old: &str,
+        #[case] new: &str,
+        #[case] expected: &[&str],
+    ) {
+        let changes =
+            collection(old).breaking_changes(&collection(new)).unwrap();
+        let changes: Vec<String> =
+            changes.iter().map(ToString::to_string).collect();
+        assert_eq!(changes, expected);
+    }
+}
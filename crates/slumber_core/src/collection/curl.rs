@@ -0,0 +1,615 @@
+//! Import a single recipe from a curl command, e.g. one copied from a
+//! browser's dev tools or an API's documentation
+
+use crate::{
+    collection::{Authentication, Method, Recipe, RecipeBody, RecipeId},
+    template::Template,
+};
+use anyhow::{anyhow, bail, Context};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use regex::Regex;
+use reqwest::header;
+use std::sync::LazyLock;
+use url::form_urlencoded;
+
+impl Recipe {
+    /// Parse a curl command into a recipe. This supports the options that
+    /// define a request (URL, method, headers, body, and authentication).
+    /// Options that only affect how curl runs (e.g. `--silent`) are ignored,
+    /// and anything else is an error, so nothing is quietly dropped.
+    ///
+    /// The recipe's ID is generated from the method and URL, e.g. `get_users`.
+    /// All values are imported as-is, so nothing is interpreted as a template.
+    pub fn from_curl(command: &str) -> anyhow::Result<Self> {
+        // Commands are often split across lines with trailing backslashes
+        static LINE_CONTINUATION: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"\\[ \t]*\r?\n").unwrap());
+        let command = LINE_CONTINUATION.replace_all(command, " ");
+        let args = shell_words::split(&command)
+            .context("Invalid curl command")?
+            .into_iter()
+            // Stray whitespace can show up as an arg if a line continuation
+            // had trailing spaces after it
+            .filter(|arg| !arg.trim().is_empty());
+        CurlCommand::parse(args)?.into_recipe()
+    }
+}
+
+/// The parts of a curl command that we care about
+#[derive(Debug, Default)]
+struct CurlCommand {
+    url: Option<String>,
+    method: Option<Method>,
+    headers: Vec<(String, String)>,
+    /// Each `--data` value, in order. curl joins them with `&`
+    data: Vec<String>,
+    form: Vec<(String, String)>,
+    /// `username:password`
+    user: Option<String>,
+    bearer: Option<String>,
+    /// Send data in the query string instead of the body
+    get: bool,
+    head: bool,
+    insecure: bool,
+}
+
+impl CurlCommand {
+    fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let program = args.next().unwrap_or_default();
+        if !(program == "curl" || program.ends_with("/curl")) {
+            bail!("Command must start with `curl`");
+        }
+
+        let mut command = Self::default();
+        while let Some(arg) = args.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                let option = CurlOption::from_long(name)
+                    .ok_or_else(|| anyhow!("Unsupported option `{arg}`"))?;
+                let value = if option.takes_value() {
+                    Some(args.next().ok_or_else(|| {
+                        anyhow!("Option `{arg}` requires a value")
+                    })?)
+                } else {
+                    None
+                };
+                command.apply(option, value)?;
+            } else if let Some(flags) =
+                arg.strip_prefix('-').filter(|flags| !flags.is_empty())
+            {
+                // Short options can be combined (`-sSL`), and the last one can
+                // have its value attached (`-XPOST`)
+                for (i, flag) in flags.char_indices() {
+                    let option =
+                        CurlOption::from_short(flag).ok_or_else(|| {
+                            anyhow!("Unsupported option `-{flag}`")
+                        })?;
+                    if option.takes_value() {
+                        let rest = &flags[i + flag.len_utf8()..];
+                        let value = if rest.is_empty() {
+                            args.next().ok_or_else(|| {
+                                anyhow!("Option `-{flag}` requires a value")
+                            })?
+                        } else {
+                            rest.to_owned()
+                        };
+                        command.apply(option, Some(value))?;
+                        break;
+                    }
+                    command.apply(option, None)?;
+                }
+            } else {
+                command.set_url(arg)?;
+            }
+        }
+        Ok(command)
+    }
+
+    /// Apply a single option. `value` is populated iff the option takes one
+    fn apply(
+        &mut self,
+        option: CurlOption,
+        value: Option<String>,
+    ) -> anyhow::Result<()> {
+        let value = value.unwrap_or_default();
+        match option {
+            CurlOption::Url => self.set_url(value)?,
+            CurlOption::Request => {
+                self.method =
+                    Some(value.parse().map_err(|_| {
+                        anyhow!("Unknown HTTP method `{value}`")
+                    })?);
+            }
+            CurlOption::Header => {
+                // `Name;` sends an empty header, `Name:` removes a default
+                // header, which we don't have
+                if let Some(name) = value.strip_suffix(';') {
+                    self.headers.push((name.trim().to_owned(), String::new()));
+                } else {
+                    let (name, value) = value
+                        .split_once(':')
+                        .ok_or_else(|| anyhow!("Invalid header `{value}`"))?;
+                    if !value.trim().is_empty() {
+                        self.headers.push((
+                            name.trim().to_owned(),
+                            value.trim().to_owned(),
+                        ));
+                    }
+                }
+            }
+            CurlOption::Data => {
+                if value.starts_with('@') {
+                    return Err(file_unsupported(&value));
+                }
+                self.data.push(value);
+            }
+            CurlOption::DataRaw => self.data.push(value),
+            CurlOption::DataUrlencode => {
+                self.data.push(urlencode_data(&value)?)
+            }
+            CurlOption::Json => {
+                if value.starts_with('@') {
+                    return Err(file_unsupported(&value));
+                }
+                self.data.push(value);
+                self.default_header(header::CONTENT_TYPE, "application/json");
+                self.default_header(header::ACCEPT, "application/json");
+            }
+            CurlOption::Form | CurlOption::FormString => {
+                let (name, field) = value.split_once('=').ok_or_else(|| {
+                    anyhow!(
+                        "Invalid form field `{value}`; expected `name=value`"
+                    )
+                })?;
+                if option == CurlOption::Form
+                    && (field.starts_with('@') || field.starts_with('<'))
+                {
+                    return Err(file_unsupported(&value));
+                }
+                self.form.push((name.to_owned(), field.to_owned()));
+            }
+            CurlOption::User => self.user = Some(value),
+            CurlOption::Bearer => self.bearer = Some(value),
+            CurlOption::Cookie => {
+                // Without a `=`, the value is the path to a cookie file
+                if !value.contains('=') {
+                    return Err(file_unsupported(&value));
+                }
+                self.headers.push((header::COOKIE.to_string(), value));
+            }
+            CurlOption::UserAgent => {
+                self.headers.push((header::USER_AGENT.to_string(), value))
+            }
+            CurlOption::Referer => {
+                self.headers.push((header::REFERER.to_string(), value))
+            }
+            CurlOption::Get => self.get = true,
+            CurlOption::Head => self.head = true,
+            CurlOption::Insecure => self.insecure = true,
+            CurlOption::Ignored | CurlOption::IgnoredValue => {}
+        }
+        Ok(())
+    }
+
+    fn set_url(&mut self, url: String) -> anyhow::Result<()> {
+        if let Some(existing) = &self.url {
+            bail!("Multiple URLs (`{existing}` and `{url}`) are not supported");
+        }
+        self.url = Some(url);
+        Ok(())
+    }
+
+    /// Add a header if it isn't already set
+    fn default_header(&mut self, name: header::HeaderName, value: &str) {
+        if self.header(&name).is_none() {
+            self.headers.push((name.to_string(), value.to_owned()));
+        }
+    }
+
+    /// Get the value of a header, case-insensitive
+    fn header(&self, name: &header::HeaderName) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name.as_str()))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn remove_header(&mut self, name: &header::HeaderName) {
+        self.headers
+            .retain(|(header, _)| !header.eq_ignore_ascii_case(name.as_str()));
+    }
+
+    fn into_recipe(mut self) -> anyhow::Result<Recipe> {
+        let url = self.url.take().ok_or_else(|| anyhow!("No URL given"))?;
+        // The fragment is never sent, so drop it
+        let url = url.split_once('#').map_or(url.as_str(), |(url, _)| url);
+        let (url, query) = url.split_once('?').unwrap_or((url, ""));
+        let mut query: Vec<(String, String)> =
+            form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect();
+
+        let has_body = !self.data.is_empty() || !self.form.is_empty();
+        if !self.data.is_empty() && !self.form.is_empty() {
+            bail!("Cannot combine `--data` and `--form`");
+        }
+        let method = self.method.unwrap_or(if self.head {
+            Method::Head
+        } else if has_body && !self.get {
+            Method::Post
+        } else {
+            Method::Get
+        });
+
+        let data = self.data.iter().join("&");
+        let body = if self.get {
+            // `--get` moves the data into the query string
+            query.extend(form_urlencoded::parse(data.as_bytes()).into_owned());
+            None
+        } else if !self.form.is_empty() {
+            // The boundary in the header is generated per request, so the
+            // header can't be reused
+            self.remove_header(&header::CONTENT_TYPE);
+            Some(RecipeBody::FormMultipart(to_templates(self.form)))
+        } else if !self.data.is_empty() {
+            Some(self.data_body(data))
+        } else {
+            None
+        };
+
+        let authentication = if let Some(token) = self.bearer {
+            Some(Authentication::Bearer(Template::raw(token)))
+        } else {
+            self.user.map(|user| match user.split_once(':') {
+                Some((username, password)) => Authentication::Basic {
+                    username: Template::raw(username.to_owned()),
+                    password: Some(Template::raw(password.to_owned())),
+                },
+                None => Authentication::Basic {
+                    username: Template::raw(user),
+                    password: None,
+                },
+            })
+        };
+
+        Ok(Recipe {
+            id: RecipeId::generate(method, url),
+            name: None,
+            method,
+            url: Template::raw(url.to_owned()),
+            body,
+            authentication,
+            signature: None,
+            certificate: None,
+            danger_skip_tls_verify: self.insecure,
+            baseline_ignore: vec![],
+            assertions: vec![],
+            display_query: None,
+            example: None,
+            query: query
+                .into_iter()
+                .map(|(name, value)| (name, Template::raw(value)))
+                .collect(),
+            headers: to_templates(self.headers),
+            parameters: IndexMap::new(),
+        })
+    }
+
+    /// Convert `--data` into a body. If it's a structured body, Slumber sets
+    /// the content type header itself, so the header is removed.
+    fn data_body(&mut self, data: String) -> RecipeBody {
+        // curl sends data as a form unless told otherwise
+        let content_type = self
+            .header(&header::CONTENT_TYPE)
+            .unwrap_or(mime::APPLICATION_WWW_FORM_URLENCODED.as_ref())
+            .to_owned();
+        let (body, is_structured) =
+            RecipeBody::untemplated_text(&content_type, data);
+        if is_structured {
+            self.remove_header(&header::CONTENT_TYPE);
+        }
+        body
+    }
+}
+
+/// An option that we know how to handle. Anything not in here is rejected
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum CurlOption {
+    Url,
+    Request,
+    Header,
+    Data,
+    /// `--data-raw`, which doesn't treat `@` as a file
+    DataRaw,
+    DataUrlencode,
+    Json,
+    Form,
+    /// `--form-string`, which doesn't treat `@` or `<` as a file
+    FormString,
+    User,
+    Bearer,
+    Cookie,
+    UserAgent,
+    Referer,
+    Get,
+    Head,
+    Insecure,
+    /// A flag that doesn't affect the request
+    Ignored,
+    /// An option with a value that doesn't affect the request
+    IgnoredValue,
+}
+
+impl CurlOption {
+    /// Short and long names for each option
+    const ALL: &'static [(Option<char>, &'static str, Self)] = &[
+        (None, "url", Self::Url),
+        (Some('X'), "request", Self::Request),
+        (Some('H'), "header", Self::Header),
+        (Some('d'), "data", Self::Data),
+        (None, "data-ascii", Self::Data),
+        (None, "data-binary", Self::Data),
+        (None, "data-raw", Self::DataRaw),
+        (None, "data-urlencode", Self::DataUrlencode),
+        (None, "json", Self::Json),
+        (Some('F'), "form", Self::Form),
+        (None, "form-string", Self::FormString),
+        (Some('u'), "user", Self::User),
+        (None, "oauth2-bearer", Self::Bearer),
+        (Some('b'), "cookie", Self::Cookie),
+        (Some('A'), "user-agent", Self::UserAgent),
+        (Some('e'), "referer", Self::Referer),
+        (Some('G'), "get", Self::Get),
+        (Some('I'), "head", Self::Head),
+        (Some('k'), "insecure", Self::Insecure),
+        (None, "compressed", Self::Ignored),
+        (Some('f'), "fail", Self::Ignored),
+        (Some('g'), "globoff", Self::Ignored),
+        (None, "http1.1", Self::Ignored),
+        (None, "http2", Self::Ignored),
+        (Some('i'), "include", Self::Ignored),
+        (Some('L'), "location", Self::Ignored),
+        (Some('N'), "no-buffer", Self::Ignored),
+        (Some('#'), "progress-bar", Self::Ignored),
+        (Some('S'), "show-error", Self::Ignored),
+        (Some('s'), "silent", Self::Ignored),
+        (Some('v'), "verbose", Self::Ignored),
+        (None, "connect-timeout", Self::IgnoredValue),
+        (Some('m'), "max-time", Self::IgnoredValue),
+        (Some('o'), "output", Self::IgnoredValue),
+        (None, "retry", Self::IgnoredValue),
+        (Some('w'), "write-out", Self::IgnoredValue),
+    ];
+
+    fn from_short(short: char) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(name, _, _)| *name == Some(short))
+            .map(|(_, _, option)| *option)
+    }
+
+    fn from_long(long: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(_, name, _)| *name == long)
+            .map(|(_, _, option)| *option)
+    }
+
+    fn takes_value(self) -> bool {
+        !matches!(
+            self,
+            Self::Get | Self::Head | Self::Insecure | Self::Ignored
+        )
+    }
+}
+
+/// Encode a `--data-urlencode` value. `name=content` encodes just the content,
+/// `=content` or `content` encodes all of it, and `name@file` reads a file
+fn urlencode_data(value: &str) -> anyhow::Result<String> {
+    let encode = |content: &str| {
+        form_urlencoded::byte_serialize(content.as_bytes()).collect::<String>()
+    };
+    match value.find(['=', '@']).map(|i| value.split_at(i)) {
+        Some((_, content)) if content.starts_with('@') => {
+            Err(file_unsupported(value))
+        }
+        Some(("", content)) => Ok(encode(&content[1..])),
+        Some((name, content)) => {
+            Ok(format!("{name}={}", encode(&content[1..])))
+        }
+        None => Ok(encode(value)),
+    }
+}
+
+/// Reading from files isn't supported, because the path is relative to
+/// wherever the command was going to be run
+fn file_unsupported(value: &str) -> anyhow::Error {
+    anyhow!("Reading from a file (`{value}`) is not supported")
+}
+
+fn to_templates(pairs: Vec<(String, String)>) -> IndexMap<String, Template> {
+    pairs
+        .into_iter()
+        .map(|(name, value)| (name, Template::raw(value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assert_err, test_util::Factory};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn recipe(id: &str, method: Method, url: &str) -> Recipe {
+        Recipe {
+            id: id.into(),
+            method,
+            url: Template::raw(url.into()),
+            ..Recipe::factory(())
+        }
+    }
+
+    fn templates(pairs: &[(&str, &str)]) -> IndexMap<String, Template> {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                ((*name).into(), Template::raw((*value).into()))
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::get(
+        "curl https://example.com/users/3",
+        recipe("get_users", Method::Get, "https://example.com/users/3")
+    )]
+    #[case::query(
+        "curl 'https://example.com/search?q=hello%20world&page=2#top'",
+        Recipe {
+            query: vec![
+                ("q".into(), Template::raw("hello world".into())),
+                ("page".into(), Template::raw("2".into())),
+            ],
+            ..recipe("get_search", Method::Get, "https://example.com/search")
+        },
+    )]
+    #[case::headers(
+        "curl -X DELETE -H 'Accept: text/plain' -H 'X-Empty;' \
+        -A slumber -b 'session=abc' --url https://example.com/api",
+        Recipe {
+            headers: templates(&[
+                ("Accept", "text/plain"),
+                ("X-Empty", ""),
+                ("user-agent", "slumber"),
+                ("cookie", "session=abc"),
+            ]),
+            ..recipe("delete_api", Method::Delete, "https://example.com/api")
+        },
+    )]
+    #[case::json(
+        "curl https://example.com/users \\\n  \
+        -H 'content-type: application/json' \\\n  \
+        --data-raw '{\"name\": \"Ted\"}' --compressed",
+        Recipe {
+            body: Some(RecipeBody::untemplated_json(json!({"name": "Ted"}))),
+            ..recipe("post_users", Method::Post, "https://example.com/users")
+        },
+    )]
+    #[case::json_option(
+        "curl --json '[1,2]' -X PUT https://example.com/ids",
+        Recipe {
+            headers: templates(&[("accept", "application/json")]),
+            body: Some(RecipeBody::untemplated_json(json!([1, 2]))),
+            ..recipe("put_ids", Method::Put, "https://example.com/ids")
+        },
+    )]
+    #[case::form_urlencoded(
+        "curl -d name=Ted -d 'age=3%30' --data-urlencode 'bio=a&b' \
+        https://example.com/users",
+        Recipe {
+            body: Some(RecipeBody::FormUrlencoded(templates(&[
+                ("name", "Ted"),
+                ("age", "30"),
+                ("bio", "a&b"),
+            ]))),
+            ..recipe("post_users", Method::Post, "https://example.com/users")
+        },
+    )]
+    #[case::raw(
+        "curl -H 'Content-Type: text/plain' -d 'hello' https://example.com",
+        Recipe {
+            headers: templates(&[("Content-Type", "text/plain")]),
+            body: Some("hello".into()),
+            ..recipe("post_example_com", Method::Post, "https://example.com")
+        },
+    )]
+    #[case::multipart(
+        "curl -F name=Ted --form-string 'avatar=@me.png' \
+        https://example.com/users",
+        Recipe {
+            body: Some(RecipeBody::FormMultipart(templates(&[
+                ("name", "Ted"),
+                ("avatar", "@me.png"),
+            ]))),
+            ..recipe("post_users", Method::Post, "https://example.com/users")
+        },
+    )]
+    #[case::get_data(
+        "curl -G -d page=2 https://example.com/users?limit=10",
+        Recipe {
+            query: vec![
+                ("limit".into(), Template::raw("10".into())),
+                ("page".into(), Template::raw("2".into())),
+            ],
+            ..recipe("get_users", Method::Get, "https://example.com/users")
+        },
+    )]
+    #[case::basic_auth(
+        "curl -sSku user:hunter2 https://example.com/login",
+        Recipe {
+            authentication: Some(Authentication::Basic {
+                username: Template::raw("user".into()),
+                password: Some(Template::raw("hunter2".into())),
+            }),
+            danger_skip_tls_verify: true,
+            ..recipe("get_login", Method::Get, "https://example.com/login")
+        },
+    )]
+    #[case::bearer_auth(
+        "curl -I --oauth2-bearer t0ken -o /dev/null https://example.com/me",
+        Recipe {
+            authentication: Some(Authentication::Bearer(Template::raw(
+                "t0ken".into()
+            ))),
+            ..recipe("head_me", Method::Head, "https://example.com/me")
+        },
+    )]
+    #[case::attached_value(
+        "/usr/bin/curl -XPATCH -d{} https://example.com/{{id}}",
+        Recipe {
+            body: Some(RecipeBody::Raw {
+                body: Template::raw("{}".into()),
+                content_type: None,
+            }),
+            ..recipe("patch_id", Method::Patch, "https://example.com/{{id}}")
+        },
+    )]
+    fn test_from_curl(#[case] command: &str, #[case] expected: Recipe) {
+        assert_eq!(Recipe::from_curl(command).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::not_curl("wget https://example.com", "must start with `curl`")]
+    #[case::no_url("curl -X GET", "No URL given")]
+    #[case::two_urls("curl a b", "Multiple URLs (`a` and `b`)")]
+    #[case::unknown_option(
+        "curl --proxy localhost https://example.com",
+        "Unsupported option `--proxy`"
+    )]
+    #[case::unknown_short("curl -sZ https://example.com", "option `-Z`")]
+    #[case::missing_value("curl https://example.com -H", "requires a value")]
+    #[case::method("curl -X FETCH https://example.com", "method `FETCH`")]
+    #[case::data_file(
+        "curl -d @body.json https://example.com",
+        "Reading from a file (`@body.json`) is not supported"
+    )]
+    #[case::form_file("curl -F 'a=<a.txt' https://example.com", "`a=<a.txt`")]
+    #[case::urlencode_file(
+        "curl --data-urlencode 'a@a.txt' https://example.com",
+        "`a@a.txt`"
+    )]
+    #[case::cookie_file("curl -b cookies.txt https://example.com", "file")]
+    #[case::data_and_form(
+        "curl -d a=1 -F b=2 https://example.com",
+        "Cannot combine `--data` and `--form`"
+    )]
+    #[case::quotes("curl 'https://example.com", "Invalid curl command")]
+    fn test_from_curl_error(
+        #[case] command: &str,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(Recipe::from_curl(command), expected_error);
+    }
+}
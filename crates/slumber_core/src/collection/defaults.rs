@@ -0,0 +1,263 @@
+//! Defaults shared by groups of recipes. The collection root and each folder
+//! can define `defaults`, which are merged into every recipe beneath them when
+//! the request is built.
+
+use crate::{
+    collection::{
+        Authentication, Collection, Recipe, RecipeDefaults, RecipeId,
+        RecipeNode,
+    },
+    template::Template,
+};
+use itertools::Itertools;
+use std::borrow::Cow;
+
+impl Collection {
+    /// Get the defaults that apply to a recipe, merged from the collection
+    /// root down through each of its ancestor folders. Folders closer to the
+    /// recipe take precedence. If the recipe isn't in the tree, only the root
+    /// defaults apply.
+    pub fn recipe_defaults(&self, recipe_id: &RecipeId) -> RecipeDefaults {
+        let mut defaults = RecipeDefaults::default();
+        defaults.extend(&self.defaults);
+        let ancestors = self
+            .recipes
+            .get_lookup_key(recipe_id)
+            .map(|key| key.as_slice())
+            .unwrap_or_default();
+        for id in ancestors {
+            if let Some(RecipeNode::Folder(folder)) = self.recipes.get(id) {
+                defaults.extend(&folder.defaults);
+            }
+        }
+        defaults
+    }
+}
+
+impl RecipeDefaults {
+    pub fn is_empty(&self) -> bool {
+        self.base_url.is_none()
+            && self.authentication.is_none()
+            && self.headers.is_empty()
+    }
+
+    /// Apply a more specific set of defaults on top of this one. Headers are
+    /// merged by name; everything else is replaced.
+    fn extend(&mut self, other: &RecipeDefaults) {
+        if let Some(base_url) = &other.base_url {
+            self.base_url = Some(base_url.clone());
+        }
+        if let Some(authentication) = &other.authentication {
+            self.authentication = Some(authentication.clone());
+        }
+        for (header, value) in &other.headers {
+            self.headers
+                .retain(|existing, _| !existing.eq_ignore_ascii_case(header));
+            self.headers.insert(header.clone(), value.clone());
+        }
+    }
+
+    /// Get the full URL template for a recipe. If the recipe's URL is a path
+    /// (starts with `/`), it's appended to the base URL. Otherwise it's used
+    /// as-is.
+    pub fn url<'a>(&self, recipe: &'a Recipe) -> Cow<'a, Template> {
+        match &self.base_url {
+            Some(base_url) if recipe.url.display().starts_with('/') => {
+                Cow::Owned(base_url.concat(&recipe.url))
+            }
+            _ => Cow::Borrowed(&recipe.url),
+        }
+    }
+
+    /// Get a recipe's full URL and its query parameters, for exporting to
+    /// another tool. Each template is converted to the tool's syntax with
+    /// `translate`. The query parameters are appended to the URL as well as
+    /// returned on their own, so the caller can use either form.
+    pub(super) fn export_url(
+        &self,
+        recipe: &Recipe,
+        mut translate: impl FnMut(&Template) -> String,
+    ) -> (String, Vec<(String, String)>) {
+        let mut url = translate(&self.url(recipe));
+        let query: Vec<(String, String)> = recipe
+            .query
+            .iter()
+            .map(|(key, value)| (key.clone(), translate(value)))
+            .collect();
+        if !query.is_empty() {
+            url.push(if url.contains('?') { '&' } else { '?' });
+            let pairs = query
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .join("&");
+            url.push_str(&pairs);
+        }
+        (url, query)
+    }
+
+    /// Get the default headers that a recipe doesn't set itself. Header names
+    /// are case-insensitive, so a recipe header overrides a default of the
+    /// same name regardless of case.
+    pub fn inherited_headers<'a>(
+        &'a self,
+        recipe: &'a Recipe,
+    ) -> impl Iterator<Item = (&'a String, &'a Template)> {
+        self.headers.iter().filter(|(header, _)| {
+            !recipe
+                .headers
+                .keys()
+                .any(|other| other.eq_ignore_ascii_case(header))
+        })
+    }
+
+    /// Get a recipe's authentication, falling back to the default
+    pub fn authentication<'a>(
+        &'a self,
+        recipe: &'a Recipe,
+    ) -> Option<&'a Authentication> {
+        recipe
+            .authentication
+            .as_ref()
+            .or(self.authentication.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::Factory, util::parse_yaml};
+    use indexmap::indexmap;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    const COLLECTION: &str = r#"
+defaults:
+  base_url: "{{host}}/api"
+  authentication: !bearer "{{token}}"
+  headers:
+    Accept: application/json
+    X-Client: slumber
+requests:
+  login: !request
+    method: POST
+    url: /login
+    authentication: !basic
+      username: user
+  users: !folder
+    defaults:
+      headers:
+        accept: text/csv
+    requests:
+      admin: !folder
+        defaults:
+          base_url: "{{host}}/admin"
+          authentication: !bearer "{{admin_token}}"
+        requests:
+          list_admins: !request
+            method: GET
+            url: /users
+            headers:
+              X-CLIENT: other
+      list_users: !request
+        method: GET
+        url: https://example.com/users
+"#;
+
+    fn recipe<'a>(collection: &'a Collection, id: &str) -> &'a Recipe {
+        collection.recipes.get_recipe(&id.into()).unwrap()
+    }
+
+    /// Defaults are merged from the root down, with the recipe itself taking
+    /// precedence over all of them
+    #[test]
+    fn test_recipe_defaults() {
+        let collection: Collection = parse_yaml(COLLECTION.as_bytes()).unwrap();
+
+        let login = recipe(&collection, "login");
+        let defaults = collection.recipe_defaults(&login.id);
+        assert_eq!(defaults.url(login).display(), "{{host}}/api/login");
+        assert_eq!(
+            defaults.authentication(login),
+            Some(&Authentication::Basic {
+                username: "user".into(),
+                password: None
+            })
+        );
+        assert_eq!(
+            defaults.inherited_headers(login).collect_vec(),
+            [
+                (&"Accept".into(), &"application/json".into()),
+                (&"X-Client".into(), &"slumber".into())
+            ]
+        );
+
+        let list_admins = recipe(&collection, "list_admins");
+        let defaults = collection.recipe_defaults(&list_admins.id);
+        assert_eq!(defaults.url(list_admins).display(), "{{host}}/admin/users");
+        assert_eq!(
+            defaults.authentication(list_admins),
+            Some(&Authentication::Bearer("{{admin_token}}".into()))
+        );
+        // Header names are case-insensitive at every level
+        assert_eq!(
+            defaults.headers,
+            indexmap! {
+                "X-Client".into() => "slumber".into(),
+                "accept".into() => "text/csv".into(),
+            }
+        );
+        assert_eq!(
+            defaults.inherited_headers(list_admins).collect_vec(),
+            [(&"accept".into(), &"text/csv".into())]
+        );
+
+        // Absolute URLs ignore the base URL
+        let list_users = recipe(&collection, "list_users");
+        let defaults = collection.recipe_defaults(&list_users.id);
+        assert_eq!(
+            defaults.url(list_users).display(),
+            "https://example.com/users"
+        );
+        assert_eq!(
+            defaults.authentication(list_users),
+            Some(&Authentication::Bearer("{{token}}".into()))
+        );
+    }
+
+    /// Query parameters are appended to the URL, after any query the URL
+    /// already has
+    #[rstest]
+    #[case::no_query("/users", &[], "{{host}}/api/users")]
+    #[case::query(
+        "/users",
+        &[("limit", "10"), ("tag", "{{tag}}")],
+        "{{host}}/api/users?limit=10&tag=TAG"
+    )]
+    #[case::existing_query(
+        "/users?sort=asc",
+        &[("limit", "10")],
+        "{{host}}/api/users?sort=asc&limit=10"
+    )]
+    fn test_export_url(
+        #[case] url: &str,
+        #[case] query: &[(&str, &str)],
+        #[case] expected: &str,
+    ) {
+        let collection: Collection = parse_yaml(COLLECTION.as_bytes()).unwrap();
+        let recipe = Recipe {
+            url: url.into(),
+            query: query
+                .iter()
+                .map(|(key, value)| ((*key).to_owned(), (*value).into()))
+                .collect(),
+            ..Recipe::factory(())
+        };
+        // Stand-in for translating to another tool's syntax
+        let translate =
+            |template: &Template| template.display().replace("{{tag}}", "TAG");
+        let (url, translated_query) =
+            collection.defaults.export_url(&recipe, translate);
+        assert_eq!(url, expected);
+        assert_eq!(translated_query.len(), query.len());
+    }
+}
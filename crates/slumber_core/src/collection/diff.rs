@@ -0,0 +1,228 @@
+//! Compare two versions of a collection, e.g. the working tree and the last
+//! git commit
+
+use crate::collection::Collection;
+use serde_yaml::Value;
+use std::fmt::{self, Display, Formatter};
+
+/// A single difference between two versions of a collection. The path is a
+/// dotted path into the collection file, e.g. `requests.login.url`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CollectionChange {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        before: Value,
+        after: Value,
+    },
+}
+
+impl Collection {
+    /// Get every difference from this collection to another. Templates are
+    /// compared by their source, so changes are reported at the template
+    /// level rather than line-by-line. Return an empty list if they match.
+    pub fn diff(&self, other: &Self) -> anyhow::Result<Vec<CollectionChange>> {
+        let before = serde_yaml::to_value(self)?;
+        let after = serde_yaml::to_value(other)?;
+        let mut changes = Vec::new();
+        diff_values(String::new(), before, after, &mut changes);
+        Ok(changes)
+    }
+}
+
+impl CollectionChange {
+    /// Dotted path to the changed value, e.g. `requests.login.url`
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Added { path, .. }
+            | Self::Removed { path, .. }
+            | Self::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// Recursively compare two YAML values, adding any differences to the list
+pub(super) fn diff_values(
+    path: String,
+    before: Value,
+    after: Value,
+    changes: &mut Vec<CollectionChange>,
+) {
+    match (before, after) {
+        (Value::Mapping(mut before), Value::Mapping(after)) => {
+            // Maintain the order of the new version, with removals at the end
+            for (key, after) in after {
+                let path = child_path(&path, &key);
+                match before.remove(&key) {
+                    Some(before) => diff_values(path, before, after, changes),
+                    None => changes
+                        .push(CollectionChange::Added { path, value: after }),
+                }
+            }
+            for (key, before) in before {
+                changes.push(CollectionChange::Removed {
+                    path: child_path(&path, &key),
+                    value: before,
+                });
+            }
+        }
+        (Value::Sequence(before), Value::Sequence(after)) => {
+            let len = before.len().max(after.len());
+            let mut before = before.into_iter();
+            let mut after = after.into_iter();
+            for i in 0..len {
+                let path = format!("{path}[{i}]");
+                match (before.next(), after.next()) {
+                    (Some(before), Some(after)) => {
+                        diff_values(path, before, after, changes)
+                    }
+                    (None, Some(value)) => {
+                        changes.push(CollectionChange::Added { path, value })
+                    }
+                    (Some(value), None) => {
+                        changes.push(CollectionChange::Removed { path, value })
+                    }
+                    (None, None) => unreachable!("Index is within both lists"),
+                }
+            }
+        }
+        // Tags (e.g. `!request`) are transparent, as long as they match
+        (Value::Tagged(before), Value::Tagged(after))
+            if before.tag == after.tag =>
+        {
+            diff_values(path, before.value, after.value, changes)
+        }
+        (before, after) if before != after => {
+            changes.push(CollectionChange::Changed {
+                path,
+                before,
+                after,
+            })
+        }
+        _ => {}
+    }
+}
+
+/// Append a mapping key to a path
+pub(super) fn child_path(parent: &str, key: &Value) -> String {
+    let key = match key {
+        Value::String(key) => key.clone(),
+        other => serde_yaml::to_string(other)
+            .unwrap_or_default()
+            .trim()
+            .to_owned(),
+    };
+    if parent.is_empty() {
+        key
+    } else {
+        format!("{parent}.{key}")
+    }
+}
+
+impl Display for CollectionChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added { path, value } => {
+                write!(f, "+ {path}{}", DisplayScalar(value))
+            }
+            Self::Removed { path, value } => {
+                write!(f, "- {path}{}", DisplayScalar(value))
+            }
+            Self::Changed {
+                path,
+                before,
+                after,
+            } => match (scalar(before), scalar(after)) {
+                (Some(before), Some(after)) => {
+                    write!(f, "~ {path}: {before} -> {after}")
+                }
+                _ => write!(f, "~ {path}"),
+            },
+        }
+    }
+}
+
+/// Display a value as `: <value>` if it's a scalar. Compound values are too
+/// large to display inline, so they're omitted.
+struct DisplayScalar<'a>(&'a Value);
+
+impl<'a> Display for DisplayScalar<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match scalar(self.0) {
+            Some(value) => write!(f, ": {value}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Format a scalar value for display. Return `None` for compound values
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some("null".into()),
+        Value::Bool(value) => Some(value.to_string()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::String(value) => Some(format!("{value:?}")),
+        Value::Sequence(_) | Value::Mapping(_) | Value::Tagged(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_yaml;
+    use rstest::rstest;
+
+    fn collection(yaml: &str) -> Collection {
+        parse_yaml(yaml.as_bytes()).unwrap()
+    }
+
+    #[rstest]
+    #[case::equal(
+        "requests: {login: !request {method: POST, url: '{{host}}/login'}}",
+        "requests: {login: !request {method: POST, url: '{{host}}/login'}}",
+        &[]
+    )]
+    #[case::changed(
+        "profiles: {local: {data: {host: 'http://localhost'}}}",
+        "profiles: {local: {data: {host: 'http://localhost:3000'}}}",
+        &[
+            "~ profiles.local.data.host: \"http://localhost\" -> \
+            \"http://localhost:3000\"",
+        ]
+    )]
+    #[case::added_removed(
+        "requests: {login: !request {method: GET, url: '{{host}}/login'}}",
+        "requests: {logout: !request {method: GET, url: '{{host}}/logout'}}",
+        &["+ requests.logout", "- requests.login"]
+    )]
+    #[case::headers(
+        "requests: {r: !request {method: GET, url: u, headers: {A: '1'}}}",
+        "requests: {r: !request {method: GET, url: u, headers: {B: '{{b}}'}}}",
+        &[
+            "+ requests.r.headers.B: \"{{b}}\"",
+            "- requests.r.headers.A: \"1\"",
+        ]
+    )]
+    #[case::tag_changed(
+        "requests: {r: !request {method: GET, url: u, body: 'raw'}}",
+        "requests: {r: !request {method: GET, url: u, body: !json {a: 1}}}",
+        &["~ requests.r.body"]
+    )]
+    fn test_diff(
+        #[case] before: &str,
+        #[case] after: &str,
+        #[case] expected: &[&str],
+    ) {
+        let changes = collection(before).diff(&collection(after)).unwrap();
+        let changes: Vec<String> =
+            changes.iter().map(ToString::to_string).collect();
+        assert_eq!(changes, expected);
+    }
+}
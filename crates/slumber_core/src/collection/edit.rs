@@ -0,0 +1,983 @@
+//! Small edits to a collection file, made from within Slumber rather than by
+//! the user in their editor
+
+use crate::{
+    collection::{
+        spans::find_line, Collection, Method, ProfileId, Recipe, RecipeBody,
+        RecipeId,
+    },
+    template::Template,
+    util::parse_yaml,
+};
+use anyhow::{anyhow, bail, Context};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{
+    value::{Tag, TaggedValue},
+    Mapping, Value,
+};
+use std::fs;
+
+/// Add an empty profile to the source text of the root collection file.
+/// `collection` is the loaded collection, including imported files, so the
+/// new ID can't collide with a profile from any file. Return the new source
+/// text.
+pub fn add_profile(
+    source: &str,
+    collection: &Collection,
+    id: &ProfileId,
+) -> anyhow::Result<String> {
+    check_new_profile_id(collection, id)?;
+    let mut profile = Mapping::new();
+    profile.insert("data".into(), Mapping::new().into());
+    insert_entry(source, "profiles", id, profile.into())
+}
+
+/// Add a copy of an existing profile to the source text of the root
+/// collection file, under a new ID. The name isn't copied, so the two can be
+/// told apart. `collection` is the loaded collection, including imported
+/// files, so a profile from any file can be copied. Return the new source
+/// text.
+pub fn clone_profile(
+    source: &str,
+    collection: &Collection,
+    from: &ProfileId,
+    id: &ProfileId,
+) -> anyhow::Result<String> {
+    if !collection.profiles.contains_key(from) {
+        bail!("No profile with ID `{from}`");
+    }
+    check_new_profile_id(collection, id)?;
+    // Copy the parsed YAML rather than re-serializing the profile, so we get
+    // exactly what the user wrote (minus comments and aliases). Check the root
+    // file first, then each imported file.
+    let get_profile = |source: &[u8]| -> anyhow::Result<Option<Value>> {
+        let value: Value = parse_yaml(source)?;
+        Ok(value
+            .get("profiles")
+            .and_then(|profiles| profiles.get(from.as_str()))
+            .cloned())
+    };
+    let mut profile = get_profile(source.as_bytes())?;
+    for path in &collection.imports {
+        if profile.is_some() {
+            break;
+        }
+        let imported = fs::read(path)
+            .with_context(|| format!("Error reading {path:?}"))?;
+        profile = get_profile(&imported)?;
+    }
+    let mut profile =
+        profile.ok_or_else(|| anyhow!("No profile with ID `{from}`"))?;
+    if let Value::Mapping(profile) = &mut profile {
+        profile.remove("name");
+    }
+    insert_entry(source, "profiles", id, profile)
+}
+
+/// The fields of a recipe that can be edited from within Slumber. Anything
+/// else has to be edited in the collection file directly.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecipeFields {
+    pub name: Option<String>,
+    pub method: Method,
+    /// Template source for the URL
+    pub url: String,
+    /// Template source for each header value
+    pub headers: IndexMap<String, String>,
+    /// Template source for a plain text body. Structured bodies (e.g. `!json`)
+    /// aren't editable, and are left as-is when editing a recipe.
+    pub body: Option<String>,
+}
+
+impl RecipeFields {
+    /// Get the editable fields of an existing recipe. If the body can't be
+    /// edited as text (see [Self::is_body_editable]), it's omitted.
+    pub fn from_recipe(recipe: &Recipe) -> Self {
+        Self {
+            name: recipe.name.clone(),
+            method: recipe.method,
+            url: recipe.url.display().into_owned(),
+            headers: recipe
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    (name.clone(), value.display().into_owned())
+                })
+                .collect(),
+            body: match &recipe.body {
+                Some(RecipeBody::Raw {
+                    body,
+                    content_type: None,
+                }) => Some(body.display().into_owned()),
+                _ => None,
+            },
+        }
+    }
+
+    /// Build a standalone recipe from these fields, e.g. to send a one-off
+    /// request that isn't in the collection. Return an error if any of the
+    /// templates are invalid.
+    pub fn to_recipe(&self, id: RecipeId) -> anyhow::Result<Recipe> {
+        fn parse(field: &str, template: &str) -> anyhow::Result<Template> {
+            template
+                .parse()
+                .with_context(|| format!("Invalid template for {field}"))
+        }
+
+        Ok(Recipe {
+            id,
+            name: self.name.clone(),
+            method: self.method,
+            url: parse("URL", &self.url)?,
+            body: self
+                .body
+                .as_deref()
+                .map(|body| {
+                    anyhow::Ok(RecipeBody::Raw {
+                        body: parse("body", body)?,
+                        content_type: None,
+                    })
+                })
+                .transpose()?,
+            authentication: None,
+            signature: None,
+            certificate: None,
+            danger_skip_tls_verify: false,
+            baseline_ignore: vec![],
+            assertions: vec![],
+            display_query: None,
+            example: None,
+            parameters: IndexMap::new(),
+            query: vec![],
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    Ok((
+                        name.clone(),
+                        parse(&format!("header `{name}`"), value)?,
+                    ))
+                })
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    /// Can this recipe's body be edited as plain text?
+    pub fn is_body_editable(recipe: &Recipe) -> bool {
+        matches!(
+            recipe.body,
+            None | Some(RecipeBody::Raw {
+                content_type: None,
+                ..
+            })
+        )
+    }
+
+    /// Write these fields into the YAML mapping for a recipe. Optional fields
+    /// that are empty are removed.
+    fn apply(&self, recipe: &mut Mapping) {
+        fn set(recipe: &mut Mapping, key: &str, value: Option<Value>) {
+            match value {
+                Some(value) => {
+                    recipe.insert(key.into(), value);
+                }
+                None => {
+                    recipe.remove(key);
+                }
+            }
+        }
+
+        set(recipe, "name", self.name.clone().map(Value::from));
+        set(recipe, "method", Some(self.method.to_string().into()));
+        set(recipe, "url", Some(self.url.clone().into()));
+        let headers: Mapping = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str().into(), value.as_str().into()))
+            .collect();
+        set(
+            recipe,
+            "headers",
+            (!headers.is_empty()).then(|| headers.into()),
+        );
+        // Structured bodies are tagged or mappings; leave them alone
+        if matches!(
+            recipe.get("body"),
+            None | Some(Value::Null | Value::String(_))
+        ) {
+            set(recipe, "body", self.body.clone().map(Value::from));
+        }
+    }
+}
+
+/// Add a recipe to the end of the top-level `requests` section of the source
+/// text of the root collection file. `collection` is the loaded collection,
+/// including imported files, so the new ID can't collide with a recipe from
+/// any file. Return the new source text.
+pub fn add_recipe(
+    source: &str,
+    collection: &Collection,
+    id: &RecipeId,
+    fields: &RecipeFields,
+) -> anyhow::Result<String> {
+    check_new_recipe_id(collection, id)?;
+    let mut recipe = Mapping::new();
+    fields.apply(&mut recipe);
+    insert_entry(source, "requests", id, tag_recipe(recipe))
+}
+
+/// Add a recipe parsed from a curl command (see [Recipe::from_curl]) to the
+/// end of the top-level `requests` section of the source text of the root
+/// collection file. If no ID is given, the generated one is used, with a
+/// numeric suffix if it's already taken. `collection` is the loaded
+/// collection, including imported files, so the ID can't collide with a
+/// recipe from any file. Return the new source text and the recipe's ID.
+pub fn import_curl(
+    source: &str,
+    collection: &Collection,
+    command: &str,
+    id: Option<RecipeId>,
+) -> anyhow::Result<(String, RecipeId)> {
+    let mut recipe = Recipe::from_curl(command)?;
+    recipe.id = match id {
+        Some(id) => {
+            check_new_recipe_id(collection, &id)?;
+            id
+        }
+        None => (1..)
+            .map(|i| match i {
+                1 => recipe.id.clone(),
+                _ => format!("{}_{i}", recipe.id).into(),
+            })
+            .find(|id| collection.recipes.get(id).is_none())
+            .expect("Infinite iterator"),
+    };
+
+    let Value::Mapping(mut mapping) = serde_yaml::to_value(&recipe)? else {
+        bail!("Recipe did not serialize to a mapping");
+    };
+    // Leave out everything that's unset, to match what a person would write
+    mapping.retain(|_, value| match value {
+        Value::Null | Value::Bool(false) => false,
+        Value::Mapping(mapping) => !mapping.is_empty(),
+        Value::Sequence(sequence) => !sequence.is_empty(),
+        _ => true,
+    });
+    let source =
+        insert_entry(source, "requests", &recipe.id, tag_recipe(mapping))?;
+    Ok((source, recipe.id))
+}
+
+/// Update the simple fields of an existing recipe in the source text of a
+/// collection file. `collection` is the loaded collection, including imported
+/// files, because a recipe can extend a recipe from another file. Return the
+/// new source text.
+///
+/// The recipe is re-serialized in place, so comments and formatting *within*
+/// the recipe are lost, but the rest of the file is left alone. Fields that
+/// aren't covered by [RecipeFields] are retained. If the recipe can't be
+/// found in the text (e.g. it's defined in flow style or in an imported
+/// file), return an error and leave the user to do it by hand.
+pub fn edit_recipe(
+    source: &str,
+    collection: &Collection,
+    id: &RecipeId,
+    fields: &RecipeFields,
+) -> anyhow::Result<String> {
+    collection.recipes.try_get_recipe(id)?;
+    let key_path = collection
+        .recipes
+        .get_lookup_key(id)
+        .map(|key| key.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|id| ["requests", id.as_str()])
+        .collect_vec();
+    let error = || {
+        anyhow!(
+            "Unable to edit recipe `{id}`; \
+            the collection file must be edited manually"
+        )
+    };
+
+    let before: Value = parse_yaml(source.as_bytes())?;
+    let mut expected = before.clone();
+    let recipe = key_path
+        .iter()
+        .try_fold(&mut expected, |value, key| {
+            // Folders and recipes are tagged, so look inside the tag
+            let value = match value {
+                Value::Tagged(tagged) => &mut tagged.value,
+                value => value,
+            };
+            value.get_mut(key)
+        })
+        .ok_or_else(error)?;
+    let Value::Tagged(tagged) = &mut *recipe else {
+        return Err(error());
+    };
+    let Value::Mapping(mapping) = &mut tagged.value else {
+        return Err(error());
+    };
+    fields.apply(mapping);
+    let recipe = recipe.clone();
+
+    // Replace the recipe's block in the text with the new version
+    let line = find_line(source, &key_path).ok_or_else(error)?;
+    let (start, end, indent) = find_block(source, line);
+    let mut entry = Mapping::new();
+    entry.insert(id.as_str().into(), recipe);
+    let entry = serde_yaml::to_string(&entry)?;
+    let mut edited = String::with_capacity(source.len() + entry.len());
+    edited.push_str(&source[..start]);
+    push_indented(&mut edited, &entry, indent);
+    edited.push_str(&source[end..]);
+
+    let after: Option<Value> = parse_yaml(edited.as_bytes()).ok();
+    if after.as_ref() == Some(&expected) {
+        Ok(edited)
+    } else {
+        Err(error())
+    }
+}
+
+/// Wrap the YAML for a recipe in its `!request` tag
+fn tag_recipe(recipe: Mapping) -> Value {
+    Value::Tagged(Box::new(TaggedValue {
+        tag: Tag::new("request"),
+        value: recipe.into(),
+    }))
+}
+
+fn check_new_recipe_id(
+    collection: &Collection,
+    id: &RecipeId,
+) -> anyhow::Result<()> {
+    if id.is_empty() {
+        bail!("Recipe ID cannot be empty");
+    }
+    if collection.recipes.get(id).is_some() {
+        bail!("Recipe `{id}` already exists");
+    }
+    Ok(())
+}
+
+fn check_new_profile_id(
+    collection: &Collection,
+    id: &ProfileId,
+) -> anyhow::Result<()> {
+    if id.is_empty() {
+        bail!("Profile ID cannot be empty");
+    }
+    if collection.profiles.contains_key(id) {
+        bail!("Profile `{id}` already exists");
+    }
+    Ok(())
+}
+
+/// Append an entry to the end of a top-level map in the source text,
+/// creating the map if it isn't present. The rest of the file is left alone,
+/// so comments and formatting are retained.
+///
+/// The edit is checked by parsing the new source text. If it doesn't make
+/// exactly the expected change (e.g. because the map is written in flow
+/// style), return an error and leave the user to do it by hand.
+fn insert_entry(
+    source: &str,
+    section: &str,
+    key: &str,
+    value: Value,
+) -> anyhow::Result<String> {
+    let mut expected: Value = parse_yaml(source.as_bytes())?;
+    // An empty file parses as null
+    if expected.is_null() {
+        expected = Mapping::new().into();
+    }
+    if let Value::Mapping(root) = &mut expected {
+        let entries = root
+            .entry(section.into())
+            .or_insert(Value::Mapping(Mapping::new()));
+        if entries.is_null() {
+            *entries = Mapping::new().into();
+        }
+        if let Value::Mapping(entries) = entries {
+            entries.insert(key.into(), value.clone());
+        }
+    }
+
+    let mut entry = Mapping::new();
+    entry.insert(key.into(), value);
+    let entry = serde_yaml::to_string(&entry)?;
+
+    let mut edited = String::with_capacity(source.len() + entry.len());
+    match find_section(source, section) {
+        Some((offset, indent)) => {
+            edited.push_str(&source[..offset]);
+            if !edited.is_empty() && !edited.ends_with('\n') {
+                edited.push('\n');
+            }
+            push_indented(&mut edited, &entry, indent);
+            edited.push_str(&source[offset..]);
+        }
+        None => {
+            edited.push_str(source);
+            if !edited.is_empty() && !edited.ends_with('\n') {
+                edited.push('\n');
+            }
+            edited.push_str(section);
+            edited.push_str(":\n");
+            push_indented(&mut edited, &entry, "  ");
+        }
+    }
+
+    let after: Option<Value> = parse_yaml(edited.as_bytes()).ok();
+    if after.as_ref() == Some(&expected) {
+        Ok(edited)
+    } else {
+        Err(anyhow!(
+            "Unable to add `{key}` to `{section}`; \
+            the collection file must be edited manually"
+        ))
+    }
+}
+
+/// Find a top-level key in the source text. Return the offset just after the
+/// last line of its block, where a new entry can be inserted, and the
+/// indentation of its entries. `None` if the key isn't present.
+fn find_section<'a>(
+    source: &'a str,
+    section: &str,
+) -> Option<(usize, &'a str)> {
+    let mut lines = source.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line))
+    });
+
+    let (start, header) = lines
+        .by_ref()
+        .find(|(_, line)| line.starts_with(&format!("{section}:")))?;
+    let mut end = start + header.len();
+    let mut indent = None;
+    for (offset, line) in lines {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') {
+            // Blank lines and comments don't end the block, but a new entry
+            // shouldn't be inserted after them either
+            continue;
+        }
+        if content.len() == line.len() {
+            // Next top-level key
+            break;
+        }
+        indent.get_or_insert(&line[..line.len() - content.len()]);
+        end = offset + line.len();
+    }
+    Some((end, indent.unwrap_or("  ")))
+}
+
+/// Find the block of text for the map entry whose key is on the given line
+/// (starting at 1). Return the start and end offsets of the block, and the
+/// indentation of the key. Trailing blank lines and comments are excluded, so
+/// they stay put if the block is replaced.
+fn find_block(source: &str, line: usize) -> (usize, usize, &str) {
+    let mut lines = source.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line))
+    });
+    let (start, header) = lines.nth(line - 1).unwrap_or((source.len(), ""));
+    let indent = &header[..header.len() - header.trim_start().len()];
+    let mut end = start + header.len();
+    for (offset, line) in lines {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        if line.len() - content.len() <= indent.len() {
+            break;
+        }
+        end = offset + line.len();
+    }
+    (start, end, indent)
+}
+
+/// Append text to a string, with each non-empty line indented
+fn push_indented(dest: &mut String, text: &str, indent: &str) {
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            dest.push_str(line);
+        } else {
+            dest.push_str(indent);
+            dest.push_str(line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_err,
+        collection::imports::load_with_imports,
+        http::content_type::ContentType,
+        test_util::{temp_dir, TempDir},
+    };
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use std::path::PathBuf;
+
+    const COLLECTION: &str = r#"profiles:
+  # Local dev server
+  local:
+    name: Local
+    data:
+      host: http://localhost:3000
+
+# Shared chains
+chains:
+  token:
+    source: !file
+      path: token.txt
+
+requests:
+  login: !request
+    method: POST
+    url: '{{host}}/login'
+"#;
+
+    /// Parse the collection's source, for ID checks
+    fn collection(source: &str) -> Collection {
+        parse_yaml(source.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_add_profile() {
+        let edited =
+            add_profile(COLLECTION, &collection(COLLECTION), &"staging".into())
+                .unwrap();
+        assert_eq!(
+            edited,
+            COLLECTION.replace("3000\n", "3000\n  staging:\n    data: {}\n")
+        );
+    }
+
+    #[test]
+    fn test_clone_profile() {
+        let edited = clone_profile(
+            COLLECTION,
+            &collection(COLLECTION),
+            &"local".into(),
+            &"local2".into(),
+        )
+        .unwrap();
+        assert_eq!(
+            edited,
+            COLLECTION.replace(
+                "3000\n",
+                "3000\n  local2:\n    data:\n      \
+                host: http://localhost:3000\n"
+            )
+        );
+        let collection: Collection = parse_yaml(edited.as_bytes()).unwrap();
+        assert_eq!(
+            collection
+                .profiles
+                .keys()
+                .map(|id| id.as_str())
+                .collect::<Vec<_>>(),
+            ["local", "local2"]
+        );
+    }
+
+    /// Entries match the indentation of the rest of the map, and the map is
+    /// created if missing
+    #[rstest]
+    #[case::indent(
+        "profiles:\n    local:\n        data: {}\n",
+        "profiles:\n    local:\n        data: {}\n    new:\n      data: {}\n"
+    )]
+    #[case::empty("profiles:\n", "profiles:\n  new:\n    data: {}\n")]
+    #[case::missing(
+        "requests: {}",
+        "requests: {}\nprofiles:\n  new:\n    data: {}\n"
+    )]
+    #[case::last_line(
+        "profiles:\n  local:\n    data: {}",
+        "profiles:\n  local:\n    data: {}\n  new:\n    data: {}\n"
+    )]
+    fn test_add_profile_layout(#[case] source: &str, #[case] expected: &str) {
+        assert_eq!(
+            add_profile(source, &collection(source), &"new".into()).unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::exists(None, "local", "Profile `local` already exists")]
+    #[case::empty(None, "", "Profile ID cannot be empty")]
+    #[case::clone_exists(
+        Some("local"),
+        "local",
+        "Profile `local` already exists"
+    )]
+    #[case::unknown(Some("unknown"), "new", "No profile with ID `unknown`")]
+    fn test_profile_error(
+        #[case] from: Option<&str>,
+        #[case] id: &str,
+        #[case] expected_error: &str,
+    ) {
+        let collection = collection(COLLECTION);
+        let result = match from {
+            Some(from) => {
+                clone_profile(COLLECTION, &collection, &from.into(), &id.into())
+            }
+            None => add_profile(COLLECTION, &collection, &id.into()),
+        };
+        assert_err!(result, expected_error);
+    }
+
+    /// Profiles from imported files can be cloned into the root file, and new
+    /// IDs can't collide with them
+    #[rstest]
+    fn test_profile_imports(temp_dir: TempDir) {
+        let root = temp_dir.join("slumber.yml");
+        fs::write(&root, "imports: [other.yml]\n").unwrap();
+        fs::write(
+            temp_dir.join("other.yml"),
+            "profiles:\n  staging:\n    name: Staging\n    data:\n      \
+            host: https://staging\n",
+        )
+        .unwrap();
+        let collection = load_with_imports(&root).unwrap();
+        let source = fs::read_to_string(&root).unwrap();
+
+        let edited = clone_profile(
+            &source,
+            &collection,
+            &"staging".into(),
+            &"staging2".into(),
+        )
+        .unwrap();
+        assert_eq!(
+            edited,
+            "imports: [other.yml]\nprofiles:\n  staging2:\n    data:\n      \
+            host: https://staging\n"
+        );
+        assert_err!(
+            add_profile(&source, &collection, &"staging".into()),
+            "Profile `staging` already exists"
+        );
+        assert_err!(
+            clone_profile(
+                &source,
+                &collection,
+                &"staging".into(),
+                &"staging".into()
+            ),
+            "Profile `staging` already exists"
+        );
+    }
+
+    fn fields() -> RecipeFields {
+        RecipeFields {
+            name: Some("Get Users".into()),
+            method: Method::Get,
+            url: "{{host}}/users".into(),
+            headers: [("accept".into(), "application/json".into())]
+                .into_iter()
+                .collect(),
+            body: None,
+        }
+    }
+
+    /// New recipe is appended, and the rest of the file is untouched
+    #[test]
+    fn test_add_recipe() {
+        let edited = add_recipe(
+            COLLECTION,
+            &collection(COLLECTION),
+            &"users".into(),
+            &fields(),
+        )
+        .unwrap();
+        assert!(edited.starts_with(COLLECTION), "{edited}");
+        let collection: Collection = parse_yaml(edited.as_bytes()).unwrap();
+        let recipe =
+            collection.recipes.try_get_recipe(&"users".into()).unwrap();
+        assert_eq!(recipe.name.as_deref(), Some("Get Users"));
+        assert_eq!(recipe.url.display(), "{{host}}/users");
+        assert_eq!(recipe.headers["accept"].display(), "application/json");
+    }
+
+    /// A recipe can be added to an empty file
+    #[test]
+    fn test_add_recipe_empty() {
+        let edited =
+            add_recipe("", &collection(""), &"users".into(), &fields())
+                .unwrap();
+        assert!(edited.starts_with("requests:\n  users: !request\n"));
+        let collection: Collection = parse_yaml(edited.as_bytes()).unwrap();
+        assert!(collection.recipes.get(&"users".into()).is_some());
+    }
+
+    #[rstest]
+    #[case::exists("login", "Recipe `login` already exists")]
+    #[case::empty("", "Recipe ID cannot be empty")]
+    fn test_add_recipe_error(#[case] id: &str, #[case] expected_error: &str) {
+        assert_err!(
+            add_recipe(
+                COLLECTION,
+                &collection(COLLECTION),
+                &id.into(),
+                &fields()
+            ),
+            expected_error
+        );
+    }
+
+    /// Imported recipe is appended with only the fields that are set, and a
+    /// generated ID doesn't clobber an existing recipe
+    #[test]
+    fn test_import_curl() {
+        let command =
+            "curl -H 'Accept: application/json' https://example.com/users";
+        let (edited, id) =
+            import_curl(COLLECTION, &collection(COLLECTION), command, None)
+                .unwrap();
+        assert_eq!(id, RecipeId::from("get_users"));
+        assert_eq!(
+            edited,
+            format!(
+                "{COLLECTION}  get_users: !request
+    method: GET
+    url: https://example.com/users
+    headers:
+      Accept: application/json
+"
+            )
+        );
+
+        let (edited, id) =
+            import_curl(&edited, &collection(&edited), command, None).unwrap();
+        assert_eq!(id, RecipeId::from("get_users_2"));
+        let collection: Collection = parse_yaml(edited.as_bytes()).unwrap();
+        assert!(collection.recipes.get(&id).is_some());
+    }
+
+    /// A recipe that extends a recipe from an imported file can be edited
+    #[rstest]
+    fn test_edit_recipe_imports(temp_dir: TempDir) {
+        let (root, source, _) = imported_recipe(&temp_dir);
+        let source = format!(
+            "{source}requests:\n  get_user: !request\n    \
+            extends: get_users\n"
+        );
+        fs::write(&root, &source).unwrap();
+        let collection = load_with_imports(&root).unwrap();
+
+        let edited =
+            edit_recipe(&source, &collection, &"get_user".into(), &fields())
+                .unwrap();
+        fs::write(&root, edited).unwrap();
+        let collection = load_with_imports(&root).unwrap();
+        let recipe = collection
+            .recipes
+            .try_get_recipe(&"get_user".into())
+            .unwrap();
+        assert_eq!(recipe.name.as_deref(), Some("Get Users"));
+    }
+
+    #[rstest]
+    #[case::exists(
+        "curl https://example.com",
+        Some("login"),
+        "Recipe `login` already exists"
+    )]
+    #[case::invalid_command(
+        "curl --proxy localhost https://example.com",
+        None,
+        "Unsupported option `--proxy`"
+    )]
+    fn test_import_curl_error(
+        #[case] command: &str,
+        #[case] id: Option<&str>,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(
+            import_curl(
+                COLLECTION,
+                &collection(COLLECTION),
+                command,
+                id.map(RecipeId::from)
+            ),
+            expected_error
+        );
+    }
+
+    /// Write a root file that imports a file defining `get_users`, and load
+    /// them both
+    fn imported_recipe(temp_dir: &TempDir) -> (PathBuf, String, Collection) {
+        let root = temp_dir.join("slumber.yml");
+        fs::write(&root, "imports: [other.yml]\n").unwrap();
+        fs::write(
+            temp_dir.join("other.yml"),
+            "requests:\n  get_users: !request\n    method: GET\n    \
+            url: https://example.com/users\n",
+        )
+        .unwrap();
+        let collection = load_with_imports(&root).unwrap();
+        let source = fs::read_to_string(&root).unwrap();
+        (root, source, collection)
+    }
+
+    /// New recipe IDs can't collide with recipes from imported files
+    #[rstest]
+    fn test_add_recipe_imports(temp_dir: TempDir) {
+        let (_, source, collection) = imported_recipe(&temp_dir);
+        assert_err!(
+            add_recipe(&source, &collection, &"get_users".into(), &fields()),
+            "Recipe `get_users` already exists"
+        );
+    }
+
+    /// Imported IDs can't collide with recipes from imported files, whether
+    /// they're given explicitly or generated
+    #[rstest]
+    fn test_import_curl_imports(temp_dir: TempDir) {
+        let (root, source, collection) = imported_recipe(&temp_dir);
+        let command = "curl https://example.com/users";
+        assert_err!(
+            import_curl(
+                &source,
+                &collection,
+                command,
+                Some("get_users".into())
+            ),
+            "Recipe `get_users` already exists"
+        );
+
+        let (edited, id) =
+            import_curl(&source, &collection, command, None).unwrap();
+        assert_eq!(id, RecipeId::from("get_users_2"));
+        // The edited collection still loads with its imports
+        fs::write(&root, edited).unwrap();
+        let collection = load_with_imports(&root).unwrap();
+        assert!(collection.recipes.get(&id).is_some());
+    }
+
+    /// Edit a recipe within a folder. Fields that can't be edited here are
+    /// retained, and so is everything outside the recipe
+    #[test]
+    fn test_edit_recipe() {
+        let source = r#"requests:
+  # Users
+  users: !folder
+    requests:
+      list_users: !request
+        method: GET
+        url: "{{host}}/users"
+        body: !json {"a": 1}
+        query:
+          - limit=10
+
+      # Other recipes
+      get_user: !request
+        method: GET
+        url: "{{host}}/users/1"
+"#;
+        let edited = edit_recipe(
+            source,
+            &collection(source),
+            &"list_users".into(),
+            &RecipeFields {
+                body: Some("ignored".into()),
+                ..fields()
+            },
+        )
+        .unwrap();
+        assert!(edited.starts_with("requests:\n  # Users\n"), "{edited}");
+        assert!(
+            edited.ends_with(
+                "\n      # Other recipes\n      get_user: !request\n        \
+                method: GET\n        url: \"{{host}}/users/1\"\n"
+            ),
+            "{edited}"
+        );
+
+        let collection: Collection = parse_yaml(edited.as_bytes()).unwrap();
+        let recipe = collection
+            .recipes
+            .try_get_recipe(&"list_users".into())
+            .unwrap();
+        assert_eq!(recipe.name.as_deref(), Some("Get Users"));
+        assert_eq!(recipe.headers["accept"].display(), "application/json");
+        // Structured body isn't touched
+        assert!(
+            matches!(
+                recipe.body,
+                Some(RecipeBody::Raw {
+                    content_type: Some(ContentType::Json),
+                    ..
+                })
+            ),
+            "{:?}",
+            recipe.body
+        );
+        assert_eq!(recipe.query.len(), 1);
+    }
+
+    #[test]
+    fn test_edit_recipe_unknown() {
+        assert_err!(
+            edit_recipe(
+                COLLECTION,
+                &collection(COLLECTION),
+                &"unknown".into(),
+                &fields()
+            ),
+            "No recipe with ID `unknown`"
+        );
+    }
+
+    /// Build a standalone recipe, parsing each template
+    #[test]
+    fn test_to_recipe() {
+        let recipe = RecipeFields {
+            body: Some("{{body}}".into()),
+            ..fields()
+        }
+        .to_recipe(RecipeId::scratch())
+        .unwrap();
+        assert_eq!(recipe.id, RecipeId::scratch());
+        assert_eq!(recipe.url, "{{host}}/users".into());
+        assert_eq!(recipe.headers["accept"], "application/json".into());
+        assert_eq!(recipe.body, Some("{{body}}".into()));
+    }
+
+    #[test]
+    fn test_to_recipe_error() {
+        let fields = RecipeFields {
+            url: "{{host".into(),
+            ..fields()
+        };
+        assert_err!(
+            fields.to_recipe(RecipeId::scratch()),
+            "Invalid template for URL"
+        );
+    }
+
+    /// Flow-style maps can't be edited as text, so the edit fails instead of
+    /// breaking the file
+    #[test]
+    fn test_add_profile_flow() {
+        let source = "profiles: {local: {data: {}}}\n";
+        assert_err!(
+            add_profile(source, &collection(source), &"new".into()),
+            "Unable to add `new` to `profiles`; \
+            the collection file must be edited manually"
+        );
+    }
+}
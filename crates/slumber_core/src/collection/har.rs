@@ -0,0 +1,261 @@
+//! Import requests captured in a HAR file, e.g. from a browser's dev tools.
+//! The requests can be turned into recipes, inserted into request history, or
+//! both. Either way they get the same recipe IDs, so history lines up with the
+//! generated recipes.
+
+use crate::{
+    collection::{
+        Collection, Method, Recipe, RecipeBody, RecipeId, RecipeNode,
+        RecipeTree,
+    },
+    http::{export, Exchange, RequestRecord},
+    template::Template,
+};
+use anyhow::{anyhow, Context};
+use indexmap::IndexMap;
+use reqwest::header;
+use std::{fs::File, io::BufReader, path::Path};
+use tracing::{info, warn};
+
+/// Headers that are set by the HTTP client or browser rather than being part
+/// of the request's definition, so they're left out of generated recipes
+const IGNORED_HEADERS: &[&str] =
+    &["accept-encoding", "connection", "content-length", "host"];
+
+/// Requests loaded from a HAR file
+#[derive(Debug)]
+pub struct HarImport {
+    /// Every entry that got a response, in the order they were sent
+    pub exchanges: Vec<Exchange>,
+}
+
+impl HarImport {
+    /// Load requests from a HAR file. Requests to the same endpoint (method
+    /// and URL, ignoring the query string) are given the same recipe ID,
+    /// generated from the method and URL. Entries exported by Slumber keep
+    /// their original recipe IDs.
+    ///
+    /// This is not async because it's only called by the CLI, where we don't
+    /// care about blocking. It keeps the code simpler.
+    pub fn load(har_file: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = har_file.as_ref();
+        info!(file = ?path, "Loading HAR file");
+        let file = File::open(path)
+            .with_context(|| format!("Error opening HAR file {path:?}"))?;
+
+        let mut endpoints: IndexMap<(reqwest::Method, String), RecipeId> =
+            IndexMap::new();
+        let exchanges =
+            export::read_foreign_har(BufReader::new(file), |method, url| {
+                let mut endpoint = url.clone();
+                endpoint.set_query(None);
+                endpoint.set_fragment(None);
+                let key = (method.clone(), endpoint.to_string());
+                if let Some(id) = endpoints.get(&key) {
+                    return Ok(id.clone());
+                }
+
+                let method: Method = method.as_str().parse().map_err(|_| {
+                    anyhow!("Unsupported HTTP method `{method}`")
+                })?;
+                let base = RecipeId::generate(method, &key.1);
+                // Different endpoints can generate the same ID, e.g. GET
+                // /users and GET /admin/users
+                let id = (1..)
+                    .map(|i| match i {
+                        1 => base.clone(),
+                        _ => format!("{base}_{i}").into(),
+                    })
+                    .find(|id| !endpoints.values().any(|other| other == id))
+                    .expect("Infinite iterator");
+                endpoints.insert(key, id.clone());
+                Ok(id)
+            })?;
+        Ok(Self { exchanges })
+    }
+
+    /// Generate a collection with a recipe for each endpoint. Each recipe is
+    /// based on the first request to its endpoint.
+    pub fn to_collection(&self) -> anyhow::Result<Collection> {
+        let mut recipes: IndexMap<RecipeId, RecipeNode> = IndexMap::new();
+        for exchange in &self.exchanges {
+            let request = &exchange.request;
+            if !recipes.contains_key(&request.recipe_id) {
+                recipes.insert(
+                    request.recipe_id.clone(),
+                    RecipeNode::Recipe(build_recipe(request)?),
+                );
+            }
+        }
+        Ok(Collection {
+            recipes: RecipeTree::new(recipes)?,
+            ..Collection::default()
+        })
+    }
+}
+
+/// Convert a recorded request back into a recipe. All values are imported
+/// as-is, so nothing is interpreted as a template.
+fn build_recipe(request: &RequestRecord) -> anyhow::Result<Recipe> {
+    let method: Method =
+        request.method.as_str().parse().map_err(|_| {
+            anyhow!("Unsupported HTTP method `{}`", request.method)
+        })?;
+    let mut url = request.url.clone();
+    let query = url
+        .query_pairs()
+        .map(|(name, value)| {
+            (name.into_owned(), Template::raw(value.into_owned()))
+        })
+        .collect();
+    url.set_query(None);
+    url.set_fragment(None);
+
+    let mut headers: IndexMap<String, Template> = request
+        .headers
+        .iter()
+        .filter(|(name, _)| {
+            // Browsers send a bunch of fetch metadata headers, e.g.
+            // `sec-fetch-mode`, that don't mean anything outside the browser
+            !IGNORED_HEADERS.contains(&name.as_str())
+                && !name.as_str().starts_with("sec-")
+        })
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.to_string(), Template::raw(value))
+        })
+        .collect();
+
+    let body = match request.body.as_deref().map(std::str::from_utf8) {
+        None => None,
+        Some(Ok("")) => None,
+        Some(Ok(text)) => {
+            let content_type = request
+                .headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            let (body, is_structured) =
+                RecipeBody::untemplated_text(content_type, text.to_owned());
+            if is_structured {
+                headers.shift_remove(header::CONTENT_TYPE.as_str());
+            }
+            Some(body)
+        }
+        // Binary bodies can't be written into a collection file
+        Some(Err(_)) => {
+            warn!(
+                recipe = %request.recipe_id,
+                "Skipping binary request body"
+            );
+            None
+        }
+    };
+
+    Ok(Recipe {
+        id: request.recipe_id.clone(),
+        name: None,
+        method,
+        url: Template::raw(url.to_string()),
+        body,
+        authentication: None,
+        signature: None,
+        certificate: None,
+        danger_skip_tls_verify: false,
+        baseline_ignore: vec![],
+        assertions: vec![],
+        display_query: None,
+        example: None,
+        query,
+        headers,
+        parameters: IndexMap::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collection::CollectionFile, http::ExchangeTiming,
+        test_util::test_data_dir,
+    };
+    use itertools::Itertools;
+    use pretty_assertions::assert_eq;
+    use reqwest::StatusCode;
+    use rstest::rstest;
+    use std::{path::PathBuf, time::Duration};
+
+    const HAR_FILE: &str = "browser.har";
+    /// Expected collection is stored in a separate file, same as the other
+    /// importers
+    const HAR_IMPORTED_FILE: &str = "browser_har_imported.yml";
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_har_import_recipes(test_data_dir: PathBuf) {
+        let imported = HarImport::load(test_data_dir.join(HAR_FILE))
+            .unwrap()
+            .to_collection()
+            .unwrap();
+        let expected =
+            CollectionFile::load(test_data_dir.join(HAR_IMPORTED_FILE))
+                .await
+                .unwrap()
+                .collection;
+        assert_eq!(imported, *expected);
+    }
+
+    /// Requests to the same endpoint share a recipe, blocked requests are
+    /// skipped, and timings are converted
+    #[rstest]
+    fn test_har_import_history(test_data_dir: PathBuf) {
+        let exchanges = HarImport::load(test_data_dir.join(HAR_FILE))
+            .unwrap()
+            .exchanges;
+        assert_eq!(
+            exchanges
+                .iter()
+                .map(|exchange| exchange.request.recipe_id.as_str())
+                .collect_vec(),
+            ["get_users", "get_users", "post_users", "get_avatars"]
+        );
+
+        let first = &exchanges[0];
+        assert_eq!(first.response.status, StatusCode::OK);
+        assert_eq!(
+            first.timing,
+            Some(ExchangeTiming {
+                dns: None,
+                connect: None,
+                tls: None,
+                wait: Duration::from_millis(22),
+                download: Duration::from_millis(3),
+            })
+        );
+        // HAR includes the TLS handshake in the connection time
+        assert_eq!(
+            exchanges[2].timing,
+            Some(ExchangeTiming {
+                dns: Some(Duration::from_millis(5)),
+                connect: Some(Duration::from_millis(10)),
+                tls: Some(Duration::from_millis(6)),
+                wait: Duration::from_millis(26),
+                download: Duration::from_millis(3),
+            })
+        );
+        // Pseudo-headers are dropped
+        assert_eq!(first.request.headers.len(), 3);
+
+        // Binary response bodies are base64 in HAR
+        assert_eq!(exchanges[3].response.body.bytes(), b"\x89PNG");
+
+        // IDs are derived from the content, so they're the same every time
+        let again = HarImport::load(test_data_dir.join(HAR_FILE))
+            .unwrap()
+            .exchanges;
+        assert_eq!(
+            exchanges.iter().map(|exchange| exchange.id).collect_vec(),
+            again.iter().map(|exchange| exchange.id).collect_vec()
+        );
+    }
+}
@@ -0,0 +1,637 @@
+//! Multi-file collections. A collection file can list other files under
+//! `imports`, and their profiles, chains, recipes, and template tests are
+//! merged into it
+
+use crate::{
+    collection::{inheritance::resolve_extends, Collection, HasId, RecipeTree},
+    util::{git, parse_yaml},
+};
+use anyhow::{anyhow, bail, Context};
+use futures::{future::BoxFuture, FutureExt};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde_yaml::Value;
+use std::{
+    fmt::Display,
+    fs::File,
+    hash::Hash,
+    io::Read,
+    mem,
+    path::{Component, Path, PathBuf},
+};
+use thiserror::Error;
+
+/// An error loading a file imported by the collection. This is a dedicated
+/// type rather than a string context, so the failing file can be pulled out of
+/// the error chain, e.g. to report the location of a syntax error.
+#[derive(Debug, Error)]
+#[error("Error importing {path:?}")]
+pub struct ImportError {
+    /// Path of the imported file
+    pub path: PathBuf,
+    #[source]
+    pub error: anyhow::Error,
+}
+
+/// Load a collection file, along with every file it imports (recursively),
+/// and merge them into a single collection. Items from imported files come
+/// after the items of the file that imports them. Each file is only loaded
+/// once, even if it's imported multiple times, so common fragments can be
+/// shared. Import cycles and IDs defined in more than one file are errors.
+pub(super) fn load_with_imports(path: &Path) -> anyhow::Result<Collection> {
+    let mut files = Vec::new();
+    collect_files(path, &mut Vec::new(), &mut files)?;
+    let collection = merge(deserialize_files(files)?)?;
+    // Profiles can extend profiles from other files, so check this once
+    // everything is merged
+    collection.validate_profile_extends()?;
+    Ok(collection)
+}
+
+/// Load a collection file as of the git HEAD commit, along with every file it
+/// imports as of that commit, and merge them like [load_with_imports]. Use
+/// this to compare the working copy of a collection against its last commit.
+pub async fn load_git_head(path: &Path) -> anyhow::Result<Collection> {
+    let mut files = Vec::new();
+    collect_head_files(normalize(path), &mut Vec::new(), &mut files).await?;
+    let collection = merge(deserialize_files(files)?)?;
+    collection.validate_profile_extends()?;
+    Ok(collection)
+}
+
+/// Load a file and everything it imports, depth-first. Each file is added to
+/// `files` before the files it imports. `stack` is the chain of imports that
+/// led to this file, for cycle detection.
+fn collect_files(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<(PathBuf, Value)>,
+) -> anyhow::Result<()> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Error opening {path:?}"))?;
+    if !check_import(&canonical, stack, files)? {
+        return Ok(());
+    }
+
+    let file = File::open(&canonical)?;
+    let (value, imports) = read_file(&file)?;
+    files.push((canonical.clone(), value));
+
+    let dir = canonical.parent().unwrap_or(Path::new("")).to_owned();
+    stack.push(canonical);
+    for import in imports {
+        let import = dir.join(import);
+        collect_files(&import, stack, files).map_err(|error| ImportError {
+            path: import,
+            error,
+        })?;
+    }
+    stack.pop();
+    Ok(())
+}
+
+/// [collect_files], but load each file from git HEAD. Files at HEAD don't
+/// necessarily exist on disk, so paths are normalized without touching the
+/// file system.
+fn collect_head_files<'a>(
+    path: PathBuf,
+    stack: &'a mut Vec<PathBuf>,
+    files: &'a mut Vec<(PathBuf, Value)>,
+) -> BoxFuture<'a, anyhow::Result<()>> {
+    async move {
+        if !check_import(&path, stack, files)? {
+            return Ok(());
+        }
+
+        let content = git::show_head(&path).await?;
+        let (value, imports) = read_file(content.as_slice())?;
+        files.push((path.clone(), value));
+
+        let dir = path.parent().unwrap_or(Path::new("")).to_owned();
+        stack.push(path);
+        for import in imports {
+            let import = normalize(&dir.join(import));
+            collect_head_files(import.clone(), stack, files)
+                .await
+                .map_err(|error| ImportError {
+                    path: import,
+                    error,
+                })?;
+        }
+        stack.pop();
+        Ok(())
+    }
+    .boxed()
+}
+
+/// Check that importing a file doesn't create a cycle. Return `false` if it's
+/// already been imported elsewhere, so it can be skipped
+fn check_import(
+    path: &Path,
+    stack: &[PathBuf],
+    files: &[(PathBuf, Value)],
+) -> anyhow::Result<bool> {
+    if let Some(start) = stack.iter().position(|p| p == path) {
+        bail!(
+            "Import cycle: {}",
+            stack[start..]
+                .iter()
+                .map(PathBuf::as_path)
+                .chain([path])
+                .map(|path| path.display())
+                .format(" -> ")
+        );
+    }
+    Ok(!files.iter().any(|(p, _)| p == path))
+}
+
+/// Parse a single collection file as YAML, without deserializing it. Return
+/// the YAML and the files it imports.
+fn read_file(reader: impl Read) -> anyhow::Result<(Value, Vec<PathBuf>)> {
+    let value: Value = parse_yaml(reader)?;
+    let imports = match value.get("imports") {
+        Some(imports) => serde_yaml::from_value(imports.clone())
+            .context("Invalid `imports`")?,
+        None => Vec::new(),
+    };
+    Ok((value, imports))
+}
+
+/// Deserialize every loaded file into a collection. Recipes can extend recipes
+/// from other files, so inheritance is resolved across all the files first.
+/// Errors in an imported file are attributed to that file.
+fn deserialize_files(
+    mut files: Vec<(PathBuf, Value)>,
+) -> anyhow::Result<Vec<(PathBuf, Collection)>> {
+    let mut trees = files
+        .iter_mut()
+        .filter_map(|(_, value)| value.get_mut("requests"))
+        .collect_vec();
+    resolve_extends(&mut trees)?;
+
+    files
+        .into_iter()
+        .enumerate()
+        .map(|(i, (path, value))| {
+            let is_root = i == 0;
+            match parse_file(value, is_root) {
+                Ok(collection) => Ok((path, collection)),
+                Err(error) if is_root => Err(error),
+                Err(error) => Err(ImportError { path, error }.into()),
+            }
+        })
+        .collect()
+}
+
+/// Deserialize a single collection file, and check that it only uses
+/// root-only fields if it's the root
+fn parse_file(value: Value, is_root: bool) -> anyhow::Result<Collection> {
+    let mut collection: Collection = serde_yaml::from_value(value)?;
+    // Imports have already been followed. The merged collection lists every
+    // imported file instead.
+    collection.imports.clear();
+    // The OpenAPI path is relative to the current directory, and there's only
+    // one per collection, so only the root file can set it
+    if !is_root && collection.openapi.is_some() {
+        bail!("`openapi` can only be set in the root collection file");
+    }
+    // Imported recipes are merged into one tree, so root defaults in an
+    // imported file would apply to everything. Folder defaults are fine.
+    if !is_root && !collection.defaults.is_empty() {
+        bail!("`defaults` can only be set in the root collection file");
+    }
+    // Environment variables are global, so they're configured in one place
+    if !is_root && !collection.environment.is_empty() {
+        bail!("`environment` can only be set in the root collection file");
+    }
+    if let Some((variable, _)) =
+        collection.environment.iter().find(|(_, options)| {
+            options.default.is_some() && options.required.is_some()
+        })
+    {
+        bail!(
+            "Environment variable `{variable}` can't have both `default` and \
+            `required`"
+        );
+    }
+    Ok(collection)
+}
+
+/// Resolve `.` and `..` in a path lexically, without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Merge loaded files into the first one. If any ID is defined in more than
+/// one file, return an error listing every collision.
+fn merge(files: Vec<(PathBuf, Collection)>) -> anyhow::Result<Collection> {
+    let mut files = files.into_iter();
+    let Some((root_path, mut collection)) = files.next() else {
+        return Ok(Collection::default());
+    };
+
+    let mut collisions = Vec::new();
+    let mut profile_origins =
+        origins(&root_path, collection.profiles.keys().cloned());
+    let mut chain_origins =
+        origins(&root_path, collection.chains.keys().cloned());
+    let mut test_origins =
+        origins(&root_path, collection.template_tests.keys().cloned());
+    let mut recipe_origins = origins(
+        &root_path,
+        collection.recipes.iter().map(|(_, node)| node.id().clone()),
+    );
+    let mut recipes = mem::take(&mut collection.recipes).into_nodes();
+
+    for (path, imported) in files {
+        check_collisions(
+            "Profile",
+            &path,
+            imported.profiles.keys().cloned(),
+            &mut profile_origins,
+            &mut collisions,
+        );
+        check_collisions(
+            "Chain",
+            &path,
+            imported.chains.keys().cloned(),
+            &mut chain_origins,
+            &mut collisions,
+        );
+        check_collisions(
+            "Template test",
+            &path,
+            imported.template_tests.keys().cloned(),
+            &mut test_origins,
+            &mut collisions,
+        );
+        check_collisions(
+            "Recipe/folder",
+            &path,
+            imported.recipes.iter().map(|(_, node)| node.id().clone()),
+            &mut recipe_origins,
+            &mut collisions,
+        );
+        collection.imports.push(path);
+        collection.profiles.extend(imported.profiles);
+        collection.chains.extend(imported.chains);
+        collection.template_tests.extend(imported.template_tests);
+        recipes.extend(imported.recipes.into_nodes());
+    }
+
+    if !collisions.is_empty() {
+        return Err(anyhow!(collisions.join("\n")));
+    }
+    // We already checked for duplicate IDs, so this shouldn't fail
+    collection.recipes = RecipeTree::new(recipes)?;
+    Ok(collection)
+}
+
+/// Map each ID to the file it was defined in
+fn origins<Id: Hash + Eq>(
+    path: &Path,
+    ids: impl Iterator<Item = Id>,
+) -> IndexMap<Id, PathBuf> {
+    ids.map(|id| (id, path.to_owned())).collect()
+}
+
+/// Record the origin of each ID from an imported file, and an error message
+/// for each ID that was already defined in another file
+fn check_collisions<Id: Display + Hash + Eq>(
+    kind: &str,
+    path: &Path,
+    ids: impl Iterator<Item = Id>,
+    origins: &mut IndexMap<Id, PathBuf>,
+    collisions: &mut Vec<String>,
+) {
+    for id in ids {
+        if let Some(existing) = origins.get(&id) {
+            collisions.push(format!(
+                "{kind} `{id}` is defined in both {} and {}",
+                existing.display(),
+                path.display()
+            ));
+        } else {
+            origins.insert(id, path.to_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_err,
+        collection::{ProfileId, RecipeId},
+        test_util::{temp_dir, TempDir},
+    };
+    use rstest::rstest;
+    use std::{fs, process::Command};
+
+    /// Write files into the temp directory, and return the path of the first
+    fn write_files(dir: &Path, files: &[(&str, &str)]) -> PathBuf {
+        for (name, content) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir.join(files[0].0)
+    }
+
+    /// Items from imported files are merged in, including nested and shared
+    /// imports
+    #[rstest]
+    fn test_load_with_imports(temp_dir: TempDir) {
+        let path = write_files(
+            &temp_dir,
+            &[
+                (
+                    "slumber.yml",
+                    "imports: [users/users.yml, common.yml]
+profiles:
+  local:
+    data: {}
+requests:
+  login: !request
+    method: POST
+    url: /login
+",
+                ),
+                (
+                    "users/users.yml",
+                    // Paths are relative to the importing file
+                    "imports: [../common.yml]
+requests:
+  users: !folder
+    requests:
+      get_user: !request
+        method: GET
+        url: /users/1
+",
+                ),
+                (
+                    "common.yml",
+                    "profiles:
+  staging:
+    data: {}
+chains:
+  token:
+    source: !file
+      path: token.txt
+",
+                ),
+            ],
+        );
+
+        let collection = load_with_imports(&path).unwrap();
+        assert_eq!(
+            collection.profiles.keys().collect_vec(),
+            [&ProfileId::from("local"), &ProfileId::from("staging")]
+        );
+        assert_eq!(collection.chains.len(), 1);
+        assert_eq!(
+            collection.recipes.recipe_ids().collect_vec(),
+            [&RecipeId::from("login"), &RecipeId::from("get_user")]
+        );
+        // Every imported file is recorded, once
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        assert_eq!(
+            collection.imports,
+            [
+                temp_dir.join("users/users.yml"),
+                temp_dir.join("common.yml")
+            ]
+        );
+    }
+
+    /// Imported files are loaded from HEAD too, so unchanged imported items
+    /// aren't reported as added
+    #[rstest]
+    #[tokio::test]
+    async fn test_load_git_head(temp_dir: TempDir) {
+        let path = write_files(
+            &temp_dir,
+            &[
+                ("slumber.yml", "imports: [users/users.yml]\n"),
+                (
+                    "users/users.yml",
+                    "imports: [../common.yml]
+requests:
+  get_user: !request
+    method: GET
+    url: /users/1
+",
+                ),
+                ("common.yml", "profiles: {local: {data: {}}}\n"),
+            ],
+        );
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(["-C", &temp_dir.to_string_lossy()])
+                .args(["-c", "user.name=Test", "-c", "user.email=test@test"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success());
+        };
+        git(&["init", "--quiet"]);
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "--message", "Initial"]);
+        // Uncommitted changes aren't included
+        fs::write(temp_dir.join("common.yml"), "profiles: {}\n").unwrap();
+
+        let head = load_git_head(&path).await.unwrap();
+        assert_eq!(
+            head.profiles.keys().collect_vec(),
+            [&ProfileId::from("local")]
+        );
+        assert_eq!(
+            head.recipes.recipe_ids().collect_vec(),
+            [&RecipeId::from("get_user")]
+        );
+        let current = load_with_imports(&path).unwrap();
+        let changes = head.diff(&current).unwrap();
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect_vec(),
+            ["- profiles.local"]
+        );
+    }
+
+    /// Recipes can extend recipes from other files, in either direction
+    #[rstest]
+    fn test_import_extends(temp_dir: TempDir) {
+        let path = write_files(
+            &temp_dir,
+            &[
+                (
+                    "slumber.yml",
+                    "imports: [users.yml]
+requests:
+  base: !request
+    method: GET
+    url: /users
+    headers:
+      Accept: application/json
+  get_user: !request
+    extends: list_users
+    url: /users/1
+",
+                ),
+                (
+                    "users.yml",
+                    "requests:
+  users: !folder
+    requests:
+      list_users: !request
+        extends: base
+        query:
+          - limit=10
+",
+                ),
+            ],
+        );
+
+        let collection = load_with_imports(&path).unwrap();
+        let list_users = collection
+            .recipes
+            .try_get_recipe(&"list_users".into())
+            .unwrap();
+        assert_eq!(list_users.url, "/users".into());
+        assert_eq!(list_users.query.len(), 1);
+        let get_user = collection
+            .recipes
+            .try_get_recipe(&"get_user".into())
+            .unwrap();
+        assert_eq!(get_user.method, "GET".parse().unwrap());
+        assert_eq!(get_user.url, "/users/1".into());
+        assert_eq!(get_user.query, list_users.query);
+        assert_eq!(get_user.headers, list_users.headers);
+    }
+
+    /// Deserialization errors in an imported file are blamed on that file
+    #[rstest]
+    fn test_import_invalid(temp_dir: TempDir) {
+        let path = write_files(
+            &temp_dir,
+            &[
+                (
+                    "slumber.yml",
+                    "imports: [users.yml]
+",
+                ),
+                (
+                    "users.yml",
+                    "requests:\n  get_user: !request\n    method: GET\n",
+                ),
+            ],
+        );
+        assert_err!(load_with_imports(&path), "missing field `url`");
+        let error = load_with_imports(&path).unwrap_err();
+        let error = error.downcast_ref::<ImportError>().unwrap();
+        assert!(error.path.ends_with("users.yml"));
+    }
+
+    #[rstest]
+    fn test_import_cycle(temp_dir: TempDir) {
+        let path = write_files(
+            &temp_dir,
+            &[("a.yml", "imports: [b.yml]"), ("b.yml", "imports: [a.yml]")],
+        );
+        assert_err!(load_with_imports(&path), "Import cycle: ");
+    }
+
+    /// Every ID defined in multiple files is reported
+    #[rstest]
+    fn test_import_collision(temp_dir: TempDir) {
+        let path = write_files(
+            &temp_dir,
+            &[
+                (
+                    "a.yml",
+                    "imports: [b.yml]
+profiles:
+  local:
+    data: {}
+requests:
+  users: !folder
+    requests:
+      get_user: !request
+        method: GET
+        url: /users/1
+",
+                ),
+                (
+                    "b.yml",
+                    "profiles:
+  local:
+    data: {}
+requests:
+  get_user: !request
+    method: GET
+    url: /users/2
+",
+                ),
+            ],
+        );
+        let error = load_with_imports(&path).unwrap_err().to_string();
+        let lines = error.lines().collect_vec();
+        assert_eq!(lines.len(), 2, "{error}");
+        assert!(lines[0].starts_with("Profile `local` is defined in both"));
+        assert!(
+            lines[1].starts_with("Recipe/folder `get_user` is defined in both")
+        );
+    }
+
+    #[rstest]
+    #[case::missing("imports: [missing.yml]", "Error importing")]
+    #[case::invalid_imports("imports: missing.yml", "Invalid `imports`")]
+    #[case::openapi(
+        "imports: [openapi.yml]",
+        "`openapi` can only be set in the root collection file"
+    )]
+    #[case::defaults(
+        "imports: [defaults.yml]",
+        "`defaults` can only be set in the root collection file"
+    )]
+    #[case::environment(
+        "imports: [environment.yml]",
+        "`environment` can only be set in the root collection file"
+    )]
+    #[case::environment_conflict(
+        "environment: {TOKEN: {default: abc, required: Set TOKEN}}",
+        "Environment variable `TOKEN` can't have both `default` and `required`"
+    )]
+    fn test_import_error(
+        temp_dir: TempDir,
+        #[case] root: &str,
+        #[case] expected_error: &str,
+    ) {
+        let path = write_files(
+            &temp_dir,
+            &[
+                ("slumber.yml", root),
+                ("openapi.yml", "openapi: api.yml"),
+                ("defaults.yml", "defaults: {base_url: http://localhost}"),
+                ("environment.yml", "environment: {TOKEN: {}}"),
+            ],
+        );
+        assert_err!(load_with_imports(&path), expected_error);
+    }
+}
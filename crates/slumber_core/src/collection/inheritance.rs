@@ -0,0 +1,530 @@
+//! Recipe and profile inheritance.
+//!
+//! A recipe can name another recipe under `extends`, and it inherits every
+//! field it doesn't set itself. This is resolved on the raw YAML, before the
+//! recipes are deserialized, so a child can leave out fields that are
+//! otherwise required (e.g. `method` and `url`). For multi-file collections,
+//! it's resolved across every file at once, so a recipe can extend a recipe
+//! from another file.
+//!
+//! A profile can also name another profile under `extends`, and it inherits
+//! every data field it doesn't set itself. Profiles are kept as written, and
+//! the chain is walked whenever a field is looked up, so the UI can show where
+//! each value came from.
+
+use crate::{
+    collection::{Collection, Profile, ProfileId},
+    template::Template,
+};
+use anyhow::{anyhow, bail};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+
+/// Field that names the parent recipe
+const EXTENDS: &str = "extends";
+
+/// Resolve inheritance for every recipe in a set of YAML recipe trees, one per
+/// collection file. A recipe can extend a recipe from any of the trees. Each
+/// recipe that extends another is replaced with its parent's fields, overlaid
+/// with its own.
+/// Headers and query parameters are merged by name, rather than replaced
+/// wholesale. The parent's `name` is never inherited.
+///
+/// Return an error if a recipe extends a folder or an unknown ID, or if there
+/// is a cycle.
+pub(super) fn resolve_extends(trees: &mut [&mut Value]) -> anyhow::Result<()> {
+    let mut nodes = IndexMap::new();
+    for tree in trees.iter() {
+        collect_nodes(tree, &mut nodes);
+    }
+    // Skip the second walk for the common case
+    if !nodes
+        .values()
+        .any(|node| node.is_some_and(|recipe| recipe.contains_key(EXTENDS)))
+    {
+        return Ok(());
+    }
+
+    let mut resolved = HashMap::new();
+    for (id, node) in &nodes {
+        if node.is_some() {
+            resolve(id, &nodes, &mut resolved, &mut Vec::new())?;
+        }
+    }
+    for tree in trees {
+        replace_recipes(tree, &resolved);
+    }
+    Ok(())
+}
+
+/// Find every node in the tree, recursing into folders. Recipes map to their
+/// body; folders map to `None`
+fn collect_nodes<'a>(
+    nodes: &'a Value,
+    found: &mut IndexMap<&'a str, Option<&'a Mapping>>,
+) {
+    let Value::Mapping(nodes) = nodes else {
+        return;
+    };
+    for (id, node) in nodes {
+        let (Some(id), Value::Tagged(node)) = (id.as_str(), node) else {
+            continue;
+        };
+        if node.tag == "request" {
+            if let Value::Mapping(recipe) = &node.value {
+                found.insert(id, Some(recipe));
+            }
+        } else if node.tag == "folder" {
+            found.insert(id, None);
+            if let Some(children) = node.value.get("requests") {
+                collect_nodes(children, found);
+            }
+        }
+    }
+}
+
+/// Resolve a single recipe, resolving its ancestors first. `stack` is the chain
+/// of recipes that led here, for cycle detection.
+fn resolve<'a>(
+    id: &'a str,
+    nodes: &IndexMap<&'a str, Option<&'a Mapping>>,
+    resolved: &mut HashMap<String, Mapping>,
+    stack: &mut Vec<&'a str>,
+) -> anyhow::Result<Mapping> {
+    if let Some(recipe) = resolved.get(id) {
+        return Ok(recipe.clone());
+    }
+    if let Some(start) = stack.iter().position(|other| *other == id) {
+        bail!(
+            "Circular inheritance: {}",
+            stack[start..].iter().chain([&id]).format(" -> ")
+        );
+    }
+
+    // Only recipes are passed in here
+    let recipe = nodes[id].expect("Node is not a recipe");
+    let recipe = match recipe.get(EXTENDS) {
+        None => recipe.clone(),
+        Some(parent_id) => {
+            let parent_id = parent_id.as_str().ok_or_else(|| {
+                anyhow!("Recipe `{id}`: `extends` must be a recipe ID")
+            })?;
+            let (&parent_id, parent) =
+                nodes.get_key_value(parent_id).ok_or_else(|| {
+                    anyhow!(
+                        "Recipe `{id}` extends unknown recipe `{parent_id}`"
+                    )
+                })?;
+            if parent.is_none() {
+                bail!(
+                    "Recipe `{id}` extends folder `{parent_id}`; \
+                    only recipes can be extended"
+                );
+            }
+            stack.push(id);
+            let parent = resolve(parent_id, nodes, resolved, stack)?;
+            stack.pop();
+            overlay(parent, recipe)
+        }
+    };
+    resolved.insert(id.to_owned(), recipe.clone());
+    Ok(recipe)
+}
+
+/// Apply a child recipe's fields on top of its (resolved) parent. A `null`
+/// field in the child removes the field from the parent, so it falls back to
+/// its default.
+fn overlay(mut parent: Mapping, child: &Mapping) -> Mapping {
+    parent.remove("name");
+    for (key, value) in child {
+        match key.as_str() {
+            Some(EXTENDS) => {}
+            _ if value.is_null() => {
+                parent.remove(key);
+            }
+            Some("headers") => {
+                let merged = merge_headers(parent.get(key), value);
+                parent.insert(key.clone(), merged);
+            }
+            Some("query") => {
+                let merged = merge_query(parent.get(key), value);
+                parent.insert(key.clone(), merged);
+            }
+            _ => {
+                parent.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    parent
+}
+
+/// Merge two header maps. Header names are case-insensitive, so a child header
+/// replaces any parent header of the same name, regardless of case. If either
+/// isn't a map, the child replaces the parent so deserialization can report it.
+fn merge_headers(parent: Option<&Value>, child: &Value) -> Value {
+    let (Some(Value::Mapping(parent)), Value::Mapping(child)) = (parent, child)
+    else {
+        return child.clone();
+    };
+    let same_name = |a: &Value, b: &Value| match (a.as_str(), b.as_str()) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        _ => a == b,
+    };
+    parent
+        .iter()
+        .filter(|(name, _)| !child.keys().any(|other| same_name(name, other)))
+        .chain(child)
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect::<Mapping>()
+        .into()
+}
+
+/// Merge two sets of query parameters. Parameters can be repeated, so a child
+/// parameter replaces *every* parent parameter of the same name. The result is
+/// always in `key=value` sequence form. If either can't be parsed, the child
+/// replaces the parent so deserialization can report it.
+fn merge_query(parent: Option<&Value>, child: &Value) -> Value {
+    let (Some(parent), Some(child)) =
+        (parent.and_then(query_params), query_params(child))
+    else {
+        return child.clone();
+    };
+    parent
+        .iter()
+        .filter(|(param, _)| !child.iter().any(|(other, _)| param == other))
+        .chain(&child)
+        .map(|(param, value)| Value::from(format!("{param}={value}")))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Get query parameters as a list of pairs, from either of the accepted forms
+fn query_params(query: &Value) -> Option<Vec<(String, String)>> {
+    match query {
+        Value::Sequence(params) => params
+            .iter()
+            .map(|param| {
+                let (param, value) = param.as_str()?.split_once('=')?;
+                Some((param.to_owned(), value.to_owned()))
+            })
+            .collect(),
+        Value::Mapping(params) => params
+            .iter()
+            .map(|(param, value)| {
+                Some((scalar_to_string(param)?, scalar_to_string(value)?))
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Replace the body of every recipe in the tree with its resolved version
+fn replace_recipes(nodes: &mut Value, resolved: &HashMap<String, Mapping>) {
+    let Value::Mapping(nodes) = nodes else {
+        return;
+    };
+    for (id, node) in nodes {
+        let (Some(id), Value::Tagged(node)) = (id.as_str(), node) else {
+            continue;
+        };
+        if node.tag == "request" {
+            if let Some(recipe) = resolved.get(id) {
+                node.value = recipe.clone().into();
+            }
+        } else if let Some(children) = node.value.get_mut("requests") {
+            replace_recipes(children, resolved);
+        }
+    }
+}
+
+impl Collection {
+    /// Get a profile, followed by each profile it inherits from, nearest
+    /// first. The chain ends early at an unknown profile or a cycle; those are
+    /// rejected when the collection is loaded, so this only happens for
+    /// collections built by hand.
+    pub fn profile_ancestry(&self, profile_id: &ProfileId) -> Vec<&Profile> {
+        let mut ancestry: Vec<&Profile> = Vec::new();
+        let mut next = self.profiles.get(profile_id);
+        while let Some(profile) = next {
+            if ancestry.iter().any(|other| other.id == profile.id) {
+                break;
+            }
+            ancestry.push(profile);
+            next = profile
+                .extends
+                .as_ref()
+                .and_then(|parent_id| self.profiles.get(parent_id));
+        }
+        ancestry
+    }
+
+    /// Get the value of a profile field, from the profile itself or the
+    /// nearest profile it inherits from
+    pub fn profile_field(
+        &self,
+        profile_id: &ProfileId,
+        field: &str,
+    ) -> Option<&Template> {
+        self.profile_ancestry(profile_id)
+            .into_iter()
+            .find_map(|profile| profile.data.get(field))
+    }
+
+    /// Get every data field of a profile, including inherited ones, mapped to
+    /// the profile that defines the value. The profile's own fields come
+    /// first, followed by inherited fields, nearest profile first.
+    pub fn profile_data(
+        &self,
+        profile_id: &ProfileId,
+    ) -> IndexMap<&str, (&ProfileId, &Template)> {
+        let mut data = IndexMap::new();
+        for profile in self.profile_ancestry(profile_id) {
+            for (field, template) in &profile.data {
+                data.entry(field.as_str())
+                    .or_insert((&profile.id, template));
+            }
+        }
+        data
+    }
+
+    /// Make sure every profile's `extends` refers to a profile that exists,
+    /// and that no profile inherits from itself
+    pub(super) fn validate_profile_extends(&self) -> anyhow::Result<()> {
+        for profile in self.profiles.values() {
+            let mut stack = vec![&profile.id];
+            let mut current = profile;
+            while let Some(parent_id) = &current.extends {
+                let parent = self.profiles.get(parent_id).ok_or_else(|| {
+                    anyhow!(
+                        "Profile `{}` extends unknown profile `{parent_id}`",
+                        current.id
+                    )
+                })?;
+                if let Some(start) =
+                    stack.iter().position(|other| *other == parent_id)
+                {
+                    bail!(
+                        "Circular inheritance: {}",
+                        stack[start..]
+                            .iter()
+                            .chain([&parent_id])
+                            .format(" -> ")
+                    );
+                }
+                stack.push(parent_id);
+                current = parent;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_err,
+        collection::{Authentication, Collection, RecipeId},
+        util::parse_yaml,
+    };
+    use indexmap::{indexmap, IndexMap};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn load(source: &str) -> Collection {
+        parse_yaml(source.as_bytes()).unwrap()
+    }
+
+    /// Fields are inherited through multiple levels and across folders, with
+    /// headers and query parameters merged by name
+    #[test]
+    fn test_extends() {
+        let collection = load(
+            r#"
+requests:
+  base: !request
+    name: Base
+    method: GET
+    url: "{{host}}/api"
+    authentication: !bearer "{{token}}"
+    query:
+      - page=1
+      - tag=a
+      - tag=b
+    headers:
+      Accept: application/json
+      X-Client: slumber
+  users: !folder
+    requests:
+      list_users: !request
+        extends: base
+        url: "{{host}}/api/users"
+        query:
+          tag: c
+        headers:
+          accept: text/csv
+      create_user: !request
+        extends: list_users
+        name: Create User
+        method: POST
+        authentication: null
+        headers: null
+"#,
+        );
+
+        let list_users = collection
+            .recipes
+            .get_recipe(&RecipeId::from("list_users"))
+            .unwrap();
+        assert_eq!(list_users.name, None);
+        assert_eq!(list_users.method, "GET".parse().unwrap());
+        assert_eq!(list_users.url, "{{host}}/api/users".into());
+        assert_eq!(
+            list_users.authentication,
+            Some(Authentication::Bearer("{{token}}".into()))
+        );
+        assert_eq!(
+            list_users.query,
+            [("page".into(), "1".into()), ("tag".into(), "c".into())]
+        );
+        assert_eq!(
+            list_users.headers,
+            indexmap! {
+                "X-Client".into() => "slumber".into(),
+                "accept".into() => "text/csv".into(),
+            }
+        );
+
+        let create_user = collection
+            .recipes
+            .get_recipe(&RecipeId::from("create_user"))
+            .unwrap();
+        assert_eq!(create_user.name.as_deref(), Some("Create User"));
+        assert_eq!(create_user.method, "POST".parse().unwrap());
+        assert_eq!(create_user.url, "{{host}}/api/users".into());
+        assert_eq!(create_user.authentication, None);
+        assert_eq!(create_user.query, list_users.query);
+        assert_eq!(create_user.headers, IndexMap::new());
+    }
+
+    #[rstest]
+    #[case::unknown(
+        "a: !request\n  extends: b",
+        "Recipe `a` extends unknown recipe `b`"
+    )]
+    #[case::folder(
+        "a: !request\n  extends: f\nf: !folder\n  requests: {}",
+        "Recipe `a` extends folder `f`; only recipes can be extended"
+    )]
+    #[case::not_id(
+        "a: !request\n  extends: [b]",
+        "Recipe `a`: `extends` must be a recipe ID"
+    )]
+    #[case::self_cycle(
+        "a: !request\n  extends: a",
+        "Circular inheritance: a -> a"
+    )]
+    #[case::cycle(
+        "a: !request\n  extends: b\nb: !request\n  extends: c\n\
+        c: !request\n  extends: b",
+        "Circular inheritance: b -> c -> b"
+    )]
+    fn test_extends_error(
+        #[case] requests: &str,
+        #[case] expected_error: &str,
+    ) {
+        let mut tree: Value = serde_yaml::from_str(requests).unwrap();
+        assert_err!(resolve_extends(&mut [&mut tree]), expected_error);
+    }
+
+    /// Profile fields are looked up through the inheritance chain, nearest
+    /// profile first
+    #[test]
+    fn test_profile_extends() {
+        let collection = load(
+            r#"
+profiles:
+  base:
+    data:
+      host: https://example.com
+      client_id: abc
+  staging:
+    extends: base
+    data:
+      host: https://staging.example.com
+  staging_eu:
+    extends: staging
+    data:
+      region: eu
+"#,
+        );
+        collection.validate_profile_extends().unwrap();
+        let id = ProfileId::from("staging_eu");
+
+        assert_eq!(
+            collection
+                .profile_ancestry(&id)
+                .into_iter()
+                .map(|profile| profile.id.as_str())
+                .collect_vec(),
+            ["staging_eu", "staging", "base"]
+        );
+        assert_eq!(
+            collection.profile_field(&id, "host"),
+            Some(&"https://staging.example.com".into())
+        );
+        assert_eq!(collection.profile_field(&id, "unknown"), None);
+        // Each field comes from the nearest profile that defines it
+        assert_eq!(
+            collection
+                .profile_data(&id)
+                .into_iter()
+                .map(|(field, (profile_id, template))| {
+                    (field, profile_id.as_str(), template.display())
+                })
+                .collect_vec(),
+            [
+                ("region", "staging_eu", "eu".into()),
+                ("host", "staging", "https://staging.example.com".into()),
+                ("client_id", "base", "abc".into()),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::unknown(
+        "{a: {extends: b, data: {}}}",
+        "Profile `a` extends unknown profile `b`"
+    )]
+    #[case::unknown_grandparent(
+        "{a: {extends: b, data: {}}, b: {extends: c, data: {}}}",
+        "Profile `b` extends unknown profile `c`"
+    )]
+    #[case::self_cycle(
+        "{a: {extends: a, data: {}}}",
+        "Circular inheritance: a -> a"
+    )]
+    #[case::cycle(
+        "{a: {extends: b, data: {}}, b: {extends: c, data: {}}, \
+        c: {extends: b, data: {}}}",
+        "Circular inheritance: b -> c -> b"
+    )]
+    fn test_profile_extends_error(
+        #[case] profiles: &str,
+        #[case] expected_error: &str,
+    ) {
+        let collection = load(&format!("profiles: {profiles}"));
+        assert_err!(collection.validate_profile_extends(), expected_error);
+    }
+}
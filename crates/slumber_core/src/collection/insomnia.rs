@@ -5,7 +5,7 @@ use crate::{
     collection::{
         self, cereal::deserialize_from_str, Chain, ChainId, ChainSource,
         Collection, Folder, HasId, Method, Profile, ProfileId, Recipe,
-        RecipeBody, RecipeId, RecipeNode, RecipeTree,
+        RecipeBody, RecipeDefaults, RecipeId, RecipeNode, RecipeTree,
     },
     http::content_type::ContentType,
     template::{Identifier, Template},
@@ -15,9 +15,10 @@ use anyhow::{anyhow, Context};
 use indexmap::IndexMap;
 use itertools::Itertools;
 use mime::Mime;
+use regex::Regex;
 use reqwest::header;
 use serde::{Deserialize, Deserializer};
-use std::{collections::HashMap, fs::File, path::Path};
+use std::{collections::HashMap, fs::File, path::Path, sync::LazyLock};
 use tracing::{debug, error, info, warn};
 
 impl Collection {
@@ -66,9 +67,14 @@ impl Collection {
             build_recipe_tree(&workspace_id, request_groups, requests)?;
 
         Ok(Collection {
+            imports: Vec::new(),
             profiles,
             recipes,
             chains,
+            defaults: RecipeDefaults::default(),
+            openapi: None,
+            environment: IndexMap::new(),
+            template_tests: IndexMap::new(),
             _ignore: serde::de::IgnoredAny,
         })
     }
@@ -289,11 +295,15 @@ impl From<Environment> for Profile {
         Profile {
             id: environment.id.into(),
             name: Some(environment.name),
+            extends: None,
             data: environment
                 .data
                 .into_iter()
-                .map(|(k, v)| (k, Template::raw(v)))
+                .map(|(k, v)| (k, translate_template(v)))
                 .collect(),
+            certificate: None,
+            tunnel: None,
+            fake_seed: None,
         }
     }
 }
@@ -305,6 +315,7 @@ impl From<RequestGroup> for RecipeNode {
             name: Some(folder.name),
             // This will be populated later
             children: IndexMap::new(),
+            defaults: RecipeDefaults::default(),
         })
     }
 }
@@ -324,7 +335,7 @@ impl From<Request> for RecipeNode {
         for header in request.headers {
             headers.insert(
                 header.name.to_lowercase(),
-                Template::raw(header.value),
+                translate_template(header.value),
             );
         }
         headers.shift_remove(header::USER_AGENT.as_str());
@@ -360,17 +371,25 @@ impl From<Request> for RecipeNode {
             id: request.id.into(),
             name: Some(request.name),
             method: request.method,
-            url: Template::raw(request.url),
+            url: translate_template(request.url),
             body,
             query: request
                 .parameters
                 .into_iter()
                 .map(|parameter| {
-                    (parameter.name, Template::raw(parameter.value))
+                    (parameter.name, translate_template(parameter.value))
                 })
                 .collect(),
             headers,
             authentication,
+            signature: None,
+            certificate: None,
+            danger_skip_tls_verify: false,
+            baseline_ignore: vec![],
+            assertions: vec![],
+            display_query: None,
+            example: None,
+            parameters: IndexMap::new(),
         })
     }
 }
@@ -381,7 +400,7 @@ impl TryFrom<Body> for RecipeBody {
     fn try_from(body: Body) -> anyhow::Result<Self> {
         let body = if body.mime_type == mime::APPLICATION_JSON {
             RecipeBody::Raw {
-                body: Template::raw(body.try_text()?),
+                body: translate_template(body.try_text()?),
                 content_type: Some(ContentType::Json),
             }
         } else if body.mime_type == mime::APPLICATION_WWW_FORM_URLENCODED {
@@ -394,7 +413,7 @@ impl TryFrom<Body> for RecipeBody {
             )
         } else {
             RecipeBody::Raw {
-                body: Template::raw(body.try_text()?),
+                body: translate_template(body.try_text()?),
                 content_type: None,
             }
         };
@@ -408,7 +427,9 @@ impl From<FormParam> for (String, Template) {
     fn from(param: FormParam) -> Self {
         match param.kind {
             // Simple string, map to a raw template
-            FormParamKind::String => (param.name, Template::raw(param.value)),
+            FormParamKind::String => {
+                (param.name, translate_template(param.value))
+            }
             // We'll map this to a chain that loads the file. The ID of the
             // chain is the ID of this param. We're banking on that chain being
             // created elsewhere. It's a bit spaghetti but otherwise we'd need
@@ -430,13 +451,13 @@ impl TryFrom<Authentication> for collection::Authentication {
         match authentication {
             Authentication::Basic { username, password } => {
                 Ok(collection::Authentication::Basic {
-                    username: Template::raw(username),
-                    password: Some(Template::raw(password)),
+                    username: translate_template(username),
+                    password: Some(translate_template(password)),
                 })
             }
-            Authentication::Bearer { token } => {
-                Ok(collection::Authentication::Bearer(Template::raw(token)))
-            }
+            Authentication::Bearer { token } => Ok(
+                collection::Authentication::Bearer(translate_template(token)),
+            ),
             // Caller should print a warning for this
             Authentication::Other { kind } => Err(kind),
         }
@@ -451,7 +472,7 @@ fn build_profiles(
     fn convert_data(
         data: IndexMap<String, String>,
     ) -> impl Iterator<Item = (String, Template)> {
-        data.into_iter().map(|(k, v)| (k, Template::raw(v)))
+        data.into_iter().map(|(k, v)| (k, translate_template(v)))
     }
 
     // The Base Environment is the one with the workspace as a parent. We
@@ -484,13 +505,55 @@ fn build_profiles(
                 Profile {
                     id,
                     name: Some(environment.name),
+                    extends: None,
                     data,
+                    certificate: None,
+                    tunnel: None,
+                    fake_seed: None,
                 },
             )
         })
         .collect()
 }
 
+/// Translate an Insomnia template, which uses Nunjucks syntax, into a Slumber
+/// template. Variable references like `{{ _.host }}` or `{{host}}` become
+/// profile fields, i.e. `{{host}}`. Anything we can't translate, such as
+/// filters or tags like `{% response %}`, is imported as raw text.
+fn translate_template(template: String) -> Template {
+    /// Environment variables can be referenced with or without the `_.`
+    /// prefix
+    static VARIABLE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"\{\{\s*(?:_\.)?([^{}]*?)\s*\}\}").unwrap()
+    });
+
+    if template.contains("{%") {
+        warn!(
+            "Template tags are not supported; importing `{template}` as raw \
+            text"
+        );
+    }
+    let mut translated = Template::default();
+    let mut raw_start = 0;
+    for captures in VARIABLE_REGEX.captures_iter(&template) {
+        let variable = captures.get(0).expect("Group 0 is always present");
+        let Ok(field) = captures[1].parse::<Identifier>() else {
+            warn!(
+                "Variable `{}` is not supported; importing it as raw text",
+                variable.as_str()
+            );
+            continue;
+        };
+        translated = translated
+            .concat(&Template::raw(
+                template[raw_start..variable.start()].to_owned(),
+            ))
+            .concat(&Template::from_field(field));
+        raw_start = variable.end();
+    }
+    translated.concat(&Template::raw(template[raw_start..].to_owned()))
+}
+
 /// Build up all the chains we need to represent the Insomnia collection.
 /// Chains don't map 1:1 with any Insomnia resource. They generally are an
 /// explicit representation of some implicit Insomnia behavior, so we have to
@@ -528,6 +591,9 @@ fn build_chains(requests: &[Request]) -> IndexMap<ChainId, Chain> {
                         selector: None,
                         content_type: None,
                         trim: Default::default(),
+                        cache: None,
+                        exclusive: false,
+                        example: None,
                     },
                 );
             }
@@ -645,6 +711,22 @@ mod tests {
         assert_eq!(imported, *expected);
     }
 
+    /// Variables are translated to fields, and everything else is raw text
+    #[rstest]
+    #[case::variable("{{ _.host }}/get", "{{host}}/get")]
+    #[case::no_prefix("{{host}}:{{port}}", "{{host}}:{{port}}")]
+    #[case::no_whitespace("{{_.host}}", "{{host}}")]
+    #[case::raw("no variables", "no variables")]
+    #[case::filter("{{ _.host | upper }}", "{_{ _.host | upper }}")]
+    #[case::nested("{{ _.auth.token }}", "{_{ _.auth.token }}")]
+    #[case::tag("{% uuid 'v4' %}", "{% uuid 'v4' %}")]
+    fn test_translate_template(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(
+            translate_template(input.to_owned()),
+            expected.parse::<Template>().unwrap()
+        );
+    }
+
     #[test]
     fn test_deserialize_shitty_option() {
         /// A wrapper to use our custom deserializer
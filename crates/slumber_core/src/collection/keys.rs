@@ -0,0 +1,294 @@
+//! List the template keys available in a collection, for editor completion
+
+use crate::{
+    collection::{Collection, ProfileId, RecipeId},
+    template::{FakeKind, Template, TemplateKey},
+};
+use indexmap::{IndexMap, IndexSet};
+use serde::Serialize;
+use serde_yaml::Value;
+use strum::IntoEnumIterator;
+
+/// A key that can be used in templates within a collection
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TemplateKeyInfo {
+    /// Key as it appears within `{{ }}`, e.g. `chains.token`
+    pub key: String,
+    pub kind: TemplateKeyKind,
+    /// For profile fields, every profile that defines or inherits the field
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<ProfileId>,
+    /// Is the key referenced by any template? If the list was scoped to a
+    /// recipe, this only considers that recipe's templates.
+    pub used: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateKeyKind {
+    Field,
+    Chain,
+    Environment,
+    Fake,
+}
+
+impl Collection {
+    /// Get every key that can be used in a template: profile fields, chains,
+    /// and fake data. Environment variables can't be listed exhaustively, so
+    /// only those referenced somewhere in the collection are included.
+    ///
+    /// If a recipe is given, only that recipe's templates are considered when
+    /// determining if a key is used. Return an error if the recipe doesn't
+    /// exist.
+    pub fn template_keys(
+        &self,
+        recipe_id: Option<&RecipeId>,
+    ) -> anyhow::Result<Vec<TemplateKeyInfo>> {
+        // Environment variables used anywhere count, even if scoped to a
+        // recipe, because they're valid everywhere
+        let mut environment: IndexSet<String> = IndexSet::new();
+        let mut used: IndexSet<String> = IndexSet::new();
+        visit_keys(serde_yaml::to_value(self)?, &mut |key| {
+            if let TemplateKey::Environment(_) = key {
+                environment.insert(key.to_string());
+            }
+            if recipe_id.is_none() {
+                used.insert(key.to_string());
+            }
+        });
+        if let Some(recipe_id) = recipe_id {
+            let recipe = self.recipes.try_get_recipe(recipe_id)?;
+            // Inherited defaults are part of the recipe too
+            let defaults = self.recipe_defaults(recipe_id);
+            for value in [
+                serde_yaml::to_value(recipe)?,
+                serde_yaml::to_value(defaults)?,
+            ] {
+                visit_keys(value, &mut |key| {
+                    used.insert(key.to_string());
+                });
+            }
+        }
+
+        // Group fields across profiles. A profile provides every field it
+        // inherits, as well as its own
+        let mut fields: IndexMap<&str, Vec<ProfileId>> = IndexMap::new();
+        for profile_id in self.profiles.keys() {
+            for field in self.profile_data(profile_id).into_keys() {
+                fields.entry(field).or_default().push(profile_id.clone());
+            }
+        }
+
+        let info = |key: String, kind, profiles| TemplateKeyInfo {
+            used: used.contains(&key),
+            key,
+            kind,
+            profiles,
+        };
+        let fields = fields.into_iter().map(|(field, profiles)| {
+            info(field.to_owned(), TemplateKeyKind::Field, profiles)
+        });
+        let chains = self.chains.keys().map(|id| {
+            info(
+                TemplateKey::Chain(id.clone()).to_string(),
+                TemplateKeyKind::Chain,
+                vec![],
+            )
+        });
+        let environment = environment
+            .into_iter()
+            .map(|key| info(key, TemplateKeyKind::Environment, vec![]));
+        let fake = FakeKind::iter().map(|kind| {
+            info(
+                TemplateKey::Fake(kind).to_string(),
+                TemplateKeyKind::Fake,
+                vec![],
+            )
+        });
+        Ok(fields
+            .chain(chains)
+            .chain(environment)
+            .chain(fake)
+            .collect())
+    }
+
+    /// Get the name of every environment variable the collection uses: those
+    /// referenced by any template, followed by any others that are configured
+    /// under `environment`
+    pub fn environment_variables(&self) -> anyhow::Result<Vec<String>> {
+        let mut variables: IndexSet<String> = IndexSet::new();
+        visit_keys(serde_yaml::to_value(self)?, &mut |key| {
+            if let TemplateKey::Environment(variable) = key {
+                variables.insert(variable.to_string());
+            }
+        });
+        variables.extend(self.environment.keys().cloned());
+        Ok(variables.into_iter().collect())
+    }
+}
+
+/// Call a function for every key referenced by a template anywhere within a
+/// serialized value. Any string that parses as a template is considered, which
+/// is simpler than visiting every template field individually.
+pub(super) fn visit_keys(value: Value, f: &mut impl FnMut(&TemplateKey)) {
+    match value {
+        Value::String(s) => {
+            if let Ok(template) = s.parse::<Template>() {
+                template.keys().into_iter().for_each(&mut *f);
+            }
+        }
+        Value::Sequence(sequence) => {
+            for value in sequence {
+                visit_keys(value, f);
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (_, value) in mapping {
+                visit_keys(value, f);
+            }
+        }
+        Value::Tagged(tagged) => visit_keys(tagged.value, f),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_err,
+        collection::{
+            Chain, ChainSource, EnvironmentVariable, Profile, Recipe,
+            RecipeDefaults,
+        },
+        test_util::{by_id, Factory},
+    };
+    use indexmap::indexmap;
+    use pretty_assertions::assert_eq;
+
+    fn collection() -> Collection {
+        Collection {
+            profiles: by_id([
+                Profile {
+                    id: "local".into(),
+                    data: indexmap! {
+                        "host".into() => "http://localhost".into(),
+                        "token".into() => "{{env.TOKEN}}".into(),
+                    },
+                    ..Profile::factory(())
+                },
+                Profile {
+                    id: "prod".into(),
+                    data: indexmap! {"host".into() => "https://prod".into()},
+                    ..Profile::factory(())
+                },
+                Profile {
+                    id: "staging".into(),
+                    extends: Some("prod".into()),
+                    ..Profile::factory(())
+                },
+            ]),
+            chains: by_id([Chain {
+                id: "user_id".into(),
+                source: ChainSource::command(["echo", "{{env.USER}}"]),
+                ..Chain::factory(())
+            }]),
+            recipes: by_id([
+                Recipe {
+                    id: "login".into(),
+                    url: "{{host}}/login".into(),
+                    ..Recipe::factory(())
+                },
+                Recipe {
+                    id: "get_user".into(),
+                    url: "{{host}}/users/{{chains.user_id}}".into(),
+                    ..Recipe::factory(())
+                },
+            ])
+            .into(),
+            defaults: RecipeDefaults {
+                headers: indexmap! {"X-Token".into() => "{{token}}".into()},
+                ..RecipeDefaults::default()
+            },
+            ..Collection::factory(())
+        }
+    }
+
+    /// Get just the non-fake keys, and whether they're used
+    fn summarize(keys: Vec<TemplateKeyInfo>) -> Vec<(String, bool)> {
+        keys.into_iter()
+            .filter(|info| info.kind != TemplateKeyKind::Fake)
+            .map(|info| (info.key, info.used))
+            .collect()
+    }
+
+    #[test]
+    fn test_template_keys() {
+        let keys = collection().template_keys(None).unwrap();
+        assert_eq!(
+            keys[0],
+            TemplateKeyInfo {
+                key: "host".into(),
+                kind: TemplateKeyKind::Field,
+                // Inherited fields count too
+                profiles: vec!["local".into(), "prod".into(), "staging".into()],
+                used: true,
+            }
+        );
+        assert_eq!(
+            keys.last().unwrap().kind,
+            TemplateKeyKind::Fake,
+            "Fake keys should be last"
+        );
+        assert_eq!(
+            summarize(keys),
+            vec![
+                ("host".into(), true),
+                ("token".into(), true),
+                ("chains.user_id".into(), true),
+                ("env.TOKEN".into(), true),
+                ("env.USER".into(), true),
+            ]
+        );
+    }
+
+    /// Scoping to a recipe only changes which keys are considered used.
+    /// Inherited defaults count as part of the recipe
+    #[test]
+    fn test_template_keys_recipe() {
+        let collection = collection();
+        let keys = collection.template_keys(Some(&"login".into())).unwrap();
+        assert_eq!(
+            summarize(keys),
+            vec![
+                ("host".into(), true),
+                ("token".into(), true),
+                ("chains.user_id".into(), false),
+                ("env.TOKEN".into(), false),
+                ("env.USER".into(), false),
+            ]
+        );
+
+        assert_err!(
+            collection.template_keys(Some(&"unknown".into())),
+            "No recipe with ID `unknown`"
+        );
+    }
+
+    /// Referenced variables come first, then configured ones that aren't
+    /// referenced anywhere
+    #[test]
+    fn test_environment_variables() {
+        let collection = Collection {
+            environment: indexmap! {
+                "USER".into() => EnvironmentVariable::default(),
+                "REGION".into() => EnvironmentVariable::default(),
+            },
+            ..collection()
+        };
+        assert_eq!(
+            collection.environment_variables().unwrap(),
+            vec!["TOKEN".to_owned(), "USER".into(), "REGION".into()]
+        );
+    }
+}
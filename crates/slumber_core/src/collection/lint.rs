@@ -0,0 +1,708 @@
+//! Check a collection for mistakes without sending any requests, for
+//! `slumber lint`
+
+use crate::{
+    collection::{
+        imports::{load_with_imports, ImportError},
+        keys::visit_keys,
+        spans::Spans,
+        ChainId, ChainSource, Collection, ProfileId, RecipeId, ReferenceIssue,
+        ReferenceLocation,
+    },
+    template::TemplateKey,
+    util::parse_yaml,
+};
+use derive_more::Display;
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::{
+    fmt::{self, Formatter},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A problem found in a collection, and where it is
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// File containing the problem. For collections with imports, this is
+    /// the file that defines the offending item
+    pub file: PathBuf,
+    /// Line number (starting at 1) of the offending item. `None` if it
+    /// couldn't be found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+/// How bad is a [Diagnostic]?
+#[derive(Copy, Clone, Debug, Display, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The collection can't be loaded, or requests will fail to build
+    #[display("error")]
+    Error,
+    /// Probably a mistake, but nothing is broken
+    #[display("warning")]
+    Warning,
+}
+
+/// Load a collection file, including its imports, and check it for problems.
+/// If the collection can't be loaded, the load error is the only diagnostic.
+/// Otherwise, it's checked for:
+/// - Templates that refer to unknown fields or chains
+/// - `!request` chains that refer to unknown recipes
+/// - Fields that are used, but missing from some profiles
+/// - Recipes that can't be built with any profile, because every profile is
+///   missing a field they use
+/// - Unused chains and profile fields
+///
+/// Diagnostics are sorted by file and line.
+pub fn lint(path: &Path) -> Vec<Diagnostic> {
+    let collection = match load_with_imports(path) {
+        Ok(collection) => collection,
+        Err(error) => return vec![load_error(path, &error)],
+    };
+    let findings = match collection.lint_findings() {
+        Ok(findings) => findings,
+        Err(error) => {
+            return vec![Diagnostic {
+                severity: Severity::Error,
+                message: format!("{error:#}"),
+                file: path.to_owned(),
+                line: None,
+            }]
+        }
+    };
+
+    // Parse every file again so we can find items within them
+    let sources = read_sources(path, &collection);
+
+    let mut diagnostics = findings
+        .into_iter()
+        .map(|finding| {
+            let (file, line) =
+                find_in_sources(&sources, &collection, &finding.location)
+                    .map(|(path, line)| (path, Some(line)))
+                    .unwrap_or_else(|| (path.to_owned(), None));
+            Diagnostic {
+                severity: finding.severity,
+                message: finding.message,
+                file,
+                line,
+            }
+        })
+        .collect_vec();
+    diagnostics.sort_by_key(|diagnostic| {
+        let file_index = sources
+            .iter()
+            .position(|(path, _)| *path == diagnostic.file);
+        (file_index, diagnostic.line)
+    });
+    diagnostics
+}
+
+/// A problem found in a loaded collection, before it's been located in the
+/// source files
+struct Finding {
+    severity: Severity,
+    location: ReferenceLocation,
+    message: String,
+}
+
+impl Collection {
+    /// Run every check on a loaded collection. See [lint]
+    fn lint_findings(&self) -> anyhow::Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+        let finding = |severity, location, message| Finding {
+            severity,
+            location,
+            message,
+        };
+
+        for issue in self.reference_issues()? {
+            let message = issue.to_string();
+            findings.push(match issue {
+                ReferenceIssue::Dangling { location, .. } => {
+                    finding(Severity::Error, location, message)
+                }
+                ReferenceIssue::UnusedChain { chain } => finding(
+                    Severity::Warning,
+                    ReferenceLocation::Chain(chain),
+                    message,
+                ),
+                ReferenceIssue::UnusedField { profiles, .. } => finding(
+                    Severity::Warning,
+                    // Point at the first definition
+                    ReferenceLocation::Profile(profiles[0].clone()),
+                    message,
+                ),
+            });
+        }
+
+        for chain in self.chains.values() {
+            if let ChainSource::Request { recipe, .. } = &chain.source {
+                if self.recipes.get_recipe(recipe).is_none() {
+                    findings.push(finding(
+                        Severity::Error,
+                        ReferenceLocation::Chain(chain.id.clone()),
+                        format!(
+                            "Chain `{}` loads from unknown recipe `{recipe}`",
+                            chain.id
+                        ),
+                    ));
+                }
+            }
+        }
+
+        // Fields defined by each profile, including inherited ones
+        let profile_fields: IndexMap<&ProfileId, IndexSet<&str>> = self
+            .profiles
+            .keys()
+            .map(|id| (id, self.profile_data(id).into_keys().collect()))
+            .collect();
+        let is_defined = |field: &str| {
+            profile_fields.values().any(|fields| fields.contains(field))
+        };
+
+        let mut item_keys: Vec<(ReferenceLocation, Vec<TemplateKey>)> =
+            Vec::new();
+        for (location, value) in self.serialize_items()? {
+            let keys = unique_keys(value);
+            let parameters = self.parameters_at(&location);
+            for key in &keys {
+                match key {
+                    // Parameters are given a value when the recipe is sent
+                    TemplateKey::Field(field)
+                        if parameters.contains(field.as_str()) => {}
+                    TemplateKey::Field(field) if is_defined(field.as_str()) => {
+                        // Only profiles that could render this template
+                        let missing = profile_fields
+                            .iter()
+                            .filter(|(profile_id, _)| {
+                                self.renders_with(&location, profile_id)
+                            })
+                            .filter(|(_, fields)| {
+                                !fields.contains(field.as_str())
+                            })
+                            .map(|(profile_id, _)| *profile_id)
+                            .collect_vec();
+                        if !missing.is_empty() {
+                            findings.push(finding(
+                                Severity::Warning,
+                                location.clone(),
+                                format!(
+                                    "{location} refers to field `{field}`, \
+                                    which {} define",
+                                    format_profiles(&missing)
+                                ),
+                            ));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            item_keys.push((location, keys));
+        }
+
+        if !self.profiles.is_empty() {
+            for recipe_id in self.recipes.recipe_ids() {
+                let fields = self.recipe_fields(recipe_id, &item_keys)?;
+                // Unknown fields are already reported as errors
+                if !fields.iter().all(|field| is_defined(field)) {
+                    continue;
+                }
+                let buildable = profile_fields.values().any(|defined| {
+                    fields.iter().all(|field| defined.contains(field.as_str()))
+                });
+                if !buildable {
+                    findings.push(finding(
+                        Severity::Warning,
+                        ReferenceLocation::Recipe(recipe_id.clone()),
+                        format!(
+                            "Recipe `{recipe_id}` can't be built with any \
+                            profile; each one is missing a field it uses"
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(findings)
+    }
+
+    /// Can a template at the given location be rendered with a profile? A
+    /// profile's own templates are only rendered with that profile, or a
+    /// profile that inherits from it. Everything else can be rendered with
+    /// any profile.
+    fn renders_with(
+        &self,
+        location: &ReferenceLocation,
+        profile_id: &ProfileId,
+    ) -> bool {
+        match location {
+            ReferenceLocation::Profile(owner) => self
+                .profile_ancestry(profile_id)
+                .iter()
+                .any(|profile| &profile.id == owner),
+            _ => true,
+        }
+    }
+
+    /// Get every profile field that a recipe needs to be built, including
+    /// fields used by its defaults and by the chains it uses (recursively)
+    fn recipe_fields(
+        &self,
+        recipe_id: &RecipeId,
+        item_keys: &[(ReferenceLocation, Vec<TemplateKey>)],
+    ) -> anyhow::Result<IndexSet<String>> {
+        let keys_at = |location: &ReferenceLocation| {
+            item_keys
+                .iter()
+                .find(|(l, _)| l == location)
+                .map(|(_, keys)| keys.as_slice())
+                .unwrap_or_default()
+        };
+        let mut keys: Vec<TemplateKey> =
+            keys_at(&ReferenceLocation::Recipe(recipe_id.clone())).to_vec();
+        keys.extend(unique_keys(serde_yaml::to_value(
+            self.recipe_defaults(recipe_id),
+        )?));
+
+        // Parameters don't come from the profile
+        let parameters =
+            self.parameters_at(&ReferenceLocation::Recipe(recipe_id.clone()));
+        let mut fields: IndexSet<String> = IndexSet::new();
+        let mut visited_chains: IndexSet<ChainId> = IndexSet::new();
+        while let Some(key) = keys.pop() {
+            match key {
+                TemplateKey::Field(field)
+                    if !parameters.contains(field.as_str()) =>
+                {
+                    fields.insert(field.to_string());
+                }
+                TemplateKey::Chain(chain_id) => {
+                    if visited_chains.insert(chain_id.clone()) {
+                        keys.extend_from_slice(keys_at(
+                            &ReferenceLocation::Chain(chain_id),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Get the path of map keys that leads to an item in its collection file
+    fn item_path(&self, location: &ReferenceLocation) -> Vec<String> {
+        // Path of a recipe or folder within the recipe tree
+        let node_path = |id: &RecipeId| {
+            self.recipes
+                .get_lookup_key(id)
+                .map(|key| key.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|id| ["requests".to_owned(), id.to_string()])
+                .collect_vec()
+        };
+        match location {
+            ReferenceLocation::Profile(id) => {
+                vec!["profiles".into(), id.to_string()]
+            }
+            ReferenceLocation::Chain(id) => {
+                vec!["chains".into(), id.to_string()]
+            }
+            ReferenceLocation::Recipe(id) => node_path(id),
+            ReferenceLocation::Defaults(None) => vec!["defaults".into()],
+            ReferenceLocation::Defaults(Some(id)) => {
+                let mut path = node_path(id);
+                path.push("defaults".into());
+                path
+            }
+        }
+    }
+}
+
+/// Get every key used by templates within a value, without duplicates
+fn unique_keys(value: Value) -> Vec<TemplateKey> {
+    let mut keys: Vec<TemplateKey> = Vec::new();
+    visit_keys(value, &mut |key| {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    });
+    keys
+}
+
+/// Format a list of profiles as the subject of a sentence, e.g. "profile `a`
+/// doesn't" or "profiles `a`, `b` don't"
+fn format_profiles(profiles: &[&ProfileId]) -> String {
+    let list = profiles.iter().map(|id| format!("`{id}`")).join(", ");
+    if profiles.len() == 1 {
+        format!("profile {list} doesn't")
+    } else {
+        format!("profiles {list} don't")
+    }
+}
+
+/// Convert an error from loading the collection to a diagnostic, locating it
+/// as precisely as possible
+fn load_error(path: &Path, error: &anyhow::Error) -> Diagnostic {
+    // If an import failed, blame the innermost imported file
+    let file = error
+        .chain()
+        .filter_map(|error| error.downcast_ref::<ImportError>())
+        .last()
+        .map(|error| error.path.clone())
+        .unwrap_or_else(|| path.to_owned());
+    let yaml_error = error
+        .chain()
+        .find_map(|error| error.downcast_ref::<serde_yaml::Error>());
+    let line = yaml_error.and_then(|yaml_error| {
+        // Syntax errors have a location. Other errors come from deserializing
+        // the parsed YAML, so they don't; find the item that causes it
+        if let Some(location) = yaml_error.location() {
+            return Some(location.line());
+        }
+        let source = fs::read_to_string(&file).ok()?;
+        let key_path = find_invalid_item(&source, &yaml_error.to_string())?;
+        Spans::parse(&source)?
+            .find_line(&key_path.iter().map(String::as_str).collect_vec())
+    });
+    Diagnostic {
+        severity: Severity::Error,
+        message: yaml_error
+            .map(ToString::to_string)
+            .unwrap_or_else(|| format!("{error:#}")),
+        file,
+        line,
+    }
+}
+
+/// Find where an item is defined: the file, which is either the collection
+/// file or one of its imports, and the line within it (starting at 1). `None`
+/// if it can't be found.
+pub fn find_item(
+    path: &Path,
+    collection: &Collection,
+    location: &ReferenceLocation,
+) -> Option<(PathBuf, usize)> {
+    find_in_sources(&read_sources(path, collection), collection, location)
+}
+
+/// Read and parse the collection file and each of its imports, recording
+/// where everything in them is defined. Files that can't be read or parsed
+/// have no spans.
+fn read_sources(
+    path: &Path,
+    collection: &Collection,
+) -> Vec<(PathBuf, Option<Spans>)> {
+    [path.to_owned()]
+        .into_iter()
+        .chain(collection.imports.iter().cloned())
+        .map(|path| {
+            let spans = fs::read_to_string(&path)
+                .ok()
+                .and_then(|source| Spans::parse(&source));
+            (path, spans)
+        })
+        .collect()
+}
+
+/// Find an item in the first source file that defines it
+fn find_in_sources(
+    sources: &[(PathBuf, Option<Spans>)],
+    collection: &Collection,
+    location: &ReferenceLocation,
+) -> Option<(PathBuf, usize)> {
+    let key_path = collection.item_path(location);
+    let key_path = key_path.iter().map(String::as_str).collect_vec();
+    sources.iter().find_map(|(path, spans)| {
+        Some((path.clone(), spans.as_ref()?.find_line(&key_path)?))
+    })
+}
+
+/// Find the profile, chain, or recipe that causes a deserialization error, by
+/// deserializing each one on its own until one fails with the same message.
+/// Recipes are narrowed down through their folders. Return the path of map
+/// keys that leads to the item.
+fn find_invalid_item(source: &str, message: &str) -> Option<Vec<String>> {
+    let value: Value = parse_yaml(source.as_bytes()).ok()?;
+    ["profiles", "chains", "requests"]
+        .into_iter()
+        .find_map(|section| match value.get(section) {
+            Some(Value::Mapping(items)) => {
+                find_invalid_in_section(section, items, message)
+            }
+            _ => None,
+        })
+}
+
+fn find_invalid_in_section(
+    section: &str,
+    items: &Mapping,
+    message: &str,
+) -> Option<Vec<String>> {
+    items.iter().find_map(|(key, item)| {
+        let id = key.as_str()?;
+        let mut collection = Mapping::new();
+        collection.insert(
+            section.into(),
+            Mapping::from_iter([(key.clone(), item.clone())]).into(),
+        );
+        let error =
+            serde_yaml::from_value::<Collection>(collection.into()).err()?;
+        if error.to_string() != message {
+            return None;
+        }
+
+        let mut path = vec![section.to_owned(), id.to_owned()];
+        if let Value::Tagged(tagged) = item {
+            if let Some(Value::Mapping(children)) = tagged.value.get("requests")
+            {
+                path.extend(
+                    find_invalid_in_section("requests", children, message)
+                        .unwrap_or_default(),
+                );
+            }
+        }
+        Some(path)
+    })
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        write!(f, ": {}: {}", self.severity, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_dir, TempDir};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    /// Lint the first file, and get each diagnostic's file name, line, and
+    /// text
+    fn lint_files(
+        dir: &Path,
+        files: &[(&str, &str)],
+    ) -> Vec<(String, Option<usize>, String)> {
+        for (name, content) in files {
+            fs::write(dir.join(name), content).unwrap();
+        }
+        lint(&dir.join(files[0].0))
+            .into_iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic
+                        .file
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .into(),
+                    diagnostic.line,
+                    format!("{}: {}", diagnostic.severity, diagnostic.message),
+                )
+            })
+            .collect()
+    }
+
+    #[rstest]
+    fn test_lint(temp_dir: TempDir) {
+        let source = r#"profiles:
+  local:
+    data:
+      host: http://localhost
+      token: abc
+  prod:
+    data:
+      host: https://prod
+      region: us
+chains:
+  unused:
+    source: !command
+      command: [echo]
+  login:
+    source: !request
+      recipe: missing
+requests:
+  users: !folder
+    requests:
+      get_user: !request
+        method: GET
+        url: "{{host}}/users/{{user_id}}"
+  deploy: !request
+    method: POST
+    url: "{{host}}/{{region}}"
+    headers:
+      Authorization: "{{token}}"
+  env: !request
+    method: GET
+    url: "{{env.PATH}}/{{chains.login}}"
+"#;
+        let diagnostic = |line, text: &str| {
+            ("slumber.yml".to_owned(), Some(line), text.to_owned())
+        };
+        assert_eq!(
+            lint_files(&temp_dir, &[("slumber.yml", source)]),
+            vec![
+                diagnostic(11, "warning: Chain `unused` is never used"),
+                diagnostic(
+                    14,
+                    "error: Chain `login` loads from unknown recipe `missing`"
+                ),
+                diagnostic(
+                    20,
+                    "error: Recipe `get_user` refers to unknown field \
+                    `user_id`"
+                ),
+                diagnostic(
+                    23,
+                    "warning: Recipe `deploy` refers to field `region`, \
+                    which profile `local` doesn't define"
+                ),
+                diagnostic(
+                    23,
+                    "warning: Recipe `deploy` refers to field `token`, \
+                    which profile `prod` doesn't define"
+                ),
+                diagnostic(
+                    23,
+                    "warning: Recipe `deploy` can't be built with any \
+                    profile; each one is missing a field it uses"
+                ),
+            ]
+        );
+    }
+
+    /// A profile's own templates are only checked against the profiles that
+    /// can render them
+    #[rstest]
+    fn test_lint_profile_template(temp_dir: TempDir) {
+        let source = r#"profiles:
+  base:
+    data:
+      url: "{{host}}/api"
+  local:
+    extends: base
+    data:
+      host: http://localhost
+  other:
+    data:
+      host: http://other
+requests:
+  get: !request
+    method: GET
+    url: "{{url}}"
+"#;
+        assert_eq!(
+            lint_files(&temp_dir, &[("slumber.yml", source)]),
+            vec![
+                (
+                    "slumber.yml".into(),
+                    Some(2),
+                    "warning: Profile `base` refers to field `host`, which \
+                    profile `base` doesn't define"
+                        .into()
+                ),
+                (
+                    "slumber.yml".into(),
+                    Some(13),
+                    "warning: Recipe `get` refers to field `url`, which \
+                    profile `other` doesn't define"
+                        .into()
+                ),
+            ]
+        );
+    }
+
+    /// Load errors are reported on their own, in the file and on the line
+    /// that caused them
+    #[rstest]
+    #[case::syntax(
+        "requests:\n  login: !request\n    method: GET\n   url: /x\n",
+        "slumber.yml",
+        Some(4)
+    )]
+    // Deserialization errors don't have a location, so the item is found
+    #[case::invalid_method(
+        "requests:
+  users: !folder
+    requests:
+      login: !request
+        method: GETT
+        url: /x
+",
+        "slumber.yml",
+        Some(4)
+    )]
+    #[case::import("imports: [common.yml]", "common.yml", Some(2))]
+    #[case::cycle("imports: [slumber.yml]", "slumber.yml", None)]
+    fn test_lint_load_error(
+        temp_dir: TempDir,
+        #[case] source: &str,
+        #[case] expected_file: &str,
+        #[case] expected_line: Option<usize>,
+    ) {
+        let common = r#"chains:
+  token:
+    source: !file
+      path: token.txt
+    selector: "$.[[["
+"#;
+        let diagnostics = lint_files(
+            &temp_dir,
+            &[("slumber.yml", source), ("common.yml", common)],
+        );
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        let (file, line, text) = &diagnostics[0];
+        assert_eq!((file.as_str(), *line), (expected_file, expected_line));
+        assert!(text.starts_with("error: "), "{text}");
+    }
+
+    /// Items can be found in imported files, and in flow style
+    #[rstest]
+    #[case::profile(ReferenceLocation::Profile("local".into()), "slumber.yml", 3)]
+    #[case::folder(ReferenceLocation::Recipe("users".into()), "common.yml", 2)]
+    #[case::nested(ReferenceLocation::Recipe("login".into()), "common.yml", 4)]
+    #[case::flow(ReferenceLocation::Chain("token".into()), "slumber.yml", 5)]
+    fn test_find_item(
+        temp_dir: TempDir,
+        #[case] location: ReferenceLocation,
+        #[case] expected_file: &str,
+        #[case] expected_line: usize,
+    ) {
+        let source = "imports: [common.yml]
+profiles:
+  local:
+    data: {}
+chains: {token: {source: !command {command: [echo]}}}
+";
+        let common = "requests:
+  users: !folder
+    requests:
+      login: !request
+        method: GET
+        url: http://localhost/login
+";
+        fs::write(temp_dir.join("slumber.yml"), source).unwrap();
+        fs::write(temp_dir.join("common.yml"), common).unwrap();
+        let path = temp_dir.join("slumber.yml");
+        let collection = load_with_imports(&path).unwrap();
+
+        let (file, line) = find_item(&path, &collection, &location).unwrap();
+        assert_eq!(
+            (file.file_name().unwrap().to_str().unwrap(), line),
+            (expected_file, expected_line)
+        );
+    }
+}
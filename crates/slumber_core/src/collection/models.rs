@@ -4,16 +4,22 @@ use crate::{
     collection::{
         cereal,
         recipe_tree::{RecipeNode, RecipeTree},
+        schema,
+    },
+    http::{
+        content_type::ContentType,
+        query::{Pattern, Query, Selector},
     },
-    http::{content_type::ContentType, query::Query},
     template::{Identifier, Template},
 };
 use anyhow::anyhow;
 use derive_more::{Deref, Display, From, FromStr};
 use indexmap::IndexMap;
 use itertools::Itertools;
+use reqwest::StatusCode;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 use strum::{EnumIter, IntoEnumIterator};
 
 /// A collection of profiles, requests, etc. This is the primary Slumber unit
@@ -21,10 +27,16 @@ use strum::{EnumIter, IntoEnumIterator};
 ///
 /// This deliberately does not implement `Clone`, because it could potentially
 /// be very large. Instead, it's hidden behind an `Arc` by `CollectionFile`.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Collection {
+    /// Other collection files whose profiles, chains, and recipes should be
+    /// merged into this one, relative to this file. After loading, this holds
+    /// the resolved path of every file that was imported, directly or
+    /// indirectly.
+    #[serde(default, skip_serializing)]
+    pub imports: Vec<PathBuf>,
     #[serde(default, deserialize_with = "cereal::deserialize_id_map")]
     pub profiles: IndexMap<ProfileId, Profile>,
     #[serde(default, deserialize_with = "cereal::deserialize_id_map")]
@@ -32,24 +44,54 @@ pub struct Collection {
     /// Internally we call these recipes, but to a user `requests` is more
     /// intuitive
     #[serde(default, rename = "requests")]
+    #[schemars(description = "Request recipes and folders, keyed by ID")]
     pub recipes: RecipeTree,
+    /// Values inherited by every recipe in the collection
+    #[serde(default, skip_serializing_if = "RecipeDefaults::is_empty")]
+    pub defaults: RecipeDefaults,
+    /// Path to an OpenAPI spec describing the API, relative to the current
+    /// directory. If given, responses are validated against the spec
+    pub openapi: Option<PathBuf>,
+    /// Options for environment variables loaded with `{{env.VARIABLE}}`,
+    /// keyed by variable name
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub environment: IndexMap<String, EnvironmentVariable>,
+    /// Test cases for templates in the collection, keyed by name. Run by
+    /// `slumber test --templates`
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub template_tests: IndexMap<String, TemplateTest>,
     /// A hack-ish to allow users to add arbitrary data to their collection
     /// file without triggering a unknown field error. Ideally we could
     /// ignore anything that starts with `.` (recursively) but that
     /// requires a custom serde impl for each type, or changes to the macro
     #[serde(default, skip_serializing, rename = ".ignore")]
+    #[schemars(
+        with = "serde_json::Value",
+        description = "Arbitrary data, e.g. for YAML anchors. Not validated"
+    )]
     pub _ignore: serde::de::IgnoredAny,
 }
 
 /// Mutually exclusive hot-swappable config group
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Profile {
     #[serde(skip)] // This will be auto-populated from the map key
     pub id: ProfileId,
     pub name: Option<String>,
+    /// Profile to inherit data fields from. Fields in this profile override
+    /// fields of the same name in the parent
+    pub extends: Option<ProfileId>,
     pub data: IndexMap<String, Template>,
+    /// Client certificate to use for all requests under this profile. Can be
+    /// overridden per-recipe
+    pub certificate: Option<ClientCertificate>,
+    /// SSH tunnel to open before sending any request under this profile
+    pub tunnel: Option<SshTunnel>,
+    /// Seed for `fake.*` template keys, so the same values are generated
+    /// every time
+    pub fake_seed: Option<u64>,
 }
 
 impl Profile {
@@ -65,7 +107,11 @@ impl crate::test_util::Factory for Profile {
         Self {
             id: ProfileId::factory(()),
             name: None,
+            extends: None,
             data: IndexMap::new(),
+            certificate: None,
+            tunnel: None,
+            fake_seed: None,
         }
     }
 }
@@ -82,6 +128,7 @@ impl crate::test_util::Factory for Profile {
     PartialEq,
     Serialize,
     Deserialize,
+    JsonSchema,
 )]
 pub struct ProfileId(String);
 
@@ -100,7 +147,7 @@ impl crate::test_util::Factory for ProfileId {
 }
 
 /// A gathering of like-minded recipes and/or folders
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Folder {
@@ -113,7 +160,13 @@ pub struct Folder {
         deserialize_with = "cereal::deserialize_id_map",
         rename = "requests"
     )]
+    #[schemars(description = "Request recipes and folders, keyed by ID")]
     pub children: IndexMap<RecipeId, RecipeNode>,
+    /// Values inherited by every recipe in this folder, including nested
+    /// folders. These take precedence over defaults from parent folders and
+    /// the collection.
+    #[serde(default, skip_serializing_if = "RecipeDefaults::is_empty")]
+    pub defaults: RecipeDefaults,
 }
 
 impl Folder {
@@ -130,10 +183,25 @@ impl crate::test_util::Factory for Folder {
             id: RecipeId::factory(()),
             name: None,
             children: IndexMap::new(),
+            defaults: RecipeDefaults::default(),
         }
     }
 }
 
+/// Request fields shared by a group of recipes, defined on a folder or the
+/// collection root. A recipe inherits each of these that it doesn't set
+/// itself.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct RecipeDefaults {
+    /// Prefix for any recipe URL that starts with `/`
+    pub base_url: Option<Template>,
+    pub authentication: Option<Authentication>,
+    #[serde(default)]
+    pub headers: IndexMap<String, Template>,
+}
+
 impl Recipe {
     /// Get a presentable name for this recipe
     pub fn name(&self) -> &str {
@@ -151,8 +219,16 @@ impl crate::test_util::Factory for Recipe {
             url: "http://localhost/url".into(),
             body: None,
             authentication: None,
+            signature: None,
+            certificate: None,
+            danger_skip_tls_verify: false,
+            baseline_ignore: vec![],
+            assertions: vec![],
+            display_query: None,
+            example: None,
             query: Vec::new(),
             headers: IndexMap::new(),
+            parameters: IndexMap::new(),
         }
     }
 }
@@ -172,7 +248,7 @@ impl crate::test_util::Factory<&str> for Recipe {
 /// order to distinguish it from a single instance of an HTTP request. And it's
 /// not called `RequestTemplate` because the word "template" has a specific
 /// meaning related to string interpolation.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Recipe {
@@ -186,13 +262,163 @@ pub struct Recipe {
     pub url: Template,
     pub body: Option<RecipeBody>,
     pub authentication: Option<Authentication>,
+    /// Sign the body and attach the signature as a header
+    pub signature: Option<BodySignature>,
+    /// Client certificate for mTLS. Takes precedence over the profile's
+    /// certificate
+    pub certificate: Option<ClientCertificate>,
+    /// Ignore TLS certificate errors for this recipe. Be careful!
+    #[serde(default)]
+    pub danger_skip_tls_verify: bool,
+    /// Values to exclude when comparing a response to the pinned baseline,
+    /// e.g. timestamps and IDs
+    #[serde(default)]
+    pub baseline_ignore: Vec<Query>,
+    /// Expectations to check against every response
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+    /// Query applied to every response before it's shown in the TUI, e.g. to
+    /// hide an envelope around the interesting data. The raw body can still
+    /// be viewed.
+    pub display_query: Option<Query>,
+    /// Canned response to use when this recipe has no response in history.
+    /// Shown in the TUI, loaded by chains, and sent by `slumber serve`
+    pub example: Option<ResponseExample>,
     #[serde(
         default,
+        serialize_with = "cereal::serialize_query_parameters",
         deserialize_with = "cereal::deserialize_query_parameters"
     )]
+    #[schemars(schema_with = "schema::query_parameters")]
     pub query: Vec<(String, Template)>,
     #[serde(default)]
     pub headers: IndexMap<String, Template>,
+    /// Named inputs that are given a value each time the recipe is sent,
+    /// rather than defined in a profile. Referenced in templates just like
+    /// profile fields, which they take precedence over.
+    #[serde(default)]
+    pub parameters: IndexMap<String, RecipeParameter>,
+}
+
+/// A canned response for a recipe, used in place of a real one. The body can
+/// be given inline, or loaded from a fixture `file`, but not both.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct ResponseExample {
+    /// Defaults to 200
+    #[serde(default, with = "crate::http::cereal::serde_status_code")]
+    #[schemars(with = "u16")]
+    pub status: StatusCode,
+    #[serde(default)]
+    pub headers: IndexMap<String, String>,
+    #[serde(default)]
+    pub body: String,
+    /// Load the body from this file, relative to the current directory. If
+    /// there's no `Content-Type` header, it's guessed from the extension.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+}
+
+/// Options for an environment variable loaded with `{{env.VARIABLE}}`. By
+/// default, an unset variable renders as an empty string. `default` and
+/// `required` change that, and can't be combined.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentVariable {
+    /// Value to use if the variable is unset
+    pub default: Option<String>,
+    /// If given, rendering fails with this message if the variable is unset,
+    /// e.g. to explain where to get the value
+    pub required: Option<String>,
+    /// Mask the value in the UI
+    #[serde(default)]
+    pub sensitive: bool,
+}
+
+/// A template to render against fixed inputs, and a pattern the result must
+/// match. Rendering never touches the network or the terminal: chains load
+/// their responses from `responses`, and prompts are answered from `prompts`.
+/// See [crate::template::TemplateContextBuilder] for evaluation.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct TemplateTest {
+    pub template: Template,
+    /// Profile to pull field values from. If omitted, no profile is selected
+    pub profile: Option<ProfileId>,
+    /// Values for fields, chains, etc., keyed the same as in the template
+    #[serde(default)]
+    pub overrides: IndexMap<String, String>,
+    /// Answers for prompts, in the order they're asked
+    #[serde(default)]
+    pub prompts: Vec<String>,
+    /// Fixture responses for request chains to load, keyed by recipe ID
+    #[serde(default)]
+    pub responses: IndexMap<RecipeId, ResponseExample>,
+    /// Regex the rendered template must match. Use `^` and `$` to match the
+    /// entire output
+    pub expect: Pattern,
+}
+
+/// An input to a recipe, given a value when the request is sent. Unlike a
+/// prompt, all parameters are collected up front, and they're validated
+/// against their type before the request is built.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct RecipeParameter {
+    #[serde(default, rename = "type")]
+    pub kind: ParameterType,
+    /// Value to use when none is given. If there's no default, a value is
+    /// required
+    pub default: Option<Template>,
+    /// Explanation of the parameter, to show in the send form
+    pub description: Option<String>,
+}
+
+/// The type of value a [RecipeParameter] accepts. Values are always passed to
+/// templates as strings; this is just for validation.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Display,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ParameterType {
+    #[default]
+    #[display("string")]
+    String,
+    #[display("integer")]
+    Integer,
+    #[display("number")]
+    Number,
+    #[display("boolean")]
+    Boolean,
+}
+
+impl ParameterType {
+    /// Check that a value is valid for this type
+    pub fn validate(self, value: &str) -> anyhow::Result<()> {
+        let valid = match self {
+            Self::String => true,
+            Self::Integer => value.parse::<i64>().is_ok(),
+            Self::Number => value.parse::<f64>().is_ok(),
+            Self::Boolean => matches!(value, "true" | "false"),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(anyhow!("Expected {self}, got `{value}`"))
+        }
+    }
 }
 
 #[derive(
@@ -207,10 +433,52 @@ pub struct Recipe {
     PartialEq,
     Serialize,
     Deserialize,
+    JsonSchema,
 )]
 pub struct RecipeId(String);
 
-#[cfg(any(test, feature = "test"))]
+impl RecipeId {
+    /// ID given to one-off requests that aren't defined in the collection.
+    /// Their history is stored under this ID, like any other recipe.
+    pub fn scratch() -> Self {
+        "_scratch".into()
+    }
+}
+
+impl RecipeId {
+    /// Generate an ID for a recipe imported from an external format, from its
+    /// method and the last meaningful segment of its URL path, e.g.
+    /// `get_users` for `GET https://example.com/users/3`. Segments without
+    /// letters are generally IDs, so they're skipped.
+    pub(super) fn generate(method: Method, url: &str) -> Self {
+        let path = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let mut segments = path.split('/');
+        let host = segments.next().unwrap_or_default();
+        let name = segments
+            .rev()
+            .find(|segment| segment.chars().any(char::is_alphabetic))
+            .unwrap_or(host);
+        let name: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let name = name.trim_matches('_');
+        let method = method.to_string().to_lowercase();
+        if name.is_empty() {
+            method.into()
+        } else {
+            format!("{method}_{name}").into()
+        }
+    }
+}
+
 impl From<&str> for RecipeId {
     fn from(value: &str) -> Self {
         value.to_owned().into()
@@ -224,6 +492,40 @@ impl crate::test_util::Factory for RecipeId {
     }
 }
 
+/// An expectation about a response, declared on a recipe. Each response for
+/// the recipe is checked against all of its assertions. See
+/// [crate::http::assertion] for evaluation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Assertion {
+    /// Status code must match exactly
+    Status(u16),
+    /// Header must be present. If a pattern is given, the value must match it
+    Header {
+        name: String,
+        #[serde(default)]
+        matches: Option<Pattern>,
+    },
+    /// Query the body (as JSON) with JSONPath. The query must match at least
+    /// one value. If multiple values match, they're compared as an array.
+    JsonPath {
+        query: Query,
+        /// Matched value must be equal to this
+        #[serde(default)]
+        equals: Option<serde_json::Value>,
+        /// Matched string must contain this substring, or matched array must
+        /// contain this element
+        #[serde(default)]
+        contains: Option<serde_json::Value>,
+    },
+    /// Response must be received within this duration
+    Latency(
+        #[serde(with = "cereal::serde_duration")]
+        #[schemars(with = "String")]
+        Duration,
+    ),
+}
+
 /// HTTP method. This is duplicated from reqwest's Method so we can enforce
 /// the method is valid during deserialization. This is also generally more
 /// ergonomic at the cost of some flexibility.
@@ -276,6 +578,9 @@ impl crate::test_util::Factory for Chain {
             selector: None,
             content_type: None,
             trim: ChainOutputTrim::default(),
+            cache: None,
+            exclusive: false,
+            example: None,
         }
     }
 }
@@ -286,7 +591,7 @@ impl crate::test_util::Factory for Chain {
 ///
 /// Type parameter allows this to be re-used for post-render purposes (with
 /// `T=String`).
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(any(test, feature = "test"), derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum Authentication<T = Template> {
@@ -294,6 +599,85 @@ pub enum Authentication<T = Template> {
     Basic { username: T, password: Option<T> },
     /// `Authorization: Bearer {token}`
     Bearer(T),
+    /// AWS Signature Version 4. Unlike the other schemes, this signs the
+    /// entire request, so it's applied after the request is otherwise fully
+    /// built. If an access key isn't given, credentials are loaded from the
+    /// standard AWS credential chain.
+    AwsSigv4 {
+        region: T,
+        service: T,
+        access_key_id: Option<T>,
+        secret_access_key: Option<T>,
+        session_token: Option<T>,
+        /// AWS profile to load credentials from. Only used if an access key
+        /// isn't given
+        profile: Option<T>,
+    },
+}
+
+/// Sign the request body with HMAC-SHA256 and attach the signature as a header,
+/// the same way webhook senders do. This makes it possible to test webhook
+/// receivers that verify signatures. The signature is computed over the final
+/// rendered body.
+///
+/// Type parameter allows this to be re-used for post-render purposes (with
+/// `T=String`).
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(any(test, feature = "test"), derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum BodySignature<T = Template> {
+    /// `Stripe-Signature: t={timestamp},v1={hex HMAC of "{timestamp}.{body}"}`
+    Stripe {
+        secret: T,
+        /// Unix timestamp to sign. If omitted, use the current time
+        timestamp: Option<T>,
+    },
+    /// `X-Hub-Signature-256: sha256={hex HMAC of body}`
+    Github { secret: T },
+    /// `{header}: {hex HMAC of body}`
+    Hmac { secret: T, header: T },
+}
+
+/// A client certificate and private key, used to identify the client to servers
+/// that require mutual TLS (mTLS). All paths are templates, so they can be
+/// pulled from the profile or a chain.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ClientCertificate {
+    /// PEM-encoded certificate (chain) and private key. If the key is in the
+    /// same file as the certificate, the key path can be omitted.
+    Pem {
+        certificate: Template,
+        key: Option<Template>,
+    },
+    /// PKCS#12 (`.p12`/`.pfx`) archive containing the certificate and key
+    Pkcs12 {
+        path: Template,
+        password: Option<Template>,
+    },
+}
+
+/// An SSH local port forward, for reaching hosts that are only accessible
+/// through a bastion. The tunnel is opened with the system `ssh` binary, so the
+/// user's SSH config, keys, and agent all apply. Requests should target
+/// `localhost:{local_port}`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct SshTunnel {
+    /// SSH destination, e.g. `user@bastion.example.com` or a host alias from
+    /// the SSH config
+    pub host: Template,
+    /// Jump host(s) to connect through first, as accepted by `ssh -J`
+    pub jump: Option<Template>,
+    /// Local port to listen on
+    pub local_port: u16,
+    /// Host to forward to, as resolved by the SSH host. Defaults to
+    /// `localhost`, i.e. the SSH host itself
+    pub remote_host: Option<Template>,
+    /// Port to forward to on the remote host
+    pub remote_port: u16,
 }
 
 /// Template for a request body. `Raw` is the "default" variant, which repesents
@@ -327,6 +711,62 @@ impl RecipeBody {
             content_type: Some(ContentType::Json),
         }
     }
+
+    /// Build a body from text *without* parsing it as a template, using a
+    /// structured body if the content type is one we support (JSON or a
+    /// URL-encoded form) and the text is valid for it. Useful for importing
+    /// from external formats. Slumber sets the `Content-Type` header for
+    /// structured bodies, so the second value indicates whether an existing
+    /// header should be dropped.
+    pub(super) fn untemplated_text(
+        content_type: &str,
+        text: String,
+    ) -> (Self, bool) {
+        Self::translated_text(content_type, text, Template::raw)
+    }
+
+    /// Like [Self::untemplated_text], but each piece of text (the whole body,
+    /// or each form value) is converted to a template with the given
+    /// function. Useful for importing from formats with their own template
+    /// syntax.
+    pub(super) fn translated_text(
+        content_type: &str,
+        text: String,
+        mut translate: impl FnMut(String) -> Template,
+    ) -> (Self, bool) {
+        let is_form = content_type.parse::<mime::Mime>().is_ok_and(|mime| {
+            mime.essence_str()
+                == mime::APPLICATION_WWW_FORM_URLENCODED.essence_str()
+        });
+        if is_form {
+            // Every pair needs a `=`, otherwise it's not really a form
+            if text.split('&').all(|pair| pair.contains('=')) {
+                let fields = url::form_urlencoded::parse(text.as_bytes())
+                    .map(|(name, value)| {
+                        (name.into_owned(), translate(value.into_owned()))
+                    })
+                    .collect();
+                return (Self::FormUrlencoded(fields), true);
+            }
+        } else if ContentType::from_mime(content_type).ok()
+            == Some(ContentType::Json)
+        {
+            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
+                let body = Self::Raw {
+                    body: translate(format!("{json:#}")),
+                    content_type: Some(ContentType::Json),
+                };
+                return (body, true);
+            }
+        }
+        (
+            Self::Raw {
+                body: translate(text),
+                content_type: None,
+            },
+            false,
+        )
+    }
 }
 
 #[cfg(any(test, feature = "test"))]
@@ -342,7 +782,7 @@ impl From<&str> for RecipeBody {
 /// A chain is a means to data from one response in another request. The chain
 /// is the middleman: it defines where and how to pull the value, then recipes
 /// can use it in a template via `{{chains.<chain_id>}}`.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Chain {
@@ -352,10 +792,10 @@ pub struct Chain {
     /// Mask chained value in the UI
     #[serde(default)]
     pub sensitive: bool,
-    /// Selector to extract a value from the response. This uses JSONPath
-    /// regardless of the content type. Non-JSON values will be converted to
-    /// JSON, then converted back.
-    pub selector: Option<Query>,
+    /// Selector to extract a value from the response. JSONPath selectors work
+    /// with any known content type; non-JSON values will be converted to
+    /// JSON, then converted back. XPath selectors are applied to XML/HTML.
+    pub selector: Option<Selector>,
     /// Hard-code the content type of the response. Only needed if a selector
     /// is given and the content type can't be dynamically determined
     /// correctly. This is needed if the chain source is not an HTTP
@@ -364,6 +804,22 @@ pub struct Chain {
     pub content_type: Option<ContentType>,
     #[serde(default)]
     pub trim: ChainOutputTrim,
+    /// Store the rendered value in the database and re-use it until it's this
+    /// old. Useful for expensive or interactive sources. Values are cached per
+    /// profile.
+    #[serde(default, with = "cereal::serde_duration_option")]
+    #[schemars(with = "Option<String>")]
+    pub cache: Option<Duration>,
+    /// Never compute this chain for more than one render at a time, even
+    /// across separate requests. Useful for sources with side effects, like
+    /// incrementing a counter or minting a single-use token.
+    #[serde(default)]
+    pub exclusive: bool,
+    /// Value to use in offline mode, instead of computing the chain from its
+    /// source. The source isn't touched at all, so commands aren't run, files
+    /// aren't read, and requests aren't triggered. The selector and trim
+    /// aren't applied either; this is the final value.
+    pub example: Option<Template>,
 }
 
 /// Unique ID for a chain, provided by the user
@@ -379,9 +835,14 @@ pub struct Chain {
     PartialEq,
     Serialize,
     Deserialize,
+    JsonSchema,
 )]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
-pub struct ChainId(#[deref(forward)] Identifier);
+pub struct ChainId(
+    #[deref(forward)]
+    #[schemars(with = "String")]
+    Identifier,
+);
 
 impl<T: Into<Identifier>> From<T> for ChainId {
     fn from(value: T) -> Self {
@@ -390,20 +851,86 @@ impl<T: Into<Identifier>> From<T> for ChainId {
 }
 
 /// The source of data for a chain
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum ChainSource {
+    /// Load AWS credentials from the standard credential chain
+    AwsCredentials {
+        /// AWS profile to load credentials for. If omitted, use the default
+        /// resolution (`AWS_PROFILE` or `default`)
+        profile: Option<Template>,
+        /// Which part of the credentials to use
+        field: AwsCredentialField,
+    },
+    /// Get a Microsoft Entra ID (Azure AD) access token, using the `az` CLI
+    AzureToken {
+        /// OAuth scope to request the token for. If omitted, use Azure Resource
+        /// Manager
+        scope: Option<Template>,
+        /// Tenant to get the token for. If omitted, use the account's default
+        tenant: Option<Template>,
+    },
     /// Run an external command to get a result
     Command {
         command: Vec<Template>,
         stdin: Option<Template>,
     },
+    /// Resolve a service to the `host:port` of a healthy instance, using Consul
+    /// service discovery
+    Consul {
+        /// Name of the service to resolve
+        service: Template,
+        /// Only consider instances with this tag
+        tag: Option<Template>,
+        /// Address of the Consul agent. If omitted, use `CONSUL_HTTP_ADDR`, or
+        /// the local agent
+        address: Option<Template>,
+    },
     /// Load from an environment variable
     #[serde(rename = "env")]
     Environment { variable: Template },
+    /// Load a variable from a dotenv (`.env`) file, without exporting it to
+    /// the environment
+    EnvFile {
+        /// Path of the file to load
+        path: Template,
+        /// Variable to load from the file
+        variable: Template,
+    },
     /// Load data from a file
     File { path: Template },
+    /// Mint a Google Cloud token, using the `gcloud` CLI
+    GcpToken {
+        /// Type of token to mint
+        #[serde(default)]
+        token_type: GcpTokenType,
+        /// Audience to mint an ID token for. Not supported for access tokens
+        audience: Option<Template>,
+    },
+    /// Load a secret from the OS secret store (keychain)
+    Keyring {
+        /// Service name the secret is stored under
+        service: Template,
+        /// User name the secret is stored under
+        user: Template,
+    },
+    /// Load a single key from a Kubernetes Secret or ConfigMap, using
+    /// `kubectl` and the user's kubeconfig
+    Kubernetes {
+        /// Type of resource to load from
+        #[serde(default)]
+        kind: KubernetesResourceKind,
+        /// Name of the Secret/ConfigMap
+        name: Template,
+        /// Key within the resource's data
+        key: Template,
+        /// Namespace containing the resource. If omitted, use the default
+        /// namespace of the kubeconfig context
+        namespace: Option<Template>,
+        /// kubeconfig context to use. If omitted, use the current context
+        context: Option<Template>,
+    },
     /// Prompt the user for a value
     Prompt {
         /// Descriptor to show to the user
@@ -411,6 +938,18 @@ pub enum ChainSource {
         /// Default value for the shown textbox
         default: Option<Template>,
     },
+    /// Pick a random value from a list, optionally weighted
+    Random {
+        /// Values to pick from. Each can be given a relative weight
+        #[serde(default)]
+        values: Vec<RandomChoice>,
+        /// File to load additional values from, one per line, each with a
+        /// weight of 1. Blank lines are skipped
+        file: Option<Template>,
+        /// Seed for the random number generator, to pick the same value
+        /// every time
+        seed: Option<u64>,
+    },
     /// Load data from the most recent response of a particular request recipe
     Request {
         recipe: RecipeId,
@@ -420,6 +959,17 @@ pub enum ChainSource {
         #[serde(default)]
         section: ChainRequestSection,
     },
+    /// Cycle through a list of values, using the next one each time the chain
+    /// is rendered. The position is persisted per chain, so it carries over
+    /// between sessions
+    Rotate {
+        /// Values to cycle through
+        #[serde(default)]
+        values: Vec<Template>,
+        /// File to load additional values from, one per line. Blank lines are
+        /// skipped. Lines come after `values` in the rotation
+        file: Option<Template>,
+    },
     /// Prompt the user to select a value from a list
     Select {
         /// Descriptor to show to the user
@@ -429,6 +979,33 @@ pub enum ChainSource {
     },
 }
 
+/// A value for a `!random` chain to pick from
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(untagged, deny_unknown_fields)]
+pub enum RandomChoice {
+    /// A value with the default weight of 1
+    Value(Template),
+    /// A value with a relative weight. A value with weight 2 is picked twice
+    /// as often as one with weight 1
+    Weighted { value: Template, weight: u32 },
+}
+
+impl RandomChoice {
+    pub fn value(&self) -> &Template {
+        match self {
+            Self::Value(value) | Self::Weighted { value, .. } => value,
+        }
+    }
+
+    pub fn weight(&self) -> u32 {
+        match self {
+            Self::Value(_) => 1,
+            Self::Weighted { weight, .. } => *weight,
+        }
+    }
+}
+
 /// Test-only helpers
 #[cfg(any(test, feature = "test"))]
 impl ChainSource {
@@ -442,7 +1019,7 @@ impl ChainSource {
 }
 
 /// The component of the response to use as the chain source
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum ChainRequestSection {
@@ -451,11 +1028,53 @@ pub enum ChainRequestSection {
     /// Pull a value from a response's headers. If the given header appears
     /// multiple times, the first value will be used
     Header(Template),
+    /// The response's status code, e.g. `201`
+    Status,
+}
+
+/// A single component of a set of AWS credentials
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum AwsCredentialField {
+    AccessKeyId,
+    SecretAccessKey,
+    /// Only present for temporary credentials. Renders to an empty string if
+    /// the credentials have no session token
+    SessionToken,
+}
+
+/// The type of token to get from Google Cloud
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum GcpTokenType {
+    /// OAuth 2.0 access token from Application Default Credentials, for
+    /// Google APIs
+    #[default]
+    AccessToken,
+    /// OpenID Connect ID token, for IAP and Cloud Run/Functions
+    IdToken,
+}
+
+/// The type of Kubernetes resource that a chain loads data from
+#[derive(
+    Copy, Clone, Debug, Default, Display, Serialize, Deserialize, JsonSchema,
+)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum KubernetesResourceKind {
+    /// Values are stored base64-encoded, and will be decoded automatically
+    #[default]
+    #[display("Secret")]
+    Secret,
+    #[display("ConfigMap")]
+    ConfigMap,
 }
 
 /// Define when a recipe with a chained request should auto-execute the
 /// dependency request.
-#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum ChainRequestTrigger {
@@ -468,13 +1087,17 @@ pub enum ChainRequestTrigger {
     NoHistory,
     /// Trigger the request if the last response is older than some
     /// duration (or there is none in history)
-    Expire(#[serde(with = "cereal::serde_duration")] Duration),
+    Expire(
+        #[serde(with = "cereal::serde_duration")]
+        #[schemars(with = "String")]
+        Duration,
+    ),
     /// Trigger the request every time the dependent request is rendered
     Always,
 }
 
 /// Trim whitespace from rendered output
-#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum ChainOutputTrim {
@@ -8,13 +8,13 @@
 //! - References are resolved within the same file. We don't support resolving
 //!   from other files.
 
-mod resolve;
+pub(crate) mod resolve;
 
 use crate::{
     collection::{
         openapi::resolve::ReferenceResolver, Authentication, Collection,
         DuplicateRecipeIdError, Folder, Method, Profile, ProfileId, Recipe,
-        RecipeBody, RecipeId, RecipeNode, RecipeTree,
+        RecipeBody, RecipeDefaults, RecipeId, RecipeNode, RecipeTree,
     },
     template::Template,
     util::{ResultTraced, NEW_ISSUE_LINK},
@@ -74,9 +74,14 @@ impl Collection {
         let recipes = build_recipe_tree(paths, components)?;
 
         Ok(Collection {
+            imports: Vec::new(),
             profiles,
             recipes,
             chains: IndexMap::new(),
+            defaults: RecipeDefaults::default(),
+            openapi: None,
+            environment: IndexMap::new(),
+            template_tests: IndexMap::new(),
             _ignore: serde::de::IgnoredAny,
         })
     }
@@ -106,7 +111,11 @@ fn build_profiles(servers: Vec<Server>) -> IndexMap<ProfileId, Profile> {
                     // will be the same value, but we provide it for
                     // discoverability; the user may want to rename it
                     name: Some(url),
+                    extends: None,
                     data,
+                    certificate: None,
+                    tunnel: None,
+                    fake_seed: None,
                 },
             )
         })
@@ -154,6 +163,7 @@ fn build_recipe_tree(
                     id: folder_id,
                     name: Some(tag),
                     children: IndexMap::default(),
+                    defaults: RecipeDefaults::default(),
                 }
                 .into()
             });
@@ -305,6 +315,14 @@ impl<'a> RecipeBuilder<'a> {
             url,
             body: builder.body,
             authentication: builder.authentication,
+            signature: None,
+            certificate: None,
+            danger_skip_tls_verify: false,
+            baseline_ignore: vec![],
+            assertions: vec![],
+            display_query: None,
+            example: None,
+            parameters: IndexMap::new(),
             query: builder.query,
             headers: builder.headers,
         }
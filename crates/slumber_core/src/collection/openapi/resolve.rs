@@ -8,10 +8,10 @@
 
 use indexmap::IndexMap;
 use openapiv3::{
-    Components, Example, Parameter, ReferenceOr, RequestBody, Schema,
-    SecurityScheme,
+    Components, Example, Header, Parameter, ReferenceOr, RequestBody, Response,
+    Schema, SecurityScheme,
 };
-use std::borrow::Cow;
+use std::borrow::{Borrow, Cow};
 use thiserror::Error;
 use winnow::{
     combinator::{preceded, rest},
@@ -24,7 +24,7 @@ use winnow::{
 pub struct ReferenceResolver(Components);
 
 /// An error that can occur while resolving a reference
-#[derive(Debug, Error, PartialEq, Eq)]
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
 pub enum ResolveError {
     #[error("`{_0}` refers to an object that does not exist in the schema")]
     UnknownReference(String),
@@ -62,6 +62,16 @@ impl ComponentKind for Example {
     }
 }
 
+impl ComponentKind for Header {
+    const TYPE_NAME: &'static str = "headers";
+
+    fn get_components(
+        components: &Components,
+    ) -> &IndexMap<String, ReferenceOr<Self>> {
+        &components.headers
+    }
+}
+
 impl ComponentKind for Parameter {
     const TYPE_NAME: &'static str = "parameters";
 
@@ -82,6 +92,16 @@ impl ComponentKind for RequestBody {
     }
 }
 
+impl ComponentKind for Response {
+    const TYPE_NAME: &'static str = "responses";
+
+    fn get_components(
+        components: &Components,
+    ) -> &IndexMap<String, ReferenceOr<Self>> {
+        &components.responses
+    }
+}
+
 impl ComponentKind for Schema {
     const TYPE_NAME: &'static str = "schemas";
 
@@ -139,6 +159,20 @@ impl ReferenceResolver {
         }
     }
 
+    /// Resolve a borrowed [ReferenceOr] into a reference to the contained item.
+    /// The item can be boxed, as schemas often are.
+    pub fn resolve_ref<'a, T: ComponentKind, B: Borrow<T>>(
+        &'a self,
+        reference_or: &'a ReferenceOr<B>,
+    ) -> Result<&'a T, ResolveError> {
+        match reference_or {
+            ReferenceOr::Item(item) => Ok(item.borrow()),
+            ReferenceOr::Reference { reference } => {
+                self.get_by_reference(reference)
+            }
+        }
+    }
+
     /// Resolve a reference URI. The reference must refer to an object of a
     /// statically known type (`T`), and must be in the same file.
     fn get_by_reference<T: ComponentKind>(
@@ -0,0 +1,627 @@
+//! Export a collection to Postman's v2.1 collection format, so it can be
+//! shared with people who use Postman. Postman has no equivalent of profiles
+//! or chains, so profiles are exported as Postman environments, and each chain
+//! becomes a collection variable that has to be filled in by hand.
+
+use crate::{
+    collection::{
+        Authentication, Collection, Folder, Recipe, RecipeBody, RecipeNode,
+    },
+    http::content_type::ContentType,
+    template::{FakeKind, Filter, Template, TemplateKey},
+};
+use indexmap::IndexMap;
+use serde::Serialize;
+use tracing::{info, warn};
+
+/// Schema URL for the v2.1 collection format
+const SCHEMA: &str =
+    "https://schema.getpostman.com/json/collection/v2.1.0/collection.json";
+
+/// A collection converted to Postman's format
+#[derive(Debug)]
+pub struct PostmanExport {
+    /// Goes in a `.postman_collection.json` file
+    pub collection: PostmanCollection,
+    /// One per profile. Each goes in a `.postman_environment.json` file
+    pub environments: Vec<PostmanEnvironment>,
+}
+
+/// A Postman collection file
+#[derive(Debug, Serialize)]
+pub struct PostmanCollection {
+    info: Info,
+    item: Vec<Item>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    variable: Vec<Variable>,
+}
+
+/// A Postman environment file, exported from a profile
+#[derive(Debug, Serialize)]
+pub struct PostmanEnvironment {
+    /// Profile ID, which is safe to use as a file name
+    #[serde(skip)]
+    pub id: String,
+    name: String,
+    values: Vec<EnvironmentValue>,
+    #[serde(rename = "_postman_variable_scope")]
+    scope: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct Info {
+    name: String,
+    schema: &'static str,
+}
+
+/// A folder or request
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Item {
+    Folder { name: String, item: Vec<Item> },
+    Request { name: String, request: Request },
+}
+
+#[derive(Debug, Serialize)]
+struct Request {
+    method: String,
+    header: Vec<KeyValue>,
+    url: Url,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<Body>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth: Option<Auth>,
+}
+
+#[derive(Debug, Serialize)]
+struct Url {
+    /// The full URL, including the query string
+    raw: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    query: Vec<KeyValue>,
+}
+
+#[derive(Debug, Serialize)]
+struct KeyValue {
+    key: String,
+    value: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+enum Body {
+    Raw {
+        raw: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        options: Option<BodyOptions>,
+    },
+    Urlencoded {
+        urlencoded: Vec<FormParam>,
+    },
+    Formdata {
+        formdata: Vec<FormParam>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct BodyOptions {
+    raw: RawOptions,
+}
+
+/// Tells Postman which syntax highlighting to use for a raw body
+#[derive(Debug, Serialize)]
+struct RawOptions {
+    language: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct FormParam {
+    key: String,
+    value: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Auth {
+    Basic { basic: Vec<AuthParam> },
+    Bearer { bearer: Vec<AuthParam> },
+    Awsv4 { awsv4: Vec<AuthParam> },
+}
+
+#[derive(Debug, Serialize)]
+struct AuthParam {
+    key: &'static str,
+    value: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+/// A collection-level variable
+#[derive(Debug, Serialize)]
+struct Variable {
+    key: String,
+    value: String,
+    description: String,
+}
+
+#[derive(Debug, Serialize)]
+struct EnvironmentValue {
+    key: String,
+    value: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    enabled: bool,
+}
+
+impl Collection {
+    /// Convert this collection to a Postman collection plus environments.
+    /// Templates are translated to Postman variables where possible. Anything
+    /// that can't be translated is exported in Slumber syntax, with a warning.
+    pub fn to_postman(&self, name: &str) -> PostmanExport {
+        info!(name, "Exporting collection to Postman");
+        let collection = PostmanCollection {
+            info: Info {
+                name: name.to_owned(),
+                schema: SCHEMA,
+            },
+            item: self
+                .recipes
+                .iter()
+                // Start with the top level; folders recurse from there
+                .filter(|(key, _)| key.as_slice().len() == 1)
+                .map(|(_, node)| self.export_node(node))
+                .collect(),
+            variable: self.export_chains(),
+        };
+
+        let environments = self
+            .profiles
+            .values()
+            .map(|profile| {
+                let context = format!("profile `{}`", profile.id);
+                PostmanEnvironment {
+                    id: profile.id.to_string(),
+                    name: profile.name().to_owned(),
+                    // Postman environments don't inherit, so flatten them
+                    values: self
+                        .profile_data(&profile.id)
+                        .into_iter()
+                        .map(|(field, (_, template))| EnvironmentValue {
+                            key: field.to_owned(),
+                            value: translate(template, &context),
+                            kind: "default",
+                            enabled: true,
+                        })
+                        .collect(),
+                    scope: "environment",
+                }
+            })
+            .collect();
+
+        PostmanExport {
+            collection,
+            environments,
+        }
+    }
+
+    fn export_node(&self, node: &RecipeNode) -> Item {
+        match node {
+            RecipeNode::Folder(Folder { children, .. }) => Item::Folder {
+                name: node.name().to_owned(),
+                item: children
+                    .values()
+                    .map(|child| self.export_node(child))
+                    .collect(),
+            },
+            RecipeNode::Recipe(recipe) => Item::Request {
+                name: recipe.name().to_owned(),
+                request: self.export_recipe(recipe),
+            },
+        }
+    }
+
+    fn export_recipe(&self, recipe: &Recipe) -> Request {
+        let context = format!("recipe `{}`", recipe.id);
+        let translate = |template: &Template| translate(template, &context);
+        if recipe.signature.is_some() {
+            warn!(
+                "Body signatures aren't supported by Postman; {context} will \
+                be exported without one"
+            );
+        }
+
+        let defaults = self.recipe_defaults(&recipe.id);
+        let (url, query) = defaults.export_url(recipe, translate);
+        let query = query
+            .into_iter()
+            .map(|(key, value)| KeyValue { key, value })
+            .collect();
+
+        let header = defaults
+            .inherited_headers(recipe)
+            .chain(&recipe.headers)
+            .map(|(key, value)| KeyValue {
+                key: key.clone(),
+                value: translate(value),
+            })
+            .collect();
+
+        let body = recipe.body.as_ref().map(|body| {
+            let form = |fields: &IndexMap<String, Template>| -> Vec<_> {
+                fields
+                    .iter()
+                    .map(|(key, value)| FormParam {
+                        key: key.clone(),
+                        value: translate(value),
+                        kind: "text",
+                    })
+                    .collect()
+            };
+            match body {
+                RecipeBody::Raw { body, content_type } => Body::Raw {
+                    raw: translate(body),
+                    options: content_type.as_ref().map(|content_type| {
+                        BodyOptions {
+                            raw: RawOptions {
+                                language: match content_type {
+                                    ContentType::Json => "json",
+                                },
+                            },
+                        }
+                    }),
+                },
+                RecipeBody::FormUrlencoded(fields) => Body::Urlencoded {
+                    urlencoded: form(fields),
+                },
+                RecipeBody::FormMultipart(fields) => Body::Formdata {
+                    formdata: form(fields),
+                },
+            }
+        });
+
+        let auth = defaults.authentication(recipe).map(|authentication| {
+            let param = |key, value: &Template| AuthParam {
+                key,
+                value: translate(value),
+                kind: "string",
+            };
+            match authentication {
+                Authentication::Basic { username, password } => {
+                    let mut basic = vec![param("username", username)];
+                    basic.extend(
+                        password
+                            .as_ref()
+                            .map(|password| param("password", password)),
+                    );
+                    Auth::Basic { basic }
+                }
+                Authentication::Bearer(token) => Auth::Bearer {
+                    bearer: vec![param("token", token)],
+                },
+                Authentication::AwsSigv4 {
+                    region,
+                    service,
+                    access_key_id,
+                    secret_access_key,
+                    session_token,
+                    profile,
+                } => {
+                    if profile.is_some() {
+                        warn!(
+                            "AWS profiles aren't supported by Postman; set \
+                            the access key for {context} by hand"
+                        );
+                    }
+                    Auth::Awsv4 {
+                        awsv4: [
+                            Some(param("region", region)),
+                            Some(param("service", service)),
+                            access_key_id
+                                .as_ref()
+                                .map(|value| param("accessKey", value)),
+                            secret_access_key
+                                .as_ref()
+                                .map(|value| param("secretKey", value)),
+                            session_token
+                                .as_ref()
+                                .map(|value| param("sessionToken", value)),
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .collect(),
+                    }
+                }
+            }
+        });
+
+        Request {
+            method: recipe.method.to_string(),
+            header,
+            url: Url { raw: url, query },
+            body,
+            auth,
+        }
+    }
+
+    /// Postman can't compute chains, so each one becomes an empty variable.
+    /// Its description includes the chain's definition, so the user knows
+    /// what to fill in.
+    fn export_chains(&self) -> Vec<Variable> {
+        self.chains
+            .values()
+            .map(|chain| {
+                let definition = serde_yaml::to_string(chain)
+                    .unwrap_or_else(|error| error.to_string());
+                Variable {
+                    key: format!("chains.{}", chain.id),
+                    value: String::new(),
+                    description: format!(
+                        "Computed by the Slumber chain `{}`, which Postman \
+                        can't run. Set it by hand or with a script. \
+                        Definition:\n\n{definition}",
+                        chain.id
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Translate a template to a Postman string, warning about anything that
+/// can't be translated. `context` describes where the template is, for the
+/// warning.
+fn translate(template: &Template, context: &str) -> String {
+    let (translated, untranslated) = template.translate(translate_key);
+    for source in untranslated {
+        warn!(
+            "`{source}` in {context} can't be translated to Postman; it will \
+            be exported as-is"
+        );
+    }
+    translated
+}
+
+/// Translate a single template key to a Postman variable. Fields keep their
+/// name, and chains are referenced by a variable of the same name (see
+/// [Collection::export_chains]). Some functions and fake data have Postman
+/// equivalents in its dynamic variables. Filters have no equivalent.
+fn translate_key(key: &TemplateKey, filters: &[Filter]) -> Option<String> {
+    if !filters.is_empty() {
+        return None;
+    }
+    let variable = match key {
+        TemplateKey::Field(field) => field.to_string(),
+        TemplateKey::Chain(_) => key.to_string(),
+        TemplateKey::Function(call) if call.arguments.is_empty() => {
+            match call.name.as_str() {
+                "uuid" => "$guid".into(),
+                "timestamp" => "$timestamp".into(),
+                _ => return None,
+            }
+        }
+        TemplateKey::Fake(kind) => dynamic_variable(*kind)?.into(),
+        TemplateKey::Environment(_)
+        | TemplateKey::Function(_)
+        | TemplateKey::Data(_)
+        | TemplateKey::Item
+        | TemplateKey::ItemField(_)
+        | TemplateKey::Expression(_) => return None,
+    };
+    Some(format!("{{{{{variable}}}}}"))
+}
+
+/// Get the Postman dynamic variable that generates the same kind of fake data
+fn dynamic_variable(kind: FakeKind) -> Option<&'static str> {
+    match kind {
+        FakeKind::Name => Some("$randomFullName"),
+        FakeKind::FirstName => Some("$randomFirstName"),
+        FakeKind::LastName => Some("$randomLastName"),
+        FakeKind::Email => Some("$randomEmail"),
+        FakeKind::Username => Some("$randomUserName"),
+        FakeKind::Password => Some("$randomPassword"),
+        FakeKind::Phone => Some("$randomPhoneNumber"),
+        FakeKind::Company => Some("$randomCompanyName"),
+        FakeKind::StreetName => Some("$randomStreetName"),
+        FakeKind::City => Some("$randomCity"),
+        FakeKind::Country => Some("$randomCountry"),
+        FakeKind::Ipv4 => Some("$randomIP"),
+        FakeKind::Word => Some("$randomWord"),
+        FakeKind::Sentence => Some("$randomLoremSentence"),
+        FakeKind::Paragraph => Some("$randomLoremParagraph"),
+        FakeKind::State | FakeKind::ZipCode => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_yaml;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    const COLLECTION: &str = r#"
+profiles:
+  base:
+    data:
+      host: http://localhost
+  dev:
+    name: Development
+    extends: base
+    data:
+      user: "{{fake.first_name}}"
+chains:
+  token:
+    source: !request
+      recipe: login
+    selector: $.token
+defaults:
+  base_url: "{{host}}/api"
+  authentication: !bearer "{{chains.token}}"
+requests:
+  login: !request
+    method: POST
+    url: /login
+    authentication: !basic
+      username: "{{user}}"
+    body: !json {"id": "{{uuid()}}"}
+  users: !folder
+    name: Users
+    requests:
+      list_users: !request
+        name: List Users
+        method: GET
+        url: /users
+        query:
+          - page=2
+          - q={{search | trim}}
+        headers:
+          Accept: text/csv
+        body: !form_urlencoded
+          env: "{{env.HOME}}"
+"#;
+
+    /// Folders, recipes, and profiles all get exported. Templates are
+    /// translated where possible, and kept as-is otherwise
+    #[test]
+    fn test_to_postman() {
+        let collection: Collection = parse_yaml(COLLECTION.as_bytes()).unwrap();
+        let export = collection.to_postman("My API");
+
+        let mut exported = serde_json::to_value(&export.collection).unwrap();
+        let variables = exported
+            .as_object_mut()
+            .unwrap()
+            .remove("variable")
+            .unwrap();
+        assert_eq!(
+            exported,
+            json!({
+                "info": {"name": "My API", "schema": SCHEMA},
+                "item": [
+                    {
+                        "name": "login",
+                        "request": {
+                            "method": "POST",
+                            "header": [],
+                            "url": {"raw": "{{host}}/api/login"},
+                            "body": {
+                                "mode": "raw",
+                                "raw": "{\n  \"id\": \"{{$guid}}\"\n}",
+                                "options": {"raw": {"language": "json"}},
+                            },
+                            "auth": {
+                                "type": "basic",
+                                "basic": [{
+                                    "key": "username",
+                                    "value": "{{user}}",
+                                    "type": "string",
+                                }],
+                            },
+                        },
+                    },
+                    {
+                        "name": "Users",
+                        "item": [{
+                            "name": "List Users",
+                            "request": {
+                                "method": "GET",
+                                "header": [
+                                    {"key": "Accept", "value": "text/csv"},
+                                ],
+                                "url": {
+                                    "raw": "{{host}}/api/users?page=2\
+                                        &q={{search | trim}}",
+                                    "query": [
+                                        {"key": "page", "value": "2"},
+                                        {
+                                            "key": "q",
+                                            "value": "{{search | trim}}",
+                                        },
+                                    ],
+                                },
+                                "body": {
+                                    "mode": "urlencoded",
+                                    "urlencoded": [{
+                                        "key": "env",
+                                        "value": "{{env.HOME}}",
+                                        "type": "text",
+                                    }],
+                                },
+                                "auth": {
+                                    "type": "bearer",
+                                    "bearer": [{
+                                        "key": "token",
+                                        "value": "{{chains.token}}",
+                                        "type": "string",
+                                    }],
+                                },
+                            },
+                        }],
+                    },
+                ],
+            })
+        );
+
+        // Chains can't be translated, so they're left for the user to fill in
+        let variables = variables.as_array().unwrap();
+        assert_eq!(variables.len(), 1);
+        assert_eq!(variables[0]["key"], "chains.token");
+        assert_eq!(variables[0]["value"], "");
+        assert!(variables[0]["description"]
+            .as_str()
+            .unwrap()
+            .contains("recipe: login"));
+
+        let environments = export
+            .environments
+            .iter()
+            .map(|environment| {
+                (
+                    environment.id.as_str(),
+                    serde_json::to_value(environment).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            environments,
+            [
+                (
+                    "base",
+                    json!({
+                        "name": "base",
+                        "values": [{
+                            "key": "host",
+                            "value": "http://localhost",
+                            "type": "default",
+                            "enabled": true,
+                        }],
+                        "_postman_variable_scope": "environment",
+                    })
+                ),
+                (
+                    "dev",
+                    json!({
+                        "name": "Development",
+                        "values": [
+                            {
+                                "key": "user",
+                                "value": "{{$randomFirstName}}",
+                                "type": "default",
+                                "enabled": true,
+                            },
+                            {
+                                "key": "host",
+                                "value": "http://localhost",
+                                "type": "default",
+                                "enabled": true,
+                            },
+                        ],
+                        "_postman_variable_scope": "environment",
+                    })
+                ),
+            ]
+        );
+    }
+}
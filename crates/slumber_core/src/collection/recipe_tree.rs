@@ -1,11 +1,13 @@
 //! Recipe/folder tree structure
 
 use crate::collection::{
-    cereal::deserialize_id_map, Folder, HasId, Recipe, RecipeId,
+    cereal::deserialize_id_map, inheritance::resolve_extends, Folder, HasId,
+    Recipe, RecipeId,
 };
 use anyhow::anyhow;
 use derive_more::From;
 use indexmap::{map::Values, IndexMap};
+use schemars::JsonSchema;
 use serde::{de::Error, Deserialize, Deserializer, Serialize};
 use strum::EnumDiscriminants;
 use thiserror::Error;
@@ -36,14 +38,16 @@ pub struct RecipeTree {
 pub struct RecipeLookupKey(Vec<RecipeId>);
 
 /// A node in the recipe tree, either a folder or recipe
-#[derive(Debug, From, Serialize, Deserialize, EnumDiscriminants)]
+#[derive(
+    Debug, From, Serialize, Deserialize, JsonSchema, EnumDiscriminants,
+)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 #[allow(clippy::large_enum_variant)]
 pub enum RecipeNode {
     Folder(Folder),
-    /// Rename this variant to match the `requests` field in the root and
-    /// folders
+    // Rename this variant to match the `requests` field in the root and
+    // folders
     #[serde(rename = "request")]
     Recipe(Recipe),
 }
@@ -80,6 +84,11 @@ impl RecipeTree {
         Ok(new)
     }
 
+    /// Take the top level of the tree, e.g. to merge it with another tree
+    pub(super) fn into_nodes(self) -> IndexMap<RecipeId, RecipeNode> {
+        self.tree
+    }
+
     /// Get a recipe/folder's tree lookup key by is unique ID
     pub fn get_lookup_key(&self, id: &RecipeId) -> Option<&RecipeLookupKey> {
         self.nodes_by_id.get(id)
@@ -136,6 +145,24 @@ impl RecipeTree {
             .filter(|id| self.get_recipe(id).is_some())
     }
 
+    /// Get every recipe at or beneath a node, in tree order. If the ID points
+    /// to a recipe, that's the only one returned. If it points to a folder,
+    /// return all recipes within it, recursively. Return an error if the ID
+    /// isn't in the tree
+    pub fn try_get_recipes_under(
+        &self,
+        id: &RecipeId,
+    ) -> anyhow::Result<Vec<&Recipe>> {
+        let lookup_key = self
+            .get_lookup_key(id)
+            .ok_or_else(|| anyhow!("No recipe node with ID `{}`", id))?;
+        Ok(self
+            .iter()
+            .filter(|(key, _)| key.0.starts_with(&lookup_key.0))
+            .filter_map(|(_, node)| node.recipe())
+            .collect())
+    }
+
     /// Get a flat iterator over all nodes in the tree, using depth first
     /// search. Each yielded item will include the lookup key to retrieve
     /// that item.
@@ -198,8 +225,12 @@ impl<'de> Deserialize<'de> for RecipeTree {
     where
         D: Deserializer<'de>,
     {
+        // Inheritance has to be resolved before deserializing recipes, because
+        // a recipe that extends another can omit required fields
+        let mut tree = serde_yaml::Value::deserialize(deserializer)?;
+        resolve_extends(&mut [&mut tree]).map_err(D::Error::custom)?;
         let tree: IndexMap<RecipeId, RecipeNode> =
-            deserialize_id_map(deserializer)?;
+            deserialize_id_map(tree).map_err(D::Error::custom)?;
         Self::new(tree).map_err(D::Error::custom)
     }
 }
@@ -366,6 +397,29 @@ mod tests {
         );
     }
 
+    #[rstest]
+    #[case::recipe("r3", &["r3"])]
+    #[case::folder("f1", &["r2", "r3"])]
+    #[case::nested_folder("f2", &["r2"])]
+    fn test_try_get_recipes_under(
+        tree: IndexMap<RecipeId, RecipeNode>,
+        #[case] node_id: &str,
+        #[case] expected: &[&str],
+    ) {
+        let tree = RecipeTree::new(tree).unwrap();
+        let recipe_ids = tree
+            .try_get_recipes_under(&id(node_id))
+            .unwrap()
+            .into_iter()
+            .map(|recipe| recipe.id.clone())
+            .collect_vec();
+        assert_eq!(recipe_ids, expected.iter().copied().map(id).collect_vec());
+        assert_err!(
+            tree.try_get_recipes_under(&id("unknown")),
+            "No recipe node with ID `unknown`"
+        );
+    }
+
     /// Deserializing with a duplicate ID anywhere in the tree should fail
     #[rstest]
     #[case::anywhere(
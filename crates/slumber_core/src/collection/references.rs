@@ -0,0 +1,448 @@
+//! Find unused and dangling references within a collection, to help keep large
+//! collections clean, and find where a field or chain is used
+
+use crate::{
+    collection::{
+        diff::child_path, keys::visit_keys, ChainId, Collection, ProfileId,
+        RecipeId, RecipeNode,
+    },
+    template::{Template, TemplateKey},
+};
+use indexmap::{IndexMap, IndexSet};
+use serde_yaml::Value;
+use std::fmt::{self, Display, Formatter};
+
+/// A problem with how items in a collection refer to each other
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReferenceIssue {
+    /// A chain that isn't used by any template
+    UnusedChain { chain: ChainId },
+    /// A profile field that isn't used by any template
+    UnusedField {
+        field: String,
+        /// Every profile that defines the field
+        profiles: Vec<ProfileId>,
+    },
+    /// A template key that refers to a field that isn't defined in any
+    /// profile, or to a chain that doesn't exist
+    Dangling {
+        key: TemplateKey,
+        location: ReferenceLocation,
+    },
+}
+
+impl ReferenceIssue {
+    /// Is this issue an error, as opposed to just clutter? Dangling keys fail
+    /// to render, while unused items are harmless
+    pub fn is_error(&self) -> bool {
+        matches!(self, Self::Dangling { .. })
+    }
+}
+
+/// The item containing a template that refers to a key
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReferenceLocation {
+    Profile(ProfileId),
+    Chain(ChainId),
+    Recipe(RecipeId),
+    /// Defaults for the given folder, or the collection root if `None`
+    Defaults(Option<RecipeId>),
+}
+
+/// A template that refers to a particular field or chain
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateReference {
+    pub location: ReferenceLocation,
+    /// Path to the template within the item, e.g. `headers.Authorization`
+    pub path: String,
+}
+
+impl Collection {
+    /// Find chains and profile fields that aren't used by any template, and
+    /// template keys that refer to fields or chains that don't exist. Uses
+    /// from anywhere in the collection count, including from other chains and
+    /// profile fields.
+    ///
+    /// A field that isn't in any profile can still be provided as an override
+    /// (e.g. `slumber request --override`), so dangling fields aren't
+    /// necessarily broken.
+    pub fn reference_issues(&self) -> anyhow::Result<Vec<ReferenceIssue>> {
+        // Group fields across profiles
+        let mut fields: IndexMap<&str, Vec<ProfileId>> = IndexMap::new();
+        for profile in self.profiles.values() {
+            for field in profile.data.keys() {
+                fields
+                    .entry(field.as_str())
+                    .or_default()
+                    .push(profile.id.clone());
+            }
+        }
+
+        let mut used_fields: IndexSet<String> = IndexSet::new();
+        let mut used_chains: IndexSet<ChainId> = IndexSet::new();
+        let mut dangling: Vec<ReferenceIssue> = Vec::new();
+        for (location, value) in self.serialize_items()? {
+            let parameters = self.parameters_at(&location);
+            visit_keys(value, &mut |key| {
+                let exists = match key {
+                    TemplateKey::Field(field) => {
+                        used_fields.insert(field.to_string());
+                        fields.contains_key(field.as_str())
+                            || parameters.contains(field.as_str())
+                    }
+                    TemplateKey::Chain(chain) => {
+                        used_chains.insert(chain.clone());
+                        self.chains.contains_key(chain)
+                    }
+                    _ => true,
+                };
+                let issue = ReferenceIssue::Dangling {
+                    key: key.clone(),
+                    location: location.clone(),
+                };
+                // Only report each key once per item
+                if !exists && !dangling.contains(&issue) {
+                    dangling.push(issue);
+                }
+            });
+        }
+
+        let unused_chains = self
+            .chains
+            .keys()
+            .filter(|id| !used_chains.contains(*id))
+            .map(|id| ReferenceIssue::UnusedChain { chain: id.clone() });
+        let unused_fields = fields
+            .into_iter()
+            .filter(|(field, _)| !used_fields.contains(*field))
+            .map(|(field, profiles)| ReferenceIssue::UnusedField {
+                field: field.to_owned(),
+                profiles,
+            });
+        Ok(unused_chains.chain(unused_fields).chain(dangling).collect())
+    }
+
+    /// Find every template that refers to a key, e.g. to see what would be
+    /// affected by changing a profile field or chain. The key is given as it
+    /// appears in a template, e.g. `host` or `chains.token`.
+    pub fn find_references(
+        &self,
+        key: &str,
+    ) -> anyhow::Result<Vec<TemplateReference>> {
+        let mut references = Vec::new();
+        for (location, value) in self.serialize_items()? {
+            visit_templates(String::new(), value, &mut |path, template| {
+                if template.keys().iter().any(|k| k.to_string() == key) {
+                    references.push(TemplateReference {
+                        location: location.clone(),
+                        path,
+                    });
+                }
+            });
+        }
+        Ok(references)
+    }
+
+    /// Names of the parameters declared by the recipe at a location. Templates
+    /// within a recipe can refer to its parameters like profile fields.
+    pub(super) fn parameters_at(
+        &self,
+        location: &ReferenceLocation,
+    ) -> IndexSet<&str> {
+        match location {
+            ReferenceLocation::Recipe(recipe_id) => self
+                .recipes
+                .get_recipe(recipe_id)
+                .map(|recipe| {
+                    recipe.parameters.keys().map(String::as_str).collect()
+                })
+                .unwrap_or_default(),
+            _ => IndexSet::new(),
+        }
+    }
+
+    /// Serialize each profile, chain, recipe, and set of defaults
+    /// individually, so we know which item each template is in
+    pub(super) fn serialize_items(
+        &self,
+    ) -> anyhow::Result<Vec<(ReferenceLocation, Value)>> {
+        let mut items: Vec<(ReferenceLocation, Value)> = Vec::new();
+        for profile in self.profiles.values() {
+            items.push((
+                ReferenceLocation::Profile(profile.id.clone()),
+                serde_yaml::to_value(profile)?,
+            ));
+        }
+        for chain in self.chains.values() {
+            items.push((
+                ReferenceLocation::Chain(chain.id.clone()),
+                serde_yaml::to_value(chain)?,
+            ));
+        }
+        for recipe in self
+            .recipes
+            .recipe_ids()
+            .filter_map(|id| self.recipes.get_recipe(id))
+        {
+            items.push((
+                ReferenceLocation::Recipe(recipe.id.clone()),
+                serde_yaml::to_value(recipe)?,
+            ));
+        }
+        if !self.defaults.is_empty() {
+            items.push((
+                ReferenceLocation::Defaults(None),
+                serde_yaml::to_value(&self.defaults)?,
+            ));
+        }
+        for (_, node) in self.recipes.iter() {
+            if let RecipeNode::Folder(folder) = node {
+                if !folder.defaults.is_empty() {
+                    items.push((
+                        ReferenceLocation::Defaults(Some(folder.id.clone())),
+                        serde_yaml::to_value(&folder.defaults)?,
+                    ));
+                }
+            }
+        }
+        Ok(items)
+    }
+}
+
+/// Call a function for every template within a serialized value, along with
+/// its path in the value. Like [visit_keys], any string that parses as a
+/// template is considered.
+fn visit_templates(
+    path: String,
+    value: Value,
+    f: &mut impl FnMut(String, &Template),
+) {
+    match value {
+        Value::String(s) => {
+            if let Ok(template) = s.parse::<Template>() {
+                f(path, &template);
+            }
+        }
+        Value::Sequence(sequence) => {
+            for (i, value) in sequence.into_iter().enumerate() {
+                visit_templates(format!("{path}[{i}]"), value, f);
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                visit_templates(child_path(&path, &key), value, f);
+            }
+        }
+        Value::Tagged(tagged) => visit_templates(path, tagged.value, f),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+impl Display for ReferenceIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnusedChain { chain } => {
+                write!(f, "Chain `{chain}` is never used")
+            }
+            Self::UnusedField { field, profiles } => {
+                write!(f, "Field `{field}` is never used (defined in ")?;
+                for (i, profile) in profiles.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "`{profile}`")?;
+                }
+                write!(f, ")")
+            }
+            Self::Dangling {
+                key: key @ TemplateKey::Chain(_),
+                location,
+            } => write!(f, "{location} refers to unknown chain `{key}`"),
+            Self::Dangling { key, location } => {
+                write!(f, "{location} refers to unknown field `{key}`")
+            }
+        }
+    }
+}
+
+impl Display for TemplateReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.path)
+    }
+}
+
+impl Display for ReferenceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Profile(id) => write!(f, "Profile `{id}`"),
+            Self::Chain(id) => write!(f, "Chain `{id}`"),
+            Self::Recipe(id) => write!(f, "Recipe `{id}`"),
+            Self::Defaults(None) => write!(f, "Collection defaults"),
+            Self::Defaults(Some(id)) => write!(f, "Folder `{id}` defaults"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_yaml;
+    use rstest::rstest;
+
+    fn issues(yaml: &str) -> Vec<String> {
+        let collection: Collection = parse_yaml(yaml.as_bytes()).unwrap();
+        collection
+            .reference_issues()
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[rstest]
+    #[case::clean(
+        "{profiles: {local: {data: {host: h}}},
+        chains: {token: {source: !command {command: [echo]}}},
+        requests: {login: !request {
+            method: POST,
+            url: '{{host}}/login',
+            headers: {Authorization: '{{chains.token}}'},
+        }}}",
+        &[]
+    )]
+    #[case::unused(
+        "{profiles: {
+            local: {data: {host: h, unused: u}},
+            prod: {data: {host: h, unused: u}},
+        },
+        chains: {token: {source: !command {command: [echo]}}},
+        requests: {login: !request {method: POST, url: '{{host}}/login'}}}",
+        &[
+            "Chain `token` is never used",
+            "Field `unused` is never used (defined in `local`, `prod`)",
+        ]
+    )]
+    #[case::dangling(
+        "{profiles: {local: {data: {host: h}}},
+        requests: {login: !request {
+            method: POST,
+            url: '{{host}}/{{path}}/{{path}}',
+            headers: {Authorization: '{{chains.token}}'},
+        }}}",
+        &[
+            "Recipe `login` refers to unknown field `path`",
+            "Recipe `login` refers to unknown chain `chains.token`",
+        ]
+    )]
+    // Uses from chains, profile fields, and nested recipes all count
+    #[case::used_indirectly(
+        "{profiles: {local: {data: {host: h, url: '{{host}}/api'}}},
+        chains: {
+            user: {source: !command {command: [echo]}},
+            token: {source: !command {command: [echo, '{{chains.user}}']}},
+        },
+        requests: {users: !folder {requests: {
+            login: !request {
+                method: GET,
+                url: '{{url}}',
+                query: {t: '{{chains.token}}'},
+            },
+        }}}}",
+        &[]
+    )]
+    #[case::dangling_in_profile_and_chain(
+        "{profiles: {local: {data: {url: '{{host}}/api'}}},
+        chains: {token: {source: !command {command: [echo, '{{user}}']}}},
+        requests: {login: !request {
+            method: GET,
+            url: '{{url}}/{{chains.token}}',
+        }}}",
+        &[
+            "Profile `local` refers to unknown field `host`",
+            "Chain `token` refers to unknown field `user`",
+        ]
+    )]
+    // Defaults count as uses, and are checked too
+    #[case::defaults(
+        "{profiles: {local: {data: {host: h}}},
+        chains: {token: {source: !command {command: [echo]}}},
+        defaults: {base_url: '{{host}}', headers: {X-Path: '{{path}}'}},
+        requests: {users: !folder {
+            defaults: {authentication: !bearer '{{chains.token}}'},
+            requests: {
+                login: !request {method: GET, url: '/login'},
+            },
+        }}}",
+        &["Collection defaults refers to unknown field `path`"]
+    )]
+    // A recipe's parameters are only defined within that recipe
+    #[case::parameters(
+        "{profiles: {local: {data: {host: h}}},
+        chains: {user: {source: !command {command: [echo, '{{user_id}}']}}},
+        requests: {get_user: !request {
+            method: GET,
+            url: '{{host}}/users/{{user_id}}',
+            parameters: {user_id: {type: integer}},
+        }}}",
+        &[
+            "Chain `user` is never used",
+            "Chain `user` refers to unknown field `user_id`",
+        ]
+    )]
+    // Environment variables and fake data aren't checked
+    #[case::other_keys(
+        "{requests: {login: !request {
+            method: GET,
+            url: '{{env.HOST}}/{{fake.email}}',
+        }}}",
+        &[]
+    )]
+    fn test_reference_issues(#[case] yaml: &str, #[case] expected: &[&str]) {
+        assert_eq!(issues(yaml), expected);
+    }
+
+    #[rstest]
+    #[case::field(
+        "host",
+        &[
+            "Profile `local`: data.api",
+            "Recipe `login`: url",
+            "Recipe `get_user`: headers.Referer",
+        ]
+    )]
+    #[case::chain(
+        "chains.token",
+        &["Chain `user`: source.command[1]", "Recipe `get_user`: url"]
+    )]
+    #[case::unused("other", &[])]
+    fn test_find_references(#[case] key: &str, #[case] expected: &[&str]) {
+        let collection: Collection = parse_yaml(
+            "{profiles: {local: {data: {
+                host: h,
+                api: '{{host}}/api',
+                other: o,
+            }}},
+            chains: {
+                token: {source: !command {command: [echo]}},
+                user: {source: !command {command: [echo, '{{chains.token}}']}},
+            },
+            requests: {
+                login: !request {method: POST, url: '{{host}}/login'},
+                users: !folder {requests: {get_user: !request {
+                    method: GET,
+                    url: '{{api}}/{{chains.token}}/{{chains.token}}',
+                    headers: {Referer: '{{host}}'},
+                }}},
+            }}"
+            .as_bytes(),
+        )
+        .unwrap();
+        let references: Vec<String> = collection
+            .find_references(key)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(references, expected);
+    }
+}
@@ -0,0 +1,438 @@
+//! Rename a recipe within a collection, updating every reference to it
+
+use crate::{
+    collection::{diff::diff_values, imports::load_with_imports, RecipeId},
+    util::parse_yaml,
+};
+use anyhow::{anyhow, bail, Context};
+use serde_yaml::Value;
+use std::{
+    fs, iter,
+    path::{Path, PathBuf},
+};
+
+/// Rename a recipe in a collection file and every file it imports, and update
+/// every chain that loads from it and every recipe that extends it. The
+/// recipe and its references can be in any of the files. Return the path and
+/// new source text of each file that changed. Nothing is written here, so if
+/// any file can't be renamed, none of them are changed.
+pub fn rename_recipe(
+    path: &Path,
+    old: &RecipeId,
+    new: &RecipeId,
+) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let collection = load_with_imports(path)?;
+    collection.recipes.try_get_recipe(old)?;
+    if new.is_empty() {
+        bail!("Recipe ID cannot be empty");
+    }
+    if collection.recipes.get(new).is_some() {
+        bail!("Recipe `{new}` already exists");
+    }
+
+    let mut renamed = Vec::new();
+    for path in iter::once(path.to_owned()).chain(collection.imports) {
+        let source = fs::read_to_string(&path)
+            .with_context(|| format!("Error reading {path:?}"))?;
+        if let Some(source) = rename_in_source(&source, old, new)
+            .with_context(|| format!("Error renaming recipe in {path:?}"))?
+        {
+            renamed.push((path, source));
+        }
+    }
+    Ok(renamed)
+}
+
+/// Rename a recipe in the source text of a single file, and update every
+/// reference to it in that file. Return `None` if the file doesn't mention
+/// the recipe.
+///
+/// The file is edited as text rather than re-serialized, so comments and
+/// formatting are retained. Each occurrence of the old ID is only replaced if
+/// doing so makes one of the expected changes to the parsed file, so
+/// unrelated text that happens to match (e.g. a profile field with the same
+/// name) is left alone. If the full rename can't be made this way, return an
+/// error and leave the user to do it by hand.
+fn rename_in_source(
+    source: &str,
+    old: &RecipeId,
+    new: &RecipeId,
+) -> anyhow::Result<Option<String>> {
+    let before: Value = parse_yaml(source.as_bytes())?;
+    let mut expected = before.clone();
+    rename_in_value(&mut expected, old, new);
+    let mut expected_changes = Vec::new();
+    diff_values(
+        String::new(),
+        before.clone(),
+        expected,
+        &mut expected_changes,
+    );
+    if expected_changes.is_empty() {
+        return Ok(None);
+    }
+
+    // Try each occurrence in turn, keeping it if it gets us closer to the
+    // expected collection without changing anything else
+    let mut accepted: Vec<usize> = Vec::new();
+    let mut change_count = 0;
+    for offset in find_candidates(source, old) {
+        accepted.push(offset);
+        let replaced = replace_at(source, &accepted, old, new);
+        let mut changes = Vec::new();
+        if let Ok(after) = parse_yaml::<Value>(replaced.as_bytes()) {
+            diff_values(String::new(), before.clone(), after, &mut changes);
+        }
+        if changes.len() > change_count
+            && changes
+                .iter()
+                .all(|change| expected_changes.contains(change))
+        {
+            change_count = changes.len();
+        } else {
+            accepted.pop();
+        }
+    }
+
+    if change_count == expected_changes.len() {
+        Ok(Some(replace_at(source, &accepted, old, new)))
+    } else {
+        Err(anyhow!(
+            "Unable to rename every reference to recipe `{old}`; \
+            the collection file must be edited manually"
+        ))
+    }
+}
+
+/// Rename a recipe within a parsed collection file, as well as references to
+/// it from `!request` chains and `extends` fields. This defines the expected
+/// outcome of the rename.
+fn rename_in_value(collection: &mut Value, old: &RecipeId, new: &RecipeId) {
+    if let Some(requests) = collection.get_mut("requests") {
+        rename_node(requests, old, new);
+        rename_extends(requests, old, new);
+    }
+    if let Some(Value::Mapping(chains)) = collection.get_mut("chains") {
+        for chain in chains.values_mut() {
+            if let Some(Value::Tagged(source)) = chain.get_mut("source") {
+                if source.tag != "request" {
+                    continue;
+                }
+                if let Some(recipe) = source.value.get_mut("recipe") {
+                    if recipe == old.as_str() {
+                        *recipe = new.to_string().into();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rename a recipe within a map of recipe nodes, recursing into folders.
+/// Return whether the recipe was found
+fn rename_node(nodes: &mut Value, old: &RecipeId, new: &RecipeId) -> bool {
+    let Value::Mapping(nodes) = nodes else {
+        return false;
+    };
+    if nodes.contains_key(old.as_str()) {
+        // Rebuild the map to retain order
+        *nodes = std::mem::take(nodes)
+            .into_iter()
+            .map(|(key, value)| {
+                if key == old.as_str() {
+                    (new.to_string().into(), value)
+                } else {
+                    (key, value)
+                }
+            })
+            .collect();
+        return true;
+    }
+    nodes.values_mut().any(|node| match node {
+        Value::Tagged(folder) => folder
+            .value
+            .get_mut("requests")
+            .is_some_and(|children| rename_node(children, old, new)),
+        _ => false,
+    })
+}
+
+/// Update `extends` in every recipe that inherits from the renamed recipe,
+/// recursing into folders
+fn rename_extends(nodes: &mut Value, old: &RecipeId, new: &RecipeId) {
+    let Value::Mapping(nodes) = nodes else {
+        return;
+    };
+    for node in nodes.values_mut() {
+        let Value::Tagged(node) = node else {
+            continue;
+        };
+        if node.tag == "folder" {
+            if let Some(children) = node.value.get_mut("requests") {
+                rename_extends(children, old, new);
+            }
+        } else if let Some(extends) = node.value.get_mut("extends") {
+            if extends == old.as_str() {
+                *extends = new.to_string().into();
+            }
+        }
+    }
+}
+
+/// Find the offset of every occurrence of an ID in the source text that isn't
+/// part of a larger word
+fn find_candidates(source: &str, id: &RecipeId) -> Vec<usize> {
+    let is_id_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    source
+        .match_indices(id.as_str())
+        .map(|(offset, _)| offset)
+        .filter(|&offset| {
+            let before = source[..offset].chars().next_back();
+            let after = source[offset + id.len()..].chars().next();
+            !before.is_some_and(is_id_char) && !after.is_some_and(is_id_char)
+        })
+        .collect()
+}
+
+/// Replace the old ID with the new one at each of the given offsets, which
+/// must be in ascending order
+fn replace_at(
+    source: &str,
+    offsets: &[usize],
+    old: &RecipeId,
+    new: &RecipeId,
+) -> String {
+    let mut replaced = String::with_capacity(source.len());
+    let mut last = 0;
+    for &offset in offsets {
+        replaced.push_str(&source[last..offset]);
+        replaced.push_str(new);
+        last = offset + old.len();
+    }
+    replaced.push_str(&source[last..]);
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_err,
+        test_util::{temp_dir, TempDir},
+    };
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    /// Write a single-file collection, and rename a recipe in it. Return the
+    /// new source text
+    fn rename(
+        dir: &Path,
+        source: &str,
+        old: &str,
+        new: &str,
+    ) -> anyhow::Result<String> {
+        let path = dir.join("slumber.yml");
+        fs::write(&path, source).unwrap();
+        let mut renamed = rename_recipe(&path, &old.into(), &new.into())?;
+        assert_eq!(renamed.len(), 1);
+        Ok(renamed.remove(0).1)
+    }
+
+    const COLLECTION: &str = r#"profiles:
+  local:
+    data:
+      # Field with the same name as a recipe
+      login: '{{host}}/login'
+
+chains:
+  token:
+    source: !request
+      recipe: login
+    selector: $.token
+  other:
+    source: !request {recipe: get_user}
+
+requests:
+  # The login recipe
+  login: !request
+    method: POST
+    url: '{{login}}'
+  users: !folder
+    requests:
+      get_user: !request
+        method: GET
+        url: '{{host}}/users/login'
+        headers:
+          Authorization: '{{chains.token}}'
+"#;
+
+    #[rstest]
+    fn test_rename_recipe(temp_dir: TempDir) {
+        let renamed =
+            rename(&temp_dir, COLLECTION, "login", "sign_in").unwrap();
+        // The chain and recipe key are changed, but nothing else (including
+        // the comment)
+        assert_eq!(
+            renamed,
+            COLLECTION
+                .replace("recipe: login", "recipe: sign_in")
+                .replace("  login: !request", "  sign_in: !request")
+        );
+    }
+
+    /// Recipes in folders and flow-style chains are renamed
+    #[rstest]
+    fn test_rename_recipe_nested(temp_dir: TempDir) {
+        let renamed =
+            rename(&temp_dir, COLLECTION, "get_user", "user").unwrap();
+        assert_eq!(
+            renamed,
+            COLLECTION
+                .replace("{recipe: get_user}", "{recipe: user}")
+                .replace("get_user: !request", "user: !request")
+        );
+    }
+
+    /// Recipes that extend the renamed recipe are updated
+    #[rstest]
+    fn test_rename_recipe_extends(temp_dir: TempDir) {
+        let source = r#"requests:
+  login: !request
+    method: POST
+    url: /login
+  users: !folder
+    requests:
+      login_admin: !request
+        extends: login
+        url: /admin/login
+"#;
+        let renamed = rename(&temp_dir, source, "login", "sign_in").unwrap();
+        assert_eq!(
+            renamed,
+            source
+                .replace("extends: login", "extends: sign_in")
+                .replace("  login: !request", "  sign_in: !request")
+        );
+    }
+
+    #[rstest]
+    #[case::unknown("unknown", "new", "No recipe with ID `unknown`")]
+    #[case::folder("users", "new", "No recipe with ID `users`")]
+    #[case::exists("login", "get_user", "Recipe `get_user` already exists")]
+    #[case::empty("login", "", "Recipe ID cannot be empty")]
+    fn test_rename_recipe_error(
+        temp_dir: TempDir,
+        #[case] old: &str,
+        #[case] new: &str,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(rename(&temp_dir, COLLECTION, old, new), expected_error);
+    }
+
+    /// If a reference can't be found in the text (e.g. because it's behind an
+    /// alias), the rename fails instead of leaving the collection broken
+    #[rstest]
+    fn test_rename_recipe_incomplete(temp_dir: TempDir) {
+        let source = r#".ignore:
+  recipe: &recipe login
+chains:
+  token:
+    source: !request
+      recipe: *recipe
+requests:
+  login: !request
+    method: POST
+    url: /login
+"#;
+        assert_err!(
+            rename(&temp_dir, source, "login", "sign_in"),
+            "Unable to rename every reference to recipe `login`"
+        );
+    }
+
+    /// The recipe and its references are renamed in whichever files they're
+    /// in. Files that don't mention the recipe are left out.
+    #[rstest]
+    fn test_rename_recipe_imports(temp_dir: TempDir) {
+        let root = "imports: [auth.yml, other.yml]
+chains:
+  token:
+    source: !request
+      recipe: login
+";
+        let auth = "requests:
+  login: !request
+    method: POST
+    url: /login
+  login_admin: !request
+    extends: login
+    url: /admin/login
+";
+        let other = "requests:
+  get_user: !request
+    method: GET
+    url: /users/login
+";
+        for (name, source) in [
+            ("slumber.yml", root),
+            ("auth.yml", auth),
+            ("other.yml", other),
+        ] {
+            fs::write(temp_dir.join(name), source).unwrap();
+        }
+
+        let renamed = rename_recipe(
+            &temp_dir.join("slumber.yml"),
+            &"login".into(),
+            &"sign_in".into(),
+        )
+        .unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        assert_eq!(
+            renamed,
+            [
+                (
+                    temp_dir.join("slumber.yml"),
+                    root.replace("recipe: login", "recipe: sign_in")
+                ),
+                (
+                    temp_dir.join("auth.yml"),
+                    auth.replace("  login:", "  sign_in:")
+                        .replace("extends: login", "extends: sign_in")
+                ),
+            ]
+        );
+    }
+
+    /// A recipe in an imported file can't take an ID that's used elsewhere
+    #[rstest]
+    fn test_rename_recipe_imports_exists(temp_dir: TempDir) {
+        fs::write(
+            temp_dir.join("slumber.yml"),
+            "imports: [other.yml]
+requests:
+  login: !request
+    method: POST
+    url: /login
+",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.join("other.yml"),
+            "requests:
+  get_user: !request
+    method: GET
+    url: /users
+",
+        )
+        .unwrap();
+        assert_err!(
+            rename_recipe(
+                &temp_dir.join("slumber.yml"),
+                &"get_user".into(),
+                &"login".into()
+            ),
+            "Recipe `login` already exists"
+        );
+    }
+}
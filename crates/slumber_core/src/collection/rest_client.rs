@@ -0,0 +1,838 @@
+//! Import and export files for the VS Code REST Client extension (`.http` or
+//! `.rest`). File variables like `@host = ...` map to profile fields, and
+//! request variables like `{{login.response.body.$.token}}` map to chains
+//! that load from the named recipe's latest response.
+
+use crate::{
+    collection::{
+        Authentication, Chain, ChainId, ChainOutputTrim, ChainRequestSection,
+        ChainSource, Collection, Method, Profile, ProfileId, Recipe,
+        RecipeBody, RecipeId, RecipeNode, RecipeTree,
+    },
+    http::{content_type::ContentType, query::Selector},
+    template::{Argument, Filter, Identifier, Template, TemplateKey},
+};
+use anyhow::{anyhow, bail, Context};
+use indexmap::{indexmap, IndexMap};
+use itertools::Itertools;
+use regex::Regex;
+use reqwest::header;
+use std::{collections::HashMap, fs, path::Path, sync::LazyLock};
+use tracing::{info, warn};
+use url::form_urlencoded;
+
+/// ID of the profile that file variables are imported into
+const PROFILE_ID: &str = "default";
+
+impl Collection {
+    /// Load a collection from a REST Client file. Each request becomes a
+    /// recipe, and all file variables become fields of a single profile.
+    ///
+    /// This is not async because it's only called by the CLI, where we don't
+    /// care about blocking. It keeps the code simpler.
+    pub fn from_rest_client(
+        rest_client_file: impl AsRef<Path>,
+    ) -> anyhow::Result<Self> {
+        let path = rest_client_file.as_ref();
+        info!(file = ?path, "Loading REST Client file");
+        let content = fs::read_to_string(path).with_context(|| {
+            format!("Error reading REST Client file {path:?}")
+        })?;
+        Importer::default().import(&content)
+    }
+
+    /// Convert this collection to a REST Client file. Fields of the given
+    /// profile become file variables. Folders are flattened, because REST
+    /// Client has no equivalent. Templates are translated to REST Client
+    /// variables where possible. Anything that can't be translated is
+    /// exported in Slumber syntax, with a warning.
+    pub fn to_rest_client(
+        &self,
+        profile_id: Option<&ProfileId>,
+    ) -> anyhow::Result<String> {
+        let mut sections: Vec<String> = Vec::new();
+        if let Some(profile_id) = profile_id {
+            if !self.profiles.contains_key(profile_id) {
+                bail!("Unknown profile `{profile_id}`");
+            }
+            let context = format!("profile `{profile_id}`");
+            let variables = self
+                .profile_data(profile_id)
+                .into_iter()
+                .map(|(field, (_, template))| {
+                    format!(
+                        "@{field} = {}",
+                        self.translate_rest_client(template, &context)
+                    )
+                })
+                .join("\n");
+            if !variables.is_empty() {
+                sections.push(variables);
+            }
+        }
+
+        for (_, node) in self.recipes.iter() {
+            if let RecipeNode::Recipe(recipe) = node {
+                sections.push(self.export_rest_client_recipe(recipe));
+            }
+        }
+        Ok(sections
+            .into_iter()
+            .map(|section| section + "\n")
+            .join("\n"))
+    }
+
+    fn export_rest_client_recipe(&self, recipe: &Recipe) -> String {
+        let context = format!("recipe `{}`", recipe.id);
+        let translate = |template: &Template| {
+            self.translate_rest_client(template, &context)
+        };
+        let defaults = self.recipe_defaults(&recipe.id);
+
+        // Only write a title if there's a name, so the name is still empty
+        // when importing again
+        let mut lines = vec![
+            match &recipe.name {
+                Some(name) => format!("### {name}"),
+                None => "###".to_owned(),
+            },
+            format!("# @name {}", recipe.id),
+        ];
+
+        let (url, _) = defaults.export_url(recipe, translate);
+        lines.push(format!("{} {url}", recipe.method));
+
+        let mut has_content_type = false;
+        for (name, value) in
+            defaults.inherited_headers(recipe).chain(&recipe.headers)
+        {
+            has_content_type |=
+                name.eq_ignore_ascii_case(header::CONTENT_TYPE.as_str());
+            lines.push(format!("{name}: {}", translate(value)));
+        }
+
+        match defaults.authentication(recipe) {
+            // REST Client encodes `username:password` itself
+            Some(Authentication::Basic { username, password }) => {
+                lines.push(format!(
+                    "Authorization: Basic {}:{}",
+                    translate(username),
+                    password.as_ref().map(translate).unwrap_or_default()
+                ));
+            }
+            Some(Authentication::Bearer(token)) => {
+                lines.push(format!(
+                    "Authorization: Bearer {}",
+                    translate(token)
+                ));
+            }
+            Some(Authentication::AwsSigv4 { .. }) => warn!(
+                "AWS authentication isn't supported by REST Client; {context} \
+                will be exported without it"
+            ),
+            None => {}
+        }
+
+        // Slumber sets the content type of structured bodies automatically,
+        // but REST Client needs it spelled out
+        let (content_type, body) = match &recipe.body {
+            None => (None, None),
+            Some(RecipeBody::Raw { body, content_type }) => (
+                content_type.map(|content_type| match content_type {
+                    ContentType::Json => mime::APPLICATION_JSON,
+                }),
+                Some(translate(body)),
+            ),
+            Some(RecipeBody::FormUrlencoded(fields)) => (
+                Some(mime::APPLICATION_WWW_FORM_URLENCODED),
+                Some(
+                    fields
+                        .iter()
+                        .map(|(key, value)| {
+                            format!("{key}={}", translate(value))
+                        })
+                        .join("\n&"),
+                ),
+            ),
+            Some(RecipeBody::FormMultipart(_)) => {
+                warn!(
+                    "Multipart forms aren't supported by REST Client; \
+                    {context} will be exported without a body"
+                );
+                (None, None)
+            }
+        };
+        if let Some(content_type) = content_type.filter(|_| !has_content_type) {
+            lines.push(format!("Content-Type: {content_type}"));
+        }
+        if let Some(body) = body {
+            lines.push(String::new());
+            lines.push(body);
+        }
+
+        lines.join("\n")
+    }
+
+    /// Translate a template to a REST Client string, warning about anything
+    /// that can't be translated. `context` describes where the template is,
+    /// for the warning.
+    fn translate_rest_client(
+        &self,
+        template: &Template,
+        context: &str,
+    ) -> String {
+        let (translated, untranslated) = template
+            .translate(|key, filters| self.rest_client_variable(key, filters));
+        for source in untranslated {
+            warn!(
+                "`{source}` in {context} can't be translated to REST Client; \
+                it will be exported as-is"
+            );
+        }
+        translated
+    }
+
+    /// Translate a single template key to a REST Client variable. Filters have
+    /// no equivalent.
+    fn rest_client_variable(
+        &self,
+        key: &TemplateKey,
+        filters: &[Filter],
+    ) -> Option<String> {
+        if !filters.is_empty() {
+            return None;
+        }
+        let variable = match key {
+            TemplateKey::Field(field) => field.to_string(),
+            TemplateKey::Chain(chain_id) => self.request_variable(chain_id)?,
+            TemplateKey::Environment(variable) => {
+                format!("$processEnv {variable}")
+            }
+            TemplateKey::Function(call) => {
+                match (call.name.as_str(), call.arguments.as_slice()) {
+                    ("uuid", []) => "$guid".into(),
+                    ("timestamp", []) => "$timestamp".into(),
+                    ("now", []) => "$datetime iso8601".into(),
+                    // REST Client's upper bound is exclusive
+                    (
+                        "random_int",
+                        [Argument::Integer(min), Argument::Integer(max)],
+                    ) => format!("$randomInt {min} {}", max.checked_add(1)?),
+                    _ => return None,
+                }
+            }
+            TemplateKey::Fake(_)
+            | TemplateKey::Data(_)
+            | TemplateKey::Item
+            | TemplateKey::ItemField(_)
+            | TemplateKey::Expression(_) => return None,
+        };
+        Some(format!("{{{{{variable}}}}}"))
+    }
+
+    /// Get the request variable equivalent to a chain. Only chains that take
+    /// a response's body or a header, without any other processing, have an
+    /// equivalent.
+    fn request_variable(&self, chain_id: &ChainId) -> Option<String> {
+        let chain = self.chains.get(chain_id)?;
+        let ChainSource::Request {
+            recipe, section, ..
+        } = &chain.source
+        else {
+            return None;
+        };
+        if !matches!(chain.trim, ChainOutputTrim::None) {
+            return None;
+        }
+        match (section, &chain.selector) {
+            (ChainRequestSection::Body, None) => {
+                Some(format!("{recipe}.response.body.*"))
+            }
+            (ChainRequestSection::Body, Some(Selector::JsonPath(query))) => {
+                Some(format!("{recipe}.response.body.{query}"))
+            }
+            (ChainRequestSection::Body, Some(Selector::XPath(xpath))) => {
+                Some(format!("{recipe}.response.body.{xpath}"))
+            }
+            (ChainRequestSection::Header(header), None) => {
+                let header = header.display();
+                (!header.contains("{{"))
+                    .then(|| format!("{recipe}.response.headers.{header}"))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// State for an import. Chains are generated as request variables are found,
+/// so this is shared across the whole file.
+#[derive(Debug, Default)]
+struct Importer {
+    chains: IndexMap<ChainId, Chain>,
+    /// The chain generated for each request variable, so repeated references
+    /// share a chain
+    request_variables: HashMap<String, ChainId>,
+}
+
+impl Importer {
+    fn import(mut self, content: &str) -> anyhow::Result<Collection> {
+        let mut variables: IndexMap<String, Template> = IndexMap::new();
+        let mut recipes: IndexMap<RecipeId, RecipeNode> = IndexMap::new();
+        for (title, lines) in split_requests(content) {
+            let Some(mut recipe) =
+                self.import_request(title, &lines, &mut variables)?
+            else {
+                continue;
+            };
+            // Generated IDs can collide, e.g. two requests to the same URL
+            if recipes.contains_key(&recipe.id) {
+                let base = recipe.id.clone();
+                recipe.id = (2..)
+                    .map(|i| RecipeId::from(format!("{base}_{i}")))
+                    .find(|id| !recipes.contains_key(id))
+                    .expect("Infinite iterator");
+            }
+            recipes.insert(recipe.id.clone(), RecipeNode::Recipe(recipe));
+        }
+
+        let profiles = if variables.is_empty() {
+            IndexMap::new()
+        } else {
+            let id: ProfileId = PROFILE_ID.to_owned().into();
+            indexmap! {
+                id.clone() => Profile {
+                    id,
+                    name: None,
+                    extends: None,
+                    data: variables,
+                    certificate: None,
+                    tunnel: None,
+                    fake_seed: None,
+                },
+            }
+        };
+        Ok(Collection {
+            profiles,
+            chains: self.chains,
+            recipes: RecipeTree::new(recipes)?,
+            ..Collection::default()
+        })
+    }
+
+    /// Import a single request, plus any file variables defined before it.
+    /// Returns `None` if the block doesn't contain a request.
+    fn import_request(
+        &mut self,
+        title: Option<&str>,
+        lines: &[&str],
+        variables: &mut IndexMap<String, Template>,
+    ) -> anyhow::Result<Option<Recipe>> {
+        static VARIABLE_REGEX: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"^@([^\s=]+)\s*=\s*(.*)$").unwrap());
+        static METADATA_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"^(?:#|//)\s*@([\w-]+)(?:\s+(.*))?$").unwrap()
+        });
+
+        // Variables, comments, and metadata come before the request line
+        let mut id: Option<RecipeId> = None;
+        let mut lines = lines.iter().copied().peekable();
+        let request_line = loop {
+            let Some(line) = lines.next() else {
+                return Ok(None);
+            };
+            let line = line.trim();
+            if let Some(captures) = VARIABLE_REGEX.captures(line) {
+                variables.insert(
+                    captures[1].to_owned(),
+                    self.translate(&captures[2]),
+                );
+            } else if let Some(captures) = METADATA_REGEX.captures(line) {
+                match &captures[1] {
+                    "name" => {
+                        id = captures
+                            .get(2)
+                            .map(|name| name.as_str().to_owned().into());
+                    }
+                    other => warn!(
+                        "Request metadata `@{other}` is not supported; \
+                        ignoring it"
+                    ),
+                }
+            } else if !line.is_empty() && !is_comment(line) {
+                break line;
+            }
+        };
+
+        let (method, url) = parse_request_line(request_line);
+        let mut url = url.to_owned();
+        // The query can continue onto the following lines
+        while let Some(line) = lines.next_if(|line| {
+            let line = line.trim_start();
+            line.starts_with('?') || line.starts_with('&')
+        }) {
+            url.push_str(line.trim());
+        }
+        let (url, query) = url.split_once('?').unwrap_or((url.as_str(), ""));
+        let query = form_urlencoded::parse(query.as_bytes())
+            .map(|(name, value)| (name.into_owned(), self.translate(&value)))
+            .collect();
+
+        // Headers go up to the first blank line
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in lines.by_ref() {
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            } else if is_comment(line) {
+                continue;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Invalid header `{line}`"))?;
+            headers.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+        let authentication = find_header(&headers, &header::AUTHORIZATION)
+            .and_then(|index| {
+                let authentication = self.authentication(&headers[index].1)?;
+                headers.remove(index);
+                Some(authentication)
+            });
+
+        // Everything else is the body
+        let body = lines.join("\n");
+        let body = body.trim_start_matches('\n').trim_end();
+        let body = if body.is_empty() {
+            None
+        } else {
+            if body.starts_with('<') {
+                warn!(
+                    "Loading a body from a file (`{}`) is not supported; \
+                    importing it as text",
+                    body.lines().next().unwrap_or_default()
+                );
+            }
+            let content_type = find_header(&headers, &header::CONTENT_TYPE)
+                .map(|index| headers[index].1.clone())
+                .unwrap_or_default();
+            // Form fields are often split across lines
+            let is_form = content_type
+                .starts_with(mime::APPLICATION_WWW_FORM_URLENCODED.as_ref());
+            let body = if is_form {
+                body.lines().map(str::trim).join("")
+            } else {
+                body.to_owned()
+            };
+            let (body, is_structured) =
+                RecipeBody::translated_text(&content_type, body, |text| {
+                    self.translate(&text)
+                });
+            if is_structured {
+                headers.retain(|(name, _)| {
+                    !name.eq_ignore_ascii_case(header::CONTENT_TYPE.as_str())
+                });
+            }
+            Some(body)
+        };
+
+        Ok(Some(Recipe {
+            id: id.unwrap_or_else(|| RecipeId::generate(method, url)),
+            name: title.map(String::from),
+            method,
+            url: self.translate(url),
+            body,
+            authentication,
+            signature: None,
+            certificate: None,
+            danger_skip_tls_verify: false,
+            baseline_ignore: vec![],
+            assertions: vec![],
+            display_query: None,
+            example: None,
+            query,
+            headers: headers
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = self.translate(&value);
+                    (name, value)
+                })
+                .collect(),
+            parameters: IndexMap::new(),
+        }))
+    }
+
+    /// Convert an `Authorization` header to authentication. Returns `None` if
+    /// the header should be left as-is
+    fn authentication(&mut self, value: &str) -> Option<Authentication> {
+        let (scheme, credentials) = value.split_once(' ')?;
+        let credentials = credentials.trim();
+        if scheme.eq_ignore_ascii_case("bearer") {
+            Some(Authentication::Bearer(self.translate(credentials)))
+        } else if scheme.eq_ignore_ascii_case("basic") {
+            // REST Client encodes `username:password` and `username password`
+            // itself. Anything else is already encoded
+            let (username, password) = credentials
+                .split_once(':')
+                .or_else(|| credentials.split_once(' '))?;
+            Some(Authentication::Basic {
+                username: self.translate(username),
+                password: Some(self.translate(password.trim())),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Translate REST Client variables in some text into a Slumber template.
+    /// Anything that can't be translated is imported as raw text.
+    fn translate(&mut self, text: &str) -> Template {
+        static VARIABLE_REGEX: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"\{\{\s*([^{}]*?)\s*\}\}").unwrap());
+
+        let mut translated = Template::default();
+        let mut raw_start = 0;
+        for captures in VARIABLE_REGEX.captures_iter(text) {
+            let variable = captures.get(0).expect("Group 0 is always present");
+            let Some(key) = self.translate_variable(&captures[1]) else {
+                warn!(
+                    "Variable `{}` is not supported; importing it as raw text",
+                    variable.as_str()
+                );
+                continue;
+            };
+            translated = translated
+                .concat(&Template::raw(
+                    text[raw_start..variable.start()].to_owned(),
+                ))
+                .concat(&key);
+            raw_start = variable.end();
+        }
+        translated.concat(&Template::raw(text[raw_start..].to_owned()))
+    }
+
+    /// Translate the contents of a single `{{...}}`. File variables become
+    /// profile fields, request variables become chains, and some system
+    /// variables have equivalent functions.
+    fn translate_variable(&mut self, variable: &str) -> Option<Template> {
+        if let Some(system) = variable.strip_prefix('$') {
+            let mut arguments = system.split_whitespace();
+            let name = arguments.next()?;
+            let source = match (name, arguments.collect_vec().as_slice()) {
+                ("guid", []) => "{{uuid()}}".to_owned(),
+                ("timestamp", []) => "{{timestamp()}}".to_owned(),
+                ("datetime", ["iso8601"]) => "{{now()}}".to_owned(),
+                // REST Client's upper bound is exclusive
+                ("randomInt", [min, max]) => {
+                    let min: i64 = min.parse().ok()?;
+                    let max: i64 = max.parse().ok()?;
+                    format!(
+                        "{{{{random_int({min}, {})}}}}",
+                        max.checked_sub(1)?
+                    )
+                }
+                ("processEnv", [variable]) => format!("{{{{env.{variable}}}}}"),
+                _ => return None,
+            };
+            return source.parse().ok();
+        }
+
+        if let Some((recipe, path)) = variable.split_once(".response.") {
+            return self
+                .request_variable(recipe, path)
+                .map(Template::from_chain);
+        }
+        variable
+            .parse::<Identifier>()
+            .ok()
+            .map(Template::from_field)
+    }
+
+    /// Get the chain for a request variable like `login.response.body.$.token`,
+    /// generating it the first time it's referenced
+    fn request_variable(
+        &mut self,
+        recipe: &str,
+        path: &str,
+    ) -> Option<ChainId> {
+        /// Chains are named after the recipe and the last word of the path
+        static NAME_REGEX: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"([\w-]+)\W*$").unwrap());
+
+        let key = format!("{recipe}.response.{path}");
+        if let Some(id) = self.request_variables.get(&key) {
+            return Some(id.clone());
+        }
+
+        let (section, selector) =
+            if let Some(selector) = path.strip_prefix("body.") {
+                let selector: Option<Selector> = match selector {
+                    "*" => None,
+                    _ => Some(selector.parse().ok()?),
+                };
+                (ChainRequestSection::Body, selector)
+            } else if let Some(header) = path.strip_prefix("headers.") {
+                (
+                    ChainRequestSection::Header(Template::raw(header.into())),
+                    None,
+                )
+            } else {
+                return None;
+            };
+
+        let suffix = NAME_REGEX
+            .captures(path)
+            .and_then(|captures| captures.get(1))
+            .map_or("body", |m| m.as_str());
+        let base = format!("{recipe}_{suffix}");
+        let id: ChainId = (1..)
+            .map(|i| match i {
+                1 => Identifier::escape(&base),
+                _ => Identifier::escape(&format!("{base}_{i}")),
+            })
+            .map(ChainId::from)
+            .find(|id| !self.chains.contains_key(id))
+            .expect("Infinite iterator");
+        self.chains.insert(
+            id.clone(),
+            Chain {
+                id: id.clone(),
+                source: ChainSource::Request {
+                    recipe: recipe.to_owned().into(),
+                    trigger: Default::default(),
+                    section,
+                },
+                sensitive: false,
+                selector,
+                content_type: None,
+                trim: ChainOutputTrim::None,
+                cache: None,
+                exclusive: false,
+                example: None,
+            },
+        );
+        self.request_variables.insert(key, id.clone());
+        Some(id)
+    }
+}
+
+/// Split a file into requests, separated by lines starting with `###`. Any
+/// text after the `###` is the request's title.
+fn split_requests(content: &str) -> Vec<(Option<&str>, Vec<&str>)> {
+    let mut requests = vec![(None, vec![])];
+    for line in content.lines() {
+        if let Some(title) = line.trim_start().strip_prefix("###") {
+            let title = title.trim();
+            requests.push(((!title.is_empty()).then_some(title), vec![]));
+        } else {
+            requests
+                .last_mut()
+                .expect("Requests is never empty")
+                .1
+                .push(line);
+        }
+    }
+    requests
+}
+
+/// Is this line a comment? Metadata like `# @name` is handled before this.
+fn is_comment(line: &str) -> bool {
+    line.starts_with('#') || line.starts_with("//")
+}
+
+/// Get the index of a header, case-insensitive
+fn find_header(
+    headers: &[(String, String)],
+    name: &header::HeaderName,
+) -> Option<usize> {
+    headers
+        .iter()
+        .position(|(header, _)| header.eq_ignore_ascii_case(name.as_str()))
+}
+
+/// Parse `METHOD URL HTTP/1.1` into method and URL. The method defaults to
+/// `GET` and the HTTP version is optional, and ignored either way.
+fn parse_request_line(line: &str) -> (Method, &str) {
+    static HTTP_VERSION_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\s+HTTP/[\d.]+$").unwrap());
+
+    let line = HTTP_VERSION_REGEX
+        .find(line)
+        .map_or(line, |version| &line[..version.start()]);
+    line.split_once(char::is_whitespace)
+        .and_then(|(method, url)| Some((method.parse().ok()?, url.trim())))
+        .unwrap_or((Method::Get, line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collection::CollectionFile, test_util::test_data_dir, util::parse_yaml,
+    };
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use std::path::PathBuf;
+
+    const REST_CLIENT_FILE: &str = "rest_client.http";
+    /// Expected collection is stored in a separate file, same as the other
+    /// importers
+    const REST_CLIENT_IMPORTED_FILE: &str = "rest_client_imported.yml";
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_rest_client_import(test_data_dir: PathBuf) {
+        let imported =
+            Collection::from_rest_client(test_data_dir.join(REST_CLIENT_FILE))
+                .unwrap();
+        let expected =
+            CollectionFile::load(test_data_dir.join(REST_CLIENT_IMPORTED_FILE))
+                .await
+                .unwrap()
+                .collection;
+        assert_eq!(imported, *expected);
+    }
+
+    /// Export a collection, then import it again. Everything that REST Client
+    /// supports should survive the trip
+    #[test]
+    fn test_rest_client_export() {
+        let collection: Collection = parse_yaml(
+            r#"
+profiles:
+  default:
+    data:
+      host: https://example.com
+      user: "{{env.USER}}"
+  other:
+    data:
+      host: https://other.example.com
+
+chains:
+  login_body:
+    source: !request
+      recipe: login
+  login_X-Session:
+    source: !request
+      recipe: login
+      section: !header X-Session
+  password:
+    source: !prompt
+      message: Password
+
+requests:
+  login: !request
+    name: Log in
+    method: POST
+    url: "{{host}}/login"
+    body: !json { "username": "{{user}}", "id": "{{uuid()}}" }
+  users: !folder
+    requests:
+      get_user: !request
+        method: GET
+        url: "{{host}}/users/1"
+        query:
+          token: "{{chains.login_body}}"
+        authentication: !basic
+          username: "{{user}}"
+          password: "{{chains.password}}"
+      put_user: !request
+        method: PUT
+        url: "{{host}}/users/1"
+        headers:
+          X-Session: "{{chains.login_X-Session}}"
+        body: !form_urlencoded
+          age: "{{random_int(1, 99)}}"
+          name: "{{fake.first_name | upper}}"
+"#
+            .as_bytes(),
+        )
+        .unwrap();
+        let exported =
+            collection.to_rest_client(Some(&"default".into())).unwrap();
+        assert_eq!(
+            exported,
+            r#"@host = https://example.com
+@user = {{$processEnv USER}}
+
+### Log in
+# @name login
+POST {{host}}/login
+Content-Type: application/json
+
+{
+  "id": "{{$guid}}",
+  "username": "{{user}}"
+}
+
+###
+# @name get_user
+GET {{host}}/users/1?token={{login.response.body.*}}
+Authorization: Basic {{user}}:{{chains.password}}
+
+###
+# @name put_user
+PUT {{host}}/users/1
+X-Session: {{login.response.headers.X-Session}}
+Content-Type: application/x-www-form-urlencoded
+
+age={{$randomInt 1 100}}
+&name={{fake.first_name | upper}}
+"#
+        );
+
+        // Everything but the untranslated templates comes back the same
+        let imported = Importer::default().import(&exported).unwrap();
+        assert_eq!(
+            imported.chains.keys().map(ChainId::to_string).collect_vec(),
+            ["login_body", "login_X-Session"]
+        );
+        assert_eq!(
+            imported.recipes.get_recipe(&"login".into()),
+            collection.recipes.get_recipe(&"login".into())
+        );
+        assert_eq!(
+            imported
+                .recipes
+                .get_recipe(&"put_user".into())
+                .unwrap()
+                .headers,
+            collection
+                .recipes
+                .get_recipe(&"put_user".into())
+                .unwrap()
+                .headers,
+        );
+    }
+
+    /// An unknown profile is an error
+    #[test]
+    fn test_rest_client_export_unknown_profile() {
+        assert_eq!(
+            Collection::default()
+                .to_rest_client(Some(&"default".into()))
+                .unwrap_err()
+                .to_string(),
+            "Unknown profile `default`"
+        );
+    }
+
+    #[rstest]
+    #[case::method_and_url(
+        "POST https://example.com",
+        Method::Post,
+        "https://example.com"
+    )]
+    #[case::http_version(
+        "PUT https://example.com HTTP/1.1",
+        Method::Put,
+        "https://example.com"
+    )]
+    #[case::url_only("https://example.com", Method::Get, "https://example.com")]
+    #[case::template_url("GET {{host}}/users", Method::Get, "{{host}}/users")]
+    fn test_parse_request_line(
+        #[case] line: &str,
+        #[case] expected_method: Method,
+        #[case] expected_url: &str,
+    ) {
+        assert_eq!(parse_request_line(line), (expected_method, expected_url));
+    }
+}
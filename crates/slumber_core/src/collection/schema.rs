@@ -0,0 +1,400 @@
+//! JSON Schema for the collection format, for editor autocomplete and
+//! validation. Most of the schema is derived from the serde types, so it stays
+//! in sync with what we actually accept. Types with hand-written serde impls
+//! get hand-written schemas here.
+
+use crate::{
+    collection::{
+        Collection, Method, RecipeBody, RecipeId, RecipeNode, RecipeTree,
+    },
+    http::query::{Pattern, Query, RegexSelector, Selector},
+    template::Template,
+};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{
+        ArrayValidation, InstanceType, Metadata, ObjectValidation, RootSchema,
+        Schema, SchemaObject, SingleOrVec, StringValidation,
+        SubschemaValidation,
+    },
+    visit::{self, Visitor},
+    JsonSchema,
+};
+use std::{collections::BTreeSet, mem};
+use strum::IntoEnumIterator;
+
+/// Generate a JSON Schema for the collection file format.
+///
+/// JSON Schema has no concept of YAML tags, so enums that serde represents
+/// with tags (e.g. `!request`) are described by their content alone. Each
+/// variant's tag is given as its title. Editors generally need to be told
+/// about the tags separately so they don't reject them.
+pub fn json_schema() -> RootSchema {
+    let mut schema = SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<Collection>();
+    let metadata = schema.schema.metadata();
+    metadata.title = Some("Slumber collection".into());
+    metadata.description =
+        Some("Profiles, chains, and request recipes for Slumber".into());
+    if let Some(Schema::Object(recipe)) = schema.definitions.get_mut("Recipe") {
+        allow_extends(recipe);
+    }
+    UntagVariants.visit_root_schema(&mut schema);
+    schema
+}
+
+/// A recipe that extends another can leave out fields that are otherwise
+/// required, because inheritance is resolved before the recipe is
+/// deserialized. `extends` never makes it to the serde type, so add it here.
+fn allow_extends(recipe: &mut SchemaObject) {
+    let object = recipe.object();
+    object.properties.insert(
+        "extends".into(),
+        described("ID of a recipe to inherit fields from", string_schema()),
+    );
+    let required = mem::take(&mut object.required);
+    let require = |fields: BTreeSet<String>| -> Schema {
+        SchemaObject {
+            object: Some(Box::new(ObjectValidation {
+                required: fields,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    };
+    recipe.subschemas().any_of =
+        Some(vec![require(required), require(["extends".into()].into())]);
+}
+
+/// Schema visitor that rewrites externally tagged enum variants to describe
+/// only their content. schemars describes each variant as an object with the
+/// variant name as its only key, but in YAML the name is given as a tag. Once
+/// their names are removed variants may overlap, so `oneOf` is loosened to
+/// `anyOf`.
+#[derive(Clone, Debug)]
+struct UntagVariants;
+
+impl Visitor for UntagVariants {
+    fn visit_schema_object(&mut self, schema: &mut SchemaObject) {
+        if let Some(subschemas) = &mut schema.subschemas {
+            if let Some(variants) = &mut subschemas.one_of {
+                let mut untagged = false;
+                for variant in variants.iter_mut() {
+                    if let Some(content) = untag_variant(variant) {
+                        *variant = content;
+                        untagged = true;
+                    }
+                }
+                if untagged {
+                    subschemas.any_of = subschemas.one_of.take();
+                }
+            }
+        }
+        visit::visit_schema_object(self, schema);
+    }
+}
+
+/// If a schema describes an externally tagged enum variant, i.e. an object
+/// with exactly one required property and nothing else, get a schema for just
+/// the variant's content
+fn untag_variant(variant: &Schema) -> Option<Schema> {
+    let Schema::Object(variant) = variant else {
+        return None;
+    };
+    let object = variant.object.as_deref()?;
+    let (tag, content) = object.properties.iter().exactly_one().ok()?;
+    if !object.required.contains(tag)
+        || !matches!(
+            object.additional_properties.as_deref(),
+            Some(Schema::Bool(false))
+        )
+    {
+        return None;
+    }
+    let description = variant
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.description.clone());
+    Some(tagged(tag, content.clone(), description))
+}
+
+/// Describe the content of a tagged value, with the tag as the title
+fn tagged(tag: &str, content: Schema, description: Option<String>) -> Schema {
+    let mut content = match content {
+        Schema::Object(content) if content.reference.is_none() => content,
+        // Annotations alongside a `$ref` are ignored, so it has to be wrapped
+        content => SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                all_of: Some(vec![content]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        },
+    };
+    let metadata = content.metadata();
+    metadata.title = Some(format!("!{tag}"));
+    if description.is_some() {
+        metadata.description = description;
+    }
+    content.into()
+}
+
+/// Query parameters can be given as a map, or as a list of `param=value` to
+/// allow repeated parameters
+pub(super) fn query_parameters(gen: &mut SchemaGenerator) -> Schema {
+    let list = SchemaObject {
+        instance_type: Some(InstanceType::Array.into()),
+        array: Some(Box::new(ArrayValidation {
+            items: Some(SingleOrVec::Single(Box::new(
+                SchemaObject {
+                    string: Some(Box::new(StringValidation {
+                        pattern: Some("^[^=]+=".into()),
+                        ..Default::default()
+                    })),
+                    ..string_schema()
+                }
+                .into(),
+            ))),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    any_of(vec![
+        gen.subschema_for::<IndexMap<String, Template>>(),
+        list.into(),
+    ])
+}
+
+impl JsonSchema for Template {
+    fn schema_name() -> String {
+        "Template".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        // Numbers and booleans are accepted and converted to strings
+        described(
+            "Template string, e.g. `{{host}}/users/{{user_id}}`",
+            SchemaObject {
+                instance_type: Some(SingleOrVec::Vec(vec![
+                    InstanceType::String,
+                    InstanceType::Number,
+                    InstanceType::Boolean,
+                ])),
+                ..Default::default()
+            },
+        )
+    }
+}
+
+impl JsonSchema for Method {
+    fn schema_name() -> String {
+        "Method".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        // Parsing is case-insensitive, but only suggest the common casings
+        let methods = Method::iter()
+            .flat_map(|method| {
+                let method = method.to_string();
+                [method.to_lowercase(), method]
+            })
+            .map(serde_json::Value::from)
+            .collect();
+        described(
+            "HTTP method",
+            SchemaObject {
+                enum_values: Some(methods),
+                ..string_schema()
+            },
+        )
+    }
+}
+
+impl JsonSchema for RecipeTree {
+    fn schema_name() -> String {
+        "RecipeTree".into()
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        <IndexMap<RecipeId, RecipeNode>>::json_schema(gen)
+    }
+}
+
+impl JsonSchema for RecipeBody {
+    fn schema_name() -> String {
+        "RecipeBody".into()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let form = gen.subschema_for::<IndexMap<String, Template>>();
+        any_of(vec![
+            gen.subschema_for::<Template>(),
+            tagged(
+                "json",
+                Schema::Bool(true),
+                Some("JSON body. Strings within the body are templates".into()),
+            ),
+            tagged(
+                "form_urlencoded",
+                form.clone(),
+                Some("URL-encoded form fields".into()),
+            ),
+            tagged(
+                "form_multipart",
+                form,
+                Some("Multipart form fields".into()),
+            ),
+        ])
+    }
+}
+
+impl JsonSchema for Selector {
+    fn schema_name() -> String {
+        "Selector".into()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        any_of(vec![
+            described(
+                "JSONPath if it starts with `$`, otherwise XPath",
+                string_schema(),
+            ),
+            tagged("regex", gen.subschema_for::<RegexSelector>(), None),
+        ])
+    }
+}
+
+impl JsonSchema for Query {
+    fn schema_name() -> String {
+        "Query".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        described("JSONPath query, e.g. `$.data[0].id`", string_schema())
+    }
+}
+
+impl JsonSchema for Pattern {
+    fn schema_name() -> String {
+        "Pattern".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            format: Some("regex".into()),
+            ..string_schema()
+        }
+        .into()
+    }
+}
+
+fn string_schema() -> SchemaObject {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        ..Default::default()
+    }
+}
+
+fn described(description: &str, mut schema: SchemaObject) -> Schema {
+    schema.metadata = Some(Box::new(Metadata {
+        description: Some(description.into()),
+        ..Default::default()
+    }));
+    schema.into()
+}
+
+fn any_of(schemas: Vec<Schema>) -> Schema {
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(schemas),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+
+    fn schema() -> Value {
+        serde_json::to_value(json_schema()).unwrap()
+    }
+
+    /// Tagged enum variants are described by their content, with the tag as
+    /// the title
+    #[test]
+    fn test_untag_variants() {
+        let schema = schema();
+        let variants = schema.pointer("/definitions/RecipeNode/anyOf").unwrap();
+        assert_eq!(
+            variants,
+            &json!([
+                {
+                    "title": "!folder",
+                    "allOf": [{"$ref": "#/definitions/Folder"}],
+                },
+                {
+                    "title": "!request",
+                    "allOf": [{"$ref": "#/definitions/Recipe"}],
+                },
+            ])
+        );
+
+        let sources = schema
+            .pointer("/definitions/ChainSource/anyOf")
+            .and_then(Value::as_array)
+            .unwrap();
+        let tags = sources
+            .iter()
+            .map(|source| source["title"].as_str().unwrap())
+            .collect_vec();
+        assert!(tags.contains(&"!request"), "{tags:?}");
+        assert!(tags.contains(&"!env"), "{tags:?}");
+        assert_eq!(schema.pointer("/definitions/ChainSource/oneOf"), None);
+    }
+
+    /// A recipe only needs a method and URL if it doesn't extend another
+    #[test]
+    fn test_recipe_extends() {
+        let schema = schema();
+        let recipe = &schema["definitions"]["Recipe"];
+        assert_eq!(recipe["properties"]["extends"]["type"], "string");
+        assert_eq!(recipe.get("required"), None);
+        assert_eq!(
+            recipe["anyOf"],
+            json!([
+                {"required": ["method", "url"]},
+                {"required": ["extends"]},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_hand_written_schemas() {
+        let schema = schema();
+        let methods =
+            schema["definitions"]["Method"]["enum"].as_array().unwrap();
+        assert!(methods.contains(&json!("GET")));
+        assert!(methods.contains(&json!("get")));
+        assert_eq!(
+            schema["definitions"]["Template"]["type"],
+            json!(["string", "number", "boolean"])
+        );
+        assert_eq!(
+            schema["properties"]["requests"]["additionalProperties"]["$ref"],
+            "#/definitions/RecipeNode"
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Find where things are defined in YAML source. serde_yaml throws away
+//! locations once a document is parsed, so we parse it a second time with an
+//! event-based parser that reports where each node starts.
+
+use std::collections::HashMap;
+use yaml_rust2::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker, TScalarStyle},
+};
+
+/// The line where each map key in a YAML document is defined. Aliases and
+/// merge keys (`<<`) are resolved the same way as when the collection is
+/// loaded, so a value that comes from an anchor points at the anchor's
+/// definition.
+#[derive(Debug)]
+pub(super) struct Spans(Node);
+
+impl Spans {
+    /// Parse YAML source text. Return `None` if it's invalid
+    pub fn parse(source: &str) -> Option<Self> {
+        let mut builder = Builder::default();
+        Parser::new_from_str(source)
+            .load(&mut builder, false)
+            .ok()?;
+        builder.root.map(Self)
+    }
+
+    /// Get the line (starting at 1) where a path of map keys is defined.
+    /// Return `None` if any key in the path is missing
+    pub fn find_line(&self, key_path: &[&str]) -> Option<usize> {
+        let mut node = &self.0;
+        let mut line = None;
+        for key in key_path {
+            let Node::Mapping(entries) = node else {
+                return None;
+            };
+            let entry = entries
+                .iter()
+                .find(|entry| entry.key.as_deref() == Some(*key))?;
+            node = &entry.value;
+            line = Some(entry.line);
+        }
+        line
+    }
+}
+
+/// Get the line (starting at 1) where a path of map keys is defined in YAML
+/// source text. See [Spans::find_line]
+pub(super) fn find_line(source: &str, key_path: &[&str]) -> Option<usize> {
+    Spans::parse(source)?.find_line(key_path)
+}
+
+/// A YAML node, reduced to the parts needed to locate map keys
+#[derive(Clone, Debug)]
+enum Node {
+    Mapping(Vec<Entry>),
+    Sequence(Vec<Node>),
+    Scalar,
+}
+
+/// A single map entry
+#[derive(Clone, Debug)]
+struct Entry {
+    /// `None` for keys that aren't strings, which can't be looked up
+    key: Option<String>,
+    /// Line where the key is defined (starting at 1)
+    line: usize,
+    value: Node,
+}
+
+/// Map key that's been parsed, while we wait for its value
+enum Key {
+    /// `<<`, which merges another map into this one
+    Merge,
+    Value(Option<String>, usize),
+}
+
+/// A collection node that's still being parsed
+enum Partial {
+    Mapping {
+        entries: Vec<Entry>,
+        /// Maps merged into this one, to be applied once it's done
+        merges: Vec<Node>,
+        key: Option<Key>,
+    },
+    Sequence(Vec<Node>),
+}
+
+/// Build a [Node] tree from parser events
+#[derive(Default)]
+struct Builder {
+    /// Collections being parsed, innermost last, with their anchor IDs
+    stack: Vec<(Partial, usize)>,
+    /// Every anchored node parsed so far, by anchor ID
+    anchors: HashMap<usize, Node>,
+    root: Option<Node>,
+}
+
+impl Builder {
+    /// Add a completed node to its parent, or make it the root if it has no
+    /// parent. `anchor_id` is 0 if the node isn't anchored.
+    fn push(&mut self, node: Node, anchor_id: usize, key: Option<Key>) {
+        if anchor_id != 0 {
+            self.anchors.insert(anchor_id, node.clone());
+        }
+        match self.stack.last_mut() {
+            None => self.root = Some(node),
+            Some((Partial::Sequence(items), _)) => items.push(node),
+            Some((
+                Partial::Mapping {
+                    entries,
+                    merges,
+                    key: pending,
+                },
+                _,
+            )) => match pending.take() {
+                // This node is the key; the value comes next
+                None => *pending = key.or(Some(Key::Value(None, 0))),
+                Some(Key::Merge) => merges.push(node),
+                Some(Key::Value(key, line)) => entries.push(Entry {
+                    key,
+                    line,
+                    value: node,
+                }),
+            },
+        }
+    }
+}
+
+impl MarkedEventReceiver for Builder {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        match event {
+            Event::Scalar(value, style, anchor_id, _) => {
+                let key = if value == "<<" && style == TScalarStyle::Plain {
+                    Key::Merge
+                } else {
+                    Key::Value(Some(value), marker.line())
+                };
+                self.push(Node::Scalar, anchor_id, Some(key));
+            }
+            Event::Alias(anchor_id) => {
+                let node = self
+                    .anchors
+                    .get(&anchor_id)
+                    .cloned()
+                    .unwrap_or(Node::Scalar);
+                self.push(node, 0, None);
+            }
+            Event::MappingStart(anchor_id, _) => self.stack.push((
+                Partial::Mapping {
+                    entries: Vec::new(),
+                    merges: Vec::new(),
+                    key: None,
+                },
+                anchor_id,
+            )),
+            Event::SequenceStart(anchor_id, _) => {
+                self.stack.push((Partial::Sequence(Vec::new()), anchor_id));
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                let Some((partial, anchor_id)) = self.stack.pop() else {
+                    return;
+                };
+                let node = match partial {
+                    Partial::Mapping {
+                        mut entries,
+                        merges,
+                        ..
+                    } => {
+                        merge(&mut entries, merges);
+                        Node::Mapping(entries)
+                    }
+                    Partial::Sequence(items) => Node::Sequence(items),
+                };
+                self.push(node, anchor_id, None);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Apply merge keys to a map's entries. Keys defined in the map itself take
+/// precedence, followed by earlier merged maps over later ones. A merged value
+/// can be a map, or a sequence of maps.
+fn merge(entries: &mut Vec<Entry>, merges: Vec<Node>) {
+    let merged = merges.into_iter().flat_map(|node| match node {
+        Node::Mapping(entries) => entries,
+        Node::Sequence(items) => items
+            .into_iter()
+            .flat_map(|item| match item {
+                Node::Mapping(entries) => entries,
+                _ => Vec::new(),
+            })
+            .collect(),
+        Node::Scalar => Vec::new(),
+    });
+    for entry in merged {
+        if !entries.iter().any(|existing| existing.key == entry.key) {
+            entries.push(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::top_level(&["chains"], Some(4))]
+    // Keys with the same name at other levels are skipped
+    #[case::nested(&["requests", "users", "requests", "login"], Some(10))]
+    #[case::quoted(&["requests", "users", "requests", "requests"], Some(15))]
+    #[case::missing(&["requests", "login"], None)]
+    #[case::flow(&["profiles", "local", "data"], Some(2))]
+    #[case::flow_multiline(&["chains", "token", "source"], Some(6))]
+    // Keys inside a block scalar are just text
+    #[case::block_scalar(
+        &["requests", "users", "requests", "login", "other"],
+        None
+    )]
+    // Values from an alias point at the anchor
+    #[case::alias(&["requests", "copy", "requests", "login"], Some(10))]
+    // Merged keys point at the merged map, unless they're overridden
+    #[case::merge(&["requests", "merged", "method"], Some(11))]
+    #[case::merge_override(&["requests", "merged", "url"], Some(20))]
+    #[case::merge_own(&["requests", "merged", "headers"], Some(21))]
+    fn test_find_line(
+        #[case] key_path: &[&str],
+        #[case] expected: Option<usize>,
+    ) {
+        let source = "# Comment
+profiles: {local: {data: {}}}
+
+chains:
+  token: {
+    source: !file {path: token.txt}}
+requests:
+  users: &users !folder
+    requests:
+      login: !request &base
+        method: GET
+        url: /login
+        body: |
+          other: 1
+      \"requests\": !request
+        headers:
+          login: x
+  merged: !request
+    <<: *base
+    url: /other
+    headers: {}
+  copy: *users
+";
+        assert_eq!(find_line(source, key_path), expected);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Spans::parse("a: [").is_none());
+    }
+}
@@ -0,0 +1,200 @@
+//! A workspace groups multiple collection files, e.g. one per service in a
+//! monorepo, so they can be switched between without restarting
+
+use crate::{
+    collection::{detect_path, search_dir, CONFIG_FILES},
+    util::{parse_yaml, ResultTraced},
+};
+use anyhow::{anyhow, Context};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde::Deserialize;
+use std::{
+    env,
+    fs::File,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// The supported file names for a workspace, in order of precedence
+const WORKSPACE_FILES: &[&str] =
+    &["slumber-workspace.yml", "slumber-workspace.yaml"];
+
+/// A set of labelled collection files, loaded from `slumber-workspace.yml`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Workspace {
+    /// Directory containing the workspace file. Member paths are relative to
+    /// this
+    #[serde(skip)]
+    directory: PathBuf,
+    /// Collections in the workspace, keyed by label
+    pub members: IndexMap<String, WorkspaceMember>,
+}
+
+/// A single collection within a workspace
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceMember {
+    #[serde(skip)] // This will be auto-populated from the map key
+    pub id: String,
+    pub name: Option<String>,
+    /// Path to the collection file, or a directory containing one
+    pub path: PathBuf,
+}
+
+impl Workspace {
+    /// Search the given directory and its ancestors for a workspace file. If
+    /// the directory isn't given, default to the current directory. Return
+    /// `None` if there is no workspace.
+    pub fn detect(dir: Option<PathBuf>) -> anyhow::Result<Option<Self>> {
+        let dir = if let Some(dir) = dir {
+            dir
+        } else {
+            env::current_dir()?
+        };
+        detect_path(&dir, WORKSPACE_FILES)
+            .map(|path| Self::load(&path))
+            .transpose()
+    }
+
+    /// Load a workspace from the given file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        info!(?path, "Loading workspace file");
+        let load = || -> anyhow::Result<Self> {
+            let file = File::open(path)?;
+            Ok(parse_yaml(&file)?)
+        };
+        let mut workspace = load()
+            .with_context(|| format!("Error loading workspace from {path:?}"))
+            .traced()?;
+        workspace.directory =
+            path.parent().map(Path::to_owned).unwrap_or_default();
+        for (id, member) in &mut workspace.members {
+            member.id = id.clone();
+        }
+        Ok(workspace)
+    }
+
+    /// Get a member by its label
+    pub fn member(&self, id: &str) -> anyhow::Result<&WorkspaceMember> {
+        self.members.get(id).ok_or_else(|| {
+            anyhow!(
+                "No workspace member `{id}`; options are: {}",
+                self.members.keys().format(", ")
+            )
+        })
+    }
+
+    /// Get the path to a member's collection file. If the member's path is a
+    /// directory, search it (but not its ancestors) for a collection file.
+    pub fn member_path(&self, id: &str) -> anyhow::Result<PathBuf> {
+        let member = self.member(id)?;
+        let path = self.directory.join(&member.path);
+        if path.is_dir() {
+            search_dir(&path, CONFIG_FILES).ok_or_else(|| {
+                anyhow!("No collection file found in {path:?} for `{id}`")
+            })
+        } else {
+            Ok(path)
+        }
+    }
+
+    /// Find the member whose collection is at the given path, if any
+    pub fn member_for_path(&self, path: &Path) -> Option<&WorkspaceMember> {
+        // Canonicalize so relative paths and symlinks match
+        let path = path.canonicalize().ok()?;
+        self.members.values().find(|member| {
+            self.member_path(&member.id)
+                .and_then(|member_path| Ok(member_path.canonicalize()?))
+                .is_ok_and(|member_path| member_path == path)
+        })
+    }
+}
+
+impl WorkspaceMember {
+    /// Get a presentable name for this member
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_err,
+        test_util::{temp_dir, TempDir},
+    };
+    use rstest::rstest;
+    use std::fs;
+
+    /// Create a workspace with members referenced by directory and by file, plus
+    /// one whose directory has no collection
+    fn workspace(temp_dir: &Path) -> PathBuf {
+        fs::create_dir_all(temp_dir.join("users")).unwrap();
+        fs::create_dir_all(temp_dir.join("billing")).unwrap();
+        fs::write(temp_dir.join("users/slumber.yml"), "requests: {}").unwrap();
+        fs::write(temp_dir.join("billing/api.yml"), "requests: {}").unwrap();
+        let path = temp_dir.join("slumber-workspace.yml");
+        fs::write(
+            &path,
+            "members:
+  users:
+    name: User Service
+    path: users
+  billing:
+    path: billing/api.yml
+  empty:
+    path: .
+",
+        )
+        .unwrap();
+        path
+    }
+
+    #[rstest]
+    fn test_detect(temp_dir: TempDir) {
+        assert!(Workspace::detect(Some(temp_dir.to_path_buf()))
+            .unwrap()
+            .is_none());
+
+        workspace(&temp_dir);
+        // Detect from a child directory
+        let workspace = Workspace::detect(Some(temp_dir.join("users")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            workspace.members.keys().collect_vec(),
+            ["users", "billing", "empty"]
+        );
+        assert_eq!(workspace.member("users").unwrap().name(), "User Service");
+        assert_eq!(workspace.member("billing").unwrap().name(), "billing");
+    }
+
+    #[rstest]
+    fn test_member_path(temp_dir: TempDir) {
+        let workspace = Workspace::load(&workspace(&temp_dir)).unwrap();
+        assert_eq!(
+            workspace.member_path("users").unwrap(),
+            temp_dir.join("users/slumber.yml")
+        );
+        assert_eq!(
+            workspace.member_path("billing").unwrap(),
+            temp_dir.join("billing/api.yml")
+        );
+        assert_err!(workspace.member_path("empty"), "No collection file found");
+        assert_err!(
+            workspace.member_path("bogus"),
+            "No workspace member `bogus`; options are: users, billing, empty"
+        );
+
+        let member = workspace
+            .member_for_path(&temp_dir.join("billing/../billing/api.yml"))
+            .unwrap();
+        assert_eq!(member.id, "billing");
+        assert!(workspace
+            .member_for_path(&temp_dir.join("slumber-workspace.yml"))
+            .is_none());
+    }
+}
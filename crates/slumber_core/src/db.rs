@@ -2,16 +2,29 @@
 //! responses.
 
 mod convert;
+mod encryption;
 mod migrations;
 
+pub use encryption::{HistoryKey, KeySource};
+
 use crate::{
-    collection::{ProfileId, RecipeId},
-    db::convert::{CollectionPath, JsonEncoded, SqlWrap},
-    http::{Exchange, ExchangeSummary, RequestId},
+    collection::{ChainId, ProfileId, RecipeId},
+    db::{
+        convert::{CollectionPath, JsonEncoded, SqlWrap},
+        encryption::EncryptionRecord,
+    },
+    http::{
+        blob::{BlobRef, BlobStore},
+        failure::FailureKind,
+        CapturedRequest, Exchange, ExchangeSummary, RequestBuildError,
+        RequestError, RequestId,
+    },
     util::{DataDirectory, ResultTraced},
 };
 use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
 use derive_more::Display;
+use reqwest::{Method, StatusCode, Url};
 use rusqlite::{named_params, Connection, DatabaseName, OptionalExtension};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
@@ -51,8 +64,11 @@ impl Database {
 
     /// Load the database. This will perform migrations, but can be called from
     /// anywhere in the app. The migrations will run on first connection, and
-    /// not after that.
-    pub fn load() -> anyhow::Result<Self> {
+    /// not after that. If history is encrypted with a passphrase, `passphrase`
+    /// is called to get it from the user.
+    pub fn load(
+        passphrase: impl FnOnce() -> anyhow::Result<String>,
+    ) -> anyhow::Result<Self> {
         let path = Self::path();
         info!(?path, "Loading database");
         let mut connection = Connection::open(path)?;
@@ -64,6 +80,21 @@ impl Database {
         // Use WAL for concurrency
         connection.pragma_update(None, "journal_mode", "WAL")?;
         Self::migrate(&mut connection)?;
+        Self::init_connection(&connection)?;
+        let database = Self {
+            connection: Arc::new(Mutex::new(connection)),
+        };
+        database.unlock(passphrase)?;
+        Ok(database)
+    }
+
+    /// Create a database that lives only in memory, and is discarded when
+    /// it's dropped. Nothing is read from or written to disk, so this is
+    /// useful for tests.
+    pub fn in_memory() -> anyhow::Result<Self> {
+        let mut connection = Connection::open_in_memory()?;
+        Self::migrate(&mut connection)?;
+        Self::init_connection(&connection)?;
         Ok(Self {
             connection: Arc::new(Mutex::new(connection)),
         })
@@ -80,6 +111,291 @@ impl Database {
         Ok(())
     }
 
+    /// Set up state that lives on the connection rather than in the file: the
+    /// encryption functions, and a view that decrypts request history. Until
+    /// [Self::unlock] is called, the functions pass values through unchanged.
+    fn init_connection(connection: &Connection) -> anyhow::Result<()> {
+        encryption::register_functions(connection, None)?;
+        connection.execute_batch(
+            "CREATE TEMP VIEW requests_decrypted AS
+            SELECT
+                id,
+                collection_id,
+                profile_id,
+                recipe_id,
+                start_time,
+                end_time,
+                method,
+                url,
+                slumber_decrypt(request_headers) AS request_headers,
+                slumber_decrypt(request_body) AS request_body,
+                status_code,
+                slumber_decrypt(response_headers) AS response_headers,
+                slumber_decrypt(response_body) AS response_body,
+                response_body_blob,
+                response_body_size,
+                response_body_full_size,
+                CAST(slumber_decrypt(environment) AS TEXT) AS environment,
+                note,
+                timing,
+                certificate
+            FROM requests_v2",
+        )?;
+        Ok(())
+    }
+
+    /// If request history is encrypted, load its key so history can be read
+    /// and written. The passphrase is only requested if the key is protected
+    /// by one.
+    fn unlock(
+        &self,
+        passphrase: impl FnOnce() -> anyhow::Result<String>,
+    ) -> anyhow::Result<()> {
+        let connection = self.connection();
+        if let Some(record) = Self::encryption_record(&connection)? {
+            info!(source = %record.source, "Unlocking encrypted history");
+            let cipher = record.unlock(passphrase)?;
+            encryption::register_functions(&connection, Some(cipher.into()))?;
+            // Zero out deleted content, so plaintext doesn't linger in free
+            // pages
+            connection.pragma_update(None, "secure_delete", "ON")?;
+        }
+        Ok(())
+    }
+
+    fn encryption_record(
+        connection: &Connection,
+    ) -> anyhow::Result<Option<EncryptionRecord>> {
+        connection
+            .query_row("SELECT * FROM history_encryption", [], |row| {
+                Ok(EncryptionRecord {
+                    source: row.get("key_source")?,
+                    salt: row.get("salt")?,
+                    iterations: row.get("iterations")?,
+                    wrapped_key: row.get("wrapped_key")?,
+                })
+            })
+            .optional()
+            .context("Error loading history encryption settings")
+            .traced()
+    }
+
+    /// Get where the key for encrypted request history is kept, or `None` if
+    /// history isn't encrypted
+    pub fn encryption(&self) -> anyhow::Result<Option<KeySource>> {
+        Ok(Self::encryption_record(&self.connection())?
+            .map(|record| record.source))
+    }
+
+    /// Encrypt request and response headers and bodies for all existing
+    /// history, and all history stored from now on. Environment snapshots,
+    /// captured requests, cached chain values, and recipe drafts are
+    /// encrypted too. Large bodies are moved out of the blob store and into
+    /// the database, because blobs are stored in plaintext. Search will no
+    /// longer find text in headers or bodies, because they're removed from the
+    /// index. Once everything is encrypted, the database file is compacted so
+    /// the old plaintext doesn't linger in free pages.
+    pub fn encrypt_history(&self, key: HistoryKey) -> anyhow::Result<()> {
+        self.encrypt_history_with(key, BlobStore::global)
+    }
+
+    /// [Self::encrypt_history], with a specific blob store. The store is only
+    /// created if there are blobs to move
+    fn encrypt_history_with(
+        &self,
+        key: HistoryKey,
+        blob_store: impl FnOnce() -> BlobStore,
+    ) -> anyhow::Result<()> {
+        let mut connection = self.connection();
+        if Self::encryption_record(&connection)?.is_some() {
+            return Err(anyhow!("History is already encrypted"));
+        }
+        let (record, cipher) = EncryptionRecord::create(key)?;
+        info!(source = %record.source, "Encrypting history");
+        encryption::register_functions(&connection, Some(cipher.into()))?;
+        connection.pragma_update(None, "secure_delete", "ON")?;
+
+        let result = (|| {
+            let transaction = connection.transaction()?;
+            transaction.execute(
+                "INSERT INTO history_encryption
+                (id, key_source, salt, iterations, wrapped_key)
+                VALUES (0, :key_source, :salt, :iterations, :wrapped_key)",
+                named_params! {
+                    ":key_source": record.source,
+                    ":salt": record.salt,
+                    ":iterations": record.iterations,
+                    ":wrapped_key": record.wrapped_key,
+                },
+            )?;
+            transaction.execute_batch(
+                "UPDATE requests_v2 SET
+                    request_headers = slumber_encrypt(request_headers),
+                    request_body = slumber_encrypt(request_body),
+                    response_headers = slumber_encrypt(response_headers),
+                    response_body = slumber_encrypt(response_body),
+                    environment = slumber_encrypt(environment);
+                UPDATE requests_fts SET
+                    request_headers = NULL,
+                    request_body = NULL,
+                    response_headers = NULL,
+                    response_body = NULL;
+                UPDATE captured_requests SET
+                    headers = slumber_encrypt(headers),
+                    body = slumber_encrypt(body);
+                UPDATE chain_cache SET value = slumber_encrypt(value);
+                UPDATE recipe_drafts SET value = slumber_encrypt(value);",
+            )?;
+            let blobs = Self::inline_blobs(&transaction, blob_store)?;
+            // Merge the index's segments, so the deleted text is dropped
+            transaction.execute(
+                "INSERT INTO requests_fts (requests_fts) VALUES ('optimize')",
+                [],
+            )?;
+            transaction.commit()?;
+            Ok::<_, anyhow::Error>(blobs)
+        })();
+        let blobs = match result {
+            Ok(blobs) => blobs,
+            Err(error) => {
+                // Nothing was written, so go back to storing plaintext
+                encryption::register_functions(&connection, None)?;
+                let _ = record.delete_key().traced();
+                return Err(error.context("Error encrypting history")).traced();
+            }
+        };
+
+        // History is safely encrypted now, so the rest is just cleanup of
+        // plaintext copies. The blobs aren't referenced anymore
+        for path in blobs {
+            let _ = std::fs::remove_file(&path)
+                .with_context(|| format!("Error deleting blob {path:?}"))
+                .traced();
+        }
+        connection
+            .execute_batch(
+                "VACUUM;
+                PRAGMA wal_checkpoint(TRUNCATE);",
+            )
+            .context("Error compacting database")
+            .traced()?;
+        Ok(())
+    }
+
+    /// Move every streamed response body from the blob store into the
+    /// database, encrypting it on the way. Return the paths of the blobs that
+    /// were moved, so they can be deleted once the transaction is committed.
+    /// If a blob can't be read, only its preview is kept, and the body is
+    /// marked as truncated.
+    fn inline_blobs(
+        connection: &Connection,
+        blob_store: impl FnOnce() -> BlobStore,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        let blobs = connection
+            .prepare(
+                "SELECT DISTINCT response_body_blob, response_body_size
+                FROM requests_v2 WHERE response_body_blob IS NOT NULL",
+            )?
+            .query_map([], |row| {
+                Ok(BlobRef {
+                    hash: row.get(0)?,
+                    size: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if blobs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let blob_store = blob_store();
+        let mut paths = Vec::with_capacity(blobs.len());
+        for blob in blobs {
+            match blob_store.read(&blob).traced() {
+                Ok(body) => {
+                    connection.execute(
+                        "UPDATE requests_v2 SET
+                            response_body = slumber_encrypt(:body),
+                            response_body_blob = NULL,
+                            response_body_size = NULL
+                        WHERE response_body_blob = :hash",
+                        named_params! {":body": body, ":hash": blob.hash},
+                    )?;
+                    paths.push(blob_store.path(&blob));
+                }
+                Err(_) => {
+                    connection.execute(
+                        "UPDATE requests_v2 SET
+                            response_body_full_size = coalesce(
+                                response_body_full_size,
+                                response_body_size
+                            ),
+                            response_body_blob = NULL,
+                            response_body_size = NULL
+                        WHERE response_body_blob = :hash",
+                        named_params! {":hash": blob.hash},
+                    )?;
+                }
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Decrypt all request history, and stop encrypting new history. Headers
+    /// and bodies are added back to the search index. If the key was stored
+    /// in the OS keychain, it's deleted.
+    pub fn decrypt_history(&self) -> anyhow::Result<()> {
+        let mut connection = self.connection();
+        let record = Self::encryption_record(&connection)?
+            .ok_or_else(|| anyhow!("History isn't encrypted"))?;
+        info!("Decrypting history");
+
+        let transaction = connection.transaction()?;
+        transaction
+            .execute_batch(
+                "UPDATE requests_v2 SET
+                    request_headers = slumber_decrypt(request_headers),
+                    request_body = slumber_decrypt(request_body),
+                    response_headers = slumber_decrypt(response_headers),
+                    response_body = slumber_decrypt(response_body),
+                    environment =
+                        CAST(slumber_decrypt(environment) AS TEXT);
+                UPDATE captured_requests SET
+                    headers = slumber_decrypt(headers),
+                    body = slumber_decrypt(body);
+                UPDATE chain_cache SET value = slumber_decrypt(value);
+                UPDATE recipe_drafts SET
+                    value = CAST(slumber_decrypt(value) AS TEXT);
+                DELETE FROM history_encryption;
+                DELETE FROM requests_fts;
+                INSERT INTO requests_fts (
+                    request_id,
+                    url,
+                    note,
+                    request_headers,
+                    request_body,
+                    response_headers,
+                    response_body
+                )
+                SELECT
+                    id,
+                    url,
+                    note,
+                    CAST(request_headers AS TEXT),
+                    CAST(request_body AS TEXT),
+                    CAST(response_headers AS TEXT),
+                    CAST(response_body AS TEXT)
+                FROM requests_v2;",
+            )
+            .and_then(|()| transaction.commit())
+            .context("Error decrypting history")
+            .traced()?;
+        encryption::register_functions(&connection, None)?;
+        connection.pragma_update(None, "secure_delete", "OFF")?;
+        // The data is already decrypted, so a stale key is just clutter
+        let _ = record.delete_key().traced();
+        Ok(())
+    }
+
     /// Get a reference to the DB connection. Panics if the lock is poisoned
     fn connection(&self) -> impl '_ + DerefMut<Target = Connection> {
         self.connection.lock().expect("Connection lock poisoned")
@@ -104,31 +420,6 @@ impl Database {
         source: &Path,
         target: &Path,
     ) -> anyhow::Result<()> {
-        fn get_collection_id(
-            connection: &Connection,
-            path: &Path,
-        ) -> anyhow::Result<CollectionId> {
-            // Convert to canonicalize and make serializable
-            let path: CollectionPath = path.try_into()?;
-
-            connection
-                .query_row(
-                    "SELECT id FROM collections WHERE path = :path",
-                    named_params! {":path": &path},
-                    |row| row.get::<_, CollectionId>("id"),
-                )
-                .map_err(|err| match err {
-                    rusqlite::Error::QueryReturnedNoRows => {
-                        // Use Display impl here because this will get shown in
-                        // CLI output
-                        anyhow!("Unknown collection `{path}`")
-                    }
-                    other => anyhow::Error::from(other)
-                        .context("Error fetching collection ID"),
-                })
-                .traced()
-        }
-
         info!(?source, ?target, "Merging database state");
         let connection = self.connection();
 
@@ -157,6 +448,59 @@ impl Database {
             .context("Error migrating table `ui_state_v2`")
             .traced()?;
 
+        connection
+            .execute(
+                "UPDATE captured_requests SET collection_id = :target
+                WHERE collection_id = :source",
+                named_params! {":source": source, ":target": target},
+            )
+            .context("Error migrating table `captured_requests`")
+            .traced()?;
+        // If both collections have a baseline for the same recipe, the most
+        // recently pinned one will win
+        connection
+            .execute(
+                "UPDATE baselines SET collection_id = :target
+                WHERE collection_id = :source",
+                named_params! {":source": source, ":target": target},
+            )
+            .context("Error migrating table `baselines`")
+            .traced()?;
+        connection
+            .execute(
+                "UPDATE request_failures SET collection_id = :target
+                WHERE collection_id = :source",
+                named_params! {":source": source, ":target": target},
+            )
+            .context("Error migrating table `request_failures`")
+            .traced()?;
+        // Same as UI state, drafts with the same name are overwritten
+        connection
+            .execute(
+                "UPDATE OR REPLACE recipe_drafts SET collection_id = :target
+                WHERE collection_id = :source",
+                named_params! {":source": source, ":target": target},
+            )
+            .context("Error migrating table `recipe_drafts`")
+            .traced()?;
+        // Cached chain values are disposable, so just throw them out
+        connection
+            .execute(
+                "DELETE FROM chain_cache WHERE collection_id = :source",
+                named_params! {":source": source},
+            )
+            .context("Error clearing table `chain_cache`")
+            .traced()?;
+        // Rotation positions would collide with the target's, and resetting
+        // them is harmless
+        connection
+            .execute(
+                "DELETE FROM chain_rotations WHERE collection_id = :source",
+                named_params! {":source": source},
+            )
+            .context("Error clearing table `chain_rotations`")
+            .traced()?;
+
         connection
             .execute(
                 "DELETE FROM collections WHERE id = :source",
@@ -167,6 +511,29 @@ impl Database {
         Ok(())
     }
 
+    /// Delete persisted UI state (selected profile/recipe, toggles, etc.) for
+    /// a single collection, or for all collections if no path is given.
+    /// Return the number of values deleted.
+    pub fn clear_ui_state(
+        &self,
+        collection: Option<&Path>,
+    ) -> anyhow::Result<usize> {
+        info!(?collection, "Clearing UI state");
+        let connection = self.connection();
+        let deleted = if let Some(path) = collection {
+            let collection_id = get_collection_id(&connection, path)?;
+            connection.execute(
+                "DELETE FROM ui_state_v2 WHERE collection_id = :collection_id",
+                named_params! {":collection_id": collection_id},
+            )
+        } else {
+            connection.execute("DELETE FROM ui_state_v2", [])
+        }
+        .context("Error clearing UI state")
+        .traced()?;
+        Ok(deleted)
+    }
+
     /// Convert this database connection into a handle for a single collection
     /// file. This will store the collection in the DB if it isn't already,
     /// then grab its generated ID to create a [CollectionDatabase].
@@ -176,7 +543,13 @@ impl Database {
     ) -> anyhow::Result<CollectionDatabase> {
         // Convert to canonicalize and make serializable
         let path: CollectionPath = path.try_into()?;
+        self.into_collection_path(path)
+    }
 
+    fn into_collection_path(
+        self,
+        path: CollectionPath,
+    ) -> anyhow::Result<CollectionDatabase> {
         // We have to set/get in two separate queries, because RETURNING doesn't
         // return anything if the insert didn't modify
         self.connection()
@@ -207,6 +580,33 @@ impl Database {
     }
 }
 
+/// Look up the ID of a collection by its file path. Return an error if the
+/// collection isn't in the database
+fn get_collection_id(
+    connection: &Connection,
+    path: &Path,
+) -> anyhow::Result<CollectionId> {
+    // Convert to canonicalize and make serializable
+    let path: CollectionPath = path.try_into()?;
+
+    connection
+        .query_row(
+            "SELECT id FROM collections WHERE path = :path",
+            named_params! {":path": &path},
+            |row| row.get::<_, CollectionId>("id"),
+        )
+        .map_err(|err| match err {
+            rusqlite::Error::QueryReturnedNoRows => {
+                // Use Display impl here because this will get shown in
+                // CLI output
+                anyhow!("Unknown collection `{path}`")
+            }
+            other => anyhow::Error::from(other)
+                .context("Error fetching collection ID"),
+        })
+        .traced()
+}
+
 /// A collection-specific database handle. This is a wrapper around a [Database]
 /// that restricts all queries to a specific collection ID. Use
 /// [Database::into_collection] to obtain one. You can freely clone this.
@@ -217,6 +617,24 @@ pub struct CollectionDatabase {
 }
 
 impl CollectionDatabase {
+    /// Maximum number of drafts stored per recipe
+    pub const MAX_DRAFTS: usize = 5;
+
+    /// Create a handle for a placeholder collection in a new
+    /// [in-memory](Database::in_memory) database
+    pub fn in_memory() -> anyhow::Result<Self> {
+        // The path is only used as a key, so it doesn't have to exist
+        Database::in_memory()?.into_collection_path(
+            CollectionPath::from_canonical(":memory:".into()),
+        )
+    }
+
+    /// Get the database that this handle belongs to. Use this to get a handle
+    /// for another collection without reopening (and unlocking) the database.
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
+
     /// Get the full path for the collection file associated with this DB handle
     pub fn collection_path(&self) -> anyhow::Result<PathBuf> {
         self.database
@@ -240,7 +658,7 @@ impl CollectionDatabase {
         self.database
             .connection()
             .query_row(
-                "SELECT * FROM requests_v2
+                "SELECT * FROM requests_decrypted
                 WHERE collection_id = :collection_id
                     AND id = :request_id
                 ORDER BY start_time DESC LIMIT 1",
@@ -275,7 +693,7 @@ impl CollectionDatabase {
             .connection()
             .query_row(
                 // `IS` needed for profile_id so `None` will match `NULL`
-                "SELECT * FROM requests_v2
+                "SELECT * FROM requests_decrypted
                 WHERE collection_id = :collection_id
                     AND profile_id IS :profile_id
                     AND recipe_id = :recipe_id
@@ -327,7 +745,14 @@ impl CollectionDatabase {
                     request_body,
                     status_code,
                     response_headers,
-                    response_body
+                    response_body,
+                    response_body_blob,
+                    response_body_size,
+                    response_body_full_size,
+                    environment,
+                    note,
+                    timing,
+                    certificate
                 )
                 VALUES (
                     :id,
@@ -338,11 +763,18 @@ impl CollectionDatabase {
                     :end_time,
                     :method,
                     :url,
-                    :request_headers,
-                    :request_body,
+                    slumber_encrypt(:request_headers),
+                    slumber_encrypt(:request_body),
                     :status_code,
-                    :response_headers,
-                    :response_body
+                    slumber_encrypt(:response_headers),
+                    slumber_encrypt(:response_body),
+                    :response_body_blob,
+                    :response_body_size,
+                    :response_body_full_size,
+                    slumber_encrypt(:environment),
+                    :note,
+                    :timing,
+                    :certificate
                 )",
                 named_params! {
                     ":id": exchange.id,
@@ -360,6 +792,26 @@ impl CollectionDatabase {
                     ":status_code": exchange.response.status.as_u16(),
                     ":response_headers": SqlWrap(&exchange.response.headers),
                     ":response_body": exchange.response.body.bytes(),
+                    ":response_body_blob": exchange
+                        .response
+                        .body
+                        .blob()
+                        .map(|blob| &blob.hash),
+                    ":response_body_size": exchange
+                        .response
+                        .body
+                        .blob()
+                        .map(|blob| blob.size),
+                    ":response_body_full_size": exchange
+                        .response
+                        .body
+                        .full_size(),
+                    ":environment":
+                        JsonEncoded(&exchange.request.environment),
+                    ":note": &exchange.request.note,
+                    ":timing": exchange.timing.as_ref().map(JsonEncoded),
+                    ":certificate":
+                        exchange.certificate.as_ref().map(JsonEncoded),
                 },
             )
             .context(format!(
@@ -370,6 +822,163 @@ impl CollectionDatabase {
         Ok(())
     }
 
+    /// Record a request that failed without getting a response, so failures
+    /// can be tallied by kind
+    pub fn insert_failure(&self, error: &RequestError) -> anyhow::Result<()> {
+        let request = &error.request;
+        self.insert_failure_summary(&FailureSummary {
+            id: request.id,
+            profile_id: request.profile_id.clone(),
+            recipe_id: request.recipe_id.clone(),
+            start_time: error.start_time,
+            end_time: error.end_time,
+            method: Some(request.method.clone()),
+            url: Some(request.url.clone()),
+            kind: error.kind(),
+            message: format!("{:#}", error.error),
+        })
+    }
+
+    /// Record a request that couldn't be built. The request was never sent,
+    /// so there's no method or URL to store
+    pub fn insert_build_failure(
+        &self,
+        error: &RequestBuildError,
+    ) -> anyhow::Result<()> {
+        self.insert_failure_summary(&FailureSummary {
+            id: error.id,
+            profile_id: error.profile_id.clone(),
+            recipe_id: error.recipe_id.clone(),
+            start_time: error.start_time,
+            end_time: error.end_time,
+            method: None,
+            url: None,
+            kind: error.kind(),
+            message: format!("{:#}", error.error),
+        })
+    }
+
+    fn insert_failure_summary(
+        &self,
+        failure: &FailureSummary,
+    ) -> anyhow::Result<()> {
+        debug!(id = %failure.id, kind = %failure.kind, "Adding failure");
+        self.database
+            .connection()
+            .execute(
+                "INSERT INTO
+                request_failures (
+                    id,
+                    collection_id,
+                    profile_id,
+                    recipe_id,
+                    start_time,
+                    end_time,
+                    method,
+                    url,
+                    kind,
+                    message
+                )
+                VALUES (
+                    :id,
+                    :collection_id,
+                    :profile_id,
+                    :recipe_id,
+                    :start_time,
+                    :end_time,
+                    :method,
+                    :url,
+                    :kind,
+                    :message
+                )",
+                named_params! {
+                    ":id": failure.id,
+                    ":collection_id": self.collection_id,
+                    ":profile_id": &failure.profile_id,
+                    ":recipe_id": &failure.recipe_id,
+                    ":start_time": &failure.start_time,
+                    ":end_time": &failure.end_time,
+                    ":method": failure.method.as_ref().map(Method::as_str),
+                    ":url": failure.url.as_ref().map(Url::as_str),
+                    ":kind": failure.kind,
+                    ":message": &failure.message,
+                },
+            )
+            .with_context(|| {
+                format!(
+                    "Error saving failed request {} to database",
+                    failure.id
+                )
+            })
+            .traced()?;
+        Ok(())
+    }
+
+    /// Get failed requests for a profile+recipe combo, newest first. If a kind
+    /// is given, only failures of that kind are returned
+    pub fn get_failures(
+        &self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: &RecipeId,
+        kind: Option<FailureKind>,
+    ) -> anyhow::Result<Vec<FailureSummary>> {
+        trace!(?profile_id, %recipe_id, ?kind, "Fetching failed requests");
+        self.database
+            .connection()
+            .prepare(
+                // `IS` needed for profile_id so `None` will match `NULL`.
+                // A NULL kind param matches everything
+                "SELECT * FROM request_failures
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND recipe_id = :recipe_id
+                    AND (:kind IS NULL OR kind = :kind)
+                ORDER BY start_time DESC",
+            )?
+            .query_map(
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":recipe_id": recipe_id,
+                    ":kind": kind,
+                },
+                |row| row.try_into(),
+            )
+            .context("Error fetching failed requests")
+            .traced()?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Error extracting failed requests")
+    }
+
+    /// Count failed requests of a particular kind for a profile+recipe combo
+    pub fn count_failures(
+        &self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: &RecipeId,
+        kind: FailureKind,
+    ) -> anyhow::Result<usize> {
+        trace!(?profile_id, %recipe_id, %kind, "Counting failed requests");
+        self.database
+            .connection()
+            .query_row(
+                // `IS` needed for profile_id so `None` will match `NULL`
+                "SELECT COUNT(*) FROM request_failures
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND recipe_id = :recipe_id
+                    AND kind = :kind",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":recipe_id": recipe_id,
+                    ":kind": kind,
+                },
+                |row| row.get(0),
+            )
+            .context("Error counting failed requests")
+            .traced()
+    }
+
     /// Get a list of all requests for a profile+recipe combo
     pub fn get_all_requests(
         &self,
@@ -384,7 +993,14 @@ impl CollectionDatabase {
         self.database
             .connection()
             .prepare(
-                "SELECT id, start_time, end_time, status_code FROM requests_v2
+                "SELECT
+                    id,
+                    start_time,
+                    end_time,
+                    status_code,
+                    json_extract(environment, '$.trigger') AS trigger,
+                    note
+                FROM requests_decrypted
                 WHERE collection_id = :collection_id
                     AND profile_id IS :profile_id
                     AND recipe_id = :recipe_id
@@ -404,6 +1020,63 @@ impl CollectionDatabase {
             .context("Error extracting request history")
     }
 
+    /// Get every exchange in this collection's history, optionally only for a
+    /// single recipe, oldest first. This loads entire requests and responses,
+    /// so it's only suitable for bulk operations like export.
+    pub fn get_all_exchanges(
+        &self,
+        recipe_id: Option<&RecipeId>,
+    ) -> anyhow::Result<Vec<Exchange>> {
+        trace!(recipe_id = ?recipe_id, "Fetching all exchanges from database");
+        self.database
+            .connection()
+            .prepare(
+                "SELECT * FROM requests_decrypted
+                WHERE collection_id = :collection_id
+                    AND (:recipe_id IS NULL OR recipe_id = :recipe_id)
+                ORDER BY start_time ASC",
+            )?
+            .query_map(
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":recipe_id": recipe_id,
+                },
+                |row| row.try_into(),
+            )
+            .context("Error fetching exchanges from database")
+            .traced()?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Error extracting exchanges")
+    }
+
+    /// Add exchanges from an external source (e.g. an export from another
+    /// machine) to this collection's history. Exchanges whose ID is already
+    /// in the database are skipped, so importing the same file twice is
+    /// harmless. Return the number of exchanges that were added.
+    pub fn import_exchanges(
+        &self,
+        exchanges: &[Exchange],
+    ) -> anyhow::Result<usize> {
+        let mut imported = 0;
+        for exchange in exchanges {
+            let exists: bool = self
+                .database
+                .connection()
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM requests_v2 WHERE id = :id)",
+                    named_params! {":id": exchange.id},
+                    |row| row.get(0),
+                )
+                .context("Error checking for existing request")
+                .traced()?;
+            if !exists {
+                self.insert_exchange(exchange)?;
+                imported += 1;
+            }
+        }
+        Ok(imported)
+    }
+
     /// Get the value of a UI state field. Key type is included as part of the
     /// key, to disambiguate between keys of identical structure
     pub fn get_ui<K, V>(
@@ -471,33 +1144,637 @@ impl CollectionDatabase {
         Ok(())
     }
 
-    #[cfg(test)]
-    pub fn collection_id(&self) -> CollectionId {
-        self.collection_id
-    }
-}
-
-/// A unique ID for a collection. This is generated when the collection is
-/// inserted into the DB.
-#[derive(Copy, Clone, Debug, Display)]
-#[cfg_attr(test, derive(Eq, Hash, PartialEq))]
-pub struct CollectionId(Uuid);
-
-impl CollectionId {
-    fn new() -> Self {
-        Self(Uuid::new_v4())
-    }
+    /// Store a request received by the HTTP listener
+    pub fn insert_captured_request(
+        &self,
+        request: &CapturedRequest,
+    ) -> anyhow::Result<()> {
+        debug!(id = %request.id, url = %request.url, "Adding captured request");
+        self.database
+            .connection()
+            .execute(
+                "INSERT INTO
+                captured_requests (
+                    id,
+                    collection_id,
+                    time,
+                    method,
+                    url,
+                    headers,
+                    body
+                )
+                VALUES (
+                    :id,
+                    :collection_id,
+                    :time,
+                    :method,
+                    :url,
+                    slumber_encrypt(:headers),
+                    slumber_encrypt(:body)
+                )",
+                named_params! {
+                    ":id": request.id,
+                    ":collection_id": self.collection_id,
+                    ":time": request.time,
+                    ":method": request.method.as_str(),
+                    ":url": request.url,
+                    ":headers": SqlWrap(&request.headers),
+                    ":body": request.body.as_ref(),
+                },
+            )
+            .with_context(|| {
+                format!("Error saving captured request {}", request.id)
+            })
+            .traced()?;
+        Ok(())
+    }
+
+    /// Get all requests received by the HTTP listener, most recent first
+    pub fn get_captured_requests(
+        &self,
+    ) -> anyhow::Result<Vec<CapturedRequest>> {
+        trace!("Fetching captured requests from database");
+        self.database
+            .connection()
+            .prepare(
+                "SELECT
+                    id,
+                    time,
+                    method,
+                    url,
+                    slumber_decrypt(headers) AS headers,
+                    slumber_decrypt(body) AS body
+                FROM captured_requests
+                WHERE collection_id = :collection_id
+                ORDER BY time DESC",
+            )?
+            .query_map(
+                named_params! {":collection_id": self.collection_id},
+                |row| row.try_into(),
+            )
+            .context("Error fetching captured requests from database")
+            .traced()?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Error extracting captured requests")
+    }
+
+    /// Get the cached value of a chain for a profile, or `None` if there is
+    /// no cached value or it has expired
+    pub fn get_chain_cache(
+        &self,
+        profile_id: Option<&ProfileId>,
+        chain_id: &ChainId,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        trace!(?profile_id, %chain_id, "Fetching cached chain value");
+        self.database
+            .connection()
+            .query_row(
+                // `IS` needed for profile_id so `None` will match `NULL`
+                "SELECT slumber_decrypt(value) AS value FROM chain_cache
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND chain_id = :chain_id
+                    AND expires_at > :now",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":chain_id": chain_id,
+                    ":now": Utc::now(),
+                },
+                |row| row.get("value"),
+            )
+            .optional()
+            .with_context(|| {
+                format!("Error fetching cached value for chain `{chain_id}`")
+            })
+            .traced()
+    }
+
+    /// Cache the value of a chain for a profile, replacing any existing value
+    pub fn set_chain_cache(
+        &self,
+        profile_id: Option<&ProfileId>,
+        chain_id: &ChainId,
+        value: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        debug!(?profile_id, %chain_id, %expires_at, "Caching chain value");
+        let mut connection = self.database.connection();
+        let result = connection.transaction().and_then(|transaction| {
+            transaction.execute(
+                "DELETE FROM chain_cache
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND chain_id = :chain_id",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":chain_id": chain_id,
+                },
+            )?;
+            transaction.execute(
+                "INSERT INTO
+                chain_cache (
+                    collection_id,
+                    profile_id,
+                    chain_id,
+                    value,
+                    expires_at
+                )
+                VALUES (
+                    :collection_id,
+                    :profile_id,
+                    :chain_id,
+                    slumber_encrypt(:value),
+                    :expires_at
+                )",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":chain_id": chain_id,
+                    ":value": value,
+                    ":expires_at": expires_at,
+                },
+            )?;
+            transaction.commit()
+        });
+        result
+            .with_context(|| {
+                format!("Error caching value for chain `{chain_id}`")
+            })
+            .traced()
+    }
+
+    /// Delete all cached chain values for this collection, across all profiles
+    pub fn clear_chain_cache(&self) -> anyhow::Result<()> {
+        info!("Clearing chain cache");
+        self.database
+            .connection()
+            .execute(
+                "DELETE FROM chain_cache WHERE collection_id = :collection_id",
+                named_params! {":collection_id": self.collection_id},
+            )
+            .context("Error clearing chain cache")
+            .traced()?;
+        Ok(())
+    }
+
+    /// Get the current position of a rotating chain, without advancing it.
+    /// Chains that have never been rotated are at position 0
+    pub fn get_chain_rotation(
+        &self,
+        chain_id: &ChainId,
+    ) -> anyhow::Result<u64> {
+        trace!(%chain_id, "Fetching chain rotation position");
+        self.database
+            .connection()
+            .query_row(
+                "SELECT position FROM chain_rotations
+                WHERE collection_id = :collection_id AND chain_id = :chain_id",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":chain_id": chain_id,
+                },
+                |row| row.get("position"),
+            )
+            .optional()
+            .map(Option::unwrap_or_default)
+            .with_context(|| {
+                format!(
+                    "Error fetching rotation position for chain `{chain_id}`"
+                )
+            })
+            .traced()
+    }
+
+    /// Advance a rotating chain to its next position, and return the position
+    /// it was at *before* advancing. The caller is responsible for wrapping
+    /// the position around its list of values.
+    pub fn advance_chain_rotation(
+        &self,
+        chain_id: &ChainId,
+    ) -> anyhow::Result<u64> {
+        debug!(%chain_id, "Advancing chain rotation");
+        // A single upsert keeps concurrent renders from getting the same
+        // position
+        let position: u64 = self
+            .database
+            .connection()
+            .query_row(
+                "INSERT INTO chain_rotations (collection_id, chain_id, position)
+                VALUES (:collection_id, :chain_id, 1)
+                ON CONFLICT DO UPDATE SET position = position + 1
+                RETURNING position",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":chain_id": chain_id,
+                },
+                |row| row.get("position"),
+            )
+            .with_context(|| {
+                format!("Error advancing rotation for chain `{chain_id}`")
+            })
+            .traced()?;
+        Ok(position - 1)
+    }
+
+    /// Get the exchange pinned as the baseline for a profile+recipe, or `None`
+    /// if no baseline has been pinned. If the given profile is `None`, match
+    /// the baseline with no associated profile.
+    pub fn get_baseline(
+        &self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: &RecipeId,
+    ) -> anyhow::Result<Option<Exchange>> {
+        trace!(?profile_id, %recipe_id, "Fetching baseline from database");
+        self.database
+            .connection()
+            .query_row(
+                // `IS` needed for profile_id so `None` will match `NULL`
+                "SELECT requests_decrypted.* FROM baselines
+                JOIN requests_decrypted
+                    ON requests_decrypted.id = baselines.request_id
+                WHERE baselines.collection_id = :collection_id
+                    AND baselines.profile_id IS :profile_id
+                    AND baselines.recipe_id = :recipe_id
+                ORDER BY baselines.rowid DESC LIMIT 1",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":recipe_id": recipe_id,
+                },
+                |row| row.try_into(),
+            )
+            .optional()
+            .with_context(|| {
+                format!("Error fetching baseline for recipe `{recipe_id}`")
+            })
+            .traced()
+    }
+
+    /// Pin an exchange as the baseline for its profile+recipe, replacing any
+    /// existing baseline. The exchange must already be in history.
+    pub fn set_baseline(&self, exchange: &Exchange) -> anyhow::Result<()> {
+        let request = &exchange.request;
+        debug!(
+            id = %exchange.id,
+            profile_id = ?request.profile_id,
+            recipe_id = %request.recipe_id,
+            "Pinning baseline",
+        );
+        let mut connection = self.database.connection();
+        let result = connection.transaction().and_then(|transaction| {
+            transaction.execute(
+                "DELETE FROM baselines
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND recipe_id = :recipe_id",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": &request.profile_id,
+                    ":recipe_id": &request.recipe_id,
+                },
+            )?;
+            transaction.execute(
+                "INSERT INTO
+                baselines (collection_id, profile_id, recipe_id, request_id)
+                VALUES (
+                    :collection_id,
+                    :profile_id,
+                    :recipe_id,
+                    :request_id
+                )",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": &request.profile_id,
+                    ":recipe_id": &request.recipe_id,
+                    ":request_id": exchange.id,
+                },
+            )?;
+            transaction.commit()
+        });
+        result
+            .with_context(|| {
+                format!(
+                    "Error pinning baseline for recipe `{}`",
+                    request.recipe_id
+                )
+            })
+            .traced()
+    }
+
+    /// Move all history, baselines, and drafts for a recipe to a new recipe ID,
+    /// after the recipe is renamed in the collection. Return the number of
+    /// requests that were moved.
+    pub fn rename_recipe(
+        &self,
+        old: &RecipeId,
+        new: &RecipeId,
+    ) -> anyhow::Result<usize> {
+        info!(%old, %new, "Renaming recipe in history");
+        let mut connection = self.database.connection();
+        let result = connection.transaction().and_then(|transaction| {
+            let updated = transaction.execute(
+                "UPDATE requests_v2 SET recipe_id = :new
+                WHERE collection_id = :collection_id AND recipe_id = :old",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":old": old,
+                    ":new": new,
+                },
+            )?;
+            transaction.execute(
+                "UPDATE baselines SET recipe_id = :new
+                WHERE collection_id = :collection_id AND recipe_id = :old",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":old": old,
+                    ":new": new,
+                },
+            )?;
+            transaction.execute(
+                "UPDATE request_failures SET recipe_id = :new
+                WHERE collection_id = :collection_id AND recipe_id = :old",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":old": old,
+                    ":new": new,
+                },
+            )?;
+            transaction.execute(
+                "UPDATE OR REPLACE recipe_drafts SET recipe_id = :new
+                WHERE collection_id = :collection_id AND recipe_id = :old",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":old": old,
+                    ":new": new,
+                },
+            )?;
+            transaction.commit()?;
+            Ok(updated)
+        });
+        result
+            .with_context(|| {
+                format!("Error renaming recipe `{old}` to `{new}` in history")
+            })
+            .traced()
+    }
+
+    /// Get all saved drafts for a recipe, newest first
+    pub fn get_drafts<V>(
+        &self,
+        recipe_id: &RecipeId,
+    ) -> anyhow::Result<Vec<RecipeDraft<V>>>
+    where
+        V: Debug + DeserializeOwned,
+    {
+        trace!(%recipe_id, "Fetching drafts from database");
+        self.database
+            .connection()
+            .prepare(
+                "SELECT
+                    name,
+                    CAST(slumber_decrypt(value) AS TEXT) AS value,
+                    updated_at
+                FROM recipe_drafts
+                WHERE collection_id = :collection_id
+                    AND recipe_id = :recipe_id
+                ORDER BY updated_at DESC",
+            )?
+            .query_map(
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":recipe_id": recipe_id,
+                },
+                |row| {
+                    let value: JsonEncoded<V> = row.get("value")?;
+                    Ok(RecipeDraft {
+                        name: row.get("name")?,
+                        updated_at: row.get("updated_at")?,
+                        value: value.0,
+                    })
+                },
+            )
+            .with_context(|| {
+                format!("Error fetching drafts for recipe `{recipe_id}`")
+            })
+            .traced()?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Error extracting drafts")
+    }
+
+    /// Save a draft for a recipe, replacing any existing draft with the same
+    /// name. Only the [Self::MAX_DRAFTS] most recently updated drafts are kept
+    /// for each recipe; older ones are deleted.
+    pub fn set_draft<V>(
+        &self,
+        recipe_id: &RecipeId,
+        name: &str,
+        value: V,
+    ) -> anyhow::Result<()>
+    where
+        V: Debug + Serialize,
+    {
+        debug!(%recipe_id, name, ?value, "Saving draft");
+        let mut connection = self.database.connection();
+        let result = connection.transaction().and_then(|transaction| {
+            transaction.execute(
+                // Upsert!
+                "INSERT INTO
+                recipe_drafts (collection_id, recipe_id, name, value, updated_at)
+                VALUES (
+                    :collection_id,
+                    :recipe_id,
+                    :name,
+                    slumber_encrypt(:value),
+                    :updated_at
+                )
+                ON CONFLICT DO UPDATE SET
+                    value = excluded.value,
+                    updated_at = excluded.updated_at",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":recipe_id": recipe_id,
+                    ":name": name,
+                    ":value": JsonEncoded(value),
+                    ":updated_at": Utc::now(),
+                },
+            )?;
+            transaction.execute(
+                "DELETE FROM recipe_drafts
+                WHERE collection_id = :collection_id
+                    AND recipe_id = :recipe_id
+                    AND name NOT IN (
+                        SELECT name FROM recipe_drafts
+                        WHERE collection_id = :collection_id
+                            AND recipe_id = :recipe_id
+                        ORDER BY updated_at DESC LIMIT :limit
+                    )",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":recipe_id": recipe_id,
+                    ":limit": Self::MAX_DRAFTS,
+                },
+            )?;
+            transaction.commit()
+        });
+        result
+            .with_context(|| {
+                format!("Error saving draft for recipe `{recipe_id}`")
+            })
+            .traced()
+    }
+
+    /// Delete a recipe's draft by name. Do nothing if it doesn't exist
+    pub fn delete_draft(
+        &self,
+        recipe_id: &RecipeId,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        debug!(%recipe_id, name, "Deleting draft");
+        self.database
+            .connection()
+            .execute(
+                "DELETE FROM recipe_drafts
+                WHERE collection_id = :collection_id
+                    AND recipe_id = :recipe_id
+                    AND name = :name",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":recipe_id": recipe_id,
+                    ":name": name,
+                },
+            )
+            .with_context(|| {
+                format!("Error deleting draft for recipe `{recipe_id}`")
+            })
+            .traced()?;
+        Ok(())
+    }
+
+    /// Search this collection's request history for text in request URLs,
+    /// notes, headers, and bodies, as well as response headers and bodies.
+    /// Each word in the query must appear somewhere in the request, in any
+    /// order. Words match as prefixes, so `user` matches `users`. Results are
+    /// ordered newest first.
+    pub fn search_requests(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<SearchMatch>> {
+        let query = fts_query(query);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        trace!(%query, limit, "Searching request history");
+        self.database
+            .connection()
+            .prepare(
+                "SELECT
+                    requests_v2.id,
+                    profile_id,
+                    recipe_id,
+                    start_time,
+                    method,
+                    requests_v2.url,
+                    status_code,
+                    snippet(requests_fts, -1, '', '', '…', 12) AS snippet
+                FROM requests_fts
+                JOIN requests_v2 ON requests_v2.id = requests_fts.request_id
+                WHERE requests_fts MATCH :query
+                    AND collection_id = :collection_id
+                ORDER BY start_time DESC
+                LIMIT :limit",
+            )?
+            .query_map(
+                named_params! {
+                    ":query": query,
+                    ":collection_id": self.collection_id,
+                    ":limit": limit,
+                },
+                |row| row.try_into(),
+            )
+            .context("Error searching request history")
+            .traced()?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Error extracting search results")
+    }
+
+    pub fn collection_id(&self) -> CollectionId {
+        self.collection_id
+    }
+}
+
+/// A unique ID for a collection. This is generated when the collection is
+/// inserted into the DB.
+#[derive(Copy, Clone, Debug, Display, Eq, Hash, PartialEq)]
+pub struct CollectionId(Uuid);
+
+impl CollectionId {
+    fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+/// A set of unsent changes to a recipe, saved so they aren't lost if the app
+/// exits unexpectedly. The value is defined by whoever saved it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecipeDraft<V> {
+    /// Unique within the recipe
+    pub name: String,
+    pub updated_at: DateTime<Utc>,
+    pub value: V,
+}
+
+/// A request from history that matched a search query
+#[derive(Clone, Debug)]
+pub struct SearchMatch {
+    pub id: RequestId,
+    pub profile_id: Option<ProfileId>,
+    pub recipe_id: RecipeId,
+    pub start_time: DateTime<Utc>,
+    pub method: Method,
+    pub url: Url,
+    pub status: StatusCode,
+    /// Excerpt of the text surrounding the match, from whichever part of the
+    /// request or response matched best. Whitespace is collapsed so it fits on
+    /// one line.
+    pub snippet: String,
+}
+
+/// A request from history that failed without getting a response. Unlike
+/// exchanges, only metadata is stored
+#[derive(Clone, Debug, PartialEq)]
+pub struct FailureSummary {
+    pub id: RequestId,
+    pub profile_id: Option<ProfileId>,
+    pub recipe_id: RecipeId,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// `None` for build failures, which never got as far as a method
+    pub method: Option<Method>,
+    /// `None` for build failures, which never got as far as a URL
+    pub url: Option<Url>,
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+/// Convert a user's search query to an FTS5 query. Each word is quoted so
+/// that punctuation is matched literally instead of being parsed as query
+/// syntax, and made a prefix match. Words are implicitly ANDed together.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 /// Create an in-memory DB, only for testing
 #[cfg(any(test, feature = "test"))]
 impl crate::test_util::Factory for Database {
     fn factory(_: ()) -> Self {
-        let mut connection = Connection::open_in_memory().unwrap();
-        Self::migrate(&mut connection).unwrap();
-        Self {
-            connection: Arc::new(Mutex::new(connection)),
-        }
+        Self::in_memory().unwrap()
     }
 }
 
@@ -515,9 +1792,20 @@ impl crate::test_util::Factory for CollectionDatabase {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{test_util::Factory, util::get_repo_root};
+    use crate::{
+        assert_err,
+        http::CapturedRequest,
+        http::{
+            EnvironmentSnapshot, ExchangeTiming, RequestRecord, RequestTrigger,
+            ResponseBody, ResponseRecord, ServerCertificate,
+        },
+        test_util::{header_map, temp_dir, Factory, TempDir},
+        util::get_repo_root,
+    };
     use itertools::Itertools;
-    use std::collections::HashMap;
+    use reqwest::StatusCode;
+    use rstest::rstest;
+    use std::{collections::HashMap, time::Duration};
 
     #[test]
     fn test_merge() {
@@ -668,6 +1956,152 @@ mod tests {
         }
     }
 
+    /// Environment snapshot should survive a round trip through the DB
+    #[test]
+    fn test_request_environment() {
+        let database = CollectionDatabase::factory(());
+        let exchange = Exchange::factory((
+            RequestRecord {
+                environment: EnvironmentSnapshot {
+                    profile_data: [
+                        ("host".to_owned(), "localhost".to_owned()),
+                        ("token".to_owned(), "<sensitive>".to_owned()),
+                    ]
+                    .into_iter()
+                    .collect(),
+                    git_revision: Some("abc123".into()),
+                    trigger: Some(RequestTrigger {
+                        chain_id: "token".into(),
+                        recipe_id: Some("login".into()),
+                    }),
+                },
+                ..RequestRecord::factory(())
+            },
+            ResponseRecord::factory(StatusCode::OK),
+        ));
+        database.insert_exchange(&exchange).unwrap();
+
+        // Trigger is included in the summary, for history lists
+        let summaries = database
+            .get_all_requests(None, &exchange.request.recipe_id)
+            .unwrap();
+        assert_eq!(summaries[0].trigger, exchange.request.environment.trigger);
+        assert_eq!(database.get_request(exchange.id).unwrap(), Some(exchange));
+    }
+
+    /// Timing should survive a round trip through the DB, including when
+    /// there isn't any
+    #[rstest]
+    #[case::timed(Some(ExchangeTiming {
+        dns: Some(Duration::from_millis(3)),
+        connect: Some(Duration::from_millis(8)),
+        tls: Some(Duration::from_millis(15)),
+        wait: Duration::from_millis(120),
+        download: Duration::from_millis(40),
+    }))]
+    #[case::untimed(None)]
+    fn test_exchange_timing(#[case] timing: Option<ExchangeTiming>) {
+        let database = CollectionDatabase::factory(());
+        let exchange = Exchange {
+            timing,
+            ..Exchange::factory(())
+        };
+        database.insert_exchange(&exchange).unwrap();
+        assert_eq!(database.get_request(exchange.id).unwrap(), Some(exchange));
+    }
+
+    /// The server certificate is stored with the exchange
+    #[test]
+    fn test_exchange_certificate() {
+        let database = CollectionDatabase::factory(());
+        let exchange = Exchange {
+            certificate: Some(ServerCertificate {
+                subject: "CN=localhost".into(),
+                issuer: "CN=Slumber Test CA".into(),
+                subject_alt_names: vec!["localhost".into()],
+                not_before: Utc::now(),
+                not_after: Utc::now(),
+                serial_number: "01".into(),
+                fingerprint: "AB:CD".into(),
+            }),
+            ..Exchange::factory(())
+        };
+        database.insert_exchange(&exchange).unwrap();
+        assert_eq!(database.get_request(exchange.id).unwrap(), Some(exchange));
+    }
+
+    /// Notes are stored with the request, and are searchable
+    #[test]
+    fn test_request_note() {
+        let database = CollectionDatabase::factory(());
+        let exchange = Exchange::factory((
+            RequestRecord {
+                note: Some("Retry after fixing the token".into()),
+                ..RequestRecord::factory(())
+            },
+            ResponseRecord::factory(StatusCode::OK),
+        ));
+        database.insert_exchange(&exchange).unwrap();
+
+        let summaries = database
+            .get_all_requests(None, &exchange.request.recipe_id)
+            .unwrap();
+        assert_eq!(summaries[0].note, exchange.request.note);
+
+        let search_match =
+            database.search_requests("token retry", 10).unwrap().pop();
+        assert_eq!(
+            search_match
+                .map(|search_match| (search_match.id, search_match.snippet)),
+            Some((exchange.id, "Retry after fixing the token".into()))
+        );
+        assert_eq!(database.get_request(exchange.id).unwrap(), Some(exchange));
+    }
+
+    /// A streamed body keeps its preview inline, and a reference to the blob
+    #[test]
+    fn test_request_blob() {
+        let database = CollectionDatabase::factory(());
+        let exchange = Exchange::factory((
+            RequestRecord::factory(()),
+            ResponseRecord {
+                body: ResponseBody::streamed(
+                    "preview".into(),
+                    BlobRef {
+                        hash: "abc123".into(),
+                        size: 1000,
+                    },
+                ),
+                ..ResponseRecord::factory(StatusCode::OK)
+            },
+        ));
+        database.insert_exchange(&exchange).unwrap();
+
+        let loaded = database.get_request(exchange.id).unwrap().unwrap();
+        assert_eq!(loaded.response.body.bytes(), b"preview");
+        assert_eq!(loaded.response.body.size(), 1000);
+        assert_eq!(loaded, exchange);
+    }
+
+    /// A truncated body remembers how large the full body was
+    #[test]
+    fn test_request_truncated() {
+        let database = CollectionDatabase::factory(());
+        let exchange = Exchange::factory((
+            RequestRecord::factory(()),
+            ResponseRecord {
+                body: ResponseBody::new("trunc".into()).truncated(1000),
+                ..ResponseRecord::factory(StatusCode::OK)
+            },
+        ));
+        database.insert_exchange(&exchange).unwrap();
+
+        let loaded = database.get_request(exchange.id).unwrap().unwrap();
+        assert_eq!(loaded.response.body.size(), 5);
+        assert_eq!(loaded.response.body.full_size(), Some(1000));
+        assert_eq!(loaded, exchange);
+    }
+
     #[test]
     fn test_load_all_requests() {
         let database = CollectionDatabase::factory(());
@@ -728,6 +2162,45 @@ mod tests {
         }
     }
 
+    /// Export all exchanges, then import them into another collection
+    #[test]
+    fn test_export_import() {
+        let database = Database::factory(());
+        let collection1 = database
+            .clone()
+            .into_collection(Path::new("../../slumber.yml"))
+            .unwrap();
+        let collection2 = database
+            .clone()
+            .into_collection(Path::new("./Cargo.toml"))
+            .unwrap();
+
+        let exchanges = [
+            Exchange::factory(RecipeId::from("recipe1")),
+            Exchange::factory(RecipeId::from("recipe2")),
+            Exchange::factory(RecipeId::from("recipe1")),
+        ];
+        for exchange in &exchanges {
+            collection1.insert_exchange(exchange).unwrap();
+        }
+
+        let ids = |exchanges: &[Exchange]| {
+            exchanges.iter().map(|exchange| exchange.id).collect_vec()
+        };
+        let all = collection1.get_all_exchanges(None).unwrap();
+        assert_eq!(ids(&all), ids(&exchanges));
+        let recipe1 = collection1
+            .get_all_exchanges(Some(&"recipe1".into()))
+            .unwrap();
+        assert_eq!(ids(&recipe1), vec![exchanges[0].id, exchanges[2].id]);
+
+        // Duplicates are skipped, even across collections
+        assert_eq!(collection1.import_exchanges(&all).unwrap(), 0);
+        let new = Exchange::factory(());
+        assert_eq!(collection2.import_exchanges(&[new]).unwrap(), 1);
+        assert_eq!(collection2.get_all_exchanges(None).unwrap().len(), 1);
+    }
+
     /// Test UI state storage and retrieval
     #[test]
     fn test_ui_state() {
@@ -755,4 +2228,597 @@ mod tests {
             Some("value2".into())
         );
     }
+
+    /// Test clearing UI state for one collection, then all of them
+    #[test]
+    fn test_clear_ui_state() {
+        let database = Database::factory(());
+        let path1 = get_repo_root().join("slumber.yml");
+        let path2 = get_repo_root().join("README.md"); // Has to be a real file
+        let collection1 = database.clone().into_collection(&path1).unwrap();
+        let collection2 = database.clone().into_collection(&path2).unwrap();
+        let key_type = "MyKey";
+        collection1.set_ui(key_type, "key1", "value1").unwrap();
+        collection1.set_ui(key_type, "key2", "value2").unwrap();
+        collection2.set_ui(key_type, "key1", "value3").unwrap();
+
+        assert_eq!(database.clear_ui_state(Some(&path1)).unwrap(), 2);
+        assert_eq!(
+            collection1.get_ui::<_, String>(key_type, "key1").unwrap(),
+            None
+        );
+        assert_eq!(
+            collection2.get_ui::<_, String>(key_type, "key1").unwrap(),
+            Some("value3".into())
+        );
+
+        assert_err!(
+            database.clear_ui_state(Some(&get_repo_root().join("Cargo.toml"))),
+            "Unknown collection"
+        );
+
+        assert_eq!(database.clear_ui_state(None).unwrap(), 1);
+        assert_eq!(
+            collection2.get_ui::<_, String>(key_type, "key1").unwrap(),
+            None
+        );
+    }
+
+    /// Test chain cache storage, expiration, and clearing
+    #[test]
+    fn test_chain_cache() {
+        let collection = CollectionDatabase::factory(());
+        let profile_id: ProfileId = "profile1".into();
+        let chain_id: ChainId = "chain1".into();
+        let expires_at = Utc::now() + chrono::Duration::minutes(1);
+
+        collection
+            .set_chain_cache(None, &chain_id, b"none", expires_at)
+            .unwrap();
+        collection
+            .set_chain_cache(Some(&profile_id), &chain_id, b"old", expires_at)
+            .unwrap();
+        // Overwrite
+        collection
+            .set_chain_cache(Some(&profile_id), &chain_id, b"new", expires_at)
+            .unwrap();
+        assert_eq!(
+            collection.get_chain_cache(None, &chain_id).unwrap(),
+            Some(b"none".to_vec())
+        );
+        assert_eq!(
+            collection
+                .get_chain_cache(Some(&profile_id), &chain_id)
+                .unwrap(),
+            Some(b"new".to_vec())
+        );
+
+        // Expired values are ignored
+        collection
+            .set_chain_cache(
+                None,
+                &chain_id,
+                b"expired",
+                Utc::now() - chrono::Duration::seconds(1),
+            )
+            .unwrap();
+        assert_eq!(collection.get_chain_cache(None, &chain_id).unwrap(), None);
+
+        collection.clear_chain_cache().unwrap();
+        assert_eq!(
+            collection
+                .get_chain_cache(Some(&profile_id), &chain_id)
+                .unwrap(),
+            None
+        );
+    }
+
+    /// Test advancing rotation positions, which are isolated by collection
+    #[test]
+    fn test_chain_rotation() {
+        let database = Database::factory(());
+        let collection1 = database
+            .clone()
+            .into_collection(&get_repo_root().join("slumber.yml"))
+            .unwrap();
+        let collection2 = database
+            .into_collection(&get_repo_root().join("README.md"))
+            .unwrap();
+        let chain_id: ChainId = "chain1".into();
+
+        assert_eq!(collection1.get_chain_rotation(&chain_id).unwrap(), 0);
+        assert_eq!(collection1.advance_chain_rotation(&chain_id).unwrap(), 0);
+        assert_eq!(collection1.advance_chain_rotation(&chain_id).unwrap(), 1);
+        assert_eq!(collection1.get_chain_rotation(&chain_id).unwrap(), 2);
+        assert_eq!(collection2.get_chain_rotation(&chain_id).unwrap(), 0);
+    }
+
+    /// Test pinning and loading baselines
+    #[test]
+    fn test_baseline() {
+        let collection = CollectionDatabase::factory(());
+        let recipe_id: RecipeId = "recipe1".into();
+        let profile_id: ProfileId = "profile1".into();
+        let exchange_none = Exchange::factory((None, recipe_id.clone()));
+        let exchange_old =
+            Exchange::factory((Some(profile_id.clone()), recipe_id.clone()));
+        let exchange_new =
+            Exchange::factory((Some(profile_id.clone()), recipe_id.clone()));
+        for exchange in [&exchange_none, &exchange_old, &exchange_new] {
+            collection.insert_exchange(exchange).unwrap();
+        }
+
+        assert!(collection
+            .get_baseline(Some(&profile_id), &recipe_id)
+            .unwrap()
+            .is_none());
+
+        collection.set_baseline(&exchange_none).unwrap();
+        collection.set_baseline(&exchange_old).unwrap();
+        // Replace the old baseline
+        collection.set_baseline(&exchange_new).unwrap();
+        let get_id = |profile_id| {
+            collection
+                .get_baseline(profile_id, &recipe_id)
+                .unwrap()
+                .map(|exchange| exchange.id)
+        };
+        assert_eq!(get_id(None), Some(exchange_none.id));
+        assert_eq!(get_id(Some(&profile_id)), Some(exchange_new.id));
+    }
+
+    /// Failures are counted by kind, per profile+recipe
+    #[test]
+    fn test_failures() {
+        let collection = CollectionDatabase::factory(());
+        let profile_id: ProfileId = "profile1".into();
+        let recipe_id: RecipeId = "recipe1".into();
+        let failure = |profile_id: Option<&ProfileId>| RequestError {
+            error: anyhow!("Error opening SSH tunnel"),
+            request: RequestRecord::factory((
+                profile_id.cloned(),
+                recipe_id.clone(),
+            ))
+            .into(),
+            start_time: Utc::now(),
+            end_time: Utc::now(),
+        };
+        for profile_id in [Some(&profile_id), Some(&profile_id), None] {
+            collection.insert_failure(&failure(profile_id)).unwrap();
+        }
+
+        let count = |profile_id, kind| {
+            collection
+                .count_failures(profile_id, &recipe_id, kind)
+                .unwrap()
+        };
+        assert_eq!(count(Some(&profile_id), FailureKind::Other), 2);
+        assert_eq!(count(None, FailureKind::Other), 1);
+        assert_eq!(count(Some(&profile_id), FailureKind::Dns), 0);
+
+        let build_error = RequestBuildError {
+            error: anyhow!("Error rendering URL"),
+            profile_id: None,
+            recipe_id: recipe_id.clone(),
+            id: RequestId::new(),
+            start_time: Utc::now(),
+            end_time: Utc::now(),
+        };
+        collection.insert_build_failure(&build_error).unwrap();
+        let kinds = |kind| {
+            collection
+                .get_failures(None, &recipe_id, kind)
+                .unwrap()
+                .into_iter()
+                .map(|failure| (failure.kind, failure.url.is_some()))
+                .collect::<Vec<_>>()
+        };
+        // Newest first
+        assert_eq!(
+            kinds(None),
+            vec![(FailureKind::Build, false), (FailureKind::Other, true)]
+        );
+        assert_eq!(
+            kinds(Some(FailureKind::Build)),
+            vec![(FailureKind::Build, false)]
+        );
+        assert_eq!(kinds(Some(FailureKind::Dns)), vec![]);
+    }
+
+    /// Renaming a recipe moves its history, baselines, and drafts, for only the
+    /// one collection
+    #[test]
+    fn test_rename_recipe() {
+        let database = Database::factory(());
+        let path1 = get_repo_root().join("slumber.yml");
+        let path2 = get_repo_root().join("README.md"); // Has to be a real file
+        let collection1 = database.clone().into_collection(&path1).unwrap();
+        let collection2 = database.clone().into_collection(&path2).unwrap();
+        let old: RecipeId = "recipe1".into();
+        let new: RecipeId = "recipe2".into();
+
+        let exchange1 = Exchange::factory((None, old.clone()));
+        let exchange2 = Exchange::factory((None, old.clone()));
+        let exchange_other = Exchange::factory((None, old.clone()));
+        for exchange in [&exchange1, &exchange2] {
+            collection1.insert_exchange(exchange).unwrap();
+        }
+        collection1.set_baseline(&exchange1).unwrap();
+        collection1.set_draft(&old, "draft", "value").unwrap();
+        collection2.insert_exchange(&exchange_other).unwrap();
+
+        assert_eq!(collection1.rename_recipe(&old, &new).unwrap(), 2);
+
+        let latest_id = |collection: &CollectionDatabase, recipe_id| {
+            collection
+                .get_latest_request(None, recipe_id)
+                .unwrap()
+                .map(|exchange| exchange.id)
+        };
+        assert_eq!(latest_id(&collection1, &old), None);
+        assert_eq!(latest_id(&collection1, &new), Some(exchange2.id));
+        assert_eq!(
+            collection1
+                .get_baseline(None, &new)
+                .unwrap()
+                .map(|exchange| exchange.id),
+            Some(exchange1.id)
+        );
+        assert!(collection1.get_drafts::<String>(&old).unwrap().is_empty());
+        assert_eq!(collection1.get_drafts::<String>(&new).unwrap().len(), 1);
+        // Other collection is untouched
+        assert_eq!(latest_id(&collection2, &old), Some(exchange_other.id));
+    }
+
+    /// Drafts are upserted by name, listed newest first, and pruned to the
+    /// most recent few per recipe
+    #[test]
+    fn test_drafts() {
+        let database = Database::factory(());
+        let path1 = get_repo_root().join("slumber.yml");
+        let path2 = get_repo_root().join("README.md"); // Has to be a real file
+        let collection1 = database.clone().into_collection(&path1).unwrap();
+        let collection2 = database.clone().into_collection(&path2).unwrap();
+        let recipe_id: RecipeId = "recipe1".into();
+        let other_recipe_id: RecipeId = "recipe2".into();
+
+        let names = |collection: &CollectionDatabase, recipe_id| {
+            collection
+                .get_drafts::<Vec<String>>(recipe_id)
+                .unwrap()
+                .into_iter()
+                .map(|draft| (draft.name, draft.value))
+                .collect_vec()
+        };
+
+        collection1.set_draft(&recipe_id, "a", ["a1"]).unwrap();
+        collection1.set_draft(&recipe_id, "b", ["b1"]).unwrap();
+        // Update an existing draft, which moves it to the front
+        collection1.set_draft(&recipe_id, "a", ["a2"]).unwrap();
+        collection1
+            .set_draft(&other_recipe_id, "a", ["other"])
+            .unwrap();
+        collection2.set_draft(&recipe_id, "a", ["other"]).unwrap();
+        assert_eq!(
+            names(&collection1, &recipe_id),
+            vec![
+                ("a".to_owned(), vec!["a2".to_owned()]),
+                ("b".to_owned(), vec!["b1".to_owned()]),
+            ]
+        );
+
+        // Oldest drafts are pruned
+        for i in 0..CollectionDatabase::MAX_DRAFTS {
+            collection1
+                .set_draft(&recipe_id, &i.to_string(), ["new"])
+                .unwrap();
+        }
+        let drafts = names(&collection1, &recipe_id);
+        assert_eq!(drafts.len(), CollectionDatabase::MAX_DRAFTS);
+        assert_eq!(drafts[0].0, "4");
+        assert!(!drafts.iter().any(|(name, _)| name == "a" || name == "b"));
+
+        collection1.delete_draft(&recipe_id, "4").unwrap();
+        assert_eq!(names(&collection1, &recipe_id)[0].0, "3");
+
+        // Other recipes and collections are untouched
+        assert_eq!(
+            names(&collection1, &other_recipe_id),
+            vec![("a".to_owned(), vec!["other".to_owned()])]
+        );
+        assert_eq!(
+            names(&collection2, &recipe_id),
+            vec![("a".to_owned(), vec!["other".to_owned()])]
+        );
+    }
+
+    #[test]
+    fn test_search_requests() {
+        let database = Database::factory(());
+        let path1 = get_repo_root().join("slumber.yml");
+        let path2 = get_repo_root().join("README.md"); // Has to be a real file
+        let collection1 = database.clone().into_collection(&path1).unwrap();
+        let collection2 = database.clone().into_collection(&path2).unwrap();
+
+        let exchange = |url: &str, body: &str| {
+            Exchange::factory((
+                RequestRecord {
+                    url: url.parse().unwrap(),
+                    ..RequestRecord::factory((None, "recipe1".into()))
+                },
+                ResponseRecord {
+                    body: body.as_bytes().to_vec().into(),
+                    ..ResponseRecord::factory(())
+                },
+            ))
+        };
+        let exchange1 = exchange(
+            "http://localhost/users/frodo",
+            r#"{"name": "Frodo Baggins", "location": "The Shire"}"#,
+        );
+        let exchange2 = exchange(
+            "http://localhost/users/sam",
+            r#"{"name": "Samwise Gamgee", "location": "The Shire"}"#,
+        );
+        let exchange_other = exchange("http://localhost/users/gollum", "{}");
+        for exchange in [&exchange1, &exchange2] {
+            collection1.insert_exchange(exchange).unwrap();
+        }
+        collection2.insert_exchange(&exchange_other).unwrap();
+
+        let search = |query: &str, limit: usize| -> Vec<RequestId> {
+            collection1
+                .search_requests(query, limit)
+                .unwrap()
+                .into_iter()
+                .map(|search_match| search_match.id)
+                .collect()
+        };
+        // Newest first
+        assert_eq!(search("shire", 10), vec![exchange2.id, exchange1.id]);
+        assert_eq!(search("shire", 1), vec![exchange2.id]);
+        // URL match
+        assert_eq!(search("users/frodo", 10), vec![exchange1.id]);
+        // Prefix match
+        assert_eq!(search("Sam", 10), vec![exchange2.id]);
+        // All words must match
+        assert_eq!(search("shire baggins", 10), vec![exchange1.id]);
+        // Request headers are searched too
+        assert_eq!(search("user-agent", 10).len(), 2);
+        // Query syntax is escaped
+        assert_eq!(search("\"frodo", 10), vec![exchange1.id]);
+        assert_eq!(search("", 10), vec![]);
+        // Other collection isn't included
+        assert_eq!(search("gollum", 10), vec![]);
+
+        let search_match = collection1
+            .search_requests("baggins", 10)
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_eq!(search_match.recipe_id, "recipe1".into());
+        assert_eq!(search_match.url.as_str(), "http://localhost/users/frodo");
+        assert_eq!(
+            search_match.snippet,
+            r#"{"name": "Frodo Baggins", "location": "The Shire"}"#
+        );
+    }
+
+    /// Encrypt history, use it, then decrypt it
+    #[test]
+    fn test_encrypt_history() {
+        let database = Database::factory(());
+        let collection = database
+            .clone()
+            .into_collection(&get_repo_root().join("slumber.yml"))
+            .unwrap();
+        let exchange = |url: &str| {
+            Exchange::factory((
+                RequestRecord {
+                    url: url.parse().unwrap(),
+                    headers: header_map([("Authorization", "Bearer hunter2")]),
+                    ..RequestRecord::factory((None, "recipe1".into()))
+                },
+                ResponseRecord {
+                    body: b"The Shire".to_vec().into(),
+                    ..ResponseRecord::factory(())
+                },
+            ))
+        };
+        // Is any of the sensitive plaintext in the file?
+        let has_plaintext = || {
+            database
+                .connection()
+                .query_row(
+                    "SELECT COUNT(*) FROM requests_v2
+                    WHERE instr(response_body, 'Shire') > 0
+                        OR instr(request_headers, 'hunter2') > 0",
+                    [],
+                    |row| row.get::<_, usize>(0),
+                )
+                .unwrap()
+        };
+        let search =
+            |query: &str| collection.search_requests(query, 10).unwrap().len();
+
+        // One before encryption, one after
+        let exchange1 = exchange("http://localhost/users/frodo");
+        collection.insert_exchange(&exchange1).unwrap();
+        database
+            .encrypt_history(HistoryKey::Passphrase("hunter2".into()))
+            .unwrap();
+        let exchange2 = exchange("http://localhost/users/sam");
+        collection.insert_exchange(&exchange2).unwrap();
+
+        assert_eq!(database.encryption().unwrap(), Some(KeySource::Passphrase));
+        assert_eq!(has_plaintext(), 0);
+        // Headers and bodies are no longer searchable, but URLs still are
+        assert_eq!(search("shire"), 0);
+        assert_eq!(search("users"), 2);
+        for exchange in [&exchange1, &exchange2] {
+            assert_eq!(
+                collection.get_request(exchange.id).unwrap().as_ref(),
+                Some(exchange)
+            );
+        }
+        assert_err!(
+            database.encrypt_history(HistoryKey::Passphrase("other".into())),
+            "History is already encrypted"
+        );
+
+        // Simulate a restart, which has to unlock with the passphrase again
+        encryption::register_functions(&database.connection(), None).unwrap();
+        assert_err!(
+            database.unlock(|| Ok("hunter3".into())),
+            "Incorrect history passphrase"
+        );
+        database.unlock(|| Ok("hunter2".into())).unwrap();
+        assert_eq!(
+            collection.get_request(exchange1.id).unwrap(),
+            Some(exchange1)
+        );
+
+        database.decrypt_history().unwrap();
+        assert_eq!(database.encryption().unwrap(), None);
+        assert_eq!(has_plaintext(), 2);
+        assert_eq!(search("shire"), 2);
+        assert_eq!(
+            collection.get_request(exchange2.id).unwrap(),
+            Some(exchange2)
+        );
+        assert_err!(database.decrypt_history(), "History isn't encrypted");
+    }
+
+    /// Everything derived from request history is encrypted along with it,
+    /// and blobs are moved into the database so they can be encrypted too
+    #[rstest]
+    fn test_encrypt_history_other_data(temp_dir: TempDir) {
+        let database = Database::factory(());
+        let collection = database
+            .clone()
+            .into_collection(&get_repo_root().join("slumber.yml"))
+            .unwrap();
+        let blob_store = BlobStore::new(temp_dir.join("blobs"));
+        let full_body = b"preview of The Shire";
+        let blob = BlobRef {
+            hash: "abc123".into(),
+            size: full_body.len() as u64,
+        };
+        std::fs::create_dir_all(temp_dir.join("blobs")).unwrap();
+        std::fs::write(blob_store.path(&blob), full_body).unwrap();
+        let exchange = |blob: BlobRef| {
+            Exchange::factory((
+                RequestRecord {
+                    environment: EnvironmentSnapshot {
+                        profile_data: [(
+                            "token".to_owned(),
+                            "hunter2".to_owned(),
+                        )]
+                        .into_iter()
+                        .collect(),
+                        git_revision: None,
+                        trigger: Some(RequestTrigger {
+                            chain_id: "token".into(),
+                            recipe_id: None,
+                        }),
+                    },
+                    ..RequestRecord::factory((None, "recipe1".into()))
+                },
+                ResponseRecord {
+                    body: ResponseBody::streamed("preview".into(), blob),
+                    ..ResponseRecord::factory(())
+                },
+            ))
+        };
+        let streamed = exchange(blob.clone());
+        // This blob's file is gone, so only the preview can be kept
+        let missing = exchange(BlobRef {
+            hash: "missing".into(),
+            size: 1000,
+        });
+        let captured = CapturedRequest {
+            headers: header_map([("Authorization", "Bearer hunter2")]),
+            body: "The Shire".into(),
+            ..CapturedRequest::factory(())
+        };
+        let chain_id: ChainId = "token".into();
+        let expires_at = Utc::now() + chrono::Duration::minutes(1);
+        collection.insert_exchange(&streamed).unwrap();
+        collection.insert_exchange(&missing).unwrap();
+        collection.insert_captured_request(&captured).unwrap();
+        collection
+            .set_chain_cache(None, &chain_id, b"hunter2", expires_at)
+            .unwrap();
+        collection
+            .set_draft(&"recipe1".into(), "draft", "hunter2")
+            .unwrap();
+
+        // Count rows with sensitive plaintext in each table
+        let plaintext = || -> [usize; 4] {
+            database
+                .connection()
+                .query_row(
+                    "SELECT
+                        (SELECT COUNT(*) FROM requests_v2
+                            WHERE instr(environment, 'hunter2') > 0),
+                        (SELECT COUNT(*) FROM captured_requests
+                            WHERE instr(headers, 'hunter2') > 0
+                                AND instr(body, 'Shire') > 0),
+                        (SELECT COUNT(*) FROM chain_cache
+                            WHERE instr(value, 'hunter2') > 0),
+                        (SELECT COUNT(*) FROM recipe_drafts
+                            WHERE instr(value, 'hunter2') > 0)",
+                    [],
+                    |row| {
+                        Ok([row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?])
+                    },
+                )
+                .unwrap()
+        };
+        let assert_readable = || {
+            assert_eq!(
+                collection.get_captured_requests().unwrap(),
+                vec![captured.clone()]
+            );
+            assert_eq!(
+                collection.get_chain_cache(None, &chain_id).unwrap(),
+                Some(b"hunter2".to_vec())
+            );
+            let drafts: Vec<RecipeDraft<String>> =
+                collection.get_drafts(&"recipe1".into()).unwrap();
+            assert_eq!(drafts[0].value, "hunter2");
+            let summaries = collection
+                .get_all_requests(None, &"recipe1".into())
+                .unwrap();
+            assert_eq!(
+                summaries[0].trigger,
+                streamed.request.environment.trigger
+            );
+            let loaded = collection.get_request(streamed.id).unwrap().unwrap();
+            assert_eq!(loaded.request, streamed.request);
+        };
+        assert_eq!(plaintext(), [2, 1, 1, 1]);
+
+        database
+            .encrypt_history_with(
+                HistoryKey::Passphrase("passphrase".into()),
+                || blob_store.clone(),
+            )
+            .unwrap();
+        assert_eq!(plaintext(), [0, 0, 0, 0]);
+        assert_readable();
+        // The full body is in the database now, and the blob is gone
+        let loaded = collection.get_request(streamed.id).unwrap().unwrap();
+        assert_eq!(loaded.response.body.bytes(), full_body);
+        assert_eq!(loaded.response.body.blob(), None);
+        assert!(!blob_store.path(&blob).exists());
+        let loaded = collection.get_request(missing.id).unwrap().unwrap();
+        assert_eq!(loaded.response.body.bytes(), b"preview");
+        assert_eq!(loaded.response.body.full_size(), Some(1000));
+        assert_eq!(loaded.response.body.blob(), None);
+
+        database.decrypt_history().unwrap();
+        assert_eq!(plaintext(), [2, 1, 1, 1]);
+        assert_readable();
+    }
 }
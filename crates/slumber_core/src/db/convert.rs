@@ -1,10 +1,12 @@
 //! Implementations to convert between Rust types and SQL data
 
 use crate::{
-    collection::{ProfileId, RecipeId},
-    db::CollectionId,
+    collection::{ChainId, ProfileId, RecipeId},
+    db::{CollectionId, FailureSummary, SearchMatch},
     http::{
-        Exchange, ExchangeSummary, RequestId, RequestRecord, ResponseRecord,
+        blob::BlobRef, failure::FailureKind, CapturedRequest, Exchange,
+        ExchangeSummary, RequestId, RequestRecord, ResponseBody,
+        ResponseRecord,
     },
     util::ResultTraced,
 };
@@ -49,6 +51,12 @@ impl FromSql for CollectionId {
     }
 }
 
+impl ToSql for ChainId {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        self.deref().to_sql()
+    }
+}
+
 impl ToSql for ProfileId {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
         self.deref().to_sql()
@@ -85,6 +93,18 @@ impl FromSql for RecipeId {
     }
 }
 
+impl ToSql for FailureKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::Owned(self.to_string().into()))
+    }
+}
+
+impl FromSql for FailureKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value.as_str()?.parse().map_err(error_other)
+    }
+}
+
 /// Wrapper to serialize paths as strings in the DB. This is flawed because
 /// paths aren't guaranteed to be UTF-8 on either Windows or Linux, but in
 /// practice they always should be. The alternative would be to serialize them
@@ -208,19 +228,66 @@ impl<'a, 'b> TryFrom<&'a Row<'b>> for Exchange {
                 body: row
                     .get::<_, Option<SqlWrap<Bytes>>>("request_body")?
                     .map(|wrap| wrap.0),
+                // Requests from before snapshots were added don't have one
+                environment: row
+                    .get::<_, Option<JsonEncoded<_>>>("environment")?
+                    .map(|wrap| wrap.0)
+                    .unwrap_or_default(),
+                note: row.get("note")?,
             }),
             response: Arc::new(ResponseRecord {
                 status: row.get::<_, SqlWrap<StatusCode>>("status_code")?.0,
                 headers: row
                     .get::<_, SqlWrap<HeaderMap>>("response_headers")?
                     .0,
-                body: row.get::<_, SqlWrap<Bytes>>("response_body")?.0.into(),
+                body: {
+                    let data = row.get::<_, SqlWrap<Bytes>>("response_body")?.0;
+                    // Large bodies are in the blob store, and only the
+                    // beginning is stored inline
+                    let body = match row
+                        .get::<_, Option<String>>("response_body_blob")?
+                    {
+                        Some(hash) => ResponseBody::streamed(
+                            data,
+                            BlobRef {
+                                hash,
+                                size: row.get("response_body_size")?,
+                            },
+                        ),
+                        None => data.into(),
+                    };
+                    match row.get("response_body_full_size")? {
+                        Some(full_size) => body.truncated(full_size),
+                        None => body,
+                    }
+                },
             }),
+            timing: row
+                .get::<_, Option<JsonEncoded<_>>>("timing")?
+                .map(|wrap| wrap.0),
+            certificate: row
+                .get::<_, Option<JsonEncoded<_>>>("certificate")?
+                .map(|wrap| wrap.0),
         })
     }
 }
 
 /// Convert from `SELECT ... FROM requests_v2`
+impl<'a, 'b> TryFrom<&'a Row<'b>> for CapturedRequest {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &'a Row<'b>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            time: row.get("time")?,
+            method: row.get::<_, SqlWrap<_>>("method")?.0,
+            url: row.get("url")?,
+            headers: row.get::<_, SqlWrap<HeaderMap>>("headers")?.0,
+            body: row.get::<_, SqlWrap<Bytes>>("body")?.0,
+        })
+    }
+}
+
 impl<'a, 'b> TryFrom<&'a Row<'b>> for ExchangeSummary {
     type Error = rusqlite::Error;
 
@@ -230,6 +297,29 @@ impl<'a, 'b> TryFrom<&'a Row<'b>> for ExchangeSummary {
             start_time: row.get("start_time")?,
             end_time: row.get("end_time")?,
             status: row.get::<_, SqlWrap<StatusCode>>("status_code")?.0,
+            trigger: row
+                .get::<_, Option<JsonEncoded<_>>>("trigger")?
+                .map(|wrap| wrap.0),
+            note: row.get("note")?,
+        })
+    }
+}
+
+/// Convert from `SELECT ... FROM requests_fts JOIN requests_v2`
+impl<'a, 'b> TryFrom<&'a Row<'b>> for SearchMatch {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &'a Row<'b>) -> Result<Self, Self::Error> {
+        let snippet: String = row.get("snippet")?;
+        Ok(Self {
+            id: row.get("id")?,
+            profile_id: row.get("profile_id")?,
+            recipe_id: row.get("recipe_id")?,
+            start_time: row.get("start_time")?,
+            method: row.get::<_, SqlWrap<_>>("method")?.0,
+            url: row.get::<_, SqlWrap<_>>("url")?.0,
+            status: row.get::<_, SqlWrap<StatusCode>>("status_code")?.0,
+            snippet: snippet.split_whitespace().collect::<Vec<_>>().join(" "),
         })
     }
 }
@@ -238,6 +328,26 @@ impl<'a, 'b> TryFrom<&'a Row<'b>> for ExchangeSummary {
 /// the orphan rule
 pub struct SqlWrap<T>(pub T);
 
+impl<'a, 'b> TryFrom<&'a Row<'b>> for FailureSummary {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &'a Row<'b>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            profile_id: row.get("profile_id")?,
+            recipe_id: row.get("recipe_id")?,
+            start_time: row.get("start_time")?,
+            end_time: row.get("end_time")?,
+            method: row
+                .get::<_, Option<SqlWrap<_>>>("method")?
+                .map(|method| method.0),
+            url: row.get::<_, Option<SqlWrap<_>>>("url")?.map(|url| url.0),
+            kind: row.get("kind")?,
+            message: row.get("message")?,
+        })
+    }
+}
+
 impl FromSql for SqlWrap<Method> {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
         value.as_str()?.parse().map(Self).map_err(error_other)
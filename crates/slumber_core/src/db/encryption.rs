@@ -0,0 +1,331 @@
+//! Optional encryption of request history at rest. Request and response
+//! headers and bodies are the parts most likely to contain tokens and personal
+//! data, so those columns are encrypted, along with anything else derived from
+//! them: environment snapshots, captured requests, cached chain values, and
+//! recipe drafts. Everything else (URLs, status codes, timestamps) stays in the
+//! clear so history can still be listed and searched.
+//!
+//! This uses envelope encryption: values are encrypted with a random data key,
+//! which is stored in the database wrapped by a key-encryption key. The
+//! key-encryption key comes either from the OS keychain, or from a passphrase
+//! that the user enters on startup. Encryption and decryption are done by SQL
+//! functions registered on the connection, so queries don't need to care
+//! whether history is encrypted.
+
+use anyhow::{anyhow, Context};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use derive_more::Display;
+use keyring::Entry;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use rusqlite::{
+    functions::FunctionFlags,
+    types::{
+        FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Value, ValueRef,
+    },
+    Connection, ToSql,
+};
+use std::{num::NonZeroU32, sync::Arc};
+
+/// Keychain entry for the key-encryption key, when stored in the keychain
+const KEYCHAIN_SERVICE: &str = "slumber";
+const KEYCHAIN_USER: &str = "history-encryption-key";
+/// Key stretching for passphrases. Tests use a single round so they're fast
+const PBKDF2_ITERATIONS: u32 = if cfg!(test) { 1 } else { 600_000 };
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+/// Key to use when enabling history encryption
+#[derive(Clone, Debug)]
+pub enum HistoryKey {
+    /// Generate a random key and store it in the OS keychain
+    Keychain,
+    /// Derive the key from a passphrase, which must be entered on startup
+    Passphrase(String),
+}
+
+/// Where the key that protects history is kept
+#[derive(Copy, Clone, Debug, Display, PartialEq)]
+pub enum KeySource {
+    #[display("OS keychain")]
+    Keychain,
+    #[display("passphrase")]
+    Passphrase,
+}
+
+/// Encryption metadata for the database, stored in the `history_encryption`
+/// table. There is at most one row.
+#[derive(Debug)]
+pub(super) struct EncryptionRecord {
+    pub source: KeySource,
+    /// Passphrase salt. `None` for keychain keys
+    pub salt: Option<Vec<u8>>,
+    /// PBKDF2 rounds. Stored so it can be raised without breaking existing
+    /// databases. `None` for keychain keys
+    pub iterations: Option<u32>,
+    /// The data key, encrypted with the key-encryption key
+    pub wrapped_key: Vec<u8>,
+}
+
+impl EncryptionRecord {
+    /// Generate a new data key, and wrap it with the given key. For keychain
+    /// keys, this stores a new key-encryption key in the keychain.
+    pub fn create(key: HistoryKey) -> anyhow::Result<(Self, Cipher)> {
+        let data_key = random_bytes::<KEY_LEN>()?;
+        let (source, salt, iterations, key_encryption_key) = match key {
+            HistoryKey::Keychain => {
+                let key_encryption_key = random_bytes::<KEY_LEN>()?;
+                keychain_entry()?
+                    .set_password(&BASE64_STANDARD.encode(key_encryption_key))
+                    .context("Error storing history key in OS keychain")?;
+                (KeySource::Keychain, None, None, key_encryption_key)
+            }
+            HistoryKey::Passphrase(passphrase) => {
+                let salt = random_bytes::<SALT_LEN>()?;
+                let key_encryption_key =
+                    derive_key(&passphrase, &salt, PBKDF2_ITERATIONS);
+                (
+                    KeySource::Passphrase,
+                    Some(salt.to_vec()),
+                    Some(PBKDF2_ITERATIONS),
+                    key_encryption_key,
+                )
+            }
+        };
+        let record = Self {
+            source,
+            salt,
+            iterations,
+            wrapped_key: Cipher::new(&key_encryption_key)?
+                .encrypt(&data_key)?,
+        };
+        Ok((record, Cipher::new(&data_key)?))
+    }
+
+    /// Unwrap the data key. The passphrase is only requested if the key is
+    /// protected by one.
+    pub fn unlock(
+        &self,
+        passphrase: impl FnOnce() -> anyhow::Result<String>,
+    ) -> anyhow::Result<Cipher> {
+        let key_encryption_key = match self.source {
+            KeySource::Keychain => {
+                let encoded = keychain_entry()?.get_password().context(
+                    "History is encrypted, but its key could not be loaded \
+                    from the OS keychain",
+                )?;
+                let bytes = BASE64_STANDARD
+                    .decode(encoded)
+                    .context("Invalid history key in OS keychain")?;
+                bytes.try_into().map_err(|_| {
+                    anyhow!("Invalid history key in OS keychain")
+                })?
+            }
+            KeySource::Passphrase => {
+                let (Some(salt), Some(iterations)) =
+                    (&self.salt, self.iterations)
+                else {
+                    return Err(anyhow!(
+                        "Missing passphrase parameters for history encryption"
+                    ));
+                };
+                derive_key(&passphrase()?, salt, iterations)
+            }
+        };
+        let data_key = Cipher::new(&key_encryption_key)?
+            .decrypt(&self.wrapped_key)
+            .map_err(|_| match self.source {
+                KeySource::Keychain => {
+                    anyhow!("History key in OS keychain is incorrect")
+                }
+                KeySource::Passphrase => {
+                    anyhow!("Incorrect history passphrase")
+                }
+            })?;
+        Cipher::new(&data_key)
+    }
+
+    /// Remove the key-encryption key from the keychain, if it's stored there
+    pub fn delete_key(&self) -> anyhow::Result<()> {
+        if self.source == KeySource::Keychain {
+            keychain_entry()?
+                .delete_credential()
+                .context("Error deleting history key from OS keychain")?;
+        }
+        Ok(())
+    }
+}
+
+/// AES-256-GCM cipher. Each encrypted value is a random nonce followed by the
+/// sealed data, so the same plaintext never encrypts the same way twice.
+#[derive(Debug)]
+pub(super) struct Cipher {
+    key: LessSafeKey,
+}
+
+impl Cipher {
+    fn new(key: &[u8]) -> anyhow::Result<Self> {
+        let key = UnboundKey::new(&AES_256_GCM, key)
+            .map_err(|_| anyhow!("Invalid encryption key"))?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+        })
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let nonce = random_bytes::<NONCE_LEN>()?;
+        let mut sealed = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("Error encrypting value"))?;
+        Ok([nonce.as_slice(), &sealed].concat())
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let error = || anyhow!("Error decrypting value");
+        if ciphertext.len() < NONCE_LEN {
+            return Err(error());
+        }
+        let (nonce, sealed) = ciphertext.split_at(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| error())?;
+        let mut buffer = sealed.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut buffer)
+            .map_err(|_| error())?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+/// Register the `slumber_encrypt` and `slumber_decrypt` SQL functions on a
+/// connection. With no cipher, both return their input unchanged. Call again
+/// to replace the cipher.
+pub(super) fn register_functions(
+    connection: &Connection,
+    cipher: Option<Arc<Cipher>>,
+) -> rusqlite::Result<()> {
+    for (name, encrypt) in
+        [("slumber_encrypt", true), ("slumber_decrypt", false)]
+    {
+        let cipher = cipher.clone();
+        connection.create_scalar_function(
+            name,
+            1,
+            FunctionFlags::SQLITE_UTF8,
+            move |context| {
+                let value: Value = context.get(0)?;
+                let Some(cipher) = &cipher else {
+                    // Encryption is disabled
+                    return Ok(value);
+                };
+                let bytes = match &value {
+                    Value::Blob(bytes) => bytes.as_slice(),
+                    // Text (e.g. JSON) is encrypted to a blob. Readers cast
+                    // the decrypted blob back to text
+                    Value::Text(text) if encrypt => text.as_bytes(),
+                    // Nothing to do for nulls
+                    _ => return Ok(value),
+                };
+                let result = if encrypt {
+                    cipher.encrypt(bytes)
+                } else {
+                    cipher.decrypt(bytes)
+                };
+                result.map(Value::Blob).map_err(|error| {
+                    rusqlite::Error::UserFunctionError(error.into())
+                })
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Derive a key-encryption key from a passphrase
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; KEY_LEN] {
+    let mut key = [0; KEY_LEN];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(iterations).unwrap_or(NonZeroU32::MIN),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    key
+}
+
+fn random_bytes<const N: usize>() -> anyhow::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow!("Error generating random bytes"))?;
+    Ok(bytes)
+}
+
+fn keychain_entry() -> anyhow::Result<Entry> {
+    Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_USER)
+        .context("Error accessing OS keychain")
+}
+
+impl ToSql for KeySource {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let s = match self {
+            Self::Keychain => "keychain",
+            Self::Passphrase => "passphrase",
+        };
+        Ok(s.into())
+    }
+}
+
+impl FromSql for KeySource {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "keychain" => Ok(Self::Keychain),
+            "passphrase" => Ok(Self::Passphrase),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_err;
+
+    #[test]
+    fn test_cipher() {
+        let cipher = Cipher::new(&[1; KEY_LEN]).unwrap();
+        let encrypted = cipher.encrypt(b"secret").unwrap();
+        assert_ne!(&encrypted[NONCE_LEN..], b"secret");
+        // Nonce is random, so the same plaintext encrypts differently
+        assert_ne!(encrypted, cipher.encrypt(b"secret").unwrap());
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"secret");
+
+        let other = Cipher::new(&[2; KEY_LEN]).unwrap();
+        assert_err!(other.decrypt(&encrypted), "Error decrypting value");
+        assert_err!(cipher.decrypt(b"short"), "Error decrypting value");
+    }
+
+    #[test]
+    fn test_passphrase() {
+        let (record, cipher) =
+            EncryptionRecord::create(HistoryKey::Passphrase("hunter2".into()))
+                .unwrap();
+        assert_eq!(record.source, KeySource::Passphrase);
+        let encrypted = cipher.encrypt(b"secret").unwrap();
+
+        let unlocked = record.unlock(|| Ok("hunter2".into())).unwrap();
+        assert_eq!(unlocked.decrypt(&encrypted).unwrap(), b"secret");
+        assert_err!(
+            record.unlock(|| Ok("hunter3".into())),
+            "Incorrect history passphrase"
+        );
+    }
+}
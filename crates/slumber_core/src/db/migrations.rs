@@ -109,6 +109,276 @@ pub fn migrations() -> Migrations<'static> {
         // also doesn't support dropping columns with UNIQUE so the old
         // one would still be there
         M::up_with_hook("", migrate_collection_paths),
+        // Cached chain values. There's no primary key because profile_id is
+        // nullable, and NULLs are never equal in a uniqueness constraint.
+        // Instead, writers delete the old value before inserting.
+        M::up(
+            "CREATE TABLE chain_cache (
+                collection_id   UUID NOT NULL,
+                profile_id      TEXT,
+                chain_id        TEXT NOT NULL,
+                value           BLOB NOT NULL,
+                expires_at      TEXT NOT NULL,
+                FOREIGN KEY(collection_id) REFERENCES collections(id)
+            )",
+        ),
+        // Requests received by `slumber listen`
+        M::up(
+            "CREATE TABLE captured_requests (
+                id              UUID PRIMARY KEY NOT NULL,
+                collection_id   UUID NOT NULL,
+                time            TEXT NOT NULL,
+                method          TEXT NOT NULL,
+                url             TEXT NOT NULL,
+                headers         BLOB NOT NULL,
+                body            BLOB NOT NULL,
+                FOREIGN KEY(collection_id) REFERENCES collections(id)
+            )",
+        ),
+        // Responses pinned as the expected output of a recipe. Same as the
+        // chain cache, there's no uniqueness constraint because profile_id is
+        // nullable.
+        M::up(
+            "CREATE TABLE baselines (
+                collection_id   UUID NOT NULL,
+                profile_id      TEXT,
+                recipe_id       TEXT NOT NULL,
+                request_id      UUID NOT NULL,
+                FOREIGN KEY(collection_id) REFERENCES collections(id),
+                FOREIGN KEY(request_id) REFERENCES requests_v2(id)
+            )",
+        ),
+        // JSON-encoded snapshot of the profile/collection used to build each
+        // request. Nullable because older requests don't have one
+        M::up("ALTER TABLE requests_v2 ADD COLUMN environment TEXT"),
+        // Full-text index over request history. Rows are linked by request
+        // ID rather than rowid, because rowids can change on VACUUM. Triggers
+        // keep the index in sync with requests_v2
+        M::up(
+            "CREATE VIRTUAL TABLE requests_fts USING fts5(
+                request_id UNINDEXED,
+                url,
+                request_headers,
+                request_body,
+                response_headers,
+                response_body
+            );
+            INSERT INTO requests_fts (
+                request_id,
+                url,
+                request_headers,
+                request_body,
+                response_headers,
+                response_body
+            )
+            SELECT
+                id,
+                url,
+                CAST(request_headers AS TEXT),
+                CAST(request_body AS TEXT),
+                CAST(response_headers AS TEXT),
+                CAST(response_body AS TEXT)
+            FROM requests_v2;
+            CREATE TRIGGER requests_fts_insert AFTER INSERT ON requests_v2
+            BEGIN
+                INSERT INTO requests_fts (
+                    request_id,
+                    url,
+                    request_headers,
+                    request_body,
+                    response_headers,
+                    response_body
+                )
+                VALUES (
+                    new.id,
+                    new.url,
+                    CAST(new.request_headers AS TEXT),
+                    CAST(new.request_body AS TEXT),
+                    CAST(new.response_headers AS TEXT),
+                    CAST(new.response_body AS TEXT)
+                );
+            END;
+            CREATE TRIGGER requests_fts_delete AFTER DELETE ON requests_v2
+            BEGIN
+                DELETE FROM requests_fts WHERE request_id = old.id;
+            END;",
+        ),
+        // Optional encryption of request history. There's at most one row,
+        // holding the wrapped data key. When history is encrypted, headers and
+        // bodies are left out of the search index, so it doesn't leak their
+        // plaintext
+        M::up(
+            "CREATE TABLE history_encryption (
+                id              INTEGER PRIMARY KEY NOT NULL CHECK (id = 0),
+                key_source      TEXT NOT NULL,
+                salt            BLOB,
+                iterations      INTEGER,
+                wrapped_key     BLOB NOT NULL
+            );
+            DROP TRIGGER requests_fts_insert;
+            CREATE TRIGGER requests_fts_insert AFTER INSERT ON requests_v2
+            WHEN NOT EXISTS (SELECT 1 FROM history_encryption)
+            BEGIN
+                INSERT INTO requests_fts (
+                    request_id,
+                    url,
+                    request_headers,
+                    request_body,
+                    response_headers,
+                    response_body
+                )
+                VALUES (
+                    new.id,
+                    new.url,
+                    CAST(new.request_headers AS TEXT),
+                    CAST(new.request_body AS TEXT),
+                    CAST(new.response_headers AS TEXT),
+                    CAST(new.response_body AS TEXT)
+                );
+            END;
+            CREATE TRIGGER requests_fts_insert_encrypted
+            AFTER INSERT ON requests_v2
+            WHEN EXISTS (SELECT 1 FROM history_encryption)
+            BEGIN
+                INSERT INTO requests_fts (request_id, url)
+                VALUES (new.id, new.url);
+            END;",
+        ),
+        // Optional user note attached to each request when it's sent. FTS
+        // tables can't be altered, so the search index is rebuilt to include
+        // it. Like the URL, notes are never encrypted, so they're indexed
+        // either way
+        M::up(
+            "ALTER TABLE requests_v2 ADD COLUMN note TEXT;
+            DROP TRIGGER requests_fts_insert;
+            DROP TRIGGER requests_fts_insert_encrypted;
+            DROP TRIGGER requests_fts_delete;
+            DROP TABLE requests_fts;
+            CREATE VIRTUAL TABLE requests_fts USING fts5(
+                request_id UNINDEXED,
+                url,
+                note,
+                request_headers,
+                request_body,
+                response_headers,
+                response_body
+            );
+            INSERT INTO requests_fts (
+                request_id,
+                url,
+                request_headers,
+                request_body,
+                response_headers,
+                response_body
+            )
+            SELECT
+                id,
+                url,
+                CAST(request_headers AS TEXT),
+                CAST(request_body AS TEXT),
+                CAST(response_headers AS TEXT),
+                CAST(response_body AS TEXT)
+            FROM requests_v2
+            WHERE NOT EXISTS (SELECT 1 FROM history_encryption);
+            INSERT INTO requests_fts (request_id, url)
+            SELECT id, url FROM requests_v2
+            WHERE EXISTS (SELECT 1 FROM history_encryption);
+            CREATE TRIGGER requests_fts_insert AFTER INSERT ON requests_v2
+            WHEN NOT EXISTS (SELECT 1 FROM history_encryption)
+            BEGIN
+                INSERT INTO requests_fts (
+                    request_id,
+                    url,
+                    note,
+                    request_headers,
+                    request_body,
+                    response_headers,
+                    response_body
+                )
+                VALUES (
+                    new.id,
+                    new.url,
+                    new.note,
+                    CAST(new.request_headers AS TEXT),
+                    CAST(new.request_body AS TEXT),
+                    CAST(new.response_headers AS TEXT),
+                    CAST(new.response_body AS TEXT)
+                );
+            END;
+            CREATE TRIGGER requests_fts_insert_encrypted
+            AFTER INSERT ON requests_v2
+            WHEN EXISTS (SELECT 1 FROM history_encryption)
+            BEGIN
+                INSERT INTO requests_fts (request_id, url, note)
+                VALUES (new.id, new.url, new.note);
+            END;
+            CREATE TRIGGER requests_fts_delete AFTER DELETE ON requests_v2
+            BEGIN
+                DELETE FROM requests_fts WHERE request_id = old.id;
+            END;",
+        ),
+        // Unsent recipe overrides from the TUI, so they survive a crash. Each
+        // session writes to its own named draft. The value is JSON-encoded
+        // and opaque to the DB
+        M::up(
+            "CREATE TABLE recipe_drafts (
+                collection_id   UUID NOT NULL,
+                recipe_id       TEXT NOT NULL,
+                name            TEXT NOT NULL,
+                value           TEXT NOT NULL,
+                updated_at      TEXT NOT NULL,
+                PRIMARY KEY (collection_id, recipe_id, name),
+                FOREIGN KEY(collection_id) REFERENCES collections(id)
+            )",
+        ),
+        // Requests that failed without a response, e.g. a DNS or connection
+        // error. These aren't full history entries because there's no
+        // response to show, but they're kept so failures can be tallied.
+        // Build failures may not have gotten as far as rendering a method or
+        // URL, so those are nullable
+        M::up(
+            "CREATE TABLE request_failures (
+                id              UUID PRIMARY KEY NOT NULL,
+                collection_id   UUID NOT NULL,
+                profile_id      TEXT,
+                recipe_id       TEXT NOT NULL,
+                start_time      TEXT NOT NULL,
+                end_time        TEXT NOT NULL,
+                method          TEXT,
+                url             TEXT,
+                kind            TEXT NOT NULL,
+                message         TEXT NOT NULL,
+                FOREIGN KEY(collection_id) REFERENCES collections(id)
+            )",
+        ),
+        // Large response bodies are streamed to the blob store, and only a
+        // preview is stored inline. The blob is referenced by its hash
+        M::up(
+            "ALTER TABLE requests_v2 ADD COLUMN response_body_blob TEXT;
+            ALTER TABLE requests_v2 ADD COLUMN response_body_size INTEGER;",
+        ),
+        // Bodies over the capture limit are truncated. Track the full size so
+        // we can tell the user what's missing
+        M::up(
+            "ALTER TABLE requests_v2 ADD COLUMN response_body_full_size INTEGER;",
+        ),
+        // Position of each `!rotate` chain in its list of values. This is
+        // just a counter; the chain wraps it around its list when rendering
+        M::up(
+            "CREATE TABLE chain_rotations (
+                collection_id   UUID NOT NULL,
+                chain_id        TEXT NOT NULL,
+                position        INTEGER NOT NULL,
+                PRIMARY KEY (collection_id, chain_id),
+                FOREIGN KEY(collection_id) REFERENCES collections(id)
+            )",
+        ),
+        // Per-phase timing breakdown of each exchange, as JSON. Null for
+        // exchanges recorded before this was added
+        M::up("ALTER TABLE requests_v2 ADD COLUMN timing TEXT"),
+        // Server TLS certificate of each exchange, as JSON. Null for plain
+        // HTTP, and exchanges recorded before this was added
+        M::up("ALTER TABLE requests_v2 ADD COLUMN certificate TEXT"),
     ])
 }
 
@@ -129,6 +399,8 @@ fn migrate_requests_v2_up(transaction: &Transaction) -> HookResult {
             // Deserialize from bytes
             request: Arc::new(row.get::<_, ByteEncoded<_>>("request")?.0),
             response: Arc::new(row.get::<_, ByteEncoded<_>>("response")?.0),
+            timing: None,
+            certificate: None,
         };
         Ok((collection_id, exchange))
     }
@@ -449,7 +721,21 @@ mod tests {
             .unwrap();
         assert_eq!(count, exchanges.len());
 
-        let mut stmt = connection.prepare("SELECT * FROM requests_v2").unwrap();
+        // These columns are added by later migrations
+        let mut stmt = connection
+            .prepare(
+                "SELECT
+                    *,
+                    NULL AS environment,
+                    NULL AS note,
+                    NULL AS response_body_blob,
+                    NULL AS response_body_size,
+                    NULL AS response_body_full_size,
+                    NULL AS timing,
+                    NULL AS certificate
+                FROM requests_v2",
+            )
+            .unwrap();
         let migrated: Vec<Exchange> = stmt
             .query_map::<Exchange, _, _>([], |row| row.try_into())
             .unwrap()
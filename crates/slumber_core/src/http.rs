@@ -35,39 +35,91 @@
 //! +----------+
 //! ```
 
-mod cereal;
+pub mod assertion;
+pub mod baseline;
+pub mod blob;
+pub(crate) mod cereal;
 pub mod content_type;
+pub mod contract;
+pub mod decode;
+pub mod diff;
+pub mod export;
+pub mod failure;
+pub mod graphql;
+mod listener;
+pub mod load;
+mod mock;
 mod models;
+mod proxy;
 pub mod query;
-
+mod server;
+pub mod status;
+mod timing;
+mod tls;
+mod tunnel;
+
+pub use listener::{CapturedRequest, Listener};
+pub use mock::{MockServer, MockedRequest};
 pub use models::*;
+pub use proxy::{Proxy, PROXY_RECIPE_ID};
+pub use timing::ExchangeTiming;
+pub use tls::ServerCertificate;
 
 use crate::{
-    collection::{Authentication, Method, Recipe, RecipeBody},
+    collection::{
+        Authentication, BodySignature, ClientCertificate, Method, Recipe,
+        RecipeBody, RecipeDefaults, SshTunnel,
+    },
     db::CollectionDatabase,
-    http::content_type::ContentType,
+    http::{
+        blob::{BlobStore, BlobWriter},
+        content_type::ContentType,
+        timing::{measure, TimingLayer, TimingResolver},
+        tunnel::{PendingTunnel, TunnelConfig, TunnelManager},
+    },
     template::{Template, TemplateContext},
-    util::ResultTraced,
+    util::{aws::AwsCredentials, expand_home, hex, hmac_sha256, ResultTraced},
 };
-use anyhow::Context;
+use anyhow::{anyhow, bail, Context};
+use base64::{prelude::BASE64_STANDARD, Engine};
 use bytes::Bytes;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::{
     future::{self, try_join_all, OptionFuture},
     try_join, Future,
 };
+use indexmap::IndexMap;
+use itertools::Itertools;
 use mime::Mime;
 use reqwest::{
     header::{self, HeaderMap, HeaderName, HeaderValue},
     multipart::{Form, Part},
-    Client, RequestBuilder, Response, Url,
+    Client, Request, RequestBuilder, Response, Url,
 };
+use rustls::pki_types::{pem::PemObject, CertificateDer};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs,
+    io::{AsyncWrite, AsyncWriteExt},
+};
 use tracing::{info, info_span};
 
 const USER_AGENT: &str = concat!("slumber/", env!("CARGO_PKG_VERSION"));
 
+/// Minimum time between download progress reports. Bodies that load faster
+/// than this never report progress at all.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// When a response body is streamed to the blob store, this much of the
+/// beginning is kept in memory as a preview
+const STREAM_PREVIEW_SIZE: usize = 100 * 1000; // 100KB
+
 /// Utility for handling all HTTP operations. The main purpose of this is to
 /// de-asyncify HTTP so it can be called in the main TUI thread. All heavy
 /// lifting will be pushed to background tasks.
@@ -76,42 +128,80 @@ const USER_AGENT: &str = concat!("slumber/", env!("CARGO_PKG_VERSION"));
 /// internally. [reqwest::Client]
 #[derive(Clone, Debug)]
 pub struct HttpEngine {
+    /// Client for requests that use standard TLS verification
     client: Client,
-    /// A client that ignores TLS errors, and the hostnames we should use it
-    /// for. If the user didn't specify any (99.9% of cases), don't bother
-    /// creating a client because it's expensive.
-    danger_client: Option<(Client, HashSet<String>)>,
+    /// A client that ignores TLS errors. This is used for hosts in
+    /// `ignore_certificate_hosts` and for recipes that opt out of TLS
+    /// verification. In 99.9% of cases it's never needed, and building a
+    /// client is expensive, so we don't build it until we need it. The cell
+    /// is shared between clones of the engine.
+    danger_client: Arc<OnceLock<Client>>,
+    /// Hostnames whose TLS errors should be ignored
+    danger_hosts: HashSet<String>,
+    /// Additional root CA certificates to trust, from the config. These are
+    /// retained so they can be applied to lazily built clients too.
+    ca_certificates: Vec<CertificateDer<'static>>,
+    /// Clients with a client certificate attached, for mTLS. A client can only
+    /// hold a single identity, so we need one client per certificate. These
+    /// are built lazily and cached because building a client is expensive.
+    /// The key also includes whether TLS errors are ignored for the client.
+    identity_clients: Arc<Mutex<HashMap<(ClientIdentity, bool), Client>>>,
+    /// SSH tunnels, which are shared across all requests
+    tunnels: TunnelManager,
     large_body_size: usize,
+    stream_body_size: usize,
+    max_body_size: Option<usize>,
+    /// Answer every request with its recipe's example response instead of
+    /// touching the network
+    offline: bool,
 }
 
 impl HttpEngine {
-    /// Build a new HTTP engine, which can be used for the entire program life
-    pub fn new(config: &HttpEngineConfig) -> Self {
-        let client = Client::builder()
-            .user_agent(USER_AGENT)
-            .build()
-            .expect("Error building reqwest client");
-        let danger_client = if config.ignore_certificate_hosts.is_empty() {
-            None
-        } else {
-            Some((
-                Client::builder()
-                    .user_agent(USER_AGENT)
-                    .danger_accept_invalid_certs(true)
-                    .build()
-                    .expect("Error building reqwest client"),
-                config.ignore_certificate_hosts.iter().cloned().collect(),
-            ))
+    /// Build a new HTTP engine, which can be used for the entire program life.
+    /// Fails only if the configured CA certificate can't be loaded.
+    pub fn new(config: &HttpEngineConfig) -> anyhow::Result<Self> {
+        let ca_certificates = match &config.ca_certificate {
+            Some(path) => {
+                let path = expand_home(path);
+                let pem = std::fs::read(&path).with_context(|| {
+                    format!("Error reading CA certificate {path:?}")
+                })?;
+                let certificates = CertificateDer::pem_slice_iter(&pem)
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| {
+                        format!("Error loading CA certificate {path:?}")
+                    })?;
+                if certificates.is_empty() {
+                    bail!("No certificates found in CA certificate {path:?}");
+                }
+                certificates
+            }
+            None => Vec::new(),
         };
-        Self {
+        let client = Self::build_client(&ca_certificates, false, None)?;
+        Ok(Self {
             client,
-            danger_client,
+            danger_client: Default::default(),
+            danger_hosts: config
+                .ignore_certificate_hosts
+                .iter()
+                .cloned()
+                .collect(),
+            ca_certificates,
+            identity_clients: Default::default(),
+            tunnels: Default::default(),
             large_body_size: config.large_body_size,
-        }
+            stream_body_size: config.stream_body_size,
+            max_body_size: config.max_body_size,
+            offline: config.offline,
+        })
     }
 
     /// Build a [RequestTicket] from a [RequestSeed]. This will render the
     /// recipe into a request. The returned ticket can then be launched.
+    ///
+    /// In offline mode, the recipe's example response is loaded here, so a
+    /// missing or invalid example fails the build rather than the send.
     pub async fn build(
         &self,
         seed: RequestSeed,
@@ -121,31 +211,49 @@ impl HttpEngine {
             id,
             recipe_id,
             options,
+            ..
         } = &seed;
         let _ =
             info_span!("Build request", request_id = %id, ?recipe_id, ?options)
                 .entered();
+        template_context.state.set_recipe(recipe_id);
 
         let future = async {
-            let recipe = template_context
-                .collection
-                .recipes
-                .try_get_recipe(recipe_id)?;
+            let recipe = seed.recipe(template_context)?;
+            recipe.apply_parameters(&seed, template_context).await?;
+            let defaults =
+                template_context.collection.recipe_defaults(recipe_id);
 
             // Render everything up front so we can parallelize it
-            let (url, query, headers, authentication, body) = try_join!(
-                recipe.render_url(template_context),
+            let (
+                url,
+                query,
+                headers,
+                authentication,
+                signature,
+                body,
+                identity,
+                tunnel,
+            ) = try_join!(
+                recipe.render_url(&defaults, template_context),
                 recipe.render_query(options, template_context),
-                recipe.render_headers(options, template_context),
-                recipe.render_authentication(options, template_context),
+                recipe.render_headers(options, &defaults, template_context),
+                recipe.render_authentication(
+                    options,
+                    &defaults,
+                    template_context
+                ),
+                recipe.render_signature(template_context),
                 recipe.render_body(options, template_context),
+                recipe.render_certificate(template_context),
+                render_tunnel(template_context),
             )?;
 
             // Build the reqwest request first, so we can have it do all the
             // hard work of encoding query params/authorization/etc.
             // We'll just copy its homework at the end to get our
             // RequestRecord
-            let client = self.get_client(&url);
+            let client = self.select_client(Some(recipe), &url, identity)?;
             let mut builder =
                 client.request(recipe.method.into(), url).query(&query);
             if let Some(body) = body {
@@ -154,29 +262,86 @@ impl HttpEngine {
             // Set headers *after* body so the use can override the Content-Type
             // header that was set if they want to
             builder = builder.headers(headers);
-            if let Some(authentication) = authentication {
+            if let Some(authentication) = &authentication {
                 builder = authentication.apply(builder);
             }
 
-            let request = builder.build()?;
-            Ok((client, request))
+            let mut request = builder.build()?;
+            // The body signature needs the final body. This has to happen
+            // before auth signing, so the signature header can be included in
+            // the auth signature
+            if let Some(signature) = &signature {
+                signature.sign(&mut request, Utc::now())?;
+            }
+            // Some auth schemes sign the entire request, so they have to be
+            // applied after everything else is finalized
+            if let Some(authentication) = &authentication {
+                authentication.sign(&mut request)?;
+            }
+
+            let fixture = if self.offline {
+                Some(Self::load_fixture(recipe)?)
+            } else {
+                None
+            };
+            Ok((client, request, tunnel, fixture))
         };
-        let (client, request) =
+        let (client, request, tunnel, fixture) =
             seed.convert_error(future, template_context).await?;
+        let environment = EnvironmentSnapshot {
+            profile_data: template_context.state.field_values(),
+            git_revision: template_context
+                .state
+                .git_revision(&template_context.database)
+                .await,
+            trigger: seed.trigger.clone(),
+        };
+        let cancel = seed.cancel.clone();
 
         Ok(RequestTicket {
             record: RequestRecord::new(
                 seed,
                 template_context.selected_profile.clone(),
+                environment,
                 &request,
                 self.large_body_size,
             )
             .into(),
-            client: client.clone(),
+            client,
             request,
+            tunnel: tunnel.map(|config| PendingTunnel {
+                manager: self.tunnels.clone(),
+                config,
+            }),
+            stream_body_size: self.stream_body_size,
+            max_body_size: self.max_body_size,
+            fixture,
+            cancel,
+        })
+    }
+
+    /// Load a recipe's example response, to stand in for the real response in
+    /// offline mode
+    fn load_fixture(recipe: &Recipe) -> anyhow::Result<ResponseRecord> {
+        let example = recipe.example.as_ref().ok_or_else(|| {
+            anyhow!(
+                "Recipe `{}` has no `example` response, which is required in \
+                offline mode",
+                recipe.id
+            )
+        })?;
+        ResponseRecord::try_from(example).with_context(|| {
+            format!("Invalid example response for recipe `{}`", recipe.id)
         })
     }
 
+    /// Close all SSH tunnels opened by this engine. Tunnels are also closed
+    /// when the engine is dropped, so this is only needed if the engine is
+    /// long-lived (e.g. stored in a static).
+    pub async fn close_tunnels(&self) {
+        self.tunnels.close_all().await;
+    }
+
     /// Render *just* the URL of a request, including query parameters
     pub async fn build_url(
         &self,
@@ -187,26 +352,28 @@ impl HttpEngine {
             id,
             recipe_id,
             options,
+            ..
         } = &seed;
         let _ =
             info_span!("Build request URL", request_id = %id, ?recipe_id, ?options)
                 .entered();
+        template_context.state.set_recipe(recipe_id);
 
         let future = async {
-            let recipe = template_context
-                .collection
-                .recipes
-                .try_get_recipe(recipe_id)?;
+            let recipe = seed.recipe(template_context)?;
+            recipe.apply_parameters(&seed, template_context).await?;
+            let defaults =
+                template_context.collection.recipe_defaults(recipe_id);
 
             // Parallelization!
             let (url, query) = try_join!(
-                recipe.render_url(template_context),
+                recipe.render_url(&defaults, template_context),
                 recipe.render_query(options, template_context),
             )?;
 
             // Use RequestBuilder so we can offload the handling of query params
-            let client = self.get_client(&url);
-            let request = client
+            let request = self
+                .client
                 .request(recipe.method.into(), url)
                 .query(&query)
                 .build()?;
@@ -227,16 +394,16 @@ impl HttpEngine {
             id,
             recipe_id,
             options,
+            ..
         } = &seed;
         let _ =
             info_span!("Build request body", request_id = %id, ?recipe_id, ?options)
                 .entered();
+        template_context.state.set_recipe(recipe_id);
 
         let future = async {
-            let recipe = template_context
-                .collection
-                .recipes
-                .try_get_recipe(recipe_id)?;
+            let recipe = seed.recipe(template_context)?;
+            recipe.apply_parameters(&seed, template_context).await?;
 
             let Some(body) =
                 recipe.render_body(options, template_context).await?
@@ -252,8 +419,8 @@ impl HttpEngine {
                 RenderedBody::FormUrlencoded(_)
                 | RenderedBody::FormMultipart(_) => {
                     let url = Url::parse("http://localhost").unwrap();
-                    let client = self.get_client(&url);
-                    let mut builder = client.request(reqwest::Method::GET, url);
+                    let mut builder =
+                        self.client.request(reqwest::Method::GET, url);
                     builder = body.apply(builder);
                     let request = builder.build()?;
                     // We just added a body so we know it's present, and we
@@ -274,21 +441,167 @@ impl HttpEngine {
         seed.convert_error(future, template_context).await
     }
 
-    /// Get the appropriate client to use for this request. If the request URL's
-    /// host is one for which the user wants to ignore TLS certs, use the
-    /// dangerous client.
-    fn get_client(&self, url: &Url) -> &Client {
-        let host = url.host_str().unwrap_or_default();
-        match &self.danger_client {
-            Some((client, hostnames)) if hostnames.contains(host) => client,
-            _ => &self.client,
+    /// Send a previously recorded request again, and write the response body
+    /// to `writer` as it downloads. Nothing is stored in history, and the
+    /// capture limit doesn't apply. This is used to get the full body of a
+    /// response that was truncated. Returns the number of bytes written.
+    ///
+    /// The client and SSH tunnel are selected the same way as in
+    /// [Self::build], using the request's recipe (if it still exists) and the
+    /// profile selected in `template_context`, so the replay presents the same
+    /// client certificate and TLS settings as the original.
+    ///
+    /// Fails if the request had a body that was too large to be recorded,
+    /// because it can't be replayed.
+    pub async fn download(
+        &self,
+        request: &RequestRecord,
+        template_context: &TemplateContext,
+        writer: &mut (impl AsyncWrite + Unpin),
+        mut on_progress: impl FnMut(DownloadProgress),
+    ) -> anyhow::Result<u64> {
+        let has_body = request
+            .headers
+            .get(header::CONTENT_LENGTH)
+            .is_some_and(|length| length != "0");
+        if has_body && request.body.is_none() {
+            bail!("Request body was too large to be recorded");
+        }
+
+        let recipe = template_context
+            .collection
+            .recipes
+            .get_recipe(&request.recipe_id);
+        let (identity, tunnel) = try_join!(
+            async {
+                match recipe {
+                    Some(recipe) => {
+                        recipe.render_certificate(template_context).await
+                    }
+                    None => Ok(None),
+                }
+            },
+            render_tunnel(template_context),
+        )?;
+        let client = self.select_client(recipe, &request.url, identity)?;
+        if let Some(config) = tunnel {
+            PendingTunnel {
+                manager: self.tunnels.clone(),
+                config,
+            }
+            .open()
+            .await?;
+        }
+
+        let mut builder = client
+            .request(request.method.clone(), request.url.clone())
+            .headers(request.headers.clone());
+        if let Some(body) = &request.body {
+            builder = builder.body(body.clone());
+        }
+        let mut response = builder.send().await?;
+
+        let total = response.content_length();
+        let mut downloaded = 0;
+        let mut last_report = Instant::now();
+        while let Some(chunk) = response.chunk().await? {
+            writer.write_all(&chunk).await?;
+            downloaded += chunk.len();
+            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                on_progress(DownloadProgress { downloaded, total });
+                last_report = Instant::now();
+            }
+        }
+        writer.flush().await?;
+        Ok(downloaded as u64)
+    }
+
+    /// Should TLS certificate errors be ignored for this request? This is
+    /// true if the recipe opts out of verification, or the URL's host is one
+    /// for which the user wants to ignore TLS errors.
+    fn is_danger(&self, recipe: Option<&Recipe>, url: &Url) -> bool {
+        recipe.is_some_and(|recipe| recipe.danger_skip_tls_verify)
+            || self
+                .danger_hosts
+                .contains(url.host_str().unwrap_or_default())
+    }
+
+    /// Select the client for a request, based on the recipe's TLS settings
+    /// and the rendered client certificate
+    fn select_client(
+        &self,
+        recipe: Option<&Recipe>,
+        url: &Url,
+        identity: Option<ClientIdentity>,
+    ) -> anyhow::Result<Client> {
+        let danger = self.is_danger(recipe, url);
+        match identity {
+            Some(identity) => self.get_identity_client(identity, danger),
+            None => Ok(self.get_client(danger).clone()),
+        }
+    }
+
+    /// Get the appropriate client to use for a request. If TLS errors should
+    /// be ignored, use the dangerous client.
+    fn get_client(&self, danger: bool) -> &Client {
+        if danger {
+            self.danger_client.get_or_init(|| {
+                Self::build_client(&self.ca_certificates, true, None)
+                    // We already built a client with the same config, so this
+                    // should never fail
+                    .expect("Error building reqwest client")
+            })
+        } else {
+            &self.client
+        }
+    }
+
+    /// Get a client that presents the given client certificate. Clients are
+    /// cached by identity, so each certificate only builds one client (or
+    /// two, if it's also used with TLS errors ignored).
+    fn get_identity_client(
+        &self,
+        identity: ClientIdentity,
+        danger: bool,
+    ) -> anyhow::Result<Client> {
+        let mut clients = self
+            .identity_clients
+            .lock()
+            .expect("Client cache lock poisoned");
+        let key = (identity, danger);
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
         }
+
+        let client =
+            Self::build_client(&self.ca_certificates, danger, Some(&key.0))?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// Build a client. All clients share the same settings, except for TLS
+    /// verification and the client certificate
+    fn build_client(
+        ca_certificates: &[CertificateDer<'static>],
+        danger: bool,
+        identity: Option<&ClientIdentity>,
+    ) -> anyhow::Result<Client> {
+        let tls = tls::client_config(ca_certificates, danger, identity)?;
+        Client::builder()
+            .user_agent(USER_AGENT)
+            .use_preconfigured_tls(tls)
+            .dns_resolver(Arc::new(TimingResolver))
+            .connector_layer(TimingLayer)
+            .tls_info(true)
+            .build()
+            .context("Error building HTTP client")
     }
 }
 
 impl Default for HttpEngine {
     fn default() -> Self {
         Self::new(&HttpEngineConfig::default())
+            .expect("Error building default HTTP engine")
     }
 }
 
@@ -296,29 +609,69 @@ impl Default for HttpEngine {
 pub struct HttpEngineConfig {
     /// TLS cert errors on these hostnames are ignored. Be careful!
     pub ignore_certificate_hosts: Vec<String>,
+    /// Path to a PEM file of additional root CA certificates to trust, e.g.
+    /// for a corporate proxy. System roots are still trusted.
+    pub ca_certificate: Option<PathBuf>,
     /// Request/response bodies over this size are treated differently, for
     /// performance reasons
     pub large_body_size: usize,
+    /// Response bodies over this size are streamed to a file instead of being
+    /// held in memory and stored in the history database
+    pub stream_body_size: usize,
+    /// Only capture this much of each response body. The rest is downloaded
+    /// but discarded, so the stored body is a truncated prefix. `None` means
+    /// no limit
+    pub max_body_size: Option<usize>,
+    /// Never send requests. Instead, each request gets its recipe's `example`
+    /// response, so a collection can be used without access to the API
+    #[serde(default)]
+    pub offline: bool,
 }
 
 impl Default for HttpEngineConfig {
     fn default() -> Self {
         Self {
             ignore_certificate_hosts: Default::default(),
+            ca_certificate: None,
             large_body_size: 1000 * 1000, // 1MB
+            stream_body_size: 100 * 1000 * 1000, // 100MB
+            max_body_size: None,
+            offline: false,
         }
     }
 }
 
 impl RequestSeed {
-    /// Run the given future and convert any error into [RequestBuildError]
+    /// Get the recipe to build from: either the one attached to the seed, or
+    /// the one in the collection with the seed's ID
+    fn recipe<'a>(
+        &'a self,
+        template_context: &'a TemplateContext,
+    ) -> anyhow::Result<&'a Recipe> {
+        match &self.recipe {
+            Some(recipe) => Ok(recipe),
+            None => template_context
+                .collection
+                .recipes
+                .try_get_recipe(&self.recipe_id),
+        }
+    }
+
+    /// Run the given future and convert any error into [RequestBuildError].
+    /// If the seed is cancelled first, the future is dropped and the error is
+    /// [RequestCancelled].
     async fn convert_error<T>(
         &self,
         future: impl Future<Output = anyhow::Result<T>>,
         template_context: &TemplateContext,
     ) -> Result<T, RequestBuildError> {
         let start_time = Utc::now();
-        future.await.traced().map_err(|error| RequestBuildError {
+        let result = self
+            .cancel
+            .run_until_cancelled(future)
+            .await
+            .unwrap_or_else(|| Err(RequestCancelled.into()));
+        result.traced().map_err(|error| RequestBuildError {
             profile_id: template_context.selected_profile.clone(),
             recipe_id: self.recipe_id.clone(),
             id: self.id,
@@ -342,6 +695,17 @@ impl RequestTicket {
     pub async fn send(
         self,
         database: &CollectionDatabase,
+    ) -> Result<Exchange, RequestError> {
+        self.send_with_progress(database, |_| {}).await
+    }
+
+    /// [Self::send], but report progress while the response body downloads.
+    /// The callback is called periodically, but only once the download has
+    /// taken long enough to be worth showing.
+    pub async fn send_with_progress(
+        self,
+        database: &CollectionDatabase,
+        mut on_progress: impl FnMut(DownloadProgress),
     ) -> Result<Exchange, RequestError> {
         let id = self.record.id;
 
@@ -351,16 +715,66 @@ impl RequestTicket {
         // This start time will be accurate because the request doesn't launch
         // until this whole future is awaited
         let start_time = Utc::now();
-        let result = async {
-            let response = self.client.execute(self.request).await?;
-            // Load the full response and convert it to our format
-            ResponseRecord::from_response(response).await
+        // In offline mode, the response was loaded when the request was built.
+        // Fixture responses aren't real, so they're kept out of history
+        if let Some(response) = self.fixture {
+            info!(status = response.status.as_u16(), "Offline response");
+            return Ok(Exchange {
+                id,
+                request: self.record,
+                response: Arc::new(response),
+                start_time,
+                end_time: Utc::now(),
+                timing: None,
+                certificate: None,
+            });
         }
-        .await;
+
+        // If cancelled, the future is dropped, which closes the connection
+        let cancel = self.cancel.clone();
+        let future = async {
+            if let Some(tunnel) = &self.tunnel {
+                tunnel.open().await?;
+            }
+            let sent = Instant::now();
+            let (response, phases) =
+                measure(self.client.execute(self.request)).await;
+            let response = response?;
+            let headers_received = Instant::now();
+            let certificate = ServerCertificate::from_response(&response);
+            // Blobs are stored in plaintext, so when history is encrypted (or
+            // we can't tell), large bodies go in the database like everything
+            // else
+            let stream = database
+                .database()
+                .encryption()
+                .is_ok_and(|source| source.is_none())
+                .then_some((self.stream_body_size, BlobStore::global));
+            // Load the full response and convert it to our format
+            let response = ResponseRecord::from_response(
+                response,
+                stream,
+                self.max_body_size,
+                &mut on_progress,
+            )
+            .await?;
+            let timing = ExchangeTiming {
+                dns: phases.dns,
+                connect: phases.connect,
+                tls: phases.tls,
+                wait: (headers_received - sent).saturating_sub(phases.total()),
+                download: headers_received.elapsed(),
+            };
+            Ok::<_, anyhow::Error>((response, timing, certificate))
+        };
+        let result = cancel
+            .run_until_cancelled(future)
+            .await
+            .unwrap_or_else(|| Err(RequestCancelled.into()));
         let end_time = Utc::now();
 
         match result {
-            Ok(response) => {
+            Ok((response, timing, certificate)) => {
                 info!(status = response.status.as_u16(), "Response");
                 let exchange = Exchange {
                     id,
@@ -368,6 +782,8 @@ impl RequestTicket {
                     response: Arc::new(response),
                     start_time,
                     end_time,
+                    timing: Some(timing),
+                    certificate,
                 };
 
                 // Error here should *not* kill the request
@@ -377,13 +793,18 @@ impl RequestTicket {
 
             // Attach metadata to the error and yeet it. Can't use map_err
             // because we need to conditionally move the request
-            Err(error) => Err(RequestError {
-                request: self.record,
-                start_time,
-                end_time,
-                error: error.into(),
-            })
-            .traced(),
+            Err(error) => {
+                let error = RequestError {
+                    request: self.record,
+                    start_time,
+                    end_time,
+                    error,
+                };
+                // Record the failure for stats. Error here should *not* mask
+                // the original error
+                let _ = database.insert_failure(&error);
+                Err(error).traced()
+            }
         }
     }
 }
@@ -391,18 +812,73 @@ impl RequestTicket {
 impl ResponseRecord {
     /// Convert [reqwest::Response] type into [ResponseRecord]. This is async
     /// because the response content is not necessarily loaded when we first get
-    /// the response. Only fails if the response content fails to load.
+    /// the response. Fails if the response content fails to load, or can't be
+    /// written to the blob store.
+    ///
+    /// If `stream` is given, a body larger than the given size is written to
+    /// the blob store as it downloads, and only a preview is kept in memory.
+    /// The store is only created once it's needed.
+    ///
+    /// If `max_size` is given, only that much of the body is captured. The
+    /// rest is still downloaded, so the full size can be recorded.
     async fn from_response(
-        response: Response,
-    ) -> reqwest::Result<ResponseRecord> {
-        // Copy response metadata out first, because we need to move the
-        // response to resolve content (not sure why...)
+        mut response: Response,
+        mut stream: Option<(usize, impl FnOnce() -> BlobStore)>,
+        max_size: Option<usize>,
+        on_progress: &mut impl FnMut(DownloadProgress),
+    ) -> anyhow::Result<ResponseRecord> {
         let status = response.status();
         let headers = response.headers().clone();
 
-        // Pre-resolve the content, so we get all the async work done
-        let body = response.bytes().await?.into();
+        // Pre-resolve the content, so we get all the async work done. Read it
+        // chunk by chunk so we can report progress on large bodies
+        let total = response.content_length();
+        let mut body = Vec::new();
+        let mut writer: Option<BlobWriter> = None;
+        let mut downloaded = 0;
+        let mut captured = 0;
+        let mut last_report = Instant::now();
+        while let Some(chunk) = response.chunk().await? {
+            downloaded += chunk.len();
+            // Anything past the limit is thrown away
+            let keep = max_size.map_or(chunk.len(), |max_size| {
+                chunk.len().min(max_size.saturating_sub(captured))
+            });
+            let chunk = &chunk[..keep];
+            captured += keep;
+            if chunk.is_empty() {
+                // Nothing to capture
+            } else if let Some(writer) = &mut writer {
+                writer.write(chunk).await?;
+            } else if let Some((_, store)) =
+                stream.take_if(|(size, _)| captured > *size)
+            {
+                // The body is too big to hold onto. Move what we have so far
+                // into a blob, and keep just the beginning
+                body.extend_from_slice(chunk);
+                let mut new_writer = store().writer().await?;
+                new_writer.write(&body).await?;
+                body.truncate(STREAM_PREVIEW_SIZE);
+                body.shrink_to_fit();
+                writer = Some(new_writer);
+            } else {
+                body.extend_from_slice(chunk);
+            }
+            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                on_progress(DownloadProgress { downloaded, total });
+                last_report = Instant::now();
+            }
+        }
 
+        let mut body = match writer {
+            Some(writer) => {
+                ResponseBody::streamed(body.into(), writer.finish().await?)
+            }
+            None => Bytes::from(body).into(),
+        };
+        if downloaded > captured {
+            body = body.truncated(downloaded as u64);
+        }
         Ok(ResponseRecord {
             status,
             headers,
@@ -413,13 +889,66 @@ impl ResponseRecord {
 
 /// Render steps for individual pieces of a recipe
 impl Recipe {
-    /// Render base URL, *excluding* query params
+    /// Validate the parameter values given for the build, and fill in defaults
+    /// for the rest. The values are stored in the render group, so templates
+    /// can reference them like profile fields.
+    async fn apply_parameters(
+        &self,
+        seed: &RequestSeed,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<()> {
+        let given = &seed.options.parameters;
+        if let Some(name) = given
+            .keys()
+            .find(|name| !self.parameters.contains_key(*name))
+        {
+            bail!(
+                "Unknown parameter `{name}` for recipe `{}`; options are: {}",
+                self.id,
+                self.parameters.keys().format(", ")
+            );
+        }
+
+        let mut values = IndexMap::with_capacity(self.parameters.len());
+        for (name, parameter) in &self.parameters {
+            let value = match (given.get(name), &parameter.default) {
+                (Some(value), _) => value.clone(),
+                (None, Some(default)) => default
+                    .render_string(template_context)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Error rendering default for parameter `{name}`"
+                        )
+                    })?,
+                (None, None) => {
+                    bail!("Missing value for parameter `{name}`")
+                }
+            };
+            parameter
+                .kind
+                .validate(&value)
+                .with_context(|| format!("Invalid parameter `{name}`"))?;
+            values.insert(name.clone(), value);
+        }
+
+        // Requests triggered by chains share the render group, but the values
+        // belong to the request the user sent
+        if seed.trigger.is_none() {
+            template_context.state.set_parameters(values);
+        }
+        Ok(())
+    }
+
+    /// Render base URL, *excluding* query params. The default base URL is
+    /// prepended if the recipe's URL is just a path
     async fn render_url(
         &self,
+        defaults: &RecipeDefaults,
         template_context: &TemplateContext,
     ) -> anyhow::Result<Url> {
-        let url = self
-            .url
+        let url = defaults
+            .url(self)
             .render_string(template_context)
             .await
             .context("Error rendering URL")?;
@@ -433,28 +962,50 @@ impl Recipe {
         options: &BuildOptions,
         template_context: &TemplateContext,
     ) -> anyhow::Result<Vec<(String, String)>> {
-        let iter = self.query.iter().enumerate().filter_map(|(i, (k, v))| {
-            // Look up and apply override. We do this by index because the
-            // keys aren't necessarily unique
-            let template = options.query_parameters.get(i, v)?;
-
-            Some(async move {
+        let iter = self
+            .query
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (k, v))| {
+                // Params set by name replace all params with that name
+                if options
+                    .extra_query_parameters
+                    .iter()
+                    .any(|(key, _)| key == k)
+                {
+                    return None;
+                }
+                // Look up and apply override. We do this by index because the
+                // keys aren't necessarily unique
+                options
+                    .query_parameters
+                    .get(i, v)
+                    .map(|template| (k, template))
+            })
+            .chain(
+                options
+                    .extra_query_parameters
+                    .iter()
+                    .map(|(k, template)| (k, template)),
+            )
+            .map(|(k, template)| async move {
                 Ok::<_, anyhow::Error>((
                     k.clone(),
                     template.render_string(template_context).await.context(
                         format!("Error rendering query parameter `{k}`"),
                     )?,
                 ))
-            })
-        });
+            });
         future::try_join_all(iter).await
     }
 
-    /// Render all headers specified by the user. This will *not* include
-    /// authentication and other implicit headers
+    /// Render all headers specified by the user, including those inherited
+    /// from defaults. This will *not* include authentication and other
+    /// implicit headers
     async fn render_headers(
         &self,
         options: &BuildOptions,
+        defaults: &RecipeDefaults,
         template_context: &TemplateContext,
     ) -> anyhow::Result<HeaderMap> {
         let mut headers = HeaderMap::new();
@@ -474,18 +1025,35 @@ impl Recipe {
             );
         }
 
-        // Render headers in an iterator so we can parallelize
-        let iter = self.headers.iter().enumerate().filter_map(
-            move |(i, (header, value_template))| {
+        // Render headers in an iterator so we can parallelize. Inherited
+        // headers come after the recipe's own, so override indexes for the
+        // recipe's headers are unaffected by defaults
+        let iter = self
+            .headers
+            .iter()
+            .chain(defaults.inherited_headers(self))
+            .enumerate()
+            .filter_map(move |(i, (header, value_template))| {
+                // Headers set by name replace the recipe's, so don't bother
+                // rendering those
+                if options
+                    .extra_headers
+                    .iter()
+                    .any(|(name, _)| name.eq_ignore_ascii_case(header))
+                {
+                    return None;
+                }
                 // Look up and apply override. We do this by index because the
                 // keys aren't necessarily unique
                 let template = options.headers.get(i, value_template)?;
-
-                Some(async move {
-                    self.render_header(template_context, header, template).await
-                })
-            },
-        );
+                Some((header, template))
+            })
+            .chain(options.extra_headers.iter().filter_map(
+                |(header, template)| Some((header, template.as_ref()?)),
+            ))
+            .map(|(header, template)| async move {
+                self.render_header(template_context, header, template).await
+            });
 
         let rendered = future::try_join_all(iter).await?;
         headers.reserve(rendered.len());
@@ -494,6 +1062,15 @@ impl Recipe {
         for (header, value) in rendered {
             headers.insert(header, value);
         }
+        // A removed header may have been set implicitly, e.g. Content-Type
+        // from the body
+        for (header, _) in options
+            .extra_headers
+            .iter()
+            .filter(|(_, template)| template.is_none())
+        {
+            headers.remove(header.as_str());
+        }
 
         Ok(headers)
     }
@@ -534,12 +1111,13 @@ impl Recipe {
     async fn render_authentication(
         &self,
         options: &BuildOptions,
+        defaults: &RecipeDefaults,
         template_context: &TemplateContext,
     ) -> anyhow::Result<Option<Authentication<String>>> {
         let authentication = options
             .authentication
             .as_ref()
-            .or(self.authentication.as_ref());
+            .or(defaults.authentication(self));
         match authentication {
             Some(Authentication::Basic { username, password }) => {
                 let (username, password) = try_join!(
@@ -568,10 +1146,178 @@ impl Recipe {
                     .context("Error rendering bearer token")?;
                 Ok(Some(Authentication::Bearer(token)))
             }
+
+            Some(Authentication::AwsSigv4 {
+                region,
+                service,
+                access_key_id,
+                secret_access_key,
+                session_token,
+                profile,
+            }) => {
+                /// Render a template that may not be present
+                async fn render_option(
+                    template: Option<&Template>,
+                    template_context: &TemplateContext,
+                    field: &str,
+                ) -> anyhow::Result<Option<String>> {
+                    OptionFuture::from(template.map(|template| {
+                        template.render_string(template_context)
+                    }))
+                    .await
+                    .transpose()
+                    .with_context(|| format!("Error rendering `{field}`"))
+                }
+
+                let (
+                    region,
+                    service,
+                    access_key_id,
+                    secret_access_key,
+                    session_token,
+                    profile,
+                ) = try_join!(
+                    async {
+                        region
+                            .render_string(template_context)
+                            .await
+                            .context("Error rendering `region`")
+                    },
+                    async {
+                        service
+                            .render_string(template_context)
+                            .await
+                            .context("Error rendering `service`")
+                    },
+                    render_option(
+                        access_key_id.as_ref(),
+                        template_context,
+                        "access_key_id"
+                    ),
+                    render_option(
+                        secret_access_key.as_ref(),
+                        template_context,
+                        "secret_access_key"
+                    ),
+                    render_option(
+                        session_token.as_ref(),
+                        template_context,
+                        "session_token"
+                    ),
+                    render_option(
+                        profile.as_ref(),
+                        template_context,
+                        "profile"
+                    ),
+                )?;
+
+                // Resolve credentials now, so signing doesn't have to do
+                // anything async. The rendered value always has the keys
+                // populated and no profile.
+                let credentials = match (access_key_id, secret_access_key) {
+                    (Some(access_key_id), Some(secret_access_key)) => {
+                        AwsCredentials {
+                            access_key_id,
+                            secret_access_key,
+                            session_token,
+                        }
+                    }
+                    (None, None) => AwsCredentials::load(profile.as_deref())
+                        .await
+                        .context("Error loading AWS credentials")?,
+                    _ => bail!(
+                        "`access_key_id` and `secret_access_key` must be \
+                        given together"
+                    ),
+                };
+                Ok(Some(Authentication::AwsSigv4 {
+                    region,
+                    service,
+                    access_key_id: Some(credentials.access_key_id),
+                    secret_access_key: Some(credentials.secret_access_key),
+                    session_token: credentials.session_token,
+                    profile: None,
+                }))
+            }
             None => Ok(None),
         }
     }
 
+    /// Render the body signature config. The signature itself can't be
+    /// computed until the body is built
+    async fn render_signature(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<Option<BodySignature<String>>> {
+        /// Render a single field
+        async fn render(
+            template: &Template,
+            template_context: &TemplateContext,
+            field: &str,
+        ) -> anyhow::Result<String> {
+            template
+                .render_string(template_context)
+                .await
+                .with_context(|| format!("Error rendering signature `{field}`"))
+        }
+
+        let Some(signature) = &self.signature else {
+            return Ok(None);
+        };
+        let rendered = match signature {
+            BodySignature::Stripe { secret, timestamp } => {
+                let (secret, timestamp) = try_join!(
+                    render(secret, template_context, "secret"),
+                    async {
+                        OptionFuture::from(timestamp.as_ref().map(
+                            |timestamp| {
+                                render(timestamp, template_context, "timestamp")
+                            },
+                        ))
+                        .await
+                        .transpose()
+                    },
+                )?;
+                BodySignature::Stripe { secret, timestamp }
+            }
+            BodySignature::Github { secret } => BodySignature::Github {
+                secret: render(secret, template_context, "secret").await?,
+            },
+            BodySignature::Hmac { secret, header } => {
+                let (secret, header) = try_join!(
+                    render(secret, template_context, "secret"),
+                    render(header, template_context, "header"),
+                )?;
+                BodySignature::Hmac { secret, header }
+            }
+        };
+        Ok(Some(rendered))
+    }
+
+    /// Render the client certificate for mTLS. The recipe's certificate takes
+    /// precedence over the selected profile's.
+    async fn render_certificate(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<Option<ClientIdentity>> {
+        let profile_certificate = || {
+            let profile_id = template_context.selected_profile.as_ref()?;
+            let profile =
+                template_context.collection.profiles.get(profile_id)?;
+            profile.certificate.as_ref()
+        };
+        let Some(certificate) =
+            self.certificate.as_ref().or_else(profile_certificate)
+        else {
+            return Ok(None);
+        };
+        let identity = certificate
+            .render(template_context)
+            .await
+            .context("Error loading client certificate")?;
+        Ok(Some(identity))
+    }
+
     /// Render request body
     async fn render_body(
         &self,
@@ -633,16 +1379,237 @@ impl Recipe {
 }
 
 impl Authentication<String> {
-    fn apply(self, builder: RequestBuilder) -> RequestBuilder {
+    /// Apply authentication to a request that's still being built
+    fn apply(&self, builder: RequestBuilder) -> RequestBuilder {
         match self {
             Authentication::Basic { username, password } => {
-                builder.basic_auth(username, password)
+                builder.basic_auth(username, password.as_ref())
             }
             Authentication::Bearer(token) => builder.bearer_auth(token),
+            // Applied in the signing stage instead
+            Authentication::AwsSigv4 { .. } => builder,
+        }
+    }
+
+    /// Apply authentication to a fully built request. This is for schemes
+    /// that sign the request, which requires the URL, headers, and body to be
+    /// finalized. Nothing else should modify the request after this.
+    fn sign(&self, request: &mut Request) -> anyhow::Result<()> {
+        match self {
+            Authentication::Basic { .. } | Authentication::Bearer(_) => {}
+            Authentication::AwsSigv4 {
+                region,
+                service,
+                access_key_id,
+                secret_access_key,
+                session_token,
+                ..
+            } => {
+                // Credentials were resolved during rendering
+                let credentials = AwsCredentials {
+                    access_key_id: access_key_id.clone().unwrap_or_default(),
+                    secret_access_key: secret_access_key
+                        .clone()
+                        .unwrap_or_default(),
+                    session_token: session_token.clone(),
+                };
+                credentials
+                    .sign(request, region, service, Utc::now())
+                    .context("Error signing request with AWS SigV4")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BodySignature<String> {
+    /// Sign the body of a fully built request, and add the signature header
+    fn sign(
+        &self,
+        request: &mut Request,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let body = match request.body() {
+            None => &[][..],
+            Some(body) => body.as_bytes().ok_or_else(|| {
+                anyhow!(
+                    "Streamed bodies (e.g. multipart forms) can't be signed"
+                )
+            })?,
+        };
+        let (header, value) = match self {
+            BodySignature::Stripe { secret, timestamp } => {
+                let timestamp = timestamp
+                    .clone()
+                    .unwrap_or_else(|| now.timestamp().to_string());
+                let payload = [timestamp.as_bytes(), b".", body].concat();
+                let signature = hex(&hmac_sha256(secret.as_bytes(), &payload));
+                ("Stripe-Signature", format!("t={timestamp},v1={signature}"))
+            }
+            BodySignature::Github { secret } => (
+                "X-Hub-Signature-256",
+                format!(
+                    "sha256={}",
+                    hex(&hmac_sha256(secret.as_bytes(), body))
+                ),
+            ),
+            BodySignature::Hmac { secret, header } => {
+                (header.as_str(), hex(&hmac_sha256(secret.as_bytes(), body)))
+            }
+        };
+        let header = HeaderName::try_from(header).with_context(|| {
+            format!("Invalid signature header name `{header}`")
+        })?;
+        // Signatures are hex, so the value is always valid
+        let value = HeaderValue::try_from(value)?;
+        request.headers_mut().insert(header, value);
+        Ok(())
+    }
+}
+
+/// Render the SSH tunnel for the selected profile, if any
+async fn render_tunnel(
+    template_context: &TemplateContext,
+) -> anyhow::Result<Option<TunnelConfig>> {
+    let tunnel = template_context
+        .selected_profile
+        .as_ref()
+        .and_then(|profile_id| {
+            template_context.collection.profiles.get(profile_id)
+        })
+        .and_then(|profile| profile.tunnel.as_ref());
+    OptionFuture::from(tunnel.map(|tunnel| tunnel.render(template_context)))
+        .await
+        .transpose()
+}
+
+impl SshTunnel {
+    /// Render all templates in the tunnel definition
+    async fn render(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<TunnelConfig> {
+        let (host, jump, remote_host) = try_join!(
+            async {
+                self.host
+                    .render_string(template_context)
+                    .await
+                    .context("Error rendering tunnel `host`")
+            },
+            async {
+                OptionFuture::from(
+                    self.jump
+                        .as_ref()
+                        .map(|jump| jump.render_string(template_context)),
+                )
+                .await
+                .transpose()
+                .context("Error rendering tunnel `jump`")
+            },
+            async {
+                OptionFuture::from(self.remote_host.as_ref().map(
+                    |remote_host| remote_host.render_string(template_context),
+                ))
+                .await
+                .transpose()
+                .context("Error rendering tunnel `remote_host`")
+            },
+        )?;
+        Ok(TunnelConfig {
+            host,
+            jump,
+            local_port: self.local_port,
+            remote_host: remote_host.unwrap_or_else(|| "localhost".into()),
+            remote_port: self.remote_port,
+        })
+    }
+}
+
+impl ClientCertificate {
+    /// Load the certificate and key, and convert them to a single PEM bundle
+    async fn render(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<ClientIdentity> {
+        /// Render a path template and read the file it points to
+        async fn read_file(
+            template: &Template,
+            template_context: &TemplateContext,
+        ) -> anyhow::Result<Vec<u8>> {
+            let path: PathBuf = template
+                .render_string(template_context)
+                .await
+                .context("Error rendering path")?
+                .into();
+            let path = expand_home(path);
+            fs::read(&path)
+                .await
+                .with_context(|| format!("Error reading file `{path:?}`"))
+        }
+
+        match self {
+            Self::Pem { certificate, key } => {
+                let (mut pem, key) = try_join!(
+                    read_file(certificate, template_context),
+                    async {
+                        OptionFuture::from(
+                            key.as_ref()
+                                .map(|key| read_file(key, template_context)),
+                        )
+                        .await
+                        .transpose()
+                    },
+                )?;
+                if let Some(key) = key {
+                    pem.push(b'\n');
+                    pem.extend(key);
+                }
+                Ok(ClientIdentity(pem))
+            }
+            Self::Pkcs12 { path, password } => {
+                let (archive, password) =
+                    try_join!(read_file(path, template_context), async {
+                        OptionFuture::from(password.as_ref().map(|password| {
+                            password.render_string(template_context)
+                        }))
+                        .await
+                        .transpose()
+                        .context("Error rendering password")
+                    },)?;
+                let password = password.unwrap_or_default();
+
+                // Unpack the archive and convert it to PEM, which is the only
+                // format rustls accepts. The parser only supports the legacy
+                // (3DES/RC2) encryption schemes, so include that hint on error
+                let error = || {
+                    anyhow!(
+                        "Error decoding PKCS#12 archive. Check the password; \
+                        archives must use legacy encryption (e.g. `openssl \
+                        pkcs12 -export -legacy`)"
+                    )
+                };
+                let pfx = p12::PFX::parse(&archive).map_err(|_| error())?;
+                let certificates =
+                    pfx.cert_x509_bags(&password).map_err(|_| error())?;
+                let keys = pfx.key_bags(&password).map_err(|_| error())?;
+                let mut pem = String::new();
+                for certificate in &certificates {
+                    pem.push_str(&pem_encode("CERTIFICATE", certificate));
+                }
+                for key in &keys {
+                    pem.push_str(&pem_encode("PRIVATE KEY", key));
+                }
+                Ok(ClientIdentity(pem.into_bytes()))
+            }
         }
     }
 }
 
+/// A rendered client certificate and private key, PEM-encoded. This serves as
+/// both the source of the TLS identity and the cache key for its client.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct ClientIdentity(Vec<u8>);
+
 impl RecipeBody {
     /// Get the value that we should set for the `Content-Type` header,
     /// according to the body
@@ -706,6 +1673,20 @@ impl From<Method> for reqwest::Method {
     }
 }
 
+/// Encode DER bytes as a single PEM block with the given label
+fn pem_encode(label: &str, der: &[u8]) -> String {
+    let encoded = BASE64_STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {label}-----\n");
+    // PEM lines are wrapped at 64 characters. Base64 is ASCII so we can
+    // split anywhere
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("Base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {label}-----\n"));
+    pem
+}
+
 /// Trim the bytes from the beginning and end of a vector that match the given
 /// predicate. This will mutate the input vector. If bytes are trimmed off the
 /// start, it will be done with a single shift.
@@ -731,12 +1712,16 @@ fn trim_bytes(bytes: &mut Vec<u8>, f: impl Fn(u8) -> bool) {
 mod tests {
     use super::*;
     use crate::{
+        assert_err,
         collection::{
-            self, Authentication, Chain, ChainSource, Collection, Profile,
+            self, Authentication, Chain, ChainRequestTrigger, ChainSource,
+            Collection, Folder, ParameterType, Profile, RecipeId, RecipeNode,
+            RecipeParameter, ResponseExample,
         },
+        http::failure::FailureKind,
         test_util::{
-            by_id, header_map, http_engine, invalid_utf8_chain, Factory,
-            TestPrompter,
+            by_id, header_map, http_engine, invalid_utf8_chain, temp_dir,
+            test_data_dir, Factory, TempDir, TestPrompter,
         },
     };
     use indexmap::{indexmap, IndexMap};
@@ -744,8 +1729,13 @@ mod tests {
     use regex::Regex;
     use reqwest::{Body, Method, StatusCode};
     use rstest::rstest;
+    use rustls::pki_types::PrivateKeyDer;
     use serde_json::json;
-    use std::ptr;
+    use std::{
+        io::{Read, Write},
+        path::PathBuf,
+        ptr,
+    };
     use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
 
     /// Create a template context. Take a set of extra recipes and chains to
@@ -797,21 +1787,25 @@ mod tests {
     /// test this is to just make sure [HttpEngine::get_client] returns the
     /// expected client
     #[rstest]
-    #[case::safe("safe", false)]
-    #[case::danger("danger", true)]
+    #[case::safe("safe", false, false)]
+    #[case::danger_host("danger", false, true)]
+    #[case::danger_recipe("safe", true, true)]
     fn test_get_client(
         http_engine: &HttpEngine,
         #[case] hostname: &str,
+        #[case] danger_skip_tls_verify: bool,
         #[case] expected_danger: bool,
     ) {
-        let client = http_engine
-            .get_client(&format!("http://{hostname}/").parse().unwrap());
-        if expected_danger {
-            assert!(ptr::eq(
-                client,
-                &http_engine.danger_client.as_ref().unwrap().0
-            ));
-        } else {
+        let recipe = Recipe {
+            danger_skip_tls_verify,
+            ..Recipe::factory(())
+        };
+        let url = format!("http://{hostname}/").parse().unwrap();
+        let client =
+            http_engine.get_client(http_engine.is_danger(Some(&recipe), &url));
+        if expected_danger {
+            assert!(ptr::eq(client, http_engine.danger_client.get().unwrap()));
+        } else {
             assert!(ptr::eq(client, &http_engine.client));
         }
     }
@@ -864,6 +1858,8 @@ mod tests {
             *ticket.record,
             RequestRecord {
                 id: ticket.record.id,
+                environment: ticket.record.environment.clone(),
+                note: None,
                 profile_id: Some(
                     template_context.collection.first_profile_id().clone()
                 ),
@@ -876,6 +1872,141 @@ mod tests {
         );
     }
 
+    /// Defaults from the collection and folders are merged into the recipe.
+    /// Inherited headers can be overridden, after the recipe's own
+    #[rstest]
+    #[tokio::test]
+    async fn test_build_defaults(http_engine: &HttpEngine) {
+        let recipe = Recipe {
+            url: "/users/{{user_id}}".into(),
+            headers: indexmap! {"Accept".into() => "text/plain".into()},
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let mut template_context = template_context([], []);
+        let collection =
+            Arc::get_mut(&mut template_context.collection).unwrap();
+        collection.defaults = RecipeDefaults {
+            base_url: Some("{{host}}/api".into()),
+            authentication: Some(Authentication::Bearer("{{token}}".into())),
+            headers: indexmap! {
+                "accept".into() => "application/json".into(),
+                "X-Client".into() => "slumber".into(),
+            },
+        };
+        collection.recipes = by_id([RecipeNode::Folder(Folder {
+            children: by_id([recipe.into()]),
+            defaults: RecipeDefaults {
+                headers: indexmap! {"x-client".into() => "folder".into()},
+                ..Default::default()
+            },
+            ..Folder::factory(())
+        })])
+        .into();
+
+        let seed = RequestSeed::new(
+            recipe_id,
+            BuildOptions {
+                headers: [(
+                    1,
+                    BuildFieldOverride::Override("{{username}}".into()),
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            },
+        );
+        let ticket = http_engine.build(seed, &template_context).await.unwrap();
+
+        let request = &ticket.request;
+        assert_eq!(request.url().as_str(), "http://localhost/api/users/1");
+        assert_eq!(
+            request.headers(),
+            &header_map([
+                ("Accept", "text/plain"),
+                ("x-client", "user"),
+                ("Authorization", "Bearer tokenzzz"),
+            ])
+        );
+    }
+
+    /// A request triggered by a chain should record which chain triggered it,
+    /// and which request was being built at the time
+    #[rstest]
+    #[tokio::test]
+    async fn test_build_triggered_request(http_engine: &HttpEngine) {
+        let server = MockServer::start().await;
+        let host = server.uri();
+        Mock::given(matchers::method("GET"))
+            .and(matchers::path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("abc"))
+            .mount(&server)
+            .await;
+
+        let auth = Recipe {
+            id: "auth".into(),
+            url: format!("{host}/token").into(),
+            ..Recipe::factory(())
+        };
+        let login = Recipe {
+            id: "login".into(),
+            url: "{{host}}/login".into(),
+            headers: indexmap! {"Token".into() => "{{chains.token}}".into()},
+            ..Recipe::factory(())
+        };
+        let chain = Chain {
+            id: "token".into(),
+            source: ChainSource::Request {
+                recipe: "auth".into(),
+                trigger: ChainRequestTrigger::Always,
+                section: Default::default(),
+            },
+            ..Chain::factory(())
+        };
+        let template_context = TemplateContext {
+            http_engine: Some(http_engine.clone()),
+            ..template_context([auth, login], [chain])
+        };
+
+        let seed = RequestSeed::new("login".into(), BuildOptions::default());
+        let ticket = http_engine.build(seed, &template_context).await.unwrap();
+        assert_eq!(ticket.record.environment.trigger, None);
+
+        let triggered = template_context
+            .database
+            .get_latest_request(
+                template_context.selected_profile.as_ref(),
+                &"auth".into(),
+            )
+            .unwrap()
+            .expect("Triggered request should be in history");
+        assert_eq!(
+            triggered.request.environment.trigger,
+            Some(RequestTrigger {
+                chain_id: "token".into(),
+                recipe_id: Some("login".into()),
+            })
+        );
+    }
+
+    /// Scratch requests are built from the attached recipe, which isn't in
+    /// the collection
+    #[rstest]
+    #[tokio::test]
+    async fn test_build_scratch(http_engine: &HttpEngine) {
+        let recipe = Recipe {
+            id: RecipeId::scratch(),
+            url: "{{host}}/users/{{user_id}}".into(),
+            ..Recipe::factory(())
+        };
+        let template_context = template_context([], []);
+
+        let seed = RequestSeed::scratch(recipe);
+        let ticket = http_engine.build(seed, &template_context).await.unwrap();
+        assert_eq!(ticket.record.recipe_id, RecipeId::scratch());
+        assert_eq!(ticket.request.url().as_str(), "http://localhost/users/1");
+    }
+
     /// Test building just a URL. Should include query params, but headers/body
     /// should *not* be built
     #[rstest]
@@ -1001,6 +2132,8 @@ mod tests {
             *ticket.record,
             RequestRecord {
                 id: ticket.record.id,
+                environment: ticket.record.environment.clone(),
+                note: None,
                 profile_id: Some(
                     template_context.collection.first_profile_id().clone()
                 ),
@@ -1016,6 +2149,335 @@ mod tests {
         );
     }
 
+    /// Test AWS SigV4 signing with explicit credentials. The signature itself
+    /// is tested against known values in the aws module; here we just want to
+    /// make sure it's wired up correctly. Loading from the credential chain
+    /// requires the AWS CLI, so that isn't tested.
+    #[rstest]
+    #[tokio::test]
+    async fn test_authentication_aws_sigv4(http_engine: &HttpEngine) {
+        let recipe = Recipe {
+            // Should be replaced by the signature
+            headers: indexmap! {"Authorization".into() => "bogus".into()},
+            authentication: Some(Authentication::AwsSigv4 {
+                region: "us-east-1".into(),
+                service: "execute-api".into(),
+                access_key_id: Some("{{username}}".into()),
+                secret_access_key: Some("{{password}}".into()),
+                session_token: Some("{{token}}".into()),
+                profile: None,
+            }),
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+
+        let seed = RequestSeed::new(recipe_id.clone(), BuildOptions::default());
+        let ticket = http_engine.build(seed, &template_context).await.unwrap();
+
+        let headers = &ticket.record.headers;
+        assert_eq!(headers.get_all("authorization").iter().count(), 1);
+        let authorization = headers["authorization"].to_str().unwrap();
+        assert!(
+            authorization.starts_with("AWS4-HMAC-SHA256 Credential=user/"),
+            "{authorization}"
+        );
+        assert!(
+            authorization.contains(
+                "/us-east-1/execute-api/aws4_request, \
+                SignedHeaders=host;x-amz-date;x-amz-security-token, \
+                Signature="
+            ),
+            "{authorization}"
+        );
+        assert_eq!(headers["x-amz-security-token"], "tokenzzz");
+        assert!(headers.contains_key("x-amz-date"));
+    }
+
+    /// Test body signing for each signature format
+    #[rstest]
+    #[case::stripe(
+        BodySignature::Stripe {
+            secret: "{{password}}".into(),
+            timestamp: Some("1700000000".into()),
+        },
+        "stripe-signature",
+        "t=1700000000,\
+        v1=1c99b0412a85df55d0bc11bc548b7b0e8540e98f7d63bf5a6b09ff03cc4cf5fb",
+    )]
+    #[case::github(
+        BodySignature::Github { secret: "{{password}}".into() },
+        "x-hub-signature-256",
+        "sha256=f1aa5222f134928eb25c7d9c4e447a3786db3385da92668686a7822e975a3d06",
+    )]
+    #[case::hmac(
+        BodySignature::Hmac {
+            secret: "{{password}}".into(),
+            header: "X-Signature".into(),
+        },
+        "x-signature",
+        "f1aa5222f134928eb25c7d9c4e447a3786db3385da92668686a7822e975a3d06",
+    )]
+    #[tokio::test]
+    async fn test_signature(
+        http_engine: &HttpEngine,
+        #[case] signature: BodySignature,
+        #[case] expected_header: &str,
+        #[case] expected_value: &str,
+    ) {
+        let recipe = Recipe {
+            body: Some(RecipeBody::Raw {
+                body: r#"{"id":1}"#.into(),
+                content_type: None,
+            }),
+            signature: Some(signature),
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+
+        let seed = RequestSeed::new(recipe_id, BuildOptions::default());
+        let ticket = http_engine.build(seed, &template_context).await.unwrap();
+
+        assert_eq!(ticket.record.headers[expected_header], expected_value);
+    }
+
+    /// Streamed bodies aren't available up front, so they can't be signed
+    #[rstest]
+    #[tokio::test]
+    async fn test_signature_error(http_engine: &HttpEngine) {
+        let recipe = Recipe {
+            body: Some(RecipeBody::FormMultipart(indexmap! {
+                "field".into() => "value".into(),
+            })),
+            signature: Some(BodySignature::Github {
+                secret: "secret".into(),
+            }),
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+
+        let seed = RequestSeed::new(recipe_id, BuildOptions::default());
+        assert_err!(
+            http_engine.build(seed, &template_context).await,
+            "Streamed bodies (e.g. multipart forms) can't be signed"
+        );
+    }
+
+    /// Access key and secret have to be given together
+    #[rstest]
+    #[tokio::test]
+    async fn test_authentication_aws_sigv4_error(http_engine: &HttpEngine) {
+        let recipe = Recipe {
+            authentication: Some(Authentication::AwsSigv4 {
+                region: "us-east-1".into(),
+                service: "execute-api".into(),
+                access_key_id: Some("{{username}}".into()),
+                secret_access_key: None,
+                session_token: None,
+                profile: None,
+            }),
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+
+        let seed = RequestSeed::new(recipe_id.clone(), BuildOptions::default());
+        assert_err!(
+            http_engine.build(seed, &template_context).await,
+            "`access_key_id` and `secret_access_key` must be given together"
+        );
+    }
+
+    /// Test building requests with a client certificate. Clients should be
+    /// cached per identity
+    #[rstest]
+    #[case::pem(ClientCertificate::Pem {
+        certificate: "{{test_data}}/client.pem".into(),
+        key: Some("{{test_data}}/client.key".into()),
+    })]
+    #[case::pkcs12(ClientCertificate::Pkcs12 {
+        path: "{{test_data}}/client.p12".into(),
+        password: Some("{{password}}".into()),
+    })]
+    #[tokio::test]
+    async fn test_certificate(
+        test_data_dir: PathBuf,
+        #[case] certificate: ClientCertificate,
+    ) {
+        // Use a fresh engine so we can check the client cache in isolation
+        let http_engine = HttpEngine::default();
+        let recipe = Recipe {
+            certificate: Some(certificate),
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = TemplateContext {
+            overrides: indexmap! {
+                "test_data".into() => test_data_dir.to_str().unwrap().into(),
+            },
+            ..template_context([recipe], [])
+        };
+
+        for _ in 0..2 {
+            let seed =
+                RequestSeed::new(recipe_id.clone(), BuildOptions::default());
+            http_engine.build(seed, &template_context).await.unwrap();
+        }
+        assert_eq!(http_engine.identity_clients.lock().unwrap().len(), 1);
+    }
+
+    /// Certificate on the profile should be used when the recipe has none
+    #[rstest]
+    #[tokio::test]
+    async fn test_certificate_profile(test_data_dir: PathBuf) {
+        let http_engine = HttpEngine::default();
+        let profile = Profile {
+            certificate: Some(ClientCertificate::Pem {
+                certificate: test_data_dir
+                    .join("client.pem")
+                    .to_str()
+                    .unwrap()
+                    .into(),
+                key: Some(
+                    test_data_dir.join("client.key").to_str().unwrap().into(),
+                ),
+            }),
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let recipe = Recipe::factory(());
+        let recipe_id = recipe.id.clone();
+        let template_context = TemplateContext {
+            collection: Collection {
+                recipes: by_id([recipe]).into(),
+                profiles: by_id([profile]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            ..TemplateContext::factory(())
+        };
+
+        let seed = RequestSeed::new(recipe_id, BuildOptions::default());
+        http_engine.build(seed, &template_context).await.unwrap();
+        assert_eq!(http_engine.identity_clients.lock().unwrap().len(), 1);
+    }
+
+    /// SSH tunnel should be rendered from the selected profile and attached to
+    /// the ticket, to be opened when the request is sent
+    #[rstest]
+    #[tokio::test]
+    async fn test_tunnel(http_engine: &HttpEngine) {
+        let profile = Profile {
+            data: indexmap! {"user".into() => "me".into()},
+            tunnel: Some(SshTunnel {
+                host: "{{user}}@bastion".into(),
+                jump: None,
+                local_port: 8080,
+                remote_host: None,
+                remote_port: 80,
+            }),
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let recipe = Recipe::factory(());
+        let recipe_id = recipe.id.clone();
+        let template_context = TemplateContext {
+            collection: Collection {
+                recipes: by_id([recipe]).into(),
+                profiles: by_id([profile]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            ..TemplateContext::factory(())
+        };
+
+        let seed = RequestSeed::new(recipe_id, BuildOptions::default());
+        let ticket = http_engine.build(seed, &template_context).await.unwrap();
+        assert_eq!(
+            ticket.tunnel.map(|tunnel| tunnel.config),
+            Some(TunnelConfig {
+                host: "me@bastion".into(),
+                jump: None,
+                local_port: 8080,
+                remote_host: "localhost".into(),
+                remote_port: 80,
+            })
+        );
+    }
+
+    /// Test errors loading client certificates
+    #[rstest]
+    #[case::missing_file(
+        ClientCertificate::Pem {
+            certificate: "not-real.pem".into(),
+            key: None,
+        },
+        "Error reading file `\"not-real.pem\"`"
+    )]
+    #[case::invalid_pem(
+        ClientCertificate::Pem {
+            certificate: "{{test_data}}/client.pem".into(),
+            key: None,
+        },
+        "Invalid client certificate"
+    )]
+    #[case::wrong_password(
+        ClientCertificate::Pkcs12 {
+            path: "{{test_data}}/client.p12".into(),
+            password: Some("wrong".into()),
+        },
+        "Error decoding PKCS#12 archive"
+    )]
+    #[tokio::test]
+    async fn test_certificate_error(
+        test_data_dir: PathBuf,
+        #[case] certificate: ClientCertificate,
+        #[case] expected_error: &str,
+    ) {
+        let http_engine = HttpEngine::default();
+        let recipe = Recipe {
+            certificate: Some(certificate),
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = TemplateContext {
+            overrides: indexmap! {
+                "test_data".into() => test_data_dir.to_str().unwrap().into(),
+            },
+            ..template_context([recipe], [])
+        };
+
+        let seed = RequestSeed::new(recipe_id, BuildOptions::default());
+        assert_err!(
+            http_engine.build(seed, &template_context).await,
+            expected_error
+        );
+    }
+
+    /// Load a custom CA certificate from the config
+    #[rstest]
+    #[case::valid("client.pem", None)]
+    #[case::missing("missing.pem", Some("Error reading CA certificate"))]
+    #[case::empty("client.key", Some("No certificates found"))]
+    fn test_ca_certificate(
+        test_data_dir: PathBuf,
+        #[case] file_name: &str,
+        #[case] expected_error: Option<&str>,
+    ) {
+        let result = HttpEngine::new(&HttpEngineConfig {
+            ca_certificate: Some(test_data_dir.join(file_name)),
+            ..Default::default()
+        });
+        match expected_error {
+            None => assert_eq!(result.unwrap().ca_certificates.len(), 1),
+            Some(expected_error) => assert_err!(result, expected_error),
+        }
+    }
+
     /// Test each possible type of body. Raw bodies are covered by
     /// [test_build_request]. This seems redundant with [test_build_body], but
     /// we need this to test that the `content-type` header is set correctly.
@@ -1133,6 +2595,8 @@ mod tests {
             *ticket.record,
             RequestRecord {
                 id: ticket.record.id,
+                environment: ticket.record.environment.clone(),
+                note: None,
                 body: expected_body.map(Bytes::from),
                 // Use the actual content type here, because the expected
                 // content type maybe be a pattern and we need an exactl string.
@@ -1211,6 +2675,7 @@ mod tests {
                 // Form field override has to be in a different test, because
                 // we're using a raw body
                 form_fields: Default::default(),
+                ..Default::default()
             },
         );
         let ticket = http_engine.build(seed, &template_context).await.unwrap();
@@ -1219,6 +2684,8 @@ mod tests {
             *ticket.record,
             RequestRecord {
                 id: ticket.record.id,
+                environment: ticket.record.environment.clone(),
+                note: None,
                 profile_id: template_context.selected_profile.clone(),
                 recipe_id,
                 method: Method::GET,
@@ -1238,6 +2705,168 @@ mod tests {
         );
     }
 
+    /// Headers and query params set by name replace or extend the recipe's
+    #[rstest]
+    #[tokio::test]
+    async fn test_build_extra_overrides(http_engine: &HttpEngine) {
+        let recipe = Recipe {
+            headers: indexmap! {
+                // Replaced, despite the different case
+                "Accept".into() => "application/json".into(),
+                // Removed
+                "X-Debug".into() => "true".into(),
+                // Included
+                "X-Client".into() => "slumber".into(),
+            },
+            query: vec![
+                // Both replaced
+                ("tag".into(), "a".into()),
+                ("tag".into(), "b".into()),
+                // Included
+                ("fast".into(), "true".into()),
+            ],
+            body: Some(RecipeBody::Raw {
+                body: "{{username}}".into(),
+                content_type: Some(ContentType::Json),
+            }),
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+
+        let seed = RequestSeed::new(
+            recipe_id.clone(),
+            BuildOptions {
+                extra_headers: vec![
+                    ("accept".into(), Some("text/plain".into())),
+                    ("x-debug".into(), None),
+                    ("content-type".into(), None),
+                    ("X-New".into(), Some("{{username}}".into())),
+                ],
+                extra_query_parameters: vec![
+                    ("tag".into(), "c".into()),
+                    ("tag".into(), "{{mode}}".into()),
+                ],
+                body: Some("{{password}}".into()),
+                ..Default::default()
+            },
+        );
+        let ticket = http_engine.build(seed, &template_context).await.unwrap();
+
+        assert_eq!(
+            *ticket.record,
+            RequestRecord {
+                id: ticket.record.id,
+                environment: ticket.record.environment.clone(),
+                note: None,
+                profile_id: template_context.selected_profile.clone(),
+                recipe_id,
+                method: Method::GET,
+                url: "http://localhost/url?fast=true&tag=c&tag=sudo"
+                    .parse()
+                    .unwrap(),
+                headers: header_map([
+                    ("X-Client", "slumber"),
+                    ("accept", "text/plain"),
+                    ("X-New", "user"),
+                ]),
+                body: Some(b"hunter2".as_slice().into()),
+            }
+        );
+    }
+
+    /// Parameters are validated, fall back to defaults, and take precedence
+    /// over profile fields
+    #[rstest]
+    #[case::defaults(&[], Ok("http://localhost/users/1?page=3&active=true"))]
+    #[case::given(
+        &[("user_id", "42"), ("page", "7")],
+        Ok("http://localhost/users/42?page=7&active=true"),
+    )]
+    #[case::invalid_type(
+        &[("page", "seven")],
+        Err("Invalid parameter `page`: Expected integer, got `seven`"),
+    )]
+    #[case::unknown(
+        &[("user", "42")],
+        Err("Unknown parameter `user` for recipe `users`; options are: \
+            user_id, page, active"),
+    )]
+    #[tokio::test]
+    async fn test_build_parameters(
+        http_engine: &HttpEngine,
+        #[case] parameters: &[(&str, &str)],
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let recipe = Recipe {
+            url: "{{host}}/users/{{user_id}}".into(),
+            query: vec![
+                ("page".into(), "{{page}}".into()),
+                ("active".into(), "{{active}}".into()),
+            ],
+            parameters: indexmap! {
+                // Profile field is used when no value is given
+                "user_id".into() => RecipeParameter {
+                    kind: ParameterType::Integer,
+                    default: Some("{{user_id}}".into()),
+                    description: None,
+                },
+                "page".into() => RecipeParameter {
+                    kind: ParameterType::Integer,
+                    default: Some("{{group_id}}".into()),
+                    description: None,
+                },
+                "active".into() => RecipeParameter {
+                    kind: ParameterType::Boolean,
+                    default: Some("true".into()),
+                    description: None,
+                },
+            },
+            ..Recipe::factory("users")
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+
+        let seed = RequestSeed::new(
+            recipe_id,
+            BuildOptions {
+                parameters: parameters
+                    .iter()
+                    .map(|(name, value)| {
+                        ((*name).to_owned(), (*value).to_owned())
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+        );
+        let result = http_engine.build_url(seed, &template_context).await;
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap().as_str(), expected),
+            Err(expected) => assert_err!(result, expected),
+        }
+    }
+
+    /// A parameter without a default must be given a value
+    #[rstest]
+    #[tokio::test]
+    async fn test_build_parameter_missing(http_engine: &HttpEngine) {
+        let recipe = Recipe {
+            url: "{{host}}/users/{{user_id}}".into(),
+            parameters: indexmap! {
+                "user_id".into() => RecipeParameter::default(),
+            },
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+
+        let seed = RequestSeed::new(recipe_id, BuildOptions::default());
+        assert_err!(
+            http_engine.build(seed, &template_context).await,
+            "Missing value for parameter `user_id`"
+        );
+    }
+
     /// Test overriding form body fields. This has to be a separate test
     /// because it's incompatible with testing raw body overrides
     #[rstest]
@@ -1276,6 +2905,8 @@ mod tests {
             *ticket.record,
             RequestRecord {
                 id: ticket.record.id,
+                environment: ticket.record.environment.clone(),
+                note: None,
                 profile_id: template_context.selected_profile.clone(),
                 recipe_id,
                 method: Method::GET,
@@ -1365,6 +2996,404 @@ mod tests {
         );
     }
 
+    /// Connecting and the TLS handshake are timed separately, against a real
+    /// TLS server
+    #[rstest]
+    #[tokio::test]
+    async fn test_send_request_timing(
+        http_engine: &HttpEngine,
+        test_data_dir: PathBuf,
+    ) {
+        let pem = std::fs::read(test_data_dir.join("client.pem")).unwrap();
+        let key = std::fs::read(test_data_dir.join("client.key")).unwrap();
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            CertificateDer::pem_slice_iter(&pem)
+                .collect::<Result<_, _>>()
+                .unwrap(),
+            PrivateKeyDer::from_pem_slice(&key).unwrap(),
+        )
+        .unwrap();
+
+        // Serve a single HTTPS request on a blocking thread
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let connection =
+                rustls::ServerConnection::new(Arc::new(config)).unwrap();
+            let mut stream = rustls::StreamOwned::new(connection, stream);
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..n]);
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-length: 6\r\n\
+                    connection: close\r\n\r\nhello!",
+                )
+                .unwrap();
+            stream.conn.send_close_notify();
+            stream.flush().unwrap();
+        });
+
+        // The server's certificate isn't trusted
+        let recipe = Recipe {
+            url: format!("https://{address}/get").as_str().into(),
+            danger_skip_tls_verify: true,
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+        let seed = RequestSeed::new(recipe_id, BuildOptions::default());
+        let ticket = http_engine.build(seed, &template_context).await.unwrap();
+        let exchange = ticket.send(&template_context.database).await.unwrap();
+        server.join().unwrap();
+
+        assert_eq!(exchange.response.body.bytes(), b"hello!");
+        let timing = exchange.timing.unwrap();
+        // The host is an IP, so there's nothing to resolve
+        assert_eq!(timing.dns, None);
+        assert!(timing.connect.is_some());
+        assert!(timing.tls.unwrap() > Duration::ZERO);
+    }
+
+    /// In offline mode, the recipe's example is returned instead of sending
+    /// the request, and nothing is stored in history
+    #[rstest]
+    #[case::inline(
+        None,
+        "{\"id\": 3}",
+        header_map([("content-type", "text/plain")]),
+    )]
+    #[case::file(
+        Some("fixture.json"),
+        "",
+        header_map([("content-type", "application/json")]),
+    )]
+    #[tokio::test]
+    async fn test_offline(
+        temp_dir: TempDir,
+        #[case] file_name: Option<&str>,
+        #[case] body: &str,
+        #[case] expected_headers: HeaderMap,
+    ) {
+        let http_engine = HttpEngine::new(&HttpEngineConfig {
+            offline: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let file = file_name.map(|file_name| temp_dir.join(file_name));
+        if let Some(file) = &file {
+            fs::write(file, "{\"id\": 3}").await.unwrap();
+        }
+        let headers = if file.is_some() {
+            IndexMap::new()
+        } else {
+            indexmap! {"content-type".into() => "text/plain".into()}
+        };
+        let recipe = Recipe {
+            // Nothing is listening here
+            url: "http://localhost:1/get".into(),
+            example: Some(ResponseExample {
+                status: StatusCode::CREATED,
+                headers,
+                body: body.into(),
+                file,
+            }),
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+
+        let seed = RequestSeed::new(recipe_id.clone(), BuildOptions::default());
+        let ticket = http_engine.build(seed, &template_context).await.unwrap();
+        let exchange = ticket.send(&template_context.database).await.unwrap();
+        assert_eq!(
+            *exchange.response,
+            ResponseRecord {
+                status: StatusCode::CREATED,
+                headers: expected_headers,
+                body: ResponseBody::new(b"{\"id\": 3}".as_slice().into())
+            }
+        );
+        assert_eq!(exchange.timing, None);
+        assert!(template_context
+            .database
+            .get_latest_request(
+                template_context.selected_profile.as_ref(),
+                &recipe_id
+            )
+            .unwrap()
+            .is_none());
+    }
+
+    /// In offline mode, a recipe without a valid example fails to build
+    #[rstest]
+    #[case::no_example(None, "has no `example` response")]
+    #[case::both_body_and_file(
+        Some(ResponseExample {
+            body: "hello".into(),
+            file: Some("fixture.json".into()),
+            ..ResponseExample::default()
+        }),
+        "Example can't have both `body` and `file`",
+    )]
+    #[case::missing_file(
+        Some(ResponseExample {
+            file: Some("not-a-real-file.json".into()),
+            ..ResponseExample::default()
+        }),
+        "Error reading file",
+    )]
+    #[tokio::test]
+    async fn test_offline_error(
+        #[case] example: Option<ResponseExample>,
+        #[case] expected_error: &str,
+    ) {
+        let http_engine = HttpEngine::new(&HttpEngineConfig {
+            offline: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let recipe = Recipe {
+            example,
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+
+        let seed = RequestSeed::new(recipe_id, BuildOptions::default());
+        assert_err!(
+            http_engine.build(seed, &template_context).await,
+            expected_error
+        );
+    }
+
+    /// A body over the stream size is written to the blob store, with just a
+    /// preview kept in memory
+    #[rstest]
+    #[case::small(100, None)]
+    #[case::large(5, Some(12))]
+    #[tokio::test]
+    async fn test_stream_body(
+        temp_dir: TempDir,
+        #[case] stream_body_size: usize,
+        #[case] expected_blob_size: Option<u64>,
+    ) {
+        let server = MockServer::start().await;
+        Mock::given(matchers::method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string("hello world!"),
+            )
+            .mount(&server)
+            .await;
+
+        let store = BlobStore::new(temp_dir.join("blobs"));
+        let response = reqwest::get(server.uri()).await.unwrap();
+        let response = ResponseRecord::from_response(
+            response,
+            Some((stream_body_size, || store.clone())),
+            None,
+            &mut |_| {},
+        )
+        .await
+        .unwrap();
+
+        let body = &response.body;
+        assert_eq!(body.bytes(), b"hello world!");
+        assert_eq!(body.size(), 12);
+        assert_eq!(body.blob().map(|blob| blob.size), expected_blob_size);
+        if let Some(blob) = body.blob() {
+            assert_eq!(store.read(blob).unwrap(), b"hello world!");
+        }
+    }
+
+    /// A body over the capture limit is cut off, but the full size is still
+    /// recorded. The limit applies to streamed bodies too
+    #[rstest]
+    #[case::under(Some(100), None, b"hello world!".as_slice(), None, None)]
+    #[case::over(Some(5), None, b"hello".as_slice(), None, Some(12))]
+    #[case::streamed(Some(8), Some(3), b"hello wo".as_slice(), Some(8), Some(12))]
+    #[tokio::test]
+    async fn test_truncate_body(
+        temp_dir: TempDir,
+        #[case] max_body_size: Option<usize>,
+        #[case] stream_body_size: Option<usize>,
+        #[case] expected_body: &[u8],
+        #[case] expected_blob_size: Option<u64>,
+        #[case] expected_full_size: Option<u64>,
+    ) {
+        let server = MockServer::start().await;
+        Mock::given(matchers::method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string("hello world!"),
+            )
+            .mount(&server)
+            .await;
+
+        let store = BlobStore::new(temp_dir.join("blobs"));
+        let response = reqwest::get(server.uri()).await.unwrap();
+        let response = ResponseRecord::from_response(
+            response,
+            stream_body_size.map(|size| (size, || store.clone())),
+            max_body_size,
+            &mut |_| {},
+        )
+        .await
+        .unwrap();
+
+        let body = &response.body;
+        assert_eq!(body.bytes(), expected_body);
+        assert_eq!(body.blob().map(|blob| blob.size), expected_blob_size);
+        if let Some(blob) = body.blob() {
+            assert_eq!(store.read(blob).unwrap(), expected_body);
+        }
+        assert_eq!(body.full_size(), expected_full_size);
+    }
+
+    /// Replaying a recorded request writes the full body, without touching
+    /// history
+    #[rstest]
+    #[tokio::test]
+    async fn test_download(http_engine: &HttpEngine) {
+        let server = MockServer::start().await;
+        Mock::given(matchers::method("POST"))
+            .and(matchers::path("/data"))
+            .and(matchers::header("X-Test", "1"))
+            .and(matchers::body_string("request"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string("hello world!"),
+            )
+            .mount(&server)
+            .await;
+
+        let request = RequestRecord {
+            method: Method::POST,
+            url: format!("{}/data", server.uri()).parse().unwrap(),
+            headers: header_map([("X-Test", "1")]),
+            body: Some("request".into()),
+            ..RequestRecord::factory(())
+        };
+        let mut output = Vec::new();
+        let size = http_engine
+            .download(&request, &template_context([], []), &mut output, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(size, 12);
+        assert_eq!(output, b"hello world!");
+    }
+
+    /// A request whose body wasn't recorded can't be replayed
+    #[rstest]
+    #[tokio::test]
+    async fn test_download_missing_body(http_engine: &HttpEngine) {
+        let request = RequestRecord {
+            method: Method::POST,
+            headers: header_map([("content-length", "5000000")]),
+            body: None,
+            ..RequestRecord::factory(())
+        };
+        assert_err!(
+            http_engine
+                .download(
+                    &request,
+                    &template_context([], []),
+                    &mut Vec::new(),
+                    |_| {}
+                )
+                .await,
+            "Request body was too large to be recorded"
+        );
+    }
+
+    /// Replaying a request uses its recipe's client certificate, so errors
+    /// loading the certificate fail the download
+    #[rstest]
+    #[tokio::test]
+    async fn test_download_certificate(http_engine: &HttpEngine) {
+        let recipe = Recipe {
+            certificate: Some(ClientCertificate::Pem {
+                certificate: "not-real.pem".into(),
+                key: None,
+            }),
+            ..Recipe::factory(())
+        };
+        let request = RequestRecord {
+            recipe_id: recipe.id.clone(),
+            ..RequestRecord::factory(())
+        };
+        assert_err!(
+            http_engine
+                .download(
+                    &request,
+                    &template_context([recipe], []),
+                    &mut Vec::new(),
+                    |_| {}
+                )
+                .await,
+            "Error loading client certificate"
+        );
+    }
+
+    /// Cancelling a request drops it, whether it's still building or waiting
+    /// for a response, and records it as a cancelled failure
+    #[rstest]
+    #[tokio::test]
+    async fn test_cancel(http_engine: &HttpEngine) {
+        let server = MockServer::start().await;
+        let host = server.uri();
+        Mock::given(matchers::method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200).set_delay(Duration::from_secs(10)),
+            )
+            .mount(&server)
+            .await;
+
+        let recipe = Recipe {
+            url: format!("{host}/get").as_str().into(),
+            ..Recipe::factory(())
+        };
+        let recipe_id = recipe.id.clone();
+        let template_context = template_context([recipe], []);
+
+        // Cancelled before the build
+        let seed = RequestSeed::new(recipe_id.clone(), BuildOptions::default());
+        seed.cancel.cancel();
+        let error = http_engine
+            .build(seed, &template_context)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), FailureKind::Cancelled);
+
+        // Cancelled while waiting for the response
+        let seed = RequestSeed::new(recipe_id.clone(), BuildOptions::default());
+        let cancel = seed.cancel.clone();
+        let ticket = http_engine.build(seed, &template_context).await.unwrap();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            cancel.cancel();
+        });
+        let error = ticket.send(&template_context.database).await.unwrap_err();
+        assert_eq!(error.kind(), FailureKind::Cancelled);
+        let failures = template_context
+            .database
+            .get_failures(
+                error.request.profile_id.as_ref(),
+                &recipe_id,
+                Some(FailureKind::Cancelled),
+            )
+            .unwrap();
+        assert_eq!(failures.len(), 1);
+    }
+
     /// Leading/trailing newlines should be stripped from rendered header
     /// values. These characters are invalid and trigger an error, so we assume
     /// they're unintentional and the user won't miss them.
@@ -1381,7 +3410,11 @@ mod tests {
         };
         let template_context = template_context([], []);
         let rendered = recipe
-            .render_headers(&BuildOptions::default(), &template_context)
+            .render_headers(
+                &BuildOptions::default(),
+                &RecipeDefaults::default(),
+                &template_context,
+            )
             .await
             .unwrap();
 
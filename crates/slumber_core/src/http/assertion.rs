@@ -0,0 +1,361 @@
+//! Check responses against the assertions declared on their recipe
+
+use crate::{
+    collection::Assertion,
+    http::{query::Query, Exchange},
+};
+use derive_more::Display;
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::{
+    fmt::{self, Formatter},
+    time::Duration,
+};
+
+/// Why a response failed an assertion
+#[derive(Clone, Debug, Display, PartialEq)]
+pub enum AssertionFailure {
+    #[display("Status: expected {expected}, got {actual}")]
+    Status { expected: u16, actual: StatusCode },
+    #[display("Header `{name}` is missing")]
+    MissingHeader { name: String },
+    #[display("Header `{name}`: `{value}` does not match `{pattern}`")]
+    HeaderMismatch {
+        name: String,
+        value: String,
+        pattern: String,
+    },
+    #[display("Body is not valid JSON")]
+    NotJson,
+    #[display("`{query}` matched nothing")]
+    NoMatch { query: Query },
+    #[display("`{query}`: expected {expected}, got {actual}")]
+    NotEqual {
+        query: Query,
+        expected: Value,
+        actual: Value,
+    },
+    #[display("`{query}`: expected to contain {expected}, got {actual}")]
+    NotContains {
+        query: Query,
+        expected: Value,
+        actual: Value,
+    },
+    #[display(
+        "Latency: expected under {}, took {}",
+        DisplayDuration(*budget),
+        DisplayDuration(*actual)
+    )]
+    Latency { budget: Duration, actual: Duration },
+}
+
+impl Assertion {
+    /// Check a request/response against this assertion
+    pub fn check(&self, exchange: &Exchange) -> Result<(), AssertionFailure> {
+        let response = &exchange.response;
+        match self {
+            Self::Status(expected) => {
+                if response.status.as_u16() == *expected {
+                    Ok(())
+                } else {
+                    Err(AssertionFailure::Status {
+                        expected: *expected,
+                        actual: response.status,
+                    })
+                }
+            }
+            Self::Header { name, matches } => {
+                let value =
+                    response.headers.get(name.as_str()).ok_or_else(|| {
+                        AssertionFailure::MissingHeader { name: name.clone() }
+                    })?;
+                match matches {
+                    Some(pattern) if !pattern.is_match(value.as_bytes()) => {
+                        Err(AssertionFailure::HeaderMismatch {
+                            name: name.clone(),
+                            value: String::from_utf8_lossy(value.as_bytes())
+                                .into_owned(),
+                            pattern: pattern.to_string(),
+                        })
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Self::JsonPath {
+                query,
+                equals,
+                contains,
+            } => {
+                let body: Value = serde_json::from_slice(response.body.bytes())
+                    .map_err(|_| AssertionFailure::NotJson)?;
+                let actual = match query.query_json(&body).as_slice() {
+                    [] => {
+                        return Err(AssertionFailure::NoMatch {
+                            query: query.clone(),
+                        })
+                    }
+                    [value] => (*value).clone(),
+                    values => values.iter().copied().cloned().collect(),
+                };
+
+                if let Some(expected) = equals {
+                    if &actual != expected {
+                        return Err(AssertionFailure::NotEqual {
+                            query: query.clone(),
+                            expected: expected.clone(),
+                            actual,
+                        });
+                    }
+                }
+                if let Some(expected) = contains {
+                    if !value_contains(&actual, expected) {
+                        return Err(AssertionFailure::NotContains {
+                            query: query.clone(),
+                            expected: expected.clone(),
+                            actual,
+                        });
+                    }
+                }
+                Ok(())
+            }
+            Self::Latency(budget) => {
+                // Duration can only be negative if the clock went backwards
+                let actual = exchange.duration().to_std().unwrap_or_default();
+                if actual <= *budget {
+                    Ok(())
+                } else {
+                    Err(AssertionFailure::Latency {
+                        budget: *budget,
+                        actual,
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Status(status) => write!(f, "status {status}"),
+            Self::Header {
+                name,
+                matches: None,
+            } => write!(f, "header `{name}` exists"),
+            Self::Header {
+                name,
+                matches: Some(pattern),
+            } => write!(f, "header `{name}` matches `{pattern}`"),
+            Self::JsonPath {
+                query,
+                equals,
+                contains,
+            } => {
+                write!(f, "`{query}`")?;
+                match (equals, contains) {
+                    (None, None) => write!(f, " exists"),
+                    (Some(equals), None) => write!(f, " equals {equals}"),
+                    (None, Some(contains)) => write!(f, " contains {contains}"),
+                    (Some(equals), Some(contains)) => {
+                        write!(f, " equals {equals} and contains {contains}")
+                    }
+                }
+            }
+            Self::Latency(budget) => {
+                write!(f, "latency under {}", DisplayDuration(*budget))
+            }
+        }
+    }
+}
+
+/// Does a JSON value contain another? Strings contain substrings, and arrays
+/// contain elements. Nothing else contains anything.
+fn value_contains(value: &Value, expected: &Value) -> bool {
+    match (value, expected) {
+        (Value::String(value), Value::String(expected)) => {
+            value.contains(expected.as_str())
+        }
+        (Value::Array(values), _) => values.contains(expected),
+        _ => false,
+    }
+}
+
+/// Display a duration in milliseconds, or seconds if it's an even number of
+/// them
+struct DisplayDuration(Duration);
+
+impl fmt::Display for DisplayDuration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.0.subsec_millis() == 0 {
+            write!(f, "{}s", self.0.as_secs())
+        } else {
+            write!(f, "{}ms", self.0.as_millis())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::{RequestRecord, ResponseRecord},
+        test_util::{header_map, Factory},
+    };
+    use rstest::{fixture, rstest};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[fixture]
+    fn exchange() -> Exchange {
+        let response = ResponseRecord {
+            status: StatusCode::OK,
+            headers: header_map([("content-type", "application/json")]),
+            body: json!({
+                "id": 3,
+                "name": "Frodo Baggins",
+                "tags": ["hobbit", "ringbearer"],
+                "items": [{"id": 1}, {"id": 2}],
+            })
+            .to_string()
+            .into_bytes()
+            .into(),
+        };
+        let exchange =
+            Exchange::factory((RequestRecord::factory(()), response));
+        Exchange {
+            end_time: exchange.start_time + chrono::Duration::milliseconds(250),
+            ..exchange
+        }
+    }
+
+    #[rstest]
+    #[case::status(Assertion::Status(200))]
+    #[case::header_exists(Assertion::Header {
+        name: "Content-Type".into(),
+        matches: None,
+    })]
+    #[case::header_matches(Assertion::Header {
+        name: "content-type".into(),
+        matches: Some("^application/json$".parse().unwrap()),
+    })]
+    #[case::json_path_exists(json_path("$.name", None, None))]
+    #[case::json_path_equals(json_path("$.id", Some(json!(3)), None))]
+    #[case::json_path_equals_multiple(json_path(
+        "$.items[*].id",
+        Some(json!([1, 2])),
+        None
+    ))]
+    #[case::json_path_contains_substring(json_path(
+        "$.name",
+        None,
+        Some(json!("Baggins"))
+    ))]
+    #[case::json_path_contains_element(json_path(
+        "$.tags",
+        None,
+        Some(json!("hobbit"))
+    ))]
+    #[case::latency(Assertion::Latency(Duration::from_millis(500)))]
+    fn test_check_pass(exchange: Exchange, #[case] assertion: Assertion) {
+        assert_eq!(assertion.check(&exchange), Ok(()));
+    }
+
+    #[rstest]
+    #[case::status(Assertion::Status(201), "Status: expected 201, got 200 OK")]
+    #[case::header_missing(
+        Assertion::Header {
+            name: "x-request-id".into(),
+            matches: None,
+        },
+        "Header `x-request-id` is missing"
+    )]
+    #[case::header_mismatch(
+        Assertion::Header {
+            name: "content-type".into(),
+            matches: Some("^text/".parse().unwrap()),
+        },
+        "Header `content-type`: `application/json` does not match `^text/`"
+    )]
+    #[case::json_path_no_match(
+        json_path("$.missing", None, None),
+        "`$.missing` matched nothing"
+    )]
+    #[case::json_path_not_equal(
+        json_path("$.name", Some(json!("Samwise")), None),
+        "`$.name`: expected \"Samwise\", got \"Frodo Baggins\""
+    )]
+    #[case::json_path_not_contains(
+        json_path("$.tags", None, Some(json!("wizard"))),
+        "`$.tags`: expected to contain \"wizard\", got [\"hobbit\",\"ringbearer\"]"
+    )]
+    #[case::json_path_contains_wrong_type(
+        json_path("$.id", None, Some(json!(3))),
+        "`$.id`: expected to contain 3, got 3"
+    )]
+    #[case::latency(
+        Assertion::Latency(Duration::from_millis(100)),
+        "Latency: expected under 100ms, took 250ms"
+    )]
+    fn test_check_fail(
+        exchange: Exchange,
+        #[case] assertion: Assertion,
+        #[case] expected: &str,
+    ) {
+        let failure = assertion.check(&exchange).unwrap_err();
+        assert_eq!(failure.to_string(), expected);
+    }
+
+    /// Non-JSON bodies fail any JSONPath assertion
+    #[rstest]
+    fn test_check_not_json(exchange: Exchange) {
+        let exchange = Exchange {
+            response: Arc::new(ResponseRecord {
+                body: b"not json".to_vec().into(),
+                ..ResponseRecord::factory(())
+            }),
+            ..exchange
+        };
+        assert_eq!(
+            json_path("$.id", None, None).check(&exchange),
+            Err(AssertionFailure::NotJson)
+        );
+    }
+
+    #[rstest]
+    #[case::status(Assertion::Status(200), "status 200")]
+    #[case::header(
+        Assertion::Header {
+            name: "content-type".into(),
+            matches: Some("json".parse().unwrap()),
+        },
+        "header `content-type` matches `json`"
+    )]
+    #[case::json_path_exists(json_path("$.id", None, None), "`$.id` exists")]
+    #[case::json_path_equals(
+        json_path("$.id", Some(json!(3)), None),
+        "`$.id` equals 3"
+    )]
+    #[case::latency_ms(
+        Assertion::Latency(Duration::from_millis(500)),
+        "latency under 500ms"
+    )]
+    #[case::latency_s(
+        Assertion::Latency(Duration::from_secs(2)),
+        "latency under 2s"
+    )]
+    fn test_display(#[case] assertion: Assertion, #[case] expected: &str) {
+        assert_eq!(assertion.to_string(), expected);
+    }
+
+    fn json_path(
+        query: &str,
+        equals: Option<Value>,
+        contains: Option<Value>,
+    ) -> Assertion {
+        Assertion::JsonPath {
+            query: query.parse().unwrap(),
+            equals,
+            contains,
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Compare responses against a pinned baseline ("golden") response, to catch
+//! unexpected changes in an API's behavior
+
+use crate::http::{query::Query, ResponseRecord};
+use derive_more::Display;
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::fmt::{self, Formatter};
+
+/// A single way in which a response differs from its baseline. Headers are not
+/// compared, because they tend to contain volatile values like dates.
+#[derive(Clone, Debug, Display, PartialEq)]
+pub enum Difference {
+    #[display("Status: expected {expected}, got {actual}")]
+    Status {
+        expected: StatusCode,
+        actual: StatusCode,
+    },
+    /// A value within a JSON body differs
+    #[display(
+        "`{path}`: expected {}, got {}",
+        DisplayValue(expected.as_ref()),
+        DisplayValue(actual.as_ref())
+    )]
+    Value {
+        /// JSONPath to the value
+        path: String,
+        /// `None` if the value is missing from the baseline
+        expected: Option<Value>,
+        /// `None` if the value is missing from the response
+        actual: Option<Value>,
+    },
+    /// Non-JSON bodies are compared byte-for-byte, so we can't say *how* they
+    /// differ
+    #[display("Body differs")]
+    Body,
+}
+
+/// Compare a response to a baseline. JSON bodies are compared structurally,
+/// with every value matched by an `ignore` query excluded from both sides.
+/// Other bodies must match exactly. Return an empty list if the two match.
+pub fn compare(
+    baseline: &ResponseRecord,
+    response: &ResponseRecord,
+    ignore: &[Query],
+) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    if baseline.status != response.status {
+        differences.push(Difference::Status {
+            expected: baseline.status,
+            actual: response.status,
+        });
+    }
+
+    let parse = |response: &ResponseRecord| {
+        let mut value: Value =
+            serde_json::from_slice(response.body.bytes()).ok()?;
+        for query in ignore {
+            query.remove(&mut value);
+        }
+        Some(value)
+    };
+    match (parse(baseline), parse(response)) {
+        (Some(expected), Some(actual)) => {
+            compare_values("$".into(), &expected, &actual, &mut differences)
+        }
+        _ if baseline.body.bytes() != response.body.bytes() => {
+            differences.push(Difference::Body)
+        }
+        _ => {}
+    }
+    differences
+}
+
+/// Recursively compare two JSON values, adding any differences to the list
+fn compare_values(
+    path: String,
+    expected: &Value,
+    actual: &Value,
+    differences: &mut Vec<Difference>,
+) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            // Check keys in the baseline first, then any new keys
+            let keys = expected.keys().chain(
+                actual.keys().filter(|key| !expected.contains_key(*key)),
+            );
+            for key in keys {
+                let path = object_path(&path, key);
+                match (expected.get(key), actual.get(key)) {
+                    (Some(expected), Some(actual)) => {
+                        compare_values(path, expected, actual, differences)
+                    }
+                    (expected, actual) => differences.push(Difference::Value {
+                        path,
+                        expected: expected.cloned(),
+                        actual: actual.cloned(),
+                    }),
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            for i in 0..expected.len().max(actual.len()) {
+                let path = format!("{path}[{i}]");
+                match (expected.get(i), actual.get(i)) {
+                    (Some(expected), Some(actual)) => {
+                        compare_values(path, expected, actual, differences)
+                    }
+                    (expected, actual) => differences.push(Difference::Value {
+                        path,
+                        expected: expected.cloned(),
+                        actual: actual.cloned(),
+                    }),
+                }
+            }
+        }
+        _ if expected != actual => differences.push(Difference::Value {
+            path,
+            expected: Some(expected.clone()),
+            actual: Some(actual.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// Append an object key to a JSONPath, using bracket notation if the key
+/// isn't a simple identifier
+pub(super) fn object_path(parent: &str, key: &str) -> String {
+    if !key.is_empty()
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        format!("{parent}.{key}")
+    } else {
+        format!("{parent}['{}']", key.replace('\'', "\\'"))
+    }
+}
+
+/// Display an optional JSON value, for [Difference]
+struct DisplayValue<'a>(Option<&'a Value>);
+
+impl<'a> fmt::Display for DisplayValue<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(value) => write!(f, "{value}"),
+            None => write!(f, "nothing"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Factory;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn response(status: StatusCode, body: &str) -> ResponseRecord {
+        ResponseRecord {
+            status,
+            body: body.to_owned().into_bytes().into(),
+            ..ResponseRecord::factory(())
+        }
+    }
+
+    #[rstest]
+    #[case::equal(
+        json!({"id": 1, "items": [1, 2]}),
+        json!({"id": 1, "items": [1, 2]}),
+        &[],
+        vec![],
+    )]
+    #[case::changed(
+        json!({"id": 1, "user": {"name": "Bob"}}),
+        json!({"id": 1, "user": {"name": "Alice"}}),
+        &[],
+        vec![Difference::Value {
+            path: "$.user.name".into(),
+            expected: Some(json!("Bob")),
+            actual: Some(json!("Alice")),
+        }],
+    )]
+    #[case::added_removed(
+        json!({"a": 1, "items": [1, 2]}),
+        json!({"b": 2, "items": [1]}),
+        &[],
+        vec![
+            Difference::Value {
+                path: "$.a".into(),
+                expected: Some(json!(1)),
+                actual: None,
+            },
+            Difference::Value {
+                path: "$.items[1]".into(),
+                expected: Some(json!(2)),
+                actual: None,
+            },
+            Difference::Value {
+                path: "$.b".into(),
+                expected: None,
+                actual: Some(json!(2)),
+            },
+        ],
+    )]
+    #[case::ignored(
+        json!({"id": 1, "created_at": "2024-01-01", "items": [{"id": 3}]}),
+        json!({"id": 2, "created_at": "2024-09-01", "items": [{"id": 4}]}),
+        &["$.id", "$.created_at", "$.items[*].id"],
+        vec![],
+    )]
+    #[case::special_key(
+        json!({"user-name": "Bob"}),
+        json!({"user-name": "Alice"}),
+        &[],
+        vec![Difference::Value {
+            path: "$['user-name']".into(),
+            expected: Some(json!("Bob")),
+            actual: Some(json!("Alice")),
+        }],
+    )]
+    fn test_compare_json(
+        #[case] baseline: Value,
+        #[case] current: Value,
+        #[case] ignore: &[&str],
+        #[case] expected: Vec<Difference>,
+    ) {
+        let ignore: Vec<Query> =
+            ignore.iter().map(|query| query.parse().unwrap()).collect();
+        let differences = compare(
+            &response(StatusCode::OK, &baseline.to_string()),
+            &response(StatusCode::OK, &current.to_string()),
+            &ignore,
+        );
+        assert_eq!(differences, expected);
+    }
+
+    #[rstest]
+    #[case::equal(StatusCode::OK, "hello", vec![])]
+    #[case::body(StatusCode::OK, "goodbye", vec![Difference::Body])]
+    #[case::status(
+        StatusCode::NOT_FOUND,
+        "hello",
+        vec![Difference::Status {
+            expected: StatusCode::OK,
+            actual: StatusCode::NOT_FOUND,
+        }],
+    )]
+    fn test_compare_text(
+        #[case] status: StatusCode,
+        #[case] body: &str,
+        #[case] expected: Vec<Difference>,
+    ) {
+        let differences = compare(
+            &response(StatusCode::OK, "hello"),
+            &response(status, body),
+            &[],
+        );
+        assert_eq!(differences, expected);
+    }
+
+    #[test]
+    fn test_display() {
+        let difference = Difference::Value {
+            path: "$.a".into(),
+            expected: Some(json!("x")),
+            actual: None,
+        };
+        assert_eq!(
+            difference.to_string(),
+            "`$.a`: expected \"x\", got nothing"
+        );
+    }
+}
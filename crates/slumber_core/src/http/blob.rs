@@ -0,0 +1,192 @@
+//! Storage for response bodies that are too large to hold in memory or store
+//! inline in the database. Each body is written to its own file, named by the
+//! SHA-256 hash of its content, and history keeps a [BlobRef] to it.
+
+use crate::util::{hex, DataDirectory};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+use tokio::{fs, io::AsyncWriteExt};
+use uuid::Uuid;
+
+/// Reference to a body in the [BlobStore]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlobRef {
+    /// Hex-encoded SHA-256 hash of the content, which is also its file name
+    pub hash: String,
+    /// Size of the content, in bytes
+    pub size: u64,
+}
+
+/// A directory of response bodies. Blobs are addressed by their content, so
+/// identical bodies share a file.
+#[derive(Clone, Debug)]
+pub struct BlobStore {
+    directory: PathBuf,
+}
+
+impl BlobStore {
+    const DIRECTORY: &'static str = "blobs";
+
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    /// The store in the data directory, which is shared by all collections
+    pub fn global() -> Self {
+        Self::new(DataDirectory::get().file(Self::DIRECTORY))
+    }
+
+    /// Path to the file holding a blob's content
+    pub fn path(&self, blob: &BlobRef) -> PathBuf {
+        self.directory.join(&blob.hash)
+    }
+
+    /// Start writing a new blob. Content is written to a temporary file, and
+    /// only moved to its final location once it's complete
+    pub async fn writer(&self) -> anyhow::Result<BlobWriter> {
+        fs::create_dir_all(&self.directory).await.with_context(|| {
+            format!("Error creating blob directory {:?}", self.directory)
+        })?;
+        let temp_path = self.directory.join(format!("{}.tmp", Uuid::new_v4()));
+        let file = fs::File::create(&temp_path)
+            .await
+            .with_context(|| format!("Error creating blob {temp_path:?}"))?;
+        Ok(BlobWriter {
+            directory: self.directory.clone(),
+            temp_path,
+            file,
+            hasher: Sha256::new(),
+            size: 0,
+        })
+    }
+
+    /// Open a blob for reading, e.g. to copy it somewhere without loading it
+    /// into memory
+    pub fn open(&self, blob: &BlobRef) -> anyhow::Result<File> {
+        let path = self.path(blob);
+        File::open(&path)
+            .with_context(|| format!("Error opening blob {path:?}"))
+    }
+
+    /// Read up to `length` bytes of a blob, starting at `offset`. Returns
+    /// fewer bytes if the blob ends first
+    pub fn read_range(
+        &self,
+        blob: &BlobRef,
+        offset: u64,
+        length: usize,
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut file = self.open(blob)?;
+        let mut buffer = Vec::with_capacity(length);
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.take(length as u64).read_to_end(&mut buffer))
+            .with_context(|| {
+                format!("Error reading blob {:?}", self.path(blob))
+            })?;
+        Ok(buffer)
+    }
+
+    /// Read the entire content of a blob into memory
+    pub fn read(&self, blob: &BlobRef) -> anyhow::Result<Vec<u8>> {
+        let path = self.path(blob);
+        std::fs::read(&path)
+            .with_context(|| format!("Error reading blob {path:?}"))
+    }
+}
+
+/// Write a blob's content incrementally. Call [Self::finish] once all content
+/// is written. If the writer is dropped before then, the partial content is
+/// deleted.
+#[derive(Debug)]
+pub struct BlobWriter {
+    directory: PathBuf,
+    temp_path: PathBuf,
+    file: fs::File,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl BlobWriter {
+    /// Append content to the blob
+    pub async fn write(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        self.hasher.update(data);
+        self.size += data.len() as u64;
+        self.file
+            .write_all(data)
+            .await
+            .with_context(|| format!("Error writing blob {:?}", self.temp_path))
+    }
+
+    /// Move the blob to its permanent location, and return a reference to it
+    pub async fn finish(mut self) -> anyhow::Result<BlobRef> {
+        self.file.flush().await.with_context(|| {
+            format!("Error writing blob {:?}", self.temp_path)
+        })?;
+        let blob = BlobRef {
+            hash: hex(&self.hasher.clone().finalize()),
+            size: self.size,
+        };
+        let path = self.directory.join(&blob.hash);
+        fs::rename(&self.temp_path, &path)
+            .await
+            .with_context(|| format!("Error saving blob {path:?}"))?;
+        Ok(blob)
+    }
+}
+
+impl Drop for BlobWriter {
+    fn drop(&mut self) {
+        // If the blob was finished, the file has already been moved and this
+        // does nothing
+        let _ = std::fs::remove_file(&self.temp_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_dir, TempDir};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_write_read(temp_dir: TempDir) {
+        let store = BlobStore::new(temp_dir.join("blobs"));
+        let mut writer = store.writer().await.unwrap();
+        writer.write(b"hello ").await.unwrap();
+        writer.write(b"world").await.unwrap();
+        let blob = writer.finish().await.unwrap();
+
+        assert_eq!(
+            blob,
+            BlobRef {
+                hash: "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9".into(),
+                size: 11,
+            }
+        );
+        // Only the finished blob is left behind
+        assert_eq!(std::fs::read_dir(&store.directory).unwrap().count(), 1);
+        assert_eq!(store.read(&blob).unwrap(), b"hello world");
+        assert_eq!(store.read_range(&blob, 6, 3).unwrap(), b"wor");
+        // Reading past the end returns whatever is left
+        assert_eq!(store.read_range(&blob, 9, 10).unwrap(), b"ld");
+    }
+
+    /// An unfinished blob is cleaned up
+    #[rstest]
+    #[tokio::test]
+    async fn test_abandon(temp_dir: TempDir) {
+        let store = BlobStore::new(temp_dir.join("blobs"));
+        let mut writer = store.writer().await.unwrap();
+        writer.write(b"hello").await.unwrap();
+        drop(writer);
+        assert_eq!(std::fs::read_dir(&store.directory).unwrap().count(), 0);
+    }
+}
@@ -1,6 +1,11 @@
 //! Serialization/deserialization for HTTP-releated types
 
-use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use crate::http::query::{RegexSelector, Selector};
+use serde::{
+    de::{self, EnumAccess, Error as _, VariantAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::fmt;
 
 /// Serialization/deserialization for [reqwest::Method]
 pub mod serde_method {
@@ -90,3 +95,176 @@ pub mod serde_status_code {
             .map_err(de::Error::custom)
     }
 }
+
+impl Selector {
+    // Constants for serialize/deserialization. Typically these are generated
+    // by macros, but we need custom implementation
+    const ENUM_NAME: &'static str = "Selector";
+    const VARIANT_REGEX: &'static str = "regex";
+    const ALL_VARIANTS: &'static [&'static str] = &[Self::VARIANT_REGEX];
+}
+
+/// Custom serialization for Selector, so JSONPath and XPath serialize as plain
+/// strings while regex gets a tag
+impl Serialize for Selector {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Selector::JsonPath(query) => serializer.collect_str(query),
+            Selector::XPath(xpath) => serializer.collect_str(xpath),
+            Selector::Regex(regex) => serializer.serialize_newtype_variant(
+                Self::ENUM_NAME,
+                2,
+                Self::VARIANT_REGEX,
+                regex,
+            ),
+        }
+    }
+}
+
+// Custom deserialization for Selector, to support both plain strings and tags
+impl<'de> Deserialize<'de> for Selector {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SelectorVisitor;
+
+        impl<'de> Visitor<'de> for SelectorVisitor {
+            type Value = Selector;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("JSONPath/XPath string or tag !regex")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+            where
+                A: EnumAccess<'de>,
+            {
+                let (tag, value) = data.variant::<String>()?;
+                match tag.as_str() {
+                    Selector::VARIANT_REGEX => {
+                        let regex: RegexSelector = value.newtype_variant()?;
+                        regex.validate().map_err(A::Error::custom)?;
+                        Ok(Selector::Regex(regex))
+                    }
+                    other => Err(A::Error::unknown_variant(
+                        other,
+                        Selector::ALL_VARIANTS,
+                    )),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(SelectorVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_err,
+        http::query::{CaptureGroup, Pattern},
+    };
+    use rstest::rstest;
+    use serde_yaml::value::{Tag, TaggedValue};
+
+    /// Test serialization/deserialization of selectors in YAML
+    #[rstest]
+    #[case::json_path("$.data".into(), "$.data".parse().unwrap())]
+    #[case::xpath("//user/@id".into(), "//user/@id".parse().unwrap())]
+    #[case::regex(
+        regex_yaml(serde_yaml::from_str("pattern: 'token=(\\w+)'").unwrap()),
+        Selector::Regex(RegexSelector {
+            pattern: r"token=(\w+)".parse::<Pattern>().unwrap(),
+            group: None,
+        }),
+    )]
+    #[case::regex_group(
+        regex_yaml(
+            serde_yaml::from_str("{pattern: '(?<key>\\w+)=(\\w+)', group: 2}")
+                .unwrap()
+        ),
+        Selector::Regex(RegexSelector {
+            pattern: r"(?<key>\w+)=(\w+)".parse::<Pattern>().unwrap(),
+            group: Some(CaptureGroup::Index(2)),
+        }),
+    )]
+    #[case::regex_named_group(
+        regex_yaml(
+            serde_yaml::from_str("{pattern: '(?<key>\\w+)=(\\w+)', group: key}")
+                .unwrap()
+        ),
+        Selector::Regex(RegexSelector {
+            pattern: r"(?<key>\w+)=(\w+)".parse::<Pattern>().unwrap(),
+            group: Some(CaptureGroup::Name("key".into())),
+        }),
+    )]
+    fn test_serde_selector(
+        #[case] yaml: serde_yaml::Value,
+        #[case] selector: Selector,
+    ) {
+        assert_eq!(
+            serde_yaml::to_value(&selector).unwrap(),
+            yaml,
+            "Serialization mismatch"
+        );
+        assert_eq!(
+            serde_yaml::from_value::<Selector>(yaml).unwrap(),
+            selector,
+            "Deserialization mismatch"
+        );
+    }
+
+    /// Test errors when deserializing a selector
+    #[rstest]
+    #[case::unknown_tag(
+        serde_yaml::Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("glob"),
+            value: "*".into(),
+        })),
+        "unknown variant `glob`, expected `regex`"
+    )]
+    #[case::invalid_pattern(
+        regex_yaml(serde_yaml::from_str("pattern: '(unclosed'").unwrap()),
+        "unclosed group"
+    )]
+    #[case::unknown_field(
+        regex_yaml(serde_yaml::from_str("{pattern: a, grup: 1}").unwrap()),
+        "unknown field `grup`"
+    )]
+    #[case::unknown_group_index(
+        regex_yaml(serde_yaml::from_str("{pattern: '(a)', group: 2}").unwrap()),
+        "Pattern `(a)` has no capture group `2`"
+    )]
+    #[case::unknown_group_name(
+        regex_yaml(
+            serde_yaml::from_str("{pattern: '(?<a>a)', group: b}").unwrap()
+        ),
+        "Pattern `(?<a>a)` has no capture group `b`"
+    )]
+    fn test_deserialize_selector_error(
+        #[case] yaml: serde_yaml::Value,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(serde_yaml::from_value::<Selector>(yaml), expected_error);
+    }
+
+    /// Wrap a value in a `!regex` tag
+    fn regex_yaml(value: serde_yaml::Value) -> serde_yaml::Value {
+        serde_yaml::Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("regex"),
+            value,
+        }))
+    }
+}
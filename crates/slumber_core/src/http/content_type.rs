@@ -10,6 +10,7 @@ use anyhow::{anyhow, Context};
 use derive_more::{Deref, Display, From};
 use mime::{Mime, APPLICATION, JSON};
 use reqwest::header::{self, HeaderMap, HeaderValue};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{borrow::Cow, ffi::OsStr, fmt::Debug, path::Path};
 
@@ -24,7 +25,9 @@ use std::{borrow::Cow, ffi::OsStr, fmt::Debug, path::Path};
 ///
 /// For the serialization string, obviously use serde. For the others, use
 /// the corresponding methods/associated functions.
-#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[derive(
+    Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, JsonSchema,
+)]
 #[serde(rename_all = "snake_case")]
 pub enum ContentType {
     Json,
@@ -37,7 +40,7 @@ impl ContentType {
 
     /// Parse the value of the content-type header and map it to a known content
     /// type
-    fn from_mime(mime_type: &str) -> anyhow::Result<Self> {
+    pub(crate) fn from_mime(mime_type: &str) -> anyhow::Result<Self> {
         let mime_type: Mime = mime_type
             .parse()
             .with_context(|| format!("Invalid content type `{mime_type}`"))?;
@@ -0,0 +1,851 @@
+//! Validate responses against an OpenAPI spec ("contract testing"), to catch
+//! drift between the spec and the server
+
+use crate::{
+    collection::openapi::resolve::{
+        ComponentKind, ReferenceResolver, ResolveError,
+    },
+    http::{
+        baseline::object_path, content_type::ContentType, Exchange,
+        ResponseRecord,
+    },
+};
+use anyhow::Context;
+use derive_more::Display;
+use indexmap::IndexMap;
+use mime::Mime;
+use openapiv3::{
+    AdditionalProperties, Header, MediaType, OpenAPI, Operation, Paths,
+    ReferenceOr, Response, Responses, Schema, SchemaKind, Server, Type,
+};
+use reqwest::{header, StatusCode};
+use serde_json::{Map, Value};
+use std::{borrow::Borrow, fs::File, path::Path};
+use tracing::info;
+
+/// An OpenAPI spec, loaded for validating responses
+pub struct Contract {
+    paths: Paths,
+    /// Top-level servers, which define the base path that every request path
+    /// starts with. Can be overridden per path or operation
+    servers: Vec<Server>,
+    resolver: ReferenceResolver,
+}
+
+/// A single way in which an exchange deviates from the spec
+#[derive(Clone, Debug, Display, PartialEq)]
+pub enum Violation {
+    /// No operation in the spec matches the request's method and path
+    #[display("No operation in the spec matches `{operation}`")]
+    UnknownOperation { operation: String },
+    /// The response status isn't documented for the operation
+    #[display("Status {status} is not documented for `{operation}`")]
+    Status {
+        operation: String,
+        status: StatusCode,
+    },
+    /// A header marked `required` in the spec is missing from the response
+    #[display("Missing required header `{header}`")]
+    MissingHeader { header: String },
+    /// The response's content type isn't listed for its status
+    #[display("Content type `{content_type}` is not documented")]
+    ContentType { content_type: String },
+    /// A value in the body doesn't match the schema
+    #[display("`{path}`: {message}")]
+    Body {
+        /// JSONPath to the offending value
+        path: String,
+        message: String,
+    },
+    /// The spec itself is broken, e.g. a `$ref` to a missing component
+    #[display("Invalid spec: {_0}")]
+    InvalidSpec(ResolveError),
+}
+
+impl Contract {
+    /// Load a spec from a YAML or JSON file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        info!(file = ?path, "Loading OpenAPI spec");
+        let file = File::open(path)
+            .with_context(|| format!("Error opening OpenAPI spec {path:?}"))?;
+        // The format can be YAML or JSON, so we can just treat it all as YAML
+        let spec: OpenAPI =
+            serde_yaml::from_reader(file).with_context(|| {
+                format!("Error deserializing OpenAPI spec {path:?}")
+            })?;
+        Ok(spec.into())
+    }
+
+    /// Validate an exchange against the spec. The request is matched to an
+    /// operation by method and path, then the response's status, required
+    /// headers, content type, and JSON body are checked against that
+    /// operation's definition. Return an empty list if everything matches.
+    pub fn validate(&self, exchange: &Exchange) -> Vec<Violation> {
+        let request = &exchange.request;
+        let method = request.method.to_string();
+        let Some((path, operation)) =
+            self.find_operation(&method, request.url.path())
+        else {
+            return vec![Violation::UnknownOperation {
+                operation: format!("{method} {}", request.url.path()),
+            }];
+        };
+
+        let response = &exchange.response;
+        let Some(expected) =
+            find_response(&operation.responses, response.status)
+        else {
+            return vec![Violation::Status {
+                operation: format!("{method} {path}"),
+                status: response.status,
+            }];
+        };
+        let mut violations = Vec::new();
+        if let Some(expected) = self.resolve(expected, &mut violations) {
+            self.validate_response(expected, response, &mut violations);
+        }
+        violations
+    }
+
+    /// Find the operation that best matches a request. The request path must
+    /// be a server's base path followed by the operation's path template. If
+    /// multiple paths match, prefer the one with the most literal
+    /// (non-parameter) segments, so `/users/me` beats `/users/{id}`.
+    fn find_operation(
+        &self,
+        method: &str,
+        path: &str,
+    ) -> Option<(&str, &Operation)> {
+        let segments: Vec<&str> =
+            path.split('/').filter(|s| !s.is_empty()).collect();
+        self.paths
+            .iter()
+            .filter_map(|(template, item)| {
+                // References to path items are rarely used, so we don't
+                // bother resolving them
+                let item = item.as_item()?;
+                let (_, operation) = item
+                    .iter()
+                    .find(|(m, _)| m.eq_ignore_ascii_case(method))?;
+                // The most specific list of servers applies
+                let servers = [&operation.servers, &item.servers]
+                    .into_iter()
+                    .find(|servers| !servers.is_empty())
+                    .unwrap_or(&self.servers);
+                let score = if servers.is_empty() {
+                    // Per the spec, the default server is `/`
+                    match_path(&[], template, &segments)
+                } else {
+                    servers
+                        .iter()
+                        .filter_map(|server| {
+                            let base = server_base_path(&server.url);
+                            match_path(&base, template, &segments)
+                        })
+                        .max()
+                }?;
+                Some((score, template.as_str(), operation))
+            })
+            .max_by_key(|(score, _, _)| *score)
+            .map(|(_, template, operation)| (template, operation))
+    }
+
+    fn validate_response(
+        &self,
+        expected: &Response,
+        response: &ResponseRecord,
+        violations: &mut Vec<Violation>,
+    ) {
+        for (name, header) in &expected.headers {
+            // The spec says Content-Type definitions should be ignored
+            if name.eq_ignore_ascii_case(header::CONTENT_TYPE.as_str()) {
+                continue;
+            }
+            let Some(header) = self.resolve::<Header, _>(header, violations)
+            else {
+                continue;
+            };
+            if header.required
+                && !response.headers.contains_key(name.to_lowercase().as_str())
+            {
+                violations.push(Violation::MissingHeader {
+                    header: name.clone(),
+                });
+            }
+        }
+
+        // If the spec doesn't describe the body, there's nothing to check
+        if expected.content.is_empty() || response.body.bytes().is_empty() {
+            return;
+        }
+        let Some(content_type) = response
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+        else {
+            violations.push(Violation::MissingHeader {
+                header: header::CONTENT_TYPE.to_string(),
+            });
+            return;
+        };
+        let Some(media_type) = find_media_type(&expected.content, content_type)
+        else {
+            violations.push(Violation::ContentType {
+                content_type: content_type.to_owned(),
+            });
+            return;
+        };
+
+        // We can only validate the schema of JSON bodies
+        let Some(schema) = &media_type.schema else {
+            return;
+        };
+        if !matches!(
+            ContentType::from_headers(&response.headers),
+            Ok(ContentType::Json)
+        ) {
+            return;
+        }
+        match serde_json::from_slice::<Value>(response.body.bytes()) {
+            Ok(value) => {
+                if let Some(schema) = self.resolve(schema, violations) {
+                    self.validate_value("$", schema, &value, violations);
+                }
+            }
+            Err(error) => violations.push(Violation::Body {
+                path: "$".into(),
+                message: format!("Invalid JSON: {error}"),
+            }),
+        }
+    }
+
+    /// Recursively validate a JSON value against a schema. This is a
+    /// structural check: types, nullability, required/extra properties, enums,
+    /// and composition (`allOf`/`anyOf`/`oneOf`/`not`). Constraints such as
+    /// `minimum` and `pattern` aren't checked.
+    fn validate_value(
+        &self,
+        path: &str,
+        schema: &Schema,
+        value: &Value,
+        violations: &mut Vec<Violation>,
+    ) {
+        if value.is_null() && schema.schema_data.nullable {
+            return;
+        }
+        let mismatch = |expected: &str| Violation::Body {
+            path: path.to_owned(),
+            message: format!("expected {expected}, got {}", value_type(value)),
+        };
+
+        match &schema.schema_kind {
+            SchemaKind::Type(Type::String(string)) => {
+                let Some(s) = value.as_str() else {
+                    violations.push(mismatch("string"));
+                    return;
+                };
+                if !string.enumeration.is_empty()
+                    && !string.enumeration.iter().flatten().any(|e| e == s)
+                {
+                    violations.push(Violation::Body {
+                        path: path.to_owned(),
+                        message: format!("{value} is not an allowed value"),
+                    });
+                }
+            }
+            SchemaKind::Type(Type::Number(_)) => {
+                if !value.is_number() {
+                    violations.push(mismatch("number"));
+                }
+            }
+            SchemaKind::Type(Type::Integer(_)) => {
+                if !value.is_i64() && !value.is_u64() {
+                    violations.push(mismatch("integer"));
+                }
+            }
+            SchemaKind::Type(Type::Boolean(_)) => {
+                if !value.is_boolean() {
+                    violations.push(mismatch("boolean"));
+                }
+            }
+            SchemaKind::Type(Type::Object(object)) => {
+                let Some(map) = value.as_object() else {
+                    violations.push(mismatch("object"));
+                    return;
+                };
+                self.validate_object(
+                    path,
+                    &object.properties,
+                    &object.required,
+                    object.additional_properties.as_ref(),
+                    map,
+                    violations,
+                );
+            }
+            SchemaKind::Type(Type::Array(array)) => {
+                let Some(items) = value.as_array() else {
+                    violations.push(mismatch("array"));
+                    return;
+                };
+                if let Some(schema) = &array.items {
+                    self.validate_items(path, schema, items, violations);
+                }
+            }
+            SchemaKind::AllOf { all_of } => {
+                self.validate_all_of(path, all_of, value, violations)
+            }
+            // We don't enforce that *exactly* one schema matches for oneOf,
+            // because overlapping variants are common in real specs
+            SchemaKind::AnyOf { any_of: schemas }
+            | SchemaKind::OneOf { one_of: schemas } => {
+                self.validate_any_of(path, schemas, value, violations)
+            }
+            SchemaKind::Not { not } => {
+                self.validate_not(path, not, value, violations)
+            }
+            // No explicit type, so check whatever the schema does define
+            SchemaKind::Any(any) => {
+                if let Some(map) = value.as_object() {
+                    self.validate_object(
+                        path,
+                        &any.properties,
+                        &any.required,
+                        any.additional_properties.as_ref(),
+                        map,
+                        violations,
+                    );
+                }
+                if let (Some(items), Some(schema)) =
+                    (value.as_array(), &any.items)
+                {
+                    self.validate_items(path, schema, items, violations);
+                }
+                self.validate_all_of(path, &any.all_of, value, violations);
+                if !any.any_of.is_empty() || !any.one_of.is_empty() {
+                    let schemas = any.any_of.iter().chain(&any.one_of);
+                    self.validate_any_of(path, schemas, value, violations);
+                }
+                if let Some(not) = &any.not {
+                    self.validate_not(path, not, value, violations);
+                }
+            }
+        }
+    }
+
+    fn validate_object(
+        &self,
+        path: &str,
+        properties: &IndexMap<String, ReferenceOr<Box<Schema>>>,
+        required: &[String],
+        additional_properties: Option<&AdditionalProperties>,
+        map: &Map<String, Value>,
+        violations: &mut Vec<Violation>,
+    ) {
+        for key in required {
+            if !map.contains_key(key) {
+                violations.push(Violation::Body {
+                    path: object_path(path, key),
+                    message: "required property is missing".into(),
+                });
+            }
+        }
+        for (key, value) in map {
+            let path = object_path(path, key);
+            if let Some(schema) = properties.get(key) {
+                if let Some(schema) = self.resolve(schema, violations) {
+                    self.validate_value(&path, schema, value, violations);
+                }
+                continue;
+            }
+            match additional_properties {
+                Some(AdditionalProperties::Any(false)) => {
+                    violations.push(Violation::Body {
+                        path,
+                        message: "property is not allowed".into(),
+                    })
+                }
+                Some(AdditionalProperties::Schema(schema)) => {
+                    if let Some(schema) = self.resolve(schema, violations) {
+                        self.validate_value(&path, schema, value, violations);
+                    }
+                }
+                Some(AdditionalProperties::Any(true)) | None => {}
+            }
+        }
+    }
+
+    fn validate_items(
+        &self,
+        path: &str,
+        schema: &ReferenceOr<Box<Schema>>,
+        items: &[Value],
+        violations: &mut Vec<Violation>,
+    ) {
+        if let Some(schema) = self.resolve(schema, violations) {
+            for (i, item) in items.iter().enumerate() {
+                let path = format!("{path}[{i}]");
+                self.validate_value(&path, schema, item, violations);
+            }
+        }
+    }
+
+    fn validate_all_of(
+        &self,
+        path: &str,
+        schemas: &[ReferenceOr<Schema>],
+        value: &Value,
+        violations: &mut Vec<Violation>,
+    ) {
+        for schema in schemas {
+            if let Some(schema) = self.resolve(schema, violations) {
+                self.validate_value(path, schema, value, violations);
+            }
+        }
+    }
+
+    fn validate_any_of<'s>(
+        &self,
+        path: &str,
+        schemas: impl IntoIterator<Item = &'s ReferenceOr<Schema>>,
+        value: &Value,
+        violations: &mut Vec<Violation>,
+    ) {
+        if !schemas
+            .into_iter()
+            .any(|schema| self.matches(schema, value))
+        {
+            violations.push(Violation::Body {
+                path: path.to_owned(),
+                message: "doesn't match any of the allowed schemas".into(),
+            });
+        }
+    }
+
+    fn validate_not(
+        &self,
+        path: &str,
+        schema: &ReferenceOr<Schema>,
+        value: &Value,
+        violations: &mut Vec<Violation>,
+    ) {
+        if self.matches(schema, value) {
+            violations.push(Violation::Body {
+                path: path.to_owned(),
+                message: "matches a schema that isn't allowed".into(),
+            });
+        }
+    }
+
+    /// Does the value match the schema? Used for composite schemas, where
+    /// individual violations from the sub-schemas aren't meaningful
+    fn matches<B: Borrow<Schema>>(
+        &self,
+        schema: &ReferenceOr<B>,
+        value: &Value,
+    ) -> bool {
+        let mut violations = Vec::new();
+        if let Some(schema) = self.resolve(schema, &mut violations) {
+            self.validate_value("$", schema, value, &mut violations);
+        }
+        violations.is_empty()
+    }
+
+    /// Resolve a component that may be a reference. If the reference is
+    /// invalid, record that as a violation
+    fn resolve<'a, T, B>(
+        &'a self,
+        reference_or: &'a ReferenceOr<B>,
+        violations: &mut Vec<Violation>,
+    ) -> Option<&'a T>
+    where
+        T: ComponentKind,
+        B: Borrow<T>,
+    {
+        self.resolver
+            .resolve_ref(reference_or)
+            .map_err(|error| violations.push(Violation::InvalidSpec(error)))
+            .ok()
+    }
+}
+
+impl From<OpenAPI> for Contract {
+    fn from(spec: OpenAPI) -> Self {
+        Self {
+            paths: spec.paths,
+            servers: spec.servers,
+            resolver: ReferenceResolver::new(spec.components),
+        }
+    }
+}
+
+/// Get the segments of a server URL's path. The URL can be absolute
+/// (`https://example.com/v1`) or relative to the spec's location (`/v1`).
+fn server_base_path(url: &str) -> Vec<&str> {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("", |i| &rest[i..]),
+        None => url,
+    };
+    path.split('/').filter(|s| !s.is_empty()).collect()
+}
+
+/// Match a request path against a server's base path followed by a path
+/// template from the spec. Every segment must match. Path parameters (and
+/// server variables in the base path) match any single segment. Return the
+/// number of literal segments matched in the template.
+fn match_path(
+    base: &[&str],
+    template: &str,
+    segments: &[&str],
+) -> Option<usize> {
+    let template: Vec<&str> =
+        template.split('/').filter(|s| !s.is_empty()).collect();
+    if segments.len() != base.len() + template.len() {
+        return None;
+    }
+    let mut literals = 0;
+    for (i, (expected, actual)) in
+        base.iter().chain(&template).zip(segments).enumerate()
+    {
+        if expected.contains('{') {
+            // Parameter, matches anything
+            continue;
+        }
+        if expected != actual {
+            return None;
+        }
+        if i >= base.len() {
+            literals += 1;
+        }
+    }
+    Some(literals)
+}
+
+/// Get the response definition for a status code. An exact match takes
+/// precedence over a range (e.g. `2XX`), which beats `default`
+fn find_response(
+    responses: &Responses,
+    status: StatusCode,
+) -> Option<&ReferenceOr<Response>> {
+    let code = status.as_u16();
+    responses
+        .responses
+        .get(&openapiv3::StatusCode::Code(code))
+        .or_else(|| {
+            responses
+                .responses
+                .get(&openapiv3::StatusCode::Range(code / 100))
+        })
+        .or(responses.default.as_ref())
+}
+
+/// Get the media type definition for a content type. An exact match takes
+/// precedence over wildcards like `application/*`
+fn find_media_type<'a>(
+    content: &'a IndexMap<String, MediaType>,
+    content_type: &str,
+) -> Option<&'a MediaType> {
+    let mime: Mime = content_type.parse().ok()?;
+    let parsed = || {
+        content.iter().filter_map(|(key, media_type)| {
+            Some((key.parse::<Mime>().ok()?, media_type))
+        })
+    };
+    parsed()
+        .find(|(key, _)| key.essence_str() == mime.essence_str())
+        .or_else(|| {
+            parsed().find(|(key, _)| {
+                (key.type_() == mime::STAR || key.type_() == mime.type_())
+                    && (key.subtype() == mime::STAR
+                        || key.subtype() == mime.subtype())
+            })
+        })
+        .map(|(_, media_type)| media_type)
+}
+
+/// Name of a JSON value's type, for error messages
+fn value_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::RequestRecord,
+        test_util::{header_map, Factory},
+    };
+    use reqwest::Method;
+    use rstest::{fixture, rstest};
+    use serde_json::json;
+
+    #[fixture]
+    fn contract() -> Contract {
+        let spec: OpenAPI = serde_yaml::from_str(
+            r##"
+openapi: 3.0.0
+info: {title: Test, version: "1"}
+servers:
+  - url: https://example.com/v1
+paths:
+  /users/{id}:
+    get:
+      responses:
+        "200":
+          description: OK
+          headers:
+            X-Rate-Limit: {required: true, schema: {type: integer}}
+          content:
+            application/json:
+              schema: {$ref: "#/components/schemas/User"}
+        4XX:
+          description: Error
+  /users/me:
+    get:
+      responses:
+        "200":
+          description: OK
+          content:
+            application/*:
+              schema: {type: object}
+components:
+  schemas:
+    User:
+      type: object
+      required: [id, name]
+      additionalProperties: false
+      properties:
+        id: {type: integer}
+        name: {type: string}
+        role: {type: string, enum: [admin, user]}
+        email: {type: string, nullable: true}
+        tags: {type: array, items: {type: string}}
+        pet: {oneOf: [{type: string}, {type: integer}]}
+"##,
+        )
+        .unwrap();
+        spec.into()
+    }
+
+    fn exchange(
+        path: &str,
+        status: StatusCode,
+        headers: &[(&str, &str)],
+        body: Value,
+    ) -> Exchange {
+        let request = RequestRecord {
+            method: Method::GET,
+            url: format!("https://example.com/v1{path}").parse().unwrap(),
+            ..RequestRecord::factory(())
+        };
+        let response = ResponseRecord {
+            status,
+            headers: header_map(headers.iter().copied()),
+            body: body.to_string().into_bytes().into(),
+        };
+        Exchange::factory((request, response))
+    }
+
+    #[rstest]
+    #[case::valid(
+        "/users/3",
+        StatusCode::OK,
+        &[("x-rate-limit", "100"), ("content-type", "application/json")],
+        json!({
+            "id": 3,
+            "name": "Bob",
+            "role": "admin",
+            "email": null,
+            "tags": ["a"],
+            "pet": 3,
+        }),
+        vec![],
+    )]
+    #[case::unknown_operation(
+        "/posts/3",
+        StatusCode::OK,
+        &[],
+        json!(null),
+        vec![Violation::UnknownOperation {
+            operation: "GET /v1/posts/3".into(),
+        }],
+    )]
+    // Every segment has to match, not just the end of the path
+    #[case::extra_segments(
+        "/admin/users/3",
+        StatusCode::OK,
+        &[],
+        json!(null),
+        vec![Violation::UnknownOperation {
+            operation: "GET /v1/admin/users/3".into(),
+        }],
+    )]
+    #[case::undocumented_status(
+        "/users/3",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        &[],
+        json!(null),
+        vec![Violation::Status {
+            operation: "GET /users/{id}".into(),
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+        }],
+    )]
+    #[case::status_range("/users/3", StatusCode::NOT_FOUND, &[], json!({}), vec![])]
+    // Literal path takes precedence over a parameter, and wildcard media type
+    #[case::specific_path(
+        "/users/me",
+        StatusCode::OK,
+        &[("content-type", "application/json")],
+        json!({}),
+        vec![],
+    )]
+    #[case::missing_header(
+        "/users/3",
+        StatusCode::OK,
+        &[("content-type", "application/json")],
+        json!({"id": 3, "name": "Bob"}),
+        vec![Violation::MissingHeader { header: "X-Rate-Limit".into() }],
+    )]
+    #[case::content_type(
+        "/users/3",
+        StatusCode::OK,
+        &[("x-rate-limit", "100"), ("content-type", "text/html")],
+        json!({"id": 3, "name": "Bob"}),
+        vec![Violation::ContentType { content_type: "text/html".into() }],
+    )]
+    #[case::body(
+        "/users/3",
+        StatusCode::OK,
+        &[("x-rate-limit", "100"), ("content-type", "application/json")],
+        json!({
+            "id": "3",
+            "role": "owner",
+            "tags": [1],
+            "pet": false,
+            "extra": 1,
+        }),
+        vec![
+            body_violation("$.name", "required property is missing"),
+            body_violation("$.extra", "property is not allowed"),
+            body_violation("$.id", "expected integer, got string"),
+            body_violation(
+                "$.pet",
+                "doesn't match any of the allowed schemas",
+            ),
+            body_violation("$.role", "\"owner\" is not an allowed value"),
+            body_violation("$.tags[0]", "expected string, got number"),
+        ],
+    )]
+    fn test_validate(
+        contract: Contract,
+        #[case] path: &str,
+        #[case] status: StatusCode,
+        #[case] headers: &[(&str, &str)],
+        #[case] body: Value,
+        #[case] expected: Vec<Violation>,
+    ) {
+        let exchange = exchange(path, status, headers, body);
+        assert_eq!(contract.validate(&exchange), expected);
+    }
+
+    /// A broken reference in the spec is reported, rather than ignored
+    #[test]
+    fn test_validate_invalid_spec() {
+        let spec: OpenAPI = serde_yaml::from_str(
+            r##"
+openapi: 3.0.0
+info: {title: Test, version: "1"}
+servers:
+  - url: /v1
+paths:
+  /users:
+    get:
+      responses:
+        "200": {$ref: "#/components/responses/Missing"}
+"##,
+        )
+        .unwrap();
+        let contract = Contract::from(spec);
+        let exchange = exchange("/users", StatusCode::OK, &[], json!([]));
+        assert_eq!(
+            contract.validate(&exchange),
+            vec![Violation::InvalidSpec(ResolveError::UnknownReference(
+                "#/components/responses/Missing".into()
+            ))]
+        );
+    }
+
+    /// Request paths are anchored at the server's base path. A path that's
+    /// only a parameter doesn't match everything
+    #[rstest]
+    #[case::root_param("/", "/{id}", "/3", Some(0))]
+    #[case::root_param_nested("/", "/{id}", "/users/3", None)]
+    #[case::base_path("https://example.com/v1", "/{id}", "/v1/3", Some(0))]
+    #[case::base_path_missing("https://example.com/v1", "/{id}", "/v2/3", None)]
+    #[case::base_path_only("https://example.com/v1", "/{id}", "/v1", None)]
+    #[case::relative("/api/v1/", "/users/me", "/api/v1/users/me", Some(2))]
+    #[case::variable(
+        "https://example.com/{version}",
+        "/users",
+        "/v2/users",
+        Some(1)
+    )]
+    #[case::host_only("https://example.com", "/users", "/users", Some(1))]
+    fn test_match_path(
+        #[case] server: &str,
+        #[case] template: &str,
+        #[case] path: &str,
+        #[case] expected: Option<usize>,
+    ) {
+        let segments: Vec<&str> =
+            path.split('/').filter(|s| !s.is_empty()).collect();
+        assert_eq!(
+            match_path(&server_base_path(server), template, &segments),
+            expected
+        );
+    }
+
+    /// Servers on a path override the top-level ones
+    #[test]
+    fn test_validate_path_servers() {
+        let spec: OpenAPI = serde_yaml::from_str(
+            r##"
+openapi: 3.0.0
+info: {title: Test, version: "1"}
+servers:
+  - url: https://example.com/v1
+paths:
+  /users:
+    servers:
+      - url: https://example.com/v1/internal
+    get:
+      responses:
+        "200": {description: OK}
+"##,
+        )
+        .unwrap();
+        let contract = Contract::from(spec);
+        let exchange = |path| exchange(path, StatusCode::OK, &[], json!(null));
+        assert_eq!(contract.validate(&exchange("/internal/users")), vec![]);
+        // The top-level base path doesn't apply here
+        assert_eq!(
+            contract.validate(&exchange("/users")),
+            vec![Violation::UnknownOperation {
+                operation: "GET /v1/users".into()
+            }]
+        );
+    }
+
+    fn body_violation(path: &str, message: &str) -> Violation {
+        Violation::Body {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
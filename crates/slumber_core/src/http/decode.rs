@@ -0,0 +1,263 @@
+//! Decode values that show up in responses in an encoded form, such as JWTs
+//! and epoch timestamps, so they can be inspected
+
+use base64::{
+    engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use derive_more::Display;
+use percent_encoding::percent_decode_str;
+use serde_json::Value;
+
+/// A way that a value can be encoded
+#[derive(Copy, Clone, Debug, Display, PartialEq)]
+pub enum Encoding {
+    #[display("JWT")]
+    Jwt,
+    /// A string containing JSON
+    #[display("JSON")]
+    Json,
+    #[display("Timestamp")]
+    Timestamp,
+    #[display("Base64")]
+    Base64,
+    #[display("URL-Encoded")]
+    UrlEncoded,
+}
+
+/// A value decoded in one particular way
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoded {
+    pub encoding: Encoding,
+    /// Human-readable form of the decoded value. May be multiple lines
+    pub text: String,
+}
+
+/// Decode a value every way that it can be decoded. Values are often valid in
+/// multiple encodings (e.g. a number could be a timestamp or base64), so it's
+/// up to the user to decide which is meaningful. Return an empty list if no
+/// decoding applies.
+pub fn decode(value: &str) -> Vec<Decoded> {
+    type Decoder = fn(&str) -> Option<String>;
+
+    let value = value.trim();
+    let decoders: [(Encoding, Decoder); 5] = [
+        (Encoding::Jwt, decode_jwt),
+        (Encoding::Json, decode_json),
+        (Encoding::Timestamp, decode_timestamp),
+        (Encoding::Base64, decode_base64),
+        (Encoding::UrlEncoded, decode_url),
+    ];
+    decoders
+        .into_iter()
+        .filter_map(|(encoding, decode)| {
+            Some(Decoded {
+                encoding,
+                text: decode(value)?,
+            })
+        })
+        .collect()
+}
+
+/// Decode the header and claims of a JWT. The signature is *not* verified.
+/// Registered timestamp claims are also shown in a human-readable format.
+fn decode_jwt(value: &str) -> Option<String> {
+    let mut segments = value.split('.');
+    let (Some(header), Some(claims), Some(_), None) = (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) else {
+        return None;
+    };
+    let decode_segment = |segment: &str| -> Option<Value> {
+        let bytes = URL_SAFE_NO_PAD.decode(segment).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    };
+    let header = decode_segment(header)?;
+    let claims = decode_segment(claims)?;
+
+    let timestamps = ["exp", "nbf", "iat", "auth_time"]
+        .into_iter()
+        .filter_map(|claim| {
+            let timestamp = claims.get(claim)?.as_i64()?;
+            let time = DateTime::from_timestamp(timestamp, 0)?;
+            Some(format!("{claim}: {}", format_time(time)))
+        })
+        .collect::<Vec<_>>();
+    let mut text = serde_json::to_string_pretty(&serde_json::json!({
+        "header": header,
+        "claims": claims,
+    }))
+    .ok()?;
+    for line in timestamps {
+        text.push('\n');
+        text.push_str(&line);
+    }
+    Some(text)
+}
+
+/// Pretty-print a string containing a JSON object or array. Scalars are
+/// excluded because nearly everything is valid JSON as a scalar.
+fn decode_json(value: &str) -> Option<String> {
+    match serde_json::from_str(value).ok()? {
+        json @ (Value::Object(_) | Value::Array(_)) => {
+            serde_json::to_string_pretty(&json).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Interpret an integer as a Unix timestamp in seconds or milliseconds. To
+/// avoid treating every number as a timestamp, only lengths that give a
+/// recent-ish date are accepted.
+fn decode_timestamp(value: &str) -> Option<String> {
+    if !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let number: i64 = value.parse().ok()?;
+    let time = match value.len() {
+        10 => DateTime::from_timestamp(number, 0)?,
+        13 => DateTime::from_timestamp_millis(number)?,
+        _ => return None,
+    };
+    Some(format!(
+        "{} (UTC)\n{} (local)",
+        format_time(time),
+        time.with_timezone(&Local)
+            .to_rfc3339_opts(SecondsFormat::AutoSi, false)
+    ))
+}
+
+/// Decode standard or URL-safe base64, with or without padding. The decoded
+/// bytes must be printable text, otherwise just about any alphanumeric string
+/// would count as base64.
+fn decode_base64(value: &str) -> Option<String> {
+    let unpadded = value.trim_end_matches('=');
+    if unpadded.is_empty() {
+        return None;
+    }
+    let bytes = STANDARD_NO_PAD
+        .decode(unpadded)
+        .or_else(|_| URL_SAFE_NO_PAD.decode(unpadded))
+        .ok()?;
+    let text = String::from_utf8(bytes).ok()?;
+    if text == value
+        || text.chars().any(|c| c.is_control() && !c.is_whitespace())
+    {
+        return None;
+    }
+    Some(decode_json(&text).unwrap_or(text))
+}
+
+/// Decode a percent-encoded value. `+` is decoded as a space, as it is in
+/// query strings and form bodies.
+fn decode_url(value: &str) -> Option<String> {
+    if !value.contains(['%', '+']) {
+        return None;
+    }
+    let text = percent_decode_str(&value.replace('+', " "))
+        .decode_utf8()
+        .ok()?
+        .into_owned();
+    if text == value {
+        return None;
+    }
+    Some(decode_json(&text).unwrap_or(text))
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    /// Get just the kinds of encodings that apply to a value
+    fn encodings(value: &str) -> Vec<Encoding> {
+        decode(value)
+            .into_iter()
+            .map(|decoded| decoded.encoding)
+            .collect()
+    }
+
+    /// Get the text for a single encoding of a value
+    fn decoded(value: &str, encoding: Encoding) -> String {
+        decode(value)
+            .into_iter()
+            .find(|decoded| decoded.encoding == encoding)
+            .unwrap_or_else(|| panic!("{value:?} not decoded as {encoding}"))
+            .text
+    }
+
+    #[test]
+    fn test_jwt() {
+        // {"alg":"HS256","typ":"JWT"}.{"sub":"1234","exp":1725624000}
+        let jwt = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+            eyJzdWIiOiIxMjM0IiwiZXhwIjoxNzI1NjI0MDAwfQ.\
+            c2lnbmF0dXJl";
+        assert_eq!(encodings(jwt), vec![Encoding::Jwt]);
+        let text = decoded(jwt, Encoding::Jwt);
+        let json = text
+            .strip_suffix("\nexp: 2024-09-06T12:00:00Z")
+            .unwrap_or_else(|| panic!("Missing timestamp: {text}"));
+        assert_eq!(
+            serde_json::from_str::<Value>(json).unwrap(),
+            serde_json::json!({
+                "header": {"alg": "HS256", "typ": "JWT"},
+                "claims": {"sub": "1234", "exp": 1725624000},
+            })
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let text = decoded("1725624000", Encoding::Timestamp);
+        assert!(text.starts_with("2024-09-06T12:00:00Z (UTC)\n"), "{text}");
+        let text = decoded("1725624000123", Encoding::Timestamp);
+        assert!(
+            text.starts_with("2024-09-06T12:00:00.123Z (UTC)\n"),
+            "{text}"
+        );
+    }
+
+    #[rstest]
+    #[case::padded("aGVsbG8gd29ybGQ=", "hello world")]
+    #[case::unpadded("aGVsbG8gd29ybGQ", "hello world")]
+    #[case::url_safe("Pz8_Pz8-", "?????>")]
+    #[case::json("eyJhIjoxfQ==", "{\n  \"a\": 1\n}")]
+    fn test_base64(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(decoded(value, Encoding::Base64), expected);
+    }
+
+    #[rstest]
+    #[case::percent("hello%20world%21", "hello world!")]
+    #[case::plus("a+b%3Dc", "a b=c")]
+    #[case::json("%7B%22a%22%3A1%7D", "{\n  \"a\": 1\n}")]
+    fn test_url(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(decoded(value, Encoding::UrlEncoded), expected);
+    }
+
+    #[test]
+    fn test_nested_json() {
+        assert_eq!(
+            decoded(r#"{"a":[1,2]}"#, Encoding::Json),
+            "{\n  \"a\": [\n    1,\n    2\n  ]\n}"
+        );
+    }
+
+    /// Values that don't look encoded shouldn't be decoded
+    #[rstest]
+    #[case::empty("")]
+    #[case::word("test")]
+    #[case::number("42")]
+    #[case::scalar_json("true")]
+    fn test_not_encoded(#[case] value: &str) {
+        assert_eq!(encodings(value), vec![]);
+    }
+}
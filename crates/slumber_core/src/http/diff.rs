@@ -0,0 +1,265 @@
+//! Line-by-line diffs between two responses, e.g. to spot what changed
+//! between two requests in history
+
+use crate::http::ResponseRecord;
+use reqwest::{header::HeaderMap, StatusCode};
+use serde_json::{Map, Value};
+
+/// Maximum size of the table used to diff two blocks of text. If the changed
+/// section of the text is larger than this, we give up on finding the minimal
+/// diff and just show the entire old section as removed and the new section as
+/// added. This caps memory usage at a few MB.
+const MAX_TABLE_SIZE: usize = 1_000_000;
+
+/// A single line of a diff
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffLine {
+    /// Line is in both versions
+    Equal(String),
+    /// Line is only in the old version
+    Removed(String),
+    /// Line is only in the new version
+    Added(String),
+}
+
+/// Differences between two responses
+#[derive(Debug)]
+pub struct ResponseDiff {
+    /// Old and new status codes
+    pub status: (StatusCode, StatusCode),
+    /// Headers as `name: value` lines, sorted by name so ordering changes
+    /// don't show up
+    pub headers: Vec<DiffLine>,
+    /// If both bodies are JSON, they're normalized before diffing (key order
+    /// and formatting), so only changes to the actual content show up
+    pub body: Vec<DiffLine>,
+}
+
+impl ResponseDiff {
+    pub fn new(old: &ResponseRecord, new: &ResponseRecord) -> Self {
+        let body = match (normalize_json(old), normalize_json(new)) {
+            (Some(old), Some(new)) => diff_lines(&old, &new),
+            _ => diff_lines(
+                &String::from_utf8_lossy(old.body.bytes()),
+                &String::from_utf8_lossy(new.body.bytes()),
+            ),
+        };
+        Self {
+            status: (old.status, new.status),
+            headers: diff_lines(
+                &header_lines(&old.headers),
+                &header_lines(&new.headers),
+            ),
+            body,
+        }
+    }
+
+    /// Are the two responses identical?
+    pub fn is_empty(&self) -> bool {
+        self.status.0 == self.status.1
+            && self
+                .headers
+                .iter()
+                .chain(&self.body)
+                .all(|line| matches!(line, DiffLine::Equal(_)))
+    }
+}
+
+/// Diff two blocks of text line-by-line. This finds a minimal diff via the
+/// longest common subsequence of lines, after trimming off any common prefix
+/// and suffix.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+
+    let equal = |line: &&str| DiffLine::Equal((*line).to_owned());
+    let mut lines: Vec<DiffLine> = old[..prefix].iter().map(equal).collect();
+    let (n, m) = (old_changed.len(), new_changed.len());
+    if n * m > MAX_TABLE_SIZE {
+        lines.extend(
+            old_changed
+                .iter()
+                .map(|line| DiffLine::Removed((*line).to_owned())),
+        );
+        lines.extend(
+            new_changed
+                .iter()
+                .map(|line| DiffLine::Added((*line).to_owned())),
+        );
+    } else {
+        // table[i][j] is the length of the longest common subsequence of
+        // old[i..] and new[j..]
+        let width = m + 1;
+        let mut table = vec![0u32; (n + 1) * width];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                table[i * width + j] = if old_changed[i] == new_changed[j] {
+                    table[(i + 1) * width + j + 1] + 1
+                } else {
+                    table[(i + 1) * width + j].max(table[i * width + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_changed[i] == new_changed[j] {
+                lines.push(equal(&old_changed[i]));
+                i += 1;
+                j += 1;
+            } else if j == m
+                || (i < n
+                    && table[(i + 1) * width + j] >= table[i * width + j + 1])
+            {
+                lines.push(DiffLine::Removed(old_changed[i].to_owned()));
+                i += 1;
+            } else {
+                lines.push(DiffLine::Added(new_changed[j].to_owned()));
+                j += 1;
+            }
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(equal));
+    lines
+}
+
+/// Format headers as `name: value` lines, sorted by name
+fn header_lines(headers: &HeaderMap) -> String {
+    let mut lines: Vec<String> = headers
+        .iter()
+        .map(|(name, value)| {
+            format!("{name}: {}", String::from_utf8_lossy(value.as_bytes()))
+        })
+        .collect();
+    lines.sort();
+    lines.join("\n")
+}
+
+/// If a response body is JSON, pretty-print it with sorted keys
+fn normalize_json(response: &ResponseRecord) -> Option<String> {
+    fn sort_keys(value: Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let mut entries: Vec<(String, Value)> =
+                    map.into_iter().collect();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key, sort_keys(value)))
+                        .collect::<Map<_, _>>(),
+                )
+            }
+            Value::Array(values) => {
+                Value::Array(values.into_iter().map(sort_keys).collect())
+            }
+            other => other,
+        }
+    }
+
+    let value: Value = serde_json::from_slice(response.body.bytes()).ok()?;
+    serde_json::to_string_pretty(&sort_keys(value)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{header_map, Factory};
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn equal(line: &str) -> DiffLine {
+        DiffLine::Equal(line.into())
+    }
+
+    fn removed(line: &str) -> DiffLine {
+        DiffLine::Removed(line.into())
+    }
+
+    fn added(line: &str) -> DiffLine {
+        DiffLine::Added(line.into())
+    }
+
+    #[rstest]
+    #[case::equal("a\nb", "a\nb", vec![equal("a"), equal("b")])]
+    #[case::empty("", "", vec![])]
+    #[case::all_added("", "a\nb", vec![added("a"), added("b")])]
+    #[case::all_removed("a\nb", "", vec![removed("a"), removed("b")])]
+    #[case::changed(
+        "a\nb\nc",
+        "a\nx\nc",
+        vec![equal("a"), removed("b"), added("x"), equal("c")]
+    )]
+    #[case::interleaved(
+        "a\nb\nc\nd",
+        "b\nx\nd\ne",
+        vec![
+            removed("a"),
+            equal("b"),
+            removed("c"),
+            added("x"),
+            equal("d"),
+            added("e"),
+        ]
+    )]
+    fn test_diff_lines(
+        #[case] old: &str,
+        #[case] new: &str,
+        #[case] expected: Vec<DiffLine>,
+    ) {
+        assert_eq!(diff_lines(old, new), expected);
+    }
+
+    /// JSON bodies are normalized, so key order and formatting don't matter
+    #[test]
+    fn test_response_diff_json() {
+        let old = ResponseRecord {
+            headers: header_map([("content-type", "application/json")]),
+            body: br#"{"name": "Frodo", "id": 1}"#.to_vec().into(),
+            ..ResponseRecord::factory(())
+        };
+        let new = ResponseRecord {
+            status: StatusCode::CREATED,
+            headers: header_map([
+                ("x-request-id", "abc"),
+                ("content-type", "application/json"),
+            ]),
+            body: json!({"id": 2, "name": "Frodo"})
+                .to_string()
+                .into_bytes()
+                .into(),
+        };
+        let diff = ResponseDiff::new(&old, &new);
+        assert!(!diff.is_empty());
+        assert_eq!(diff.status, (StatusCode::OK, StatusCode::CREATED));
+        assert_eq!(
+            diff.headers,
+            vec![
+                equal("content-type: application/json"),
+                added("x-request-id: abc")
+            ]
+        );
+        assert_eq!(
+            diff.body,
+            vec![
+                equal("{"),
+                removed("  \"id\": 1,"),
+                added("  \"id\": 2,"),
+                equal("  \"name\": \"Frodo\""),
+                equal("}"),
+            ]
+        );
+
+        let diff = ResponseDiff::new(&old, &old);
+        assert!(diff.is_empty());
+    }
+}
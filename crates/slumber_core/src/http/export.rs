@@ -0,0 +1,1037 @@
+//! Export request history to a portable format, and import it back. Used to
+//! move history between machines, or to attach it to bug reports.
+//!
+//! Two formats are supported:
+//! - [HAR](http://www.softwareishard.com/blog/har-12-spec/), which is readable
+//!   by browser dev tools and many other HTTP tools. Slumber-specific metadata
+//!   is stored in custom `_`-prefixed fields so it survives a round trip.
+//! - JSON Lines, one exchange per line. This is lossless and easy to process
+//!   with tools like `jq`.
+
+use crate::{
+    collection::{ProfileId, RecipeId},
+    http::{
+        EnvironmentSnapshot, Exchange, ExchangeTiming, RequestId,
+        RequestRecord, ResponseBody, ResponseRecord, ServerCertificate,
+    },
+};
+use anyhow::{anyhow, Context};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Method, StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    io::{BufRead, Read, Write},
+    sync::Arc,
+    time,
+};
+
+/// HAR version we generate
+const HAR_VERSION: &str = "1.2";
+
+/// Write exchanges as a HAR document
+pub fn write_har(
+    exchanges: &[Exchange],
+    mut writer: impl Write,
+) -> anyhow::Result<()> {
+    let har = Har {
+        log: HarLog {
+            version: HAR_VERSION.into(),
+            creator: HarCreator {
+                name: "slumber".into(),
+                version: env!("CARGO_PKG_VERSION").into(),
+            },
+            entries: exchanges.iter().map(HarEntry::from).collect(),
+        },
+    };
+    serde_json::to_writer_pretty(&mut writer, &har)
+        .context("Error writing HAR")?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Read exchanges from a HAR document. Only HAR files exported by Slumber can
+/// be imported, because every entry needs a recipe ID.
+pub fn read_har(reader: impl Read) -> anyhow::Result<Vec<Exchange>> {
+    let har: Har =
+        serde_json::from_reader(reader).context("Error parsing HAR")?;
+    har.log
+        .entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            entry
+                .try_into()
+                .with_context(|| format!("Error importing HAR entry {i}"))
+        })
+        .collect()
+}
+
+/// Read exchanges from a HAR document generated by any tool, such as a
+/// browser's dev tools. Entries that weren't exported by Slumber are given a
+/// recipe ID by `recipe_id`, and a request ID derived from their content, so
+/// importing the same file twice doesn't duplicate anything. Entries that
+/// never got a response (e.g. because the browser blocked them) are skipped.
+pub fn read_foreign_har(
+    reader: impl Read,
+    mut recipe_id: impl FnMut(&Method, &Url) -> anyhow::Result<RecipeId>,
+) -> anyhow::Result<Vec<Exchange>> {
+    let har: Har =
+        serde_json::from_reader(reader).context("Error parsing HAR")?;
+    har.log
+        .entries
+        .into_iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.response.status != 0)
+        .map(|(i, entry)| {
+            entry
+                .into_exchange(&mut recipe_id)
+                .with_context(|| format!("Error importing HAR entry {i}"))
+        })
+        .collect()
+}
+
+/// Write exchanges as JSON Lines, one exchange per line
+pub fn write_jsonl(
+    exchanges: &[Exchange],
+    mut writer: impl Write,
+) -> anyhow::Result<()> {
+    for exchange in exchanges {
+        serde_json::to_writer(&mut writer, &JsonExchange::from(exchange))
+            .context("Error writing JSON")?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Write a single exchange as a pretty-printed JSON object. This is the same
+/// format as one line of [write_jsonl]
+pub fn write_json(
+    exchange: &Exchange,
+    mut writer: impl Write,
+) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, &JsonExchange::from(exchange))
+        .context("Error writing JSON")?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Read exchanges from JSON Lines. Blank lines are ignored.
+pub fn read_jsonl(reader: impl BufRead) -> anyhow::Result<Vec<Exchange>> {
+    let mut exchanges = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let exchange: JsonExchange = serde_json::from_str(&line)
+            .with_context(|| format!("Error parsing line {}", i + 1))?;
+        exchanges.push(
+            exchange
+                .try_into()
+                .with_context(|| format!("Error importing line {}", i + 1))?,
+        );
+    }
+    Ok(exchanges)
+}
+
+/// Top level of a HAR document
+#[derive(Debug, Serialize, Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HarLog {
+    version: String,
+    creator: HarCreator,
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HarCreator {
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarEntry {
+    started_date_time: DateTime<Utc>,
+    /// Total elapsed time, in milliseconds
+    time: f64,
+    request: HarRequest,
+    response: HarResponse,
+    #[serde(default)]
+    cache: HarCache,
+    timings: HarTimings,
+    /// Slumber request ID. Generated on import if missing
+    #[serde(rename = "_id", default, skip_serializing_if = "Option::is_none")]
+    id: Option<RequestId>,
+    #[serde(
+        rename = "_recipeId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    recipe_id: Option<RecipeId>,
+    #[serde(
+        rename = "_profileId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    profile_id: Option<ProfileId>,
+    #[serde(rename = "_environment", default)]
+    environment: EnvironmentSnapshot,
+    /// User's note on the request. This is a standard HAR field, so other
+    /// tools will show it too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    http_version: String,
+    #[serde(default)]
+    cookies: Vec<HarHeader>,
+    headers: Vec<HarHeader>,
+    #[serde(default)]
+    query_string: Vec<HarHeader>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    post_data: Option<HarPostData>,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarResponse {
+    status: u16,
+    status_text: String,
+    http_version: String,
+    #[serde(default)]
+    cookies: Vec<HarHeader>,
+    headers: Vec<HarHeader>,
+    content: HarContent,
+    #[serde(default)]
+    redirect_url: String,
+    headers_size: i64,
+    body_size: i64,
+}
+
+/// Name/value pair, used for headers, cookies, and query params
+#[derive(Debug, Serialize, Deserialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarPostData {
+    mime_type: String,
+    /// Browsers omit this for multipart bodies with files
+    #[serde(default)]
+    text: String,
+    /// HAR has no standard way to represent binary request bodies, so we mimic
+    /// the `encoding` field from the response content
+    #[serde(
+        rename = "_encoding",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    encoding: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarContent {
+    size: i64,
+    mime_type: String,
+    #[serde(default)]
+    text: String,
+    /// `base64` for binary bodies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HarCache {}
+
+/// Phase timings, in milliseconds. If the exchange wasn't timed, we only know
+/// the total time, so it's all attributed to waiting. Timings in files
+/// exported by Slumber are ignored on import, because we can't tell which of
+/// those cases we're in. Other tools use `-1` for phases that didn't happen.
+#[derive(Debug, Serialize, Deserialize)]
+struct HarTimings {
+    /// Not generated by Slumber
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blocked: Option<f64>,
+    /// Omitted if no DNS lookup was needed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dns: Option<f64>,
+    /// Includes the TLS handshake. Omitted if the connection was reused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    connect: Option<f64>,
+    /// TLS handshake. Omitted for plain HTTP, or if the connection was reused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ssl: Option<f64>,
+    send: f64,
+    wait: f64,
+    receive: f64,
+}
+
+impl HarTimings {
+    /// Convert timings from another tool. Blocking and sending both count as
+    /// waiting, because that's everything between opening the connection and
+    /// the first byte of the response.
+    fn to_timing(&self) -> ExchangeTiming {
+        let phase = |ms: Option<f64>| ms.filter(|ms| *ms >= 0.0);
+        let tls = phase(self.ssl);
+        // HAR includes the handshake in the connection time; we don't
+        let connect = phase(self.connect)
+            .map(|connect| (connect - tls.unwrap_or_default()).max(0.0));
+        let wait = [self.blocked, Some(self.send), Some(self.wait)]
+            .into_iter()
+            .flatten()
+            .filter(|ms| *ms > 0.0)
+            .sum();
+        ExchangeTiming {
+            dns: phase(self.dns).map(from_millis),
+            connect: connect.map(from_millis),
+            tls: tls.map(from_millis),
+            wait: from_millis(wait),
+            download: from_millis(self.receive),
+        }
+    }
+}
+
+impl From<&Exchange> for HarEntry {
+    fn from(exchange: &Exchange) -> Self {
+        let request = &exchange.request;
+        let response = &exchange.response;
+        let time = exchange.duration().num_microseconds().unwrap_or_default()
+            as f64
+            / 1000.0;
+
+        let post_data = request.body.as_ref().map(|body| {
+            let (text, encoding) = encode_body(body);
+            HarPostData {
+                mime_type: mime_type(&request.headers),
+                text,
+                encoding,
+            }
+        });
+        let (text, encoding) = encode_body(response.body.bytes());
+
+        Self {
+            started_date_time: exchange.start_time,
+            time,
+            request: HarRequest {
+                method: request.method.to_string(),
+                url: request.url.to_string(),
+                // We don't track the version that was actually used
+                http_version: "HTTP/1.1".into(),
+                cookies: Vec::new(),
+                headers: encode_headers(&request.headers),
+                query_string: request
+                    .url
+                    .query_pairs()
+                    .map(|(name, value)| HarHeader {
+                        name: name.into_owned(),
+                        value: value.into_owned(),
+                    })
+                    .collect(),
+                post_data,
+                headers_size: -1,
+                body_size: request
+                    .body
+                    .as_ref()
+                    .map_or(0, |body| body.len() as i64),
+            },
+            response: HarResponse {
+                status: response.status.as_u16(),
+                status_text: response
+                    .status
+                    .canonical_reason()
+                    .unwrap_or_default()
+                    .into(),
+                http_version: "HTTP/1.1".into(),
+                cookies: Vec::new(),
+                headers: encode_headers(&response.headers),
+                content: HarContent {
+                    size: response.body.size() as i64,
+                    mime_type: mime_type(&response.headers),
+                    text,
+                    encoding,
+                },
+                redirect_url: String::new(),
+                headers_size: -1,
+                body_size: response.body.size() as i64,
+            },
+            cache: HarCache {},
+            timings: match exchange.timing {
+                Some(timing) => HarTimings {
+                    blocked: None,
+                    dns: timing.dns.map(millis),
+                    connect: timing.connect.map(|connect| {
+                        millis(connect + timing.tls.unwrap_or_default())
+                    }),
+                    ssl: timing.tls.map(millis),
+                    send: 0.0,
+                    wait: millis(timing.wait),
+                    receive: millis(timing.download),
+                },
+                None => HarTimings {
+                    blocked: None,
+                    dns: None,
+                    connect: None,
+                    ssl: None,
+                    send: 0.0,
+                    wait: time,
+                    receive: 0.0,
+                },
+            },
+            id: Some(exchange.id),
+            recipe_id: Some(request.recipe_id.clone()),
+            profile_id: request.profile_id.clone(),
+            environment: request.environment.clone(),
+            comment: request.note.clone(),
+        }
+    }
+}
+
+impl TryFrom<HarEntry> for Exchange {
+    type Error = anyhow::Error;
+
+    fn try_from(entry: HarEntry) -> anyhow::Result<Self> {
+        entry.into_exchange(|_, _| {
+            Err(anyhow!(
+                "Missing `_recipeId`; only HAR files exported by Slumber can \
+                be imported"
+            ))
+        })
+    }
+}
+
+impl HarEntry {
+    /// Convert to an exchange. If the entry wasn't exported by Slumber, get
+    /// its recipe ID from `recipe_id`, and use its timings.
+    fn into_exchange(
+        self,
+        recipe_id: impl FnOnce(&Method, &Url) -> anyhow::Result<RecipeId>,
+    ) -> anyhow::Result<Exchange> {
+        let method: Method = self.request.method.parse()?;
+        let url: Url = self.request.url.parse()?;
+        let (id, recipe_id, timing) = match self.recipe_id {
+            Some(recipe_id) => (self.id.unwrap_or_default(), recipe_id, None),
+            None => (
+                self.id.unwrap_or_else(|| {
+                    content_id(&self.request, &self.started_date_time)
+                }),
+                recipe_id(&method, &url)?,
+                Some(self.timings.to_timing()),
+            ),
+        };
+        let request_body = self
+            .request
+            .post_data
+            .map(|post_data| {
+                decode_body(post_data.text, post_data.encoding.as_deref())
+            })
+            .transpose()?;
+        let response_body = decode_body(
+            self.response.content.text,
+            self.response.content.encoding.as_deref(),
+        )?;
+        let end_time = self.started_date_time
+            + Duration::microseconds((self.time * 1000.0).round() as i64);
+
+        Ok(build_exchange(
+            id,
+            RequestRecord {
+                id: Default::default(),
+                profile_id: self.profile_id,
+                recipe_id,
+                method,
+                url,
+                headers: decode_headers(self.request.headers)?,
+                body: request_body,
+                environment: self.environment,
+                note: self.comment,
+            },
+            ResponseRecord {
+                status: StatusCode::from_u16(self.response.status)?,
+                headers: decode_headers(self.response.headers)?,
+                body: ResponseBody::new(response_body),
+            },
+            self.started_date_time,
+            end_time,
+            timing,
+            None,
+        ))
+    }
+}
+
+/// A single exchange in JSON Lines format. Unlike HAR, this maps directly onto
+/// what's stored in the database.
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonExchange {
+    id: RequestId,
+    profile_id: Option<ProfileId>,
+    recipe_id: RecipeId,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    /// Derived from the start and end times, for convenience when processing
+    /// the output. Ignored on import.
+    #[serde(default, skip_deserializing)]
+    duration_ms: i64,
+    /// Omitted if the exchange wasn't timed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timing: Option<JsonTiming>,
+    /// Omitted if the request wasn't made over TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    certificate: Option<ServerCertificate>,
+    request: JsonRequest,
+    response: JsonResponse,
+}
+
+/// [ExchangeTiming], with each phase in (fractional) milliseconds
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonTiming {
+    /// Omitted if no DNS lookup was needed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dns_ms: Option<f64>,
+    /// Omitted if the connection was reused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    connect_ms: Option<f64>,
+    /// Omitted for plain HTTP, or if the connection was reused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tls_ms: Option<f64>,
+    wait_ms: f64,
+    download_ms: f64,
+}
+
+impl From<ExchangeTiming> for JsonTiming {
+    fn from(timing: ExchangeTiming) -> Self {
+        Self {
+            dns_ms: timing.dns.map(millis),
+            connect_ms: timing.connect.map(millis),
+            tls_ms: timing.tls.map(millis),
+            wait_ms: millis(timing.wait),
+            download_ms: millis(timing.download),
+        }
+    }
+}
+
+impl From<JsonTiming> for ExchangeTiming {
+    fn from(timing: JsonTiming) -> Self {
+        Self {
+            dns: timing.dns_ms.map(from_millis),
+            connect: timing.connect_ms.map(from_millis),
+            tls: timing.tls_ms.map(from_millis),
+            wait: from_millis(timing.wait_ms),
+            download: from_millis(timing.download_ms),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonRequest {
+    #[serde(with = "super::cereal::serde_method")]
+    method: Method,
+    url: Url,
+    headers: Vec<HarHeader>,
+    body: Option<JsonBody>,
+    #[serde(default)]
+    environment: EnvironmentSnapshot,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonResponse {
+    #[serde(with = "super::cereal::serde_status_code")]
+    status: StatusCode,
+    headers: Vec<HarHeader>,
+    body: JsonBody,
+}
+
+/// Body content. Text bodies are stored as-is to keep the file readable, and
+/// binary bodies are base64-encoded.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonBody {
+    Text(String),
+    Base64 { base64: String },
+}
+
+impl From<&[u8]> for JsonBody {
+    fn from(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Self::Text(text.to_owned()),
+            Err(_) => Self::Base64 {
+                base64: BASE64_STANDARD.encode(bytes),
+            },
+        }
+    }
+}
+
+impl TryFrom<JsonBody> for Bytes {
+    type Error = anyhow::Error;
+
+    fn try_from(body: JsonBody) -> anyhow::Result<Self> {
+        match body {
+            JsonBody::Text(text) => Ok(text.into()),
+            JsonBody::Base64 { base64 } => decode_body(base64, Some("base64")),
+        }
+    }
+}
+
+impl From<&Exchange> for JsonExchange {
+    fn from(exchange: &Exchange) -> Self {
+        let request = &exchange.request;
+        let response = &exchange.response;
+        Self {
+            id: exchange.id,
+            profile_id: request.profile_id.clone(),
+            recipe_id: request.recipe_id.clone(),
+            start_time: exchange.start_time,
+            end_time: exchange.end_time,
+            duration_ms: exchange.duration().num_milliseconds(),
+            timing: exchange.timing.map(JsonTiming::from),
+            certificate: exchange.certificate.clone(),
+            request: JsonRequest {
+                method: request.method.clone(),
+                url: request.url.clone(),
+                headers: encode_headers(&request.headers),
+                body: request.body.as_deref().map(JsonBody::from),
+                environment: request.environment.clone(),
+                note: request.note.clone(),
+            },
+            response: JsonResponse {
+                status: response.status,
+                headers: encode_headers(&response.headers),
+                body: response.body.bytes().into(),
+            },
+        }
+    }
+}
+
+impl TryFrom<JsonExchange> for Exchange {
+    type Error = anyhow::Error;
+
+    fn try_from(exchange: JsonExchange) -> anyhow::Result<Self> {
+        Ok(build_exchange(
+            exchange.id,
+            RequestRecord {
+                id: Default::default(),
+                profile_id: exchange.profile_id,
+                recipe_id: exchange.recipe_id,
+                method: exchange.request.method,
+                url: exchange.request.url,
+                headers: decode_headers(exchange.request.headers)?,
+                body: exchange.request.body.map(Bytes::try_from).transpose()?,
+                environment: exchange.request.environment,
+                note: exchange.request.note,
+            },
+            ResponseRecord {
+                status: exchange.response.status,
+                headers: decode_headers(exchange.response.headers)?,
+                body: ResponseBody::new(exchange.response.body.try_into()?),
+            },
+            exchange.start_time,
+            exchange.end_time,
+            exchange.timing.map(ExchangeTiming::from),
+            exchange.certificate,
+        ))
+    }
+}
+
+/// Assemble an exchange from imported parts. The request's ID is replaced with
+/// the exchange's, so they can't disagree.
+fn build_exchange(
+    id: RequestId,
+    mut request: RequestRecord,
+    response: ResponseRecord,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    timing: Option<ExchangeTiming>,
+    certificate: Option<ServerCertificate>,
+) -> Exchange {
+    request.id = id;
+    Exchange {
+        id,
+        request: Arc::new(request),
+        response: Arc::new(response),
+        start_time,
+        end_time,
+        timing,
+        certificate,
+    }
+}
+
+/// Convert a duration to fractional milliseconds, with microsecond precision
+fn millis(duration: time::Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+/// Convert fractional milliseconds to a duration. Negative values are invalid,
+/// so they're clamped to zero.
+fn from_millis(ms: f64) -> time::Duration {
+    time::Duration::from_micros((ms * 1000.0).round().max(0.0) as u64)
+}
+
+/// Generate a request ID for a HAR entry that doesn't have one, from a hash of
+/// when and where it was sent. The same entry always gets the same ID, so it
+/// can be recognized if it's imported again.
+fn content_id(request: &HarRequest, start_time: &DateTime<Utc>) -> RequestId {
+    let mut hasher = Sha256::new();
+    hasher.update(start_time.to_rfc3339());
+    hasher.update(&request.method);
+    hasher.update(&request.url);
+    let hash = hasher.finalize();
+    let bytes: [u8; 16] = hash[..16].try_into().expect("Hash is 32 bytes");
+    RequestId(uuid::Builder::from_random_bytes(bytes).into_uuid())
+}
+
+/// Convert a header map to name/value pairs. Values that aren't valid UTF-8
+/// are converted lossily.
+fn encode_headers(headers: &HeaderMap) -> Vec<HarHeader> {
+    headers
+        .iter()
+        .map(|(name, value)| HarHeader {
+            name: name.to_string(),
+            value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        })
+        .collect()
+}
+
+fn decode_headers(headers: Vec<HarHeader>) -> anyhow::Result<HeaderMap> {
+    headers
+        .into_iter()
+        // HTTP/2 pseudo-headers such as `:authority`, which browsers include
+        // in HAR files. They duplicate the method and URL
+        .filter(|header| !header.name.starts_with(':'))
+        .map(|header| {
+            let name: HeaderName = header
+                .name
+                .parse()
+                .with_context(|| format!("Invalid header `{}`", header.name))?;
+            let value: HeaderValue =
+                header.value.parse().with_context(|| {
+                    format!("Invalid value for header `{}`", header.name)
+                })?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Get the MIME type of a body from its `Content-Type` header, or an empty
+/// string if there isn't one
+fn mime_type(headers: &HeaderMap) -> String {
+    headers
+        .get(header::CONTENT_TYPE)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .unwrap_or_default()
+}
+
+/// Encode body bytes as text. Return the text, and the encoding used if the
+/// bytes weren't valid UTF-8
+fn encode_body(bytes: &[u8]) -> (String, Option<String>) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_owned(), None),
+        Err(_) => (BASE64_STANDARD.encode(bytes), Some("base64".into())),
+    }
+}
+
+fn decode_body(text: String, encoding: Option<&str>) -> anyhow::Result<Bytes> {
+    match encoding {
+        None => Ok(text.into()),
+        Some("base64") => Ok(BASE64_STANDARD
+            .decode(text)
+            .context("Invalid base64 body")?
+            .into()),
+        Some(encoding) => {
+            Err(anyhow!("Unsupported body encoding `{encoding}`"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_err,
+        http::RequestTrigger,
+        test_util::{header_map, Factory},
+    };
+    use chrono::TimeZone;
+    use indexmap::indexmap;
+    use rstest::rstest;
+    use serde_json::json;
+
+    /// An exchange that exercises everything we need to preserve: text and
+    /// binary bodies, headers, environment, and note. Times are whole
+    /// milliseconds because that's all HAR supports.
+    fn exchange() -> Exchange {
+        let request = RequestRecord {
+            profile_id: Some("local".into()),
+            method: Method::POST,
+            url: "http://localhost/users?sort=name".parse().unwrap(),
+            headers: header_map([("Content-Type", "application/json")]),
+            body: Some(br#"{"name":"Frodo"}"#.as_slice().into()),
+            environment: EnvironmentSnapshot {
+                profile_data: indexmap! {"host".into() => "localhost".into()},
+                git_revision: Some("abc123".into()),
+                trigger: Some(RequestTrigger {
+                    chain_id: "token".into(),
+                    recipe_id: Some("login".into()),
+                }),
+            },
+            note: Some("Frodo's account".into()),
+            ..RequestRecord::factory((None, "create_user".into()))
+        };
+        let response = ResponseRecord {
+            status: StatusCode::CREATED,
+            headers: header_map([("Content-Type", "image/png")]),
+            body: ResponseBody::new(b"\x89PNG\xff".as_slice().into()),
+        };
+        let start_time = Utc.timestamp_millis_opt(1_700_000_000_123).unwrap();
+        Exchange {
+            start_time,
+            end_time: start_time + Duration::milliseconds(250),
+            ..Exchange::factory((request, response))
+        }
+    }
+
+    /// Timing that adds up to the duration of [exchange]
+    fn timing() -> ExchangeTiming {
+        ExchangeTiming {
+            dns: Some(time::Duration::from_micros(1500)),
+            connect: Some(time::Duration::from_millis(10)),
+            tls: Some(time::Duration::from_millis(20)),
+            wait: time::Duration::from_millis(170),
+            download: time::Duration::from_micros(48500),
+        }
+    }
+
+    fn certificate() -> ServerCertificate {
+        ServerCertificate {
+            subject: "CN=localhost".into(),
+            issuer: "CN=Slumber Test CA".into(),
+            subject_alt_names: vec!["localhost".into()],
+            not_before: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            not_after: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            serial_number: "01:02".into(),
+            fingerprint: "AB:CD".into(),
+        }
+    }
+
+    #[test]
+    fn test_har_round_trip() {
+        let exchanges = [exchange()];
+        let mut har = Vec::new();
+        write_har(&exchanges, &mut har).unwrap();
+        let imported = read_har(har.as_slice()).unwrap();
+        assert_eq!(imported, exchanges);
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let exchanges = [
+            Exchange {
+                timing: Some(timing()),
+                certificate: Some(certificate()),
+                ..exchange()
+            },
+            Exchange::factory(()),
+        ];
+        let mut jsonl = Vec::new();
+        write_jsonl(&exchanges, &mut jsonl).unwrap();
+        assert_eq!(jsonl.iter().filter(|b| **b == b'\n').count(), 2);
+        let imported = read_jsonl(jsonl.as_slice()).unwrap();
+        assert_eq!(imported, exchanges);
+    }
+
+    #[test]
+    fn test_json_content() {
+        let mut json = Vec::new();
+        write_json(&exchange(), &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["duration_ms"], json!(250));
+        assert_eq!(json["response"]["status"], json!(201));
+        assert_eq!(json["response"]["body"], json!({"base64": "iVBOR/8="}));
+    }
+
+    /// Certificate is included for TLS requests, and omitted otherwise
+    #[test]
+    fn test_certificate_content() {
+        let mut json = Vec::new();
+        write_json(&exchange(), &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json.get("certificate"), None);
+
+        let exchange = Exchange {
+            certificate: Some(certificate()),
+            ..exchange()
+        };
+        let mut json = Vec::new();
+        write_json(&exchange, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(
+            json["certificate"],
+            json!({
+                "subject": "CN=localhost",
+                "issuer": "CN=Slumber Test CA",
+                "subject_alt_names": ["localhost"],
+                "not_before": "2024-01-01T00:00:00Z",
+                "not_after": "2025-01-01T00:00:00Z",
+                "serial_number": "01:02",
+                "fingerprint": "AB:CD",
+            })
+        );
+    }
+
+    /// Timing is included if the exchange was timed, in both formats
+    #[test]
+    fn test_timing_content() {
+        let exchange = Exchange {
+            timing: Some(timing()),
+            ..exchange()
+        };
+
+        let mut json = Vec::new();
+        write_json(&exchange, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(
+            json["timing"],
+            json!({
+                "dns_ms": 1.5,
+                "connect_ms": 10.0,
+                "tls_ms": 20.0,
+                "wait_ms": 170.0,
+                "download_ms": 48.5,
+            })
+        );
+
+        let mut har = Vec::new();
+        write_har(&[exchange], &mut har).unwrap();
+        let har: serde_json::Value = serde_json::from_slice(&har).unwrap();
+        assert_eq!(
+            har["log"]["entries"][0]["timings"],
+            json!({
+                "dns": 1.5,
+                "connect": 30.0,
+                "ssl": 20.0,
+                "send": 0.0,
+                "wait": 170.0,
+                "receive": 48.5,
+            })
+        );
+    }
+
+    /// HAR output matches the spec, so other tools can read it
+    #[test]
+    fn test_har_content() {
+        let mut har = Vec::new();
+        write_har(&[exchange()], &mut har).unwrap();
+        let har: serde_json::Value = serde_json::from_slice(&har).unwrap();
+        let entry = &har["log"]["entries"][0];
+        assert_eq!(har["log"]["version"], json!("1.2"));
+        assert_eq!(entry["startedDateTime"], json!("2023-11-14T22:13:20.123Z"));
+        assert_eq!(entry["time"], json!(250.0));
+        assert_eq!(entry["request"]["method"], json!("POST"));
+        assert_eq!(
+            entry["request"]["queryString"],
+            json!([{"name": "sort", "value": "name"}])
+        );
+        assert_eq!(
+            entry["request"]["postData"],
+            json!({"mimeType": "application/json", "text": r#"{"name":"Frodo"}"#})
+        );
+        assert_eq!(entry["response"]["status"], json!(201));
+        assert_eq!(entry["response"]["statusText"], json!("Created"));
+        assert_eq!(
+            entry["response"]["content"],
+            json!({
+                "size": 5,
+                "mimeType": "image/png",
+                "text": "iVBOR/8=",
+                "encoding": "base64",
+            })
+        );
+        assert_eq!(entry["_recipeId"], json!("create_user"));
+        assert_eq!(entry["_profileId"], json!("local"));
+    }
+
+    /// HAR files from other tools don't have a recipe ID, so we can't import
+    /// them. Bodies must be in an encoding we understand.
+    #[rstest]
+    #[case::no_recipe(
+        json!({}),
+        "only HAR files exported by Slumber can be imported"
+    )]
+    #[case::bad_encoding(
+        json!({"_recipeId": "r", "response": {"content": {"encoding": "gzip"}}}),
+        "Unsupported body encoding `gzip`"
+    )]
+    fn test_har_import_error(
+        #[case] overrides: serde_json::Value,
+        #[case] expected_error: &str,
+    ) {
+        let mut entry = json!({
+            "startedDateTime": "2023-11-14T22:13:20.123Z",
+            "time": 10.0,
+            "request": {
+                "method": "GET",
+                "url": "http://localhost/",
+                "httpVersion": "HTTP/1.1",
+                "headers": [],
+                "headersSize": -1,
+                "bodySize": 0,
+            },
+            "response": {
+                "status": 200,
+                "statusText": "OK",
+                "httpVersion": "HTTP/1.1",
+                "headers": [],
+                "content": {"size": 0, "mimeType": ""},
+                "headersSize": -1,
+                "bodySize": 0,
+            },
+            "timings": {"send": 0, "wait": 10, "receive": 0},
+        });
+        merge(&mut entry, overrides);
+        let har = json!({
+            "log": {
+                "version": "1.2",
+                "creator": {"name": "test", "version": "1"},
+                "entries": [entry],
+            }
+        });
+        assert_err!(read_har(har.to_string().as_bytes()), expected_error);
+    }
+
+    /// Recursively merge one JSON object into another
+    fn merge(target: &mut serde_json::Value, source: serde_json::Value) {
+        match (target, source) {
+            (
+                serde_json::Value::Object(target),
+                serde_json::Value::Object(source),
+            ) => {
+                for (key, value) in source {
+                    merge(target.entry(key).or_insert(json!({})), value);
+                }
+            }
+            (target, source) => *target = source,
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Classify why a request failed to get a response, so failures can be
+//! explained to the user and tallied in history
+
+use crate::http::{RequestBuildError, RequestCancelled, RequestError};
+use derive_more::{Display, FromStr};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, iter};
+use strum::EnumIter;
+
+/// Broad category of a failed request. These are stored in the database, so
+/// renaming a variant requires a migration.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Display,
+    FromStr,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumIter,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The request couldn't be built, e.g. a template failed to render. The
+    /// request was never sent.
+    Build,
+    /// The hostname couldn't be resolved
+    Dns,
+    /// The host was resolved, but a connection couldn't be opened, e.g. it
+    /// was refused or the network is unreachable
+    Connect,
+    /// The connection was opened but the TLS handshake failed, e.g. the
+    /// server's certificate isn't trusted
+    Tls,
+    /// The server didn't respond in time
+    Timeout,
+    /// The user cancelled the request before it finished
+    Cancelled,
+    /// Anything else, e.g. the SSH tunnel couldn't be opened or the response
+    /// body couldn't be read
+    Other,
+}
+
+impl FailureKind {
+    /// Categorize the error from a failed request
+    pub fn classify(error: &anyhow::Error) -> Self {
+        if error.is::<RequestCancelled>() {
+            return Self::Cancelled;
+        }
+        let Some(error) = error.downcast_ref::<reqwest::Error>() else {
+            return Self::Other;
+        };
+        if error.is_timeout() {
+            Self::Timeout
+        } else if error.is_connect() {
+            // reqwest doesn't distinguish DNS or TLS failures from other
+            // connection errors, so we have to check the messages of the
+            // underlying hyper/rustls errors
+            let messages: Vec<String> =
+                iter::successors(error.source(), |&error| error.source())
+                    .map(|error| error.to_string().to_lowercase())
+                    .collect();
+            if messages
+                .iter()
+                .any(|message| message.starts_with("dns error"))
+            {
+                Self::Dns
+            } else if messages.iter().any(|message| is_tls_message(message)) {
+                Self::Tls
+            } else {
+                Self::Connect
+            }
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Did the request fail before reaching the server? These are usually
+    /// caused by a wrong host or network settings, rather than the request
+    /// itself
+    pub fn is_connection(self) -> bool {
+        matches!(self, Self::Dns | Self::Connect)
+    }
+
+    /// Human-readable description of the failure
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Build => "Couldn't build request",
+            Self::Dns => "Couldn't resolve host",
+            Self::Connect => "Couldn't connect to host",
+            Self::Tls => "TLS handshake failed",
+            Self::Timeout => "Request timed out",
+            Self::Cancelled => "Request cancelled",
+            Self::Other => "Request failed",
+        }
+    }
+}
+
+impl RequestError {
+    /// Categorize why this request failed
+    pub fn kind(&self) -> FailureKind {
+        FailureKind::classify(&self.error)
+    }
+}
+
+impl RequestBuildError {
+    /// Build errors are all the same kind, unless the build was cancelled.
+    /// This mirrors [RequestError::kind] for convenience
+    pub fn kind(&self) -> FailureKind {
+        if self.error.is::<RequestCancelled>() {
+            FailureKind::Cancelled
+        } else {
+            FailureKind::Build
+        }
+    }
+}
+
+/// Does an error message from the TLS layer indicate a failed handshake?
+/// rustls errors don't survive as a concrete type through hyper, so we have to
+/// go by the message.
+fn is_tls_message(message: &str) -> bool {
+    ["certificate", "tls", "handshake", "fatal alert"]
+        .iter()
+        .any(|needle| message.contains(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use std::net::TcpListener;
+    use strum::IntoEnumIterator;
+
+    /// A refused connection is a connection error, but not a DNS error
+    #[rstest]
+    #[tokio::test]
+    async fn test_classify_connect() {
+        // Bind then drop a listener to get a port that nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let error = reqwest::get(format!("http://127.0.0.1:{port}"))
+            .await
+            .unwrap_err();
+        let kind = FailureKind::classify(&error.into());
+        assert_eq!(kind, FailureKind::Connect);
+        assert!(kind.is_connection());
+    }
+
+    #[rstest]
+    #[case::certificate("invalid peer certificate: UnknownIssuer", true)]
+    #[case::alert("received fatal alert: HandshakeFailure", true)]
+    #[case::refused("connection refused (os error 111)", false)]
+    fn test_is_tls_message(#[case] message: &str, #[case] expected: bool) {
+        assert_eq!(is_tls_message(message), expected);
+    }
+
+    #[rstest]
+    fn test_classify_other() {
+        let kind = FailureKind::classify(&anyhow!("Error opening SSH tunnel"));
+        assert_eq!(kind, FailureKind::Other);
+        assert!(!kind.is_connection());
+    }
+
+    #[rstest]
+    fn test_classify_cancelled() {
+        let kind = FailureKind::classify(&RequestCancelled.into());
+        assert_eq!(kind, FailureKind::Cancelled);
+        assert!(!kind.is_connection());
+    }
+
+    /// Kinds are stored in the DB by name, so they need to round trip
+    #[rstest]
+    fn test_parse() {
+        for kind in FailureKind::iter() {
+            assert_eq!(kind.to_string().parse::<FailureKind>().unwrap(), kind);
+        }
+    }
+}
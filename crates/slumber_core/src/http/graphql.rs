@@ -0,0 +1,211 @@
+//! Detect GraphQL requests, and pick apart their responses so data and errors
+//! can be shown separately
+
+use crate::http::{Exchange, RequestRecord};
+use itertools::Itertools;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::{self, Display, Formatter};
+
+/// Does this request look like a GraphQL request? GraphQL over HTTP is just a
+/// JSON body with a `query` field (or a `query` URL parameter for GETs), so
+/// there's no foolproof way to tell. We check for the common conventions.
+pub fn is_graphql_request(request: &RequestRecord) -> bool {
+    let body_has_query = request
+        .body
+        .as_ref()
+        .and_then(|body| serde_json::from_slice::<Value>(body).ok())
+        .is_some_and(|body| body.get("query").is_some_and(Value::is_string));
+    let url_has_query = request.url.path().ends_with("graphql")
+        && request.url.query_pairs().any(|(param, _)| param == "query");
+    body_has_query || url_has_query
+}
+
+/// The body of a response to a GraphQL request. Per the GraphQL spec, errors
+/// can be returned alongside data (a "partial" response), typically with a
+/// 200 status code, so checking the status isn't enough to spot a failure.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct GraphQlResponse {
+    #[serde(default)]
+    pub data: Option<Value>,
+    #[serde(default)]
+    pub errors: Vec<GraphQlError>,
+}
+
+impl GraphQlResponse {
+    /// Parse the response of a GraphQL request. Return `None` if the request
+    /// doesn't look like GraphQL, or the response doesn't have the shape of a
+    /// GraphQL response (e.g. an HTML error page from a proxy).
+    pub fn parse(exchange: &Exchange) -> Option<Self> {
+        if !is_graphql_request(&exchange.request) {
+            return None;
+        }
+        let body: Value =
+            serde_json::from_slice(exchange.response.body.bytes()).ok()?;
+        if body.get("data").is_none() && body.get("errors").is_none() {
+            return None;
+        }
+        serde_json::from_value(body).ok()
+    }
+}
+
+/// A single error from a GraphQL response
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct GraphQlError {
+    pub message: String,
+    /// Path to the field in `data` that caused the error
+    #[serde(default)]
+    pub path: Vec<PathSegment>,
+    /// Where in the query document the error occurred
+    #[serde(default)]
+    pub locations: Vec<Location>,
+    /// Server-specific detail, e.g. an error code
+    #[serde(default)]
+    pub extensions: Option<Value>,
+}
+
+/// One step in the path to the field that caused an error
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+/// A position in a GraphQL query document
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Render the error over multiple lines, with one line for each piece of
+/// context the server gave us
+impl Display for GraphQlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.path.is_empty() {
+            write!(f, "\n  Path: ")?;
+            for (i, segment) in self.path.iter().enumerate() {
+                match segment {
+                    PathSegment::Field(field) if i == 0 => {
+                        write!(f, "{field}")?
+                    }
+                    PathSegment::Field(field) => write!(f, ".{field}")?,
+                    PathSegment::Index(index) => write!(f, "[{index}]")?,
+                }
+            }
+        }
+        if !self.locations.is_empty() {
+            let locations = self
+                .locations
+                .iter()
+                .map(|location| {
+                    format!(
+                        "line {}, column {}",
+                        location.line, location.column
+                    )
+                })
+                .join("; ");
+            write!(f, "\n  Location: {locations}")?;
+        }
+        if let Some(extensions) = &self.extensions {
+            write!(f, "\n  Extensions: {extensions}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::ResponseRecord, test_util::Factory};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn exchange(
+        url: &str,
+        request_body: Option<Value>,
+        body: Value,
+    ) -> Exchange {
+        Exchange {
+            request: RequestRecord {
+                url: url.parse().unwrap(),
+                body: request_body.map(|body| body.to_string().into()),
+                ..RequestRecord::factory(())
+            }
+            .into(),
+            response: ResponseRecord {
+                body: body.to_string().into_bytes().into(),
+                ..ResponseRecord::factory(())
+            }
+            .into(),
+            ..Exchange::factory(())
+        }
+    }
+
+    #[rstest]
+    #[case::body(
+        "http://localhost/api",
+        Some(json!({"query": "{ me { name } }"})),
+        true
+    )]
+    #[case::url("http://localhost/graphql?query={me{name}}", None, true)]
+    #[case::rest("http://localhost/users", Some(json!({"name": "Ted"})), false)]
+    #[case::query_not_string(
+        "http://localhost/api",
+        Some(json!({"query": {"name": "Ted"}})),
+        false
+    )]
+    fn test_is_graphql_request(
+        #[case] url: &str,
+        #[case] body: Option<Value>,
+        #[case] expected: bool,
+    ) {
+        let exchange = exchange(url, body, json!({}));
+        assert_eq!(is_graphql_request(&exchange.request), expected);
+    }
+
+    /// Partial response, with both data and errors
+    #[test]
+    fn test_parse() {
+        let exchange = exchange(
+            "http://localhost/graphql",
+            Some(json!({"query": "{ users { name } }"})),
+            json!({
+                "data": {"users": [{"name": null}]},
+                "errors": [{
+                    "message": "Name is private",
+                    "path": ["users", 0, "name"],
+                    "locations": [{"line": 1, "column": 11}],
+                    "extensions": {"code": "FORBIDDEN"},
+                }],
+            }),
+        );
+        let response = GraphQlResponse::parse(&exchange).unwrap();
+        assert_eq!(response.data, Some(json!({"users": [{"name": null}]})));
+        assert_eq!(
+            response.errors[0].to_string(),
+            "Name is private
+  Path: users[0].name
+  Location: line 1, column 11
+  Extensions: {\"code\":\"FORBIDDEN\"}"
+        );
+    }
+
+    /// Non-GraphQL requests and responses are ignored
+    #[rstest]
+    #[case::not_graphql_request(None, json!({"data": 1}))]
+    #[case::not_graphql_response(
+        Some(json!({"query": "{ me }"})),
+        json!({"message": "Bad gateway"})
+    )]
+    fn test_parse_none(
+        #[case] request_body: Option<Value>,
+        #[case] body: Value,
+    ) {
+        let exchange = exchange("http://localhost/api", request_body, body);
+        assert_eq!(GraphQlResponse::parse(&exchange), None);
+    }
+}
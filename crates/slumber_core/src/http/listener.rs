@@ -0,0 +1,182 @@
+//! A minimal HTTP server for capturing incoming requests, e.g. webhooks and
+//! OAuth redirects. Every request gets the same canned response; the point is
+//! to record what was sent to us.
+
+use crate::{
+    db::CollectionDatabase,
+    http::{server::LocalServer, RequestId},
+    util::ResultTraced,
+};
+use anyhow::Context;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Incoming, service::service_fn};
+use reqwest::{header::HeaderMap, Method, StatusCode};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use tracing::info;
+
+/// An HTTP request received by a [Listener]
+#[derive(Clone, Debug)]
+#[cfg_attr(any(test, feature = "test"), derive(PartialEq))]
+pub struct CapturedRequest {
+    pub id: RequestId,
+    /// When the request was received
+    pub time: DateTime<Utc>,
+    pub method: Method,
+    /// Path and query of the request, e.g. `/callback?code=abc`
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+#[cfg(any(test, feature = "test"))]
+impl crate::test_util::Factory for CapturedRequest {
+    fn factory(_: ()) -> Self {
+        Self {
+            id: RequestId::new(),
+            time: Utc::now(),
+            method: Method::POST,
+            url: "/webhook".into(),
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"{\"event\": \"created\"}"),
+        }
+    }
+}
+
+/// An HTTP server that saves every request it receives to the database
+#[derive(Debug)]
+pub struct Listener {
+    server: LocalServer,
+    /// Status code to respond to every request with
+    status: StatusCode,
+}
+
+impl Listener {
+    /// Bind to a local address. Use port 0 to let the OS pick a port
+    pub async fn bind(
+        address: SocketAddr,
+        status: StatusCode,
+    ) -> anyhow::Result<Self> {
+        let server = LocalServer::bind(address).await?;
+        Ok(Self { server, status })
+    }
+
+    /// Get the address we're actually bound to. Useful if the port was 0
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        self.server.local_addr()
+    }
+
+    /// Accept requests until the future is dropped. Each request is stored in
+    /// the database, then passed to the callback. Errors on individual
+    /// connections are logged, but don't stop the listener.
+    pub async fn run(
+        self,
+        database: CollectionDatabase,
+        on_request: impl 'static + Fn(&CapturedRequest) + Send + Sync,
+    ) {
+        let on_request = Arc::new(on_request);
+        let status = self.status;
+        self.server
+            .serve(move || {
+                let database = database.clone();
+                let on_request = Arc::clone(&on_request);
+                service_fn(move |request| {
+                    let database = database.clone();
+                    let on_request = Arc::clone(&on_request);
+                    async move {
+                        // Don't fail the response if we can't store the
+                        // request; the error has already been traced
+                        if let Ok(request) = capture(request).await.traced() {
+                            info!(id = %request.id, "Captured request");
+                            let _ = database.insert_captured_request(&request);
+                            on_request(&request);
+                        }
+                        let mut response =
+                            hyper::Response::new(Full::new(Bytes::new()));
+                        *response.status_mut() = status;
+                        Ok::<_, Infallible>(response)
+                    }
+                })
+            })
+            .await;
+    }
+}
+
+/// Read an incoming request in full
+async fn capture(
+    request: hyper::Request<Incoming>,
+) -> anyhow::Result<CapturedRequest> {
+    let (parts, body) = request.into_parts();
+    let body = body
+        .collect()
+        .await
+        .context("Error reading request body")?
+        .to_bytes();
+    Ok(CapturedRequest {
+        id: RequestId::new(),
+        time: Utc::now(),
+        method: parts.method,
+        url: parts
+            .uri
+            .path_and_query()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "/".into()),
+        headers: parts.headers,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Factory;
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        sync::Mutex,
+    };
+
+    /// Send a request to the listener, and make sure it's captured and stored
+    #[tokio::test]
+    async fn test_listener() {
+        let database = CollectionDatabase::factory(());
+        let listener = Listener::bind(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0).into(),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+        let address = listener.local_addr().unwrap();
+        let received: Arc<Mutex<Vec<CapturedRequest>>> = Default::default();
+        let handle = tokio::spawn({
+            let database = database.clone();
+            let received = Arc::clone(&received);
+            listener.run(database, move |request| {
+                received.lock().unwrap().push(request.clone())
+            })
+        });
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{address}/webhook?source=test"))
+            .header("X-Event", "created")
+            .body("{\"id\": 1}")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        handle.abort();
+
+        let received = received.lock().unwrap();
+        let [request] = received.as_slice() else {
+            panic!("Expected exactly one request, got {received:?}");
+        };
+        assert_eq!(request.method, Method::POST);
+        assert_eq!(request.url, "/webhook?source=test");
+        assert_eq!(request.headers["X-Event"], "created");
+        assert_eq!(request.body.as_ref(), b"{\"id\": 1}");
+        assert_eq!(
+            database.get_captured_requests().unwrap(),
+            vec![request.clone()]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Send many requests concurrently and aggregate the results, for basic load
+//! testing
+
+use crate::http::Exchange;
+use chrono::Duration;
+use futures::future;
+use indexmap::IndexMap;
+use reqwest::StatusCode;
+use std::{collections::BTreeMap, future::Future, time::Instant};
+use tokio::sync::Semaphore;
+
+/// Aggregated results of many requests. Individual exchanges aren't retained,
+/// because there could be a lot of them. They're all in history anyway.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    /// Number of responses received with each status code
+    pub statuses: BTreeMap<StatusCode, usize>,
+    /// Number of requests that failed without a response, grouped by error
+    /// message, in order of first occurrence
+    pub errors: IndexMap<String, usize>,
+    /// Duration of every request that received a response. Sorted ascending,
+    /// so we can compute percentiles
+    latencies: Vec<Duration>,
+    /// Time from the first request being sent until the last one finished
+    pub elapsed: Duration,
+}
+
+impl LoadReport {
+    /// Total number of requests, including failures
+    pub fn total(&self) -> usize {
+        self.latencies.len() + self.error_count()
+    }
+
+    /// Number of requests that failed without receiving a response
+    pub fn error_count(&self) -> usize {
+        self.errors.values().sum()
+    }
+
+    /// Get the latency at a percentile (0-100), using the nearest-rank
+    /// method. Return `None` if no responses were received
+    pub fn percentile(&self, percentile: u8) -> Option<Duration> {
+        let rank = (usize::from(percentile.min(100)) * self.latencies.len())
+            .div_ceil(100);
+        self.latencies.get(rank.saturating_sub(1)).copied()
+    }
+
+    /// Average requests completed per second over the whole run
+    pub fn requests_per_second(&self) -> f64 {
+        let seconds = self.elapsed.num_milliseconds() as f64 / 1000.0;
+        if seconds > 0.0 {
+            self.total() as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    fn add(&mut self, result: anyhow::Result<Exchange>) {
+        match result {
+            Ok(exchange) => {
+                *self.statuses.entry(exchange.response.status).or_default() +=
+                    1;
+                self.latencies.push(exchange.duration());
+            }
+            Err(error) => {
+                *self.errors.entry(format!("{error:#}")).or_default() += 1
+            }
+        }
+    }
+}
+
+/// Run request futures with at most `concurrency` in flight at once, and
+/// aggregate their results. Each future should send a single request; this
+/// is generic so the caller can do extra work around each send. Futures are
+/// started in order.
+pub async fn run_concurrent<Fut>(
+    requests: impl IntoIterator<Item = Fut>,
+    concurrency: usize,
+) -> LoadReport
+where
+    Fut: Future<Output = anyhow::Result<Exchange>>,
+{
+    // Semaphore permits are handed out in FIFO order, so requests start in
+    // order
+    let semaphore = Semaphore::new(concurrency.max(1));
+    let start = Instant::now();
+    let results = future::join_all(requests.into_iter().map(|request| async {
+        // The semaphore is never closed, so this can't fail
+        let _permit = semaphore.acquire().await.expect("Semaphore closed");
+        request.await
+    }))
+    .await;
+
+    let mut report = LoadReport {
+        elapsed: Duration::from_std(start.elapsed()).unwrap_or_default(),
+        ..LoadReport::default()
+    };
+    for result in results {
+        report.add(result);
+    }
+    report.latencies.sort();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::{RequestRecord, ResponseRecord},
+        test_util::Factory,
+    };
+    use anyhow::anyhow;
+    use indexmap::indexmap;
+    use rstest::rstest;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Build an exchange with a status and duration
+    fn exchange(status: StatusCode, millis: i64) -> Exchange {
+        let exchange = Exchange::factory((
+            RequestRecord::factory(()),
+            ResponseRecord {
+                status,
+                ..ResponseRecord::factory(())
+            },
+        ));
+        Exchange {
+            end_time: exchange.start_time + Duration::milliseconds(millis),
+            ..exchange
+        }
+    }
+
+    /// Concurrency limit is respected, and results are aggregated
+    #[tokio::test]
+    async fn test_run_concurrent() {
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let requests = (0..10).map(|i| {
+            let in_flight = &in_flight;
+            let max_in_flight = &max_in_flight;
+            async move {
+                let count = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(count, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                match i {
+                    0..=6 => Ok(exchange(StatusCode::OK, 10 * (i + 1))),
+                    7 | 8 => Ok(exchange(StatusCode::NOT_FOUND, 5)),
+                    _ => Err(anyhow!("Connection refused")),
+                }
+            }
+        });
+
+        let report = run_concurrent(requests, 3).await;
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        assert_eq!(report.total(), 10);
+        assert_eq!(
+            report.statuses,
+            [(StatusCode::OK, 7), (StatusCode::NOT_FOUND, 2)].into()
+        );
+        assert_eq!(
+            report.errors,
+            indexmap! {"Connection refused".to_owned() => 1}
+        );
+        assert_eq!(report.error_count(), 1);
+        assert_eq!(report.percentile(0), Some(Duration::milliseconds(5)));
+        assert_eq!(report.percentile(50), Some(Duration::milliseconds(30)));
+        assert_eq!(report.percentile(100), Some(Duration::milliseconds(70)));
+    }
+
+    #[rstest]
+    #[case::p0(0, Some(1))]
+    #[case::p1(1, Some(1))]
+    #[case::p50(50, Some(50))]
+    #[case::p90(90, Some(90))]
+    #[case::p99(99, Some(99))]
+    #[case::p100(100, Some(100))]
+    fn test_percentile(#[case] percentile: u8, #[case] expected: Option<i64>) {
+        let report = LoadReport {
+            latencies: (1..=100).map(Duration::milliseconds).collect(),
+            ..LoadReport::default()
+        };
+        assert_eq!(
+            report.percentile(percentile),
+            expected.map(Duration::milliseconds)
+        );
+    }
+
+    #[test]
+    fn test_percentile_empty() {
+        assert_eq!(LoadReport::default().percentile(50), None);
+    }
+}
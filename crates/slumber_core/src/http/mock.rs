@@ -0,0 +1,492 @@
+//! A mock server for the APIs in a collection. Each incoming request is
+//! matched to a recipe by method and path, then answered with that recipe's
+//! most recent response from history. If the recipe has never been sent, its
+//! example response is used instead.
+
+use crate::{
+    collection::{Collection, ProfileId, Recipe, RecipeId, RecipeNode},
+    db::CollectionDatabase,
+    http::{
+        proxy::{error_response, remove_hop_by_hop},
+        server::LocalServer,
+        ResponseRecord,
+    },
+    util::ResultTraced,
+};
+use anyhow::Context;
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::service::service_fn;
+use reqwest::{header, Method, StatusCode};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+/// A request received by a [MockServer], and how it was answered
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct MockedRequest {
+    pub method: Method,
+    /// Path and query of the request, e.g. `/users?page=2`
+    pub url: String,
+    /// The recipe the request matched, if any
+    pub recipe_id: Option<RecipeId>,
+    pub status: StatusCode,
+}
+
+/// An HTTP server that mimics the APIs defined by a collection
+#[derive(Debug)]
+pub struct MockServer {
+    server: LocalServer,
+    routes: Arc<Vec<Route>>,
+}
+
+impl MockServer {
+    /// Bind to a local address, with a route for each recipe in the
+    /// collection. Use port 0 to let the OS pick a port
+    pub async fn bind(
+        address: SocketAddr,
+        collection: &Collection,
+    ) -> anyhow::Result<Self> {
+        let routes = Route::from_collection(collection)?;
+        let server = LocalServer::bind(address).await?;
+        Ok(Self {
+            server,
+            routes: Arc::new(routes),
+        })
+    }
+
+    /// Get the address we're actually bound to. Useful if the port was 0
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        self.server.local_addr()
+    }
+
+    /// Serve requests until the future is dropped. Responses are loaded from
+    /// history for the given profile. Each request is passed to the callback
+    /// after it's been answered. Errors on individual connections are logged,
+    /// but don't stop the server.
+    pub async fn run(
+        self,
+        database: CollectionDatabase,
+        profile_id: Option<ProfileId>,
+        on_request: impl 'static + Fn(&MockedRequest) + Send + Sync,
+    ) {
+        let routes = self.routes;
+        let on_request = Arc::new(on_request);
+        let profile_id = Arc::new(profile_id);
+        self.server
+            .serve(move || {
+                let routes = Arc::clone(&routes);
+                let database = database.clone();
+                let profile_id = Arc::clone(&profile_id);
+                let on_request = Arc::clone(&on_request);
+                service_fn(move |request| {
+                    let response = respond(
+                        &routes,
+                        &database,
+                        Option::as_ref(&profile_id),
+                        &request,
+                    );
+                    on_request(&MockedRequest {
+                        method: request.method().clone(),
+                        url: request
+                            .uri()
+                            .path_and_query()
+                            .map(ToString::to_string)
+                            .unwrap_or_else(|| "/".into()),
+                        recipe_id: response.recipe_id,
+                        status: response.response.status(),
+                    });
+                    async move { Ok::<_, Infallible>(response.response) }
+                })
+            })
+            .await;
+    }
+}
+
+/// A response, and the recipe it came from
+struct MockResponse {
+    recipe_id: Option<RecipeId>,
+    response: hyper::Response<Full<Bytes>>,
+}
+
+/// Find the route for a request, and get its response. The request body is
+/// never needed, so it isn't read.
+fn respond<B>(
+    routes: &[Route],
+    database: &CollectionDatabase,
+    profile_id: Option<&ProfileId>,
+    request: &hyper::Request<B>,
+) -> MockResponse {
+    let method = request.method();
+    let path = request.uri().path();
+    let Some(route) = Route::find(routes, method, path) else {
+        return MockResponse {
+            recipe_id: None,
+            response: error_response(
+                StatusCode::NOT_FOUND,
+                format!("No recipe matches `{method} {path}`"),
+            ),
+        };
+    };
+
+    let exchange = database
+        .get_latest_request(profile_id, &route.recipe_id)
+        .traced()
+        .ok()
+        .flatten();
+    let response = match (&exchange, &route.example) {
+        (Some(exchange), _) => to_response(&exchange.response),
+        (None, Some(example)) => to_response(example),
+        (None, None) => error_response(
+            StatusCode::NOT_FOUND,
+            format!(
+                "Recipe `{}` has no response in history and no example",
+                route.recipe_id
+            ),
+        ),
+    };
+    MockResponse {
+        recipe_id: Some(route.recipe_id.clone()),
+        response,
+    }
+}
+
+/// Build a response from a recorded one
+fn to_response(response: &ResponseRecord) -> hyper::Response<Full<Bytes>> {
+    let mut headers = response.headers.clone();
+    remove_hop_by_hop(&mut headers);
+    // Set by hyper based on the body we give it
+    headers.remove(header::CONTENT_LENGTH);
+
+    let mut output = hyper::Response::new(Full::new(Bytes::copy_from_slice(
+        response.body.bytes(),
+    )));
+    *output.status_mut() = response.status;
+    *output.headers_mut() = headers;
+    output
+}
+
+/// Requests that a recipe can answer
+#[derive(Debug)]
+struct Route {
+    recipe_id: RecipeId,
+    method: Method,
+    segments: Vec<Segment>,
+    /// The recipe's example response, converted up front so that any errors
+    /// in it show up at startup
+    example: Option<ResponseRecord>,
+}
+
+impl Route {
+    fn from_collection(collection: &Collection) -> anyhow::Result<Vec<Self>> {
+        collection
+            .recipes
+            .iter()
+            .filter_map(|(_, node)| match node {
+                RecipeNode::Recipe(recipe) => Some(recipe),
+                RecipeNode::Folder(_) => None,
+            })
+            .map(|recipe| Self::new(collection, recipe))
+            .collect()
+    }
+
+    fn new(collection: &Collection, recipe: &Recipe) -> anyhow::Result<Self> {
+        let url = collection.recipe_defaults(&recipe.id).url(recipe);
+        let example = recipe
+            .example
+            .as_ref()
+            .map(ResponseRecord::try_from)
+            .transpose()
+            .with_context(|| {
+                format!("Invalid header in example for recipe `{}`", recipe.id)
+            })?;
+        Ok(Self {
+            recipe_id: recipe.id.clone(),
+            method: recipe.method.into(),
+            segments: Segment::parse_url(&url.display()),
+            example,
+        })
+    }
+
+    /// Find the route that best matches a request. If multiple routes match,
+    /// the one with the most literal segments wins, e.g. `/users/me` beats
+    /// `/users/{{user_id}}`. After that, the first route wins.
+    fn find<'a>(
+        routes: &'a [Self],
+        method: &Method,
+        path: &str,
+    ) -> Option<&'a Self> {
+        let path: Vec<&str> = split_path(path).collect();
+        routes
+            .iter()
+            .filter(|route| route.matches(method, &path))
+            // max_by_key returns the *last* max, so reverse to get the first
+            .rev()
+            .max_by_key(|route| {
+                route
+                    .segments
+                    .iter()
+                    .filter(|segment| matches!(segment, Segment::Literal(_)))
+                    .count()
+            })
+    }
+
+    fn matches(&self, method: &Method, path: &[&str]) -> bool {
+        self.method == *method
+            && self.segments.len() == path.len()
+            && self.segments.iter().zip(path).all(|(segment, actual)| {
+                match segment {
+                    Segment::Literal(expected) => expected == actual,
+                    Segment::Wildcard => true,
+                }
+            })
+    }
+}
+
+/// One `/`-separated piece of a route's path
+#[derive(Debug, PartialEq)]
+enum Segment {
+    /// Matches only this exact text
+    Literal(String),
+    /// A segment containing a template, e.g. `{{user_id}}`. Matches anything
+    Wildcard,
+}
+
+impl Segment {
+    /// Get the path segments from a recipe's URL. The scheme and host are
+    /// dropped, and so is a template at the start of the URL, since it's
+    /// presumably the host, e.g. `{{host}}/users`.
+    fn parse_url(url: &str) -> Vec<Self> {
+        let path = match url.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("", |i| &rest[i..]),
+            None if url.starts_with("{{") => {
+                url.find("}}").map_or("", |i| &url[i + 2..])
+            }
+            None => url,
+        };
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        split_path(path)
+            .map(|segment| {
+                if segment.contains("{{") {
+                    Self::Wildcard
+                } else {
+                    Self::Literal(segment.to_owned())
+                }
+            })
+            .collect()
+    }
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+/// Convenience for building a route in tests
+#[cfg(test)]
+impl From<&str> for Route {
+    fn from(url: &str) -> Self {
+        Self {
+            recipe_id: url.to_owned().into(),
+            method: Method::GET,
+            segments: Segment::parse_url(url),
+            example: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::{Exchange, RequestRecord, ResponseBody},
+        test_util::Factory,
+        util::parse_yaml,
+    };
+    use pretty_assertions::assert_eq;
+    use reqwest::header::HeaderValue;
+    use rstest::rstest;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    #[rstest]
+    #[case::host_template(
+        "{{host}}/users/{{user_id}}",
+        vec![Segment::Literal("users".into()), Segment::Wildcard],
+    )]
+    #[case::absolute(
+        "https://example.com/api/users?page=1",
+        vec![Segment::Literal("api".into()), Segment::Literal("users".into())],
+    )]
+    #[case::template_host(
+        "https://{{host}}/users/",
+        vec![Segment::Literal("users".into())],
+    )]
+    #[case::relative("/users/me", vec![
+        Segment::Literal("users".into()),
+        Segment::Literal("me".into()),
+    ])]
+    #[case::partial_template(
+        "{{host}}/files/{{name}}.json",
+        vec![Segment::Literal("files".into()), Segment::Wildcard],
+    )]
+    #[case::root("https://example.com", vec![])]
+    fn test_parse_url(#[case] url: &str, #[case] expected: Vec<Segment>) {
+        assert_eq!(Segment::parse_url(url), expected);
+    }
+
+    #[rstest]
+    #[case::literal("/users", Some("{{host}}/users"))]
+    #[case::trailing_slash("/users/", Some("{{host}}/users"))]
+    #[case::wildcard("/users/3", Some("{{host}}/users/{{id}}"))]
+    #[case::most_specific("/users/me", Some("{{host}}/users/me"))]
+    #[case::first_wins("/posts/3", Some("{{host}}/{{kind}}/3"))]
+    #[case::too_long("/users/3/posts", None)]
+    #[case::no_match("/comments", None)]
+    fn test_find(#[case] path: &str, #[case] expected: Option<&str>) {
+        let routes: Vec<Route> = [
+            "{{host}}/users",
+            "{{host}}/users/{{id}}",
+            "{{host}}/users/me",
+            "{{host}}/{{kind}}/3",
+            "{{host}}/posts/{{id}}",
+        ]
+        .into_iter()
+        .map(Route::from)
+        .collect();
+        assert_eq!(
+            Route::find(&routes, &Method::GET, path)
+                .map(|route| route.recipe_id.as_str()),
+            expected
+        );
+        // Method has to match too
+        assert!(Route::find(&routes, &Method::POST, path).is_none());
+    }
+
+    /// Responses come from history first, then the recipe's example
+    #[tokio::test]
+    async fn test_mock_server() {
+        let collection: Collection = parse_yaml(
+            r#"
+requests:
+  list_users: !request
+    method: GET
+    url: "{{host}}/users"
+    example:
+      body: "[]"
+  get_user: !request
+    method: GET
+    url: "{{host}}/users/{{id}}"
+    example:
+      status: 201
+      headers:
+        Content-Type: application/json
+      body: '{"id": 1}'
+  delete_user: !request
+    method: DELETE
+    url: "{{host}}/users/{{id}}"
+"#
+            .as_bytes(),
+        )
+        .unwrap();
+        let database = CollectionDatabase::factory(());
+        let recorded = Exchange::factory((
+            RequestRecord::factory((None, "list_users".into())),
+            ResponseRecord {
+                status: StatusCode::OK,
+                headers: [(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("text/plain"),
+                )]
+                .into_iter()
+                .collect(),
+                body: ResponseBody::new("recorded".into()),
+            },
+        ));
+        database.insert_exchange(&recorded).unwrap();
+
+        let server = MockServer::bind(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0).into(),
+            &collection,
+        )
+        .await
+        .unwrap();
+        let address = server.local_addr().unwrap();
+        let handle = tokio::spawn(server.run(database, None, |_| {}));
+        let client = reqwest::Client::new();
+
+        // From history
+        let response = client
+            .get(format!("http://{address}/users"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(response.text().await.unwrap(), "recorded");
+
+        // From the example
+        let response = client
+            .get(format!("http://{address}/users/3"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/json"
+        );
+        assert_eq!(response.text().await.unwrap(), "{\"id\": 1}");
+
+        // Matching recipe, but nothing to respond with
+        let response = client
+            .delete(format!("http://{address}/users/3"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.text().await.unwrap(),
+            "Recipe `delete_user` has no response in history and no example"
+        );
+
+        // No matching recipe
+        let response = client
+            .post(format!("http://{address}/users"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.text().await.unwrap(),
+            "No recipe matches `POST /users`"
+        );
+
+        handle.abort();
+    }
+
+    /// Example headers are validated when the server starts
+    #[tokio::test]
+    async fn test_invalid_example() {
+        let collection: Collection = parse_yaml(
+            r#"
+requests:
+  list_users: !request
+    method: GET
+    url: "{{host}}/users"
+    example:
+      headers:
+        "Bad Header": value
+"#
+            .as_bytes(),
+        )
+        .unwrap();
+        let error = MockServer::bind(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0).into(),
+            &collection,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid header in example for recipe `list_users`"
+        );
+    }
+}
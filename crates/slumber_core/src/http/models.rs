@@ -4,30 +4,39 @@
 //! exchange is incomplete or failed.
 
 use crate::{
-    collection::{Authentication, ProfileId, RecipeBody, RecipeId},
+    collection::{
+        Authentication, ChainId, ProfileId, Recipe, RecipeBody, RecipeId,
+        ResponseExample,
+    },
     http::{
+        blob::{BlobRef, BlobStore},
         cereal,
         content_type::{ContentType, ResponseContent},
+        tunnel::PendingTunnel,
+        ExchangeTiming, ServerCertificate,
     },
     template::Template,
-    util::ResultTraced,
+    util::{expand_home, format_byte_size, ResultTraced},
 };
-use anyhow::Context;
+use anyhow::{bail, Context};
 use bytes::Bytes;
 use chrono::{DateTime, Duration, Utc};
 use derive_more::{Display, From, FromStr};
+use indexmap::IndexMap;
 use mime::Mime;
+use percent_encoding::percent_decode_str;
 use reqwest::{
-    header::{self, HeaderMap},
+    header::{self, HeaderMap, HeaderName, HeaderValue},
     Body, Client, Method, Request, StatusCode, Url,
 };
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
-    fmt::{Debug, Write},
+    fmt::{self, Debug, Formatter, Write},
     sync::{Arc, OnceLock},
 };
 use thiserror::Error;
+use tokio_util::sync::CancellationToken;
 use tracing::error;
 use uuid::Uuid;
 
@@ -70,6 +79,17 @@ pub struct RequestSeed {
     pub recipe_id: RecipeId,
     /// Configuration for the build
     pub options: BuildOptions,
+    /// What caused this request to be sent, if it was triggered by a chain
+    /// rather than sent directly by the user
+    pub trigger: Option<RequestTrigger>,
+    /// Free-text note from the user, to label the request in history
+    pub note: Option<String>,
+    /// Recipe to build from, for one-off requests that aren't defined in the
+    /// collection. If `None`, the recipe is looked up by `recipe_id`.
+    pub recipe: Option<Arc<Recipe>>,
+    /// Trigger this to cancel the request. Cancellation applies to both the
+    /// build and the send
+    pub cancel: CancellationToken,
 }
 
 impl RequestSeed {
@@ -78,6 +98,20 @@ impl RequestSeed {
             id: RequestId::new(),
             recipe_id,
             options,
+            trigger: None,
+            note: None,
+            recipe: None,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Create a seed for a one-off request that isn't defined in the
+    /// collection
+    pub fn scratch(recipe: Recipe) -> Self {
+        let recipe_id = recipe.id.clone();
+        Self {
+            recipe: Some(recipe.into()),
+            ..Self::new(recipe_id, BuildOptions::default())
         }
     }
 }
@@ -105,6 +139,19 @@ pub struct BuildOptions {
     /// Override body. This should *not* be used for form bodies, since those
     /// can be override on a field-by-field basis.
     pub body: Option<RecipeBody>,
+    /// Headers to set by name, applied after the index-based overrides in
+    /// `headers`. Each one replaces all headers of the same name (case
+    /// insensitive) in the recipe, including inherited ones, or is added if
+    /// the recipe has none. A `None` value removes the header.
+    pub extra_headers: Vec<(String, Option<Template>)>,
+    /// Query parameters to set by name, applied after the index-based
+    /// overrides in `query_parameters`. Any recipe parameter with the same
+    /// name as one of these is dropped, and these are appended after the
+    /// recipe's remaining parameters. Repeating a name adds multiple values.
+    pub extra_query_parameters: Vec<(String, Template)>,
+    /// Values for the recipe's declared parameters, keyed by name. Parameters
+    /// not given here fall back to their defaults.
+    pub parameters: IndexMap<String, String>,
 }
 
 /// A collection of modifications made to a particular section of a recipe
@@ -165,6 +212,17 @@ pub struct RequestTicket {
     pub(super) client: Client,
     /// Our brave little astronaut, ready to be launched...
     pub(super) request: Request,
+    /// SSH tunnel that must be open before launch. Opening is deferred until
+    /// send so that building a request never has side effects
+    pub(super) tunnel: Option<PendingTunnel>,
+    /// Response bodies over this size are streamed to the blob store
+    pub(super) stream_body_size: usize,
+    /// Only capture this much of the response body
+    pub(super) max_body_size: Option<usize>,
+    /// In offline mode, the response to return instead of sending the request
+    pub(super) fixture: Option<ResponseRecord>,
+    /// Carried over from the seed, so the request can be cancelled mid-flight
+    pub(super) cancel: CancellationToken,
 }
 
 impl RequestTicket {
@@ -189,6 +247,14 @@ pub struct Exchange {
     pub start_time: DateTime<Utc>,
     /// When did we finish receiving the *entire* response?
     pub end_time: DateTime<Utc>,
+    /// How long each phase of the exchange took. `None` for exchanges that
+    /// weren't timed, e.g. imported from a file or recorded before timing was
+    /// tracked
+    pub timing: Option<ExchangeTiming>,
+    /// Certificate the server presented, if the request was made over TLS.
+    /// `None` for plain HTTP, and for exchanges that were imported or
+    /// recorded before certificates were tracked
+    pub certificate: Option<ServerCertificate>,
 }
 
 impl Exchange {
@@ -200,12 +266,16 @@ impl Exchange {
 
 /// Metadata about an exchange. Useful in lists where request/response content
 /// isn't needed.
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct ExchangeSummary {
     pub id: RequestId,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
     pub status: StatusCode,
+    /// What caused the request to be sent, if it was triggered by a chain
+    pub trigger: Option<RequestTrigger>,
+    /// Note the user attached to the request when sending it
+    pub note: Option<String>,
 }
 
 impl From<&Exchange> for ExchangeSummary {
@@ -215,6 +285,8 @@ impl From<&Exchange> for ExchangeSummary {
             start_time: exchange.start_time,
             end_time: exchange.end_time,
             status: exchange.response.status,
+            trigger: exchange.request.environment.trigger.clone(),
+            note: exchange.request.note.clone(),
         }
     }
 }
@@ -250,6 +322,51 @@ pub struct RequestRecord {
     /// Body content as bytes. This should be decoded as needed. This will
     /// **not** be populated for bodies that are above the "large" threshold.
     pub body: Option<Bytes>,
+    /// Profile values and collection version used to build the request
+    #[serde(default)]
+    pub environment: EnvironmentSnapshot,
+    /// Free-text note the user attached when sending the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// The state of the collection when a request was built, so old requests can
+/// be interpreted later (e.g. "which host was this sent to?")
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnvironmentSnapshot {
+    /// Value of each profile field used to build the request, in the order
+    /// they were rendered. Sensitive values are masked.
+    pub profile_data: IndexMap<String, String>,
+    /// Git commit of the repository containing the collection file. `None` if
+    /// the collection isn't in a git repository
+    pub git_revision: Option<String>,
+    /// What caused the request to be sent, if it was triggered by a chain.
+    /// `None` for requests sent directly by the user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<RequestTrigger>,
+}
+
+/// Provenance of a request that was sent automatically, because a chain with a
+/// `trigger` referred to its recipe
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RequestTrigger {
+    /// Chain whose `trigger` caused the request to be sent
+    pub chain_id: ChainId,
+    /// Recipe of the request the user was sending when the chain was
+    /// rendered. If triggers are nested, this is the outermost request. `None`
+    /// if the chain wasn't rendered as part of a request, e.g. in a template
+    /// preview.
+    pub recipe_id: Option<RecipeId>,
+}
+
+impl Display for RequestTrigger {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "chain `{}`", self.chain_id)?;
+        if let Some(recipe_id) = &self.recipe_id {
+            write!(f, " while building `{recipe_id}`")?;
+        }
+        Ok(())
+    }
 }
 
 impl RequestRecord {
@@ -265,6 +382,7 @@ impl RequestRecord {
     pub(super) fn new(
         seed: RequestSeed,
         profile_id: Option<ProfileId>,
+        environment: EnvironmentSnapshot,
         request: &Request,
         max_body_size: usize,
     ) -> Self {
@@ -272,6 +390,8 @@ impl RequestRecord {
             id: seed.id,
             profile_id,
             recipe_id: seed.recipe_id,
+            environment,
+            note: seed.note,
 
             method: request.method().clone(),
             url: request.url().clone(),
@@ -342,6 +462,8 @@ impl crate::test_util::Factory for RequestRecord {
             url: "http://localhost/url".parse().unwrap(),
             headers: HeaderMap::new(),
             body: None,
+            environment: EnvironmentSnapshot::default(),
+            note: None,
         }
     }
 }
@@ -365,6 +487,8 @@ impl crate::test_util::Factory<(Option<ProfileId>, RecipeId)>
                 ("User-Agent", "slumber"),
             ]),
             body: None,
+            environment: EnvironmentSnapshot::default(),
+            note: None,
         }
     }
 }
@@ -427,6 +551,8 @@ impl crate::test_util::Factory<(RequestRecord, ResponseRecord)> for Exchange {
             response: response.into(),
             start_time: Utc::now(),
             end_time: Utc::now(),
+            timing: None,
+            certificate: None,
         }
     }
 }
@@ -450,14 +576,65 @@ pub struct ResponseRecord {
     pub body: ResponseBody,
 }
 
+/// Build a response from a canned example, e.g. for a mock server. Fails if
+/// any of the example's headers are invalid.
+impl TryFrom<&ResponseExample> for ResponseRecord {
+    type Error = anyhow::Error;
+
+    fn try_from(example: &ResponseExample) -> Result<Self, Self::Error> {
+        let mut headers = example
+            .headers
+            .iter()
+            .map(|(name, value)| -> anyhow::Result<_> {
+                Ok((HeaderName::try_from(name)?, HeaderValue::try_from(value)?))
+            })
+            .collect::<anyhow::Result<HeaderMap>>()?;
+        let body: Bytes = match &example.file {
+            Some(_) if !example.body.is_empty() => {
+                bail!("Example can't have both `body` and `file`")
+            }
+            Some(path) => {
+                let path = expand_home(path);
+                // Guess the content type from the extension, unless the user
+                // gave one explicitly
+                if !headers.contains_key(header::CONTENT_TYPE) {
+                    if let Ok(content_type) = ContentType::from_path(&path) {
+                        headers.insert(
+                            header::CONTENT_TYPE,
+                            HeaderValue::try_from(
+                                content_type.to_mime().as_ref(),
+                            )?,
+                        );
+                    }
+                }
+                std::fs::read(&path)
+                    .with_context(|| format!("Error reading file {path:?}"))?
+                    .into()
+            }
+            None => example.body.clone().into(),
+        };
+        Ok(Self {
+            status: example.status,
+            headers,
+            body: ResponseBody::new(body),
+        })
+    }
+}
+
 impl ResponseRecord {
     /// Attempt to parse the body of this response, and store it in the body
     /// struct. If parsing fails, we'll store `None` instead.
     pub fn parse_body(&self) {
-        let body = ContentType::parse_response(self)
-            .context("Error parsing response body")
-            .traced()
-            .ok();
+        // A streamed body is too large to parse, and we only have the
+        // beginning of it anyway
+        let body = if self.body.blob().is_some() {
+            None
+        } else {
+            ContentType::parse_response(self)
+                .context("Error parsing response body")
+                .traced()
+                .ok()
+        };
         // Store whether we succeeded or not, so we know not to try again
         if self.body.parsed.set(body).is_err() {
             // Unfortunately we don't have any helpful context to include here.
@@ -480,7 +657,8 @@ impl ResponseRecord {
     /// Get a suggested file name for the content of this response. First we'll
     /// check the Content-Disposition header. If it's missing or doesn't have a
     /// file name, we'll check the Content-Type to at least guess at an
-    /// extension.
+    /// extension. Any directory components are stripped from the name, so it's
+    /// safe to join onto a directory.
     pub fn file_name(&self) -> Option<String> {
         self.headers
             .get(header::CONTENT_DISPOSITION)
@@ -488,14 +666,35 @@ impl ResponseRecord {
                 // Parse header for the `filename="{}"` parameter
                 // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Content-Disposition
                 let value = value.to_str().ok()?;
-                value.split(';').find_map(|part| {
-                    let (key, value) = part.trim().split_once('=')?;
-                    if key == "filename" {
-                        Some(value.trim_matches('"').to_owned())
-                    } else {
-                        None
-                    }
-                })
+                let parameters: Vec<(&str, &str)> = value
+                    .split(';')
+                    .filter_map(|part| part.trim().split_once('='))
+                    .collect();
+                let get = |name: &str| {
+                    parameters.iter().find_map(|(key, value)| {
+                        key.eq_ignore_ascii_case(name).then_some(*value)
+                    })
+                };
+                // `filename*` is the extended form, which supports non-ASCII
+                // names, so prefer it
+                // https://www.rfc-editor.org/rfc/rfc6266#section-4.3
+                let file_name = get("filename*")
+                    .and_then(decode_extended_value)
+                    .or_else(|| {
+                        get("filename")
+                            .map(|value| value.trim_matches('"').to_owned())
+                    })?;
+                // Never let the server pick the directory
+                let file_name = file_name
+                    .rsplit(['/', '\\'])
+                    .next()
+                    .unwrap_or_default()
+                    .to_owned();
+                if matches!(file_name.as_str(), "" | "." | "..") {
+                    None
+                } else {
+                    Some(file_name)
+                }
             })
             .or_else(|| {
                 // Grab the extension from the Content-Type header. Don't use
@@ -507,13 +706,43 @@ impl ResponseRecord {
     }
 }
 
+/// How much of a response body has been downloaded so far. Reported while a
+/// large or slow body is being loaded.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DownloadProgress {
+    /// Bytes received so far
+    pub downloaded: usize,
+    /// Total size of the body, from the `Content-Length` header. `None` if the
+    /// header is missing, e.g. for a chunked or compressed response
+    pub total: Option<u64>,
+}
+
+impl Display for DownloadProgress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_byte_size(self.downloaded))?;
+        if let Some(total) = self.total {
+            write!(f, " of {}", format_byte_size(total as usize))?;
+        }
+        Ok(())
+    }
+}
+
 /// HTTP response body. Content is stored as bytes because it may not
 /// necessarily be valid UTF-8. Converted to text only as needed.
 #[derive(Default, Deserialize)]
 #[serde(from = "Bytes")] // Can't use into=Bytes because that requires cloning
 pub struct ResponseBody {
-    /// Raw body
+    /// Raw body. For a streamed body, this is just the beginning
     data: Bytes,
+    /// If the body was too large to hold in memory, it was streamed to the
+    /// blob store as it downloaded. This refers to the full content
+    #[serde(skip)]
+    blob: Option<BlobRef>,
+    /// If the body was larger than the configured capture limit, only a
+    /// prefix was kept. This is the size of the complete body, as sent by the
+    /// server
+    #[serde(skip)]
+    full_size: Option<u64>,
     /// For responses of a known content type, we can parse the body into a
     /// real data structure. This is populated *eagerly*. Call
     /// [ResponseRecord::parse_body] to set the parsed body.
@@ -525,15 +754,51 @@ impl ResponseBody {
     pub fn new(data: Bytes) -> Self {
         Self {
             data,
+            blob: None,
+            full_size: None,
             parsed: Default::default(),
         }
     }
 
-    /// Raw content bytes
+    /// Create a body whose full content is in the blob store. `preview` is the
+    /// beginning of the content, to be used where the full body isn't needed
+    pub fn streamed(preview: Bytes, blob: BlobRef) -> Self {
+        Self {
+            data: preview,
+            blob: Some(blob),
+            full_size: None,
+            parsed: Default::default(),
+        }
+    }
+
+    /// Mark this body as a truncated prefix of a larger body, which was
+    /// `full_size` bytes in total
+    pub fn truncated(mut self, full_size: u64) -> Self {
+        self.full_size = Some(full_size);
+        self
+    }
+
+    /// Raw content bytes. For a streamed body, this is only the preview. Use
+    /// [Self::load_bytes] to get the full content.
     pub fn bytes(&self) -> &[u8] {
         &self.data
     }
 
+    /// Reference to the full content in the blob store, if the body was
+    /// streamed because it's large
+    pub fn blob(&self) -> Option<&BlobRef> {
+        self.blob.as_ref()
+    }
+
+    /// Get the full content. For a streamed body, this reads the entire blob
+    /// into memory, so only use it when the user asks for the whole body
+    pub fn load_bytes(&self) -> anyhow::Result<Bytes> {
+        match &self.blob {
+            Some(blob) => Ok(BlobStore::global().read(blob)?.into()),
+            None => Ok(self.data.clone()),
+        }
+    }
+
     /// Owned raw content bytes
     pub fn into_bytes(self) -> Bytes {
         self.data
@@ -544,9 +809,19 @@ impl ResponseBody {
         std::str::from_utf8(&self.data).ok()
     }
 
-    /// Get body size, in bytes
+    /// Get body size, in bytes. For a streamed body, this is the size of the
+    /// full content. For a truncated body, this is only the captured portion
     pub fn size(&self) -> usize {
-        self.bytes().len()
+        match &self.blob {
+            Some(blob) => blob.size as usize,
+            None => self.data.len(),
+        }
+    }
+
+    /// If the body was truncated because it exceeded the capture limit, get
+    /// the size of the complete body that the server sent
+    pub fn full_size(&self) -> Option<u64> {
+        self.full_size
     }
 
     /// Get the parsed version of this body. Must haved call
@@ -564,7 +839,7 @@ impl Debug for ResponseBody {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // Don't print the actual body because it could be huge
         f.debug_tuple("Body")
-            .field(&format!("<{} bytes>", self.data.len()))
+            .field(&format!("<{} bytes>", self.size()))
             .finish()
     }
 }
@@ -617,6 +892,8 @@ impl PartialEq for ResponseBody {
     fn eq(&self, other: &Self) -> bool {
         // Ignore derived data
         self.data == other.data
+            && self.blob == other.blob
+            && self.full_size == other.full_size
     }
 }
 
@@ -653,6 +930,12 @@ impl PartialEq for RequestBuildError {
     }
 }
 
+/// The user cancelled a request before it finished. This is the underlying
+/// error of the [RequestBuildError] or [RequestError] for that request.
+#[derive(Debug, Error)]
+#[error("Request cancelled")]
+pub struct RequestCancelled;
+
 /// An error that can occur during a request. This does *not* including building
 /// errors.
 #[derive(Debug, Error)]
@@ -662,8 +945,8 @@ impl PartialEq for RequestBuildError {
     .request.id,
 )]
 pub struct RequestError {
-    /// Underlying error. This will always be a `reqwest::Error`, but wrapping
-    /// it in anyhow makes it easier to render
+    /// Underlying error. This is usually a `reqwest::Error`, but can also be
+    /// a failure to open the profile's SSH tunnel
     #[source]
     pub error: anyhow::Error,
 
@@ -685,6 +968,22 @@ impl PartialEq for RequestError {
     }
 }
 
+/// Decode an RFC 8187 extended parameter value, e.g. `UTF-8''na%C3%AFve.txt`.
+/// Only UTF-8 is supported, which is all that servers are required to send.
+fn decode_extended_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+    if !charset.eq_ignore_ascii_case("utf-8") {
+        return None;
+    }
+    percent_decode_str(encoded)
+        .decode_utf8()
+        .ok()
+        .map(String::from)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -724,6 +1023,25 @@ mod tests {
         },
         Some("data.jpeg")
     )]
+    #[case::extended(
+        ResponseRecord {
+            headers: header_map(indexmap! {
+                "content-disposition" => "attachment; filename=\"naive.txt\"; \
+                    filename*=UTF-8''na%C3%AFve.txt",
+            }),
+            ..ResponseRecord::factory(())
+        },
+        Some("naïve.txt")
+    )]
+    #[case::path_traversal(
+        ResponseRecord {
+            headers: header_map(indexmap! {
+                "content-disposition" => "attachment; filename=\"../../.bashrc\"",
+            }),
+            ..ResponseRecord::factory(())
+        },
+        Some(".bashrc")
+    )]
     #[case::none(ResponseRecord::factory(()), None)]
     fn test_file_name(
         #[case] response: ResponseRecord,
@@ -732,6 +1050,20 @@ mod tests {
         assert_eq!(response.file_name().as_deref(), expected);
     }
 
+    #[rstest]
+    #[case::known_total(Some(5_000_000), "1.2 MB of 5.0 MB")]
+    #[case::unknown_total(None, "1.2 MB")]
+    fn test_download_progress(
+        #[case] total: Option<u64>,
+        #[case] expected: &str,
+    ) {
+        let progress = DownloadProgress {
+            downloaded: 1_200_000,
+            total,
+        };
+        assert_eq!(progress.to_string(), expected);
+    }
+
     #[test]
     fn test_to_curl() {
         let headers = indexmap! {
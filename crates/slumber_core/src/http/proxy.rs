@@ -0,0 +1,461 @@
+//! An HTTP forward proxy that records traffic from other clients into request
+//! history. Plain HTTP requests are recorded in full. HTTPS requests (via
+//! `CONNECT`) are tunneled through untouched, because we can't see inside them
+//! without intercepting TLS.
+
+use crate::{
+    collection::{Method as RecipeMethod, Recipe, RecipeBody, RecipeId},
+    db::CollectionDatabase,
+    http::{
+        content_type::ContentType, server::LocalServer, Exchange, RequestId,
+        RequestRecord, ResponseBody, ResponseRecord,
+    },
+    template::Template,
+    util::ResultTraced,
+};
+use anyhow::{anyhow, Context};
+use bytes::Bytes;
+use chrono::Utc;
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Incoming, service::service_fn};
+use hyper_util::rt::TokioIo;
+use indexmap::IndexMap;
+use reqwest::{
+    header::{self, HeaderMap, HeaderName},
+    redirect, Client, Method, StatusCode, Url,
+};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use tokio::net::TcpStream;
+use tracing::{error, info};
+
+/// Recipe ID that proxied requests are stored under in history, since they
+/// don't correspond to any recipe in the collection
+pub const PROXY_RECIPE_ID: &str = "_proxy";
+
+/// Headers that apply to a single connection, and therefore shouldn't be
+/// forwarded by a proxy. See RFC 9110 section 7.6.1
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// An HTTP proxy that stores every exchange it forwards in the database
+#[derive(Debug)]
+pub struct Proxy {
+    server: LocalServer,
+    client: Client,
+}
+
+impl Proxy {
+    /// Bind to a local address. Use port 0 to let the OS pick a port
+    pub async fn bind(address: SocketAddr) -> anyhow::Result<Self> {
+        let server = LocalServer::bind(address).await?;
+        // Redirects should be passed back to the client so it can decide what
+        // to do. Ignore proxy env vars so we don't end up proxying to ourself
+        let client = Client::builder()
+            .redirect(redirect::Policy::none())
+            .no_proxy()
+            .build()?;
+        Ok(Self { server, client })
+    }
+
+    /// Get the address we're actually bound to. Useful if the port was 0
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        self.server.local_addr()
+    }
+
+    /// Forward requests until the future is dropped. Each completed exchange
+    /// is stored in the database, then passed to the callback. Errors on
+    /// individual connections are logged, but don't stop the proxy.
+    pub async fn run(
+        self,
+        database: CollectionDatabase,
+        on_exchange: impl 'static + Fn(&Exchange) + Send + Sync,
+    ) {
+        let client = self.client;
+        let on_exchange = Arc::new(on_exchange);
+        self.server
+            .serve(move || {
+                let client = client.clone();
+                let database = database.clone();
+                let on_exchange = Arc::clone(&on_exchange);
+                service_fn(move |request| {
+                    let client = client.clone();
+                    let database = database.clone();
+                    let on_exchange = Arc::clone(&on_exchange);
+                    async move {
+                        Ok::<_, Infallible>(
+                            handle(&client, &database, &*on_exchange, request)
+                                .await,
+                        )
+                    }
+                })
+            })
+            .await;
+    }
+}
+
+/// Handle a single request from a proxy client
+async fn handle(
+    client: &Client,
+    database: &CollectionDatabase,
+    on_exchange: &(dyn Fn(&Exchange) + Send + Sync),
+    request: hyper::Request<Incoming>,
+) -> hyper::Response<Full<Bytes>> {
+    if request.method() == Method::CONNECT {
+        return tunnel(request);
+    }
+    match forward(client, request).await.traced() {
+        Ok(exchange) => {
+            info!(id = %exchange.id, "Proxied request");
+            // Don't fail the response if we can't store the exchange; the
+            // error has already been traced
+            let _ = database.insert_exchange(&exchange);
+            on_exchange(&exchange);
+            to_response(&exchange.response)
+        }
+        Err(error) => {
+            error_response(StatusCode::BAD_GATEWAY, format!("{error:#}"))
+        }
+    }
+}
+
+/// Send a request on to its destination, and record the exchange
+async fn forward(
+    client: &Client,
+    request: hyper::Request<Incoming>,
+) -> anyhow::Result<Exchange> {
+    let start_time = Utc::now();
+    let (parts, body) = request.into_parts();
+    // Clients send the full URL to a proxy
+    let url: Url = parts.uri.to_string().parse().with_context(|| {
+        format!(
+            "Invalid URL `{}`; proxy requests must be absolute",
+            parts.uri
+        )
+    })?;
+    let body = body
+        .collect()
+        .await
+        .context("Error reading request body")?
+        .to_bytes();
+    let mut headers = parts.headers;
+    remove_hop_by_hop(&mut headers);
+    // The client will set this based on the URL
+    headers.remove(header::HOST);
+
+    let response = client
+        .request(parts.method.clone(), url.clone())
+        .headers(headers.clone())
+        .body(body.clone())
+        .send()
+        .await
+        .with_context(|| format!("Error forwarding request to {url}"))?;
+    let status = response.status();
+    let mut response_headers = response.headers().clone();
+    remove_hop_by_hop(&mut response_headers);
+    let response_body = response
+        .bytes()
+        .await
+        .context("Error reading response body")?;
+
+    let id = RequestId::new();
+    Ok(Exchange {
+        id,
+        request: RequestRecord {
+            id,
+            profile_id: None,
+            recipe_id: PROXY_RECIPE_ID.to_owned().into(),
+            method: parts.method,
+            url,
+            headers,
+            body: (!body.is_empty()).then_some(body),
+            environment: Default::default(),
+            note: None,
+        }
+        .into(),
+        response: ResponseRecord {
+            status,
+            headers: response_headers,
+            body: ResponseBody::new(response_body),
+        }
+        .into(),
+        start_time,
+        end_time: Utc::now(),
+        timing: None,
+        certificate: None,
+    })
+}
+
+/// Open a raw TCP tunnel for a `CONNECT` request. The tunnel runs in the
+/// background once the client upgrades the connection. Nothing in the tunnel
+/// is recorded.
+fn tunnel(request: hyper::Request<Incoming>) -> hyper::Response<Full<Bytes>> {
+    let Some(authority) = request.uri().authority().map(ToString::to_string)
+    else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "CONNECT requests must specify a host".into(),
+        );
+    };
+    info!(%authority, "Opening tunnel");
+    tokio::spawn(async move {
+        let result: anyhow::Result<()> = async {
+            let upgraded = hyper::upgrade::on(request).await?;
+            let mut server = TcpStream::connect(&authority).await?;
+            tokio::io::copy_bidirectional(
+                &mut TokioIo::new(upgraded),
+                &mut server,
+            )
+            .await?;
+            Ok(())
+        }
+        .await;
+        if let Err(error) = result {
+            error!(%authority, error = format!("{error:#}"), "Tunnel error");
+        }
+    });
+    hyper::Response::new(Full::default())
+}
+
+pub(super) fn remove_hop_by_hop(headers: &mut HeaderMap) {
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(*name);
+    }
+}
+
+/// Build a response to send back to the proxy client
+fn to_response(response: &ResponseRecord) -> hyper::Response<Full<Bytes>> {
+    let mut output = hyper::Response::new(Full::new(Bytes::copy_from_slice(
+        response.body.bytes(),
+    )));
+    *output.status_mut() = response.status;
+    *output.headers_mut() = response.headers.clone();
+    output
+}
+
+pub(super) fn error_response(
+    status: StatusCode,
+    message: String,
+) -> hyper::Response<Full<Bytes>> {
+    let mut response = hyper::Response::new(Full::new(message.into()));
+    *response.status_mut() = status;
+    response
+}
+
+impl RequestRecord {
+    /// Convert a recorded request into a recipe that would send the same
+    /// request again. Values are imported literally, i.e. they won't be parsed
+    /// as templates.
+    pub fn to_recipe(&self, id: RecipeId) -> anyhow::Result<Recipe> {
+        let method: RecipeMethod = self.method.to_string().try_into()?;
+
+        let mut url = self.url.clone();
+        let query = url
+            .query_pairs()
+            .map(|(param, value)| {
+                (param.into_owned(), Template::raw(value.into_owned()))
+            })
+            .collect();
+        url.set_query(None);
+
+        let content_type = ContentType::from_headers(&self.headers).ok();
+        let body = self
+            .body
+            .as_ref()
+            .map(|body| to_recipe_body(body, content_type))
+            .transpose()?;
+        // If the body has a known type, the recipe will set the header for us
+        let skip_headers: &[HeaderName] = if content_type.is_some() {
+            &[header::CONTENT_LENGTH, header::CONTENT_TYPE]
+        } else {
+            &[header::CONTENT_LENGTH]
+        };
+        let headers: IndexMap<String, Template> = self
+            .headers
+            .iter()
+            .filter(|(name, _)| !skip_headers.contains(name))
+            .map(|(name, value)| {
+                let value = value.to_str().with_context(|| {
+                    format!("Header `{name}` is not valid UTF-8")
+                })?;
+                Ok((name.to_string(), Template::raw(value.to_owned())))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Recipe {
+            id,
+            name: None,
+            method,
+            url: Template::raw(url.to_string()),
+            body,
+            authentication: None,
+            signature: None,
+            certificate: None,
+            danger_skip_tls_verify: false,
+            baseline_ignore: vec![],
+            assertions: vec![],
+            display_query: None,
+            example: None,
+            parameters: IndexMap::new(),
+            query,
+            headers,
+        })
+    }
+}
+
+fn to_recipe_body(
+    body: &[u8],
+    content_type: Option<ContentType>,
+) -> anyhow::Result<RecipeBody> {
+    let text = std::str::from_utf8(body)
+        .map_err(|_| anyhow!("Binary request bodies aren't supported"))?;
+    if let Some(ContentType::Json) = content_type {
+        // Re-serialize it to get consistent formatting
+        if let Ok(value) = serde_json::from_str(text) {
+            return Ok(RecipeBody::untemplated_json(value));
+        }
+    }
+    Ok(RecipeBody::Raw {
+        body: Template::raw(text.to_owned()),
+        content_type,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assert_err, test_util::Factory};
+    use pretty_assertions::assert_eq;
+    use reqwest::header::{HeaderValue, CONTENT_TYPE};
+    use serde_json::json;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+    use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+    /// Send a request through the proxy, and make sure it's forwarded and
+    /// recorded
+    #[tokio::test]
+    async fn test_proxy() {
+        let server = MockServer::start().await;
+        Mock::given(matchers::method("POST"))
+            .and(matchers::path("/users"))
+            .and(matchers::header("X-Test", "1"))
+            .and(matchers::body_string("{\"name\": \"Bob\"}"))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .insert_header("X-Server", "mock")
+                    .set_body_string("created"),
+            )
+            .mount(&server)
+            .await;
+
+        let database = CollectionDatabase::factory(());
+        let proxy =
+            Proxy::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0).into())
+                .await
+                .unwrap();
+        let address = proxy.local_addr().unwrap();
+        let handle = tokio::spawn(proxy.run(database.clone(), |_| {}));
+
+        let client = Client::builder()
+            .proxy(reqwest::Proxy::http(format!("http://{address}")).unwrap())
+            .build()
+            .unwrap();
+        let url = format!("{}/users?page=1", server.uri());
+        let response = client
+            .post(&url)
+            .header("X-Test", "1")
+            .body("{\"name\": \"Bob\"}")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["X-Server"], "mock");
+        assert_eq!(response.text().await.unwrap(), "created");
+        handle.abort();
+
+        let [summary] = &database
+            .get_all_requests(None, &PROXY_RECIPE_ID.to_owned().into())
+            .unwrap()[..]
+        else {
+            panic!("Expected exactly one recorded request");
+        };
+        let exchange = database.get_request(summary.id).unwrap().unwrap();
+        assert_eq!(exchange.request.method, Method::POST);
+        assert_eq!(exchange.request.url.as_str(), url);
+        assert_eq!(exchange.request.headers["X-Test"], "1");
+        assert_eq!(
+            exchange.request.body.as_deref(),
+            Some(b"{\"name\": \"Bob\"}".as_slice())
+        );
+        assert_eq!(exchange.response.status, StatusCode::CREATED);
+        assert_eq!(exchange.response.body.bytes(), b"created");
+    }
+
+    #[test]
+    fn test_to_recipe() {
+        let request = RequestRecord {
+            method: Method::POST,
+            url: "https://example.com/users?page=1&sort=name"
+                .parse()
+                .unwrap(),
+            headers: [
+                (CONTENT_TYPE, HeaderValue::from_static("application/json")),
+                (header::CONTENT_LENGTH, HeaderValue::from_static("15")),
+                (header::ACCEPT, HeaderValue::from_static("*/*")),
+            ]
+            .into_iter()
+            .collect(),
+            body: Some(Bytes::from_static(b"{\"name\":\"Bob\"}")),
+            ..RequestRecord::factory(())
+        };
+        let recipe = request.to_recipe("create_user".into()).unwrap();
+        assert_eq!(
+            recipe,
+            Recipe {
+                id: "create_user".into(),
+                name: None,
+                method: RecipeMethod::Post,
+                url: Template::raw("https://example.com/users".into()),
+                body: Some(RecipeBody::untemplated_json(
+                    json!({"name": "Bob"})
+                )),
+                authentication: None,
+                signature: None,
+                certificate: None,
+                danger_skip_tls_verify: false,
+                baseline_ignore: vec![],
+                assertions: vec![],
+                display_query: None,
+                example: None,
+                parameters: IndexMap::new(),
+                query: vec![
+                    ("page".into(), Template::raw("1".into())),
+                    ("sort".into(), Template::raw("name".into())),
+                ],
+                headers: [("accept".into(), Template::raw("*/*".into()))]
+                    .into_iter()
+                    .collect(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_to_recipe_binary_body() {
+        let request = RequestRecord {
+            body: Some(Bytes::from_static(b"\xff\xfe")),
+            ..RequestRecord::factory(())
+        };
+        assert_err!(
+            request.to_recipe("binary".into()),
+            "Binary request bodies aren't supported"
+        );
+    }
+}
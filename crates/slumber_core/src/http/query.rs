@@ -2,11 +2,40 @@
 
 use crate::http::content_type::ResponseContent;
 use derive_more::{Display, FromStr};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json_path::{ExactlyOneError, JsonPath};
 use std::borrow::Cow;
+use sxd_document::{dom::Document, Package};
+use sxd_xpath::{Context, ExecutionError, Factory, ParserError, Value};
 use thiserror::Error;
 
+/// A selector to extract a single value from a chain. Plain string selectors
+/// that start with `$` are JSONPath, which can be applied to any structured
+/// content type. Any other string is XPath, which is applied to XML or HTML.
+/// Regex selectors are given with the `!regex` tag, and work on any text.
+///
+/// Serialization is implemented manually, to support both strings and tags.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selector {
+    JsonPath(Query),
+    XPath(XPath),
+    Regex(RegexSelector),
+}
+
+impl FromStr for Selector {
+    type Err = SelectorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('$') {
+            let query = s.parse().map_err(SelectorParseError::JsonPath)?;
+            Ok(Self::JsonPath(query))
+        } else {
+            Ok(Self::XPath(s.parse()?))
+        }
+    }
+}
+
 /// A wrapper around a JSONPath. This combines some common behavior, and will
 /// make it easy to swap out the query language in the future if necessary.
 #[derive(Clone, Debug, Display, FromStr, PartialEq, Serialize, Deserialize)]
@@ -30,6 +59,25 @@ impl Query {
         content_type.parse_json(Cow::Owned(queried))
     }
 
+    /// Apply a query to some content for display, returning the result in the
+    /// original format. Unlike [Self::query], a single result is *not* wrapped
+    /// in an array, so a query that selects one field of an envelope shows
+    /// just the contents of that field.
+    pub fn query_display(
+        &self,
+        value: &dyn ResponseContent,
+    ) -> Box<dyn ResponseContent> {
+        let content_type = value.content_type();
+        let json_value = value.to_json();
+        let mut results = self.0.query(&json_value).all();
+        let queried = if results.len() == 1 {
+            results.remove(0).clone()
+        } else {
+            serde_json::Value::Array(results.into_iter().cloned().collect())
+        };
+        content_type.parse_json(Cow::Owned(queried))
+    }
+
     /// Apply a query to some content, returning a string. The query should
     /// return a single result. If it's a scalar, that will be stringified. If
     /// it's an array/object, it'll be converted back into its input format,
@@ -59,17 +107,270 @@ impl Query {
 
         Ok(stringified)
     }
+
+    /// Get every value matched by this query from a JSON value, in document
+    /// order
+    pub fn query_json<'a>(
+        &self,
+        value: &'a serde_json::Value,
+    ) -> Vec<&'a serde_json::Value> {
+        self.0.query(value).all()
+    }
+
+    /// Delete every value matched by this query from a JSON value
+    pub fn remove(&self, value: &mut serde_json::Value) {
+        let pointers: Vec<String> = self
+            .0
+            .query_located(value)
+            .locations()
+            .map(|path| path.to_json_pointer())
+            .collect();
+        // Results are in document order. Go backwards so removing an array
+        // element doesn't shift the indexes of later matches
+        for pointer in pointers.iter().rev() {
+            let Some((parent, key)) = pointer.rsplit_once('/') else {
+                // Query matched the root, which we can't remove
+                continue;
+            };
+            // Unescape per RFC 6901
+            let key = key.replace("~1", "/").replace("~0", "~");
+            match value.pointer_mut(parent) {
+                Some(serde_json::Value::Object(map)) => {
+                    map.remove(&key);
+                }
+                Some(serde_json::Value::Array(array)) => {
+                    if let Ok(index) = key.parse::<usize>() {
+                        array.remove(index);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// An XPath expression, for querying XML and HTML content. The expression is
+/// validated when parsed, but stored as a string because the compiled form
+/// can't be cloned or compared.
+#[derive(Clone, Debug, Display, PartialEq)]
+pub struct XPath(String);
+
+impl XPath {
+    /// Parse XML or HTML content into a document that can be queried. Strict
+    /// XML is attempted first. If that fails, the content is parsed as HTML,
+    /// which is lenient enough to accept just about anything.
+    pub fn parse_document(content: &[u8]) -> anyhow::Result<Package> {
+        let content = std::str::from_utf8(content)?;
+        Ok(sxd_document::parser::parse(content)
+            .unwrap_or_else(|_| sxd_html::parse_html(content)))
+    }
+
+    /// Apply this XPath to a document, returning a string. If the expression
+    /// evaluates to a set of nodes, there must be exactly one node, and its
+    /// text content is returned. Scalar values (e.g. from `count()`) are
+    /// stringified.
+    pub fn query_to_string(
+        &self,
+        document: &Document,
+    ) -> Result<String, QueryError> {
+        let xpath = Factory::new()
+            .build(&self.0)
+            .ok()
+            .flatten()
+            .expect("XPath was validated during parsing");
+        let value = xpath.evaluate(&Context::new(), document.root())?;
+        match value {
+            Value::Nodeset(nodes) => {
+                let node =
+                    nodes.document_order_first().filter(|_| nodes.size() == 1);
+                node.map(|node| node.string_value()).ok_or(
+                    QueryError::InvalidResult {
+                        actual_count: nodes.size(),
+                    },
+                )
+            }
+            value => Ok(value.into_string()),
+        }
+    }
+}
+
+impl FromStr for XPath {
+    type Err = SelectorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Factory::new().build(s) {
+            Ok(Some(_)) => Ok(Self(s.to_owned())),
+            Ok(None) => Err(SelectorParseError::Empty),
+            Err(error) => Err(SelectorParseError::XPath(error)),
+        }
+    }
+}
+
+/// Extract a value from plain text (e.g. command output) with a regular
+/// expression. The first match is used.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RegexSelector {
+    pub pattern: Pattern,
+    /// Capture group to extract, by index or name. If omitted, use the first
+    /// group, or the whole match if the pattern has no groups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<CaptureGroup>,
+}
+
+impl RegexSelector {
+    /// Make sure the capture group exists in the pattern. Called during
+    /// deserialization so mistakes are caught when the collection is loaded
+    pub fn validate(&self) -> Result<(), SelectorParseError> {
+        let exists = match &self.group {
+            None => true,
+            Some(CaptureGroup::Index(index)) => {
+                *index < self.pattern.0.captures_len()
+            }
+            Some(CaptureGroup::Name(name)) => self
+                .pattern
+                .0
+                .capture_names()
+                .any(|n| n == Some(name.as_str())),
+        };
+        if exists {
+            Ok(())
+        } else {
+            Err(SelectorParseError::UnknownGroup {
+                pattern: self.pattern.to_string(),
+                group: self.group.clone().unwrap(),
+            })
+        }
+    }
+
+    /// Apply the pattern to some content, returning the selected capture
+    /// group of the first match
+    pub fn extract(&self, content: &[u8]) -> Result<Vec<u8>, QueryError> {
+        let regex = &self.pattern.0;
+        let captures = regex.captures(content).ok_or_else(|| {
+            QueryError::RegexNoMatch {
+                pattern: self.pattern.to_string(),
+                input: snippet(content),
+            }
+        })?;
+        let group = match &self.group {
+            None if regex.captures_len() > 1 => captures.get(1),
+            None => captures.get(0),
+            Some(CaptureGroup::Index(index)) => captures.get(*index),
+            Some(CaptureGroup::Name(name)) => captures.name(name),
+        };
+        // A group can be missing from a match if it's optional, e.g. `(a)?`
+        let group = group.ok_or_else(|| QueryError::RegexGroupUnmatched {
+            pattern: self.pattern.to_string(),
+            group: self.group.clone().unwrap_or(CaptureGroup::Index(1)),
+            input: snippet(content),
+        })?;
+        Ok(group.as_bytes().to_vec())
+    }
+}
+
+/// A compiled regex. This wrapper is needed to implement comparison and
+/// serialization
+#[derive(Clone, Debug, Display, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Pattern(regex::bytes::Regex);
+
+impl Pattern {
+    /// Does the pattern match anywhere in the given bytes?
+    pub fn is_match(&self, bytes: &[u8]) -> bool {
+        self.0.is_match(bytes)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(pattern: Pattern) -> Self {
+        pattern.0.as_str().to_owned()
+    }
+}
+
+/// A regex capture group, identified by index or name
+#[derive(
+    Clone, Debug, Display, PartialEq, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(untagged)]
+pub enum CaptureGroup {
+    Index(usize),
+    Name(String),
+}
+
+/// Get the start of some input, for error messages
+fn snippet(content: &[u8]) -> String {
+    const MAX_LENGTH: usize = 50;
+    let text = String::from_utf8_lossy(content);
+    match text.char_indices().nth(MAX_LENGTH) {
+        Some((index, _)) => format!("{}...", &text[..index]),
+        None => text.into_owned(),
+    }
+}
+
+/// Error parsing a [Selector]
+#[derive(Debug, Error)]
+pub enum SelectorParseError {
+    #[error("Invalid JSONPath: {0}")]
+    JsonPath(serde_json_path::ParseError),
+    #[error("Invalid XPath: {0}")]
+    XPath(ParserError),
+    #[error("Selector cannot be empty")]
+    Empty,
+    #[error("Pattern `{pattern}` has no capture group `{group}`")]
+    UnknownGroup {
+        pattern: String,
+        group: CaptureGroup,
+    },
 }
 
 /// A remapping of [serde_json_path::ExactlyOneError]. This is a simplified
 /// version that implements `Clone`, which makes it easier to use within
 /// template errors.
-#[derive(Copy, Clone, Debug, Error)]
+#[derive(Clone, Debug, Error)]
 #[cfg_attr(test, derive(PartialEq))]
 pub enum QueryError {
     /// Got either 0 or 2+ results for JSON path query
     #[error("Expected exactly one result from query, but got {actual_count}")]
     InvalidResult { actual_count: usize },
+    /// XPath expression failed at runtime, e.g. calling an unknown function
+    #[error("Evaluating XPath: {0}")]
+    XPath(#[from] ExecutionError),
+    /// Regex didn't match anywhere in the input
+    #[error("Pattern `{pattern}` did not match input `{input}`")]
+    RegexNoMatch { pattern: String, input: String },
+    /// Regex matched, but the selected capture group wasn't part of the match
+    #[error(
+        "Capture group `{group}` of pattern `{pattern}` did not match input \
+        `{input}`"
+    )]
+    RegexGroupUnmatched {
+        pattern: String,
+        group: CaptureGroup,
+        input: String,
+    },
 }
 
 impl From<ExactlyOneError> for QueryError {
@@ -125,6 +426,156 @@ mod tests {
         assert_err!(query.query_to_string(&*content), expected_err);
     }
 
+    #[rstest]
+    #[case::single("$.data", json!({"data": {"a": 1}}), json!({"a": 1}))]
+    #[case::multiple("$.data[*]", json!({"data": [1, 2]}), json!([1, 2]))]
+    #[case::empty("$.missing", json!({"data": 1}), json!([]))]
+    fn test_query_display(
+        #[case] query: &str,
+        #[case] content: serde_json::Value,
+        #[case] expected: serde_json::Value,
+    ) {
+        let query = Query::from_str(query).unwrap();
+        let out = query.query_display(&*json(content));
+        assert_eq!(*out.to_json(), expected);
+    }
+
+    #[rstest]
+    #[case::field("$.id", json!({"id": 1, "name": "a"}), json!({"name": "a"}))]
+    #[case::nested(
+        "$.users[*].id",
+        json!({"users": [{"id": 1, "name": "a"}, {"id": 2}]}),
+        json!({"users": [{"name": "a"}, {}]}),
+    )]
+    #[case::array_elements(
+        "$[?@ > 1]",
+        json!([1, 2, 3, 0]),
+        json!([1, 0]),
+    )]
+    #[case::escaped_key("$['a/b']", json!({"a/b": 1, "c": 2}), json!({"c": 2}))]
+    #[case::no_match("$.missing", json!({"id": 1}), json!({"id": 1}))]
+    #[case::root("$", json!({"id": 1}), json!({"id": 1}))]
+    fn test_remove(
+        #[case] query: &str,
+        #[case] mut value: serde_json::Value,
+        #[case] expected: serde_json::Value,
+    ) {
+        let query = Query::from_str(query).unwrap();
+        query.remove(&mut value);
+        assert_eq!(value, expected);
+    }
+
+    #[rstest]
+    #[case::json_path("$.data", true)]
+    #[case::xpath("//user/name", false)]
+    #[case::xpath_function("count(//user)", false)]
+    fn test_parse_selector(#[case] selector: &str, #[case] is_json_path: bool) {
+        let parsed: Selector = selector.parse().unwrap();
+        assert_eq!(matches!(parsed, Selector::JsonPath(_)), is_json_path);
+    }
+
+    #[rstest]
+    #[case::json_path("$.", "Invalid JSONPath")]
+    #[case::xpath("//user[", "Invalid XPath")]
+    #[case::empty("", "Selector cannot be empty")]
+    fn test_parse_selector_error(
+        #[case] selector: &str,
+        #[case] expected: &str,
+    ) {
+        assert_err!(selector.parse::<Selector>(), expected);
+    }
+
+    #[rstest]
+    #[case::too_many_results(
+        "//b",
+        "<a><b/><b/></a>",
+        "Expected exactly one result from query, but got 2"
+    )]
+    #[case::no_results(
+        "//c",
+        "<a><b/></a>",
+        "Expected exactly one result from query, but got 0"
+    )]
+    #[case::unknown_function("fake(//b)", "<a><b/></a>", "Evaluating XPath")]
+    fn test_xpath_query_to_string_error(
+        #[case] xpath: &str,
+        #[case] content: &str,
+        #[case] expected_err: &str,
+    ) {
+        let xpath = XPath::from_str(xpath).unwrap();
+        let package = XPath::parse_document(content.as_bytes()).unwrap();
+        assert_err!(
+            xpath.query_to_string(&package.as_document()),
+            expected_err
+        );
+    }
+
+    #[rstest]
+    #[case::whole_match(r"\d+", None, "id: 123", "123")]
+    #[case::default_group(r"id: (\d+)", None, "id: 123", "123")]
+    #[case::group_index(
+        r"(\w+): (\d+)",
+        Some(CaptureGroup::Index(2)),
+        "id: 123",
+        "123"
+    )]
+    #[case::group_name(
+        r"(?<key>\w+): (?<value>\d+)",
+        Some(CaptureGroup::Name("key".into())),
+        "id: 123",
+        "id"
+    )]
+    #[case::first_match(r"id: (\d+)", None, "id: 1\nid: 2", "1")]
+    #[case::multiline(r"(?m)^token=(.*)$", None, "a=b\ntoken=abc\n", "abc")]
+    fn test_regex_extract(
+        #[case] pattern: &str,
+        #[case] group: Option<CaptureGroup>,
+        #[case] content: &str,
+        #[case] expected: &str,
+    ) {
+        let selector = RegexSelector {
+            pattern: pattern.parse().unwrap(),
+            group,
+        };
+        assert_eq!(
+            selector.extract(content.as_bytes()).unwrap(),
+            expected.as_bytes()
+        );
+    }
+
+    #[rstest]
+    #[case::no_match(
+        r"id: (\d+)",
+        None,
+        "name: Bob",
+        "Pattern `id: (\\d+)` did not match input `name: Bob`"
+    )]
+    #[case::truncated(
+        r"id: (\d+)",
+        None,
+        &"x".repeat(100),
+        &format!("did not match input `{}...`", "x".repeat(50)),
+    )]
+    #[case::group_unmatched(
+        r"id: (\d+)?",
+        None,
+        "id: none",
+        "Capture group `1` of pattern `id: (\\d+)?` did not match input \
+        `id: none`"
+    )]
+    fn test_regex_extract_error(
+        #[case] pattern: &str,
+        #[case] group: Option<CaptureGroup>,
+        #[case] content: &str,
+        #[case] expected_err: &str,
+    ) {
+        let selector = RegexSelector {
+            pattern: pattern.parse().unwrap(),
+            group,
+        };
+        assert_err!(selector.extract(content.as_bytes()), expected_err);
+    }
+
     /// Helper to create JSON content
     fn json(value: serde_json::Value) -> Box<dyn ResponseContent> {
         Box::new(Json::from(value))
@@ -0,0 +1,122 @@
+//! The accept loop shared by the local HTTP servers: the mock server, the
+//! request listener, and the proxy
+
+use anyhow::Context;
+use hyper::{
+    body::{Body, Incoming},
+    server::conn::http1,
+    service::Service,
+    Request, Response,
+};
+use hyper_util::rt::TokioIo;
+use std::{error::Error, net::SocketAddr, time::Duration};
+use tokio::{net::TcpListener, time};
+use tracing::{error, info_span, Instrument};
+
+/// How long to wait after failing to accept a connection. Errors like running
+/// out of file descriptors won't clear up immediately, so retrying right away
+/// would just spin.
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+/// A TCP listener that serves HTTP/1 on each connection it accepts
+#[derive(Debug)]
+pub(super) struct LocalServer {
+    listener: TcpListener,
+}
+
+impl LocalServer {
+    /// Bind to a local address. Use port 0 to let the OS pick a port
+    pub async fn bind(address: SocketAddr) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(address)
+            .await
+            .with_context(|| format!("Error binding to {address}"))?;
+        Ok(Self { listener })
+    }
+
+    /// Get the address we're actually bound to. Useful if the port was 0
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve connections until the future is dropped. `make_service` is
+    /// called for each connection to get the service that handles its
+    /// requests. Errors accepting or serving a connection are logged, but
+    /// never stop the server.
+    pub async fn serve<S, B>(self, make_service: impl Fn() -> S)
+    where
+        S: 'static + Service<Request<Incoming>, Response = Response<B>> + Send,
+        S::Future: Send,
+        S::Error: Into<Box<dyn Error + Send + Sync>>,
+        B: 'static + Body + Send,
+        B::Data: Send,
+        B::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        loop {
+            let (stream, remote) = match self.listener.accept().await {
+                Ok(connection) => connection,
+                Err(error) => {
+                    error!(%error, "Error accepting connection");
+                    time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+            let service = make_service();
+            let future = async move {
+                // Upgrades are needed for the proxy to tunnel CONNECT
+                // requests. They do nothing for services that don't use them.
+                if let Err(error) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .with_upgrades()
+                    .await
+                {
+                    error!(%error, "Error serving connection");
+                }
+            };
+            tokio::spawn(future.instrument(info_span!("Connection", %remote)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::service::service_fn;
+    use reqwest::StatusCode;
+    use std::{
+        convert::Infallible,
+        net::{Ipv4Addr, SocketAddrV4},
+    };
+    use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+    /// A broken connection is logged and dropped, and the server keeps
+    /// serving others
+    #[tokio::test]
+    async fn test_serve() {
+        let server =
+            LocalServer::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0).into())
+                .await
+                .unwrap();
+        let address = server.local_addr().unwrap();
+        let handle = tokio::spawn(server.serve(|| {
+            service_fn(|_| async {
+                Ok::<_, Infallible>(Response::new(Full::new(Bytes::from(
+                    "hello",
+                ))))
+            })
+        }));
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(b"not http\r\n\r\n").await.unwrap();
+        drop(stream);
+
+        for _ in 0..2 {
+            let response =
+                reqwest::get(format!("http://{address}/")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.text().await.unwrap(), "hello");
+        }
+        handle.abort();
+    }
+}
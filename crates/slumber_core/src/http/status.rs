@@ -0,0 +1,343 @@
+//! Reference descriptions for HTTP status codes, to explain a response without
+//! leaving Slumber
+
+use reqwest::StatusCode;
+
+/// Link to the definition of a status code in RFC 9110 (HTTP Semantics)
+macro_rules! rfc9110 {
+    ($code:literal) => {
+        concat!("https://www.rfc-editor.org/rfc/rfc9110#status.", $code)
+    };
+}
+
+/// A short explanation of a status code, and where it's defined
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StatusReference {
+    pub description: &'static str,
+    /// Link to the section of the spec that defines the code
+    pub spec: &'static str,
+}
+
+/// Look up the reference for a status code. Codes that aren't registered with
+/// IANA get the description for their class (e.g. 4xx), so this only returns
+/// `None` for codes outside 100-599.
+// The class ranges intentionally overlap the specific codes above them
+#[allow(clippy::match_overlapping_arm)]
+pub fn status_reference(status: StatusCode) -> Option<StatusReference> {
+    let (description, spec) = match status.as_u16() {
+        100 => (
+            "The server has received the request headers, and the client \
+            should continue sending the body.",
+            rfc9110!(100),
+        ),
+        101 => (
+            "The server is switching to the protocol requested in the \
+            Upgrade header, e.g. WebSocket.",
+            rfc9110!(101),
+        ),
+        103 => (
+            "Early hints: the server is sending headers (typically Link \
+            preloads) before the final response.",
+            "https://www.rfc-editor.org/rfc/rfc8297#section-2",
+        ),
+        200 => ("The request succeeded.", rfc9110!(200)),
+        201 => (
+            "The request succeeded and a new resource was created. The \
+            Location header typically points to it.",
+            rfc9110!(201),
+        ),
+        202 => (
+            "The request was accepted for processing, but processing hasn't \
+            finished yet.",
+            rfc9110!(202),
+        ),
+        203 => (
+            "The request succeeded, but the content was modified by a \
+            transforming proxy.",
+            rfc9110!(203),
+        ),
+        204 => (
+            "The request succeeded and there is no content to send.",
+            rfc9110!(204),
+        ),
+        205 => (
+            "The request succeeded, and the client should reset the document \
+            view (e.g. clear a form).",
+            rfc9110!(205),
+        ),
+        206 => (
+            "Only part of the resource is being sent, as requested by the \
+            Range header.",
+            rfc9110!(206),
+        ),
+        207 => (
+            "Multi-status (WebDAV): the body contains a separate status for \
+            each of several resources.",
+            "https://www.rfc-editor.org/rfc/rfc4918#section-11.1",
+        ),
+        300 => (
+            "The resource has multiple representations, and the client should \
+            pick one.",
+            rfc9110!(300),
+        ),
+        301 => (
+            "The resource has permanently moved to the URL in the Location \
+            header. Clients may change POST to GET when following it.",
+            rfc9110!(301),
+        ),
+        302 => (
+            "The resource is temporarily at the URL in the Location header. \
+            Clients may change POST to GET when following it.",
+            rfc9110!(302),
+        ),
+        303 => (
+            "The response can be found with a GET to the URL in the Location \
+            header.",
+            rfc9110!(303),
+        ),
+        304 => (
+            "The resource hasn't changed since the version identified by the \
+            request's conditional headers (e.g. If-None-Match), so the \
+            cached copy can be used.",
+            rfc9110!(304),
+        ),
+        307 => (
+            "The resource is temporarily at the URL in the Location header. \
+            The method and body must not change when following it.",
+            rfc9110!(307),
+        ),
+        308 => (
+            "The resource has permanently moved to the URL in the Location \
+            header. The method and body must not change when following it.",
+            rfc9110!(308),
+        ),
+        400 => (
+            "The server can't process the request because of a client error, \
+            such as malformed syntax or an invalid body.",
+            rfc9110!(400),
+        ),
+        401 => (
+            "The request lacks valid authentication credentials. The \
+            WWW-Authenticate header says how to authenticate.",
+            rfc9110!(401),
+        ),
+        402 => ("Reserved for future use (payment required).", rfc9110!(402)),
+        403 => (
+            "The server understood the request but refuses to authorize it. \
+            Re-authenticating won't help.",
+            rfc9110!(403),
+        ),
+        404 => (
+            "The server can't find the requested resource, or won't say \
+            whether it exists.",
+            rfc9110!(404),
+        ),
+        405 => (
+            "The method isn't supported for this resource. The Allow header \
+            lists the supported methods.",
+            rfc9110!(405),
+        ),
+        406 => (
+            "The server can't produce a response matching the request's \
+            Accept headers.",
+            rfc9110!(406),
+        ),
+        407 => (
+            "The client must authenticate with the proxy first.",
+            rfc9110!(407),
+        ),
+        408 => (
+            "The server timed out waiting for the request.",
+            rfc9110!(408),
+        ),
+        409 => (
+            "The request conflicts with the current state of the resource, \
+            e.g. an edit conflict or duplicate.",
+            rfc9110!(409),
+        ),
+        410 => (
+            "The resource is permanently gone, and no forwarding address is \
+            known.",
+            rfc9110!(410),
+        ),
+        411 => (
+            "The server requires a Content-Length header.",
+            rfc9110!(411),
+        ),
+        412 => (
+            "A precondition in the request's conditional headers (e.g. \
+            If-Match) evaluated to false.",
+            rfc9110!(412),
+        ),
+        413 => (
+            "The request body is larger than the server allows.",
+            rfc9110!(413),
+        ),
+        414 => ("The URL is longer than the server allows.", rfc9110!(414)),
+        415 => (
+            "The server doesn't support the request body's format. Check the \
+            Content-Type header.",
+            rfc9110!(415),
+        ),
+        416 => (
+            "The Range header can't be satisfied, e.g. it's past the end of \
+            the resource.",
+            rfc9110!(416),
+        ),
+        417 => (
+            "The expectation in the Expect header can't be met.",
+            rfc9110!(417),
+        ),
+        418 => (
+            "I'm a teapot. Reserved as an April Fools' joke; some servers use \
+            it to refuse requests they don't want to handle.",
+            rfc9110!(418),
+        ),
+        421 => (
+            "The request was sent to a server that can't respond for this \
+            URL's scheme and authority.",
+            rfc9110!(421),
+        ),
+        422 => (
+            "The body is well-formed, but its contents are invalid, e.g. it \
+            failed validation.",
+            rfc9110!(422),
+        ),
+        423 => (
+            "The resource is locked (WebDAV).",
+            "https://www.rfc-editor.org/rfc/rfc4918#section-11.3",
+        ),
+        425 => (
+            "The server won't process a request that might be replayed (sent \
+            in TLS early data).",
+            "https://www.rfc-editor.org/rfc/rfc8470#section-5.2",
+        ),
+        426 => (
+            "The client must switch to the protocol in the Upgrade header.",
+            rfc9110!(426),
+        ),
+        428 => (
+            "The server requires the request to be conditional (e.g. include \
+            If-Match), to prevent lost updates.",
+            "https://www.rfc-editor.org/rfc/rfc6585#section-3",
+        ),
+        429 => (
+            "Too many requests in a given amount of time (rate limiting). The \
+            Retry-After header may say how long to wait.",
+            "https://www.rfc-editor.org/rfc/rfc6585#section-4",
+        ),
+        431 => (
+            "The request's headers are too large, either individually or in \
+            total.",
+            "https://www.rfc-editor.org/rfc/rfc6585#section-5",
+        ),
+        451 => (
+            "The resource is unavailable for legal reasons, e.g. censorship \
+            or a court order.",
+            "https://www.rfc-editor.org/rfc/rfc7725#section-3",
+        ),
+        500 => (
+            "The server hit an unexpected condition. Check the server's logs.",
+            rfc9110!(500),
+        ),
+        501 => (
+            "The server doesn't support the functionality needed to handle \
+            the request, e.g. an unknown method.",
+            rfc9110!(501),
+        ),
+        502 => (
+            "A gateway or proxy got an invalid response from the upstream \
+            server.",
+            rfc9110!(502),
+        ),
+        503 => (
+            "The server is temporarily unable to handle the request, e.g. \
+            it's overloaded or down for maintenance. The Retry-After header \
+            may say how long to wait.",
+            rfc9110!(503),
+        ),
+        504 => (
+            "A gateway or proxy didn't get a response from the upstream \
+            server in time.",
+            rfc9110!(504),
+        ),
+        505 => (
+            "The server doesn't support the HTTP version of the request.",
+            rfc9110!(505),
+        ),
+        511 => (
+            "The client must authenticate to gain network access, e.g. a \
+            captive portal on public Wi-Fi.",
+            "https://www.rfc-editor.org/rfc/rfc6585#section-6",
+        ),
+        // Unregistered (or rarely used) codes get the description of their
+        // class
+        100..=199 => (
+            "Informational: the request was received and processing is \
+            continuing.",
+            "https://www.rfc-editor.org/rfc/rfc9110#status.1xx",
+        ),
+        200..=299 => (
+            "Success: the request was received, understood, and accepted.",
+            "https://www.rfc-editor.org/rfc/rfc9110#status.2xx",
+        ),
+        300..=399 => (
+            "Redirection: further action is needed to complete the request.",
+            "https://www.rfc-editor.org/rfc/rfc9110#status.3xx",
+        ),
+        400..=499 => (
+            "Client error: the request contains an error or can't be \
+            fulfilled.",
+            "https://www.rfc-editor.org/rfc/rfc9110#status.4xx",
+        ),
+        500..=599 => (
+            "Server error: the server failed to fulfill a valid request.",
+            "https://www.rfc-editor.org/rfc/rfc9110#status.5xx",
+        ),
+        _ => return None,
+    };
+    Some(StatusReference { description, spec })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::registered(
+        404,
+        "The server can't find",
+        "https://www.rfc-editor.org/rfc/rfc9110#status.404"
+    )]
+    #[case::other_rfc(
+        429,
+        "Too many requests",
+        "https://www.rfc-editor.org/rfc/rfc6585#section-4"
+    )]
+    #[case::unregistered(
+        499,
+        "Client error",
+        "https://www.rfc-editor.org/rfc/rfc9110#status.4xx"
+    )]
+    fn test_status_reference(
+        #[case] code: u16,
+        #[case] description: &str,
+        #[case] spec: &str,
+    ) {
+        let reference =
+            status_reference(StatusCode::from_u16(code).unwrap()).unwrap();
+        assert!(
+            reference.description.starts_with(description),
+            "{}",
+            reference.description
+        );
+        assert_eq!(reference.spec, spec);
+    }
+
+    #[test]
+    fn test_status_reference_out_of_range() {
+        assert_eq!(status_reference(StatusCode::from_u16(600).unwrap()), None);
+    }
+}
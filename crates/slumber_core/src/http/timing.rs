@@ -0,0 +1,379 @@
+//! Per-phase timing for HTTP exchanges. reqwest doesn't report timing on its
+//! own, so we instrument the pieces we can hook into: DNS resolution goes
+//! through our own resolver, opening connections goes through a connector
+//! layer, the start of each TLS handshake is marked by the TLS session cache,
+//! and everything else is measured around the calls to reqwest.
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use rustls::{
+    client::{
+        ClientSessionMemoryCache, ClientSessionStore, Tls12ClientSessionValue,
+        Tls13ClientSessionValue,
+    },
+    pki_types::ServerName,
+    NamedGroup,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::Cell,
+    error::Error,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::net;
+use tower::{Layer, Service};
+
+tokio::task_local! {
+    /// Phases measured so far for the request running in the current task.
+    /// Only set while a request is being sent
+    static PHASES: Cell<Phases>;
+}
+
+/// How long each phase of an exchange took. All phases are sequential, so they
+/// add up to (roughly) the total duration of the exchange.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeTiming {
+    /// Resolving the host name. `None` if the request reused a pooled
+    /// connection, so no lookup was needed
+    pub dns: Option<Duration>,
+    /// Opening the TCP connection. `None` if the request reused a pooled
+    /// connection
+    pub connect: Option<Duration>,
+    /// The TLS handshake. `None` for plain HTTP, or if the request reused a
+    /// pooled connection
+    pub tls: Option<Duration>,
+    /// From when the connection was ready until the response headers arrived:
+    /// sending the request, and the server's processing time
+    pub wait: Duration,
+    /// Downloading the response body
+    pub download: Duration,
+}
+
+impl ExchangeTiming {
+    /// Time to first byte: everything before the response started arriving
+    pub fn ttfb(&self) -> Duration {
+        self.dns.unwrap_or_default()
+            + self.connect.unwrap_or_default()
+            + self.tls.unwrap_or_default()
+            + self.wait
+    }
+
+    /// Sum of all phases
+    pub fn total(&self) -> Duration {
+        self.ttfb() + self.download
+    }
+}
+
+/// Phases measured by hooks inside reqwest. Each is summed over every lookup
+/// and connection the request needed, e.g. if it was redirected.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(super) struct Phases {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+    /// When the TLS handshake started on the connection being opened
+    tls_start: Option<Instant>,
+}
+
+impl Phases {
+    /// Total time spent in the measured phases
+    pub fn total(&self) -> Duration {
+        self.dns.unwrap_or_default()
+            + self.connect.unwrap_or_default()
+            + self.tls.unwrap_or_default()
+    }
+}
+
+/// Run a future that sends a request, and measure how long it spent on DNS
+/// resolution, connecting, and TLS handshakes. This only captures work done
+/// by [TimingResolver], [TimingLayer], and [TimingSessionStore] within the
+/// same task.
+pub(super) async fn measure<T>(future: impl Future<Output = T>) -> (T, Phases) {
+    PHASES
+        .scope(Cell::default(), async {
+            let output = future.await;
+            (output, PHASES.with(Cell::get))
+        })
+        .await
+}
+
+/// Update the phases of the request being sent in the current task. If we're
+/// outside a measured request, this does nothing.
+fn update_phases(f: impl FnOnce(&mut Phases)) {
+    let _ = PHASES.try_with(|cell| {
+        let mut phases = cell.get();
+        f(&mut phases);
+        cell.set(phases);
+    });
+}
+
+/// Add time to a phase
+fn add(phase: &mut Option<Duration>, elapsed: Duration) {
+    *phase = Some(phase.unwrap_or_default() + elapsed);
+}
+
+/// DNS resolver that records how long each lookup takes. Lookups are
+/// attributed to the request being sent in the current task, via [measure].
+/// The resolution itself is the same as reqwest's default, using the system
+/// resolver on a blocking thread.
+#[derive(Debug, Default)]
+pub(super) struct TimingResolver;
+
+impl Resolve for TimingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let start = Instant::now();
+            // The port is replaced by the connector, so it doesn't matter
+            let addrs = net::lookup_host((name.as_str().to_owned(), 0)).await?;
+            let elapsed = start.elapsed();
+            // Redirects can trigger multiple lookups; count all of them
+            update_phases(|phases| add(&mut phases.dns, elapsed));
+            Ok::<_, Box<dyn Error + Send + Sync>>(Box::new(addrs) as Addrs)
+        })
+    }
+}
+
+/// Connector layer that records how long it takes to open each connection.
+/// reqwest's connector resolves the host, opens the TCP connection, and does
+/// the TLS handshake all in one call. DNS is already measured by
+/// [TimingResolver], and [TimingSessionStore] marks where the handshake
+/// starts, so the rest is split between connecting and TLS.
+#[derive(Copy, Clone, Debug, Default)]
+pub(super) struct TimingLayer;
+
+impl<S> Layer<S> for TimingLayer {
+    type Service = TimingConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimingConnector(inner)
+    }
+}
+
+/// Connector service created by [TimingLayer]
+#[derive(Clone, Debug)]
+pub(super) struct TimingConnector<S>(S);
+
+impl<S, Request> Service<Request> for TimingConnector<S>
+where
+    S: Service<Request>,
+    S::Future: 'static + Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let future = self.0.call(request);
+        Box::pin(async move {
+            let mut dns_before = Duration::ZERO;
+            update_phases(|phases| {
+                dns_before = phases.dns.unwrap_or_default();
+                phases.tls_start = None;
+            });
+            let start = Instant::now();
+            let result = future.await;
+            let end = Instant::now();
+            update_phases(|phases| {
+                let dns =
+                    phases.dns.unwrap_or_default().saturating_sub(dns_before);
+                // The TCP connection is open once the handshake starts. With
+                // no handshake, the connection is ready as soon as it's open
+                let connected = phases.tls_start.take().unwrap_or(end);
+                add(
+                    &mut phases.connect,
+                    (connected - start).saturating_sub(dns),
+                );
+                if connected < end {
+                    add(&mut phases.tls, end - connected);
+                }
+            });
+            result
+        })
+    }
+}
+
+/// TLS session cache that marks the start of each TLS handshake. Before
+/// sending its first message, rustls checks the cache for a session to
+/// resume, which happens right after the TCP connection opens. The caching
+/// itself is delegated to rustls's default in-memory cache.
+#[derive(Debug)]
+pub(super) struct TimingSessionStore(ClientSessionMemoryCache);
+
+impl TimingSessionStore {
+    /// Same size as rustls's default cache
+    const SIZE: usize = 256;
+
+    /// Mark the start of the handshake for the connection being opened. The
+    /// cache is read multiple times during the handshake, so only the first
+    /// read counts
+    fn mark_tls_start() {
+        update_phases(|phases| {
+            phases.tls_start.get_or_insert_with(Instant::now);
+        });
+    }
+}
+
+impl Default for TimingSessionStore {
+    fn default() -> Self {
+        Self(ClientSessionMemoryCache::new(Self::SIZE))
+    }
+}
+
+impl ClientSessionStore for TimingSessionStore {
+    fn set_kx_hint(&self, server_name: ServerName<'static>, group: NamedGroup) {
+        self.0.set_kx_hint(server_name, group);
+    }
+
+    fn kx_hint(&self, server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        Self::mark_tls_start();
+        self.0.kx_hint(server_name)
+    }
+
+    fn set_tls12_session(
+        &self,
+        server_name: ServerName<'static>,
+        value: Tls12ClientSessionValue,
+    ) {
+        self.0.set_tls12_session(server_name, value);
+    }
+
+    fn tls12_session(
+        &self,
+        server_name: &ServerName<'_>,
+    ) -> Option<Tls12ClientSessionValue> {
+        Self::mark_tls_start();
+        self.0.tls12_session(server_name)
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName<'static>) {
+        self.0.remove_tls12_session(server_name);
+    }
+
+    fn insert_tls13_ticket(
+        &self,
+        server_name: ServerName<'static>,
+        value: Tls13ClientSessionValue,
+    ) {
+        self.0.insert_tls13_ticket(server_name, value);
+    }
+
+    fn take_tls13_ticket(
+        &self,
+        server_name: &ServerName<'static>,
+    ) -> Option<Tls13ClientSessionValue> {
+        Self::mark_tls_start();
+        self.0.take_tls13_ticket(server_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::{future, net::SocketAddr};
+    use tokio::time;
+
+    #[tokio::test]
+    async fn test_measure_dns() {
+        let resolver = TimingResolver;
+        let ((), phases) = measure(async {
+            let addrs: Vec<SocketAddr> = resolver
+                .resolve("localhost".parse().unwrap())
+                .await
+                .unwrap()
+                .collect();
+            assert!(!addrs.is_empty());
+        })
+        .await;
+        assert!(phases.dns.is_some());
+
+        // Nothing resolved, nothing measured
+        let ((), phases) = measure(async {}).await;
+        assert_eq!(phases, Phases::default());
+    }
+
+    /// Opening a connection is split into connecting and TLS at the point
+    /// where the session cache is first read
+    #[rstest]
+    #[case::http(false)]
+    #[case::https(true)]
+    #[tokio::test]
+    async fn test_measure_connect(#[case] tls: bool) {
+        /// Fake connector that takes 10ms to connect, then 10ms for the TLS
+        /// handshake
+        #[derive(Clone)]
+        struct Connector {
+            tls: bool,
+        }
+
+        impl Service<()> for Connector {
+            type Response = ();
+            type Error = ();
+            type Future = Pin<Box<dyn Future<Output = Result<(), ()>> + Send>>;
+
+            fn poll_ready(
+                &mut self,
+                _: &mut Context<'_>,
+            ) -> Poll<Result<(), ()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, (): ()) -> Self::Future {
+                let tls = self.tls;
+                Box::pin(async move {
+                    time::sleep(Duration::from_millis(10)).await;
+                    if tls {
+                        let store = TimingSessionStore::default();
+                        let name = ServerName::try_from("localhost").unwrap();
+                        store.kx_hint(&name);
+                        time::sleep(Duration::from_millis(10)).await;
+                        // Later reads don't move the start
+                        store.tls12_session(&name);
+                    }
+                    Ok(())
+                })
+            }
+        }
+
+        let mut connector = TimingLayer.layer(Connector { tls });
+        let (result, phases) = measure(async {
+            future::poll_fn(|cx| connector.poll_ready(cx)).await?;
+            connector.call(()).await
+        })
+        .await;
+        result.unwrap();
+
+        let connect = phases.connect.unwrap();
+        assert!(connect >= Duration::from_millis(10));
+        if tls {
+            assert!(connect < Duration::from_millis(20));
+            assert!(phases.tls.unwrap() >= Duration::from_millis(10));
+        } else {
+            assert_eq!(phases.tls, None);
+        }
+        assert_eq!(phases.dns, None);
+    }
+
+    #[test]
+    fn test_ttfb() {
+        let timing = ExchangeTiming {
+            dns: Some(Duration::from_millis(5)),
+            connect: Some(Duration::from_millis(10)),
+            tls: Some(Duration::from_millis(15)),
+            wait: Duration::from_millis(100),
+            download: Duration::from_millis(20),
+        };
+        assert_eq!(timing.ttfb(), Duration::from_millis(130));
+        assert_eq!(timing.total(), Duration::from_millis(150));
+    }
+}
@@ -0,0 +1,269 @@
+//! TLS configuration for HTTP clients, and details of the TLS connection that
+//! a response was received over
+
+use crate::{
+    http::{timing::TimingSessionStore, ClientIdentity},
+    util::ResultTraced,
+};
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use reqwest::{tls::TlsInfo, Response};
+use rustls::{
+    client::{
+        danger::{
+            HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+        },
+        Resumption,
+    },
+    crypto::{ring, CryptoProvider},
+    pki_types::{
+        pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime,
+    },
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+};
+use tracing::debug;
+use x509_parser::{
+    certificate::X509Certificate, extensions::GeneralName, prelude::FromDer,
+    time::ASN1Time,
+};
+
+/// The certificate that a server presented during the TLS handshake. reqwest
+/// only exposes the server's own (leaf) certificate, not the intermediates
+/// that came with it, so the rest of the chain is only visible via `issuer`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ServerCertificate {
+    /// Distinguished name of the certificate holder, e.g.
+    /// `CN=example.com`
+    pub subject: String,
+    /// Distinguished name of the CA that signed the certificate
+    pub issuer: String,
+    /// Host names and IP addresses that the certificate is valid for
+    pub subject_alt_names: Vec<String>,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// Hex serial number, as assigned by the issuer
+    pub serial_number: String,
+    /// SHA-256 hash of the DER-encoded certificate, in hex
+    pub fingerprint: String,
+}
+
+impl ServerCertificate {
+    /// Get the certificate from a response. `None` if the request wasn't
+    /// made over TLS, or the certificate couldn't be parsed. The client must
+    /// be built with `tls_info(true)`.
+    pub(super) fn from_response(response: &Response) -> Option<Self> {
+        let der = response.extensions().get::<TlsInfo>()?.peer_certificate()?;
+        // A certificate we can't parse shouldn't fail the request
+        Self::from_der(der).traced().ok()
+    }
+
+    /// Parse a DER-encoded X.509 certificate
+    pub fn from_der(der: &[u8]) -> anyhow::Result<Self> {
+        let (_, certificate) = X509Certificate::from_der(der)
+            .context("Error parsing server certificate")?;
+        let subject_alt_names = certificate
+            .subject_alternative_name()?
+            .map(|extension| {
+                extension
+                    .value
+                    .general_names
+                    .iter()
+                    .filter_map(general_name_to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self {
+            subject: certificate.subject().to_string(),
+            issuer: certificate.issuer().to_string(),
+            subject_alt_names,
+            not_before: to_date_time(certificate.validity().not_before)?,
+            not_after: to_date_time(certificate.validity().not_after)?,
+            serial_number: colon_hex(certificate.raw_serial()),
+            fingerprint: colon_hex(&Sha256::digest(der)),
+        })
+    }
+
+    /// Is the current time within the certificate's validity period?
+    pub fn is_valid(&self) -> bool {
+        (self.not_before..=self.not_after).contains(&Utc::now())
+    }
+}
+
+/// Build the TLS config for a client. This matches what reqwest builds for the
+/// same settings, but we need our own so the handshake can be timed by
+/// [TimingSessionStore].
+pub(super) fn client_config(
+    ca_certificates: &[CertificateDer<'static>],
+    danger: bool,
+    identity: Option<&ClientIdentity>,
+) -> anyhow::Result<ClientConfig> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .context("Error configuring TLS")?;
+    let builder = if danger {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerifier(provider)))
+    } else {
+        builder.with_root_certificates(root_certificates(ca_certificates)?)
+    };
+    let mut config = match identity {
+        Some(ClientIdentity(pem)) => {
+            let chain = CertificateDer::pem_slice_iter(pem)
+                .collect::<Result<Vec<_>, _>>()
+                .context("Invalid client certificate")?;
+            let key = PrivateKeyDer::from_pem_slice(pem)
+                .context("Invalid client certificate")?;
+            builder
+                .with_client_auth_cert(chain, key)
+                .context("Invalid client certificate")?
+        }
+        None => builder.with_no_client_auth(),
+    };
+    // We only speak HTTP/1
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    config.resumption =
+        Resumption::store(Arc::new(TimingSessionStore::default()));
+    Ok(config)
+}
+
+/// Load the OS's trusted root certificates, plus any additional CAs from the
+/// config
+fn root_certificates(
+    ca_certificates: &[CertificateDer<'static>],
+) -> anyhow::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    // The OS store often has some ancient certificates that can't be parsed.
+    // Skip those instead of failing
+    let native = rustls_native_certs::load_native_certs();
+    for error in &native.errors {
+        debug!(%error, "Error loading native root certificates");
+    }
+    roots.add_parsable_certificates(native.certs);
+    for certificate in ca_certificates {
+        roots
+            .add(certificate.clone())
+            .context("Invalid CA certificate")?;
+    }
+    Ok(roots)
+}
+
+/// Certificate verifier that accepts anything. Used when TLS errors are
+/// ignored for a host or recipe
+#[derive(Debug)]
+struct NoVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _: &CertificateDer<'_>,
+        _: &[CertificateDer<'_>],
+        _: &ServerName<'_>,
+        _: &[u8],
+        _: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _: &[u8],
+        _: &CertificateDer<'_>,
+        _: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _: &[u8],
+        _: &CertificateDer<'_>,
+        _: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Stringify a SAN entry. Only DNS names and IPs are used for HTTPS, so
+/// anything else is dropped
+fn general_name_to_string(name: &GeneralName) -> Option<String> {
+    match name {
+        GeneralName::DNSName(name) => Some((*name).to_owned()),
+        GeneralName::IPAddress(bytes) => {
+            let address: IpAddr = match *bytes {
+                [a, b, c, d] => Ipv4Addr::new(*a, *b, *c, *d).into(),
+                _ => Ipv6Addr::from(<[u8; 16]>::try_from(*bytes).ok()?).into(),
+            };
+            Some(address.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn to_date_time(time: ASN1Time) -> anyhow::Result<DateTime<Utc>> {
+    DateTime::from_timestamp(time.timestamp(), 0)
+        .ok_or_else(|| anyhow!("Certificate timestamp `{time}` out of range"))
+}
+
+/// Format bytes as uppercase hex pairs separated by colons, the usual format
+/// for serial numbers and fingerprints
+fn colon_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::get_repo_root;
+    use chrono::TimeZone;
+    use std::fs;
+
+    #[test]
+    fn test_from_der() {
+        let der =
+            fs::read(get_repo_root().join("test_data/server.der")).unwrap();
+        let certificate = ServerCertificate::from_der(&der).unwrap();
+        assert_eq!(
+            certificate,
+            ServerCertificate {
+                subject: "O=Slumber, CN=localhost".into(),
+                issuer: "O=Slumber, CN=localhost".into(),
+                subject_alt_names: vec![
+                    "localhost".into(),
+                    "*.slumber.test".into(),
+                    "127.0.0.1".into()
+                ],
+                not_before: Utc
+                    .with_ymd_and_hms(2026, 10, 15, 21, 27, 17)
+                    .unwrap(),
+                not_after: Utc
+                    .with_ymd_and_hms(2126, 9, 21, 21, 27, 17)
+                    .unwrap(),
+                serial_number: "54:E0:B3:D7:F9:51:EE:3A:48:0B:76:C9:EC:00:\
+                    7B:A8:08:42:A9:9D"
+                    .into(),
+                fingerprint: "A7:C1:88:D0:43:A3:68:10:9B:4D:70:1C:EB:9E:6E:\
+                    39:90:68:95:83:A1:12:F0:96:8B:BA:22:DC:4A:21:B4:55"
+                    .into(),
+            }
+        );
+        assert!(certificate.is_valid());
+    }
+
+    #[test]
+    fn test_from_der_invalid() {
+        assert!(ServerCertificate::from_der(b"not a certificate").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! SSH tunnel management. Tunnels are opened on demand, immediately before a
+//! request is sent, and then kept open for reuse by subsequent requests.
+
+use anyhow::{anyhow, bail, Context};
+use std::{
+    collections::HashMap,
+    io,
+    process::{ExitStatus, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    io::AsyncReadExt,
+    net::{self, TcpListener, TcpStream},
+    process::{Child, Command},
+    sync::Mutex,
+    time::{self, Instant},
+};
+use tracing::{info, info_span};
+
+/// How long to wait for a tunnel to start accepting connections
+const OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often to check if a tunnel is ready
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A fully rendered SSH tunnel definition. See
+/// [SshTunnel](crate::collection::SshTunnel) for field descriptions.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(super) struct TunnelConfig {
+    pub host: String,
+    pub jump: Option<String>,
+    pub local_port: u16,
+    pub remote_host: String,
+    pub remote_port: u16,
+}
+
+impl TunnelConfig {
+    /// Get the arguments to pass to `ssh`
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            // Forward only, don't run a remote command
+            "-N".into(),
+            // Exit immediately if the port can't be bound, instead of leaving
+            // us with a useless connection
+            "-o".into(),
+            "ExitOnForwardFailure=yes".into(),
+            // We have no way to show a password prompt, so fail instead of
+            // hanging forever
+            "-o".into(),
+            "BatchMode=yes".into(),
+            "-L".into(),
+            format!(
+                "{}:{}:{}",
+                self.local_port, self.remote_host, self.remote_port
+            ),
+        ];
+        if let Some(jump) = &self.jump {
+            args.extend(["-J".into(), jump.clone()]);
+        }
+        args.push(self.host.clone());
+        args
+    }
+}
+
+/// Container for all open SSH tunnels. Tunnels are keyed by local port, since
+/// that's the resource they hold. Each tunnel is an `ssh` child process, which
+/// is killed when the manager (and all its clones) is dropped.
+#[derive(Clone, Debug, Default)]
+pub(super) struct TunnelManager {
+    tunnels: Arc<Mutex<HashMap<u16, (TunnelConfig, Child)>>>,
+}
+
+impl TunnelManager {
+    /// Make sure a tunnel with the given config is open. If it's already open,
+    /// do nothing. If a *different* tunnel is open on the same local port, it
+    /// will be closed and replaced.
+    pub async fn open(&self, config: &TunnelConfig) -> anyhow::Result<()> {
+        // Hold the lock for the entire open, so concurrent requests don't
+        // try to open the same tunnel twice
+        let mut tunnels = self.tunnels.lock().await;
+        if let Some((open_config, child)) = tunnels.get_mut(&config.local_port)
+        {
+            // try_wait returns None if the process is still running
+            if open_config == config && matches!(child.try_wait(), Ok(None)) {
+                return Ok(());
+            }
+            // Tunnel is either dead or outdated. Wait for it to exit, so the
+            // port is free for the new one
+            if let Some((_, mut child)) = tunnels.remove(&config.local_port) {
+                let _ = child.kill().await;
+            }
+        }
+
+        let _ = info_span!("Opening SSH tunnel", ?config).entered();
+        let child = Self::spawn(config).await?;
+        info!("SSH tunnel open");
+        tunnels.insert(config.local_port, (config.clone(), child));
+        Ok(())
+    }
+
+    /// Close all open tunnels
+    pub async fn close_all(&self) {
+        let mut tunnels = self.tunnels.lock().await;
+        for (_, (config, mut child)) in tunnels.drain() {
+            info!(?config, "Closing SSH tunnel");
+            let _ = child.kill().await;
+        }
+    }
+
+    /// Spawn an `ssh` process and wait until its local port is accepting
+    /// connections
+    async fn spawn(config: &TunnelConfig) -> anyhow::Result<Child> {
+        // If something else is already listening on the port, our connection
+        // check below would succeed against *that*, and requests would go to
+        // the wrong place. ssh listens on every localhost address, so check
+        // all of them
+        for address in net::lookup_host(("localhost", config.local_port))
+            .await
+            .context("Error resolving `localhost`")?
+        {
+            if let Err(error) = TcpListener::bind(address).await {
+                if error.kind() == io::ErrorKind::AddrInUse {
+                    bail!("Local port {} is already in use", config.local_port);
+                }
+            }
+        }
+
+        let mut child = Command::new("ssh")
+            .args(config.args())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Error executing `ssh`")?;
+
+        let deadline = Instant::now() + OPEN_TIMEOUT;
+        loop {
+            let connected =
+                TcpStream::connect(("localhost", config.local_port))
+                    .await
+                    .is_ok();
+            // Check this *after* connecting, so we know it was ssh that
+            // accepted the connection
+            if let Some(status) = child.try_wait()? {
+                return Err(Self::exit_error(&mut child, status).await);
+            }
+            if connected {
+                return Ok(child);
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "Timed out after {}s waiting for SSH tunnel on port {}",
+                    OPEN_TIMEOUT.as_secs(),
+                    config.local_port
+                ));
+            }
+            time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Build an error for an `ssh` process that exited early, including
+    /// whatever it wrote to stderr
+    async fn exit_error(
+        child: &mut Child,
+        status: ExitStatus,
+    ) -> anyhow::Error {
+        let mut stderr = String::new();
+        if let Some(mut pipe) = child.stderr.take() {
+            let _ = pipe.read_to_string(&mut stderr).await;
+        }
+        anyhow!("`ssh` exited with {status}: {}", stderr.trim())
+    }
+}
+
+/// A tunnel that needs to be open before a particular request is sent
+#[derive(Debug)]
+pub(super) struct PendingTunnel {
+    pub manager: TunnelManager,
+    pub config: TunnelConfig,
+}
+
+impl PendingTunnel {
+    pub async fn open(&self) -> anyhow::Result<()> {
+        self.manager.open(&self.config).await.with_context(|| {
+            format!("Error opening SSH tunnel to {}", self.config.host)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_err;
+
+    /// A tunnel can't be opened on a port that's already in use, because
+    /// requests would go to whatever is already listening there
+    #[tokio::test]
+    async fn test_port_in_use() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_port = listener.local_addr().unwrap().port();
+        let config = TunnelConfig {
+            host: "user@bastion".into(),
+            jump: None,
+            local_port,
+            remote_host: "api.internal".into(),
+            remote_port: 80,
+        };
+        assert_err!(
+            TunnelManager::default().open(&config).await,
+            "already in use"
+        );
+    }
+
+    #[test]
+    fn test_args() {
+        let config = TunnelConfig {
+            host: "user@bastion".into(),
+            jump: Some("jump1,jump2".into()),
+            local_port: 8080,
+            remote_host: "api.internal".into(),
+            remote_port: 80,
+        };
+        assert_eq!(
+            config.args(),
+            [
+                "-N",
+                "-o",
+                "ExitOnForwardFailure=yes",
+                "-o",
+                "BatchMode=yes",
+                "-L",
+                "8080:api.internal:80",
+                "-J",
+                "jump1,jump2",
+                "user@bastion",
+            ]
+        );
+    }
+}
@@ -2,11 +2,24 @@
 
 mod cereal;
 mod error;
+mod expression;
+mod fake;
+mod filter;
+mod function;
+mod harness;
 mod parse;
 mod prompt;
 mod render;
 
 pub use error::{ChainError, TemplateError, TriggeredRequestError};
+pub use expression::{Expression, ExpressionError, Operand, Operator};
+pub use fake::FakeKind;
+pub use filter::{Filter, FilterError};
+pub use function::{Argument, FunctionCall, FunctionError};
+pub use harness::{
+    TemplateContextBuilder, TemplateTestFailure, TestHttpProvider,
+    TestPrompter, TestSelectPrompter,
+};
 pub use prompt::{Prompt, PromptChannel, Prompter, Select};
 
 use crate::{
@@ -14,7 +27,10 @@ use crate::{
     db::CollectionDatabase,
     http::HttpEngine,
     template::{
-        parse::{TemplateInputChunk, CHAIN_PREFIX, ENV_PREFIX},
+        parse::{
+            TemplateInputChunk, CHAIN_PREFIX, DATA_PREFIX, ENV_PREFIX,
+            FAKE_PREFIX, ITEM_KEY, ITEM_PREFIX,
+        },
         render::RenderGroupState,
     },
 };
@@ -75,6 +91,12 @@ pub struct TemplateContext {
     pub overrides: IndexMap<String, String>,
     /// A conduit to ask the user questions
     pub prompter: Box<dyn Prompter>,
+    /// Seed for `fake.*` keys, to generate the same values every time. This
+    /// takes precedence over the profile's `fake_seed`.
+    pub fake_seed: Option<u64>,
+    /// Use each chain's `example` value instead of computing it from its
+    /// source, if it has one. This is set in offline mode.
+    pub offline: bool,
     /// State that should be shared across al renders that use this context.
     /// This is meant to be opaque; just use [Default::default] to initialize.
     pub state: RenderGroupState,
@@ -99,6 +121,15 @@ impl Template {
     pub fn is_empty(&self) -> bool {
         self.chunks.is_empty()
     }
+
+    /// Join two templates end to end, e.g. to prefix a path with a base URL
+    pub fn concat(&self, other: &Template) -> Template {
+        Self {
+            chunks: join_raw(
+                self.chunks.iter().chain(&other.chunks).cloned().collect(),
+            ),
+        }
+    }
 }
 
 #[cfg(any(test, feature = "test"))]
@@ -207,10 +238,31 @@ pub enum TemplateKey {
     /// A value from a predefined chain of another recipe
     #[display("{CHAIN_PREFIX}{_0}")]
     Chain(ChainId),
-    /// A value pulled from the process environment
-    /// DEPRECATED: To be removed in 2.0, replaced by !env chain source
+    /// A value pulled from the process environment. Options for each variable
+    /// can be set under the collection's `environment` field
     #[display("{ENV_PREFIX}{_0}")]
     Environment(Identifier),
+    /// A call to a built-in function that generates a value, e.g. `uuid()`
+    Function(FunctionCall),
+    /// Randomly generated placeholder data, e.g. `fake.email`
+    #[display("{FAKE_PREFIX}{_0}")]
+    Fake(FakeKind),
+    /// A column from the current row of a data file, e.g. `data.username`.
+    /// Rows are provided as overrides by `slumber run --data`
+    #[display("{DATA_PREFIX}{_0}")]
+    Data(Identifier),
+    /// The current element of the innermost `{{#each}}` loop: `this`
+    #[display("{ITEM_KEY}")]
+    Item,
+    /// A field of the current element of the innermost `{{#each}}` loop,
+    /// e.g. `this.id`
+    #[display("{ITEM_PREFIX}{_0}")]
+    ItemField(Identifier),
+    /// Arithmetic or comparison of other keys and literals, e.g.
+    /// `page * 50`. Recursive generation isn't supported by the derive, so
+    /// these are tested by hand.
+    #[cfg_attr(test, proptest(skip))]
+    Expression(Box<Expression>),
 }
 
 #[cfg(any(test, feature = "test"))]
@@ -224,13 +276,15 @@ impl crate::test_util::Factory for TemplateContext {
             database: CollectionDatabase::factory(()),
             overrides: IndexMap::new(),
             prompter: Box::<TestPrompter>::default(),
+            fake_seed: None,
+            offline: false,
             state: RenderGroupState::default(),
         }
     }
 }
 
-/// Join consecutive raw chunks in a generated template, to make it valid
-#[cfg(test)]
+/// Join consecutive raw chunks, to uphold the invariant that no two raw chunks
+/// are adjacent
 fn join_raw(chunks: Vec<TemplateInputChunk>) -> Vec<TemplateInputChunk> {
     let len = chunks.len();
     chunks
@@ -263,10 +317,12 @@ mod tests {
         assert_err,
         collection::{
             Chain, ChainOutputTrim, ChainRequestSection, ChainRequestTrigger,
-            ChainSource, Profile, Recipe, RecipeId,
+            ChainSource, EnvironmentVariable, GcpTokenType, Profile,
+            RandomChoice, Recipe, RecipeId, ResponseExample,
         },
         http::{
-            content_type::ContentType, Exchange, RequestRecord, ResponseRecord,
+            content_type::ContentType, Exchange, RequestRecord, RequestTrigger,
+            ResponseRecord,
         },
         test_util::{
             by_id, header_map, http_engine, invalid_utf8_chain, temp_dir,
@@ -275,6 +331,7 @@ mod tests {
     };
     use chrono::Utc;
     use indexmap::indexmap;
+    use reqwest::StatusCode;
     use rstest::rstest;
     use serde_json::json;
     use std::time::Duration;
@@ -328,6 +385,11 @@ mod tests {
             render!("{{override1}}", context).unwrap(),
             "override".to_owned()
         );
+        // Filters apply to overridden values too
+        assert_eq!(
+            render!("{{field1 | upper}}", context).unwrap(),
+            "OVERRIDE".to_owned()
+        );
     }
 
     /// Test that a field key renders correctly
@@ -343,12 +405,17 @@ mod tests {
         "start {{user_id}} 🧡💛 {{group_id}} end",
         "start 1 🧡💛 3 end"
     )]
+    #[case::filter("{{padded | trim}}", "dXNlcjpwYXNz")]
+    #[case::filters("{{padded | trim | base64_decode | upper}}", "USER:PASS")]
+    // Filters apply to the key's final value, not the nested template
+    #[case::nested_filter("{{nested | urlencode}}", "user%20id%3A%201")]
     #[tokio::test]
     async fn test_field(#[case] template: &str, #[case] expected: &str) {
         let context = profile_context(indexmap! {
             "user_id".into() => "1".into(),
             "group_id".into() => "3".into(),
             "nested".into() => "user id: {{user_id}}".into(),
+            "padded".into() => "  dXNlcjpwYXNz\n".into(),
         });
 
         assert_eq!(&render!(template, context).unwrap(), expected);
@@ -362,15 +429,70 @@ mod tests {
         "Rendering nested template for field `nested`: \
         Unknown field `onion_id`"
     )]
+    #[case::filter(
+        "{{invalid | trim | base64_decode}}",
+        "Applying filter `base64_decode`: Decoding base64"
+    )]
     #[tokio::test]
     async fn test_field_error(#[case] template: &str, #[case] expected: &str) {
         let context = profile_context(indexmap! {
             "nested".into() => "{{onion_id}}".into(),
             "recursive".into() => "{{recursive}}".into(),
+            "invalid".into() => "not base64!".into(),
         });
         assert_err!(render!(template, context), expected);
     }
 
+    /// Fields missing from the selected profile are taken from the profiles
+    /// it extends. Nested templates are rendered with the selected profile,
+    /// so an inherited template sees the child's overrides.
+    #[rstest]
+    #[case::own("{{user}}", "frodo")]
+    #[case::inherited("{{host}}", "http://localhost")]
+    #[case::grandparent("{{version}}", "v1")]
+    #[case::nested("{{url}}", "http://localhost/v1/users/frodo")]
+    #[tokio::test]
+    async fn test_field_inherited(
+        #[case] template: &str,
+        #[case] expected: &str,
+    ) {
+        let root = Profile {
+            id: "root".into(),
+            data: indexmap! {
+                "version".into() => "v1".into(),
+                "user".into() => "root".into(),
+            },
+            ..Profile::factory(())
+        };
+        let base = Profile {
+            id: "base".into(),
+            extends: Some("root".into()),
+            data: indexmap! {
+                "host".into() => "http://localhost".into(),
+                "url".into() => "{{host}}/{{version}}/users/{{user}}".into(),
+                "user".into() => "base".into(),
+            },
+            ..Profile::factory(())
+        };
+        let child = Profile {
+            id: "child".into(),
+            extends: Some("base".into()),
+            data: indexmap! {"user".into() => "frodo".into()},
+            ..Profile::factory(())
+        };
+        let context = TemplateContext {
+            selected_profile: Some(child.id.clone()),
+            collection: Collection {
+                profiles: by_id([root, base, child]),
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(&render!(template, context).unwrap(), expected);
+    }
+
     /// Test success cases with chained responses
     #[rstest]
     #[case::no_selector(
@@ -399,6 +521,7 @@ mod tests {
         ChainRequestSection::Header("{{header}}".into()),
         "Secret Value",
     )]
+    #[case::status(None, ChainRequestSection::Status, "201")]
     #[tokio::test]
     async fn test_chain_request(
         #[case] selector: Option<&str>,
@@ -441,9 +564,9 @@ mod tests {
             ..RequestRecord::factory(())
         };
         let response = ResponseRecord {
+            status: StatusCode::CREATED,
             body: response_body.to_string().into_bytes().into(),
             headers: response_headers,
-            ..ResponseRecord::factory(())
         };
         database
             .insert_exchange(&Exchange::factory((request, response)))
@@ -468,6 +591,176 @@ mod tests {
         );
     }
 
+    /// Test XPath selectors on XML and HTML responses. These don't need a
+    /// content type
+    #[rstest]
+    #[case::xml(
+        r#"<?xml version="1.0"?><users><user id="3">Bob</user></users>"#,
+        "//user",
+        "Bob"
+    )]
+    #[case::xml_attribute(
+        r#"<users><user id="3">Bob</user></users>"#,
+        "/users/user/@id",
+        "3"
+    )]
+    #[case::html(
+        "<html><body><p>Hi<br>there</p><a href='/next'>Next</body></html>",
+        "//a/@href",
+        "/next"
+    )]
+    #[case::count("<a><b/><b/></a>", "count(//b)", "2")]
+    #[case::namespace(
+        r#"<users xmlns="https://example.com"><user>Bob</user></users>"#,
+        "//*[local-name()='user']",
+        "Bob"
+    )]
+    #[tokio::test]
+    async fn test_chain_request_xpath(
+        #[case] body: &'static str,
+        #[case] selector: &str,
+        #[case] expected_value: &str,
+    ) {
+        let recipe = Recipe::factory(());
+        let chain = Chain {
+            source: ChainSource::Request {
+                recipe: recipe.id.clone(),
+                trigger: Default::default(),
+                section: Default::default(),
+            },
+            selector: Some(selector.parse().unwrap()),
+            ..Chain::factory(())
+        };
+
+        let database = CollectionDatabase::factory(());
+        let response = ResponseRecord {
+            body: body.into(),
+            ..ResponseRecord::factory(())
+        };
+        database
+            .insert_exchange(&Exchange {
+                response: response.into(),
+                ..Exchange::factory(recipe.id.clone())
+            })
+            .unwrap();
+
+        let context = TemplateContext {
+            collection: Collection {
+                recipes: by_id([recipe]).into(),
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            database,
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(
+            render!("{{chains.chain1}}", context).unwrap(),
+            expected_value
+        );
+    }
+
+    /// When a recipe has no history, chains fall back to its example response.
+    /// History always takes precedence over the example.
+    #[rstest]
+    #[case::example(false, ChainRequestSection::Body, "example")]
+    #[case::example_header(
+        false,
+        ChainRequestSection::Header("Token".into()),
+        "example-token"
+    )]
+    #[case::example_status(false, ChainRequestSection::Status, "201")]
+    #[case::history(true, ChainRequestSection::Body, "recorded")]
+    #[tokio::test]
+    async fn test_chain_request_example(
+        #[case] has_history: bool,
+        #[case] section: ChainRequestSection,
+        #[case] expected_value: &str,
+    ) {
+        let recipe = Recipe {
+            example: Some(ResponseExample {
+                status: StatusCode::CREATED,
+                headers: indexmap! {"Token".into() => "example-token".into()},
+                body: "example".into(),
+                file: None,
+            }),
+            ..Recipe::factory(())
+        };
+        let chain = Chain {
+            source: ChainSource::Request {
+                recipe: recipe.id.clone(),
+                trigger: Default::default(),
+                section,
+            },
+            ..Chain::factory(())
+        };
+
+        let database = CollectionDatabase::factory(());
+        if has_history {
+            database
+                .insert_exchange(&Exchange {
+                    response: ResponseRecord {
+                        body: "recorded".into(),
+                        ..ResponseRecord::factory(())
+                    }
+                    .into(),
+                    ..Exchange::factory(recipe.id.clone())
+                })
+                .unwrap();
+        }
+
+        let context = TemplateContext {
+            collection: Collection {
+                recipes: by_id([recipe]).into(),
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            database,
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(
+            render!("{{chains.chain1}}", context).unwrap(),
+            expected_value
+        );
+    }
+
+    /// An example response with an invalid header can't be used
+    #[tokio::test]
+    async fn test_chain_request_invalid_example() {
+        let recipe = Recipe {
+            example: Some(ResponseExample {
+                headers: indexmap! {"bad header".into() => "value".into()},
+                ..ResponseExample::default()
+            }),
+            ..Recipe::factory(())
+        };
+        let chain = Chain {
+            source: ChainSource::Request {
+                recipe: recipe.id.clone(),
+                trigger: Default::default(),
+                section: Default::default(),
+            },
+            ..Chain::factory(())
+        };
+        let context = TemplateContext {
+            collection: Collection {
+                recipes: by_id([recipe]).into(),
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+
+        assert_err!(
+            render!("{{chains.chain1}}", context),
+            "Invalid example response for recipe"
+        );
+    }
+
     /// Test all possible error cases for chained requests. This covers all
     /// chain-specific error variants
     #[rstest]
@@ -588,6 +881,27 @@ mod tests {
         }),
         "Expected exactly one result",
     )]
+    // XPath returned multiple results
+    #[case::xpath_multiple_results(
+        Chain {
+            source: ChainSource::Request {
+                recipe: "recipe1".into(),
+                trigger: Default::default(),
+                section:Default::default()
+            },
+            selector: Some("//b".parse().unwrap()),
+            ..Chain::factory(())
+        },
+        Some("recipe1"),
+        Some(Exchange {
+            response: ResponseRecord {
+                body: "<a><b/><b/></a>".into(),
+                ..ResponseRecord::factory(())
+            }.into(),
+            ..Exchange::factory(RecipeId::from("recipe1"))
+        }),
+        "Expected exactly one result from query, but got 2",
+    )]
     #[tokio::test]
     async fn test_chain_request_error(
         #[case] chain: Chain,
@@ -675,6 +989,7 @@ mod tests {
             url: format!("{host}/get").into(),
             ..Recipe::factory(())
         };
+        let recipe_id = recipe.id.clone();
         let chain = Chain {
             source: ChainSource::Request {
                 recipe: recipe.id.clone(),
@@ -696,6 +1011,21 @@ mod tests {
         };
 
         assert_eq!(render!("{{chains.chain1}}", context).unwrap(), "hello!");
+
+        // The triggered request knows where it came from. It wasn't rendered
+        // as part of another request, so there's no parent recipe
+        let exchange = context
+            .database
+            .get_latest_request(None, &recipe_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            exchange.request.environment.trigger,
+            Some(RequestTrigger {
+                chain_id: "chain1".into(),
+                recipe_id: None,
+            })
+        );
     }
 
     /// Test success with chained command
@@ -728,6 +1058,73 @@ mod tests {
         assert_eq!(render!("{{chains.chain1}}", context).unwrap(), expected);
     }
 
+    /// In offline mode, a chain's example is used instead of running its
+    /// source. Without an example, the source still runs. Online, the example
+    /// is ignored
+    #[rstest]
+    #[case::offline(true, "example {{user_id}}", "example 1")]
+    #[case::offline_no_example(true, "", "hello!")]
+    #[case::online(false, "example", "hello!")]
+    #[tokio::test]
+    async fn test_chain_example(
+        #[case] offline: bool,
+        #[case] example: &str,
+        #[case] expected: &str,
+    ) {
+        let chain = Chain {
+            source: ChainSource::Command {
+                command: vec!["echo".into(), "-n".into(), "hello!".into()],
+                stdin: None,
+            },
+            example: Some(example.into()).filter(|_| !example.is_empty()),
+            ..Chain::factory(())
+        };
+        let profile = Profile {
+            data: indexmap! {"user_id".into() => "1".into()},
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let context = TemplateContext {
+            collection: Collection {
+                chains: by_id([chain]),
+                profiles: by_id([profile]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            offline,
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(render!("{{chains.chain1}}", context).unwrap(), expected);
+    }
+
+    /// Test extracting a value from command output with a regex
+    #[tokio::test]
+    async fn test_chain_command_regex() {
+        let chain = Chain {
+            source: ChainSource::Command {
+                command: vec!["echo".into(), "Your token is: abc123".into()],
+                stdin: None,
+            },
+            selector: Some(
+                serde_yaml::from_str("!regex {pattern: 'token is: (\\w+)'}")
+                    .unwrap(),
+            ),
+            ..Chain::factory(())
+        };
+        let context = TemplateContext {
+            collection: Collection {
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(render!("{{chains.chain1}}", context).unwrap(), "abc123");
+    }
+
     /// Test failure with chained command
     #[rstest]
     #[case::no_command(&[], None, "No command given")]
@@ -801,54 +1198,709 @@ mod tests {
         assert_eq!(render!("{{chains.chain1}}", context).unwrap(), expected);
     }
 
-    /// Test success with a chained environment variable
+    /// Test the `default` filter, which replaces missing values
     #[rstest]
-    #[case::present(Some("test!"), "test!")]
-    #[case::missing(None, "")]
+    #[case::present("{{present | default('x')}}", "value")]
+    #[case::empty_field("{{empty | default('x')}}", "x")]
+    #[case::unknown_field("{{unknown | default('x')}}", "x")]
+    #[case::nested_unknown_field("{{nested | default('x')}}", "x")]
+    #[case::unknown_chain("{{chains.unknown | default('x')}}", "x")]
+    #[case::no_response("{{chains.no_response | default('x')}}", "x")]
+    #[case::missing_file("{{chains.missing_file | default('x')}}", "x")]
+    #[case::env("{{env.SLUMBER_UNSET | default('x')}}", "x")]
+    // Filters after the default apply to the fallback too
+    #[case::then_filter("{{unknown | default('x') | upper}}", "X")]
+    #[case::chained_defaults("{{unknown | default('') | default('y')}}", "y")]
     #[tokio::test]
-    async fn test_chain_environment(
-        #[case] env_value: Option<&str>,
+    async fn test_default_filter(
+        #[case] template: &str,
         #[case] expected: &str,
     ) {
-        let source = ChainSource::Environment {
-            variable: "TEST".into(),
-        };
-        let chain = Chain {
-            source,
-            ..Chain::factory(())
+        let recipe = Recipe::factory(());
+        let profile = Profile {
+            data: indexmap! {
+                "present".into() => "value".into(),
+                "empty".into() => "".into(),
+                "nested".into() => "{{unknown}}".into(),
+            },
+            ..Profile::factory(())
         };
+        let profile_id = profile.id.clone();
+        let chains = [
+            Chain {
+                id: "no_response".into(),
+                source: ChainSource::Request {
+                    recipe: recipe.id.clone(),
+                    trigger: Default::default(),
+                    section: Default::default(),
+                },
+                ..Chain::factory(())
+            },
+            Chain {
+                id: "missing_file".into(),
+                source: ChainSource::File {
+                    path: "./not-a-real-file.txt".into(),
+                },
+                ..Chain::factory(())
+            },
+        ];
         let context = TemplateContext {
             collection: Collection {
-                chains: by_id([chain]),
+                profiles: by_id([profile]),
+                recipes: by_id([recipe]).into(),
+                chains: by_id(chains),
                 ..Collection::factory(())
             }
             .into(),
+            selected_profile: Some(profile_id),
             ..TemplateContext::factory(())
         };
-        // This prevents tests from competing for environment variables, and
-        // isolates us from the external env
+
         let result = {
-            let _guard = env_lock::lock_env([("TEST", env_value)]);
-            render!("{{chains.chain1}}", context)
+            let _guard = env_lock::lock_env([("SLUMBER_UNSET", None::<&str>)]);
+            render!(template, context)
         };
         assert_eq!(result.unwrap(), expected);
     }
 
-    /// Test success with chained file
+    /// `default` only catches missing values, not other failures
     #[rstest]
+    #[case::command(
+        "{{chains.command | default('x')}}",
+        "Resolving chain `command`"
+    )]
+    #[case::filter(
+        "{{present | base64_decode | default('x')}}",
+        "Applying filter `base64_decode`"
+    )]
     #[tokio::test]
-    async fn test_chain_file(temp_dir: TempDir) {
-        // Create a temp file that we'll read from
-        let path = temp_dir.join("stuff.txt");
-        fs::write(&path, "hello!").await.unwrap();
-        // Sanity check to debug race condition
-        assert_eq!(fs::read_to_string(&path).await.unwrap(), "hello!");
-        let path: Template = path.to_str().unwrap().into();
-
-        let chain = Chain {
-            source: ChainSource::File { path: path.clone() },
-            ..Chain::factory(())
-        };
+    async fn test_default_filter_error(
+        #[case] template: &str,
+        #[case] expected_error: &str,
+    ) {
+        let profile = Profile {
+            data: indexmap! {"present".into() => "value".into()},
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let chain = Chain {
+            id: "command".into(),
+            source: ChainSource::command(["totally not a program"]),
+            ..Chain::factory(())
+        };
+        let context = TemplateContext {
+            collection: Collection {
+                profiles: by_id([profile]),
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            ..TemplateContext::factory(())
+        };
+
+        assert_err!(render!(template, context), expected_error);
+    }
+
+    /// Built-in functions should render without any collection data
+    #[tokio::test]
+    async fn test_function() {
+        let context = TemplateContext::factory(());
+        let rendered = render!(
+            "{{uuid() | upper}} {{random_int(4, 4)}} {{now('%%')}}",
+            context
+        )
+        .unwrap();
+        let (uuid, rest) = rendered.split_once(' ').unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(uuid, uuid.to_uppercase());
+        assert_eq!(rest, "4 %");
+    }
+
+    /// Function errors should name the function
+    #[rstest]
+    #[case::unknown(
+        "{{bogus()}}",
+        "Calling function `bogus`: Unknown function"
+    )]
+    #[case::arguments(
+        "{{uuid(1)}}",
+        "Calling function `uuid`: Expected 0 argument(s), got 1"
+    )]
+    #[tokio::test]
+    async fn test_function_error(
+        #[case] template: &str,
+        #[case] expected_error: &str,
+    ) {
+        let context = TemplateContext::factory(());
+        assert_err!(render!(template, context), expected_error);
+    }
+
+    /// Test conditional blocks, which pick a branch based on whether the
+    /// condition renders to a non-empty value
+    #[rstest]
+    #[case::present("{{#if present}}yes{{/if}}", "yes")]
+    #[case::empty("{{#if empty}}yes{{/if}}", "")]
+    #[case::unknown("{{#if unknown}}yes{{else}}no{{/if}}", "no")]
+    #[case::nested_unknown("{{#if nested}}yes{{else}}no{{/if}}", "no")]
+    #[case::filter("{{#if empty | default('x')}}yes{{else}}no{{/if}}", "yes")]
+    #[case::keys(
+        "?{{#if present}}q={{present}}{{else}}q={{unknown | default('-')}}{{/if}}&",
+        "?q=value&"
+    )]
+    #[case::nested_blocks(
+        "{{#if present}}a{{#if empty}}b{{else}}c{{/if}}d{{/if}}",
+        "acd"
+    )]
+    #[tokio::test]
+    async fn test_conditional(#[case] template: &str, #[case] expected: &str) {
+        let profile = Profile {
+            data: indexmap! {
+                "present".into() => "value".into(),
+                "empty".into() => "".into(),
+                "nested".into() => "{{unknown}}".into(),
+            },
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let context = TemplateContext {
+            collection: Collection {
+                profiles: by_id([profile]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(render!(template, context).unwrap(), expected);
+    }
+
+    /// Errors other than a missing value in the condition aren't hidden. Errors
+    /// in the branch that isn't rendered are ignored.
+    #[rstest]
+    #[case::condition(
+        "{{#if present | base64_decode}}yes{{/if}}",
+        Some("Applying filter `base64_decode`")
+    )]
+    #[case::selected_branch(
+        "{{#if present}}{{unknown}}{{/if}}",
+        Some("unknown")
+    )]
+    #[case::other_branch("{{#if present}}yes{{else}}{{unknown}}{{/if}}", None)]
+    #[tokio::test]
+    async fn test_conditional_error(
+        #[case] template: &str,
+        #[case] expected_error: Option<&str>,
+    ) {
+        let profile = Profile {
+            data: indexmap! {"present".into() => "value".into()},
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let context = TemplateContext {
+            collection: Collection {
+                profiles: by_id([profile]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            ..TemplateContext::factory(())
+        };
+
+        let result = render!(template, context);
+        if let Some(expected_error) = expected_error {
+            assert_err!(result, expected_error);
+        } else {
+            assert_eq!(result.unwrap(), "yes");
+        }
+    }
+
+    /// Test loop blocks, which render their body once for each element of an
+    /// array
+    #[rstest]
+    #[case::strings("{{#each names}}{{this}};{{/each}}", "Frodo;Sam;")]
+    #[case::separator(
+        "[{{#each users sep=', '}}{{this.id}}{{/each}}]",
+        "[1, 2]"
+    )]
+    #[case::fields(
+        r#"{{#each users sep=','}}{"name":"{{this.name}}"}{{/each}}"#,
+        r#"{"name":"Frodo"},{"name":"Sam"}"#
+    )]
+    #[case::objects(
+        "{{#each users}}{{this}}{{/each}}",
+        r#"{"id":1,"name":"Frodo"}{"admin":true,"id":2,"name":"Sam"}"#
+    )]
+    #[case::empty("{{#each empty}}x{{else}}none{{/each}}", "none")]
+    #[case::empty_no_else("a{{#each empty}}x{{/each}}b", "ab")]
+    #[case::default("{{#each unknown | default('[]')}}x{{/each}}", "")]
+    #[case::outer_keys(
+        "{{#each names}}{{greeting}} {{this}}!{{/each}}",
+        "hi Frodo!hi Sam!"
+    )]
+    #[case::nested(
+        "{{#each matrix}}[{{#each this sep=','}}{{this}}{{/each}}]{{/each}}",
+        "[1,2][3]"
+    )]
+    #[case::conditional(
+        "{{#each users}}{{#if this.admin}}!{{/if}}{{this.id}}{{/each}}",
+        "1!2"
+    )]
+    #[case::field_default(
+        "{{#each names}}{{this.id | default('?')}}{{/each}}",
+        "??"
+    )]
+    #[tokio::test]
+    async fn test_loop(#[case] template: &str, #[case] expected: &str) {
+        let profile = Profile {
+            data: indexmap! {
+                "names".into() => r#"["Frodo", "Sam"]"#.into(),
+                "users".into() => "[{\"id\": 1, \"name\": \"Frodo\"}, \
+                    {\"id\": 2, \"name\": \"Sam\", \"admin\": true}]"
+                    .into(),
+                "matrix".into() => "[[1, 2], [3]]".into(),
+                "empty".into() => "[]".into(),
+                "greeting".into() => "hi".into(),
+            },
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let context = TemplateContext {
+            collection: Collection {
+                profiles: by_id([profile]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(render!(template, context).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::not_array(
+        "{{#each text}}x{{/each}}",
+        "Loop value is not a JSON array"
+    )]
+    #[case::unknown("{{#each unknown}}x{{/each}}", "Unknown field `unknown`")]
+    #[case::outside_loop("{{this}}", "can only be used inside a loop")]
+    #[case::outside_loop_field("{{this.id}}", "can only be used inside a loop")]
+    #[case::unknown_item_field(
+        "{{#each users}}{{this.email}}{{/each}}",
+        "Current loop item has no field `email`"
+    )]
+    #[tokio::test]
+    async fn test_loop_error(
+        #[case] template: &str,
+        #[case] expected_error: &str,
+    ) {
+        let profile = Profile {
+            data: indexmap! {
+                "users".into() => r#"[{"id": 1}]"#.into(),
+                "text".into() => "hello".into(),
+            },
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let context = TemplateContext {
+            collection: Collection {
+                profiles: by_id([profile]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            ..TemplateContext::factory(())
+        };
+
+        assert_err!(render!(template, context), expected_error);
+    }
+
+    /// Arithmetic, comparisons, and ternaries, inside and outside of blocks
+    #[rstest]
+    #[case::multiply("{{page * 50}}", "150")]
+    #[case::group("{{(page - 1) * 50}}", "100")]
+    #[case::fraction("{{page / 2}}", "1.5")]
+    #[case::comparison("{{page == 3}}", "true")]
+    #[case::comparison_text("{{name == 'Frodo'}}", "true")]
+    #[case::ternary("{{page > 0 ? 'yes' : 'no'}}", "yes")]
+    // Only the selected branch is rendered
+    #[case::ternary_lazy("{{page < 0 ? unknown : name}}", "Frodo")]
+    #[case::default("{{unknown + 1 | default('0')}}", "0")]
+    #[case::conditional_false(
+        "{{#if page > 5}}big{{else}}small{{/if}}",
+        "small"
+    )]
+    #[case::conditional_zero("{{#if page % 3}}x{{else}}y{{/if}}", "y")]
+    #[case::loop_item(
+        "{{#each users sep=','}}{{this.id * 10}}{{/each}}",
+        "10,20"
+    )]
+    #[tokio::test]
+    async fn test_expression(#[case] template: &str, #[case] expected: &str) {
+        let profile = Profile {
+            data: indexmap! {
+                "page".into() => "3".into(),
+                "name".into() => "Frodo".into(),
+                "users".into() => r#"[{"id": 1}, {"id": 2}]"#.into(),
+            },
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let context = TemplateContext {
+            collection: Collection {
+                profiles: by_id([profile]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(render!(template, context).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::not_a_number(
+        "{{name * 2}}",
+        "Evaluating `name * 2`: Expected a number, got `Frodo`"
+    )]
+    #[case::divide_by_zero("{{10 / (page - 3)}}", "Division by zero")]
+    #[case::unknown("{{unknown + 1}}", "Unknown field `unknown`")]
+    #[tokio::test]
+    async fn test_expression_error(
+        #[case] template: &str,
+        #[case] expected_error: &str,
+    ) {
+        let profile = Profile {
+            data: indexmap! {
+                "page".into() => "3".into(),
+                "name".into() => "Frodo".into(),
+            },
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let context = TemplateContext {
+            collection: Collection {
+                profiles: by_id([profile]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            ..TemplateContext::factory(())
+        };
+
+        assert_err!(render!(template, context), expected_error);
+    }
+
+    /// Fake data should use the context's seed, then the profile's
+    #[rstest]
+    #[case::unseeded(None, None, None)]
+    #[case::profile(Some(1), None, Some(1))]
+    #[case::context(None, Some(2), Some(2))]
+    #[case::context_precedence(Some(1), Some(2), Some(2))]
+    #[tokio::test]
+    async fn test_fake(
+        #[case] profile_seed: Option<u64>,
+        #[case] context_seed: Option<u64>,
+        #[case] expected_seed: Option<u64>,
+    ) {
+        let profile = Profile {
+            fake_seed: profile_seed,
+            ..Profile::factory(())
+        };
+        let profile_id = profile.id.clone();
+        let context = TemplateContext {
+            collection: Collection {
+                profiles: by_id([profile]),
+                ..Collection::factory(())
+            }
+            .into(),
+            selected_profile: Some(profile_id),
+            fake_seed: context_seed,
+            ..TemplateContext::factory(())
+        };
+
+        let rendered = render!("{{fake.email}}", context).unwrap();
+        assert!(rendered.contains('@'));
+        if expected_seed.is_some() {
+            assert_eq!(rendered, FakeKind::Email.generate(expected_seed));
+        }
+    }
+
+    /// Data columns are provided via overrides. Missing columns can be
+    /// replaced with a default
+    #[rstest]
+    #[case::present("{{data.name}}", "Alice")]
+    #[case::missing_default("{{data.age | default('42')}}", "42")]
+    #[tokio::test]
+    async fn test_data(#[case] template: &str, #[case] expected: &str) {
+        let context = TemplateContext {
+            overrides: indexmap! { "data.name".into() => "Alice".into() },
+            ..TemplateContext::factory(())
+        };
+        assert_eq!(render!(template, context).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_data_error() {
+        let context = TemplateContext::factory(());
+        assert_err!(
+            render!("{{data.name}}", context),
+            "No value for data column `name`"
+        );
+    }
+
+    /// Test success with a chained environment variable
+    #[rstest]
+    #[case::present(Some("test!"), "test!")]
+    #[case::missing(None, "")]
+    #[tokio::test]
+    async fn test_chain_environment(
+        #[case] env_value: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        let source = ChainSource::Environment {
+            variable: "TEST".into(),
+        };
+        let chain = Chain {
+            source,
+            ..Chain::factory(())
+        };
+        let context = TemplateContext {
+            collection: Collection {
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+        // This prevents tests from competing for environment variables, and
+        // isolates us from the external env
+        let result = {
+            let _guard = env_lock::lock_env([("TEST", env_value)]);
+            render!("{{chains.chain1}}", context)
+        };
+        assert_eq!(result.unwrap(), expected);
+    }
+
+    /// Test success with chained file
+    #[rstest]
+    #[tokio::test]
+    async fn test_chain_file(temp_dir: TempDir) {
+        // Create a temp file that we'll read from
+        let path = temp_dir.join("stuff.txt");
+        fs::write(&path, "hello!").await.unwrap();
+        // Sanity check to debug race condition
+        assert_eq!(fs::read_to_string(&path).await.unwrap(), "hello!");
+        let path: Template = path.to_str().unwrap().into();
+
+        let chain = Chain {
+            source: ChainSource::File { path: path.clone() },
+            ..Chain::factory(())
+        };
+        let context = TemplateContext {
+            collection: Collection {
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(
+            render!("{{chains.chain1}}", context).unwrap(),
+            "hello!",
+            "{path:?}"
+        );
+    }
+
+    /// Test failure with chained file
+    #[tokio::test]
+    async fn test_chain_file_error() {
+        let chain = Chain {
+            source: ChainSource::File {
+                path: "not-real".into(),
+            },
+            ..Chain::factory(())
+        };
+        let context = TemplateContext {
+            collection: Collection {
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+
+        assert_err!(
+            render!("{{chains.chain1}}", context),
+            "Reading file `not-real`"
+        );
+    }
+
+    /// Test that cached chain values are persisted across render groups
+    #[rstest]
+    #[tokio::test]
+    async fn test_chain_cache(temp_dir: TempDir) {
+        let path = temp_dir.join("stuff.txt");
+        fs::write(&path, "first").await.unwrap();
+
+        let source = || ChainSource::File {
+            path: path.to_str().unwrap().into(),
+        };
+        let cached_chain = Chain {
+            id: "cached".into(),
+            source: source(),
+            cache: Some(Duration::from_secs(60)),
+            ..Chain::factory(())
+        };
+        let uncached_chain = Chain {
+            id: "uncached".into(),
+            source: source(),
+            ..Chain::factory(())
+        };
+        let collection: Arc<Collection> = Collection {
+            chains: by_id([cached_chain, uncached_chain]),
+            ..Collection::factory(())
+        }
+        .into();
+        let database = CollectionDatabase::factory(());
+        // Each render group gets a fresh context, so the in-memory chain
+        // cache is reset
+        let context = || TemplateContext {
+            collection: Arc::clone(&collection),
+            database: database.clone(),
+            ..TemplateContext::factory(())
+        };
+        let template = "{{chains.cached}} {{chains.uncached}}";
+
+        assert_eq!(render!(template, context()).unwrap(), "first first");
+        fs::write(&path, "second").await.unwrap();
+        assert_eq!(render!(template, context()).unwrap(), "first second");
+
+        database.clear_chain_cache().unwrap();
+        assert_eq!(render!(template, context()).unwrap(), "second second");
+    }
+
+    /// Test that an exclusive chain is never computed by two render groups at
+    /// once. Chains with the same ID in different collections don't block
+    /// each other
+    #[rstest]
+    #[case::same_collection(true, "start\nend\nstart\nend\n")]
+    #[case::different_collection(false, "start\nstart\nend\nend\n")]
+    #[tokio::test]
+    async fn test_chain_exclusive(
+        temp_dir: TempDir,
+        #[case] same_collection: bool,
+        #[case] expected: &str,
+    ) {
+        let path = temp_dir.join("log.txt");
+        let script = format!(
+            "echo start >> {path}; sleep 0.1; echo end >> {path}",
+            path = path.display()
+        );
+        let chain = Chain {
+            source: ChainSource::command(["sh", "-c", &script]),
+            exclusive: true,
+            example: None,
+            ..Chain::factory(())
+        };
+        let collection: Arc<Collection> = Collection {
+            chains: by_id([chain]),
+            ..Collection::factory(())
+        }
+        .into();
+        let database = CollectionDatabase::factory(());
+        let context = |database| TemplateContext {
+            collection: Arc::clone(&collection),
+            database,
+            ..TemplateContext::factory(())
+        };
+
+        let first = context(database.clone());
+        let second = context(if same_collection {
+            database
+        } else {
+            CollectionDatabase::factory(())
+        });
+        let (first, second) = tokio::join!(
+            async { render!("{{chains.chain1}}", first) },
+            async { render!("{{chains.chain1}}", second) },
+        );
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(fs::read_to_string(&path).await.unwrap(), expected);
+    }
+
+    /// Test loading a variable from a dotenv file
+    #[rstest]
+    #[case::unquoted("TOKEN", "abc123")]
+    #[case::quoted("PASSWORD", "hunter 2")]
+    #[case::exported("EXPORTED", "yes")]
+    #[case::redefined("USER", "second")]
+    #[tokio::test]
+    async fn test_chain_env_file(
+        temp_dir: TempDir,
+        #[case] variable: &str,
+        #[case] expected: &str,
+    ) {
+        let path = temp_dir.join(".env");
+        fs::write(
+            &path,
+            "# Comment\n\
+            TOKEN=abc123\n\
+            PASSWORD=\"hunter 2\"\n\
+            export EXPORTED=yes\n\
+            USER=first\n\
+            USER=second\n",
+        )
+        .await
+        .unwrap();
+
+        let chain = Chain {
+            source: ChainSource::EnvFile {
+                path: path.to_str().unwrap().into(),
+                variable: variable.into(),
+            },
+            ..Chain::factory(())
+        };
+        let context = TemplateContext {
+            collection: Collection {
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(render!("{{chains.chain1}}", context).unwrap(), expected);
+    }
+
+    /// Test failure with dotenv file
+    #[rstest]
+    #[case::missing_file(None, "Error reading file")]
+    #[case::missing_variable(Some("OTHER=1\n"), "Variable is not defined")]
+    #[case::invalid(Some("TOKEN='unterminated\n"), "Error parsing file")]
+    #[tokio::test]
+    async fn test_chain_env_file_error(
+        temp_dir: TempDir,
+        #[case] content: Option<&str>,
+        #[case] expected_error: &str,
+    ) {
+        let path = temp_dir.join(".env");
+        if let Some(content) = content {
+            fs::write(&path, content).await.unwrap();
+        }
+
+        let chain = Chain {
+            source: ChainSource::EnvFile {
+                path: path.to_str().unwrap().into(),
+                variable: "TOKEN".into(),
+            },
+            ..Chain::factory(())
+        };
         let context = TemplateContext {
             collection: Collection {
                 chains: by_id([chain]),
@@ -858,19 +1910,66 @@ mod tests {
             ..TemplateContext::factory(())
         };
 
+        assert_err!(render!("{{chains.chain1}}", context), expected_error);
+    }
+
+    /// Test cycling through values, followed by the lines of a file
+    #[rstest]
+    #[tokio::test]
+    async fn test_chain_rotate(http_engine: &HttpEngine, temp_dir: TempDir) {
+        let path = temp_dir.join("values.txt");
+        fs::write(&path, "second\n\nthird\n").await.unwrap();
+
+        let chain = Chain {
+            source: ChainSource::Rotate {
+                values: vec!["first".into()],
+                file: Some(path.to_str().unwrap().into()),
+            },
+            ..Chain::factory(())
+        };
+        let collection: Arc<Collection> = Collection {
+            chains: by_id([chain]),
+            ..Collection::factory(())
+        }
+        .into();
+        let database = CollectionDatabase::factory(());
+        let context = |http_engine: Option<&HttpEngine>| TemplateContext {
+            collection: Arc::clone(&collection),
+            database: database.clone(),
+            http_engine: http_engine.cloned(),
+            ..TemplateContext::factory(())
+        };
+        // Multiple uses within a render group get the same value
+        let template = "{{chains.chain1}} {{chains.chain1}}";
+
         assert_eq!(
-            render!("{{chains.chain1}}", context).unwrap(),
-            "hello!",
-            "{path:?}"
+            render!(template, context(Some(http_engine))).unwrap(),
+            "first first"
+        );
+        assert_eq!(
+            render!(template, context(Some(http_engine))).unwrap(),
+            "second second"
+        );
+        // Previews don't advance the rotation
+        assert_eq!(render!(template, context(None)).unwrap(), "third third");
+        assert_eq!(
+            render!(template, context(Some(http_engine))).unwrap(),
+            "third third"
+        );
+        // Wrap around
+        assert_eq!(
+            render!(template, context(Some(http_engine))).unwrap(),
+            "first first"
         );
     }
 
-    /// Test failure with chained file
+    /// Rotating through an empty list is an error
     #[tokio::test]
-    async fn test_chain_file_error() {
+    async fn test_chain_rotate_empty() {
         let chain = Chain {
-            source: ChainSource::File {
-                path: "not-real".into(),
+            source: ChainSource::Rotate {
+                values: vec![],
+                file: None,
             },
             ..Chain::factory(())
         };
@@ -885,7 +1984,145 @@ mod tests {
 
         assert_err!(
             render!("{{chains.chain1}}", context),
-            "Reading file `not-real`"
+            "No values to rotate through"
+        );
+    }
+
+    /// Test picking random values. Zero-weight values are never picked
+    #[rstest]
+    #[case::weighted(
+        vec![
+            RandomChoice::Weighted { value: "never".into(), weight: 0 },
+            RandomChoice::Value("always".into()),
+        ],
+        None,
+        "always"
+    )]
+    #[case::file(vec![], Some("\nfrom file\n"), "from file")]
+    #[case::file_zero_weight(
+        vec![RandomChoice::Weighted { value: "never".into(), weight: 0 }],
+        Some("from file"),
+        "from file"
+    )]
+    #[tokio::test]
+    async fn test_chain_random(
+        temp_dir: TempDir,
+        #[case] values: Vec<RandomChoice>,
+        #[case] file_content: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        let file = if let Some(content) = file_content {
+            let path = temp_dir.join("values.txt");
+            fs::write(&path, content).await.unwrap();
+            Some(path.to_str().unwrap().into())
+        } else {
+            None
+        };
+        let chain = Chain {
+            source: ChainSource::Random {
+                values,
+                file,
+                seed: None,
+            },
+            ..Chain::factory(())
+        };
+        let context = TemplateContext {
+            collection: Collection {
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(render!("{{chains.chain1}}", context).unwrap(), expected);
+    }
+
+    /// With a seed, the same value is picked every time
+    #[tokio::test]
+    async fn test_chain_random_seed() {
+        let chain = Chain {
+            source: ChainSource::Random {
+                values: (0..100)
+                    .map(|i| RandomChoice::Value(i.to_string().into()))
+                    .collect(),
+                file: None,
+                seed: Some(42),
+            },
+            ..Chain::factory(())
+        };
+        let collection: Arc<Collection> = Collection {
+            chains: by_id([chain]),
+            ..Collection::factory(())
+        }
+        .into();
+        let context = || TemplateContext {
+            collection: Arc::clone(&collection),
+            ..TemplateContext::factory(())
+        };
+
+        let first = render!("{{chains.chain1}}", context()).unwrap();
+        for _ in 0..5 {
+            assert_eq!(render!("{{chains.chain1}}", context()).unwrap(), first);
+        }
+    }
+
+    /// Picking from an empty list, or one where every weight is zero, is an
+    /// error
+    #[rstest]
+    #[case::empty(vec![])]
+    #[case::zero_weight(vec![
+        RandomChoice::Weighted { value: "never".into(), weight: 0 },
+    ])]
+    #[tokio::test]
+    async fn test_chain_random_empty(#[case] values: Vec<RandomChoice>) {
+        let chain = Chain {
+            source: ChainSource::Random {
+                values,
+                file: None,
+                seed: None,
+            },
+            ..Chain::factory(())
+        };
+        let context = TemplateContext {
+            collection: Collection {
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+
+        assert_err!(
+            render!("{{chains.chain1}}", context),
+            "No values to pick from"
+        );
+    }
+
+    /// Audience is only valid for GCP ID tokens. This is checked before
+    /// calling `gcloud`, so we can test it without the CLI installed
+    #[tokio::test]
+    async fn test_chain_gcp_token_error() {
+        let chain = Chain {
+            source: ChainSource::GcpToken {
+                token_type: GcpTokenType::AccessToken,
+                audience: Some("https://example.com".into()),
+            },
+            ..Chain::factory(())
+        };
+        let context = TemplateContext {
+            collection: Collection {
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+
+        assert_err!(
+            render!("{{chains.chain1}}", context),
+            "Loading Google Cloud token: `audience` is only supported for \
+            `token_type: id_token`"
         );
     }
 
@@ -1116,6 +2353,51 @@ mod tests {
         );
     }
 
+    /// Rendered profile fields should be recorded for the request's
+    /// environment snapshot, with sensitive values masked
+    #[tokio::test]
+    async fn test_field_values() {
+        let chain = Chain {
+            source: ChainSource::Prompt {
+                message: Some("password".into()),
+                default: None,
+            },
+            sensitive: true,
+            ..Chain::factory(())
+        };
+        let profile = Profile {
+            data: indexmap! {
+                "user_id".into() => "1".into(),
+                "password".into() => "{{chains.chain1}}".into(),
+                "unused".into() => "{{chains.unknown}}".into(),
+            },
+            ..Profile::factory(())
+        };
+        let context = TemplateContext {
+            selected_profile: Some(profile.id.clone()),
+            collection: Collection {
+                profiles: by_id([profile]),
+                chains: by_id([chain]),
+                ..Collection::factory(())
+            }
+            .into(),
+            prompter: Box::new(TestPrompter::new(["hunter2"])),
+            ..TemplateContext::factory(())
+        };
+
+        assert_eq!(
+            render!("{{password}}:{{user_id | upper}}", context).unwrap(),
+            "hunter2:1"
+        );
+        assert_eq!(
+            context.state.field_values(),
+            indexmap! {
+                "password".to_owned() => "<sensitive>".to_owned(),
+                "user_id".to_owned() => "1".to_owned(),
+            }
+        );
+    }
+
     /// Test linking two chains together. This example is contribed because the
     /// command could just read the file itself, but don't worry about it it's
     /// just a test.
@@ -1219,6 +2501,93 @@ mod tests {
         assert_eq!(result.unwrap(), expected);
     }
 
+    /// Options from the collection's `environment` field apply when the
+    /// variable is unset
+    #[rstest]
+    #[case::default_present("{{env.REGION}}", Some("eu"), Ok("eu"))]
+    #[case::default_missing("{{env.REGION}}", None, Ok("us-east-1"))]
+    #[case::required_present("{{env.TOKEN}}", Some("abc"), Ok("abc"))]
+    #[case::required_missing(
+        "{{env.TOKEN}}",
+        None,
+        Err(
+            "Environment variable `TOKEN` is not set: Get a token from the \
+            dashboard"
+        )
+    )]
+    // A required variable counts as missing, so `default` can replace it
+    #[case::required_filter_default(
+        "{{env.TOKEN | default('none')}}",
+        None,
+        Ok("none")
+    )]
+    #[tokio::test]
+    async fn test_environment_options(
+        #[case] template: &str,
+        #[case] env_value: Option<&str>,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let context = TemplateContext {
+            collection: Collection {
+                environment: indexmap! {
+                    "REGION".into() => EnvironmentVariable {
+                        default: Some("us-east-1".into()),
+                        ..EnvironmentVariable::default()
+                    },
+                    "TOKEN".into() => EnvironmentVariable {
+                        required: Some("Get a token from the dashboard".into()),
+                        ..EnvironmentVariable::default()
+                    },
+                },
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+        let result = {
+            let _guard = env_lock::lock_env([
+                ("REGION", env_value),
+                ("TOKEN", env_value),
+            ]);
+            render!(template, context)
+        };
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap(), expected),
+            Err(expected) => assert_err!(result, expected),
+        }
+    }
+
+    /// Variables configured as sensitive are flagged in the rendered output
+    #[tokio::test]
+    async fn test_environment_sensitive() {
+        let context = TemplateContext {
+            collection: Collection {
+                environment: indexmap! {
+                    "TOKEN".into() => EnvironmentVariable {
+                        sensitive: true,
+                        ..EnvironmentVariable::default()
+                    },
+                },
+                ..Collection::factory(())
+            }
+            .into(),
+            ..TemplateContext::factory(())
+        };
+        let chunks = {
+            let _guard = env_lock::lock_env([("TOKEN", Some("abc"))]);
+            Template::from("{{env.TOKEN}}")
+                .render_chunks(&context)
+                .await
+        };
+        assert_eq!(
+            chunks,
+            vec![TemplateChunk::Rendered {
+                value: Arc::new("abc".into()),
+                sensitive: true
+            }]
+        );
+    }
+
     /// Test rendering non-UTF-8 data
     #[rstest]
     #[tokio::test]
@@ -1,7 +1,10 @@
 use crate::{
-    collection::{ChainId, ProfileId, RecipeId},
+    collection::{ChainId, KubernetesResourceKind, ProfileId, RecipeId},
     http::{query::QueryError, RequestBuildError, RequestError},
-    template::TemplateKey,
+    template::{
+        ExpressionError, Filter, FilterError, FunctionError, Identifier,
+        TemplateKey,
+    },
     util::doc_link,
 };
 use itertools::Itertools;
@@ -51,6 +54,30 @@ pub enum TemplateError {
     #[error("Unknown field `{field}`")]
     FieldUnknown { field: String },
 
+    /// An environment variable marked `required` in the collection is unset
+    #[error("Environment variable `{variable}` is not set: {message}")]
+    EnvironmentRequired { variable: String, message: String },
+
+    /// A `data.*` key was rendered without a data row providing that column
+    #[error(
+        "No value for data column `{column}`; data columns are provided by \
+        `slumber run --data`"
+    )]
+    DataUnknown { column: String },
+
+    /// A `this` key was rendered outside of a loop block
+    #[error("`this` can only be used inside a loop (`#each`) block")]
+    ItemOutsideLoop,
+
+    /// A `this.*` key referred to a field that the current loop item doesn't
+    /// have, or the item isn't an object
+    #[error("Current loop item has no field `{field}`")]
+    ItemFieldUnknown { field: String },
+
+    /// The value of a loop block didn't render to a JSON array
+    #[error("Loop value is not a JSON array")]
+    LoopNotArray,
+
     /// An bubbled-up error from rendering a profile field value
     #[error("Rendering nested template for field `{field}`")]
     FieldNested {
@@ -77,9 +104,58 @@ pub enum TemplateError {
         #[source]
         error: ChainError,
     },
+
+    /// A filter failed to transform a rendered value
+    #[error("Applying filter `{filter}`")]
+    Filter {
+        filter: Filter,
+        #[source]
+        error: FilterError,
+    },
+
+    /// A built-in function was unknown or failed
+    #[error("Calling function `{function}`")]
+    Function {
+        function: Identifier,
+        #[source]
+        error: FunctionError,
+    },
+
+    /// An operator in an expression couldn't be applied, e.g. arithmetic on
+    /// text. `expression` is the part of the expression that failed
+    #[error("Evaluating `{expression}`")]
+    Expression {
+        expression: String,
+        #[source]
+        error: ExpressionError,
+    },
 }
 
 impl TemplateError {
+    /// Did this error occur because the key's source doesn't exist, as
+    /// opposed to failing while loading or processing it? A `default` filter
+    /// will only replace missing values, so that real failures (e.g. a command
+    /// that exits with an error) aren't hidden.
+    pub fn is_missing(&self) -> bool {
+        match self {
+            Self::NoProfileSelected
+            | Self::FieldUnknown { .. }
+            | Self::EnvironmentRequired { .. }
+            | Self::DataUnknown { .. }
+            | Self::ItemFieldUnknown { .. } => true,
+            Self::FieldNested { error, .. } => error.is_missing(),
+            Self::Chain { error, .. } => error.is_missing(),
+            Self::ProfileUnknown { .. }
+            | Self::InvalidUtf8(_)
+            | Self::InfiniteLoop(_)
+            | Self::ItemOutsideLoop
+            | Self::LoopNotArray
+            | Self::Filter { .. }
+            | Self::Function { .. }
+            | Self::Expression { .. } => false,
+        }
+    }
+
     /// Does the given error have *any* error in its chain that contains
     /// [TriggeredRequestError::NotAllowed]? This makes it easy to attach
     /// additional error context.
@@ -125,6 +201,15 @@ pub enum ChainError {
     #[error("No response available")]
     NoResponse,
 
+    /// The recipe has no response in history, and its example response
+    /// couldn't be used instead
+    #[error("Invalid example response for recipe `{recipe_id}`")]
+    Example {
+        recipe_id: RecipeId,
+        #[source]
+        error: Arc<anyhow::Error>,
+    },
+
     /// Couldn't guess content type from request/file/etc. metadata
     #[error(
         "Selector cannot be applied; content type not provided and could not \
@@ -173,6 +258,61 @@ pub enum ChainError {
         error: Arc<io::Error>,
     },
 
+    /// Error loading a variable from a dotenv file
+    #[error("Loading `{variable}` from env file `{path}`")]
+    EnvFile {
+        path: PathBuf,
+        variable: String,
+        #[source]
+        error: Arc<anyhow::Error>,
+    },
+
+    /// Error resolving AWS credentials
+    #[error("Loading AWS credentials")]
+    AwsCredentials(#[source] Arc<anyhow::Error>),
+
+    /// Error resolving a service via Consul
+    #[error("Resolving service from Consul")]
+    Consul(#[source] Arc<anyhow::Error>),
+
+    /// Error getting an access token from Azure
+    #[error("Loading Azure access token")]
+    AzureToken(#[source] Arc<anyhow::Error>),
+
+    /// Error minting a token from Google Cloud
+    #[error("Loading Google Cloud token")]
+    GcpToken(#[source] Arc<anyhow::Error>),
+
+    /// Error loading a secret from the OS secret store
+    #[error(
+        "Loading secret for user `{user}` from keyring service `{service}`"
+    )]
+    Keyring {
+        service: String,
+        user: String,
+        #[source]
+        error: Arc<anyhow::Error>,
+    },
+
+    /// Error loading a value from a Kubernetes resource. This covers both
+    /// `kubectl` failures and a missing/malformed key in the resource
+    #[error("Loading `{key}` from Kubernetes {kind} `{name}`")]
+    Kubernetes {
+        kind: KubernetesResourceKind,
+        name: String,
+        key: String,
+        #[source]
+        error: Arc<anyhow::Error>,
+    },
+
+    /// `!rotate` chain has no values in its list or its file
+    #[error("No values to rotate through")]
+    RotateEmpty,
+
+    /// `!random` chain has no values, or all their weights are zero
+    #[error("No values to pick from")]
+    RandomEmpty,
+
     /// Never got a response from the prompt channel. Do *not* store the
     /// `RecvError` here, because it provides useless extra output to the user.
     #[error("No response from prompt/select")]
@@ -192,6 +332,29 @@ pub enum ChainError {
     MissingHeader { header: String },
 }
 
+impl ChainError {
+    /// Is the chain's data missing? See [TemplateError::is_missing]
+    fn is_missing(&self) -> bool {
+        match self {
+            Self::ChainUnknown(_)
+            | Self::RecipeUnknown(_)
+            | Self::NoResponse
+            | Self::MissingHeader { .. }
+            // Selector didn't find anything
+            | Self::Query(
+                QueryError::InvalidResult { actual_count: 0 }
+                | QueryError::RegexNoMatch { .. }
+                | QueryError::RegexGroupUnmatched { .. },
+            ) => true,
+            Self::File { error, .. } => {
+                error.kind() == io::ErrorKind::NotFound
+            }
+            Self::Nested { error, .. } => error.is_missing(),
+            _ => false,
+        }
+    }
+}
+
 /// Error occurred while trying to build/execute a triggered request.
 ///
 /// This type implements `Clone` so it can be shared between deduplicated chain
@@ -234,6 +397,16 @@ impl PartialEq for ChainError {
                     error: r_error,
                 },
             ) => l_recipe_id == r_recipe_id && l_error == r_error,
+            (
+                Self::Example {
+                    recipe_id: l_recipe_id,
+                    error: l_error,
+                },
+                Self::Example {
+                    recipe_id: r_recipe_id,
+                    error: r_error,
+                },
+            ) => l_recipe_id == r_recipe_id && Arc::ptr_eq(l_error, r_error),
             (
                 Self::ParseResponse { error: l_error },
                 Self::ParseResponse { error: r_error },
@@ -259,6 +432,63 @@ impl PartialEq for ChainError {
                     error: r_error,
                 },
             ) => l_path == r_path && Arc::ptr_eq(l_error, r_error),
+            (
+                Self::EnvFile {
+                    path: l_path,
+                    variable: l_variable,
+                    error: l_error,
+                },
+                Self::EnvFile {
+                    path: r_path,
+                    variable: r_variable,
+                    error: r_error,
+                },
+            ) => {
+                l_path == r_path
+                    && l_variable == r_variable
+                    && Arc::ptr_eq(l_error, r_error)
+            }
+            (Self::AwsCredentials(l0), Self::AwsCredentials(r0)) => {
+                Arc::ptr_eq(l0, r0)
+            }
+            (Self::AzureToken(l0), Self::AzureToken(r0)) => Arc::ptr_eq(l0, r0),
+            (Self::Consul(l0), Self::Consul(r0)) => Arc::ptr_eq(l0, r0),
+            (Self::GcpToken(l0), Self::GcpToken(r0)) => Arc::ptr_eq(l0, r0),
+            (
+                Self::Keyring {
+                    service: l_service,
+                    user: l_user,
+                    error: l_error,
+                },
+                Self::Keyring {
+                    service: r_service,
+                    user: r_user,
+                    error: r_error,
+                },
+            ) => {
+                l_service == r_service
+                    && l_user == r_user
+                    && Arc::ptr_eq(l_error, r_error)
+            }
+            (
+                Self::Kubernetes {
+                    kind: l_kind,
+                    name: l_name,
+                    key: l_key,
+                    error: l_error,
+                },
+                Self::Kubernetes {
+                    kind: r_kind,
+                    name: r_name,
+                    key: r_key,
+                    error: r_error,
+                },
+            ) => {
+                l_kind == r_kind
+                    && l_name == r_name
+                    && l_key == r_key
+                    && Arc::ptr_eq(l_error, r_error)
+            }
             (
                 Self::Nested {
                     field: l_field,
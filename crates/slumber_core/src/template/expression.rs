@@ -0,0 +1,402 @@
+//! Arithmetic and comparison expressions inside template keys, e.g.
+//! `{{page * 50}}` or `{{count > 0 ? 'yes' : 'no'}}`
+
+use crate::template::{filter::StringLiteral, TemplateKey};
+use derive_more::Display;
+use std::{
+    cmp::Ordering,
+    fmt::{self, Formatter},
+};
+use thiserror::Error;
+
+/// An expression that combines keys and literals with operators. A key is only
+/// parsed as an expression if it contains at least one operator or
+/// parentheses, so a lone key or literal is never an expression.
+///
+/// The `Display` impl here should return exactly what this was parsed from,
+/// with whitespace normalized.
+#[derive(Clone, Debug, Display, PartialEq)]
+pub enum Expression {
+    Operand(Operand),
+    /// A parenthesized sub-expression. This is kept in the tree, rather than
+    /// just used to guide parsing, so the expression can be stringified back
+    /// to its source.
+    #[display("({_0})")]
+    Group(Box<Self>),
+    /// Apply an operator to two sub-expressions, e.g. `page * 50`
+    #[display("{left} {operator} {right}")]
+    Binary {
+        left: Box<Self>,
+        operator: Operator,
+        right: Box<Self>,
+    },
+    /// `condition ? then : otherwise`. Only the selected branch is evaluated.
+    #[display("{condition} ? {then} : {otherwise}")]
+    Ternary {
+        condition: Box<Self>,
+        then: Box<Self>,
+        otherwise: Box<Self>,
+    },
+}
+
+impl Expression {
+    pub fn binary(left: Self, operator: Operator, right: Self) -> Self {
+        Self::Binary {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        }
+    }
+
+    pub fn ternary(condition: Self, then: Self, otherwise: Self) -> Self {
+        Self::Ternary {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+        }
+    }
+
+    /// Get every key used as an operand, in order of appearance. This includes
+    /// keys in both branches of ternaries.
+    pub fn keys(&self) -> Vec<&TemplateKey> {
+        fn collect<'a>(
+            expression: &'a Expression,
+            keys: &mut Vec<&'a TemplateKey>,
+        ) {
+            match expression {
+                Expression::Operand(Operand::Key(key)) => keys.push(key),
+                Expression::Operand(_) => {}
+                Expression::Group(inner) => collect(inner, keys),
+                Expression::Binary { left, right, .. } => {
+                    collect(left, keys);
+                    collect(right, keys);
+                }
+                Expression::Ternary {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    collect(condition, keys);
+                    collect(then, keys);
+                    collect(otherwise, keys);
+                }
+            }
+        }
+
+        let mut keys = Vec::new();
+        collect(self, &mut keys);
+        keys
+    }
+}
+
+/// A single value in an expression
+#[derive(Clone, Debug, Display, PartialEq)]
+pub enum Operand {
+    /// Any key other than an expression, e.g. `page` or `chains.count`
+    Key(TemplateKey),
+    Number(f64),
+    #[display("{}", StringLiteral(_0))]
+    String(String),
+}
+
+/// A binary operator. Arithmetic operators require both sides to be numbers.
+/// Comparisons compare numerically if both sides are numbers, and as text
+/// otherwise.
+#[derive(Copy, Clone, Debug, Display, PartialEq)]
+pub enum Operator {
+    #[display("+")]
+    Add,
+    #[display("-")]
+    Subtract,
+    #[display("*")]
+    Multiply,
+    #[display("/")]
+    Divide,
+    #[display("%")]
+    Remainder,
+    #[display("==")]
+    Equal,
+    #[display("!=")]
+    NotEqual,
+    #[display("<")]
+    Less,
+    #[display("<=")]
+    LessOrEqual,
+    #[display(">")]
+    Greater,
+    #[display(">=")]
+    GreaterOrEqual,
+}
+
+impl Operator {
+    /// Apply this operator to two evaluated operands
+    pub fn apply(
+        self,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, ExpressionError> {
+        let arithmetic =
+            |f: fn(f64, f64) -> f64| -> Result<_, ExpressionError> {
+                Ok(Value::Number(f(left.to_number()?, right.to_number()?)))
+            };
+        let compare = |f: fn(Ordering) -> bool| -> Result<_, ExpressionError> {
+            let ordering = match (left.as_number(), right.as_number()) {
+                (Some(left), Some(right)) => {
+                    // Both numbers are finite, so they're always comparable
+                    left.partial_cmp(&right).unwrap_or(Ordering::Equal)
+                }
+                _ => left.to_string().cmp(&right.to_string()),
+            };
+            Ok(Value::Bool(f(ordering)))
+        };
+
+        match self {
+            Self::Add => arithmetic(|a, b| a + b),
+            Self::Subtract => arithmetic(|a, b| a - b),
+            Self::Multiply => arithmetic(|a, b| a * b),
+            Self::Divide | Self::Remainder => {
+                let (left, right) = (left.to_number()?, right.to_number()?);
+                if right == 0.0 {
+                    Err(ExpressionError::DivideByZero)
+                } else if self == Self::Divide {
+                    Ok(Value::Number(left / right))
+                } else {
+                    Ok(Value::Number(left % right))
+                }
+            }
+            Self::Equal => compare(Ordering::is_eq),
+            Self::NotEqual => compare(Ordering::is_ne),
+            Self::Less => compare(Ordering::is_lt),
+            Self::LessOrEqual => compare(Ordering::is_le),
+            Self::Greater => compare(Ordering::is_gt),
+            Self::GreaterOrEqual => compare(Ordering::is_ge),
+        }
+    }
+}
+
+/// The result of evaluating an expression or one of its operands. Keys always
+/// evaluate to strings, which are converted to numbers when needed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    String(String),
+    Bool(bool),
+}
+
+impl Value {
+    /// Get the numeric value of this, if it has one. Strings are parsed,
+    /// ignoring surrounding whitespace. Infinity and NaN aren't numbers as
+    /// far as we're concerned.
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Number(number) => Some(*number),
+            Self::String(s) => s
+                .trim()
+                .parse()
+                .ok()
+                .filter(|number: &f64| number.is_finite()),
+            Self::Bool(_) => None,
+        }
+    }
+
+    /// Get the numeric value of this, or an error if it isn't a number
+    fn to_number(&self) -> Result<f64, ExpressionError> {
+        self.as_number().ok_or_else(|| ExpressionError::NotANumber {
+            value: self.to_string(),
+        })
+    }
+
+    /// Is this value true, for the purposes of a condition? `false`, zero, and
+    /// empty strings are false. Strings are *not* parsed here, so a key that
+    /// renders to `0` is true, the same as in an `{{#if}}` block.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Self::Number(number) => *number != 0.0,
+            Self::String(s) => !s.is_empty(),
+            Self::Bool(b) => *b,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            // Whole numbers are written without a decimal point. Adding zero
+            // turns -0 into 0
+            Self::Number(number) => write!(f, "{}", number + 0.0),
+            Self::String(s) => write!(f, "{s}"),
+            Self::Bool(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+/// Error evaluating an [Expression]
+#[derive(Clone, Debug, Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum ExpressionError {
+    /// Arithmetic on a value that isn't a number
+    #[error("Expected a number, got `{value}`")]
+    NotANumber { value: String },
+
+    #[error("Division by zero")]
+    DivideByZero,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_err;
+    use rstest::rstest;
+
+    fn number(value: f64) -> Value {
+        Value::Number(value)
+    }
+
+    fn string(value: &str) -> Value {
+        Value::String(value.into())
+    }
+
+    #[rstest]
+    #[case::add(Operator::Add, number(2.0), string("3"), number(5.0))]
+    #[case::subtract(
+        Operator::Subtract,
+        number(2.0),
+        number(3.0),
+        number(-1.0)
+    )]
+    #[case::multiply(
+        Operator::Multiply,
+        string(" 4 "),
+        number(2.5),
+        number(10.0)
+    )]
+    #[case::divide(Operator::Divide, number(7.0), number(2.0), number(3.5))]
+    #[case::remainder(
+        Operator::Remainder,
+        number(7.0),
+        number(2.0),
+        number(1.0)
+    )]
+    #[case::equal_numeric(
+        Operator::Equal,
+        string("1.0"),
+        number(1.0),
+        Value::Bool(true)
+    )]
+    #[case::equal_text(
+        Operator::Equal,
+        string("a"),
+        string("a"),
+        Value::Bool(true)
+    )]
+    #[case::not_equal(
+        Operator::NotEqual,
+        string("a"),
+        string("b"),
+        Value::Bool(true)
+    )]
+    #[case::less_numeric(
+        Operator::Less,
+        string("9"),
+        string("10"),
+        Value::Bool(true)
+    )]
+    #[case::less_text(
+        Operator::Less,
+        string("b"),
+        string("abc"),
+        Value::Bool(false)
+    )]
+    #[case::less_or_equal(
+        Operator::LessOrEqual,
+        number(2.0),
+        number(2.0),
+        Value::Bool(true)
+    )]
+    #[case::greater(
+        Operator::Greater,
+        number(0.0),
+        number(0.0),
+        Value::Bool(false)
+    )]
+    #[case::greater_or_equal(
+        Operator::GreaterOrEqual,
+        number(1.0),
+        number(0.0),
+        Value::Bool(true)
+    )]
+    #[case::compare_bool(
+        Operator::Equal,
+        Value::Bool(true),
+        string("true"),
+        Value::Bool(true)
+    )]
+    fn test_apply(
+        #[case] operator: Operator,
+        #[case] left: Value,
+        #[case] right: Value,
+        #[case] expected: Value,
+    ) {
+        assert_eq!(operator.apply(left, right).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::not_a_number(
+        Operator::Add,
+        string("abc"),
+        number(1.0),
+        "Expected a number, got `abc`"
+    )]
+    #[case::bool(
+        Operator::Multiply,
+        number(1.0),
+        Value::Bool(true),
+        "Expected a number, got `true`"
+    )]
+    #[case::infinity(
+        Operator::Add,
+        string("inf"),
+        number(1.0),
+        "Expected a number, got `inf`"
+    )]
+    #[case::divide_by_zero(
+        Operator::Divide,
+        number(1.0),
+        string("0"),
+        "Division by zero"
+    )]
+    #[case::remainder_by_zero(
+        Operator::Remainder,
+        number(1.0),
+        number(0.0),
+        "Division by zero"
+    )]
+    fn test_apply_error(
+        #[case] operator: Operator,
+        #[case] left: Value,
+        #[case] right: Value,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(operator.apply(left, right), expected_error);
+    }
+
+    #[rstest]
+    #[case::whole(number(100.0), "100")]
+    #[case::fraction(number(2.5), "2.5")]
+    #[case::negative_zero(number(-0.0), "0")]
+    #[case::bool(Value::Bool(false), "false")]
+    fn test_display_value(#[case] value: Value, #[case] expected: &str) {
+        assert_eq!(value.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case::zero(number(0.0), false)]
+    #[case::number(number(3.0), true)]
+    #[case::empty(string(""), false)]
+    #[case::string_zero(string("0"), true)]
+    #[case::false_(Value::Bool(false), false)]
+    #[case::true_(Value::Bool(true), true)]
+    fn test_is_truthy(#[case] value: Value, #[case] expected: bool) {
+        assert_eq!(value.is_truthy(), expected);
+    }
+}
@@ -0,0 +1,138 @@
+//! Randomly generated placeholder data, rendered with syntax like
+//! `{{fake.email}}`
+
+use fake::{
+    faker::{
+        address::en::{CityName, CountryName, StateName, StreetName, ZipCode},
+        company::en::CompanyName,
+        internet::en::{IPv4, Password, SafeEmail, Username},
+        lorem::en::{Paragraph, Sentence, Word},
+        name::en::{FirstName, LastName, Name},
+        phone_number::en::PhoneNumber,
+    },
+    Fake,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use strum::{EnumIter, EnumString, IntoStaticStr};
+
+/// A type of fake data that can be generated. The name of each variant, in
+/// snake_case, is what goes after `fake.` in a template.
+///
+/// The `Display` impl here should return exactly what this was parsed from.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    strum::Display,
+    EnumIter,
+    EnumString,
+    IntoStaticStr,
+    PartialEq,
+)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[strum(serialize_all = "snake_case")]
+pub enum FakeKind {
+    /// Full name
+    Name,
+    FirstName,
+    LastName,
+    /// Email address on a reserved domain (e.g. `example.com`), so generated
+    /// addresses never belong to anyone
+    Email,
+    Username,
+    Password,
+    Phone,
+    Company,
+    StreetName,
+    City,
+    State,
+    Country,
+    ZipCode,
+    Ipv4,
+    /// A single lorem ipsum word
+    Word,
+    /// A lorem ipsum sentence
+    Sentence,
+    /// A lorem ipsum paragraph
+    Paragraph,
+}
+
+impl FakeKind {
+    /// Generate a value. With a seed, the value is the same every time for a
+    /// given kind. Without one, it's different every time.
+    pub fn generate(self, seed: Option<u64>) -> String {
+        match seed {
+            Some(seed) => {
+                // Mix in the kind, so fake.first_name and fake.last_name
+                // don't share a sequence
+                let seed = seed ^ hash(self.into());
+                self.generate_with(&mut StdRng::seed_from_u64(seed))
+            }
+            None => self.generate_with(&mut rand::thread_rng()),
+        }
+    }
+
+    fn generate_with(self, rng: &mut impl Rng) -> String {
+        match self {
+            Self::Name => Name().fake_with_rng(rng),
+            Self::FirstName => FirstName().fake_with_rng(rng),
+            Self::LastName => LastName().fake_with_rng(rng),
+            Self::Email => SafeEmail().fake_with_rng(rng),
+            Self::Username => Username().fake_with_rng(rng),
+            Self::Password => Password(12..20).fake_with_rng(rng),
+            Self::Phone => PhoneNumber().fake_with_rng(rng),
+            Self::Company => CompanyName().fake_with_rng(rng),
+            Self::StreetName => StreetName().fake_with_rng(rng),
+            Self::City => CityName().fake_with_rng(rng),
+            Self::State => StateName().fake_with_rng(rng),
+            Self::Country => CountryName().fake_with_rng(rng),
+            Self::ZipCode => ZipCode().fake_with_rng(rng),
+            Self::Ipv4 => IPv4().fake_with_rng(rng),
+            Self::Word => Word().fake_with_rng(rng),
+            Self::Sentence => Sentence(4..10).fake_with_rng(rng),
+            Self::Paragraph => Paragraph(3..6).fake_with_rng(rng),
+        }
+    }
+}
+
+/// FNV-1a hash of a string. std's hasher isn't guaranteed to be stable between
+/// releases, which would break reproducibility of seeded values
+fn hash(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use strum::IntoEnumIterator;
+
+    /// Every kind should generate a non-empty value
+    #[test]
+    fn test_generate() {
+        for kind in FakeKind::iter() {
+            assert!(!kind.generate(None).is_empty(), "{kind} is empty");
+        }
+        assert!(FakeKind::Email.generate(None).contains('@'));
+        FakeKind::Ipv4.generate(None).parse::<Ipv4Addr>().unwrap();
+    }
+
+    /// Seeded values should be reproducible, and unique per kind
+    #[test]
+    fn test_generate_seed() {
+        assert_eq!(
+            FakeKind::Email.generate(Some(3)),
+            FakeKind::Email.generate(Some(3))
+        );
+        assert_ne!(
+            FakeKind::Email.generate(Some(3)),
+            FakeKind::Email.generate(Some(4))
+        );
+        assert_ne!(
+            FakeKind::FirstName.generate(Some(3)),
+            FakeKind::LastName.generate(Some(3))
+        );
+    }
+}
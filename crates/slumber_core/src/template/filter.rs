@@ -0,0 +1,599 @@
+//! Filters that transform a rendered template value, applied with pipe syntax
+//! like `{{chains.token | trim | base64}}`
+
+use crate::util::hex;
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
+    Engine,
+};
+use itertools::Itertools;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+    fmt::{self, Display, Formatter},
+    iter,
+    str::Utf8Error,
+};
+use strum::EnumString;
+use thiserror::Error;
+
+/// Characters to percent-encode in `urlencode`. Everything except the
+/// unreserved characters from RFC 3986
+const URL_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Padding is optional in `base64_decode`, because it's often stripped
+const LENIENT_CONFIG: GeneralPurposeConfig = GeneralPurposeConfig::new()
+    .with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const BASE64_STANDARD_LENIENT: GeneralPurpose =
+    GeneralPurpose::new(&alphabet::STANDARD, LENIENT_CONFIG);
+const BASE64_URL_SAFE_LENIENT: GeneralPurpose =
+    GeneralPurpose::new(&alphabet::URL_SAFE, LENIENT_CONFIG);
+
+/// A built-in transformation for a rendered value. Filters are applied left to
+/// right, each one receiving the output of the previous.
+///
+/// The `Display` impl here should return exactly what this was parsed from.
+#[derive(Clone, Debug, derive_more::Display, EnumString, PartialEq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[strum(serialize_all = "snake_case")]
+pub enum Filter {
+    /// Replace a missing or empty value with a literal. This doesn't transform
+    /// values, so it's handled by the renderer instead of [Filter::apply]. It
+    /// takes an argument, so it can't be parsed by name alone.
+    #[display("default({})", StringLiteral(_0))]
+    #[strum(disabled)]
+    Default(String),
+    /// Remove leading and trailing whitespace
+    #[display("trim")]
+    Trim,
+    /// Convert to uppercase
+    #[display("upper")]
+    Upper,
+    /// Convert to lowercase
+    #[display("lower")]
+    Lower,
+    /// Percent-encode everything except unreserved URL characters
+    #[display("urlencode")]
+    Urlencode,
+    /// Decode percent-encoded characters, and `+` as a space, so query
+    /// strings and form values can be decoded too
+    #[display("urldecode")]
+    Urldecode,
+    /// Encode with standard base64
+    #[display("base64")]
+    Base64,
+    /// Decode base64 with either alphabet, with or without padding
+    #[display("base64_decode")]
+    Base64Decode,
+    /// Encode with the URL-safe base64 alphabet, without padding
+    #[display("base64url")]
+    #[strum(serialize = "base64url")]
+    Base64Url,
+    /// Encode as lowercase hex
+    #[display("hex")]
+    Hex,
+    /// Escape for use inside a JSON string. Surrounding quotes are *not* added
+    #[display("json_escape")]
+    JsonEscape,
+    /// SHA-256 hash, as lowercase hex
+    #[display("sha256")]
+    Sha256,
+    /// SHA-512 hash, as lowercase hex
+    #[display("sha512")]
+    Sha512,
+    /// Replace every occurrence of a substring. The pattern is literal text,
+    /// not a regex
+    #[display("replace({}, {})", StringLiteral(from), StringLiteral(to))]
+    #[strum(disabled)]
+    Replace { from: String, to: String },
+    /// Split on a separator and take one part. Negative indexes count from
+    /// the end, and an index that's out of range gives an empty value
+    #[display("split({}, {index})", StringLiteral(separator))]
+    #[strum(disabled)]
+    Split { separator: String, index: i64 },
+    /// Take the characters from `start` up to (but not including) `end`, or
+    /// to the end of the value if `end` isn't given. Negative indexes count
+    /// from the end, and indexes past either end are clamped.
+    #[display("{}", DisplaySubstring(*start, *end))]
+    #[strum(disabled)]
+    Substring { start: i64, end: Option<i64> },
+    /// Pad the start of the value to a minimum width in characters, with
+    /// spaces or the given fill character
+    #[display("{}", DisplayPad(*width, *fill))]
+    #[strum(disabled)]
+    Pad { width: usize, fill: Option<char> },
+    /// Convert to `snake_case`
+    #[display("snake_case")]
+    SnakeCase,
+    /// Convert to `kebab-case`
+    #[display("kebab_case")]
+    KebabCase,
+    /// Convert to `camelCase`
+    #[display("camel_case")]
+    CamelCase,
+    /// Convert to `PascalCase`
+    #[display("pascal_case")]
+    PascalCase,
+    /// Round a number to the nearest integer. Halves round away from zero
+    #[display("round")]
+    Round,
+    /// Format a number with exactly this many decimal places, e.g. `fixed(2)`
+    /// turns `3.14159` into `3.14`
+    #[display("fixed({digits})")]
+    #[strum(disabled)]
+    Fixed { digits: u8 },
+    /// Group the digits of a number's integer part in threes, with commas or
+    /// the given separator, e.g. `1234567.5` becomes `1,234,567.5`
+    #[display("{}", DisplayThousands(*separator))]
+    #[strum(disabled)]
+    Thousands { separator: Option<char> },
+}
+
+impl Filter {
+    /// Transform a value
+    pub fn apply(&self, value: Vec<u8>) -> Result<Vec<u8>, FilterError> {
+        let text = || std::str::from_utf8(&value);
+        let output = match self {
+            Self::Default(_) => value,
+            Self::Trim => text()?.trim().into(),
+            Self::Upper => text()?.to_uppercase().into_bytes(),
+            Self::Lower => text()?.to_lowercase().into_bytes(),
+            Self::Urlencode => {
+                percent_encoding::percent_encode(&value, URL_ENCODE_SET)
+                    .to_string()
+                    .into_bytes()
+            }
+            Self::Urldecode => {
+                let value: Vec<u8> = value
+                    .into_iter()
+                    .map(|b| if b == b'+' { b' ' } else { b })
+                    .collect();
+                percent_encoding::percent_decode(&value).collect()
+            }
+            Self::Base64 => BASE64_STANDARD.encode(&value).into_bytes(),
+            Self::Base64Url => {
+                BASE64_URL_SAFE_NO_PAD.encode(&value).into_bytes()
+            }
+            Self::Base64Decode => {
+                let engine = if value.iter().any(|b| matches!(b, b'-' | b'_')) {
+                    &BASE64_URL_SAFE_LENIENT
+                } else {
+                    &BASE64_STANDARD_LENIENT
+                };
+                engine.decode(value.trim_ascii())?
+            }
+            Self::Hex => hex(&value).into_bytes(),
+            Self::JsonEscape => {
+                let json = serde_json::Value::from(text()?).to_string();
+                // Strip the surrounding quotes
+                json[1..json.len() - 1].into()
+            }
+            Self::Sha256 => hex(&Sha256::digest(&value)).into_bytes(),
+            Self::Sha512 => hex(&Sha512::digest(&value)).into_bytes(),
+            Self::Replace { from, to } => {
+                // An empty pattern would match between every character
+                if from.is_empty() {
+                    value
+                } else {
+                    text()?.replace(from.as_str(), to).into_bytes()
+                }
+            }
+            Self::Split { separator, index } => {
+                let text = text()?;
+                let parts: Vec<&str> = if separator.is_empty() {
+                    // Split into individual characters
+                    text.char_indices()
+                        .map(|(i, c)| &text[i..i + c.len_utf8()])
+                        .collect()
+                } else {
+                    text.split(separator.as_str()).collect()
+                };
+                resolve_index(*index, parts.len())
+                    .and_then(|index| parts.get(index))
+                    .copied()
+                    .unwrap_or_default()
+                    .into()
+            }
+            Self::Substring { start, end } => {
+                let chars: Vec<char> = text()?.chars().collect();
+                let clamp = |index: i64| {
+                    resolve_index(index, chars.len())
+                        .unwrap_or(0)
+                        .min(chars.len())
+                };
+                let start = clamp(*start);
+                let end = end.map(clamp).unwrap_or(chars.len());
+                chars[start..end.max(start)]
+                    .iter()
+                    .collect::<String>()
+                    .into_bytes()
+            }
+            Self::Pad { width, fill } => {
+                let text = text()?;
+                let padding = width.saturating_sub(text.chars().count());
+                let mut output = iter::repeat(fill.unwrap_or(' '))
+                    .take(padding)
+                    .collect::<String>();
+                output.push_str(text);
+                output.into_bytes()
+            }
+            Self::SnakeCase => words(text()?)
+                .into_iter()
+                .map(str::to_lowercase)
+                .join("_")
+                .into_bytes(),
+            Self::KebabCase => words(text()?)
+                .into_iter()
+                .map(str::to_lowercase)
+                .join("-")
+                .into_bytes(),
+            Self::CamelCase => words(text()?)
+                .into_iter()
+                .enumerate()
+                .map(|(i, word)| {
+                    if i == 0 {
+                        word.to_lowercase()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .join("")
+                .into_bytes(),
+            Self::PascalCase => words(text()?)
+                .into_iter()
+                .map(capitalize)
+                .join("")
+                .into_bytes(),
+            // Adding zero turns -0 into 0
+            Self::Round => (parse_number(text()?)?.round() + 0.0)
+                .to_string()
+                .into_bytes(),
+            Self::Fixed { digits } => {
+                let number = parse_number(text()?)?;
+                format!("{number:.*}", usize::from(*digits)).into_bytes()
+            }
+            Self::Thousands { separator } => {
+                let text = text()?.trim();
+                let number = parse_number(text)?;
+                // Group the original text where possible, so large integers
+                // don't lose precision. Anything else, e.g. `1e6`, is
+                // normalized first
+                let is_plain = text.bytes().all(|b| {
+                    b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.')
+                });
+                let text = if is_plain {
+                    text.to_owned()
+                } else {
+                    (number + 0.0).to_string()
+                };
+                group_thousands(&text, separator.unwrap_or(',')).into_bytes()
+            }
+        };
+        Ok(output)
+    }
+}
+
+/// Parse a value as a number for the number formatting filters, ignoring
+/// surrounding whitespace. Infinity and NaN aren't numbers.
+fn parse_number(text: &str) -> Result<f64, FilterError> {
+    text.trim()
+        .parse()
+        .ok()
+        .filter(|number: &f64| number.is_finite())
+        .ok_or_else(|| FilterError::NotANumber {
+            value: text.to_owned(),
+        })
+}
+
+/// Insert a separator between each group of three digits in the integer part
+/// of a decimal number. The sign and fractional part are left alone.
+fn group_thousands(number: &str, separator: char) -> String {
+    let (sign, unsigned) = match number.strip_prefix(['+', '-']) {
+        Some(unsigned) => number.split_at(number.len() - unsigned.len()),
+        None => ("", number),
+    };
+    let (integer, fraction) = match unsigned.find('.') {
+        Some(index) => unsigned.split_at(index),
+        None => (unsigned, ""),
+    };
+
+    let mut output = sign.to_owned();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            output.push(separator);
+        }
+        output.push(digit);
+    }
+    output.push_str(fraction);
+    output
+}
+
+/// Convert a possibly negative index into a position in a sequence of `len`
+/// items. Negative indexes count back from the end. Returns `None` if a
+/// negative index reaches past the start.
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    if index >= 0 {
+        usize::try_from(index).ok()
+    } else {
+        len.checked_sub(usize::try_from(index.unsigned_abs()).ok()?)
+    }
+}
+
+/// Split text into words for case conversion. Words are separated by any
+/// character that isn't a letter or digit, and by case changes: `userId` is
+/// `user` and `Id`, and `HTTPServer` is `HTTP` and `Server`.
+fn words(text: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut words = Vec::new();
+    let mut start: Option<usize> = None;
+    for (i, &(position, c)) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if let Some(start) = start.take() {
+                words.push(&text[start..position]);
+            }
+            continue;
+        }
+
+        if let Some(word_start) = start {
+            let previous = chars[i - 1].1;
+            let next_is_lower = chars
+                .get(i + 1)
+                .is_some_and(|(_, next)| next.is_lowercase());
+            let is_boundary =
+                c.is_uppercase() && (!previous.is_uppercase() || next_is_lower);
+            if is_boundary {
+                words.push(&text[word_start..position]);
+                start = Some(position);
+            }
+        } else {
+            start = Some(position);
+        }
+    }
+    if let Some(start) = start {
+        words.push(&text[start..]);
+    }
+    words
+}
+
+/// Uppercase the first character of a word and lowercase the rest
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+/// Display a string as a single-quoted literal, escaping quotes and
+/// backslashes
+pub(super) struct StringLiteral<'a>(pub &'a str);
+
+impl<'a> Display for StringLiteral<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "'")?;
+        for c in self.0.chars() {
+            if c == '\'' || c == '\\' {
+                write!(f, "\\")?;
+            }
+            write!(f, "{c}")?;
+        }
+        write!(f, "'")
+    }
+}
+
+/// Display `substring(start)` or `substring(start, end)`
+struct DisplaySubstring(i64, Option<i64>);
+
+impl Display for DisplaySubstring {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self(start, None) => write!(f, "substring({start})"),
+            Self(start, Some(end)) => write!(f, "substring({start}, {end})"),
+        }
+    }
+}
+
+/// Display `pad(width)` or `pad(width, 'fill')`
+struct DisplayPad(usize, Option<char>);
+
+impl Display for DisplayPad {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self(width, None) => write!(f, "pad({width})"),
+            Self(width, Some(fill)) => {
+                write!(f, "pad({width}, {})", StringLiteral(&fill.to_string()))
+            }
+        }
+    }
+}
+
+/// Display `thousands` or `thousands('sep')`
+struct DisplayThousands(Option<char>);
+
+impl Display for DisplayThousands {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self(None) => write!(f, "thousands"),
+            Self(Some(separator)) => write!(
+                f,
+                "thousands({})",
+                StringLiteral(&separator.to_string())
+            ),
+        }
+    }
+}
+
+/// Error applying a [Filter] to a value
+#[derive(Clone, Debug, Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum FilterError {
+    /// Text filter applied to binary data
+    #[error("Value is not valid UTF-8")]
+    InvalidUtf8(#[from] Utf8Error),
+
+    #[error("Decoding base64")]
+    Base64(#[from] base64::DecodeError),
+
+    /// Number formatting filter applied to something that isn't a number
+    #[error("Expected a number, got `{value}`")]
+    NotANumber { value: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_err;
+    use rstest::rstest;
+
+    fn replace(from: &str, to: &str) -> Filter {
+        Filter::Replace {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    fn split(separator: &str, index: i64) -> Filter {
+        Filter::Split {
+            separator: separator.into(),
+            index,
+        }
+    }
+
+    fn substring(start: i64, end: Option<i64>) -> Filter {
+        Filter::Substring { start, end }
+    }
+
+    fn pad(width: usize, fill: Option<char>) -> Filter {
+        Filter::Pad { width, fill }
+    }
+
+    fn fixed(digits: u8) -> Filter {
+        Filter::Fixed { digits }
+    }
+
+    fn thousands(separator: Option<char>) -> Filter {
+        Filter::Thousands { separator }
+    }
+
+    #[rstest]
+    #[case::trim(Filter::Trim, "  hello\n", "hello")]
+    #[case::upper(Filter::Upper, "Hello", "HELLO")]
+    #[case::lower(Filter::Lower, "Hello", "hello")]
+    #[case::urlencode(
+        Filter::Urlencode,
+        "a b&c=d/é~",
+        "a%20b%26c%3Dd%2F%C3%A9~"
+    )]
+    #[case::urldecode(Filter::Urldecode, "a%20b%26c%3Dd", "a b&c=d")]
+    #[case::urldecode_plus(Filter::Urldecode, "a+b%2Bc", "a b+c")]
+    #[case::base64(Filter::Base64, "user:pass", "dXNlcjpwYXNz")]
+    #[case::base64_decode(Filter::Base64Decode, "dXNlcjpwYXNz", "user:pass")]
+    #[case::base64url(Filter::Base64Url, "??>", "Pz8-")]
+    #[case::base64_decode_padded(Filter::Base64Decode, "Pz8+Pw==", "??>?")]
+    #[case::base64_decode_url(Filter::Base64Decode, "Pz8-Pw", "??>?")]
+    #[case::base64_decode_whitespace(Filter::Base64Decode, " Pz8+Pw\n", "??>?")]
+    #[case::hex(Filter::Hex, "Hi!", "486921")]
+    #[case::json_escape(Filter::JsonEscape, "say \"hi\"\n", r#"say \"hi\"\n"#)]
+    #[case::sha256(
+        Filter::Sha256,
+        "hello",
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    )]
+    #[case::sha512(
+        Filter::Sha512,
+        "hello",
+        "9b71d224bd62f3785d96d46ad3ea3d73319bfbc2890caadae2dff72519673ca7\
+        2323c3d99ba5c11d7c7acc6e14b8c5da0c4663475c2e5c3adef46f73bcdec043"
+    )]
+    #[case::replace(replace("-", "_"), "a-b-c", "a_b_c")]
+    #[case::replace_empty(replace("", "_"), "abc", "abc")]
+    #[case::split(split(",", 1), "a,b,c", "b")]
+    #[case::split_negative(split(",", -1), "a,b,c", "c")]
+    #[case::split_out_of_range(split(",", 3), "a,b,c", "")]
+    #[case::split_chars(split("", 1), "añb", "ñ")]
+    #[case::substring(substring(1, Some(3)), "añbc", "ñb")]
+    #[case::substring_open(substring(-2, None), "añbc", "bc")]
+    #[case::substring_clamped(substring(-10, Some(10)), "abc", "abc")]
+    #[case::substring_backwards(substring(2, Some(1)), "abc", "")]
+    #[case::pad(pad(5, Some('0')), "42", "00042")]
+    #[case::pad_space(pad(3, None), "é", "  é")]
+    #[case::pad_wide(pad(2, None), "abc", "abc")]
+    #[case::snake_case(
+        Filter::SnakeCase,
+        "userID HTTPServer-v2Name",
+        "user_id_http_server_v2_name"
+    )]
+    #[case::kebab_case(Filter::KebabCase, "First Name", "first-name")]
+    #[case::camel_case(Filter::CamelCase, "user_id", "userId")]
+    #[case::pascal_case(Filter::PascalCase, "user-ID", "UserId")]
+    #[case::round(Filter::Round, " 2.5\n", "3")]
+    #[case::round_negative(Filter::Round, "-2.5", "-3")]
+    #[case::round_negative_zero(Filter::Round, "-0.2", "0")]
+    #[case::fixed(fixed(2), "3.14159", "3.14")]
+    #[case::fixed_pad(fixed(3), "7", "7.000")]
+    #[case::fixed_zero(fixed(0), "1.6", "2")]
+    #[case::thousands(thousands(None), "1234567.891", "1,234,567.891")]
+    #[case::thousands_short(thousands(None), "123", "123")]
+    #[case::thousands_negative(thousands(None), "-123456", "-123,456")]
+    #[case::thousands_separator(thousands(Some('.')), "1234", "1.234")]
+    #[case::thousands_large(
+        thousands(None),
+        "12345678901234567890",
+        "12,345,678,901,234,567,890"
+    )]
+    #[case::thousands_exponent(thousands(None), "1e6", "1,000,000")]
+    fn test_apply(
+        #[case] filter: Filter,
+        #[case] input: &str,
+        #[case] expected: &str,
+    ) {
+        let output = filter.apply(input.as_bytes().to_vec()).unwrap();
+        assert_eq!(std::str::from_utf8(&output).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::upper_binary(Filter::Upper, b"\xc3\x28", "not valid UTF-8")]
+    #[case::base64_decode(Filter::Base64Decode, b"not base64!", "base64")]
+    #[case::base64_mixed(Filter::Base64Decode, b"Pz8+Pw-_", "base64")]
+    #[case::round(Filter::Round, b"abc", "Expected a number, got `abc`")]
+    #[case::fixed(fixed(2), b"", "Expected a number, got ``")]
+    #[case::thousands(thousands(None), b"inf", "Expected a number, got `inf`")]
+    fn test_apply_error(
+        #[case] filter: Filter,
+        #[case] input: &[u8],
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(filter.apply(input.to_vec()), expected_error);
+    }
+
+    #[rstest]
+    #[case::replace(replace("'", "\\"), r"replace('\'', '\\')")]
+    #[case::split(split(",", -1), "split(',', -1)")]
+    #[case::substring(substring(0, None), "substring(0)")]
+    #[case::substring_end(substring(0, Some(3)), "substring(0, 3)")]
+    #[case::pad(pad(5, None), "pad(5)")]
+    #[case::pad_fill(pad(5, Some('0')), "pad(5, '0')")]
+    #[case::fixed(fixed(2), "fixed(2)")]
+    #[case::thousands(thousands(None), "thousands")]
+    #[case::thousands_separator(thousands(Some(' ')), "thousands(' ')")]
+    fn test_display(#[case] filter: Filter, #[case] expected: &str) {
+        assert_eq!(filter.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case::plain("fallback", "default('fallback')")]
+    #[case::empty("", "default('')")]
+    #[case::escaped(r#"it's \ "ok""#, r#"default('it\'s \\ "ok"')"#)]
+    fn test_display_default(#[case] fallback: &str, #[case] expected: &str) {
+        assert_eq!(Filter::Default(fallback.into()).to_string(), expected);
+    }
+}
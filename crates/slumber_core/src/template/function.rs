@@ -0,0 +1,280 @@
+//! Built-in functions that generate dynamic values, called with syntax like
+//! `{{uuid()}}` or `{{random_int(1, 100)}}`
+
+use crate::template::{filter::StringLiteral, Identifier};
+use chrono::Utc;
+use derive_more::Display;
+use itertools::Itertools;
+use rand::Rng;
+use std::fmt::{self, Formatter, Write};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Signature of a function implementation. Takes the call's arguments and
+/// returns the rendered value
+type FunctionImpl = fn(&Arguments) -> Result<String, FunctionError>;
+
+/// Every function available in templates. To add a new function, write its
+/// implementation below and register it here. Keep this sorted by name.
+const FUNCTIONS: &[(&str, FunctionImpl)] = &[
+    ("now", now),
+    ("random_int", random_int),
+    ("timestamp", timestamp),
+    ("uuid", uuid),
+];
+
+/// A call to a built-in function, e.g. `now('%Y-%m-%d')`. The function name is
+/// resolved at render time, so parsing doesn't depend on the set of available
+/// functions.
+///
+/// The `Display` impl here should return exactly what this was parsed from,
+/// with whitespace normalized.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub struct FunctionCall {
+    pub name: Identifier,
+    pub arguments: Vec<Argument>,
+}
+
+impl FunctionCall {
+    /// Look up the function by name and call it
+    pub fn call(&self) -> Result<Vec<u8>, FunctionError> {
+        let (_, function) = FUNCTIONS
+            .iter()
+            .find(|(name, _)| *name == self.name.as_str())
+            .ok_or(FunctionError::Unknown)?;
+        let value = function(&Arguments(&self.arguments))?;
+        Ok(value.into_bytes())
+    }
+}
+
+impl Display for FunctionCall {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.name, self.arguments.iter().format(", "))
+    }
+}
+
+/// A literal argument to a function call
+#[derive(Clone, Debug, Display, PartialEq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub enum Argument {
+    #[display("{}", StringLiteral(_0))]
+    String(String),
+    Integer(i64),
+}
+
+/// Accessors for a function's arguments, to check their count and types
+struct Arguments<'a>(&'a [Argument]);
+
+impl<'a> Arguments<'a> {
+    /// Ensure the number of arguments is in the given range (inclusive)
+    fn expect_count(
+        &self,
+        min: usize,
+        max: usize,
+    ) -> Result<(), FunctionError> {
+        let count = self.0.len();
+        if count < min || count > max {
+            let expected = if min == max {
+                min.to_string()
+            } else {
+                format!("{min}-{max}")
+            };
+            Err(FunctionError::ArgumentCount { expected, count })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get a string argument, or `None` if it wasn't given
+    fn string(&self, index: usize) -> Result<Option<&'a str>, FunctionError> {
+        match self.0.get(index) {
+            None => Ok(None),
+            Some(Argument::String(value)) => Ok(Some(value)),
+            Some(Argument::Integer(_)) => Err(FunctionError::ArgumentType {
+                index,
+                expected: "a string",
+            }),
+        }
+    }
+
+    /// Get an integer argument. Call [Self::expect_count] first to make sure
+    /// it's present
+    fn integer(&self, index: usize) -> Result<i64, FunctionError> {
+        match self.0.get(index) {
+            Some(Argument::Integer(value)) => Ok(*value),
+            _ => Err(FunctionError::ArgumentType {
+                index,
+                expected: "an integer",
+            }),
+        }
+    }
+}
+
+/// `now()` or `now(format)`: Current time in UTC, as RFC 3339 or with a
+/// strftime-style format
+fn now(arguments: &Arguments) -> Result<String, FunctionError> {
+    arguments.expect_count(0, 1)?;
+    let now = Utc::now();
+    match arguments.string(0)? {
+        None => Ok(now.to_rfc3339()),
+        Some(format) => {
+            // Invalid format specifiers cause a formatting error, which would
+            // panic with to_string()
+            let mut output = String::new();
+            write!(output, "{}", now.format(format)).map_err(|_| {
+                FunctionError::InvalidFormat {
+                    format: format.to_owned(),
+                }
+            })?;
+            Ok(output)
+        }
+    }
+}
+
+/// `random_int(min, max)`: Random integer in a range (inclusive)
+fn random_int(arguments: &Arguments) -> Result<String, FunctionError> {
+    arguments.expect_count(2, 2)?;
+    let min = arguments.integer(0)?;
+    let max = arguments.integer(1)?;
+    if min > max {
+        return Err(FunctionError::InvalidRange { min, max });
+    }
+    Ok(rand::thread_rng().gen_range(min..=max).to_string())
+}
+
+/// `timestamp()`: Current Unix timestamp, in seconds
+fn timestamp(arguments: &Arguments) -> Result<String, FunctionError> {
+    arguments.expect_count(0, 0)?;
+    Ok(Utc::now().timestamp().to_string())
+}
+
+/// `uuid()`: Random v4 UUID
+fn uuid(arguments: &Arguments) -> Result<String, FunctionError> {
+    arguments.expect_count(0, 0)?;
+    Ok(Uuid::new_v4().to_string())
+}
+
+/// Error calling a [FunctionCall]
+#[derive(Clone, Debug, Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum FunctionError {
+    #[error(
+        "Unknown function; must be one of: {}",
+        FUNCTIONS.iter().map(|(name, _)| name).format(", ")
+    )]
+    Unknown,
+
+    #[error("Expected {expected} argument(s), got {count}")]
+    ArgumentCount { expected: String, count: usize },
+
+    #[error("Argument {index} must be {expected}")]
+    ArgumentType {
+        index: usize,
+        expected: &'static str,
+    },
+
+    #[error("Invalid range: {min} is greater than {max}")]
+    InvalidRange { min: i64, max: i64 },
+
+    #[error("Invalid format string `{format}`")]
+    InvalidFormat { format: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_err;
+    use chrono::{DateTime, NaiveDate};
+    use rstest::rstest;
+
+    /// Shorthand for building a call
+    fn call(
+        name: &'static str,
+        arguments: impl IntoIterator<Item = Argument>,
+    ) -> FunctionCall {
+        FunctionCall {
+            name: name.into(),
+            arguments: arguments.into_iter().collect(),
+        }
+    }
+
+    /// Call a function and get its output as a string
+    fn output(call: FunctionCall) -> String {
+        String::from_utf8(call.call().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_uuid() {
+        let first = output(call("uuid", []));
+        let second = output(call("uuid", []));
+        assert_eq!(first.parse::<Uuid>().unwrap().get_version_num(), 4);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_now() {
+        let now = output(call("now", []));
+        DateTime::parse_from_rfc3339(&now).unwrap();
+
+        let date = output(call("now", [Argument::String("%Y-%m-%d".into())]));
+        NaiveDate::parse_from_str(&date, "%Y-%m-%d").unwrap();
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let timestamp: i64 = output(call("timestamp", [])).parse().unwrap();
+        assert!((timestamp - Utc::now().timestamp()).abs() <= 1);
+    }
+
+    #[rstest]
+    #[case::range(1, 3)]
+    #[case::negative(-5, -1)]
+    #[case::single(7, 7)]
+    fn test_random_int(#[case] min: i64, #[case] max: i64) {
+        for _ in 0..20 {
+            let value: i64 = output(call(
+                "random_int",
+                [Argument::Integer(min), Argument::Integer(max)],
+            ))
+            .parse()
+            .unwrap();
+            assert!((min..=max).contains(&value), "{value} out of range");
+        }
+    }
+
+    #[rstest]
+    #[case::unknown(call("bogus", []), "Unknown function; must be one of")]
+    #[case::too_many(
+        call("uuid", [Argument::Integer(1)]),
+        "Expected 0 argument(s), got 1"
+    )]
+    #[case::too_few(
+        call("random_int", [Argument::Integer(1)]),
+        "Expected 2 argument(s), got 1"
+    )]
+    #[case::wrong_type(
+        call("random_int", [Argument::Integer(1), Argument::String("2".into())]),
+        "Argument 1 must be an integer"
+    )]
+    #[case::invalid_range(
+        call("random_int", [Argument::Integer(3), Argument::Integer(1)]),
+        "Invalid range: 3 is greater than 1"
+    )]
+    #[case::invalid_format(
+        call("now", [Argument::String("%Q".into())]),
+        "Invalid format string `%Q`"
+    )]
+    fn test_call_error(#[case] call: FunctionCall, #[case] expected: &str) {
+        assert_err!(call.call(), expected);
+    }
+
+    #[test]
+    fn test_display() {
+        let call = call(
+            "f",
+            [Argument::String("it's".into()), Argument::Integer(-3)],
+        );
+        assert_eq!(call.to_string(), r"f('it\'s', -3)");
+    }
+}
@@ -0,0 +1,552 @@
+//! Tools for rendering templates deterministically, e.g. to unit test the
+//! templates in a collection. Prompts are answered from a fixed list and
+//! request chains load canned responses, so nothing is read from a terminal or
+//! sent over the network.
+
+use crate::{
+    collection::{Collection, ProfileId, RecipeId, TemplateTest},
+    db::CollectionDatabase,
+    http::{
+        query::Pattern, EnvironmentSnapshot, Exchange, RequestId,
+        RequestRecord, ResponseBody, ResponseRecord,
+    },
+    template::{
+        render::RenderGroupState, Prompt, Prompter, Select, TemplateContext,
+    },
+};
+use anyhow::Context;
+use bytes::Bytes;
+use chrono::Utc;
+use derive_more::Display;
+use indexmap::IndexMap;
+use reqwest::{
+    header::{self, HeaderMap, HeaderValue},
+    Method, StatusCode,
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+impl TemplateContext {
+    /// Start building a context that renders without a terminal or network
+    /// access. See [TemplateContextBuilder].
+    pub fn builder(
+        collection: impl Into<Arc<Collection>>,
+    ) -> TemplateContextBuilder {
+        TemplateContextBuilder {
+            collection: collection.into(),
+            selected_profile: None,
+            overrides: IndexMap::new(),
+            prompter: Box::<TestPrompter>::default(),
+            http: TestHttpProvider::default(),
+            fake_seed: None,
+        }
+    }
+}
+
+/// Builder for a [TemplateContext] that renders deterministically. By default
+/// no profile is selected, prompts go unanswered (unless they have a
+/// default), and request chains have no responses to load.
+#[derive(Debug)]
+pub struct TemplateContextBuilder {
+    collection: Arc<Collection>,
+    selected_profile: Option<ProfileId>,
+    overrides: IndexMap<String, String>,
+    prompter: Box<dyn Prompter>,
+    http: TestHttpProvider,
+    fake_seed: Option<u64>,
+}
+
+impl TemplateContextBuilder {
+    /// Select the profile to pull field values from
+    pub fn profile(mut self, profile_id: impl Into<ProfileId>) -> Self {
+        self.selected_profile = Some(profile_id.into());
+        self
+    }
+
+    /// Override the value of a field, chain, etc., e.g. `chains.token`
+    pub fn override_value(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.overrides.insert(key.into(), value.into());
+        self
+    }
+
+    /// Answer prompts and selects with the given prompter, e.g.
+    /// [TestPrompter]
+    pub fn prompter(mut self, prompter: impl 'static + Prompter) -> Self {
+        self.prompter = Box::new(prompter);
+        self
+    }
+
+    /// Canned responses for request chains to load
+    pub fn http(mut self, http: TestHttpProvider) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Seed for `fake.*` keys, to generate the same values every time
+    pub fn fake_seed(mut self, seed: u64) -> Self {
+        self.fake_seed = Some(seed);
+        self
+    }
+
+    /// Build the context. Canned responses are stored as history in a new
+    /// in-memory database, which is discarded along with the context.
+    pub fn build(self) -> anyhow::Result<TemplateContext> {
+        let database = CollectionDatabase::in_memory()?;
+        for (recipe_id, response) in self.http.responses {
+            let method = self
+                .collection
+                .recipes
+                .get_recipe(&recipe_id)
+                .map_or(Method::GET, |recipe| recipe.method.into());
+            let id = RequestId::new();
+            let now = Utc::now();
+            let exchange = Exchange {
+                id,
+                request: RequestRecord {
+                    id,
+                    profile_id: self.selected_profile.clone(),
+                    recipe_id,
+                    method,
+                    // Nothing was sent, so there's no meaningful URL
+                    url: "http://localhost/".parse().expect("Valid URL"),
+                    headers: HeaderMap::new(),
+                    body: None,
+                    environment: EnvironmentSnapshot::default(),
+                    note: None,
+                }
+                .into(),
+                response: response.into(),
+                start_time: now,
+                end_time: now,
+                timing: None,
+                certificate: None,
+            };
+            database.insert_exchange(&exchange)?;
+        }
+
+        Ok(TemplateContext {
+            collection: self.collection,
+            selected_profile: self.selected_profile,
+            http_engine: None,
+            database,
+            overrides: self.overrides,
+            prompter: self.prompter,
+            fake_seed: self.fake_seed,
+            offline: false,
+            state: RenderGroupState::default(),
+        })
+    }
+}
+
+/// Canned HTTP responses for request chains. Nothing is ever sent; instead,
+/// each response is stored in history as the latest response for its recipe,
+/// which is where chains look for it. A chain that requires a new request
+/// (e.g. `trigger: always`) will fail to render.
+#[derive(Debug, Default)]
+pub struct TestHttpProvider {
+    responses: IndexMap<RecipeId, ResponseRecord>,
+}
+
+impl TestHttpProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the response for a recipe, replacing any previous one
+    pub fn response(
+        mut self,
+        recipe_id: impl Into<RecipeId>,
+        response: ResponseRecord,
+    ) -> Self {
+        self.responses.insert(recipe_id.into(), response);
+        self
+    }
+
+    /// Set the response for a recipe to a `200 OK` with the given body
+    pub fn body(
+        self,
+        recipe_id: impl Into<RecipeId>,
+        body: impl Into<Bytes>,
+    ) -> Self {
+        self.response(
+            recipe_id,
+            ResponseRecord {
+                status: StatusCode::OK,
+                headers: HeaderMap::new(),
+                body: ResponseBody::new(body.into()),
+            },
+        )
+    }
+
+    /// Set the response for a recipe to a `200 OK` with a JSON body
+    pub fn json(
+        self,
+        recipe_id: impl Into<RecipeId>,
+        body: &serde_json::Value,
+    ) -> Self {
+        self.response(
+            recipe_id,
+            ResponseRecord {
+                status: StatusCode::OK,
+                headers: [(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                )]
+                .into_iter()
+                .collect(),
+                body: ResponseBody::new(body.to_string().into()),
+            },
+        )
+    }
+}
+
+/// Why a [TemplateTest] failed
+#[derive(Debug, Display)]
+pub enum TemplateTestFailure {
+    /// The test's inputs were invalid, or the template failed to render
+    #[display("{_0:#}")]
+    Error(anyhow::Error),
+    #[display("`{actual}` does not match `{expected}`")]
+    Mismatch { expected: Pattern, actual: String },
+}
+
+impl TemplateTest {
+    /// Render the template from this test's inputs, and check the output
+    /// against the expected pattern
+    pub async fn run(
+        &self,
+        collection: Arc<Collection>,
+    ) -> Result<(), TemplateTestFailure> {
+        let actual = self
+            .render(collection)
+            .await
+            .map_err(TemplateTestFailure::Error)?;
+        if self.expect.is_match(actual.as_bytes()) {
+            Ok(())
+        } else {
+            Err(TemplateTestFailure::Mismatch {
+                expected: self.expect.clone(),
+                actual,
+            })
+        }
+    }
+
+    async fn render(
+        &self,
+        collection: Arc<Collection>,
+    ) -> anyhow::Result<String> {
+        let mut http = TestHttpProvider::new();
+        for (recipe_id, example) in &self.responses {
+            let response =
+                ResponseRecord::try_from(example).with_context(|| {
+                    format!(
+                        "Invalid header in response for recipe `{recipe_id}`"
+                    )
+                })?;
+            http = http.response(recipe_id.clone(), response);
+        }
+        let mut builder = TemplateContext::builder(collection)
+            .prompter(TestPrompter::new(self.prompts.iter().cloned()))
+            .http(http);
+        if let Some(profile_id) = &self.profile {
+            builder = builder.profile(profile_id.clone());
+        }
+        for (key, value) in &self.overrides {
+            builder = builder.override_value(key.clone(), value.clone());
+        }
+        let context = builder.build()?;
+        self.template
+            .render_string(&context)
+            .await
+            .context("Error rendering template")
+    }
+}
+
+/// Response to prompts and selects with zero or more values in sequence.
+/// Selects are answered with the next value only if it's one of the options.
+#[derive(Debug, Default)]
+pub struct TestPrompter {
+    responses: Vec<String>,
+    /// Track where in the sequence of responses we are
+    index: AtomicUsize,
+}
+
+impl TestPrompter {
+    pub fn new<T: Into<String>>(
+        responses: impl IntoIterator<Item = T>,
+    ) -> Self {
+        Self {
+            responses: responses.into_iter().map(T::into).collect(),
+            index: 0.into(),
+        }
+    }
+}
+
+impl Prompter for TestPrompter {
+    fn prompt(&self, prompt: Prompt) {
+        // Grab the next value in the sequence. If we're all out, don't respond
+        let index = self.index.fetch_add(1, Ordering::Relaxed);
+        if let Some(value) = self.responses.get(index) {
+            prompt.channel.respond(value.clone())
+        } else if let Some(default) = prompt.default {
+            prompt.channel.respond(default);
+        }
+    }
+
+    fn select(&self, select: Select) {
+        // If the next value isn't a valid option, don't respond. The select
+        // fails the same as an unanswered prompt
+        let index = self.index.fetch_add(1, Ordering::Relaxed);
+        if let Some(value) = self
+            .responses
+            .get(index)
+            .filter(|value| select.options.contains(value))
+        {
+            select.channel.respond(value.clone());
+        }
+    }
+}
+
+/// Response to selects with zero or more values in sequence
+#[derive(Debug, Default)]
+pub struct TestSelectPrompter {
+    /// Index within the contained select to grab response for
+    responses: Vec<usize>,
+    /// Track where in the sequence of responses we are
+    index: AtomicUsize,
+}
+
+impl TestSelectPrompter {
+    pub fn new<T: Into<usize>>(responses: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            responses: responses.into_iter().map(T::into).collect(),
+            index: 0.into(),
+        }
+    }
+}
+
+impl Prompter for TestSelectPrompter {
+    fn prompt(&self, prompt: Prompt) {
+        // There are no text responses, so fall back to the default. If there
+        // isn't one, the prompt goes unanswered
+        if let Some(default) = prompt.default {
+            prompt.channel.respond(default);
+        }
+    }
+
+    fn select(&self, mut select: Select) {
+        let index = self.index.fetch_add(1, Ordering::Relaxed);
+        if let Some(value) = self.responses.get(index) {
+            select.channel.respond(select.options.swap_remove(*value))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assert_err, template::Template, util::parse_yaml};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    const COLLECTION: &str = r#"
+profiles:
+  dev:
+    data:
+      host: https://dev.example.com
+  prod:
+    data:
+      host: https://example.com
+
+chains:
+  token:
+    source: !request
+      recipe: login
+    selector: $.token
+  fresh_token:
+    source: !request
+      recipe: login
+      trigger: !always
+    selector: $.token
+  password:
+    source: !prompt
+      message: Password
+  region:
+    source: !select
+      options: [us, eu]
+
+requests:
+  login: !request
+    method: POST
+    url: "{{host}}/login"
+"#;
+
+    fn collection() -> Collection {
+        parse_yaml(COLLECTION.as_bytes()).unwrap()
+    }
+
+    async fn render(context: &TemplateContext, template: &str) -> String {
+        template
+            .parse::<Template>()
+            .unwrap()
+            .render_string(context)
+            .await
+            .unwrap()
+    }
+
+    /// Profile, prompts, and chains all render from the builder's inputs
+    #[tokio::test]
+    async fn test_builder() {
+        let context = TemplateContext::builder(collection())
+            .profile("prod".to_owned())
+            .prompter(TestPrompter::new(["hunter2"]))
+            .http(
+                TestHttpProvider::new().json("login", &json!({"token": "abc"})),
+            )
+            .override_value("user", "frodo")
+            .build()
+            .unwrap();
+        assert_eq!(
+            render(
+                &context,
+                "{{host}} {{user}} {{chains.token}} {{chains.password}}"
+            )
+            .await,
+            "https://example.com frodo abc hunter2"
+        );
+    }
+
+    /// With no profile selected, canned responses are stored without a
+    /// profile too, so chains still find them
+    #[tokio::test]
+    async fn test_no_profile() {
+        let context = TemplateContext::builder(collection())
+            .http(
+                TestHttpProvider::new().json("login", &json!({"token": "abc"})),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(render(&context, "{{chains.token}}").await, "abc");
+    }
+
+    /// Chains that need to send a request can't be rendered
+    #[tokio::test]
+    async fn test_triggered_request() {
+        let context = TemplateContext::builder(collection())
+            .http(
+                TestHttpProvider::new().json("login", &json!({"token": "abc"})),
+            )
+            .build()
+            .unwrap();
+        assert_err!(
+            "{{chains.fresh_token}}"
+                .parse::<Template>()
+                .unwrap()
+                .render_string(&context)
+                .await,
+            "Triggered request execution not allowed in this context"
+        );
+    }
+
+    /// Selects are answered from the same responses as prompts, and fail
+    /// instead of panicking if the response isn't an option
+    #[rstest]
+    #[case::valid("eu", Ok("eu"))]
+    #[case::invalid("asia", Err("No response from prompt/select"))]
+    #[tokio::test]
+    async fn test_select(
+        #[case] response: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let context = TemplateContext::builder(collection())
+            .prompter(TestPrompter::new([response]))
+            .build()
+            .unwrap();
+        let result = "{{chains.region}}"
+            .parse::<Template>()
+            .unwrap()
+            .render_string(&context)
+            .await;
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap(), expected),
+            Err(expected) => assert_err!(result, expected),
+        }
+    }
+
+    /// Template tests declared in a collection render from their own inputs
+    #[rstest]
+    #[case::pass("auth", None)]
+    #[case::mismatch(
+        "wrong_profile",
+        Some(
+            "`Bearer abc https://dev.example.com` does not match \
+            `^Bearer abc https://example.com$`"
+        )
+    )]
+    #[case::render_error("no_response", Some("Error rendering template"))]
+    #[case::invalid_response(
+        "invalid_response",
+        Some("Invalid header in response for recipe `login`")
+    )]
+    #[tokio::test]
+    async fn test_template_test(
+        #[case] name: &str,
+        #[case] expected_error: Option<&str>,
+    ) {
+        let tests = r#"
+template_tests:
+  auth:
+    template: "Bearer {{chains.token}} {{host}}"
+    profile: prod
+    responses:
+      login:
+        headers:
+          Content-Type: application/json
+        body: '{"token": "abc"}'
+    expect: "^Bearer abc https://example.com$"
+  wrong_profile:
+    template: "Bearer {{chains.token}} {{host}}"
+    profile: dev
+    responses:
+      login:
+        headers:
+          Content-Type: application/json
+        body: '{"token": "abc"}'
+    expect: "^Bearer abc https://example.com$"
+  no_response:
+    template: "{{chains.token}}"
+    expect: abc
+  invalid_response:
+    template: "{{chains.token}}"
+    responses:
+      login:
+        headers:
+          "bad header": value
+    expect: abc
+"#;
+        let collection: Collection =
+            parse_yaml(format!("{COLLECTION}{tests}").as_bytes()).unwrap();
+        let collection = Arc::new(collection);
+        let result = collection.template_tests[name]
+            .run(Arc::clone(&collection))
+            .await
+            .map_err(|failure| failure.to_string());
+        match expected_error {
+            None => assert_eq!(result, Ok(())),
+            Some(expected) => {
+                let error = result.unwrap_err();
+                assert!(
+                    error.contains(expected),
+                    "Expected `{error}` to contain `{expected}`"
+                );
+            }
+        }
+    }
+}
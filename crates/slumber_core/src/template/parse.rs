@@ -2,7 +2,12 @@
 
 use crate::{
     collection::ChainId,
-    template::{error::TemplateParseError, Identifier, Template, TemplateKey},
+    template::{
+        error::TemplateParseError,
+        filter::{Filter, StringLiteral},
+        Argument, Expression, FunctionCall, Identifier, Operand, Operator,
+        Template, TemplateKey,
+    },
 };
 #[cfg(test)]
 use proptest::strategy::Strategy;
@@ -10,15 +15,18 @@ use regex::Regex;
 use std::{
     borrow::Cow,
     fmt::Write,
+    slice,
     str::FromStr,
     sync::{Arc, LazyLock},
 };
 use winnow::{
+    ascii::{dec_int, dec_uint, digit1, space0, space1},
     combinator::{
-        alt, cut_err, eof, not, peek, preceded, repeat, repeat_till, terminated,
+        alt, cut_err, delimited, eof, not, opt, peek, preceded, repeat,
+        repeat_till, separated, separated_pair, terminated,
     },
-    error::StrContext,
-    token::{any, take_while},
+    error::{ContextError, StrContext},
+    token::{any, none_of, one_of, take_while},
     PResult, Parser,
 };
 
@@ -28,16 +36,37 @@ const ESCAPE: &str = "_";
 const KEY_OPEN: &str = "{{";
 /// Marks the end of a template key
 const KEY_CLOSE: &str = "}}";
+/// Separates a key from each filter applied to it
+const FILTER_PIPE: &str = "|";
+/// Opens a conditional block, followed by its condition key
+const IF_OPEN: &str = "#if";
+/// Separates the two branches of a conditional block
+const ELSE: &str = "else";
+/// Closes a conditional block
+const IF_CLOSE: &str = "/if";
+/// Opens a loop block, followed by the key of the array to loop over
+const EACH_OPEN: &str = "#each";
+/// Closes a loop block
+const EACH_CLOSE: &str = "/each";
+/// Introduces the optional separator of a loop block, e.g. `sep=', '`
+const SEPARATOR: &str = "sep=";
 // Export these so they can be used in TemplateKey's Display impl
 pub const CHAIN_PREFIX: &str = "chains.";
+pub const DATA_PREFIX: &str = "data.";
 pub const ENV_PREFIX: &str = "env.";
+pub const FAKE_PREFIX: &str = "fake.";
+pub const ITEM_KEY: &str = "this";
+pub const ITEM_PREFIX: &str = "this.";
 
 impl Template {
     /// Create a template that renders a single field, equivalent to
     /// `{{<field>}}`
     pub fn from_field(field: Identifier) -> Self {
         Self {
-            chunks: vec![TemplateInputChunk::Key(TemplateKey::Field(field))],
+            chunks: vec![TemplateInputChunk::Key {
+                key: TemplateKey::Field(field),
+                filters: vec![],
+            }],
         }
     }
 
@@ -45,72 +74,234 @@ impl Template {
     /// `{{chains.<id>}}`
     pub fn from_chain(id: ChainId) -> Self {
         Self {
-            chunks: vec![TemplateInputChunk::Key(TemplateKey::Chain(id))],
+            chunks: vec![TemplateInputChunk::Key {
+                key: TemplateKey::Chain(id),
+                filters: vec![],
+            }],
         }
     }
 
     /// Convert the template to a string. This will only allocate for escaped or
     /// keyed templates. This is guaranteed to return the exact string that was
     /// parsed to create the template, and therefore will parse back to the same
-    /// template. If it doesn't, that's a bug.
+    /// template. If it doesn't, that's a bug. The one exception is whitespace
+    /// around filter pipes, which is normalized to a single space.
     pub fn display(&self) -> Cow<'_, str> {
         let mut buf = Cow::Borrowed("");
+        display_chunks(&mut buf, &self.chunks);
+        buf
+    }
 
-        // Re-stringify the template
+    /// Stringify for a tool with a simpler template language, e.g. Postman.
+    /// Raw text is written as-is, without escaping. Each key is passed to
+    /// `translate_key` along with its filters, which returns the text to
+    /// replace it with, or `None` if it can't be translated. Anything that
+    /// can't be translated, including conditional and loop blocks, is written
+    /// in Slumber syntax. The source of each of those chunks is also returned,
+    /// so the caller can warn about them.
+    pub fn translate<F>(&self, mut translate_key: F) -> (String, Vec<String>)
+    where
+        F: FnMut(&TemplateKey, &[Filter]) -> Option<String>,
+    {
+        let mut output = String::new();
+        let mut untranslated = Vec::new();
         for chunk in &self.chunks {
-            match chunk {
-                TemplateInputChunk::Raw(s) => {
-                    // Add underscores between { to escape them. Any sequence
-                    // of {_* followed by another { needs to be escaped. Regex
-                    // matches have to be non-overlapping so we can't  just use
-                    // {_*{, because that wouldn't catch cases like {_{_{. So
-                    // we have to do our own lookahead.
-                    //
-                    // Keep in mind that escape sequences are going to be an
-                    // extreme rarity, so we need to optimize for the case where
-                    // there are none and only allocate when necessary.
-                    static REGEX: LazyLock<Regex> =
-                        LazyLock::new(|| Regex::new(r#"\{_*"#).unwrap());
-                    // Track how far into s we've copied, so we can do as few
-                    // copies as possible
-                    let mut last_copied = 0;
-                    for m in REGEX.find_iter(s) {
-                        let rest = &s[m.end()..];
-                        // Don't allocate until we know this needs an escape
-                        // sequence
-                        if rest.starts_with('{') {
-                            let buf = buf.to_mut();
-                            buf.push_str(&s[last_copied..m.end()]);
-                            buf.push('_');
-                            last_copied = m.end();
-                        }
-                    }
+            let translated = match chunk {
+                TemplateInputChunk::Raw(text) => Some(text.to_string()),
+                TemplateInputChunk::Key { key, filters } => {
+                    translate_key(key, filters)
+                }
+                TemplateInputChunk::Conditional { .. }
+                | TemplateInputChunk::Loop { .. } => None,
+            };
+            match translated {
+                Some(text) => output.push_str(&text),
+                None => {
+                    let mut source = Cow::Borrowed("");
+                    display_chunks(&mut source, slice::from_ref(chunk));
+                    output.push_str(&source);
+                    untranslated.push(source.into_owned());
+                }
+            }
+        }
+        (output, untranslated)
+    }
+
+    /// Get every key referenced by this template, in order of appearance. This
+    /// includes conditions and keys within both branches of conditionals, and
+    /// loop arrays and keys within loop bodies. Expressions are replaced by
+    /// the keys they use. Keys used multiple times are repeated.
+    pub fn keys(&self) -> Vec<&TemplateKey> {
+        fn push<'a>(key: &'a TemplateKey, keys: &mut Vec<&'a TemplateKey>) {
+            match key {
+                TemplateKey::Expression(expression) => {
+                    keys.extend(expression.keys())
+                }
+                _ => keys.push(key),
+            }
+        }
 
-                    // If this is the first chunk and there were no regex
-                    // matches, don't allocate yet
-                    if buf.is_empty() {
-                        buf = Cow::Borrowed(s);
-                    } else {
-                        // Fencepost: get everything from the last escape
-                        // sequence to the end
-                        buf.to_mut().push_str(&s[last_copied..]);
+        fn collect<'a>(
+            chunks: &'a [TemplateInputChunk],
+            keys: &mut Vec<&'a TemplateKey>,
+        ) {
+            for chunk in chunks {
+                match chunk {
+                    TemplateInputChunk::Raw(_) => {}
+                    TemplateInputChunk::Key { key, .. } => push(key, keys),
+                    TemplateInputChunk::Conditional {
+                        condition,
+                        then,
+                        otherwise,
+                        ..
+                    } => {
+                        push(condition, keys);
+                        collect(then, keys);
+                        collect(otherwise.as_deref().unwrap_or_default(), keys);
+                    }
+                    TemplateInputChunk::Loop {
+                        items,
+                        body,
+                        otherwise,
+                        ..
+                    } => {
+                        push(items, keys);
+                        collect(body, keys);
+                        collect(otherwise.as_deref().unwrap_or_default(), keys);
                     }
                 }
-                TemplateInputChunk::Key(key) => {
-                    // If the previous chunk ends with a potential escape
-                    // sequence, add an underscore to escape the upcoming key
-                    static REGEX: LazyLock<Regex> =
-                        LazyLock::new(|| Regex::new(r#"\{_*$"#).unwrap());
-                    if REGEX.is_match(&buf) {
-                        buf.to_mut().push_str(ESCAPE);
+            }
+        }
+
+        let mut keys = Vec::new();
+        collect(&self.chunks, &mut keys);
+        keys
+    }
+}
+
+/// Re-stringify a list of chunks into the buffer. Conditional and loop blocks
+/// recurse into their branches.
+fn display_chunks<'a>(
+    buf: &mut Cow<'a, str>,
+    chunks: &'a [TemplateInputChunk],
+) {
+    for chunk in chunks {
+        match chunk {
+            TemplateInputChunk::Raw(s) => {
+                // Add underscores between { to escape them. Any sequence
+                // of {_* followed by another { needs to be escaped. Regex
+                // matches have to be non-overlapping so we can't  just use
+                // {_*{, because that wouldn't catch cases like {_{_{. So
+                // we have to do our own lookahead.
+                //
+                // Keep in mind that escape sequences are going to be an
+                // extreme rarity, so we need to optimize for the case where
+                // there are none and only allocate when necessary.
+                static REGEX: LazyLock<Regex> =
+                    LazyLock::new(|| Regex::new(r#"\{_*"#).unwrap());
+                // Track how far into s we've copied, so we can do as few
+                // copies as possible
+                let mut last_copied = 0;
+                for m in REGEX.find_iter(s) {
+                    let rest = &s[m.end()..];
+                    // Don't allocate until we know this needs an escape
+                    // sequence
+                    if rest.starts_with('{') {
+                        let buf = buf.to_mut();
+                        buf.push_str(&s[last_copied..m.end()]);
+                        buf.push('_');
+                        last_copied = m.end();
                     }
+                }
 
-                    write!(buf.to_mut(), "{KEY_OPEN}{key}{KEY_CLOSE}").unwrap();
+                // If this is the first chunk and there were no regex
+                // matches, don't allocate yet
+                if buf.is_empty() {
+                    *buf = Cow::Borrowed(s);
+                } else {
+                    // Fencepost: get everything from the last escape
+                    // sequence to the end
+                    buf.to_mut().push_str(&s[last_copied..]);
+                }
+            }
+            TemplateInputChunk::Key { key, filters } => {
+                let buf = open_tag(buf);
+                write!(buf, "{key}").unwrap();
+                display_filters(buf, filters);
+                buf.push_str(KEY_CLOSE);
+            }
+            TemplateInputChunk::Conditional {
+                condition,
+                filters,
+                then,
+                otherwise,
+            } => {
+                let tag = open_tag(buf);
+                write!(tag, "{IF_OPEN} {condition}").unwrap();
+                display_filters(tag, filters);
+                tag.push_str(KEY_CLOSE);
+                display_chunks(buf, then);
+                display_else(buf, otherwise.as_deref());
+                let tag = open_tag(buf);
+                tag.push_str(IF_CLOSE);
+                tag.push_str(KEY_CLOSE);
+            }
+            TemplateInputChunk::Loop {
+                items,
+                filters,
+                separator,
+                body,
+                otherwise,
+            } => {
+                let tag = open_tag(buf);
+                write!(tag, "{EACH_OPEN} {items}").unwrap();
+                display_filters(tag, filters);
+                if let Some(separator) = separator {
+                    write!(tag, " {SEPARATOR}{}", StringLiteral(separator))
+                        .unwrap();
                 }
+                tag.push_str(KEY_CLOSE);
+                display_chunks(buf, body);
+                display_else(buf, otherwise.as_deref());
+                let tag = open_tag(buf);
+                tag.push_str(EACH_CLOSE);
+                tag.push_str(KEY_CLOSE);
             }
         }
+    }
+}
 
-        buf
+/// Start writing a `{{ }}` tag. If the previous chunk ends with a potential
+/// escape sequence, add an underscore to escape the upcoming tag
+fn open_tag<'b>(buf: &'b mut Cow<'_, str>) -> &'b mut String {
+    static REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"\{_*$"#).unwrap());
+    let escape = REGEX.is_match(buf);
+    let buf = buf.to_mut();
+    if escape {
+        buf.push_str(ESCAPE);
+    }
+    buf.push_str(KEY_OPEN);
+    buf
+}
+
+/// Write the `{{else}}` branch of a block, if it has one
+fn display_else<'a>(
+    buf: &mut Cow<'a, str>,
+    otherwise: Option<&'a [TemplateInputChunk]>,
+) {
+    if let Some(otherwise) = otherwise {
+        let tag = open_tag(buf);
+        tag.push_str(ELSE);
+        tag.push_str(KEY_CLOSE);
+        display_chunks(buf, otherwise);
+    }
+}
+
+/// Write each filter for a key, preceded by a pipe
+fn display_filters(buf: &mut String, filters: &[Filter]) {
+    for filter in filters {
+        write!(buf, " {FILTER_PIPE} {filter}").unwrap();
     }
 }
 
@@ -153,8 +344,9 @@ impl FromStr for Identifier {
     }
 }
 
-/// A parsed piece of a template. After parsing, each chunk is either raw text
-/// or a parsed key, ready to be rendered.
+/// A parsed piece of a template. After parsing, each chunk is either raw text,
+/// a parsed key, or a conditional or loop block of further chunks, ready to
+/// be rendered.
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub enum TemplateInputChunk {
@@ -167,7 +359,40 @@ pub enum TemplateInputChunk {
         #[cfg_attr(test, proptest(strategy = "\".+\".prop_map(Arc::new)"))]
         Arc<String>,
     ),
-    Key(TemplateKey),
+    /// A key to render, plus any filters to apply to its value, in order
+    Key {
+        key: TemplateKey,
+        filters: Vec<Filter>,
+    },
+    /// A block that renders one of two lists of chunks, depending on whether
+    /// its condition (a key plus filters) renders to a non-empty value:
+    /// `{{#if key}}...{{else}}...{{/if}}`. Recursive generation isn't
+    /// supported by the derive, so these are tested by hand.
+    #[cfg_attr(test, proptest(skip))]
+    Conditional {
+        condition: TemplateKey,
+        filters: Vec<Filter>,
+        then: Vec<TemplateInputChunk>,
+        /// `None` if there's no `{{else}}`, so we can stringify back to the
+        /// exact source
+        otherwise: Option<Vec<TemplateInputChunk>>,
+    },
+    /// A block that renders its body once for each element of a JSON array:
+    /// `{{#each key}}...{{else}}...{{/each}}`. Within the body, `{{this}}`
+    /// is the current element and `{{this.field}}` is one of its fields. The
+    /// else branch is rendered if the array is empty. Tested by hand, like
+    /// [Self::Conditional].
+    #[cfg_attr(test, proptest(skip))]
+    Loop {
+        items: TemplateKey,
+        filters: Vec<Filter>,
+        /// Rendered between each pair of elements, from `sep='...'`
+        separator: Option<String>,
+        body: Vec<TemplateInputChunk>,
+        /// `None` if there's no `{{else}}`, so we can stringify back to the
+        /// exact source
+        otherwise: Option<Vec<TemplateInputChunk>>,
+    },
 }
 
 /// Parse a template into keys and raw text
@@ -176,17 +401,24 @@ pub enum TemplateInputChunk {
 /// - Use take_till or similar in raw string parsing
 /// - https://docs.rs/winnow/latest/winnow/_topic/performance/index.html
 fn all_chunks(input: &mut &str) -> PResult<Vec<TemplateInputChunk>> {
-    repeat_till(
-        0..,
-        alt((
-            key.map(TemplateInputChunk::Key),
-            raw.map(TemplateInputChunk::Raw),
-        ))
-        .context(StrContext::Label("template chunk")),
-        eof,
-    )
-    .map(|(chunks, _)| chunks)
-    .context(StrContext::Label("template"))
+    repeat_till(0.., chunk, eof)
+        .map(|(chunks, _)| chunks)
+        .context(StrContext::Label("template"))
+        .parse_next(input)
+}
+
+/// Parse a single chunk: a conditional or loop block, a key, or raw text. Raw
+/// text has to be non-empty, otherwise an unclosed block would loop forever at
+/// the end of input.
+fn chunk(input: &mut &str) -> PResult<TemplateInputChunk> {
+    alt((
+        conditional,
+        each,
+        key,
+        raw.verify(|text: &Arc<String>| !text.is_empty())
+            .map(TemplateInputChunk::Raw),
+    ))
+    .context(StrContext::Label("template chunk"))
     .parse_next(input)
 }
 
@@ -224,20 +456,121 @@ fn escape_sequence<'a>(input: &mut &'a str) -> PResult<&'a str> {
     .parse_next(input)
 }
 
-/// Parse a template key
-fn key(input: &mut &str) -> PResult<TemplateKey> {
+/// Parse a template key, including its filters
+fn key(input: &mut &str) -> PResult<TemplateInputChunk> {
     preceded(
         KEY_OPEN,
         // Any error inside a template key is fatal, including an unclosed key
-        cut_err(terminated(key_contents, KEY_CLOSE)),
+        cut_err(terminated((key_contents, filters), KEY_CLOSE)),
     )
+    .map(|(key, filters)| TemplateInputChunk::Key { key, filters })
     .context(StrContext::Label("key"))
     .parse_next(input)
 }
 
-/// Parse the contents of a key (inside the `{{ }}`)
+/// Parse a conditional block `{{#if key}}...{{else}}...{{/if}}`, where the
+/// else branch is optional. Once we've seen `{{#if `, any error is fatal,
+/// including an unclosed block.
+fn conditional(input: &mut &str) -> PResult<TemplateInputChunk> {
+    preceded(
+        (KEY_OPEN, IF_OPEN, space1),
+        cut_err((
+            terminated((key_contents, filters), KEY_CLOSE),
+            block_chunks(IF_CLOSE),
+            opt(preceded(
+                (KEY_OPEN, ELSE, KEY_CLOSE),
+                block_chunks(IF_CLOSE),
+            )),
+            (KEY_OPEN, IF_CLOSE, KEY_CLOSE),
+        )),
+    )
+    .map(|((condition, filters), then, otherwise, _)| {
+        TemplateInputChunk::Conditional {
+            condition,
+            filters,
+            then,
+            otherwise,
+        }
+    })
+    .context(StrContext::Label("conditional"))
+    .parse_next(input)
+}
+
+/// Parse a loop block `{{#each key sep=', '}}...{{else}}...{{/each}}`, where
+/// the separator and else branch are optional. Once we've seen `{{#each `, any
+/// error is fatal, including an unclosed block.
+fn each(input: &mut &str) -> PResult<TemplateInputChunk> {
+    preceded(
+        (KEY_OPEN, EACH_OPEN, space1),
+        cut_err((
+            terminated(
+                (
+                    key_contents,
+                    filters,
+                    opt(preceded((space1, SEPARATOR), string_literal)),
+                ),
+                KEY_CLOSE,
+            ),
+            block_chunks(EACH_CLOSE),
+            opt(preceded(
+                (KEY_OPEN, ELSE, KEY_CLOSE),
+                block_chunks(EACH_CLOSE),
+            )),
+            (KEY_OPEN, EACH_CLOSE, KEY_CLOSE),
+        )),
+    )
+    .map(|((items, filters, separator), body, otherwise, _)| {
+        TemplateInputChunk::Loop {
+            items,
+            filters,
+            separator,
+            body,
+            otherwise,
+        }
+    })
+    .context(StrContext::Label("loop"))
+    .parse_next(input)
+}
+
+/// Parse the chunks within one branch of a block, up to (but not including)
+/// the next `{{else}}` or the given closing tag
+fn block_chunks<'a>(
+    close: &'static str,
+) -> impl Parser<&'a str, Vec<TemplateInputChunk>, ContextError> {
+    repeat_till(
+        0..,
+        chunk,
+        peek(alt((
+            (KEY_OPEN, ELSE, KEY_CLOSE),
+            (KEY_OPEN, close, KEY_CLOSE),
+        ))),
+    )
+    .map(|(chunks, _)| chunks)
+}
+
+/// Parse the contents of a key (inside the `{{ }}`): either an expression or a
+/// single key
 fn key_contents(input: &mut &str) -> PResult<TemplateKey> {
     alt((
+        // A lone operand isn't an expression, so it falls through to be parsed
+        // as a plain key. This keeps `{{1}}` a field rather than a number
+        expression
+            .verify(|expression: &Expression| {
+                !matches!(expression, Expression::Operand(_))
+            })
+            .map(|expression| TemplateKey::Expression(Box::new(expression)))
+            .context(StrContext::Label("expression")),
+        single_key,
+    ))
+    .parse_next(input)
+}
+
+/// Parse a single key, i.e. anything other than an expression
+fn single_key(input: &mut &str) -> PResult<TemplateKey> {
+    alt((
+        function_call
+            .map(TemplateKey::Function)
+            .context(StrContext::Label("function")),
         preceded(
             CHAIN_PREFIX,
             identifier.map(|id| TemplateKey::Chain(id.into())),
@@ -245,13 +578,268 @@ fn key_contents(input: &mut &str) -> PResult<TemplateKey> {
         .context(StrContext::Label("chain")),
         preceded(ENV_PREFIX, identifier.map(TemplateKey::Environment))
             .context(StrContext::Label("environment")),
+        // An unknown kind is fatal, rather than falling back to a field
+        preceded(
+            FAKE_PREFIX,
+            cut_err(take_while(1.., Identifier::is_char_allowed).parse_to()),
+        )
+        .map(TemplateKey::Fake)
+        .context(StrContext::Label("fake data type")),
+        preceded(DATA_PREFIX, identifier.map(TemplateKey::Data))
+            .context(StrContext::Label("data column")),
+        preceded(ITEM_PREFIX, identifier.map(TemplateKey::ItemField))
+            .context(StrContext::Label("loop item field")),
+        // `this` is reserved for the current loop item
         identifier
-            .map(TemplateKey::Field)
+            .map(|id| {
+                if id.as_str() == ITEM_KEY {
+                    TemplateKey::Item
+                } else {
+                    TemplateKey::Field(id)
+                }
+            })
             .context(StrContext::Label("field")),
     ))
     .parse_next(input)
 }
 
+/// Parse an expression like `page * 50` or `count > 0 ? 'yes' : 'no'`. From
+/// lowest to highest precedence, there are ternaries, comparisons, sums, and
+/// products. Once we've seen an operator, any error is fatal.
+fn expression(input: &mut &str) -> PResult<Expression> {
+    (
+        comparison,
+        opt(preceded(
+            (space0, "?", space0),
+            cut_err(separated_pair(
+                expression,
+                (space0, ":", space0),
+                expression,
+            )),
+        )),
+    )
+        .map(|(condition, branches)| match branches {
+            Some((then, otherwise)) => {
+                Expression::ternary(condition, then, otherwise)
+            }
+            None => condition,
+        })
+        .parse_next(input)
+}
+
+/// Parse a sum, optionally compared to another sum. Comparisons don't chain,
+/// so `a < b < c` is invalid
+fn comparison(input: &mut &str) -> PResult<Expression> {
+    (
+        sum,
+        opt((
+            delimited(
+                space0,
+                alt((
+                    "==".value(Operator::Equal),
+                    "!=".value(Operator::NotEqual),
+                    "<=".value(Operator::LessOrEqual),
+                    ">=".value(Operator::GreaterOrEqual),
+                    "<".value(Operator::Less),
+                    ">".value(Operator::Greater),
+                )),
+                space0,
+            ),
+            cut_err(sum),
+        )),
+    )
+        .map(|(left, right)| match right {
+            Some((operator, right)) => {
+                Expression::binary(left, operator, right)
+            }
+            None => left,
+        })
+        .parse_next(input)
+}
+
+/// Parse products joined by `+` or `-`
+fn sum(input: &mut &str) -> PResult<Expression> {
+    left_associative(input, product, |input| {
+        alt(("+".value(Operator::Add), "-".value(Operator::Subtract)))
+            .parse_next(input)
+    })
+}
+
+/// Parse operands joined by `*`, `/`, or `%`
+fn product(input: &mut &str) -> PResult<Expression> {
+    left_associative(input, operand, |input| {
+        alt((
+            "*".value(Operator::Multiply),
+            "/".value(Operator::Divide),
+            "%".value(Operator::Remainder),
+        ))
+        .parse_next(input)
+    })
+}
+
+/// Parse one or more terms joined by operators of the same precedence, and
+/// group them left to right
+fn left_associative(
+    input: &mut &str,
+    term: fn(&mut &str) -> PResult<Expression>,
+    operator: fn(&mut &str) -> PResult<Operator>,
+) -> PResult<Expression> {
+    let first = term(input)?;
+    let rest: Vec<(Operator, Expression)> =
+        repeat(0.., (delimited(space0, operator, space0), cut_err(term)))
+            .parse_next(input)?;
+    Ok(rest.into_iter().fold(first, |left, (operator, right)| {
+        Expression::binary(left, operator, right)
+    }))
+}
+
+/// Parse a single operand of an expression: a parenthesized sub-expression, a
+/// literal, or a key
+fn operand(input: &mut &str) -> PResult<Expression> {
+    alt((
+        delimited(("(", space0), cut_err(expression), cut_err((space0, ")")))
+            .map(|expression| Expression::Group(Box::new(expression))),
+        number.map(|number| Expression::Operand(Operand::Number(number))),
+        string_literal.map(|s| Expression::Operand(Operand::String(s))),
+        single_key.map(|key| Expression::Operand(Operand::Key(key))),
+    ))
+    .context(StrContext::Label("operand"))
+    .parse_next(input)
+}
+
+/// Parse a number literal like `50`, `-1`, or `0.5`. Digits followed by more
+/// identifier characters are an identifier instead, e.g. `1st`
+fn number(input: &mut &str) -> PResult<f64> {
+    terminated(
+        (opt("-"), digit1, opt((".", digit1))).take().parse_to(),
+        not(one_of(Identifier::is_char_allowed)),
+    )
+    .parse_next(input)
+}
+
+/// Parse a function call like `now('%Y-%m-%d')`. Once we've seen the opening
+/// paren, any error is fatal. The function name isn't validated here; unknown
+/// functions fail during render.
+fn function_call(input: &mut &str) -> PResult<FunctionCall> {
+    (
+        terminated(identifier, "("),
+        cut_err(terminated(
+            separated(0.., delimited(space0, argument, space0), ","),
+            (space0, ")"),
+        )),
+    )
+        .map(|(name, arguments)| FunctionCall { name, arguments })
+        .parse_next(input)
+}
+
+/// Parse a literal function argument: a quoted string or an integer
+fn argument(input: &mut &str) -> PResult<Argument> {
+    alt((
+        string_literal.map(Argument::String),
+        dec_int.map(Argument::Integer),
+    ))
+    .context(StrContext::Label("argument"))
+    .parse_next(input)
+}
+
+/// Parse any number of filters, each preceded by a pipe. Whitespace is allowed
+/// on either side of each pipe
+fn filters(input: &mut &str) -> PResult<Vec<Filter>> {
+    repeat(
+        0..,
+        preceded((space0, FILTER_PIPE, space0), cut_err(filter)),
+    )
+    .parse_next(input)
+}
+
+/// Parse a single filter. Filters that take arguments each have their own
+/// parser; the rest are parsed by name alone.
+fn filter(input: &mut &str) -> PResult<Filter> {
+    alt((
+        filter_call("default", string_literal).map(Filter::Default),
+        filter_call(
+            "replace",
+            separated_pair(string_literal, argument_separator, string_literal),
+        )
+        .map(|(from, to)| Filter::Replace { from, to }),
+        filter_call(
+            "split",
+            separated_pair(string_literal, argument_separator, dec_int),
+        )
+        .map(|(separator, index)| Filter::Split { separator, index }),
+        filter_call(
+            "substring",
+            (dec_int, opt(preceded(argument_separator, dec_int))),
+        )
+        .map(|(start, end)| Filter::Substring { start, end }),
+        filter_call(
+            "pad",
+            (
+                dec_uint,
+                opt(preceded(argument_separator, cut_err(character_literal))),
+            ),
+        )
+        .map(|(width, fill)| Filter::Pad { width, fill }),
+        filter_call("fixed", dec_uint).map(|digits| Filter::Fixed { digits }),
+        alt((
+            filter_call("thousands", character_literal).map(Some),
+            // Make sure this isn't just the start of a longer name
+            terminated("thousands", not(one_of(Identifier::is_char_allowed)))
+                .value(None),
+        ))
+        .map(|separator| Filter::Thousands { separator }),
+        take_while(1.., Identifier::is_char_allowed).parse_to(),
+    ))
+    .context(StrContext::Label("filter"))
+    .parse_next(input)
+}
+
+/// Parse a filter that takes arguments, e.g. `default('fallback')`. Once
+/// we've seen the name and opening paren, any error is fatal
+fn filter_call<'a, O>(
+    name: &'static str,
+    arguments: impl Parser<&'a str, O, ContextError>,
+) -> impl Parser<&'a str, O, ContextError> {
+    preceded(
+        (name, "("),
+        cut_err(terminated(delimited(space0, arguments, space0), ")")),
+    )
+}
+
+/// Parse the comma between two arguments
+fn argument_separator(input: &mut &str) -> PResult<()> {
+    (space0, ",", space0).void().parse_next(input)
+}
+
+/// Parse a string literal containing exactly one character
+fn character_literal(input: &mut &str) -> PResult<char> {
+    string_literal
+        .verify_map(|s| {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(c),
+                _ => None,
+            }
+        })
+        .context(StrContext::Label("character"))
+        .parse_next(input)
+}
+
+/// Parse a single- or double-quoted string. A backslash escapes the next
+/// character, so quotes and backslashes can be included.
+fn string_literal(input: &mut &str) -> PResult<String> {
+    fn contents<'a>(quote: char) -> impl Parser<&'a str, String, ContextError> {
+        repeat(0.., alt((preceded('\\', any), none_of([quote, '\\']))))
+    }
+
+    alt((
+        delimited('\'', contents('\''), '\''),
+        delimited('"', contents('"'), '"'),
+    ))
+    .context(StrContext::Label("string"))
+    .parse_next(input)
+}
+
 /// Parse a field name/chain ID/env variable etc, inside a key. See [Identifier]
 /// for the definition of allowed syntax.
 fn identifier(input: &mut &str) -> PResult<Identifier> {
@@ -264,7 +852,7 @@ fn identifier(input: &mut &str) -> PResult<Identifier> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{assert_err, assert_matches};
+    use crate::{assert_err, assert_matches, template::FakeKind};
     use proptest::proptest;
     use rstest::rstest;
     use serde_test::{assert_tokens, Token};
@@ -284,17 +872,86 @@ mod tests {
 
     /// Shorthand for creating a field key chunk
     fn key_field(field: &'static str) -> TemplateInputChunk {
-        TemplateInputChunk::Key(TemplateKey::Field(field.into()))
+        key(TemplateKey::Field(field.into()), [])
     }
 
     /// Shorthand for creating an env key chunk
     fn key_env(variable: &'static str) -> TemplateInputChunk {
-        TemplateInputChunk::Key(TemplateKey::Environment(variable.into()))
+        key(TemplateKey::Environment(variable.into()), [])
     }
 
     /// Shorthand for creating a chain key chunk
     fn key_chain(chain_id: &'static str) -> TemplateInputChunk {
-        TemplateInputChunk::Key(TemplateKey::Chain(chain_id.into()))
+        key(TemplateKey::Chain(chain_id.into()), [])
+    }
+
+    /// Shorthand for creating a function call key chunk
+    fn key_function(
+        name: &'static str,
+        arguments: impl IntoIterator<Item = Argument>,
+    ) -> TemplateInputChunk {
+        let call = FunctionCall {
+            name: name.into(),
+            arguments: arguments.into_iter().collect(),
+        };
+        key(TemplateKey::Function(call), [])
+    }
+
+    /// Shorthand for creating a conditional block chunk
+    fn conditional(
+        condition: TemplateKey,
+        filters: impl IntoIterator<Item = Filter>,
+        then: impl IntoIterator<Item = TemplateInputChunk>,
+        otherwise: Option<Vec<TemplateInputChunk>>,
+    ) -> TemplateInputChunk {
+        TemplateInputChunk::Conditional {
+            condition,
+            filters: filters.into_iter().collect(),
+            then: then.into_iter().collect(),
+            otherwise,
+        }
+    }
+
+    /// Shorthand for creating a loop block chunk
+    fn each(
+        items: TemplateKey,
+        separator: Option<&str>,
+        body: impl IntoIterator<Item = TemplateInputChunk>,
+        otherwise: Option<Vec<TemplateInputChunk>>,
+    ) -> TemplateInputChunk {
+        TemplateInputChunk::Loop {
+            items,
+            filters: vec![],
+            separator: separator.map(String::from),
+            body: body.into_iter().collect(),
+            otherwise,
+        }
+    }
+
+    /// Shorthand for creating a key chunk that evaluates an expression
+    fn key_expression(expression: Expression) -> TemplateInputChunk {
+        key(TemplateKey::Expression(Box::new(expression)), [])
+    }
+
+    /// Shorthand for creating an expression operand that renders a field
+    fn field_operand(field: &'static str) -> Expression {
+        Expression::Operand(Operand::Key(TemplateKey::Field(field.into())))
+    }
+
+    /// Shorthand for creating a number literal operand
+    fn number_operand(number: f64) -> Expression {
+        Expression::Operand(Operand::Number(number))
+    }
+
+    /// Shorthand for creating a key chunk with filters
+    fn key(
+        key: TemplateKey,
+        filters: impl IntoIterator<Item = Filter>,
+    ) -> TemplateInputChunk {
+        TemplateInputChunk::Key {
+            key,
+            filters: filters.into_iter().collect(),
+        }
     }
 
     /// Test round tripping between raw strings and templates. Parse, display,
@@ -308,6 +965,44 @@ mod tests {
     #[case::field_number_id("{{1}}", tmpl([key_field("1")]), true)]
     #[case::chain("{{chains.chain1}}", tmpl([key_chain("chain1")]), true)]
     #[case::env("{{env.ENV}}", tmpl([key_env("ENV")]), true)]
+    #[case::data(
+        "{{data.user-id}}",
+        tmpl([key(TemplateKey::Data("user-id".into()), [])]),
+        true
+    )]
+    #[case::filter(
+        "{{chains.token | trim}}",
+        tmpl([key(TemplateKey::Chain("token".into()), [Filter::Trim])]),
+        true
+    )]
+    #[case::filters(
+        "{{field1 | trim | base64_decode | sha256}}",
+        tmpl([key(
+            TemplateKey::Field("field1".into()),
+            [Filter::Trim, Filter::Base64Decode, Filter::Sha256],
+        )]),
+        true
+    )]
+    #[case::function("{{uuid()}}", tmpl([key_function("uuid", [])]), true)]
+    #[case::fake(
+        "{{fake.first_name}}",
+        tmpl([key(TemplateKey::Fake(FakeKind::FirstName), [])]),
+        true
+    )]
+    #[case::function_arguments(
+        "{{random_int(-1, 'two') | trim}}",
+        tmpl([key(
+            TemplateKey::Function(FunctionCall {
+                name: "random_int".into(),
+                arguments: vec![
+                    Argument::Integer(-1),
+                    Argument::String("two".into()),
+                ],
+            }),
+            [Filter::Trim],
+        )]),
+        true
+    )]
     #[case::utf8(
         "intro\n{{user_id}} 💚💙💜 {{chains.chain}}\noutro\r\nmore outro",
         tmpl([
@@ -337,6 +1032,180 @@ mod tests {
         ]),
         true,
     )]
+    #[case::conditional(
+        "{{#if field1 | trim}}a {{field1}}{{/if}}",
+        tmpl([conditional(
+            TemplateKey::Field("field1".into()),
+            [Filter::Trim],
+            [raw("a "), key_field("field1")],
+            None,
+        )]),
+        true
+    )]
+    #[case::conditional_else(
+        "x{{#if env.HOME}}{{else}}{_{{/if}}",
+        tmpl([
+            raw("x"),
+            conditional(
+                TemplateKey::Environment("HOME".into()),
+                [],
+                [],
+                Some(vec![raw("{")]),
+            ),
+        ]),
+        true
+    )]
+    #[case::conditional_nested(
+        "{{#if a}}{{#if b}}1{{else}}2{{/if}}{{/if}}",
+        tmpl([conditional(
+            TemplateKey::Field("a".into()),
+            [],
+            [conditional(
+                TemplateKey::Field("b".into()),
+                [],
+                [raw("1")],
+                Some(vec![raw("2")]),
+            )],
+            None,
+        )]),
+        true
+    )]
+    #[case::each(
+        "[{{#each chains.users}}{{this.id}},{{/each}}]",
+        tmpl([
+            raw("["),
+            each(
+                TemplateKey::Chain("users".into()),
+                None,
+                [key(TemplateKey::ItemField("id".into()), []), raw(",")],
+                None,
+            ),
+            raw("]"),
+        ]),
+        true
+    )]
+    #[case::each_separator_else(
+        r"{{#each ids sep=', \'x'}}{{this}}{{else}}none{{/each}}",
+        tmpl([each(
+            TemplateKey::Field("ids".into()),
+            Some(", 'x"),
+            [key(TemplateKey::Item, [])],
+            Some(vec![raw("none")]),
+        )]),
+        true
+    )]
+    #[case::each_filters(
+        "{{#each ids | default('[]') sep=','}}{{/each}}",
+        tmpl([TemplateInputChunk::Loop {
+            items: TemplateKey::Field("ids".into()),
+            filters: vec![Filter::Default("[]".into())],
+            separator: Some(",".into()),
+            body: vec![],
+            otherwise: None,
+        }]),
+        true
+    )]
+    #[case::each_nested(
+        "{{#each a}}{{#if this}}{{#each this}}{{/each}}{{/if}}{{/each}}",
+        tmpl([each(
+            TemplateKey::Field("a".into()),
+            None,
+            [conditional(
+                TemplateKey::Item,
+                [],
+                [each(TemplateKey::Item, None, [], None)],
+                None,
+            )],
+            None,
+        )]),
+        true
+    )]
+    #[case::expression(
+        "{{page * 50 + 1}}",
+        tmpl([key_expression(Expression::binary(
+            Expression::binary(
+                field_operand("page"),
+                Operator::Multiply,
+                number_operand(50.0),
+            ),
+            Operator::Add,
+            number_operand(1.0),
+        ))]),
+        true
+    )]
+    #[case::expression_precedence(
+        "{{a - b % 2}}",
+        tmpl([key_expression(Expression::binary(
+            field_operand("a"),
+            Operator::Subtract,
+            Expression::binary(
+                field_operand("b"),
+                Operator::Remainder,
+                number_operand(2.0),
+            ),
+        ))]),
+        true
+    )]
+    #[case::expression_group(
+        "{{(a - b) / 0.5}}",
+        tmpl([key_expression(Expression::binary(
+            Expression::Group(Box::new(Expression::binary(
+                field_operand("a"),
+                Operator::Subtract,
+                field_operand("b"),
+            ))),
+            Operator::Divide,
+            number_operand(0.5),
+        ))]),
+        true
+    )]
+    #[case::expression_ternary(
+        "{{count > 0 ? 'yes' : 'no'}}",
+        tmpl([key_expression(Expression::ternary(
+            Expression::binary(
+                field_operand("count"),
+                Operator::Greater,
+                number_operand(0.0),
+            ),
+            Expression::Operand(Operand::String("yes".into())),
+            Expression::Operand(Operand::String("no".into())),
+        ))]),
+        true
+    )]
+    #[case::expression_filters(
+        "{{-1 * this.count | trim}}",
+        tmpl([key(
+            TemplateKey::Expression(Box::new(Expression::binary(
+                number_operand(-1.0),
+                Operator::Multiply,
+                Expression::Operand(Operand::Key(TemplateKey::ItemField(
+                    "count".into()
+                ))),
+            ))),
+            [Filter::Trim],
+        )]),
+        true
+    )]
+    #[case::expression_conditional(
+        "{{#if env.COUNT != '0'}}x{{/if}}",
+        tmpl([conditional(
+            TemplateKey::Expression(Box::new(Expression::binary(
+                Expression::Operand(Operand::Key(TemplateKey::Environment(
+                    "COUNT".into()
+                ))),
+                Operator::NotEqual,
+                Expression::Operand(Operand::String("0".into())),
+            ))),
+            [],
+            [raw("x")],
+            None,
+        )]),
+        true
+    )]
+    // Identifiers can contain dashes, so subtraction needs whitespace
+    #[case::dash_field("{{a-1}}", tmpl([key_field("a-1")]), true)]
+    // Outside a block, `else` is just a field
+    #[case::else_field("{{else}}", tmpl([key_field("else")]), true)]
     // `{_` should be treated literally when not followed by another {
     #[case::literal_underscores("{_a {_ _{", tmpl([raw("{_a {_ _{")]), false)]
     fn test_parse_display(
@@ -369,13 +1238,178 @@ mod tests {
     #[case::invalid_dotted_key("{{bogus.one}}", "invalid key")]
     #[case::invalid_chain("{{chains.one.two}}", "invalid key")]
     #[case::invalid_env("{{env.one.two}}", "invalid key")]
+    #[case::invalid_data("{{data.one.two}}", "invalid key")]
     #[case::whitespace_key("{{ field }}", "invalid identifier")]
     // the first { is escaped, 2nd and 3rd make the key, 4th is a problem
+    #[case::unknown_filter("{{field | bogus}}", "invalid filter")]
+    #[case::unknown_fake("{{fake.bogus}}", "invalid fake data type")]
+    #[case::default_no_argument("{{field | default}}", "invalid filter")]
+    #[case::default_unquoted("{{field | default(hi)}}", "invalid string")]
+    #[case::default_unclosed_string("{{field | default('hi)}}", "invalid")]
+    #[case::empty_filter("{{field | }}", "invalid filter")]
+    #[case::replace_one_argument("{{field | replace('a')}}", "invalid filter")]
+    #[case::split_string_index("{{field | split(',', '1')}}", "invalid filter")]
+    #[case::substring_no_arguments("{{field | substring()}}", "invalid filter")]
+    #[case::pad_negative("{{field | pad(-1)}}", "invalid filter")]
+    #[case::pad_long_fill("{{field | pad(4, 'ab')}}", "invalid character")]
+    #[case::fixed_no_argument("{{field | fixed}}", "invalid filter")]
+    #[case::fixed_too_large("{{field | fixed(256)}}", "invalid filter")]
+    #[case::thousands_long_separator(
+        "{{field | thousands(', ')}}",
+        "invalid character"
+    )]
+    #[case::thousands_prefix("{{field | thousandsx}}", "invalid filter")]
+    #[case::function_unclosed("{{uuid(}}", "invalid function")]
+    #[case::function_trailing_comma("{{now('%Y',)}}", "invalid")]
+    #[case::function_unquoted("{{now(%Y)}}", "invalid function")]
     #[case::bonus_braces(r#"\\{{{{field}}"#, "invalid identifier")]
+    #[case::conditional_unclosed("{{#if field}}yes", "invalid template chunk")]
+    #[case::conditional_no_key("{{#if }}yes{{/if}}", "invalid identifier")]
+    #[case::conditional_no_space(
+        "{{#iffield}}yes{{/if}}",
+        "invalid identifier"
+    )]
+    #[case::conditional_double_else(
+        "{{#if field}}{{else}}{{else}}{{/if}}",
+        "invalid conditional"
+    )]
+    #[case::conditional_stray_close("{{/if}}", "invalid identifier")]
+    #[case::each_unclosed("{{#each items}}yes", "invalid template chunk")]
+    #[case::each_wrong_close("{{#each items}}yes{{/if}}", "invalid identifier")]
+    #[case::each_separator_unquoted(
+        "{{#each items sep=,}}{{/each}}",
+        "invalid loop"
+    )]
+    #[case::invalid_item_field("{{this.}}", "invalid key")]
+    #[case::expression_missing_operand("{{a + }}", "invalid identifier")]
+    #[case::expression_unclosed_group("{{(a + 1}}", "invalid operand")]
+    #[case::expression_chained_comparison("{{a < b < c}}", "invalid key")]
+    #[case::expression_ternary_no_else("{{a ? b}}", "invalid expression")]
     fn test_parse_error(#[case] template: &str, #[case] expected_error: &str) {
         assert_err!(template.parse::<Template>(), expected_error);
     }
 
+    /// Test parsing the `default` filter's string argument
+    #[rstest]
+    #[case::single_quotes("{{field1 | default('hi')}}", "hi")]
+    #[case::double_quotes(r#"{{field1 | default("hi")}}"#, "hi")]
+    #[case::whitespace("{{field1 | default(  'hi' )}}", "hi")]
+    #[case::empty("{{field1 | default('')}}", "")]
+    #[case::escaped(r"{{field1 | default('it\'s a \\ }}')}}", r"it's a \ }}")]
+    #[case::other_quote(r#"{{field1 | default('say "hi"')}}"#, r#"say "hi""#)]
+    fn test_parse_default_filter(#[case] input: &str, #[case] expected: &str) {
+        let parsed: Template = input.parse().unwrap();
+        assert_eq!(
+            parsed,
+            tmpl([key(
+                TemplateKey::Field("field1".into()),
+                [Filter::Default(expected.into())]
+            )])
+        );
+        // Make sure it survives a round trip, even if the syntax changes
+        let displayed = parsed.display();
+        assert_eq!(displayed.parse::<Template>().unwrap(), parsed);
+    }
+
+    /// Filters with arguments. Whitespace around arguments is optional, and
+    /// normalized on display
+    #[rstest]
+    #[case::replace(
+        "{{field1 | replace('-',\"_\")}}",
+        Filter::Replace { from: "-".into(), to: "_".into() },
+        "{{field1 | replace('-', '_')}}"
+    )]
+    #[case::split(
+        "{{field1 | split( ',' , -1 )}}",
+        Filter::Split { separator: ",".into(), index: -1 },
+        "{{field1 | split(',', -1)}}"
+    )]
+    #[case::substring(
+        "{{field1 | substring(2)}}",
+        Filter::Substring { start: 2, end: None },
+        "{{field1 | substring(2)}}"
+    )]
+    #[case::substring_end(
+        "{{field1 | substring(0,3)}}",
+        Filter::Substring { start: 0, end: Some(3) },
+        "{{field1 | substring(0, 3)}}"
+    )]
+    #[case::pad(
+        "{{field1 | pad(4)}}",
+        Filter::Pad { width: 4, fill: None },
+        "{{field1 | pad(4)}}"
+    )]
+    #[case::pad_fill(
+        "{{field1 | pad(4, \"0\")}}",
+        Filter::Pad { width: 4, fill: Some('0') },
+        "{{field1 | pad(4, '0')}}"
+    )]
+    #[case::fixed(
+        "{{field1 | fixed( 2 )}}",
+        Filter::Fixed { digits: 2 },
+        "{{field1 | fixed(2)}}"
+    )]
+    #[case::thousands(
+        "{{field1 | thousands}}",
+        Filter::Thousands { separator: None },
+        "{{field1 | thousands}}"
+    )]
+    #[case::thousands_separator(
+        "{{field1 | thousands(\"_\")}}",
+        Filter::Thousands { separator: Some('_') },
+        "{{field1 | thousands('_')}}"
+    )]
+    #[case::case_conversion(
+        "{{field1 | snake_case}}",
+        Filter::SnakeCase,
+        "{{field1 | snake_case}}"
+    )]
+    fn test_parse_argument_filters(
+        #[case] input: &str,
+        #[case] expected: Filter,
+        #[case] expected_display: &str,
+    ) {
+        let parsed: Template = input.parse().unwrap();
+        assert_eq!(
+            parsed,
+            tmpl([key(TemplateKey::Field("field1".into()), [expected])])
+        );
+        assert_eq!(parsed.display(), expected_display);
+    }
+
+    /// Whitespace around function arguments is optional, and normalized on
+    /// display
+    #[rstest]
+    #[case::no_whitespace("{{random_int(1,2)}}")]
+    #[case::extra_whitespace("{{random_int(  1 ,\t2 )}}")]
+    fn test_parse_function_whitespace(#[case] input: &str) {
+        let parsed: Template = input.parse().unwrap();
+        assert_eq!(
+            parsed,
+            tmpl([key_function(
+                "random_int",
+                [Argument::Integer(1), Argument::Integer(2)]
+            )])
+        );
+        assert_eq!(parsed.display(), "{{random_int(1, 2)}}");
+    }
+
+    /// Whitespace around filter pipes is optional, and normalized on display
+    #[rstest]
+    #[case::no_whitespace("{{field1|trim|upper}}")]
+    #[case::extra_whitespace("{{field1  |\ttrim |  upper}}")]
+    fn test_parse_filter_whitespace(#[case] input: &str) {
+        let parsed: Template = input.parse().unwrap();
+        assert_eq!(
+            parsed,
+            tmpl([key(
+                TemplateKey::Field("field1".into()),
+                [Filter::Trim, Filter::Upper]
+            )])
+        );
+        assert_eq!(parsed.display(), "{{field1 | trim | upper}}");
+    }
+
     /// Test that [Template::from_field] generates the correct template
     #[test]
     fn test_from_field() {
@@ -392,6 +1426,67 @@ mod tests {
         assert_eq!(&template.chunks, &[key_chain("chain1")]);
     }
 
+    #[rstest]
+    #[case::raw("hello", &[])]
+    #[case::keys(
+        "{{field1}} {{chains.chain1 | trim}} {{env.ENV1}} {{field1}}",
+        &["field1", "chains.chain1", "env.ENV1", "field1"],
+    )]
+    #[case::conditional(
+        "{{#if field1}}{{field2}}{{else}}{{#if env.ENV1}}{{fake.email}}{{/if}}\
+        {{/if}}",
+        &["field1", "field2", "env.ENV1", "fake.email"],
+    )]
+    #[case::each(
+        "{{#each chains.users}}{{this.id}}{{field1}}{{else}}{{field2}}\
+        {{/each}}",
+        &["chains.users", "this.id", "field1", "field2"],
+    )]
+    #[case::expression(
+        "{{#if a > 1}}{{b ? (c + 1) : 'd'}}{{/if}}",
+        &["a", "b", "c"],
+    )]
+    fn test_keys(#[case] template: Template, #[case] expected: &[&str]) {
+        let keys: Vec<String> = template
+            .keys()
+            .into_iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(keys, expected);
+    }
+
+    /// Test [Template::translate]. Raw text isn't escaped, and anything the
+    /// callback rejects is kept in Slumber syntax
+    #[rstest]
+    #[case::raw("{_{hello}} world", "{{hello}} world", &[])]
+    #[case::keys("{{field1}}/{{field2}}", "<field1>/<field2>", &[])]
+    #[case::filter(
+        "{{field1 | trim}}!",
+        "{{field1 | trim}}!",
+        &["{{field1 | trim}}"],
+    )]
+    #[case::untranslated("{{env.HOME}}", "{{env.HOME}}", &["{{env.HOME}}"])]
+    #[case::conditional(
+        "a{{#if field1}}{{field2}}{{/if}}",
+        "a{{#if field1}}{{field2}}{{/if}}",
+        &["{{#if field1}}{{field2}}{{/if}}"],
+    )]
+    fn test_translate(
+        #[case] template: Template,
+        #[case] expected: &str,
+        #[case] expected_untranslated: &[&str],
+    ) {
+        let (translated, untranslated) =
+            template.translate(|key, filters| match key {
+                TemplateKey::Field(field) if filters.is_empty() => {
+                    Some(format!("<{field}>"))
+                }
+                _ => None,
+            });
+        assert_eq!(translated, expected);
+        assert_eq!(untranslated, expected_untranslated);
+    }
+
     /// Test [Template::raw]. This should parse+stringify back to the same thing
     #[rstest]
     #[case::empty("", tmpl([]))]
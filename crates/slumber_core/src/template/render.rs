@@ -2,24 +2,67 @@
 
 use crate::{
     collection::{
-        ChainId, ChainOutputTrim, ChainRequestSection, ChainRequestTrigger,
-        ChainSource, RecipeId,
+        AwsCredentialField, ChainId, ChainOutputTrim, ChainRequestSection,
+        ChainRequestTrigger, ChainSource, EnvironmentVariable, GcpTokenType,
+        KubernetesResourceKind, RandomChoice, RecipeId,
+    },
+    db::{CollectionDatabase, CollectionId},
+    http::{
+        content_type::ContentType,
+        query::{Selector, XPath},
+        Exchange, RequestSeed, RequestTrigger, ResponseRecord,
     },
-    http::{content_type::ContentType, Exchange, RequestSeed, ResponseRecord},
     template::{
-        error::TriggeredRequestError, parse::TemplateInputChunk, ChainError,
-        Prompt, Select, Template, TemplateChunk, TemplateContext,
-        TemplateError, TemplateKey,
+        error::TriggeredRequestError, expression::Value,
+        parse::TemplateInputChunk, ChainError, Expression, FakeKind, Filter,
+        FunctionCall, Operand, Prompt, Select, Template, TemplateChunk,
+        TemplateContext, TemplateError, TemplateKey,
+    },
+    util::{
+        aws::AwsCredentials, azure, consul, expand_home, gcp, git, run_cli,
+        secrets, FutureCache, FutureCacheOutcome, ResultTraced,
     },
-    util::{expand_home, FutureCache, FutureCacheOutcome, ResultTraced},
 };
+use anyhow::{anyhow, Context};
 use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
 use chrono::Utc;
-use futures::future;
-use std::{env, path::PathBuf, process::Stdio, sync::Arc};
-use tokio::{fs, io::AsyncWriteExt, process::Command, sync::oneshot};
+use futures::{
+    future::{self, BoxFuture},
+    FutureExt,
+};
+use indexmap::IndexMap;
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    SeedableRng,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env, iter,
+    path::PathBuf,
+    process::Stdio,
+    sync::{Arc, LazyLock, Mutex, OnceLock},
+};
+use tokio::{
+    fs,
+    io::AsyncWriteExt,
+    process::Command,
+    sync::{self, oneshot, OwnedMutexGuard},
+};
 use tracing::{debug, debug_span, error, instrument, trace, trace_span};
 
+/// One lock per exclusive chain, shared by every render group in the process,
+/// so that an exclusive chain is only ever computed by one render at a time.
+/// Chain IDs are only unique within a collection, so locks are keyed by
+/// collection as well. See [Chain::exclusive](crate::collection::Chain::exclusive).
+static CHAIN_LOCKS: LazyLock<Mutex<HashMap<ChainLockKey, ChainLock>>> =
+    LazyLock::new(Default::default);
+
+type ChainLockKey = (CollectionId, ChainId);
+type ChainLock = Arc<sync::Mutex<()>>;
+
 /// Outcome of rendering a single chunk. This allows attaching some metadata to
 /// the render.
 #[derive(Clone, Debug)]
@@ -33,8 +76,380 @@ struct RenderedChunk {
     sensitive: bool,
 }
 
+impl RenderedChunk {
+    /// Create a chunk from a `default` filter's fallback value
+    fn fallback(value: &str) -> Self {
+        Self {
+            value: value.as_bytes().to_vec().into(),
+            sensitive: false,
+        }
+    }
+
+    /// Apply a filter to the rendered value
+    fn filter(self, filter: &Filter) -> TemplateResult {
+        let value = filter.apply(Arc::unwrap_or_clone(self.value)).map_err(
+            |error| TemplateError::Filter {
+                filter: filter.clone(),
+                error,
+            },
+        )?;
+        Ok(Self {
+            value: value.into(),
+            sensitive: self.sensitive,
+        })
+    }
+}
+
+/// Apply filters to the result of rendering a key, in order. `default`
+/// replaces a missing or empty value, and any other filter transforms a
+/// successful value. Errors that aren't caught by a `default` pass through.
+fn apply_filters(result: TemplateResult, filters: &[Filter]) -> TemplateResult {
+    filters
+        .iter()
+        .fold(result, |result, filter| match (result, filter) {
+            (Err(error), Filter::Default(fallback)) if error.is_missing() => {
+                Ok(RenderedChunk::fallback(fallback))
+            }
+            (Ok(chunk), Filter::Default(fallback))
+                if chunk.value.is_empty() =>
+            {
+                Ok(RenderedChunk::fallback(fallback))
+            }
+            (Ok(chunk), Filter::Default(_)) => Ok(chunk),
+            (Ok(chunk), filter) => chunk.filter(filter),
+            (Err(error), _) => Err(error),
+        })
+}
+
+/// Render a list of parsed chunks. Raw text chunks will be mapped 1:1, while
+/// a conditional block expands to the rendered chunks of whichever branch it
+/// selected, and a loop block to its body once per element. This is boxed
+/// because blocks render recursively. `item` is the element of the innermost
+/// loop that we're in, if any.
+fn render_input_chunks<'a>(
+    chunks: &'a [TemplateInputChunk],
+    context: &'a TemplateContext,
+    stack: RenderKeyStack<'a>,
+    item: Option<Arc<LoopItem>>,
+) -> BoxFuture<'a, Vec<TemplateChunk>> {
+    // Map over each parsed chunk, and render the keys into strings. This
+    // clone is pretty cheap because raw text uses Arc and keys just contain
+    // metadata
+    let futures = chunks.iter().map(move |chunk| {
+        // Fork the local state, one copy for each new branch we're spawning
+        let mut stack = stack.clone();
+        let item = item.clone();
+        async move {
+            match chunk {
+                TemplateInputChunk::Raw(text) => {
+                    vec![TemplateChunk::Raw(Arc::clone(text))]
+                }
+                TemplateInputChunk::Key { key, filters } => {
+                    let result = render_scoped_key(
+                        key,
+                        item.as_deref(),
+                        context,
+                        &mut stack,
+                    )
+                    .await;
+                    if let (TemplateKey::Field(field), Ok(chunk)) =
+                        (key, &result)
+                    {
+                        context.state.record_field(field, chunk);
+                    }
+                    vec![apply_filters(result, filters).into()]
+                }
+                TemplateInputChunk::Conditional {
+                    condition,
+                    filters,
+                    then,
+                    otherwise,
+                } => {
+                    let truthy = match (condition, filters.as_slice()) {
+                        // Check the value of an expression before it's
+                        // converted to text, so e.g. a comparison that
+                        // renders `false` is falsey
+                        (TemplateKey::Expression(expression), []) => {
+                            evaluate_expression(
+                                expression,
+                                item.as_deref(),
+                                context,
+                                &mut stack,
+                            )
+                            .await
+                            .map(|evaluated| evaluated.value.is_truthy())
+                        }
+                        _ => apply_filters(
+                            render_scoped_key(
+                                condition,
+                                item.as_deref(),
+                                context,
+                                &mut stack,
+                            )
+                            .await,
+                            filters,
+                        )
+                        .map(|chunk| !chunk.value.is_empty()),
+                    };
+                    // A missing value is falsey, but a real failure should
+                    // still be shown to the user
+                    let branch = match truthy {
+                        Ok(true) => then,
+                        Err(error) if !error.is_missing() => {
+                            return vec![TemplateChunk::Error(error)]
+                        }
+                        Ok(false) | Err(_) => {
+                            otherwise.as_deref().unwrap_or_default()
+                        }
+                    };
+                    render_input_chunks(branch, context, stack, item).await
+                }
+                TemplateInputChunk::Loop {
+                    items,
+                    filters,
+                    separator,
+                    body,
+                    otherwise,
+                } => {
+                    // Unlike a conditional, a missing value is an error. An
+                    // empty array has to be given explicitly, e.g. with
+                    // `default('[]')`
+                    let result = apply_filters(
+                        render_scoped_key(
+                            items,
+                            item.as_deref(),
+                            context,
+                            &mut stack,
+                        )
+                        .await,
+                        filters,
+                    );
+                    let array = match result {
+                        Ok(array) => array,
+                        Err(error) => return vec![TemplateChunk::Error(error)],
+                    };
+                    let Ok(serde_json::Value::Array(elements)) =
+                        serde_json::from_slice(&array.value)
+                    else {
+                        return vec![TemplateChunk::Error(
+                            TemplateError::LoopNotArray,
+                        )];
+                    };
+                    if elements.is_empty() {
+                        let otherwise =
+                            otherwise.as_deref().unwrap_or_default();
+                        return render_input_chunks(
+                            otherwise, context, stack, item,
+                        )
+                        .await;
+                    }
+
+                    let rendered =
+                        future::join_all(elements.into_iter().map(|value| {
+                            let item = LoopItem {
+                                value,
+                                sensitive: array.sensitive,
+                            };
+                            render_input_chunks(
+                                body,
+                                context,
+                                stack.clone(),
+                                Some(item.into()),
+                            )
+                        }))
+                        .await;
+                    let separator = separator.clone().map(Arc::new);
+                    let mut chunks = Vec::new();
+                    for (i, rendered) in rendered.into_iter().enumerate() {
+                        if i > 0 {
+                            chunks.extend(
+                                separator.clone().map(TemplateChunk::Raw),
+                            );
+                        }
+                        chunks.extend(rendered);
+                    }
+                    chunks
+                }
+            }
+        }
+    });
+
+    // Parallelization!
+    async move {
+        future::join_all(futures)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+    .boxed()
+}
+
+/// Render a key inside a block. Keys that refer to the current loop item are
+/// rendered from the item, expressions are evaluated with access to the item,
+/// and everything else goes through [render_key].
+async fn render_scoped_key<'a>(
+    key: &'a TemplateKey,
+    item: Option<&LoopItem>,
+    context: &'a TemplateContext,
+    stack: &mut RenderKeyStack<'a>,
+) -> TemplateResult {
+    if let TemplateKey::Expression(expression) = key {
+        return evaluate_expression(expression, item, context, stack)
+            .await
+            .map(EvaluatedExpression::into_chunk);
+    }
+    match item.and_then(|item| item.render(key)) {
+        Some(result) => result,
+        None => render_key(key, context, stack).await,
+    }
+}
+
+/// Evaluate an expression, rendering each key operand. Operands are evaluated
+/// one at a time, left to right, and only the selected branch of a ternary is
+/// evaluated at all. This is boxed because expressions are recursive.
+fn evaluate_expression<'a, 'b>(
+    expression: &'a Expression,
+    item: Option<&'b LoopItem>,
+    context: &'a TemplateContext,
+    stack: &'b mut RenderKeyStack<'a>,
+) -> BoxFuture<'b, Result<EvaluatedExpression, TemplateError>>
+where
+    'a: 'b,
+{
+    async move {
+        match expression {
+            Expression::Operand(Operand::Number(number)) => {
+                Ok(EvaluatedExpression {
+                    value: Value::Number(*number),
+                    sensitive: false,
+                })
+            }
+            Expression::Operand(Operand::String(s)) => {
+                Ok(EvaluatedExpression {
+                    value: Value::String(s.clone()),
+                    sensitive: false,
+                })
+            }
+            Expression::Operand(Operand::Key(key)) => {
+                let chunk =
+                    render_scoped_key(key, item, context, stack).await?;
+                if let TemplateKey::Field(field) = key {
+                    context.state.record_field(field, &chunk);
+                }
+                let value =
+                    String::from_utf8(Arc::unwrap_or_clone(chunk.value))
+                        .map_err(TemplateError::InvalidUtf8)?;
+                Ok(EvaluatedExpression {
+                    value: Value::String(value),
+                    sensitive: chunk.sensitive,
+                })
+            }
+            Expression::Group(inner) => {
+                evaluate_expression(inner, item, context, stack).await
+            }
+            Expression::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left =
+                    evaluate_expression(left, item, context, stack).await?;
+                let right =
+                    evaluate_expression(right, item, context, stack).await?;
+                let value = operator.apply(left.value, right.value).map_err(
+                    |error| TemplateError::Expression {
+                        expression: expression.to_string(),
+                        error,
+                    },
+                )?;
+                Ok(EvaluatedExpression {
+                    value,
+                    sensitive: left.sensitive || right.sensitive,
+                })
+            }
+            Expression::Ternary {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let condition =
+                    evaluate_expression(condition, item, context, stack)
+                        .await?;
+                let branch = if condition.value.is_truthy() {
+                    then
+                } else {
+                    otherwise
+                };
+                let result =
+                    evaluate_expression(branch, item, context, stack).await?;
+                // The output reveals something about the condition, so it
+                // inherits its sensitivity
+                Ok(EvaluatedExpression {
+                    value: result.value,
+                    sensitive: condition.sensitive || result.sensitive,
+                })
+            }
+        }
+    }
+    .boxed()
+}
+
+/// Render a single key, either from the overrides or its source
+async fn render_key<'a>(
+    key: &'a TemplateKey,
+    context: &'a TemplateContext,
+    stack: &mut RenderKeyStack<'a>,
+) -> TemplateResult {
+    // The formatted key should match the source that it was parsed
+    // from, therefore we can use it to match the override key
+    let raw = key.to_string();
+
+    // If the key is in the overrides, use the given value
+    // without parsing it
+    match context.overrides.get(&raw) {
+        Some(value) => {
+            trace!(key = raw, value, "Rendered template key from override");
+            Ok(RenderedChunk {
+                value: value.clone().into_bytes().into(),
+                // The overriden value *could* be marked
+                // sensitive, but we're taking a shortcut and
+                // assuming it isn't
+                sensitive: false,
+            })
+        }
+        None => {
+            let span = trace_span!("Rendering template key", key = raw);
+            let _ = span.enter();
+            stack.push(key)?;
+            // Standard case - parse the key and render it
+            let result = key.to_source().render(context, stack).await;
+            stack.pop();
+            if let Ok(value) = &result {
+                trace!(?value, "Rendered template key to value");
+            }
+            result
+        }
+    }
+}
+
 type TemplateResult = Result<RenderedChunk, TemplateError>;
 
+/// Outcome of evaluating an expression, before it's converted to text
+struct EvaluatedExpression {
+    value: Value,
+    sensitive: bool,
+}
+
+impl EvaluatedExpression {
+    fn into_chunk(self) -> RenderedChunk {
+        RenderedChunk {
+            value: self.value.to_string().into_bytes().into(),
+            sensitive: self.sensitive,
+        }
+    }
+}
+
 impl Template {
     /// Render the template using values from the given context. If any chunk
     /// failed to render, return an error. The template is rendered as bytes.
@@ -77,6 +492,17 @@ impl Template {
         context: &'a TemplateContext,
         stack: &mut RenderKeyStack<'a>,
     ) -> Result<Vec<u8>, TemplateError> {
+        let rendered = self.render_stitched_impl(context, stack).await?;
+        Ok(Arc::unwrap_or_clone(rendered.value))
+    }
+
+    /// Render the template into a single chunk. The output is sensitive if
+    /// any of its rendered chunks are
+    async fn render_stitched_impl<'a>(
+        &'a self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+    ) -> TemplateResult {
         // Render each individual template chunk in the string
         let chunks = self.render_chunks_impl(context, stack).await;
 
@@ -90,10 +516,12 @@ impl Template {
             })
             .sum();
         let mut buf = Vec::with_capacity(len);
+        let mut any_sensitive = false;
         for chunk in chunks {
             match chunk {
                 TemplateChunk::Raw(text) => buf.extend(text.as_bytes()),
-                TemplateChunk::Rendered { value, .. } => {
+                TemplateChunk::Rendered { value, sensitive } => {
+                    any_sensitive |= sensitive;
                     // Only clone if we have multiple copies of this data, which
                     // only occurs if a chain is used more than once
                     buf.extend(Arc::unwrap_or_clone(value))
@@ -102,7 +530,10 @@ impl Template {
             }
         }
 
-        Ok(buf)
+        Ok(RenderedChunk {
+            value: buf.into(),
+            sensitive: any_sensitive,
+        })
     }
 
     /// Internal version of [Self::render_string] with local render state
@@ -122,67 +553,7 @@ impl Template {
         context: &'a TemplateContext,
         stack: &mut RenderKeyStack<'a>,
     ) -> Vec<TemplateChunk> {
-        async fn render_key<'a>(
-            key: &'a TemplateKey,
-            context: &'a TemplateContext,
-            stack: &mut RenderKeyStack<'a>,
-        ) -> TemplateResult {
-            // The formatted key should match the source that it was parsed
-            // from, therefore we can use it to match the override key
-            let raw = key.to_string();
-
-            // If the key is in the overrides, use the given value
-            // without parsing it
-            match context.overrides.get(&raw) {
-                Some(value) => {
-                    trace!(
-                        key = raw,
-                        value,
-                        "Rendered template key from override"
-                    );
-                    Ok(RenderedChunk {
-                        value: value.clone().into_bytes().into(),
-                        // The overriden value *could* be marked
-                        // sensitive, but we're taking a shortcut and
-                        // assuming it isn't
-                        sensitive: false,
-                    })
-                }
-                None => {
-                    let span = trace_span!("Rendering template key", key = raw);
-                    let _ = span.enter();
-                    stack.push(key)?;
-                    // Standard case - parse the key and render it
-                    let result = key.to_source().render(context, stack).await;
-                    stack.pop();
-                    if let Ok(value) = &result {
-                        trace!(?value, "Rendered template key to value");
-                    }
-                    result
-                }
-            }
-        }
-
-        // Map over each parsed chunk, and render the keys into strings. The
-        // raw text chunks will be mapped 1:1. This clone is pretty cheap
-        // because raw text uses Arc and keys just contain metadata
-        let futures = self.chunks.iter().map(|chunk| {
-            // Fork the local state, one copy for each new branch we're spawning
-            let mut stack = stack.clone();
-            async move {
-                match chunk {
-                    TemplateInputChunk::Raw(text) => {
-                        TemplateChunk::Raw(Arc::clone(text))
-                    }
-                    TemplateInputChunk::Key(key) => {
-                        render_key(key, context, &mut stack).await.into()
-                    }
-                }
-            }
-        });
-
-        // Parallelization!
-        future::join_all(futures).await
+        render_input_chunks(&self.chunks, context, stack.clone(), None).await
     }
 
     /// Render a template whose result will be used as configuration for a
@@ -224,6 +595,13 @@ impl TemplateKey {
             Self::Environment(variable) => {
                 Box::new(EnvironmentTemplateSource { variable })
             }
+            Self::Function(call) => Box::new(FunctionTemplateSource { call }),
+            Self::Fake(kind) => Box::new(FakeTemplateSource { kind: *kind }),
+            Self::Data(column) => Box::new(DataTemplateSource { column }),
+            Self::Item | Self::ItemField(_) => Box::new(ItemTemplateSource),
+            Self::Expression(expression) => {
+                Box::new(ExpressionTemplateSource { expression })
+            }
         }
     }
 }
@@ -261,6 +639,14 @@ impl<'a> TemplateSource<'a> for FieldTemplateSource<'a> {
     ) -> TemplateResult {
         let field = self.field;
 
+        // Recipe parameters take precedence over the profile
+        if let Some(value) = context.state.parameter(field) {
+            return Ok(RenderedChunk {
+                value: value.to_owned().into_bytes().into(),
+                sensitive: false,
+            });
+        }
+
         // Get the value from the profile
         let profile_id = context
             .selected_profile
@@ -268,31 +654,27 @@ impl<'a> TemplateSource<'a> for FieldTemplateSource<'a> {
             .ok_or_else(|| TemplateError::NoProfileSelected)?;
         // Typically the caller should validate the ID is valid, this is just
         // a backup check
-        let profile =
-            context.collection.profiles.get(profile_id).ok_or_else(|| {
-                TemplateError::ProfileUnknown {
-                    profile_id: profile_id.clone(),
-                }
-            })?;
-        let template = profile.data.get(field).ok_or_else(|| {
-            TemplateError::FieldUnknown {
+        if !context.collection.profiles.contains_key(profile_id) {
+            return Err(TemplateError::ProfileUnknown {
+                profile_id: profile_id.clone(),
+            });
+        }
+        // Fall back to the profiles this one inherits from
+        let template = context
+            .collection
+            .profile_field(profile_id, field)
+            .ok_or_else(|| TemplateError::FieldUnknown {
                 field: field.to_owned(),
-            }
-        })?;
+            })?;
 
-        // recursion!
-        let rendered =
-            template
-                .render_impl(context, stack)
-                .await
-                .map_err(|error| TemplateError::FieldNested {
-                    field: field.to_owned(),
-                    error: Box::new(error),
-                })?;
-        Ok(RenderedChunk {
-            value: rendered.into(),
-            sensitive: false,
-        })
+        // recursion! The field is sensitive if anything it pulls from is
+        template
+            .render_stitched_impl(context, stack)
+            .await
+            .map_err(|error| TemplateError::FieldNested {
+                field: field.to_owned(),
+                error: Box::new(error),
+            })
     }
 }
 
@@ -332,6 +714,47 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
                     || ChainError::ChainUnknown(self.chain_id.clone()),
                 )?;
 
+            // Wait for any other render computing this chain to finish. The
+            // lock is held until the value is computed and cached, so if the
+            // chain is cached persistently, whoever's waiting will pick up our
+            // value instead of computing a new one
+            let _lock = if chain.exclusive {
+                Some(
+                    lock_chain(context.database.collection_id(), self.chain_id)
+                        .await,
+                )
+            } else {
+                None
+            };
+
+            // In offline mode, the example stands in for the entire chain
+            if let Some(example) =
+                chain.example.as_ref().filter(|_| context.offline)
+            {
+                let value = example
+                    .render_chain_config("example", context, stack)
+                    .await?;
+                return Ok(RenderedChunk {
+                    value: value.into_bytes().into(),
+                    sensitive: chain.sensitive,
+                });
+            }
+
+            // If the chain is cached persistently, try that before computing
+            let profile_id = context.selected_profile.as_ref();
+            if chain.cache.is_some() {
+                if let Some(value) = context
+                    .database
+                    .get_chain_cache(profile_id, self.chain_id)
+                    .map_err(|error| ChainError::Database(error.into()))?
+                {
+                    return Ok(RenderedChunk {
+                        value: value.into(),
+                        sensitive: chain.sensitive,
+                    });
+                }
+            }
+
             // Resolve the value based on the source type. Also resolve its
             // content type. For responses this will come from its header, from
             // files from its extension. For anything else, we'll fall back to
@@ -340,6 +763,28 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
             // We intentionally throw the content detection error away here,
             // because it isn't that intuitive for users and is hard to plumb
             let (value, content_type) = match &chain.source {
+                ChainSource::AwsCredentials { profile, field } => (
+                    self.render_aws_credentials(
+                        context,
+                        stack,
+                        profile.as_ref(),
+                        *field,
+                    )
+                    .await?,
+                    // No way to guess content type on this
+                    None,
+                ),
+                ChainSource::AzureToken { scope, tenant } => (
+                    self.render_azure_token(
+                        context,
+                        stack,
+                        scope.as_ref(),
+                        tenant.as_ref(),
+                    )
+                    .await?,
+                    // No way to guess content type on this
+                    None,
+                ),
                 ChainSource::Command { command, stdin } => (
                     self.render_command(
                         context,
@@ -351,15 +796,76 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
                     // No way to guess content type on this
                     None,
                 ),
+                ChainSource::Consul {
+                    service,
+                    tag,
+                    address,
+                } => (
+                    self.render_consul(
+                        context,
+                        stack,
+                        service,
+                        tag.as_ref(),
+                        address.as_ref(),
+                    )
+                    .await?,
+                    // No way to guess content type on this
+                    None,
+                ),
                 ChainSource::File { path } => {
                     self.render_file(context, stack, path).await?
                 }
+                ChainSource::GcpToken {
+                    token_type,
+                    audience,
+                } => (
+                    self.render_gcp_token(
+                        context,
+                        stack,
+                        *token_type,
+                        audience.as_ref(),
+                    )
+                    .await?,
+                    // No way to guess content type on this
+                    None,
+                ),
                 ChainSource::Environment { variable } => (
                     self.render_environment_variable(context, stack, variable)
                         .await?,
                     // No way to guess content type on this
                     None,
                 ),
+                ChainSource::EnvFile { path, variable } => (
+                    self.render_env_file(context, stack, path, variable)
+                        .await?,
+                    // No way to guess content type on this
+                    None,
+                ),
+                ChainSource::Keyring { service, user } => (
+                    self.render_keyring(context, stack, service, user).await?,
+                    // No way to guess content type on this
+                    None,
+                ),
+                ChainSource::Kubernetes {
+                    kind,
+                    name,
+                    key,
+                    namespace,
+                    context: kube_context,
+                } => (
+                    self.render_kubernetes(
+                        context,
+                        stack,
+                        *kind,
+                        name,
+                        key,
+                        namespace.as_ref(),
+                        kube_context.as_ref(),
+                    )
+                    .await?,
+                    // No way to guess content type on this
+                    None,
+                ),
                 ChainSource::Prompt { message, default } => (
                     self.render_prompt(
                         context,
@@ -373,6 +879,18 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
                     // No way to guess content type on this
                     None,
                 ),
+                ChainSource::Random { values, file, seed } => (
+                    self.render_random(
+                        context,
+                        stack,
+                        values,
+                        file.as_ref(),
+                        *seed,
+                    )
+                    .await?,
+                    // No way to guess content type on this
+                    None,
+                ),
                 ChainSource::Request {
                     recipe,
                     trigger,
@@ -390,6 +908,12 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
                         .await?;
                     (value, content_type)
                 }
+                ChainSource::Rotate { values, file } => (
+                    self.render_rotate(context, stack, values, file.as_ref())
+                        .await?,
+                    // No way to guess content type on this
+                    None,
+                ),
                 ChainSource::Select { message, options } => (
                     self.render_select(
                         context,
@@ -407,23 +931,48 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
             let content_type = chain.content_type.or(content_type);
 
             // If a selector path is present, filter down the value
-            let value = if let Some(selector) = &chain.selector {
-                let content_type =
-                    content_type.ok_or(ChainError::UnknownContentType)?;
-                // Parse according to detected content type
-                let value =
-                    content_type.parse_content(&value).map_err(|error| {
-                        ChainError::ParseResponse {
+            let value = match &chain.selector {
+                Some(Selector::JsonPath(query)) => {
+                    let content_type =
+                        content_type.ok_or(ChainError::UnknownContentType)?;
+                    // Parse according to detected content type
+                    let value = content_type.parse_content(&value).map_err(
+                        |error| ChainError::ParseResponse {
                             error: error.into(),
-                        }
-                    })?;
-                selector.query_to_string(&*value)?.into_bytes()
-            } else {
-                value
+                        },
+                    )?;
+                    query.query_to_string(&*value)?.into_bytes()
+                }
+                // XPath doesn't need a content type, it's always XML/HTML
+                Some(Selector::XPath(xpath)) => {
+                    let package =
+                        XPath::parse_document(&value).map_err(|error| {
+                            ChainError::ParseResponse {
+                                error: error.into(),
+                            }
+                        })?;
+                    xpath.query_to_string(&package.as_document())?.into_bytes()
+                }
+                // Regex works on any text, so no parsing needed
+                Some(Selector::Regex(regex)) => regex.extract(&value)?,
+                None => value,
             };
 
+            let value = chain.trim.apply(value);
+
+            if let Some(ttl) = chain.cache {
+                // Failing to cache shouldn't fail the render. The error is
+                // already traced
+                let _ = context.database.set_chain_cache(
+                    profile_id,
+                    self.chain_id,
+                    &value,
+                    Utc::now() + ttl,
+                );
+            }
+
             Ok(RenderedChunk {
-                value: chain.trim.apply(value).into(),
+                value: value.into(),
                 sensitive: chain.sensitive,
             })
         }
@@ -443,7 +992,8 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
 impl<'a> ChainTemplateSource<'a> {
     /// Get an HTTP response for a recipe. This will either get the most recent
     /// response from history or re-execute the request, depending on trigger
-    /// behavior.
+    /// behavior. If the request is never triggered and there's no history,
+    /// use the recipe's example response.
     async fn get_response(
         &self,
         context: &'a TemplateContext,
@@ -493,15 +1043,16 @@ impl<'a> ChainTemplateSource<'a> {
                     .http_engine
                     .as_ref()
                     .ok_or(TriggeredRequestError::NotAllowed)?;
-                let ticket = http_engine
-                    .build(
-                        RequestSeed::new(recipe_id.clone(), build_options),
-                        context,
-                    )
-                    .await
-                    .map_err(|error| {
-                        TriggeredRequestError::Build(error.into())
-                    })?;
+                let seed = RequestSeed {
+                    trigger: Some(RequestTrigger {
+                        chain_id: self.chain_id.clone(),
+                        recipe_id: context.state.recipe_id.get().cloned(),
+                    }),
+                    ..RequestSeed::new(recipe_id.clone(), build_options)
+                };
+                let ticket = http_engine.build(seed, context).await.map_err(
+                    |error| TriggeredRequestError::Build(error.into()),
+                )?;
                 ticket
                     .send(&context.database)
                     .await
@@ -515,9 +1066,22 @@ impl<'a> ChainTemplateSource<'a> {
 
         // Grab the most recent request in history, or send a new request
         let exchange = match trigger {
-            ChainRequestTrigger::Never => {
-                get_most_recent()?.ok_or(ChainError::NoResponse)?
-            }
+            ChainRequestTrigger::Never => match get_most_recent()? {
+                Some(exchange) => exchange,
+                // Fall back to the recipe's example, if it has one
+                None => {
+                    let example = recipe
+                        .example
+                        .as_ref()
+                        .ok_or(ChainError::NoResponse)?;
+                    return ResponseRecord::try_from(example).map_err(
+                        |error| ChainError::Example {
+                            recipe_id: recipe.id.clone(),
+                            error: error.into(),
+                        },
+                    );
+                }
+            },
             ChainRequestTrigger::NoHistory => {
                 // If a exchange is present in history, use that. If not, fetch
                 if let Some(exchange) = get_most_recent()? {
@@ -543,7 +1107,8 @@ impl<'a> ChainTemplateSource<'a> {
     }
 
     /// Extract the specified component bytes from the response. For headers,
-    /// the header name is a template so we'll render that.
+    /// the header name is a template so we'll render that. Status codes are
+    /// rendered as their numeric value.
     async fn extract_response_value(
         &self,
         context: &'a TemplateContext,
@@ -553,8 +1118,13 @@ impl<'a> ChainTemplateSource<'a> {
     ) -> Result<Vec<u8>, ChainError> {
         Ok(match component {
             // This will clone the bytes, which is necessary for the subsequent
-            // string conversion anyway
-            ChainRequestSection::Body => response.body.into_bytes().into(),
+            // string conversion anyway. A streamed body has to be loaded from
+            // the blob store
+            ChainRequestSection::Body => response
+                .body
+                .load_bytes()
+                .map_err(|error| ChainError::Database(error.into()))?
+                .into(),
             ChainRequestSection::Header(header) => {
                 let header = header
                     .render_chain_config("section", context, stack)
@@ -567,6 +1137,9 @@ impl<'a> ChainTemplateSource<'a> {
                     .as_bytes()
                     .to_vec()
             }
+            ChainRequestSection::Status => {
+                response.status.as_u16().to_string().into_bytes()
+            }
         })
     }
 
@@ -584,6 +1157,37 @@ impl<'a> ChainTemplateSource<'a> {
         Ok(value.into_bytes())
     }
 
+    /// Render a chained value from a dotenv file
+    async fn render_env_file(
+        &self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+        path: &'a Template,
+        variable: &'a Template,
+    ) -> Result<Vec<u8>, ChainError> {
+        let path: PathBuf = path
+            .render_chain_config("path", context, stack)
+            .await?
+            .into();
+        let path = expand_home(path).into_owned(); // Expand ~
+        let variable = variable
+            .render_chain_config("variable", context, stack)
+            .await?;
+
+        let result = fs::read(&path)
+            .await
+            .context("Error reading file")
+            .and_then(|content| load_env_file_variable(&content, &variable));
+        let value = result
+            .map_err(|error| ChainError::EnvFile {
+                path,
+                variable,
+                error: error.into(),
+            })
+            .traced()?;
+        Ok(value.into_bytes())
+    }
+
     /// Render a chained value from a file. Return the files bytes, as well as
     /// its content type if it's known
     async fn render_file(
@@ -698,6 +1302,213 @@ impl<'a> ChainTemplateSource<'a> {
         Ok(output.stdout)
     }
 
+    /// Render a single field from the user's AWS credentials
+    async fn render_aws_credentials(
+        &self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+        profile: Option<&'a Template>,
+        field: AwsCredentialField,
+    ) -> Result<Vec<u8>, ChainError> {
+        let profile = if let Some(template) = profile {
+            Some(
+                template
+                    .render_chain_config("profile", context, stack)
+                    .await?,
+            )
+        } else {
+            None
+        };
+        let credentials = AwsCredentials::load(profile.as_deref())
+            .await
+            .map_err(|error| ChainError::AwsCredentials(error.into()))
+            .traced()?;
+        let value = match field {
+            AwsCredentialField::AccessKeyId => credentials.access_key_id,
+            AwsCredentialField::SecretAccessKey => {
+                credentials.secret_access_key
+            }
+            AwsCredentialField::SessionToken => {
+                credentials.session_token.unwrap_or_default()
+            }
+        };
+        Ok(value.into_bytes())
+    }
+
+    /// Get an access token from Azure
+    async fn render_azure_token(
+        &self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+        scope: Option<&'a Template>,
+        tenant: Option<&'a Template>,
+    ) -> Result<Vec<u8>, ChainError> {
+        let scope = if let Some(template) = scope {
+            Some(
+                template
+                    .render_chain_config("scope", context, stack)
+                    .await?,
+            )
+        } else {
+            None
+        };
+        let tenant = if let Some(template) = tenant {
+            Some(
+                template
+                    .render_chain_config("tenant", context, stack)
+                    .await?,
+            )
+        } else {
+            None
+        };
+        let token = azure::access_token(scope.as_deref(), tenant.as_deref())
+            .await
+            .map_err(|error| ChainError::AzureToken(error.into()))
+            .traced()?;
+        Ok(token.into_bytes())
+    }
+
+    /// Resolve a service's address via Consul
+    async fn render_consul(
+        &self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+        service: &'a Template,
+        tag: Option<&'a Template>,
+        address: Option<&'a Template>,
+    ) -> Result<Vec<u8>, ChainError> {
+        let service = service
+            .render_chain_config("service", context, stack)
+            .await?;
+        let tag = if let Some(template) = tag {
+            Some(template.render_chain_config("tag", context, stack).await?)
+        } else {
+            None
+        };
+        let address = if let Some(template) = address {
+            Some(
+                template
+                    .render_chain_config("address", context, stack)
+                    .await?,
+            )
+        } else {
+            None
+        };
+        let address =
+            consul::resolve(&service, tag.as_deref(), address.as_deref())
+                .await
+                .map_err(|error| ChainError::Consul(error.into()))
+                .traced()?;
+        Ok(address.into_bytes())
+    }
+
+    /// Mint an access or ID token from Google Cloud
+    async fn render_gcp_token(
+        &self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+        token_type: GcpTokenType,
+        audience: Option<&'a Template>,
+    ) -> Result<Vec<u8>, ChainError> {
+        let audience = if let Some(template) = audience {
+            Some(
+                template
+                    .render_chain_config("audience", context, stack)
+                    .await?,
+            )
+        } else {
+            None
+        };
+        let result = match (token_type, audience) {
+            (GcpTokenType::AccessToken, None) => gcp::access_token().await,
+            (GcpTokenType::AccessToken, Some(_)) => Err(anyhow!(
+                "`audience` is only supported for `token_type: id_token`"
+            )),
+            (GcpTokenType::IdToken, audience) => {
+                gcp::id_token(audience.as_deref()).await
+            }
+        };
+        let token = result
+            .map_err(|error| ChainError::GcpToken(error.into()))
+            .traced()?;
+        Ok(token.into_bytes())
+    }
+
+    /// Load a secret from the OS secret store
+    async fn render_keyring(
+        &self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+        service: &'a Template,
+        user: &'a Template,
+    ) -> Result<Vec<u8>, ChainError> {
+        let service = service
+            .render_chain_config("service", context, stack)
+            .await?;
+        let user = user.render_chain_config("user", context, stack).await?;
+        let secret = secrets::get(&service, &user)
+            .await
+            .map_err(|error| ChainError::Keyring {
+                service,
+                user,
+                error: error.into(),
+            })
+            .traced()?;
+        Ok(secret.into_bytes())
+    }
+
+    /// Render a value from a Kubernetes Secret/ConfigMap. This shells out to
+    /// `kubectl` so we get the user's kubeconfig and auth plugins for free.
+    #[allow(clippy::too_many_arguments)]
+    async fn render_kubernetes(
+        &self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+        kind: KubernetesResourceKind,
+        name: &'a Template,
+        key: &'a Template,
+        namespace: Option<&'a Template>,
+        kube_context: Option<&'a Template>,
+    ) -> Result<Vec<u8>, ChainError> {
+        let name = name.render_chain_config("name", context, stack).await?;
+        let key = key.render_chain_config("key", context, stack).await?;
+        let resource_type = match kind {
+            KubernetesResourceKind::Secret => "secret",
+            KubernetesResourceKind::ConfigMap => "configmap",
+        };
+        let mut args = vec!["get", resource_type, &name, "--output", "json"]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if let Some(namespace) = namespace {
+            let namespace = namespace
+                .render_chain_config("namespace", context, stack)
+                .await?;
+            args.extend(["--namespace".into(), namespace]);
+        }
+        if let Some(kube_context) = kube_context {
+            let kube_context = kube_context
+                .render_chain_config("context", context, stack)
+                .await?;
+            args.extend(["--context".into(), kube_context]);
+        }
+
+        // Shitty try block
+        let result = async {
+            let output = run_cli("kubectl", &args).await?;
+            extract_kubernetes_value(kind, &output, &key)
+        };
+        result
+            .await
+            .map_err(|error| ChainError::Kubernetes {
+                kind,
+                name,
+                key,
+                error: error.into(),
+            })
+            .traced()
+    }
+
     /// Render a value by asking the user to provide it
     async fn render_prompt(
         &self,
@@ -777,21 +1588,215 @@ impl<'a> ChainTemplateSource<'a> {
 
         rx.await.map_err(|_| ChainError::PromptNoResponse)
     }
+
+    /// Render the next value in a rotation. Only the selected value is
+    /// rendered. If the render is meant to be idempotent (i.e. there's no HTTP
+    /// engine), the current value is used without advancing the rotation.
+    async fn render_rotate(
+        &self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+        values: &'a [Template],
+        file: Option<&'a Template>,
+    ) -> Result<Vec<u8>, ChainError> {
+        let mut lines = Self::load_lines(context, stack, file).await?;
+        let len = values.len() + lines.len();
+        if len == 0 {
+            return Err(ChainError::RotateEmpty);
+        }
+        let position = if context.http_engine.is_some() {
+            context.database.advance_chain_rotation(self.chain_id)
+        } else {
+            context.database.get_chain_rotation(self.chain_id)
+        }
+        .map_err(|error| ChainError::Database(error.into()))?;
+        let index = (position % len as u64) as usize;
+
+        if let Some(template) = values.get(index) {
+            let value = template
+                .render_chain_config(format!("values[{index}]"), context, stack)
+                .await?;
+            Ok(value.into_bytes())
+        } else {
+            Ok(lines.swap_remove(index - values.len()).into_bytes())
+        }
+    }
+
+    /// Pick a random value, according to the weight of each. File lines each
+    /// have a weight of 1. Only the picked value is rendered.
+    async fn render_random(
+        &self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+        values: &'a [RandomChoice],
+        file: Option<&'a Template>,
+        seed: Option<u64>,
+    ) -> Result<Vec<u8>, ChainError> {
+        let mut lines = Self::load_lines(context, stack, file).await?;
+        let weights = values
+            .iter()
+            .map(RandomChoice::weight)
+            .chain(iter::repeat(1).take(lines.len()));
+        // Fails if there are no values, or all weights are zero
+        let distribution =
+            WeightedIndex::new(weights).map_err(|_| ChainError::RandomEmpty)?;
+        let index = match seed {
+            Some(seed) => distribution.sample(&mut StdRng::seed_from_u64(seed)),
+            None => distribution.sample(&mut rand::thread_rng()),
+        };
+
+        if let Some(choice) = values.get(index) {
+            let value = choice
+                .value()
+                .render_chain_config(format!("values[{index}]"), context, stack)
+                .await?;
+            Ok(value.into_bytes())
+        } else {
+            Ok(lines.swap_remove(index - values.len()).into_bytes())
+        }
+    }
+
+    /// Load values for a list-based chain from a file, one per line. Blank
+    /// lines are skipped. If there's no file, there are no values
+    async fn load_lines(
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+        file: Option<&'a Template>,
+    ) -> Result<Vec<String>, ChainError> {
+        let Some(path) = file else {
+            return Ok(Vec::new());
+        };
+        let path: PathBuf = path
+            .render_chain_config("file", context, stack)
+            .await?
+            .into();
+        let path = expand_home(path).into_owned(); // Expand ~
+        let content = fs::read_to_string(&path).await.map_err(|error| {
+            ChainError::File {
+                path,
+                error: error.into(),
+            }
+        })?;
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(String::from)
+            .collect())
+    }
 }
 
-/// A value sourced from the process's environment
+/// A value sourced from the process's environment. If the collection
+/// configures the variable under `environment`, its options decide what
+/// happens when the variable is unset.
 struct EnvironmentTemplateSource<'a> {
     variable: &'a str,
 }
 
 #[async_trait]
 impl<'a> TemplateSource<'a> for EnvironmentTemplateSource<'a> {
+    async fn render(
+        &self,
+        context: &'a TemplateContext,
+        _: &mut RenderKeyStack,
+    ) -> TemplateResult {
+        let options = context.collection.environment.get(self.variable);
+        let value = match env::var(self.variable) {
+            Ok(value) => value,
+            // Unset variables are empty, unless configured otherwise
+            Err(_) => match options {
+                Some(EnvironmentVariable {
+                    default: Some(default),
+                    ..
+                }) => default.clone(),
+                Some(EnvironmentVariable {
+                    required: Some(message),
+                    ..
+                }) => {
+                    return Err(TemplateError::EnvironmentRequired {
+                        variable: self.variable.to_owned(),
+                        message: message.clone(),
+                    })
+                }
+                _ => String::new(),
+            },
+        };
+        Ok(RenderedChunk {
+            value: value.into_bytes().into(),
+            sensitive: options.is_some_and(|options| options.sensitive),
+        })
+    }
+}
+
+/// A column from a data file row. Row values are passed in as overrides, so
+/// if we got here, the current row (if any) doesn't have this column
+struct DataTemplateSource<'a> {
+    column: &'a str,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for DataTemplateSource<'a> {
     async fn render(
         &self,
         _: &'a TemplateContext,
         _: &mut RenderKeyStack,
     ) -> TemplateResult {
-        let value = load_environment_variable(self.variable).into_bytes();
+        Err(TemplateError::DataUnknown {
+            column: self.column.to_owned(),
+        })
+    }
+}
+
+/// The current loop item. Keys in a loop body are rendered from the item
+/// before getting here, so if we got here we're not in a loop
+struct ItemTemplateSource;
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for ItemTemplateSource {
+    async fn render(
+        &self,
+        _: &'a TemplateContext,
+        _: &mut RenderKeyStack,
+    ) -> TemplateResult {
+        Err(TemplateError::ItemOutsideLoop)
+    }
+}
+
+/// An expression outside of any loop. Expressions are normally evaluated
+/// directly by [render_scoped_key], so this is just for completeness
+struct ExpressionTemplateSource<'a> {
+    expression: &'a Expression,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for ExpressionTemplateSource<'a> {
+    async fn render(
+        &self,
+        context: &'a TemplateContext,
+        stack: &mut RenderKeyStack<'a>,
+    ) -> TemplateResult {
+        evaluate_expression(self.expression, None, context, stack)
+            .await
+            .map(EvaluatedExpression::into_chunk)
+    }
+}
+
+/// A value generated by a built-in function
+struct FunctionTemplateSource<'a> {
+    call: &'a FunctionCall,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for FunctionTemplateSource<'a> {
+    async fn render(
+        &self,
+        _: &'a TemplateContext,
+        _: &mut RenderKeyStack,
+    ) -> TemplateResult {
+        let value =
+            self.call.call().map_err(|error| TemplateError::Function {
+                function: self.call.name.clone(),
+                error,
+            })?;
         Ok(RenderedChunk {
             value: value.into(),
             sensitive: false,
@@ -799,6 +1804,46 @@ impl<'a> TemplateSource<'a> for EnvironmentTemplateSource<'a> {
     }
 }
 
+/// Randomly generated placeholder data
+struct FakeTemplateSource {
+    kind: FakeKind,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for FakeTemplateSource {
+    async fn render(
+        &self,
+        context: &'a TemplateContext,
+        _: &mut RenderKeyStack,
+    ) -> TemplateResult {
+        // An explicit seed takes precedence over the profile's
+        let seed = context.fake_seed.or_else(|| {
+            let profile_id = context.selected_profile.as_ref()?;
+            context.collection.profiles.get(profile_id)?.fake_seed
+        });
+        Ok(RenderedChunk {
+            value: self.kind.generate(seed).into_bytes().into(),
+            sensitive: false,
+        })
+    }
+}
+
+/// Acquire the process-wide lock for an exclusive chain
+async fn lock_chain(
+    collection_id: CollectionId,
+    chain_id: &ChainId,
+) -> OwnedMutexGuard<()> {
+    let lock = Arc::clone(
+        CHAIN_LOCKS
+            .lock()
+            .unwrap()
+            .entry((collection_id, chain_id.clone()))
+            .or_default(),
+    );
+    trace!(%chain_id, "Waiting for exclusive chain lock");
+    lock.lock_owned().await
+}
+
 /// State for a render group, which consists of one or more related renders
 /// (e.g. all the template renders for a single recipe). This state is stored in
 /// the template context.
@@ -808,6 +1853,123 @@ pub struct RenderGroupState {
     /// chain within a render group don't have to do the work multiple
     /// times.
     chain_results: FutureCache<ChainId, TemplateResult>,
+    /// Value of each profile field rendered in this group, before filters.
+    /// Used to snapshot the environment of a request
+    field_values: Mutex<IndexMap<String, RenderedChunk>>,
+    /// Recipe of the outermost request being built in this group. Requests
+    /// triggered by chains are built within the same group, so this tells
+    /// them what caused them.
+    recipe_id: OnceLock<RecipeId>,
+    /// Parameter values for the outermost recipe, which take precedence over
+    /// profile fields
+    parameters: OnceLock<IndexMap<String, String>>,
+    /// Git commit of the collection file. Getting this spawns git, so it's
+    /// only done once per group, even if many requests are built
+    git_revision: sync::OnceCell<Option<String>>,
+}
+
+impl RenderGroupState {
+    /// Placeholder for sensitive values in [Self::field_values]
+    const SENSITIVE_PLACEHOLDER: &'static str = "<sensitive>";
+
+    /// Store the value of a profile field
+    fn record_field(&self, field: &str, chunk: &RenderedChunk) {
+        self.field_values
+            .lock()
+            .unwrap()
+            .insert(field.to_owned(), chunk.clone());
+    }
+
+    /// Record the recipe being built in this group. Only the first call has
+    /// any effect, so requests triggered during the build don't replace the
+    /// outermost recipe.
+    pub(crate) fn set_recipe(&self, recipe_id: &RecipeId) {
+        let _ = self.recipe_id.set(recipe_id.clone());
+    }
+
+    /// Record the parameter values of the recipe being built. Like
+    /// [Self::set_recipe], only the first call has any effect.
+    pub(crate) fn set_parameters(&self, parameters: IndexMap<String, String>) {
+        let _ = self.parameters.set(parameters);
+    }
+
+    /// Get the current git commit of the repository containing the collection
+    /// file, if any. This is only looked up the first time it's needed.
+    pub(crate) async fn git_revision(
+        &self,
+        database: &CollectionDatabase,
+    ) -> Option<String> {
+        self.git_revision
+            .get_or_init(|| async {
+                let path = database.collection_path().ok()?;
+                git::revision(&path).await
+            })
+            .await
+            .clone()
+    }
+
+    /// Get the value of a recipe parameter, if it's been set
+    fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters.get()?.get(name).map(String::as_str)
+    }
+
+    /// Get the value of each profile field that's been rendered so far, in the
+    /// order they were first rendered. Sensitive values are masked, and binary
+    /// values are decoded lossily, because this is only meant for display.
+    pub fn field_values(&self) -> IndexMap<String, String> {
+        self.field_values
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(field, chunk)| {
+                let value = if chunk.sensitive {
+                    Self::SENSITIVE_PLACEHOLDER.to_owned()
+                } else {
+                    String::from_utf8_lossy(&chunk.value).into_owned()
+                };
+                (field.clone(), value)
+            })
+            .collect()
+    }
+}
+
+/// The current element of a loop block. This is only visible to keys written
+/// directly in the loop body, not to keys in nested templates such as profile
+/// fields.
+#[derive(Debug)]
+struct LoopItem {
+    value: serde_json::Value,
+    /// Elements are as sensitive as the array they came from
+    sensitive: bool,
+}
+
+impl LoopItem {
+    /// Render a `this` or `this.<field>` key. Return `None` for any other key,
+    /// which should be rendered normally
+    fn render(&self, key: &TemplateKey) -> Option<TemplateResult> {
+        let value = match key {
+            TemplateKey::Item => &self.value,
+            TemplateKey::ItemField(field) => {
+                let Some(value) = self.value.get(field.as_str()) else {
+                    return Some(Err(TemplateError::ItemFieldUnknown {
+                        field: field.to_string(),
+                    }));
+                };
+                value
+            }
+            _ => return None,
+        };
+        // Strings are inserted as-is, anything else as JSON
+        let value = match value {
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        Some(Ok(RenderedChunk {
+            value: value.into_bytes().into(),
+            sensitive: self.sensitive,
+        }))
+    }
 }
 
 /// Track the series of template keys that we've followed to get to the current
@@ -869,6 +2031,50 @@ impl ChainOutputTrim {
     }
 }
 
+/// Pull a single value out of a Kubernetes Secret/ConfigMap, as output by
+/// `kubectl get --output json`. Secret values (and ConfigMap binary values)
+/// are base64-encoded, so they'll be decoded here.
+fn extract_kubernetes_value(
+    kind: KubernetesResourceKind,
+    output: &[u8],
+    key: &str,
+) -> anyhow::Result<Vec<u8>> {
+    /// The subset of the resource that we care about. Secrets and ConfigMaps
+    /// have the same shape, but Secrets don't use `binaryData`
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Resource {
+        #[serde(default)]
+        data: HashMap<String, String>,
+        #[serde(default)]
+        binary_data: HashMap<String, String>,
+    }
+
+    let resource: Resource = serde_json::from_slice(output)
+        .context("Error parsing `kubectl` output")?;
+    let decode = |value: &str| {
+        BASE64_STANDARD
+            .decode(value)
+            .context("Error decoding base64 value")
+    };
+    match (kind, resource.data.get(key)) {
+        (KubernetesResourceKind::Secret, Some(value)) => decode(value),
+        (KubernetesResourceKind::ConfigMap, Some(value)) => {
+            Ok(value.clone().into_bytes())
+        }
+        (KubernetesResourceKind::Secret, None) => {
+            Err(anyhow!("Key not found in resource"))
+        }
+        (KubernetesResourceKind::ConfigMap, None) => {
+            let value = resource
+                .binary_data
+                .get(key)
+                .ok_or_else(|| anyhow!("Key not found in resource"))?;
+            decode(value)
+        }
+    }
+}
+
 /// Load variable from environment. If the variable is missing or otherwise
 /// inaccessible, return an empty string. This models standard shell behavior,
 /// so it should be intuitive for users.
@@ -880,3 +2086,90 @@ impl ChainOutputTrim {
 fn load_environment_variable(variable: &str) -> String {
     env::var(variable).unwrap_or_default()
 }
+
+/// Find a single variable in the contents of a dotenv file. Unlike
+/// [load_environment_variable], a missing variable is an error. If the
+/// variable is defined multiple times, the last definition wins, to match
+/// shell semantics.
+fn load_env_file_variable(
+    content: &[u8],
+    variable: &str,
+) -> anyhow::Result<String> {
+    let mut value = None;
+    for result in dotenvy::from_read_iter(content) {
+        let (key, v) = result.context("Error parsing file")?;
+        if key == variable {
+            value = Some(v);
+        }
+    }
+    value.ok_or_else(|| anyhow!("Variable is not defined"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_err;
+    use rstest::rstest;
+    use serde_json::json;
+
+    /// Test loading values from `kubectl` output
+    #[rstest]
+    #[case::secret(
+        KubernetesResourceKind::Secret,
+        json!({"data": {"password": "aHVudGVyMg=="}}),
+        "password",
+        "hunter2"
+    )]
+    #[case::config_map(
+        KubernetesResourceKind::ConfigMap,
+        json!({"data": {"host": "localhost"}}),
+        "host",
+        "localhost"
+    )]
+    #[case::config_map_binary(
+        KubernetesResourceKind::ConfigMap,
+        json!({"data": {}, "binaryData": {"cert": "Y2VydA=="}}),
+        "cert",
+        "cert"
+    )]
+    fn test_extract_kubernetes_value(
+        #[case] kind: KubernetesResourceKind,
+        #[case] output: serde_json::Value,
+        #[case] key: &str,
+        #[case] expected: &str,
+    ) {
+        let output = serde_json::to_vec(&output).unwrap();
+        assert_eq!(
+            extract_kubernetes_value(kind, &output, key).unwrap(),
+            expected.as_bytes()
+        );
+    }
+
+    /// Test errors loading values from `kubectl` output
+    #[rstest]
+    #[case::missing_key(
+        KubernetesResourceKind::Secret,
+        b"{\"data\": {}}",
+        "Key not found in resource"
+    )]
+    #[case::invalid_base64(
+        KubernetesResourceKind::Secret,
+        b"{\"data\": {\"key\": \"???\"}}",
+        "Error decoding base64 value"
+    )]
+    #[case::invalid_json(
+        KubernetesResourceKind::ConfigMap,
+        b"not json",
+        "Error parsing `kubectl` output"
+    )]
+    fn test_extract_kubernetes_value_error(
+        #[case] kind: KubernetesResourceKind,
+        #[case] output: &[u8],
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(
+            extract_kubernetes_value(kind, output, "key"),
+            expected_error
+        );
+    }
+}
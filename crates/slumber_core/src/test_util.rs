@@ -3,7 +3,6 @@
 use crate::{
     collection::{ChainSource, HasId},
     http::{HttpEngine, HttpEngineConfig},
-    template::{Prompt, Prompter, Select},
     util::{get_repo_root, ResultTraced},
 };
 use anyhow::Context;
@@ -11,13 +10,12 @@ use derive_more::Deref;
 use indexmap::IndexMap;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use rstest::fixture;
-use std::{
-    env, fs,
-    path::PathBuf,
-    sync::atomic::{AtomicUsize, Ordering},
-};
+use std::{env, fs, path::PathBuf};
 use uuid::Uuid;
 
+/// These used to live here, and are re-exported for existing tests
+pub use crate::template::{TestPrompter, TestSelectPrompter};
+
 /// Test-only trait to build a placeholder instance of a struct. This is similar
 /// to `Default`, but allows for useful placeholders that may not make sense in
 /// the context of the broader app. It also makes it possible to implement a
@@ -65,6 +63,7 @@ pub fn http_engine() -> HttpEngine {
         ignore_certificate_hosts: vec!["danger".to_owned()],
         ..Default::default()
     })
+    .unwrap()
 }
 
 /// Guard for a temporary directory. Create the directory on creation, delete
@@ -91,72 +90,6 @@ impl Drop for TempDir {
     }
 }
 
-/// Response to prompts with zero or more values in sequence
-#[derive(Debug, Default)]
-pub struct TestPrompter {
-    responses: Vec<String>,
-    /// Track where in the sequence of responses we are
-    index: AtomicUsize,
-}
-
-impl TestPrompter {
-    pub fn new<T: Into<String>>(
-        responses: impl IntoIterator<Item = T>,
-    ) -> Self {
-        Self {
-            responses: responses.into_iter().map(T::into).collect(),
-            index: 0.into(),
-        }
-    }
-}
-
-impl Prompter for TestPrompter {
-    fn prompt(&self, prompt: Prompt) {
-        // Grab the next value in the sequence. If we're all out, don't respond
-        let index = self.index.fetch_add(1, Ordering::Relaxed);
-        if let Some(value) = self.responses.get(index) {
-            prompt.channel.respond(value.clone())
-        } else if let Some(default) = prompt.default {
-            prompt.channel.respond(default);
-        }
-    }
-
-    fn select(&self, _select: Select) {
-        unimplemented!("TestPrompter does not support selects")
-    }
-}
-
-/// Response to selects with zero or more values in sequence
-#[derive(Debug, Default)]
-pub struct TestSelectPrompter {
-    /// Index within the contained select to grab response for
-    responses: Vec<usize>,
-    /// Track where in the sequence of responses we are
-    index: AtomicUsize,
-}
-
-impl TestSelectPrompter {
-    pub fn new<T: Into<usize>>(responses: impl IntoIterator<Item = T>) -> Self {
-        Self {
-            responses: responses.into_iter().map(T::into).collect(),
-            index: 0.into(),
-        }
-    }
-}
-
-impl Prompter for TestSelectPrompter {
-    fn prompt(&self, _prompt: Prompt) {
-        unimplemented!("TestSelectPrompter does not support prompts")
-    }
-
-    fn select(&self, mut select: Select) {
-        let index = self.index.fetch_add(1, Ordering::Relaxed);
-        if let Some(value) = self.responses.get(index) {
-            select.channel.respond(select.options.swap_remove(*value))
-        }
-    }
-}
-
 /// Construct a map of values keyed by their ID
 pub fn by_id<T: HasId>(
     values: impl IntoIterator<Item = T>,
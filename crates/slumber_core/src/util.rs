@@ -1,26 +1,40 @@
 //! Miscellaneous utility constants/types/functions
 
+pub mod aws;
+pub mod azure;
+pub mod consul;
+pub mod gcp;
+pub mod git;
 mod paths;
+pub mod secrets;
 
 pub use crate::util::paths::*;
 
 use crate::{http::RequestError, template::ChainError};
+use anyhow::{anyhow, Context};
 use chrono::{
     format::{DelayedFormat, StrftimeItems},
     DateTime, Duration, Local, Utc,
 };
 use derive_more::{DerefMut, Display};
+use hmac::{Hmac, Mac};
+use itertools::Itertools;
 use serde::de::DeserializeOwned;
+use sha2::Sha256;
 use std::{
     collections::{hash_map::Entry, HashMap},
     fmt::{self, Debug},
     hash::Hash,
     io::Read,
     ops::Deref,
+    process::Stdio,
     sync::Arc,
 };
-use tokio::sync::{Mutex, OwnedRwLockWriteGuard, RwLock};
-use tracing::error;
+use tokio::{
+    process::Command,
+    sync::{Mutex, OwnedRwLockWriteGuard, RwLock},
+};
+use tracing::{debug_span, error};
 
 const WEBSITE: &str = "https://slumber.lucaspickering.me";
 /// Link to the GitHub New Issue form
@@ -83,6 +97,43 @@ pub fn format_byte_size(size: usize) -> String {
     format!("{size:.1} {suffix}B")
 }
 
+/// Run an external CLI tool (`kubectl`, `aws`, etc.) to completion and return
+/// its stdout. A non-zero exit is treated as an error, which includes stderr
+/// because that's where these tools explain what went wrong.
+pub(crate) async fn run_cli(
+    program: &str,
+    args: &[String],
+) -> anyhow::Result<Vec<u8>> {
+    let _ = debug_span!("Executing CLI", program, ?args).entered();
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("Error executing `{program}`"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`{program}` exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Compute an HMAC-SHA256 of some data
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)
+        .expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Encode bytes as lowercase hex
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).join("")
+}
+
 /// Extension trait for [Result]
 pub trait ResultTraced<T, E>: Sized {
     /// If this is an error, trace it. Return the same result.
@@ -0,0 +1,63 @@
+//! Helpers for integrating with Azure
+
+use crate::util::run_cli;
+use anyhow::Context;
+use serde::Deserialize;
+
+/// Get a Microsoft Entra ID (Azure AD) access token for the signed-in `az`
+/// account. The CLI handles all the interactive/device code/managed identity
+/// flows via `az login`, so we don't have to. If no scope is given, the CLI
+/// defaults to Azure Resource Manager.
+pub async fn access_token(
+    scope: Option<&str>,
+    tenant: Option<&str>,
+) -> anyhow::Result<String> {
+    let mut args = ["account", "get-access-token", "--output", "json"]
+        .map(String::from)
+        .to_vec();
+    if let Some(scope) = scope {
+        args.extend(["--scope".into(), scope.into()]);
+    }
+    if let Some(tenant) = tenant {
+        args.extend(["--tenant".into(), tenant.into()]);
+    }
+    let output = run_cli("az", &args).await?;
+    parse_token(&output)
+}
+
+/// Parse the access token from the JSON output of `az account
+/// get-access-token`
+fn parse_token(output: &[u8]) -> anyhow::Result<String> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Output {
+        access_token: String,
+    }
+
+    let output: Output = serde_json::from_slice(output)
+        .context("Error parsing access token from `az` output")?;
+    Ok(output.access_token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_err;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_token() {
+        let output = json!({
+            "accessToken": "eyJ0eXAi",
+            "expiresOn": "2024-09-06 12:00:00.000000",
+            "subscription": "00000000-0000-0000-0000-000000000000",
+            "tenant": "00000000-0000-0000-0000-000000000000",
+            "tokenType": "Bearer",
+        });
+        assert_eq!(
+            parse_token(&serde_json::to_vec(&output).unwrap()).unwrap(),
+            "eyJ0eXAi"
+        );
+        assert_err!(parse_token(b"{}"), "Error parsing access token");
+    }
+}
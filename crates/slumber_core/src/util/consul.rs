@@ -0,0 +1,231 @@
+//! Service discovery via Consul
+
+use anyhow::{anyhow, Context};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::{
+    env,
+    net::{IpAddr, SocketAddr},
+};
+
+/// Consul agent to use if none is specified by the user or the environment
+const DEFAULT_ADDRESS: &str = "http://127.0.0.1:8500";
+/// Standard Consul env var for the agent address
+const ADDRESS_VARIABLE: &str = "CONSUL_HTTP_ADDR";
+/// Standard Consul env var for the ACL token
+const TOKEN_VARIABLE: &str = "CONSUL_HTTP_TOKEN";
+
+/// Resolve a service to the `host:port` of one of its healthy instances, using
+/// the Consul health API. If no agent address is given, use `CONSUL_HTTP_ADDR`,
+/// then fall back to the local agent. If multiple instances are healthy, the
+/// first one returned by Consul is used.
+pub async fn resolve(
+    service: &str,
+    tag: Option<&str>,
+    address: Option<&str>,
+) -> anyhow::Result<String> {
+    let address = match address {
+        Some(address) => address.to_owned(),
+        None => env::var(ADDRESS_VARIABLE)
+            .unwrap_or_else(|_| DEFAULT_ADDRESS.to_owned()),
+    };
+    // The env var is allowed to omit the scheme
+    let address = if address.contains("://") {
+        address
+    } else {
+        format!("http://{address}")
+    };
+    let url = health_url(&address, service)?;
+
+    let mut request = Client::new().get(url).query(&[("passing", "true")]);
+    if let Some(tag) = tag {
+        request = request.query(&[("tag", tag)]);
+    }
+    if let Ok(token) = env::var(TOKEN_VARIABLE) {
+        request = request.header("X-Consul-Token", token);
+    }
+    let body = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Error querying Consul at {address}"))?
+        .bytes()
+        .await?;
+    parse_address(&body)
+        .with_context(|| format!("Error resolving service `{service}`"))
+}
+
+/// Build the health API URL for a service. The service name is a single path
+/// segment, so it's percent-encoded
+fn health_url(address: &str, service: &str) -> anyhow::Result<Url> {
+    let mut url = Url::parse(address)
+        .with_context(|| format!("Invalid Consul address `{address}`"))?;
+    url.path_segments_mut()
+        .map_err(|()| anyhow!("Invalid Consul address `{address}`"))?
+        .pop_if_empty()
+        .extend(["v1", "health", "service", service]);
+    Ok(url)
+}
+
+/// Get the `host:port` of the first entry in a Consul health API response
+fn parse_address(body: &[u8]) -> anyhow::Result<String> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Entry {
+        node: Node,
+        service: Service,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Node {
+        address: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Service {
+        address: String,
+        port: u16,
+    }
+
+    let entries: Vec<Entry> = serde_json::from_slice(body)
+        .context("Error parsing Consul response")?;
+    let entry = entries
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("No healthy instances"))?;
+    // Service address is optional in Consul; if it's empty, the service is
+    // reachable at the node's address
+    let host = if entry.service.address.is_empty() {
+        entry.node.address
+    } else {
+        entry.service.address
+    };
+    // IPv6 addresses need brackets to be used in a URL
+    Ok(match host.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, entry.service.port).to_string(),
+        Err(_) => format!("{host}:{}", entry.service.port),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_err;
+    use rstest::rstest;
+    use serde_json::json;
+    use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+    #[rstest]
+    #[case::service_address("10.0.0.5", "10.0.0.5:8080")]
+    #[case::node_address("", "10.0.0.1:8080")]
+    #[case::ipv6("fd00::5", "[fd00::5]:8080")]
+    #[case::hostname("api.internal", "api.internal:8080")]
+    fn test_parse_address(
+        #[case] service_address: &str,
+        #[case] expected: &str,
+    ) {
+        let body = json!([
+            {
+                "Node": {"Node": "node-1", "Address": "10.0.0.1"},
+                "Service": {
+                    "ID": "api-1",
+                    "Service": "api",
+                    "Address": service_address,
+                    "Port": 8080,
+                },
+                "Checks": [],
+            },
+            {
+                "Node": {"Node": "node-2", "Address": "10.0.0.2"},
+                "Service": {
+                    "ID": "api-2",
+                    "Service": "api",
+                    "Address": "",
+                    "Port": 8081,
+                },
+                "Checks": [],
+            },
+        ]);
+        assert_eq!(
+            parse_address(&serde_json::to_vec(&body).unwrap()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_address_error() {
+        assert_err!(parse_address(b"[]"), "No healthy instances");
+        assert_err!(parse_address(b"{}"), "Error parsing Consul response");
+    }
+
+    #[rstest]
+    #[case::root(
+        "http://consul:8500",
+        "http://consul:8500/v1/health/service/api"
+    )]
+    #[case::trailing_slash(
+        "http://consul:8500/",
+        "http://consul:8500/v1/health/service/api"
+    )]
+    #[case::prefix(
+        "https://example.com/consul",
+        "https://example.com/consul/v1/health/service/api"
+    )]
+    #[case::ipv6(
+        "http://[::1]:8500",
+        "http://[::1]:8500/v1/health/service/api"
+    )]
+    fn test_health_url(#[case] address: &str, #[case] expected: &str) {
+        assert_eq!(health_url(address, "api").unwrap().as_str(), expected);
+    }
+
+    /// The service name can't escape its path segment
+    #[test]
+    fn test_health_url_encoding() {
+        assert_eq!(
+            health_url("http://consul:8500", "api/../v2?x#y")
+                .unwrap()
+                .as_str(),
+            "http://consul:8500/v1/health/service/api%2F..%2Fv2%3Fx%23y"
+        );
+        assert_err!(health_url("http://[::1", "api"), "Invalid Consul address");
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let server = MockServer::start().await;
+        let body = json!([{
+            "Node": {"Address": "10.0.0.1"},
+            "Service": {"Address": "", "Port": 3000},
+        }]);
+        Mock::given(matchers::method("GET"))
+            .and(matchers::path("/v1/health/service/api"))
+            .and(matchers::query_param("passing", "true"))
+            .and(matchers::query_param("tag", "v2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&body))
+            .mount(&server)
+            .await;
+        Mock::given(matchers::method("GET"))
+            .and(matchers::path("/v1/health/service/my%20api"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&body))
+            .mount(&server)
+            .await;
+
+        assert_eq!(
+            resolve("api", Some("v2"), Some(&server.uri()))
+                .await
+                .unwrap(),
+            "10.0.0.1:3000"
+        );
+        assert_eq!(
+            resolve("my api", None, Some(&server.uri())).await.unwrap(),
+            "10.0.0.1:3000"
+        );
+        assert_err!(
+            resolve("unknown", None, Some(&server.uri())).await,
+            "Error querying Consul"
+        );
+    }
+}
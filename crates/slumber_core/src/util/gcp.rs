@@ -0,0 +1,52 @@
+//! Helpers for integrating with Google Cloud
+
+use crate::util::run_cli;
+use anyhow::anyhow;
+
+/// Get an OAuth 2.0 access token for the user's Application Default
+/// Credentials (ADC). Like AWS, we defer to the `gcloud` CLI rather than
+/// pulling in an SDK, so whatever `gcloud auth application-default login` set
+/// up will be used.
+pub async fn access_token() -> anyhow::Result<String> {
+    let args = ["auth", "application-default", "print-access-token"]
+        .map(String::from)
+        .to_vec();
+    let output = run_cli("gcloud", &args).await?;
+    parse_token(&output)
+}
+
+/// Get an OpenID Connect ID token for the active `gcloud` account. This is
+/// what IAP and Cloud Run expect. Note that `gcloud` only supports a custom
+/// audience for service accounts.
+pub async fn id_token(audience: Option<&str>) -> anyhow::Result<String> {
+    let mut args = ["auth", "print-identity-token"].map(String::from).to_vec();
+    if let Some(audience) = audience {
+        args.push(format!("--audiences={audience}"));
+    }
+    let output = run_cli("gcloud", &args).await?;
+    parse_token(&output)
+}
+
+/// Parse a token from `gcloud` output, which is just the token followed by a
+/// newline
+fn parse_token(output: &[u8]) -> anyhow::Result<String> {
+    let token = std::str::from_utf8(output)?.trim();
+    if token.is_empty() {
+        Err(anyhow!("`gcloud` did not return a token"))
+    } else {
+        Ok(token.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_err;
+
+    #[test]
+    fn test_parse_token() {
+        assert_eq!(parse_token(b"ya29.token\n").unwrap(), "ya29.token");
+        assert_err!(parse_token(b"\n"), "did not return a token");
+        assert_err!(parse_token(b"\xc3\x28"), "invalid utf-8");
+    }
+}
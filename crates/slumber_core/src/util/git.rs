@@ -0,0 +1,146 @@
+//! Inspect the git repository that contains a collection file. Collections
+//! don't have to be in a repository, so most of these return `None` instead of
+//! an error when git isn't available.
+
+use crate::util::run_cli;
+use anyhow::anyhow;
+use std::path::Path;
+use tracing::debug;
+
+/// State of a collection file relative to git
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GitStatus {
+    /// File has been modified (or is untracked) since the last commit
+    pub uncommitted: bool,
+    /// The file at HEAD differs from the branch's upstream (e.g. `origin`),
+    /// because of unpushed or unpulled commits
+    pub upstream_differs: bool,
+}
+
+impl GitStatus {
+    /// Is the file in sync with both HEAD and upstream?
+    pub fn is_clean(&self) -> bool {
+        !self.uncommitted && !self.upstream_differs
+    }
+}
+
+/// Get the commit hash of HEAD in the repository containing the file. Return
+/// `None` if it's not in a repository or git isn't installed.
+pub async fn revision(path: &Path) -> Option<String> {
+    let output = git(path, &["rev-parse", "HEAD"])
+        .await
+        .inspect_err(|error| debug!(%error, "Error getting git revision"))
+        .ok()?;
+    Some(String::from_utf8_lossy(&output).trim().to_owned())
+}
+
+/// Check if the file has uncommitted changes or differs from upstream. Return
+/// `None` if it's not in a repository or git isn't installed.
+pub async fn status(path: &Path) -> Option<GitStatus> {
+    let file_name = file_name(path).ok()?;
+    let output = git(path, &["status", "--porcelain", "--", file_name])
+        .await
+        .inspect_err(|error| debug!(%error, "Error getting git status"))
+        .ok()?;
+    // An error here most likely means there's no upstream branch
+    let upstream_differs = git(
+        path,
+        &[
+            "diff",
+            "--name-only",
+            "HEAD",
+            "@{upstream}",
+            "--",
+            file_name,
+        ],
+    )
+    .await
+    .is_ok_and(|output| !output.is_empty());
+    Some(GitStatus {
+        uncommitted: !output.is_empty(),
+        upstream_differs,
+    })
+}
+
+/// Get the contents of the file as of the HEAD commit
+pub async fn show_head(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let file_name = file_name(path)?;
+    // ./ makes the path relative to the working directory, instead of the
+    // repository root
+    git(path, &["show", &format!("HEAD:./{file_name}")]).await
+}
+
+/// Run a git command in the directory containing the given file
+async fn git(path: &Path, args: &[&str]) -> anyhow::Result<Vec<u8>> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let args: Vec<String> = ["-C", &directory.to_string_lossy()]
+        .into_iter()
+        .chain(args.iter().copied())
+        .map(String::from)
+        .collect();
+    run_cli("git", &args).await
+}
+
+/// Get the name of a file as a string, for use in git arguments
+fn file_name(path: &Path) -> anyhow::Result<&str> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid collection path {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_dir, TempDir};
+    use rstest::rstest;
+    use std::fs;
+
+    /// Run git in a directory, for test setup
+    async fn setup_git(directory: &Path, args: &[&str]) {
+        git(&directory.join("x"), args).await.unwrap();
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_git(temp_dir: TempDir) {
+        let path = temp_dir.join("slumber.yml");
+        fs::write(&path, "requests: {}\n").unwrap();
+
+        // Git fails, e.g. because the directory doesn't exist
+        let missing = temp_dir.join("missing").join("slumber.yml");
+        assert_eq!(status(&missing).await, None);
+        assert_eq!(revision(&missing).await, None);
+
+        setup_git(&temp_dir, &["init", "--quiet"]).await;
+        setup_git(&temp_dir, &["add", "slumber.yml"]).await;
+        setup_git(
+            &temp_dir,
+            &[
+                "-c",
+                "user.name=Test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "--quiet",
+                "--message",
+                "Initial",
+            ],
+        )
+        .await;
+        assert_eq!(status(&path).await, Some(GitStatus::default()));
+        assert_eq!(revision(&path).await.unwrap().len(), 40);
+
+        fs::write(&path, "requests: {}\nprofiles: {}\n").unwrap();
+        assert_eq!(
+            status(&path).await,
+            Some(GitStatus {
+                uncommitted: true,
+                upstream_differs: false
+            })
+        );
+        assert_eq!(show_head(&path).await.unwrap(), b"requests: {}\n");
+    }
+}
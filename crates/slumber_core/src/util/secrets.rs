@@ -0,0 +1,68 @@
+//! Access to the OS secret store: macOS Keychain, Windows Credential Manager,
+//! or the Secret Service API (GNOME Keyring, KWallet, etc.) on Linux. Secrets
+//! are identified by a service name and a user name.
+
+use anyhow::{anyhow, Context};
+use keyring::Entry;
+use tokio::task;
+
+/// Load a secret from the secret store
+pub async fn get(service: &str, user: &str) -> anyhow::Result<String> {
+    let service = service.to_owned();
+    let user = user.to_owned();
+    blocking(move || {
+        let entry = Entry::new(&service, &user)?;
+        match entry.get_password() {
+            Ok(secret) => Ok(secret),
+            Err(keyring::Error::NoEntry) => Err(anyhow!(
+                "No secret for user `{user}` in service `{service}`"
+            )),
+            Err(error) => Err(error.into()),
+        }
+    })
+    .await
+}
+
+/// Create or update a secret in the secret store
+pub async fn set(
+    service: &str,
+    user: &str,
+    secret: &str,
+) -> anyhow::Result<()> {
+    let service = service.to_owned();
+    let user = user.to_owned();
+    let secret = secret.to_owned();
+    blocking(move || {
+        Entry::new(&service, &user)?.set_password(&secret)?;
+        Ok(())
+    })
+    .await
+}
+
+/// Delete a secret from the secret store
+pub async fn delete(service: &str, user: &str) -> anyhow::Result<()> {
+    let service = service.to_owned();
+    let user = user.to_owned();
+    blocking(move || {
+        let entry = Entry::new(&service, &user)?;
+        match entry.delete_credential() {
+            Ok(()) => Ok(()),
+            Err(keyring::Error::NoEntry) => Err(anyhow!(
+                "No secret for user `{user}` in service `{service}`"
+            )),
+            Err(error) => Err(error.into()),
+        }
+    })
+    .await
+}
+
+/// The keyring API is synchronous, and some platform backends block on IPC
+/// (or a user unlock prompt), so run it on the blocking thread pool
+async fn blocking<T: 'static + Send>(
+    f: impl 'static + FnOnce() -> anyhow::Result<T> + Send,
+) -> anyhow::Result<T> {
+    task::spawn_blocking(f)
+        .await
+        .context("Secret store task panicked")?
+        .context("Error accessing secret store")
+}
@@ -29,29 +29,30 @@ pub struct TuiContext {
 
 impl TuiContext {
     /// Initialize global context. Should be called only once, during startup.
-    pub fn init(config: Config) {
+    pub fn init(config: Config) -> anyhow::Result<()> {
         INSTANCE
-            .set(Self::new(config))
+            .set(Self::new(config)?)
             .expect("Global context is already initialized");
+        Ok(())
     }
 
     /// Initialize the global context for tests. This will use a default config,
     /// and if the context is already initialized, do nothing.
     #[cfg(test)]
     pub fn init_test() {
-        INSTANCE.get_or_init(|| Self::new(Config::default()));
+        INSTANCE.get_or_init(|| Self::new(Config::default()).unwrap());
     }
 
-    fn new(config: Config) -> Self {
+    fn new(config: Config) -> anyhow::Result<Self> {
         let styles = Styles::new(&config.theme);
         let input_engine = InputEngine::new(config.input_bindings.clone());
-        let http_engine = HttpEngine::new(&config.http);
-        Self {
+        let http_engine = HttpEngine::new(&config.http)?;
+        Ok(Self {
             config,
             styles,
             input_engine,
             http_engine,
-        }
+        })
     }
 
     /// Get a reference to the global context
@@ -157,8 +157,14 @@ impl Default for InputEngine {
                 Action::OpenHelp => KeyCode::Char('?').into(),
                 Action::Fullscreen => KeyCode::Char('f').into(),
                 Action::ReloadCollection => KeyCode::F(5).into(),
+                Action::SwitchCollection => KeyCode::Char('o').into(),
                 Action::History => KeyCode::Char('h').into(),
+                Action::InFlight => KeyCode::Char('i').into(),
+                Action::EnvironmentVariables => KeyCode::Char('v').into(),
                 Action::Search => KeyCode::Char('/').into(),
+                Action::ToggleBookmark => KeyCode::Char('b').into(),
+                Action::NextBookmark => KeyCode::Char(']').into(),
+                Action::PreviousBookmark => KeyCode::Char('[').into(),
                 Action::PreviousPane => KeyCode::BackTab.into(),
                 Action::NextPane => KeyCode::Tab.into(),
                 Action::Up => KeyCode::Up.into(),
@@ -170,6 +176,11 @@ impl Default for InputEngine {
                 Action::Home => KeyCode::Home.into(),
                 Action::End => KeyCode::End.into(),
                 Action::Submit => KeyCode::Enter.into(),
+                Action::SendWithNote => KeyCode::Char('n').into(),
+                Action::CancelRequest => KeyCombination {
+                    code: KeyCode::Char('k'),
+                    modifiers: KeyModifiers::CONTROL,
+                }.into(),
                 Action::Toggle => KeyCode::Char(' ').into(),
                 Action::Cancel => KeyCode::Esc.into(),
                 Action::Edit => KeyCode::Char('e').into(),
@@ -17,32 +17,44 @@ mod view;
 
 use crate::{
     context::TuiContext,
-    message::{Message, MessageSender, RequestConfig},
+    message::{
+        BatchCallback, CollectionEditFn, Message, MessageSender, RequestConfig,
+    },
     util::{
-        clear_event_buffer, get_editor_command, save_file, signals,
-        ResultReported,
+        clear_event_buffer, download_file, get_editor_command, save_file,
+        signals, ResultReported,
     },
     view::{PreviewPrompter, RequestState, View},
 };
 use anyhow::{anyhow, Context};
 use chrono::Utc;
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, Event, EventStream},
+    event::{
+        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste,
+        EnableMouseCapture, Event, EventStream,
+    },
     terminal::{EnterAlternateScreen, LeaveAlternateScreen},
 };
 use futures::StreamExt;
-use notify::{event::ModifyKind, RecursiveMode, Watcher};
+use notify::{event::ModifyKind, RecommendedWatcher, RecursiveMode, Watcher};
 use ratatui::{prelude::CrosstermBackend, Terminal};
 use slumber_config::{Action, Config};
 use slumber_core::{
-    collection::{Collection, CollectionFile, ProfileId},
+    collection::{
+        self, Collection, CollectionFile, ProfileId, Recipe, ReferenceLocation,
+        Workspace,
+    },
     db::{CollectionDatabase, Database},
-    http::RequestSeed,
+    http::{BuildOptions, ExchangeSummary, RequestId, RequestSeed},
     template::{Prompter, Template, TemplateChunk, TemplateContext},
+    util::git,
 };
 use std::{
+    collections::HashMap,
+    fs,
     future::Future,
     io::{self, Stdout},
+    mem,
     ops::Deref,
     path::{Path, PathBuf},
     sync::Arc,
@@ -54,8 +66,10 @@ use tokio::{
         mpsc::{self, UnboundedReceiver},
         Semaphore,
     },
+    task::AbortHandle,
     time,
 };
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, trace};
 
 /// Main controller struct for the TUI. The app uses a React-ish architecture
@@ -74,6 +88,15 @@ pub struct Tui {
     messages_tx: MessageSender,
     view: View,
     collection_file: CollectionFile,
+    /// Workspace that the collection belongs to, and the label of the
+    /// collection's member within it. `None` if not in a workspace
+    workspace: Option<WorkspaceSelection>,
+    /// Reloads the collection on changes. This stops when dropped, so it's
+    /// replaced when switching collections
+    collection_watcher: Option<RecommendedWatcher>,
+    /// Background task that periodically checks git status, which has to be
+    /// restarted when switching collections
+    git_status_task: Option<AbortHandle>,
     should_run: bool,
     /// Each active HTTP request should grab one permit from the semaphore. The
     /// primary purpose of this is to track whether any requests are in-flight.
@@ -81,9 +104,14 @@ pub struct Tui {
     /// This is probably overkill because we could just use an `AtomicU8`, but
     /// it simplifies the semantics of incrementing/decrementing correctly.
     http_semaphore: Arc<Semaphore>,
+    /// Cancellation handles for requests that are building or in flight.
+    /// Each is removed once its request finishes
+    cancel_tokens: HashMap<RequestId, CancellationToken>,
 }
 
 type Term = Terminal<CrosstermBackend<Stdout>>;
+/// A workspace, and the label of the selected collection's member within it
+type WorkspaceSelection = (Arc<Workspace>, String);
 
 impl Tui {
     /// Rough **maximum** time for each iteration of the main loop
@@ -91,24 +119,35 @@ impl Tui {
     /// Maximum number of concurrent HTTP requests. This limit is fairly
     /// arbitrary; in practice we don't ever expect to hit it.
     const MAX_HTTP_REQUESTS: usize = 100;
+    /// How often to check the collection file's git status
+    const GIT_STATUS_INTERVAL: Duration = Duration::from_secs(30);
 
     /// Start the TUI. Any errors that occur during startup will be panics,
-    /// because they prevent TUI execution.
-    pub async fn start(collection_path: Option<PathBuf>) -> anyhow::Result<()> {
+    /// because they prevent TUI execution. If `offline` is set, requests get
+    /// example responses instead of being sent, regardless of config.
+    /// `passphrase` is called before the terminal is taken over, if history is
+    /// encrypted with a passphrase.
+    pub async fn start(
+        collection_path: Option<PathBuf>,
+        offline: bool,
+        passphrase: impl FnOnce() -> anyhow::Result<String>,
+    ) -> anyhow::Result<()> {
         initialize_panic_handler();
-        let collection_path = CollectionFile::try_path(None, collection_path)?;
+        let (collection_path, workspace) = find_collection(collection_path)?;
 
         // ===== Initialize global state =====
         // This stuff only needs to be set up *once per session*
 
-        let config = Config::load()?;
+        let mut config = Config::load()?;
+        config.http.offline |= offline;
         // Create a message queue for handling async tasks
         let (messages_tx, messages_rx) = mpsc::unbounded_channel();
         let messages_tx = MessageSender::new(messages_tx);
         // Load a database for this particular collection
-        let database = Database::load()?.into_collection(&collection_path)?;
+        let database =
+            Database::load(passphrase)?.into_collection(&collection_path)?;
         // Initialize global view context
-        TuiContext::init(config);
+        TuiContext::init(config)?;
 
         // ===== Initialize collection & view =====
 
@@ -118,8 +157,11 @@ impl Tui {
             .await
             .reported(&messages_tx)
             .unwrap_or_else(|| CollectionFile::with_path(collection_path));
-        let view =
+        let mut view =
             View::new(&collection_file, database.clone(), messages_tx.clone());
+        if let Some((workspace, member)) = &workspace {
+            view.set_workspace(Arc::clone(workspace), member.clone());
+        }
 
         // The code to revert the terminal takeover is in `Tui::drop`, so we
         // shouldn't take over the terminal until right before creating the
@@ -133,10 +175,14 @@ impl Tui {
             messages_tx,
 
             collection_file,
+            workspace,
+            collection_watcher: None,
+            git_status_task: None,
             should_run: true,
 
             view,
             http_semaphore: Semaphore::new(Self::MAX_HTTP_REQUESTS).into(),
+            cancel_tokens: HashMap::new(),
         };
 
         app.run().await
@@ -148,8 +194,8 @@ impl Tui {
     async fn run(mut self) -> anyhow::Result<()> {
         // Spawn background tasks
         self.listen_for_signals();
-        // Hang onto this because it stops running when dropped
-        let _watcher = self.watch_collection()?;
+        self.watch_collection()?;
+        self.watch_git_status();
 
         let input_engine = &TuiContext::get().input_engine;
         // Stream of terminal input events
@@ -208,6 +254,10 @@ impl Tui {
             }
         }
 
+        // The HTTP engine lives in a static so it never gets dropped. Close
+        // its tunnels explicitly so we don't leave orphaned ssh processes
+        TuiContext::get().http_engine.close_tunnels().await;
+
         Ok(())
     }
 
@@ -217,18 +267,47 @@ impl Tui {
             Message::CollectionStartReload => {
                 let future = self.collection_file.reload();
                 let messages_tx = self.messages_tx();
-                self.spawn(async move {
-                    let collection = future.await?;
-                    messages_tx.send(Message::CollectionEndReload(collection));
-                    Ok(())
+                tokio::spawn(async move {
+                    let message = match future.await {
+                        Ok(collection) => {
+                            Message::CollectionEndReload(collection)
+                        }
+                        Err(error) => Message::CollectionReloadError(error),
+                    };
+                    messages_tx.send(message);
                 });
             }
             Message::CollectionEndReload(collection) => {
                 self.reload_collection(collection)
             }
+            Message::CollectionReloadError(error) => {
+                self.view.set_collection_error(error)
+            }
             Message::CollectionEdit => {
                 let path = self.collection_file.path().to_owned();
-                self.edit_file(&path)?
+                self.edit_file(&path, None)?
+            }
+            Message::CollectionEditItem(location) => {
+                self.edit_collection_item(&location)?
+            }
+            Message::CollectionWrite { edit, on_complete } => {
+                self.write_collection(edit)?;
+                on_complete(());
+            }
+            Message::CollectionStartSwitch(member) => {
+                self.start_switch_collection(member)?
+            }
+            Message::CollectionEndSwitch {
+                member,
+                collection_file,
+            } => self.end_switch_collection(member, collection_file)?,
+            Message::CollectionGitStatus(status) => {
+                self.view.set_git_status(status)
+            }
+
+            Message::ChainCacheClear => {
+                self.database.clear_chain_cache()?;
+                self.view.notify("Cleared chain cache");
             }
 
             Message::CopyRequestUrl(request_config) => {
@@ -244,9 +323,24 @@ impl Tui {
             Message::SaveFile { default_path, data } => {
                 self.spawn(save_file(self.messages_tx(), default_path, data));
             }
+            Message::DownloadFile {
+                default_path,
+                request,
+            } => {
+                // Replay with the request's profile, so the client
+                // certificate and tunnel match the original request
+                let template_context =
+                    self.template_context(request.profile_id.clone(), false)?;
+                self.spawn(download_file(
+                    self.messages_tx(),
+                    default_path,
+                    request,
+                    template_context,
+                ));
+            }
 
             Message::EditFile { path, on_complete } => {
-                self.edit_file(&path)?;
+                self.edit_file(&path, None)?;
                 on_complete(path);
             }
 
@@ -256,14 +350,36 @@ impl Tui {
             Message::HttpBeginRequest(request_config) => {
                 self.send_request(request_config)?
             }
+            Message::HttpBeginScratch { profile_id, recipe } => {
+                self.send_scratch(profile_id, *recipe)?
+            }
+            Message::HttpBeginBatch {
+                requests,
+                parallel,
+                on_complete,
+            } => self.send_batch(requests, parallel, on_complete)?,
             Message::HttpBuildError { error } => {
+                self.cancel_tokens.remove(&error.id);
                 self.view
                     .set_request_state(RequestState::BuildError { error });
             }
             Message::HttpLoading { request } => {
                 self.view.set_request_state(RequestState::loading(request))
             }
+            Message::HttpProgress { id, progress } => {
+                self.view.set_request_progress(id, progress)
+            }
+            Message::HttpCancel(id) => {
+                if let Some(token) = self.cancel_tokens.remove(&id) {
+                    token.cancel();
+                }
+            }
             Message::HttpComplete(result) => {
+                let id = match &result {
+                    Ok(exchange) => exchange.id,
+                    Err(error) => error.request.id,
+                };
+                self.cancel_tokens.remove(&id);
                 let state = match result {
                     Ok(exchange) => RequestState::response(exchange),
                     Err(error) => RequestState::RequestError { error },
@@ -344,9 +460,40 @@ impl Tui {
         });
     }
 
+    /// Spawn a task to periodically check the collection file's git status, so
+    /// we can show when it has uncommitted changes or differs from upstream.
+    /// Commits don't modify the collection file, so we can't rely on the file
+    /// watcher for this.
+    fn watch_git_status(&mut self) {
+        if let Some(task) = self.git_status_task.take() {
+            task.abort();
+        }
+        let path = self.collection_file.path().to_owned();
+        let messages_tx = self.messages_tx();
+        let task = tokio::spawn(async move {
+            let mut interval = time::interval(Self::GIT_STATUS_INTERVAL);
+            loop {
+                interval.tick().await;
+                let status = git::status(&path).await;
+                messages_tx.send(Message::CollectionGitStatus(status));
+            }
+        });
+        self.git_status_task = Some(task.abort_handle());
+    }
+
+    /// Check the collection file's git status once, e.g. after it changes
+    fn refresh_git_status(&self) {
+        let path = self.collection_file.path().to_owned();
+        let messages_tx = self.messages_tx();
+        tokio::spawn(async move {
+            let status = git::status(&path).await;
+            messages_tx.send(Message::CollectionGitStatus(status));
+        });
+    }
+
     /// Spawn a watcher to automatically reload the collection when the file
-    /// changes. Return the watcher because it stops when dropped.
-    fn watch_collection(&self) -> anyhow::Result<impl Watcher> {
+    /// changes. This replaces the previous watcher, if any.
+    fn watch_collection(&mut self) -> anyhow::Result<()> {
         // Spawn a watcher for the collection file
         let messages_tx = self.messages_tx();
         let f = move |result: notify::Result<_>| {
@@ -371,22 +518,113 @@ impl Tui {
         let mut watcher = notify::recommended_watcher(f)?;
         watcher
             .watch(self.collection_file.path(), RecursiveMode::NonRecursive)?;
+        // Changes to imported files affect the collection too
+        let imports = &self.collection_file.collection.imports;
+        for path in imports {
+            watcher.watch(path, RecursiveMode::NonRecursive)?;
+        }
         info!(
-            path = ?self.collection_file.path(), ?watcher,
+            path = ?self.collection_file.path(), ?imports, ?watcher,
             "Watching collection file for changes"
         );
-        Ok(watcher)
+        self.collection_watcher = Some(watcher);
+        Ok(())
     }
 
-    /// Reload state with a new collection
+    /// Apply an edit to the collection file's source text and write it back.
+    /// The file watcher will pick up the change and reload the collection.
+    fn write_collection(&self, edit: CollectionEditFn) -> anyhow::Result<()> {
+        let path = self.collection_file.path();
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Error reading {path:?}"))?;
+        let edited = edit(&source)?;
+        fs::write(path, edited)
+            .with_context(|| format!("Error writing {path:?}"))
+    }
+
+    /// Reload state with a new collection. Unlike switching collections, this
+    /// updates the existing view in place so the user doesn't lose their spot
     fn reload_collection(&mut self, collection: Collection) {
-        self.collection_file.collection = collection.into();
+        let imports_changed =
+            collection.imports != self.collection_file.collection.imports;
+        let previous = mem::replace(
+            &mut self.collection_file.collection,
+            collection.into(),
+        );
+        // The set of imported files may have changed, so we may need to watch
+        // different files
+        if imports_changed {
+            let messages_tx = self.messages_tx();
+            self.watch_collection().reported(&messages_tx);
+        }
+
+        self.view
+            .reload_collection(&previous, &self.collection_file);
+        // The file changed, so the status probably did too
+        self.refresh_git_status();
+    }
 
-        // Rebuild the whole view, because tons of things can change
+    /// Load a different collection from the workspace. The load is async, and
+    /// the collection is swapped in once it's done
+    fn start_switch_collection(&self, member: String) -> anyhow::Result<()> {
+        let (workspace, _) = self
+            .workspace
+            .as_ref()
+            .ok_or_else(|| anyhow!("Not in a workspace"))?;
+        let path = workspace.member_path(&member)?;
+        let messages_tx = self.messages_tx();
+        tokio::spawn(async move {
+            // Same as on startup: if the collection is invalid, show the error
+            // and keep watching the file so the user can fix it
+            let collection_file = CollectionFile::load(path.clone())
+                .await
+                .reported(&messages_tx)
+                .unwrap_or_else(|| CollectionFile::with_path(path));
+            messages_tx.send(Message::CollectionEndSwitch {
+                member,
+                collection_file,
+            });
+        });
+        Ok(())
+    }
+
+    /// Swap in a collection from another workspace member. This replaces
+    /// everything that's tied to the collection file: the database, view, and
+    /// background watchers.
+    fn end_switch_collection(
+        &mut self,
+        member: String,
+        collection_file: CollectionFile,
+    ) -> anyhow::Result<()> {
+        // Reuse the open database, so encrypted history isn't unlocked again
+        self.database = self
+            .database
+            .database()
+            .clone()
+            .into_collection(collection_file.path())?;
+        self.collection_file = collection_file;
+        if let Some((_, current)) = &mut self.workspace {
+            *current = member;
+        }
+        self.watch_collection()?;
+        self.watch_git_status();
+        self.rebuild_view();
+        Ok(())
+    }
+
+    /// Rebuild the whole view, because tons of things can change when the
+    /// collection does
+    fn rebuild_view(&mut self) {
         let database = self.database.clone();
         let messages_tx = self.messages_tx();
         let collection_file = &self.collection_file;
         self.view = View::new(collection_file, database, messages_tx);
+        if let Some((workspace, member)) = &self.workspace {
+            self.view
+                .set_workspace(Arc::clone(workspace), member.clone());
+        }
+        // The file changed, and the new view doesn't have the status anyway
+        self.refresh_git_status();
     }
 
     /// GOODBYE
@@ -401,11 +639,35 @@ impl Tui {
         Ok(())
     }
 
-    /// Open a file in the user's configured editor. **This will  block the main
-    /// thread**, because we assume we're opening a terminal editor and
-    /// therefore should yield the terminal to the editor.
-    fn edit_file(&mut self, path: &Path) -> anyhow::Result<()> {
-        let mut command = get_editor_command(path)?;
+    /// Open the collection in the user's editor, at the line where an item is
+    /// defined. If the item can't be found, just open the collection file.
+    /// Changes are picked up by the file watcher, so there's no need to
+    /// reload here.
+    fn edit_collection_item(
+        &mut self,
+        location: &ReferenceLocation,
+    ) -> anyhow::Result<()> {
+        let collection_path = self.collection_file.path();
+        let (path, line) = collection::find_item(
+            collection_path,
+            &self.collection_file.collection,
+            location,
+        )
+        .map(|(path, line)| (path, Some(line)))
+        .unwrap_or_else(|| (collection_path.to_owned(), None));
+        self.edit_file(&path, line)
+    }
+
+    /// Open a file in the user's configured editor, optionally at a particular
+    /// line. **This will  block the main thread**, because we assume we're
+    /// opening a terminal editor and therefore should yield the terminal to the
+    /// editor.
+    fn edit_file(
+        &mut self,
+        path: &Path,
+        line: Option<usize>,
+    ) -> anyhow::Result<()> {
+        let mut command = get_editor_command(path, line)?;
         let error_context =
             format!("Error spawning editor with command `{command:?}`");
 
@@ -445,6 +707,7 @@ impl Tui {
             profile_id,
             recipe_id,
             options,
+            ..
         }: RequestConfig,
     ) -> anyhow::Result<()> {
         let seed = RequestSeed::new(recipe_id, options);
@@ -469,6 +732,7 @@ impl Tui {
             profile_id,
             recipe_id,
             options,
+            ..
         }: RequestConfig,
     ) -> anyhow::Result<()> {
         let seed = RequestSeed::new(recipe_id, options);
@@ -497,6 +761,7 @@ impl Tui {
             profile_id,
             recipe_id,
             options,
+            ..
         }: RequestConfig,
     ) -> anyhow::Result<()> {
         let seed = RequestSeed::new(recipe_id, options);
@@ -517,22 +782,98 @@ impl Tui {
 
     /// Launch an HTTP request in a separate task
     fn send_request(
+        &mut self,
+        request_config: RequestConfig,
+    ) -> anyhow::Result<()> {
+        // We can't use self.spawn here because HTTP errors are handled
+        // differently from all other error types
+        tokio::spawn(self.request_future(request_config, None, |_| {})?);
+        Ok(())
+    }
+
+    /// Launch a one-off HTTP request from a recipe that isn't in the
+    /// collection
+    fn send_scratch(
+        &mut self,
+        profile_id: Option<ProfileId>,
+        recipe: Recipe,
+    ) -> anyhow::Result<()> {
+        let request_config = RequestConfig {
+            profile_id,
+            recipe_id: recipe.id.clone(),
+            options: BuildOptions::default(),
+            note: None,
+        };
+        tokio::spawn(self.request_future(
+            request_config,
+            Some(recipe),
+            |_| {},
+        )?);
+        Ok(())
+    }
+
+    /// Launch several HTTP requests. In parallel mode, each request gets its
+    /// own task. Otherwise they're sent one at a time, in order, from a
+    /// single task. Each request is sent even if a previous one failed.
+    fn send_batch(
+        &mut self,
+        requests: Vec<RequestConfig>,
+        parallel: bool,
+        on_complete: BatchCallback,
+    ) -> anyhow::Result<()> {
+        let futures = requests
+            .into_iter()
+            .enumerate()
+            .map(|(i, request_config)| {
+                let on_complete = Arc::clone(&on_complete);
+                self.request_future(request_config, None, move |outcome| {
+                    on_complete(i, outcome)
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if parallel {
+            for future in futures {
+                tokio::spawn(future);
+            }
+        } else {
+            tokio::spawn(async move {
+                for future in futures {
+                    future.await;
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Mark a request as building, and return a future that builds and sends
+    /// it. Progress is reported to the view as the future runs.
+    /// `on_complete` is called with a summary of the response (or an error
+    /// message) just before the final state is reported, so it's visible in
+    /// the next draw.
+    fn request_future(
         &mut self,
         RequestConfig {
             profile_id,
             recipe_id,
             options,
+            note,
         }: RequestConfig,
-    ) -> anyhow::Result<()> {
-        // Launch the request in a separate task so it doesn't block.
-        // These clones are all cheap.
-
+        recipe: Option<Recipe>,
+        on_complete: impl 'static + Send + FnOnce(Result<ExchangeSummary, String>),
+    ) -> anyhow::Result<impl Future<Output = ()>> {
+        // These clones are all cheap
         let template_context =
             self.template_context(profile_id.clone(), false)?;
         let messages_tx = self.messages_tx();
 
         // Mark request state as building
-        let initialized = RequestSeed::new(recipe_id.clone(), options);
+        let initialized = RequestSeed {
+            note,
+            recipe: recipe.map(Arc::new),
+            ..RequestSeed::new(recipe_id.clone(), options)
+        };
+        self.cancel_tokens
+            .insert(initialized.id, initialized.cancel.clone());
         self.view.set_request_state(RequestState::Building {
             id: initialized.id,
             start_time: Utc::now(),
@@ -540,23 +881,28 @@ impl Tui {
             recipe_id,
         });
 
-        // We can't use self.spawn here because HTTP errors are handled
-        // differently from all other error types
         let database = self.database.clone();
         let semaphore = Arc::clone(&self.http_semaphore);
-        tokio::spawn(async move {
+        Ok(async move {
             // Track this request so the main loop knows something is in flight
-            let permit =
+            let _permit =
                 semaphore.acquire().await.expect("HTTP semaphore closed");
             // Build the request
-            let ticket = TuiContext::get()
+            let ticket = match TuiContext::get()
                 .http_engine
                 .build(initialized, &template_context)
                 .await
-                .map_err(|error| {
-                    // Report the error, but don't actually return anything
+            {
+                Ok(ticket) => ticket,
+                Err(error) => {
+                    // Record the failure so it shows up in history. Error here
+                    // should *not* mask the original error
+                    let _ = database.insert_build_failure(&error);
+                    on_complete(Err(format!("{:#}", error.error)));
                     messages_tx.send(Message::HttpBuildError { error });
-                })?;
+                    return;
+                }
+            };
 
             // Report liftoff
             messages_tx.send(Message::HttpLoading {
@@ -564,16 +910,18 @@ impl Tui {
             });
 
             // Send the request and report the result to the main thread
-            let result = ticket.send(&database).await;
+            let id = ticket.record().id;
+            let result = ticket
+                .send_with_progress(&database, |progress| {
+                    messages_tx.send(Message::HttpProgress { id, progress })
+                })
+                .await;
+            on_complete(match &result {
+                Ok(exchange) => Ok(ExchangeSummary::from(exchange)),
+                Err(error) => Err(format!("{:#}", error.error)),
+            });
             messages_tx.send(Message::HttpComplete(result));
-
-            drop(permit);
-            // By returning an empty result, we can use `?` to break out early.
-            // `return` and `break` don't work in an async block :/
-            Ok::<(), ()>(())
-        });
-
-        Ok(())
+        })
     }
 
     /// Spawn a task to render a template, storing the result in a pre-defined
@@ -635,6 +983,8 @@ impl Tui {
             database: self.database.clone(),
             overrides: Default::default(),
             prompter,
+            fake_seed: None,
+            offline: context.config.http.offline,
             state: Default::default(),
         })
     }
@@ -649,6 +999,35 @@ impl Drop for Tui {
     }
 }
 
+/// Find the collection file to load on startup. If the path wasn't given
+/// explicitly and there's a workspace file in the current or an ancestor
+/// directory, use the detected collection if it's a member of the workspace,
+/// otherwise the first member. Return the workspace and selected member label
+/// too, if any.
+fn find_collection(
+    collection_path: Option<PathBuf>,
+) -> anyhow::Result<(PathBuf, Option<WorkspaceSelection>)> {
+    let workspace = if collection_path.is_none() {
+        Workspace::detect(None)?
+            .filter(|workspace| !workspace.members.is_empty())
+    } else {
+        None
+    };
+    let Some(workspace) = workspace else {
+        return Ok((CollectionFile::try_path(None, collection_path)?, None));
+    };
+
+    let member = CollectionFile::try_path(None, None)
+        .ok()
+        .and_then(|path| workspace.member_for_path(&path))
+        .or_else(|| workspace.members.values().next())
+        .expect("Workspace has at least one member")
+        .id
+        .clone();
+    let path = workspace.member_path(&member)?;
+    Ok((path, Some((workspace.into(), member))))
+}
+
 /// Restore terminal state during a panic
 fn initialize_panic_handler() {
     let original_hook = std::panic::take_hook();
@@ -662,7 +1041,12 @@ fn initialize_panic_handler() {
 fn initialize_terminal() -> anyhow::Result<Term> {
     crossterm::terminal::enable_raw_mode()?;
     let mut stdout = io::stdout();
-    crossterm::execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    crossterm::execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
     let backend = CrosstermBackend::new(stdout);
     Ok(Terminal::new(backend)?)
 }
@@ -674,7 +1058,8 @@ fn restore_terminal() -> anyhow::Result<()> {
     crossterm::execute!(
         io::stdout(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     Ok(())
 }
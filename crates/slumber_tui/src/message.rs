@@ -6,12 +6,16 @@ use anyhow::Context;
 use derive_more::From;
 use slumber_config::Action;
 use slumber_core::{
-    collection::{Collection, ProfileId, RecipeId},
+    collection::{
+        Collection, CollectionFile, ProfileId, Recipe, RecipeId,
+        ReferenceLocation,
+    },
     http::{
-        BuildOptions, Exchange, RequestBuildError, RequestError, RequestRecord,
+        BuildOptions, DownloadProgress, Exchange, ExchangeSummary,
+        RequestBuildError, RequestError, RequestId, RequestRecord,
     },
     template::{Prompt, Prompter, Select, Template, TemplateChunk},
-    util::ResultTraced,
+    util::{git::GitStatus, ResultTraced},
 };
 use std::{path::PathBuf, sync::Arc};
 use tokio::sync::mpsc::UnboundedSender;
@@ -61,8 +65,38 @@ pub enum Message {
     CollectionStartReload,
     /// Store a reloaded collection value in state
     CollectionEndReload(Collection),
+    /// Reloading the collection failed. The previous collection stays loaded
+    /// until the file is fixed
+    CollectionReloadError(anyhow::Error),
     /// Open the collection in the user's editor
     CollectionEdit,
+    /// Open the collection in the user's editor, at the definition of a
+    /// particular item. If the item is defined in an imported file, that file
+    /// is opened instead.
+    CollectionEditItem(ReferenceLocation),
+    /// Make a small edit to the collection file's source text and write it
+    /// back, e.g. to add a profile
+    CollectionWrite {
+        #[debug(skip)]
+        edit: CollectionEditFn,
+        /// Function to call once the file is written. The collection won't
+        /// have been reloaded yet
+        #[debug(skip)]
+        on_complete: Callback<()>,
+    },
+    /// Switch to another collection in the workspace, by member label
+    CollectionStartSwitch(String),
+    /// Replace the current collection with one loaded from a workspace member
+    CollectionEndSwitch {
+        member: String,
+        collection_file: CollectionFile,
+    },
+    /// Store the git status of the collection file, for display. `None` if
+    /// it isn't in a git repository
+    CollectionGitStatus(Option<GitStatus>),
+
+    /// Delete all persisted chain values for the collection
+    ChainCacheClear,
 
     /// Show a yes/no confirmation to the user. Use the included channel to
     /// return the value.
@@ -91,14 +125,37 @@ pub enum Message {
 
     /// Launch an HTTP request from the given recipe/profile.
     HttpBeginRequest(RequestConfig),
+    /// Launch a one-off HTTP request from a recipe that isn't in the
+    /// collection
+    HttpBeginScratch {
+        profile_id: Option<ProfileId>,
+        recipe: Box<Recipe>,
+    },
     /// Request failed to build
     HttpBuildError { error: RequestBuildError },
     /// We launched the HTTP request
     HttpLoading { request: Arc<RequestRecord> },
+    /// Part of the response body has downloaded
+    HttpProgress {
+        id: RequestId,
+        progress: DownloadProgress,
+    },
+    /// The user wants to cancel an in-flight request. If it's already done,
+    /// this does nothing
+    HttpCancel(RequestId),
     /// The HTTP request either succeeded or failed. We don't need to store the
     /// recipe ID here because it's in the inner container already. Combining
     /// these two cases saves a bit of boilerplate.
     HttpComplete(Result<Exchange, RequestError>),
+    /// Send several requests, either one at a time (in order) or all at once.
+    /// Each request goes through the usual life cycle, and additionally
+    /// `on_complete` is called with its index in the batch when it finishes.
+    HttpBeginBatch {
+        requests: Vec<RequestConfig>,
+        parallel: bool,
+        #[debug(skip)]
+        on_complete: BatchCallback,
+    },
 
     /// User input from the terminal
     Input {
@@ -129,6 +186,15 @@ pub enum Message {
         /// Data to save
         data: Vec<u8>,
     },
+    /// Send a recorded request again, and stream its response body to a
+    /// file. Used to get the full content of a truncated body
+    DownloadFile {
+        /// A suggestion for the file name. User will have the opportunity to
+        /// change this
+        default_path: Option<String>,
+        /// Request to replay
+        request: Arc<RequestRecord>,
+    },
 
     /// Render a template string, to be previewed in the UI. Ideally this could
     /// be launched directly by the component that needs it, but only the
@@ -151,6 +217,16 @@ pub enum Message {
 /// A static callback included in a message
 pub type Callback<T> = Box<dyn 'static + Send + Sync + FnOnce(T)>;
 
+/// Called as each request in a batch finishes, with the request's index in
+/// the batch and its outcome: either a summary of the response, or an error
+/// message if the request couldn't be built or sent
+pub type BatchCallback =
+    Arc<dyn 'static + Send + Sync + Fn(usize, Result<ExchangeSummary, String>)>;
+
+/// An edit to the source text of the collection file, returning the new text
+pub type CollectionEditFn =
+    Box<dyn 'static + Send + Sync + FnOnce(&str) -> anyhow::Result<String>>;
+
 /// Configuration that defines how to render a request
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq))]
@@ -158,4 +234,6 @@ pub struct RequestConfig {
     pub profile_id: Option<ProfileId>,
     pub recipe_id: RecipeId,
     pub options: BuildOptions,
+    /// User's note to attach to the request. Only used when sending
+    pub note: Option<String>,
 }
@@ -8,17 +8,24 @@ use crossterm::event;
 use editor_command::EditorBuilder;
 use futures::{future, FutureExt};
 use slumber_core::{
-    template::Prompt,
-    util::{doc_link, expand_home, ResultTraced},
+    http::RequestRecord,
+    template::{Prompt, TemplateContext},
+    util::{doc_link, expand_home, format_byte_size, ResultTraced},
 };
 use std::{
+    ffi::OsStr,
     io,
     ops::Deref,
     path::{Path, PathBuf},
     process::Command,
+    sync::Arc,
     time::Duration,
 };
-use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::oneshot};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::oneshot,
+};
 use tracing::{debug, error, info, warn};
 
 /// Extension trait for [Result]
@@ -109,55 +116,11 @@ pub async fn save_file(
     default_path: Option<String>,
     data: Vec<u8>,
 ) -> anyhow::Result<()> {
-    // If the user closed the prompt, just exit
-    let Some(path) =
-        prompt(&messages_tx, "Enter a path for the file", default_path).await
+    let Some((path, result)) = open_file(&messages_tx, default_path).await
     else {
         return Ok(());
     };
 
-    // If the user input nothing, assume they just want to exit
-    if path.is_empty() {
-        return Ok(());
-    }
-
-    let path = expand_home(PathBuf::from(path)); // Expand ~
-    let result = {
-        // Attempt to open the file *if it doesn't exist already*
-        let result = OpenOptions::new()
-            .create_new(true)
-            .write(true)
-            .open(&path)
-            .await;
-
-        match result {
-            Ok(file) => Ok(file),
-            // If the file already exists, ask for confirmation to overwrite
-            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
-                warn!(?path, "File already exists, asking to overwrite");
-
-                // Hi, sorry, follow up question. Are you sure?
-                if confirm(
-                    &messages_tx,
-                    format!("`{}` already exists, overwrite?", path.display()),
-                )
-                .await
-                {
-                    // REALLY attempt to open the file
-                    OpenOptions::new()
-                        .create(true)
-                        .write(true)
-                        .truncate(true)
-                        .open(&path)
-                        .await
-                } else {
-                    return Ok(());
-                }
-            }
-            Err(error) => Err(error),
-        }
-    };
-
     debug!(?path, bytes = data.len(), "Writing to file");
     async {
         let mut file = result?;
@@ -175,22 +138,148 @@ pub async fn save_file(
     Ok(())
 }
 
+/// Send a recorded request again and stream its response body to a file. The
+/// file is chosen the same way as [save_file]. Used to get the full body of a
+/// response that was truncated.
+pub async fn download_file(
+    messages_tx: MessageSender,
+    default_path: Option<String>,
+    request: Arc<RequestRecord>,
+    template_context: TemplateContext,
+) -> anyhow::Result<()> {
+    let Some((path, result)) = open_file(&messages_tx, default_path).await
+    else {
+        return Ok(());
+    };
+
+    debug!(?path, request_id = %request.id, "Downloading to file");
+    let size = async {
+        let mut file = result?;
+        TuiContext::get()
+            .http_engine
+            .download(&request, &template_context, &mut file, |progress| {
+                messages_tx
+                    .send(Message::Notify(format!("Downloading {progress}")));
+            })
+            .await
+    }
+    .await
+    .with_context(|| format!("Error downloading to file `{}`", path.display()))
+    .traced()?;
+
+    messages_tx.send(Message::Notify(format!(
+        "Saved {} to {}",
+        format_byte_size(size as usize),
+        path.display()
+    )));
+    Ok(())
+}
+
+/// Ask the user for a path, then open a *new* file there. If the file already
+/// exists, ask for confirmation before overwriting it. Return `None` if the
+/// user backed out at any point.
+async fn open_file(
+    messages_tx: &MessageSender,
+    default_path: Option<String>,
+) -> Option<(PathBuf, io::Result<File>)> {
+    // If the user closed the prompt, just exit
+    let path =
+        prompt(messages_tx, "Enter a path for the file", default_path).await?;
+
+    // If the user input nothing, assume they just want to exit
+    if path.is_empty() {
+        return None;
+    }
+
+    let path = expand_home(PathBuf::from(path)).into_owned(); // Expand ~
+
+    // Attempt to open the file *if it doesn't exist already*
+    let result = OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(&path)
+        .await;
+
+    let result = match result {
+        Ok(file) => Ok(file),
+        // If the file already exists, ask for confirmation to overwrite
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+            warn!(?path, "File already exists, asking to overwrite");
+
+            // Hi, sorry, follow up question. Are you sure?
+            if confirm(
+                messages_tx,
+                format!("`{}` already exists, overwrite?", path.display()),
+            )
+            .await
+            {
+                // REALLY attempt to open the file
+                OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(&path)
+                    .await
+            } else {
+                return None;
+            }
+        }
+        Err(error) => Err(error),
+    };
+    Some((path, result))
+}
+
 /// Get a command to open the given file in the user's configured editor. Return
 /// an error if the user has no editor configured
-pub fn get_editor_command(file: &Path) -> anyhow::Result<Command> {
-    EditorBuilder::new()
+pub fn get_editor_command(
+    file: &Path,
+    line: Option<usize>,
+) -> anyhow::Result<Command> {
+    let mut command = EditorBuilder::new()
         // Config field takes priority over environment variables
         .source(TuiContext::get().config.editor.as_deref())
         .environment()
         .source(Some("vim"))
-        .path(file)
         .build()
         .with_context(|| {
             format!(
                 "Error opening editor; see {}",
                 doc_link("api/configuration/editor"),
             )
-        })
+        })?;
+    add_file_args(&mut command, file, line);
+    Ok(command)
+}
+
+/// Add the file to open to an editor command. There's no standard way to open
+/// a file at a particular line, so the line is only passed to editors that we
+/// know the syntax for. Others just open the file.
+fn add_file_args(command: &mut Command, file: &Path, line: Option<usize>) {
+    let program = Path::new(command.get_program())
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default()
+        .to_owned();
+    match (line, program.as_str()) {
+        (
+            Some(line),
+            "vi" | "vim" | "nvim" | "nano" | "emacs" | "emacsclient" | "micro"
+            | "kak",
+        ) => {
+            command.arg(format!("+{line}")).arg(file);
+        }
+        (Some(line), "code" | "code-insiders" | "codium") => {
+            command
+                .arg("--goto")
+                .arg(format!("{}:{line}", file.display()));
+        }
+        (Some(line), "hx" | "helix" | "subl" | "zed") => {
+            command.arg(format!("{}:{line}", file.display()));
+        }
+        _ => {
+            command.arg(file);
+        }
+    }
 }
 
 /// Ask the user for some text input and wait for a response. Return `None` if
@@ -234,6 +323,24 @@ mod tests {
     };
     use tokio::fs;
 
+    /// Line numbers are passed in whatever format the editor understands
+    #[rstest]
+    #[case::vim("vim", Some(3), &["+3", "slumber.yml"])]
+    #[case::full_path("/usr/bin/nvim", Some(3), &["+3", "slumber.yml"])]
+    #[case::vscode("code", Some(3), &["--goto", "slumber.yml:3"])]
+    #[case::helix("hx", Some(3), &["slumber.yml:3"])]
+    #[case::unknown("ed", Some(3), &["slumber.yml"])]
+    #[case::no_line("vim", None, &["slumber.yml"])]
+    fn test_add_file_args(
+        #[case] program: &str,
+        #[case] line: Option<usize>,
+        #[case] expected: &[&str],
+    ) {
+        let mut command = Command::new(program);
+        add_file_args(&mut command, Path::new("slumber.yml"), line);
+        assert_eq!(command.get_args().collect::<Vec<_>>(), expected);
+    }
+
     /// Test various cases of save_file
     #[rstest]
     #[case::new_file(false, false)]
@@ -30,10 +30,12 @@ use anyhow::anyhow;
 use ratatui::Frame;
 use slumber_config::Action;
 use slumber_core::{
-    collection::{CollectionFile, ProfileId},
+    collection::{Collection, CollectionFile, ProfileId, Workspace},
     db::CollectionDatabase,
+    http::{DownloadProgress, RequestId},
+    util::git::GitStatus,
 };
-use std::{fmt::Debug, sync::Arc};
+use std::{fmt::Debug, ops::Deref, sync::Arc};
 use tracing::{debug, trace_span, warn};
 
 /// Primary entrypoint for the view. This contains the main draw functions, as
@@ -100,6 +102,40 @@ impl View {
         }
     }
 
+    /// Swap in a reloaded collection. Rather than rebuilding the entire view,
+    /// only the panes affected by changes are rebuilt. Modals (including
+    /// in-progress prompts), requests, and pane selections are retained.
+    pub fn reload_collection(
+        &mut self,
+        previous: &Collection,
+        collection_file: &CollectionFile,
+    ) {
+        let collection = &collection_file.collection;
+        ViewContext::set_collection(Arc::clone(collection));
+        let changes = previous
+            .diff(collection)
+            .inspect_err(|error| {
+                warn!(
+                    error = error.deref(),
+                    "Error comparing collections, rebuilding all panes"
+                )
+            })
+            .ok();
+        self.root
+            .data_mut()
+            .reload_collection(collection, changes.as_deref());
+        self.notify(format!(
+            "Reloaded collection from {}",
+            collection_file.path().to_string_lossy()
+        ));
+    }
+
+    /// Show an error from reloading the collection. It's shown until the
+    /// next successful reload.
+    pub fn set_collection_error(&mut self, error: anyhow::Error) {
+        self.root.data_mut().set_collection_error(error);
+    }
+
     /// ID of the selected profile. `None` iff the list is empty
     pub fn selected_profile_id(&self) -> Option<&ProfileId> {
         self.root.data().selected_profile_id()
@@ -113,6 +149,26 @@ impl View {
         ViewContext::push_event(Event::HttpSetState(state));
     }
 
+    /// Queue an event to update download progress of an in-flight request
+    pub fn set_request_progress(
+        &mut self,
+        id: RequestId,
+        progress: DownloadProgress,
+    ) {
+        ViewContext::push_event(Event::HttpSetProgress { id, progress });
+    }
+
+    /// Update the collection file's git status, to show in the footer
+    pub fn set_git_status(&mut self, status: Option<GitStatus>) {
+        self.root.data_mut().set_git_status(status);
+    }
+
+    /// Set the workspace that the collection belongs to, so the user can
+    /// switch to other members
+    pub fn set_workspace(&mut self, workspace: Arc<Workspace>, member: String) {
+        self.root.data_mut().set_workspace(workspace, member);
+    }
+
     /// Queue an event to open a new modal. The input can be anything that
     /// converts to modal content
     pub fn open_modal(&mut self, modal: impl IntoModal + 'static) {
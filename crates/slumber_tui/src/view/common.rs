@@ -3,6 +3,7 @@
 
 pub mod actions;
 pub mod button;
+pub mod form;
 pub mod header_table;
 pub mod list;
 pub mod modal;
@@ -0,0 +1,247 @@
+//! A form of labeled text boxes, for modals that collect several values at once
+
+use crate::{
+    context::TuiContext,
+    message::Message,
+    view::{
+        common::text_box::TextBox,
+        component::Component,
+        draw::{Draw, DrawMetadata},
+        event::{Child, Event, EventHandler, Update},
+        ViewContext,
+    },
+};
+use ratatui::{
+    layout::{Constraint, Layout},
+    text::Span,
+    Frame,
+};
+use slumber_config::Action;
+
+/// A column of labeled text boxes, one field per row. One field has focus at
+/// a time, and up/down moves focus between the enabled fields. Submitting any
+/// field emits [SubmitForm] for the parent to handle, and cancelling any field
+/// closes the modal. The field type `K` identifies each field.
+#[derive(Debug)]
+pub struct Form<K> {
+    fields: Vec<FormField<K>>,
+    /// Index of the field with focus
+    selected: usize,
+    /// Width of the label column, to fit the longest label
+    label_width: u16,
+}
+
+/// A single row in a [Form]
+#[derive(Debug)]
+pub struct FormField<K> {
+    key: K,
+    label: String,
+    text_box: Component<TextBox>,
+    /// Disabled fields are shown but can't be selected
+    enabled: bool,
+}
+
+/// Local event emitted when the user submits any field in a [Form]
+#[derive(Debug)]
+pub struct SubmitForm;
+
+impl<K> Form<K> {
+    pub fn new(fields: Vec<FormField<K>>) -> Self {
+        let label_width = fields
+            .iter()
+            .map(|field| field.label.len() as u16)
+            .max()
+            .unwrap_or_default()
+            + 2;
+        Self {
+            selected: fields
+                .iter()
+                .position(|field| field.enabled)
+                .unwrap_or_default(),
+            fields,
+            label_width,
+        }
+    }
+
+    /// Number of rows needed to draw the form
+    pub fn height(&self) -> u16 {
+        self.fields.len() as u16
+    }
+
+    /// Get every field in the form, in order
+    pub fn fields(&self) -> &[FormField<K>] {
+        &self.fields
+    }
+
+    /// Get the current text of a field, with surrounding whitespace removed.
+    /// `None` if the field isn't in the form or is disabled
+    pub fn text(&self, key: &K) -> Option<&str>
+    where
+        K: PartialEq,
+    {
+        self.fields
+            .iter()
+            .find(|field| &field.key == key && field.enabled)
+            .map(|field| field.text_box.data().text().trim())
+    }
+
+    /// Replace the text of a field
+    #[cfg(test)]
+    pub fn set_text(&mut self, key: &K, text: &str)
+    where
+        K: PartialEq,
+    {
+        let field = self
+            .fields
+            .iter_mut()
+            .find(|field| &field.key == key)
+            .unwrap();
+        field.text_box.data_mut().set_text(text.into());
+        // Setting text submits the text box, which submits the form. Drop
+        // that so each test can submit explicitly
+        while ViewContext::pop_event().is_some() {}
+    }
+
+    /// Move focus to the next/previous enabled field, wrapping around
+    fn cycle(&mut self, forward: bool) {
+        let len = self.fields.len();
+        for i in 1..len {
+            let index = if forward {
+                (self.selected + i) % len
+            } else {
+                (self.selected + len - i) % len
+            };
+            if self.fields[index].enabled {
+                self.selected = index;
+                return;
+            }
+        }
+    }
+}
+
+impl<K> FormField<K> {
+    pub fn new(key: K, label: impl Into<String>, text_box: TextBox) -> Self {
+        let text_box = text_box
+            .on_submit(|| ViewContext::push_event(Event::new_local(SubmitForm)))
+            .on_cancel(|| {
+                ViewContext::push_event(Event::CloseModal { submitted: false })
+            });
+        Self {
+            key,
+            label: label.into(),
+            text_box: text_box.into(),
+            enabled: true,
+        }
+    }
+
+    /// Set whether the field can be selected. Disabled fields are still shown
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Finish submitting a form, once its contents have been validated. If valid,
+/// close the modal and return the value so the modal can use it when it
+/// closes. Otherwise, show the error and leave the modal open.
+pub fn submit<T>(result: Result<T, String>) -> Option<T> {
+    match result {
+        Ok(value) => {
+            ViewContext::push_event(Event::CloseModal { submitted: true });
+            Some(value)
+        }
+        Err(error) => {
+            ViewContext::send_message(Message::Notify(error));
+            None
+        }
+    }
+}
+
+impl<K> EventHandler for Form<K> {
+    fn update(&mut self, event: Event) -> Update {
+        if let Some(Action::Up | Action::PreviousPane) = event.action() {
+            self.cycle(false);
+        } else if let Some(Action::Down | Action::NextPane) = event.action() {
+            self.cycle(true);
+        } else {
+            return Update::Propagate(event);
+        }
+        Update::Consumed
+    }
+
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        // Only the focused field gets input
+        self.fields
+            .get_mut(self.selected)
+            .map(|field| field.text_box.to_child_mut())
+            .into_iter()
+            .collect()
+    }
+}
+
+impl<K> Draw for Form<K> {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let styles = &TuiContext::get().styles;
+        let rows =
+            Layout::vertical(self.fields.iter().map(|_| Constraint::Length(1)))
+                .split(metadata.area());
+        for (i, (field, area)) in
+            self.fields.iter().zip(rows.iter()).enumerate()
+        {
+            let [label_area, value_area] = Layout::horizontal([
+                Constraint::Length(self.label_width),
+                Constraint::Min(0),
+            ])
+            .areas(*area);
+            let has_focus = i == self.selected;
+            let label_style = if has_focus {
+                styles.text.highlight
+            } else {
+                Default::default()
+            };
+            frame.render_widget(
+                Span::styled(field.label.as_str(), label_style),
+                label_area,
+            );
+            field.text_box.draw(frame, (), value_area, has_focus);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{harness, TestHarness};
+    use rstest::rstest;
+
+    /// Focus skips disabled fields and wraps around
+    #[rstest]
+    fn test_cycle(_harness: TestHarness) {
+        let mut form = Form::new(vec![
+            FormField::new(1, "One", TextBox::default()).enabled(false),
+            FormField::new(2, "Two", TextBox::default()),
+            FormField::new(3, "Three", TextBox::default()).enabled(false),
+            FormField::new(4, "Four", TextBox::default()),
+        ]);
+        // Initial focus is on the first enabled field
+        assert_eq!(form.selected, 1);
+        form.cycle(true);
+        assert_eq!(form.selected, 3);
+        form.cycle(true);
+        assert_eq!(form.selected, 1);
+        form.cycle(false);
+        assert_eq!(form.selected, 3);
+
+        assert_eq!(form.text(&3), None);
+        form.set_text(&4, " four ");
+        assert_eq!(form.text(&4), Some("four"));
+    }
+}
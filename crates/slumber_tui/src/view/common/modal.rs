@@ -169,10 +169,12 @@ impl Draw for ModalQueue {
             // The child gave us the content dimensions, we need to add one cell
             // of buffer for the border
             let mut area = centered_rect(width, height, metadata.area());
-            area.x -= 1;
-            area.y -= 1;
+            area.x = area.x.saturating_sub(1);
+            area.y = area.y.saturating_sub(1);
             area.width += 2;
             area.height += 2;
+            // Don't let the border push the modal off screen
+            let area = area.intersection(metadata.area());
 
             let block = Block::default()
                 .title(modal.data().title())
@@ -9,17 +9,62 @@ use crate::{
 use persisted::PersistedContainer;
 use ratatui::Frame;
 use slumber_config::Action;
-use std::fmt::Debug;
+use std::{cell::RefCell, fmt::Debug};
 
 /// Multi-tab display. Generic parameter defines the available tabs.
 #[derive(Debug, Default)]
 pub struct Tabs<T: FixedSelect> {
     tabs: FixedSelectState<T, usize>,
+    /// Tabs that don't apply in the current context. These aren't shown, and
+    /// are skipped when cycling. This is set during draw, because that's when
+    /// the parent knows what's available.
+    hidden: RefCell<Vec<T>>,
 }
 
 impl<T: FixedSelect> Tabs<T> {
+    /// Get the selected tab. If the selected tab is hidden, fall back to the
+    /// nearest visible tab. The hidden tab remains selected, so it will be
+    /// shown again once it's available.
     pub fn selected(&self) -> T {
-        self.tabs.selected()
+        let selected = self.tabs.selected();
+        let hidden = self.hidden.borrow();
+        if !hidden.contains(&selected) {
+            return selected;
+        }
+        let tabs: Vec<T> = T::iter().collect();
+        let index = self.tabs.selected_index();
+        (1..tabs.len())
+            .flat_map(|distance| {
+                [index.checked_add(distance), index.checked_sub(distance)]
+            })
+            .flatten()
+            .filter_map(|i| tabs.get(i))
+            .find(|tab| !hidden.contains(tab))
+            .copied()
+            .unwrap_or(selected)
+    }
+
+    /// Hide some tabs, replacing any previously hidden. At least one tab must
+    /// remain visible.
+    pub fn set_hidden(&self, hidden: &[T]) {
+        *self.hidden.borrow_mut() = hidden.to_owned();
+    }
+
+    /// Cycle to the previous/next visible tab
+    fn cycle(&mut self, forward: bool) {
+        // Start from the displayed tab, in case the selected one is hidden
+        let current = self.selected();
+        self.tabs.select(&current);
+        for _ in 0..T::COUNT {
+            if forward {
+                self.tabs.next();
+            } else {
+                self.tabs.previous();
+            }
+            if !self.hidden.borrow().contains(&self.tabs.selected()) {
+                break;
+            }
+        }
     }
 }
 
@@ -29,8 +74,8 @@ impl<T: FixedSelect> EventHandler for Tabs<T> {
             return Update::Propagate(event);
         };
         match action {
-            Action::Left => self.tabs.previous(),
-            Action::Right => self.tabs.next(),
+            Action::Left => self.cycle(false),
+            Action::Right => self.cycle(true),
             _ => return Update::Propagate(event),
         }
         Update::Consumed
@@ -39,9 +84,18 @@ impl<T: FixedSelect> EventHandler for Tabs<T> {
 
 impl<T: FixedSelect> Draw for Tabs<T> {
     fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let hidden = self.hidden.borrow();
+        let visible: Vec<T> =
+            T::iter().filter(|tab| !hidden.contains(tab)).collect();
+        let selected = self.selected();
         frame.render_widget(
-            ratatui::widgets::Tabs::new(T::iter().map(|e| e.to_string()))
-                .select(self.tabs.selected_index())
+            ratatui::widgets::Tabs::new(visible.iter().map(T::to_string))
+                .select(
+                    visible
+                        .iter()
+                        .position(|tab| *tab == selected)
+                        .unwrap_or_default(),
+                )
                 .highlight_style(TuiContext::get().styles.tab.highlight),
             metadata.area(),
         )
@@ -36,6 +36,9 @@ pub struct TemplatePreview {
     /// contention on the mutex because of the single write, and reads being
     /// single-threaded.
     text: Arc<Mutex<Text<'static>>>,
+    /// The unrendered template, kept so callers can switch between it and the
+    /// preview without rendering again
+    raw: Text<'static>,
 }
 
 impl TemplatePreview {
@@ -46,7 +49,7 @@ impl TemplatePreview {
     /// unrendered and rendered content.
     pub fn new(template: Template, content_type: Option<ContentType>) -> Self {
         // Calculate raw text
-        let text = highlight::highlight_if(
+        let raw = highlight::highlight_if(
             content_type,
             // We have to clone the template to detach the lifetime. We're
             // choosing to pay one upfront cost here so we don't have to
@@ -55,7 +58,7 @@ impl TemplatePreview {
             // self-referential
             template.display().into_owned().into(),
         );
-        let text = Arc::new(Mutex::new(text));
+        let text = Arc::new(Mutex::new(raw.clone()));
 
         // Trigger a task to render the preview and write the answer back into
         // the mutex
@@ -71,7 +74,7 @@ impl TemplatePreview {
             });
         }
 
-        Self { text }
+        Self { text, raw }
     }
 
     /// Generate text from the rendered template, and replace the text in the
@@ -88,6 +91,12 @@ impl TemplatePreview {
             .expect("Template preview text lock is poisoned") = text;
     }
 
+    /// Get the unrendered template text. Unlike [Self::text], this never
+    /// changes
+    pub fn raw(&self) -> &Text<'static> {
+        &self.raw
+    }
+
     pub fn text(&self) -> impl '_ + Deref<Target = Text<'static>> {
         self.text
             .lock()
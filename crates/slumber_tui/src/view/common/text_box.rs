@@ -8,6 +8,7 @@ use crate::{
     },
 };
 use crossterm::event::{KeyCode, KeyModifiers};
+use itertools::Itertools;
 use persisted::PersistedContainer;
 use ratatui::{
     layout::Rect,
@@ -175,6 +176,15 @@ impl EventHandler for TextBox {
                 // Propagate any keystrokes we don't handle (e.g. f keys)
                 _ => return Update::Propagate(event),
             },
+            Event::Input {
+                event: crossterm::event::Event::Paste(text),
+                ..
+            } => {
+                // The box is a single line, so flatten multi-line pastes
+                for c in text.lines().join(" ").chars() {
+                    self.state.insert(c);
+                }
+            }
             _ => return Update::Propagate(event),
         }
         Update::Consumed
@@ -449,6 +459,22 @@ mod tests {
         assert_eq!(cancel_count, 1);
     }
 
+    /// Pasted text is inserted at the cursor, with line breaks flattened
+    #[rstest]
+    fn test_paste(
+        _harness: TestHarness,
+        #[with(10, 1)] terminal: TestTerminal,
+    ) {
+        let mut component =
+            TestComponent::new(&terminal, TextBox::default(), ());
+        component.send_text("ad").assert_empty();
+        component.send_key(KeyCode::Left).assert_empty();
+        component
+            .send_input(crossterm::event::Event::Paste("b\r\nc".into()))
+            .assert_empty();
+        assert_state(&component.data().state, "ab cd", 4);
+    }
+
     /// Test text navigation and deleting. [TextState] has its own tests so
     /// we're mostly just testing that keys are mapped correctly
     #[rstest]
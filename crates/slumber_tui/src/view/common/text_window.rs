@@ -45,6 +45,8 @@ pub struct TextWindowProps<'a> {
     /// Text to render. We take a reference because this component tends to
     /// contain a lot of text, and we don't want to force a clone on render
     pub text: &'a Text<'a>,
+    /// Lines (starting at 0) to highlight in the gutter, e.g. bookmarks
+    pub marks: &'a [usize],
     /// Extra text to render below the text window
     pub footer: Option<Text<'a>>,
     pub margins: ScrollbarMargins,
@@ -70,6 +72,11 @@ impl Default for ScrollbarMargins {
 }
 
 impl TextWindow {
+    /// Get the index of the first visible line
+    pub fn first_line(&self) -> usize {
+        self.offset_y.get()
+    }
+
     /// Get the final line that we can't scroll past. This will be the first
     /// line of the last page of text
     fn max_scroll_line(&self) -> usize {
@@ -97,7 +104,7 @@ impl TextWindow {
 
     /// Scroll to a specific line number. The target line will end up as close
     /// to the top of the page as possible
-    fn scroll_to(&mut self, line: usize) {
+    pub fn scroll_to(&mut self, line: usize) {
         *self.offset_y.get_mut() = cmp::min(line, self.max_scroll_line());
     }
 
@@ -222,7 +229,14 @@ impl<'a> Draw<TextWindowProps<'a>> for TextWindow {
         frame.render_widget(
             Paragraph::new(
                 (first_line..=last_line)
-                    .map(|n| n.to_string().into())
+                    .map(|n| {
+                        let style = if props.marks.contains(&(n - 1)) {
+                            styles.text_window.mark
+                        } else {
+                            Style::default()
+                        };
+                        Line::styled(n.to_string(), style)
+                    })
                     .collect::<Vec<Line>>(),
             )
             .alignment(Alignment::Right)
@@ -304,6 +318,7 @@ mod tests {
             TextWindow::default(),
             TextWindowProps {
                 text: &text,
+                marks: &[],
                 // Don't overflow the frame
                 margins: ScrollbarMargins {
                     right: 0,
@@ -387,6 +402,7 @@ mod tests {
             TextWindow::default(),
             TextWindowProps {
                 text: &text,
+                marks: &[],
                 // Don't overflow the frame
                 margins: ScrollbarMargins {
                     right: 0,
@@ -413,6 +429,7 @@ mod tests {
             TextWindow::default(),
             TextWindowProps {
                 text: &text,
+                marks: &[],
                 // Don't overflow the frame
                 margins: ScrollbarMargins {
                     right: 0,
@@ -442,6 +459,7 @@ mod tests {
             TextWindow::default(),
             TextWindowProps {
                 text: &text,
+                marks: &[],
                 // Don't overflow the frame
                 margins: ScrollbarMargins {
                     right: 0,
@@ -460,6 +478,7 @@ mod tests {
         let text = ["1 less long line", "2", "3", "4"].join("\n").into();
         component.set_props(TextWindowProps {
             text: &text,
+            marks: &[],
             margins: ScrollbarMargins {
                 right: 0,
                 bottom: 0,
@@ -484,6 +503,7 @@ mod tests {
             TextWindow::default(),
             TextWindowProps {
                 text: &text,
+                marks: &[],
                 // Don't overflow the frame
                 margins: ScrollbarMargins {
                     right: 0,
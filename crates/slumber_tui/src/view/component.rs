@@ -1,16 +1,38 @@
+mod assertion;
+mod baseline;
+mod batch;
+mod captured_requests;
+mod certificate;
+mod connection_error;
+mod contract;
+mod decode;
+mod diagnostics;
+mod environment;
 mod exchange_pane;
+mod graphql;
 mod help;
 mod history;
+mod history_search;
+mod in_flight;
 mod internal;
 mod misc;
+mod parameter_form;
 mod primary;
+mod profile_compare;
 mod profile_select;
 mod queryable_body;
+mod recipe_diff;
+mod recipe_form;
 mod recipe_list;
 mod recipe_pane;
+mod references;
 mod request_view;
+mod response_diff;
 mod response_view;
 mod root;
+mod status_code;
+mod timing;
+mod workspace;
 
 pub use internal::Component;
 pub use root::Root;
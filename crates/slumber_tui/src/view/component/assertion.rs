@@ -0,0 +1,82 @@
+//! Check responses against the assertions declared on their recipe
+
+use crate::{
+    message::Message,
+    util::ResultReported,
+    view::{
+        component::{baseline::load_exchange, misc::ErrorListModal},
+        ViewContext,
+    },
+};
+use slumber_core::http::{assertion::AssertionFailure, Exchange, RequestId};
+
+/// Outcome of checking an exchange against its recipe's assertions
+#[derive(Debug, Default)]
+pub struct AssertionResults {
+    /// Total number of assertions checked
+    pub total: usize,
+    pub failures: Vec<AssertionFailure>,
+}
+
+/// Check an exchange against the assertions of its recipe. The recipe is
+/// pulled from the current collection, so changes to the assertions apply to
+/// old responses too. If the recipe no longer exists, there's nothing to check.
+pub fn run_assertions(exchange: &Exchange) -> AssertionResults {
+    let collection = ViewContext::collection();
+    let Some(recipe) =
+        collection.recipes.get_recipe(&exchange.request.recipe_id)
+    else {
+        return AssertionResults::default();
+    };
+    AssertionResults {
+        total: recipe.assertions.len(),
+        failures: recipe
+            .assertions
+            .iter()
+            .filter_map(|assertion| assertion.check(exchange).err())
+            .collect(),
+    }
+}
+
+/// Check a request from history against its recipe's assertions. If any fail,
+/// show them in a modal.
+pub fn check_assertions(request_id: RequestId) {
+    let Some(exchange) = ViewContext::with_database(|database| {
+        load_exchange(database, request_id)
+    })
+    .reported(&ViewContext::messages_tx()) else {
+        return;
+    };
+    let results = run_assertions(&exchange);
+
+    if results.total == 0 {
+        ViewContext::send_message(Message::Notify(
+            "Recipe has no assertions; set the `assertions` field in your \
+            collection"
+                .into(),
+        ));
+    } else if results.failures.is_empty() {
+        ViewContext::send_message(Message::Notify(results.title()));
+    } else {
+        ViewContext::open_modal(ErrorListModal::new(
+            results.title(),
+            results.failures,
+        ));
+    }
+}
+
+impl AssertionResults {
+    /// Summarize the results, e.g. "2/3 Assertions Failed"
+    pub fn title(&self) -> String {
+        let noun = if self.total == 1 {
+            "Assertion"
+        } else {
+            "Assertions"
+        };
+        if self.failures.is_empty() {
+            format!("{} {noun} Passed", self.total)
+        } else {
+            format!("{}/{} {noun} Failed", self.failures.len(), self.total)
+        }
+    }
+}
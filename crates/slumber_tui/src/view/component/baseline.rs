@@ -0,0 +1,85 @@
+//! Pin responses as a recipe's baseline, and compare new responses to it
+
+use crate::{
+    message::Message,
+    util::ResultReported,
+    view::{component::misc::ErrorListModal, ViewContext},
+};
+use anyhow::anyhow;
+use slumber_core::{
+    db::CollectionDatabase,
+    http::{baseline, Exchange, RequestId},
+};
+
+/// Pin a request from history as the baseline for its profile+recipe
+pub fn pin_baseline(request_id: RequestId) {
+    let result = ViewContext::with_database(|database| {
+        let exchange = load_exchange(database, request_id)?;
+        database.set_baseline(&exchange)
+    });
+    if result.reported(&ViewContext::messages_tx()).is_some() {
+        ViewContext::send_message(Message::Notify(
+            "Pinned response as baseline".into(),
+        ));
+    }
+}
+
+/// Compare a request to the baseline for its profile+recipe. If there are any
+/// differences, show them in a modal.
+pub fn compare_baseline(request_id: RequestId) {
+    let result = ViewContext::with_database(|database| {
+        let exchange = load_exchange(database, request_id)?;
+        let request = &exchange.request;
+        let baseline = database
+            .get_baseline(request.profile_id.as_ref(), &request.recipe_id)?
+            .ok_or_else(|| {
+                anyhow!(
+                    "No baseline pinned for recipe `{}`. Use \
+                    \"Pin as Baseline\" on a response to set one",
+                    request.recipe_id
+                )
+            })?;
+        Ok::<_, anyhow::Error>((exchange, baseline))
+    });
+    let Some((exchange, baseline)) =
+        result.reported(&ViewContext::messages_tx())
+    else {
+        return;
+    };
+
+    // If the recipe was removed from the collection, we can still compare,
+    // we just don't know what to ignore
+    let collection = ViewContext::collection();
+    let ignore = collection
+        .recipes
+        .get_recipe(&exchange.request.recipe_id)
+        .map(|recipe| recipe.baseline_ignore.as_slice())
+        .unwrap_or_default();
+    let differences =
+        baseline::compare(&baseline.response, &exchange.response, ignore);
+    if differences.is_empty() {
+        ViewContext::send_message(Message::Notify(
+            "Response matches baseline".into(),
+        ));
+    } else {
+        let noun = if differences.len() == 1 {
+            "Difference"
+        } else {
+            "Differences"
+        };
+        ViewContext::open_modal(ErrorListModal::new(
+            format!("{} {noun} from Baseline", differences.len()),
+            differences,
+        ));
+    }
+}
+
+/// Load a request from history, failing if it's missing
+pub fn load_exchange(
+    database: &CollectionDatabase,
+    request_id: RequestId,
+) -> anyhow::Result<Exchange> {
+    database
+        .get_request(request_id)?
+        .ok_or_else(|| anyhow!("Request `{request_id}` not found"))
+}
@@ -0,0 +1,257 @@
+//! Send several recipes at once, and summarize the results
+
+use crate::{
+    context::TuiContext,
+    message::{BatchCallback, Message, RequestConfig},
+    view::{
+        common::modal::Modal,
+        draw::{Draw, DrawMetadata, Generate},
+        event::EventHandler,
+        ViewContext,
+    },
+};
+use ratatui::{
+    layout::Constraint,
+    text::{Line, Span, Text},
+    Frame,
+};
+use slumber_config::Action;
+use slumber_core::{
+    collection::{ProfileId, RecipeId},
+    http::{BuildOptions, ExchangeSummary},
+};
+use std::sync::{Arc, Mutex, MutexGuard};
+use unicode_width::UnicodeWidthStr;
+
+/// Send each of the given recipes, and open a modal showing the status of
+/// each as it completes. Requests are sent one at a time, in order, unless
+/// `parallel` is enabled.
+pub fn send_batch(
+    recipe_ids: Vec<RecipeId>,
+    profile_id: Option<ProfileId>,
+    parallel: bool,
+) {
+    if recipe_ids.is_empty() {
+        let binding = TuiContext::get()
+            .input_engine
+            .binding_display(Action::Toggle);
+        ViewContext::send_message(Message::Notify(format!(
+            "No recipes marked; press {binding} in the recipe list to mark one"
+        )));
+        return;
+    }
+
+    let modal = BatchModal::new(&recipe_ids, parallel);
+    ViewContext::send_message(Message::HttpBeginBatch {
+        requests: recipe_ids
+            .into_iter()
+            .map(|recipe_id| RequestConfig {
+                profile_id: profile_id.clone(),
+                recipe_id,
+                options: BuildOptions::default(),
+                note: None,
+            })
+            .collect(),
+        parallel,
+        on_complete: modal.outcomes.on_complete(),
+    });
+    ViewContext::open_modal(modal);
+}
+
+/// Status of each request in a batch, updated as they complete
+#[derive(Debug)]
+pub struct BatchModal {
+    /// Display name of each recipe in the batch
+    names: Vec<String>,
+    parallel: bool,
+    /// Outcome of each request, parallel to `names`
+    outcomes: BatchOutcomes,
+}
+
+/// Result of a single request in a batch
+pub type Outcome = Result<ExchangeSummary, String>;
+
+/// Outcome of each request in a batch, in the order the requests were given.
+/// Each is `None` until its request completes. Clones share the same list, so
+/// the modal that displays the outcomes sees them as the callback fills them
+/// in.
+#[derive(Clone, Debug)]
+pub struct BatchOutcomes(Arc<Mutex<Vec<Option<Outcome>>>>);
+
+impl BatchOutcomes {
+    /// Create a list of pending outcomes for a batch of `len` requests
+    pub fn new(len: usize) -> Self {
+        Self(Arc::new(Mutex::new(vec![None; len])))
+    }
+
+    /// Get a callback that records the outcome of each request
+    pub fn on_complete(&self) -> BatchCallback {
+        let outcomes = self.clone();
+        Arc::new(move |index, outcome| {
+            if let Some(slot) = outcomes.lock().get_mut(index) {
+                *slot = Some(outcome);
+            }
+        })
+    }
+
+    /// Lock the list to access the outcomes
+    pub fn lock(&self) -> MutexGuard<'_, Vec<Option<Outcome>>> {
+        self.0.lock().unwrap()
+    }
+
+    /// Get the number of completed requests and the total number of requests
+    pub fn progress(&self) -> (usize, usize) {
+        let outcomes = self.lock();
+        let done = outcomes.iter().filter(|outcome| outcome.is_some()).count();
+        (done, outcomes.len())
+    }
+}
+
+impl BatchModal {
+    fn new(recipe_ids: &[RecipeId], parallel: bool) -> Self {
+        let collection = ViewContext::collection();
+        let names = recipe_ids
+            .iter()
+            .map(|id| {
+                collection
+                    .recipes
+                    .get_recipe(id)
+                    .map(|recipe| recipe.name().to_owned())
+                    .unwrap_or_else(|| id.to_string())
+            })
+            .collect();
+        Self {
+            names,
+            parallel,
+            outcomes: BatchOutcomes::new(recipe_ids.len()),
+        }
+    }
+}
+
+impl Modal for BatchModal {
+    fn title(&self) -> Line<'_> {
+        let (done, total) = self.outcomes.progress();
+        let mode = if self.parallel {
+            "parallel"
+        } else {
+            "sequential"
+        };
+        format!("Sent {done}/{total} ({mode})").into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Percentage(60),
+            Constraint::Length(self.names.len().min(20) as u16),
+        )
+    }
+}
+
+impl EventHandler for BatchModal {}
+
+impl Draw for BatchModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let styles = &TuiContext::get().styles;
+        let name_width = self
+            .names
+            .iter()
+            .map(|name| name.width())
+            .max()
+            .unwrap_or(0);
+        let outcomes = self.outcomes.lock();
+        let lines: Vec<Line> = self
+            .names
+            .iter()
+            .zip(outcomes.iter())
+            .map(|(name, outcome)| {
+                let mut spans = vec![Span::raw(format!(
+                    "{name}{:pad$}  ",
+                    "",
+                    pad = name_width - name.width()
+                ))];
+                match outcome {
+                    None => {
+                        spans.push(Span::styled("Pending", styles.text.hint))
+                    }
+                    Some(Ok(summary)) => {
+                        spans.push(summary.status.generate());
+                        spans.push(" ".into());
+                        spans.push(
+                            (summary.end_time - summary.start_time).generate(),
+                        );
+                    }
+                    Some(Err(error)) => spans
+                        .push(Span::styled(error.clone(), styles.text.error)),
+                }
+                Line::from(spans)
+            })
+            .collect();
+        frame.render_widget(Text::from(lines), metadata.area());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{harness, terminal, TestHarness, TestTerminal},
+        view::test_util::TestComponent,
+    };
+    use chrono::{TimeDelta, Utc};
+    use reqwest::StatusCode;
+    use rstest::rstest;
+    use slumber_core::http::RequestId;
+
+    /// Outcomes are shown as they come in, in the order of the batch
+    #[rstest]
+    fn test_batch_modal(
+        _harness: TestHarness,
+        #[with(30, 3)] terminal: TestTerminal,
+    ) {
+        let modal =
+            BatchModal::new(&["a".into(), "bb".into(), "ccc".into()], false);
+        let on_complete = modal.outcomes.on_complete();
+        let mut component = TestComponent::new(&terminal, modal, ());
+        assert_eq!(
+            component.data().title().to_string(),
+            "Sent 0/3 (sequential)"
+        );
+
+        let start_time = Utc::now();
+        on_complete(
+            1,
+            Ok(ExchangeSummary {
+                id: RequestId::new(),
+                start_time,
+                end_time: start_time + TimeDelta::milliseconds(25),
+                status: StatusCode::NOT_FOUND,
+                trigger: None,
+                note: None,
+            }),
+        );
+        on_complete(0, Err("Connection refused".into()));
+        component.drain_draw().assert_empty();
+        assert_eq!(
+            component.data().title().to_string(),
+            "Sent 2/3 (sequential)"
+        );
+        let styles = &TuiContext::get().styles;
+        terminal.assert_buffer_lines([
+            Line::from(vec![
+                "a    ".into(),
+                Span::styled("Connection refused", styles.text.error),
+                "       ".into(),
+            ]),
+            Line::from(vec![
+                "bb   ".into(),
+                Span::styled("404 Not Found", styles.status_code.error),
+                " 25ms       ".into(),
+            ]),
+            Line::from(vec![
+                "ccc  ".into(),
+                Span::styled("Pending", styles.text.hint),
+                " ".repeat(18).into(),
+            ]),
+        ]);
+    }
+}
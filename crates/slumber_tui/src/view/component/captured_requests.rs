@@ -0,0 +1,144 @@
+use crate::view::{
+    common::{
+        header_table::HeaderTable,
+        list::List,
+        modal::Modal,
+        text_window::{TextWindow, TextWindowProps},
+    },
+    component::Component,
+    draw::{Draw, DrawMetadata, Generate},
+    event::{Child, EventHandler},
+    state::{select::SelectState, StateCell},
+    util::highlight,
+};
+use ratatui::{
+    layout::{Constraint, Layout},
+    text::{Line, Text},
+    widgets::Paragraph,
+    Frame,
+};
+use slumber_core::{
+    http::{content_type::ContentType, CapturedRequest, RequestId},
+    util::MaybeStr,
+};
+
+/// Browse requests received by `slumber listen`
+#[derive(Debug)]
+pub struct CapturedRequests {
+    select: Component<SelectState<CapturedRequest>>,
+    /// Highlighted body of the selected request. Cached so we don't
+    /// re-highlight on every draw
+    body: StateCell<RequestId, Text<'static>>,
+    body_text_window: Component<TextWindow>,
+}
+
+impl CapturedRequests {
+    /// Construct a new modal with the given list of requests. Parent is
+    /// responsible for loading the list from the database.
+    pub fn new(requests: Vec<CapturedRequest>) -> Self {
+        Self {
+            select: SelectState::builder(requests).build().into(),
+            body: Default::default(),
+            body_text_window: Default::default(),
+        }
+    }
+}
+
+impl Modal for CapturedRequests {
+    fn title(&self) -> Line<'_> {
+        "Captured Requests".into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(80), Constraint::Percentage(60))
+    }
+}
+
+impl EventHandler for CapturedRequests {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![
+            self.select.to_child_mut(),
+            self.body_text_window.to_child_mut(),
+        ]
+    }
+}
+
+impl Draw for CapturedRequests {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Length(40), Constraint::Min(0)])
+                .spacing(1)
+                .areas(metadata.area());
+
+        self.select.draw(
+            frame,
+            List::from(self.select.data()),
+            list_area,
+            true,
+        );
+
+        let Some(request) = self.select.data().selected() else {
+            frame.render_widget(
+                Paragraph::new(
+                    "No requests captured yet. Run `slumber listen <port>`",
+                ),
+                detail_area,
+            );
+            return;
+        };
+
+        let [url_area, headers_area, body_area] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Length(request.headers.len() as u16 + 2),
+            Constraint::Min(0),
+        ])
+        .areas(detail_area);
+        frame.render_widget(
+            format!("{} {}", request.method, request.url),
+            url_area,
+        );
+        frame.render_widget(
+            HeaderTable {
+                headers: &request.headers,
+            }
+            .generate(),
+            headers_area,
+        );
+        let body = self.body.get_or_update(&request.id, || {
+            let content_type = ContentType::from_headers(&request.headers).ok();
+            highlight::highlight_if(
+                content_type,
+                format!("{:#}", MaybeStr(&request.body)).into(),
+            )
+        });
+        self.body_text_window.draw(
+            frame,
+            TextWindowProps {
+                text: &body,
+                marks: &[],
+                margins: Default::default(),
+                footer: None,
+            },
+            body_area,
+            false,
+        );
+    }
+}
+
+impl Generate for &CapturedRequest {
+    type Output<'this> = Line<'this> where Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        vec![
+            self.time.generate(),
+            " ".into(),
+            self.method.to_string().into(),
+            " ".into(),
+            self.url.as_str().into(),
+        ]
+        .into()
+    }
+}
@@ -0,0 +1,206 @@
+//! Show the TLS certificate that the server presented for a response
+
+use crate::{
+    context::TuiContext,
+    message::Message,
+    util::ResultReported,
+    view::{
+        common::{
+            modal::Modal,
+            text_window::{TextWindow, TextWindowProps},
+        },
+        component::{baseline::load_exchange, Component},
+        draw::{Draw, DrawMetadata},
+        event::{Child, EventHandler},
+        ViewContext,
+    },
+};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use ratatui::{
+    layout::Constraint,
+    text::{Line, Span, Text},
+    Frame,
+};
+use slumber_core::http::{RequestId, ServerCertificate};
+
+/// Width of the field label column, including padding
+const LABEL_WIDTH: usize = 12;
+
+/// Show the server certificate for a request from history. If it wasn't made
+/// over TLS, there's nothing to show so just tell the user.
+pub fn view_certificate(request_id: RequestId) {
+    let Some(exchange) = ViewContext::with_database(|database| {
+        load_exchange(database, request_id)
+    })
+    .reported(&ViewContext::messages_tx()) else {
+        return;
+    };
+    match &exchange.certificate {
+        Some(certificate) => {
+            ViewContext::open_modal(CertificateModal::new(certificate))
+        }
+        None => ViewContext::send_message(Message::Notify(
+            "No TLS certificate for this request".into(),
+        )),
+    }
+}
+
+/// Modal listing the fields of a server certificate
+#[derive(Debug)]
+struct CertificateModal {
+    text: Text<'static>,
+    text_window: Component<TextWindow>,
+}
+
+impl CertificateModal {
+    fn new(certificate: &ServerCertificate) -> Self {
+        let styles = &TuiContext::get().styles;
+        let field = |label: &str, value: String| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{label:<LABEL_WIDTH$}"),
+                    styles.text.title,
+                ),
+                value.into(),
+            ])
+        };
+        let mut not_after =
+            field("Valid Until", format_date(certificate.not_after));
+        if !certificate.is_valid() {
+            not_after.push_span(Span::styled(" (invalid)", styles.text.error));
+        }
+        let lines = vec![
+            field("Subject", certificate.subject.clone()),
+            field("Issuer", certificate.issuer.clone()),
+            field("Alt Names", certificate.subject_alt_names.iter().join(", ")),
+            field("Valid From", format_date(certificate.not_before)),
+            not_after,
+            field("Serial", certificate.serial_number.clone()),
+            field("SHA-256", certificate.fingerprint.clone()),
+        ];
+        Self {
+            text: lines.into(),
+            text_window: Component::default(),
+        }
+    }
+}
+
+impl Modal for CertificateModal {
+    fn title(&self) -> Line<'_> {
+        "TLS Certificate".into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Percentage(80),
+            Constraint::Length(self.text.height() as u16),
+        )
+    }
+}
+
+impl EventHandler for CertificateModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.text_window.to_child_mut()]
+    }
+}
+
+impl Draw for CertificateModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        self.text_window.draw(
+            frame,
+            TextWindowProps {
+                text: &self.text,
+                marks: &[],
+                margins: Default::default(),
+                footer: None,
+            },
+            metadata.area(),
+            true,
+        );
+    }
+}
+
+/// Certificate dates are often years apart, so include the year. Stick to UTC
+/// so they match what other tools show
+fn format_date(date: DateTime<Utc>) -> String {
+    date.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{harness, TestHarness},
+        view::event::Event,
+    };
+    use chrono::TimeZone;
+    use rstest::rstest;
+    use slumber_core::{assert_matches, http::Exchange, test_util::Factory};
+
+    fn certificate() -> ServerCertificate {
+        ServerCertificate {
+            subject: "CN=localhost".into(),
+            issuer: "CN=Slumber Test CA".into(),
+            subject_alt_names: vec!["localhost".into(), "127.0.0.1".into()],
+            not_before: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            not_after: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            serial_number: "01:02".into(),
+            fingerprint: "AB:CD".into(),
+        }
+    }
+
+    /// Each field gets a line, and an expired certificate is flagged
+    #[rstest]
+    fn test_modal(_harness: TestHarness) {
+        let modal = CertificateModal::new(&certificate());
+        let styles = &TuiContext::get().styles;
+        let label = |text: &'static str| Span::styled(text, styles.text.title);
+        assert_eq!(
+            modal.text,
+            Text::from(vec![
+                Line::from(vec![label("Subject     "), "CN=localhost".into()]),
+                Line::from(vec![
+                    label("Issuer      "),
+                    "CN=Slumber Test CA".into()
+                ]),
+                Line::from(vec![
+                    label("Alt Names   "),
+                    "localhost, 127.0.0.1".into()
+                ]),
+                Line::from(vec![
+                    label("Valid From  "),
+                    "2024-01-01 00:00:00 UTC".into()
+                ]),
+                Line::from(vec![
+                    label("Valid Until "),
+                    "2025-01-01 00:00:00 UTC".into(),
+                    Span::styled(" (invalid)", styles.text.error),
+                ]),
+                Line::from(vec![label("Serial      "), "01:02".into()]),
+                Line::from(vec![label("SHA-256     "), "AB:CD".into()]),
+            ])
+        );
+    }
+
+    /// Plain HTTP requests don't have a certificate
+    #[rstest]
+    fn test_view_certificate(mut harness: TestHarness) {
+        let exchange = Exchange {
+            certificate: Some(certificate()),
+            ..Exchange::factory(())
+        };
+        harness.database.insert_exchange(&exchange).unwrap();
+        view_certificate(exchange.id);
+        let modal = assert_matches!(
+            ViewContext::pop_event(),
+            Some(Event::OpenModal(modal)) => modal,
+        );
+        assert_eq!(modal.title(), Line::from("TLS Certificate"));
+
+        let exchange = Exchange::factory(());
+        harness.database.insert_exchange(&exchange).unwrap();
+        view_certificate(exchange.id);
+        assert_matches!(harness.pop_message_now(), Message::Notify(_));
+    }
+}
@@ -0,0 +1,275 @@
+//! Help the user recover when a request can't reach the server, e.g. because
+//! of a DNS or connection error
+
+use crate::{
+    context::TuiContext,
+    message::Message,
+    view::{
+        common::{button::ButtonGroup, modal::Modal},
+        component::{recipe_form::RecipeFormModal, Component},
+        draw::{Draw, DrawMetadata},
+        event::{Child, Event, EventHandler, Update},
+        ViewContext,
+    },
+};
+use derive_more::Display;
+use ratatui::{
+    layout::{Constraint, Layout},
+    text::{Line, Text},
+    widgets::{Paragraph, Wrap},
+    Frame,
+};
+use slumber_core::{
+    collection::{ProfileId, RecipeId, ReferenceLocation},
+    http::RequestError,
+};
+use std::env;
+use strum::{EnumCount, EnumIter};
+
+/// Environment variables that reqwest reads proxy settings from. It checks the
+/// lowercase versions too.
+const PROXY_VARIABLES: &[&str] =
+    &["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY", "NO_PROXY"];
+
+/// Local event to send the selected recipe again. Handled by the primary view,
+/// because it holds the recipe's build options
+#[derive(Debug)]
+pub struct RetryRequest;
+
+/// Explain a failure to reach the server, with actions to fix it
+#[derive(Debug)]
+pub struct ConnectionErrorModal {
+    title: &'static str,
+    profile_id: Option<ProfileId>,
+    recipe_id: RecipeId,
+    text: Text<'static>,
+    buttons: Component<ButtonGroup<ConnectionErrorButton>>,
+    /// Button that closed the modal, to act on in `on_close`
+    selected: Option<ConnectionErrorButton>,
+}
+
+#[derive(
+    Copy, Clone, Debug, Default, Display, EnumCount, EnumIter, PartialEq,
+)]
+enum ConnectionErrorButton {
+    #[default]
+    Retry,
+    #[display("Edit Host")]
+    EditHost,
+    Close,
+}
+
+impl ConnectionErrorModal {
+    pub fn new(error: &RequestError) -> Self {
+        let styles = &TuiContext::get().styles;
+        let request = &error.request;
+        let kind = error.kind();
+
+        let mut lines = vec![
+            Line::styled(format!("{:#}", error.error), styles.text.error),
+            Line::default(),
+        ];
+        // The failure was recorded before we got here, so it's included
+        let count = ViewContext::with_database(|database| {
+            database.count_failures(
+                request.profile_id.as_ref(),
+                &request.recipe_id,
+                kind,
+            )
+        })
+        .unwrap_or_default();
+        if count > 1 {
+            lines.push(
+                format!(
+                    "This has happened {count} times for this recipe & profile"
+                )
+                .into(),
+            );
+        }
+        lines.push(proxy_settings().into());
+
+        Self {
+            title: kind.description(),
+            profile_id: request.profile_id.clone(),
+            recipe_id: request.recipe_id.clone(),
+            text: lines.into(),
+            buttons: Default::default(),
+            selected: None,
+        }
+    }
+}
+
+impl Modal for ConnectionErrorModal {
+    fn title(&self) -> Line<'_> {
+        Line::styled(self.title, TuiContext::get().styles.text.error)
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Percentage(60),
+            // Leave room for wrapping, then a gap and the buttons
+            Constraint::Length(self.text.height() as u16 + 4),
+        )
+    }
+
+    fn on_close(self: Box<Self>, submitted: bool) {
+        if !submitted {
+            return;
+        }
+        let is_scratch = self.recipe_id == RecipeId::scratch();
+        match self.selected {
+            // Scratch requests are sent from the form, so reopen it with the
+            // failed request
+            Some(ConnectionErrorButton::Retry) if is_scratch => {
+                ViewContext::open_modal(RecipeFormModal::scratch(
+                    self.profile_id,
+                ))
+            }
+            Some(ConnectionErrorButton::Retry) => {
+                ViewContext::push_event(Event::new_local(RetryRequest))
+            }
+            // Hosts are typically defined in the profile
+            Some(ConnectionErrorButton::EditHost) => {
+                let message = match (self.profile_id, is_scratch) {
+                    (Some(profile_id), _) => Message::CollectionEditItem(
+                        ReferenceLocation::Profile(profile_id),
+                    ),
+                    (None, false) => Message::CollectionEditItem(
+                        ReferenceLocation::Recipe(self.recipe_id),
+                    ),
+                    (None, true) => Message::CollectionEdit,
+                };
+                ViewContext::send_message(message);
+            }
+            Some(ConnectionErrorButton::Close) | None => {}
+        }
+    }
+}
+
+impl EventHandler for ConnectionErrorModal {
+    fn update(&mut self, event: Event) -> Update {
+        let Some(button) = event.local::<ConnectionErrorButton>() else {
+            return Update::Propagate(event);
+        };
+        self.selected = Some(*button);
+        ViewContext::push_event(Event::CloseModal { submitted: true });
+        Update::Consumed
+    }
+
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.buttons.to_child_mut()]
+    }
+}
+
+impl Draw for ConnectionErrorModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let [text_area, buttons_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)])
+                .areas(metadata.area());
+        frame.render_widget(
+            Paragraph::new(self.text.clone()).wrap(Wrap::default()),
+            text_area,
+        );
+        self.buttons.draw(frame, (), buttons_area, true);
+    }
+}
+
+/// Describe the proxy settings that requests are sent with
+fn proxy_settings() -> String {
+    let variables: Vec<String> = PROXY_VARIABLES
+        .iter()
+        .filter_map(|name| {
+            let value = env::var(name)
+                .or_else(|_| env::var(name.to_lowercase()))
+                .ok()?;
+            Some(format!("{name}={value}"))
+        })
+        .collect();
+    if variables.is_empty() {
+        format!("No proxy configured ({})", PROXY_VARIABLES.join(", "))
+    } else {
+        format!("Proxy: {}", variables.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{harness, TestHarness};
+    use anyhow::anyhow;
+    use chrono::Utc;
+    use rstest::rstest;
+    use slumber_core::{
+        assert_matches, http::RequestRecord, test_util::Factory,
+    };
+
+    fn error(
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+    ) -> RequestError {
+        RequestError {
+            error: anyhow!("Connection refused"),
+            request: RequestRecord::factory((profile_id, recipe_id)).into(),
+            start_time: Utc::now(),
+            end_time: Utc::now(),
+        }
+    }
+
+    /// Select a button and close the modal
+    fn press(mut modal: ConnectionErrorModal, button: ConnectionErrorButton) {
+        assert_matches!(
+            modal.update(Event::new_local(button)),
+            Update::Consumed
+        );
+        Box::new(modal).on_close(true);
+    }
+
+    /// Retrying a recipe is handled by the primary view, but a scratch request
+    /// has to be resent from its form
+    #[rstest]
+    fn test_retry(_harness: TestHarness) {
+        let modal =
+            ConnectionErrorModal::new(&error(None, RecipeId::factory(())));
+        press(modal, ConnectionErrorButton::Retry);
+        assert_matches!(
+            ViewContext::pop_event(),
+            Some(Event::CloseModal { .. })
+        );
+        let event = ViewContext::pop_event().unwrap();
+        assert_matches!(event.local::<RetryRequest>(), Some(_));
+
+        let modal =
+            ConnectionErrorModal::new(&error(None, RecipeId::scratch()));
+        press(modal, ConnectionErrorButton::Retry);
+        assert_matches!(
+            ViewContext::pop_event(),
+            Some(Event::CloseModal { .. })
+        );
+        assert_matches!(ViewContext::pop_event(), Some(Event::OpenModal(_)));
+    }
+
+    /// Editing the host opens the profile, if there is one
+    #[rstest]
+    fn test_edit_host(mut harness: TestHarness) {
+        let profile_id = harness.collection.first_profile_id().clone();
+        let modal = ConnectionErrorModal::new(&error(
+            Some(profile_id.clone()),
+            RecipeId::factory(()),
+        ));
+        press(modal, ConnectionErrorButton::EditHost);
+        let id = assert_matches!(
+            harness.pop_message_now(),
+            Message::CollectionEditItem(ReferenceLocation::Profile(id)) => id
+        );
+        assert_eq!(id, profile_id);
+
+        let recipe_id = RecipeId::factory(());
+        let modal = ConnectionErrorModal::new(&error(None, recipe_id.clone()));
+        press(modal, ConnectionErrorButton::EditHost);
+        let id = assert_matches!(
+            harness.pop_message_now(),
+            Message::CollectionEditItem(ReferenceLocation::Recipe(id)) => id
+        );
+        assert_eq!(id, recipe_id);
+    }
+}
@@ -0,0 +1,67 @@
+//! Validate responses against the collection's OpenAPI spec
+
+use crate::{
+    message::Message,
+    util::ResultReported,
+    view::{
+        component::{baseline::load_exchange, misc::ErrorListModal},
+        ViewContext,
+    },
+};
+use slumber_core::http::{
+    contract::{Contract, Violation},
+    Exchange, RequestId,
+};
+
+/// Validate an exchange against the collection's OpenAPI spec. Return `None`
+/// if the collection doesn't link a spec, or it failed to load (in which case
+/// the error is reported).
+pub fn validate_contract(exchange: &Exchange) -> Option<Vec<Violation>> {
+    let collection = ViewContext::collection();
+    let path = collection.openapi.as_deref()?;
+    let contract = Contract::load(path).reported(&ViewContext::messages_tx())?;
+    Some(contract.validate(exchange))
+}
+
+/// Validate a request from history against the collection's OpenAPI spec. If
+/// there are any violations, show them in a modal.
+pub fn check_contract(request_id: RequestId) {
+    if ViewContext::collection().openapi.is_none() {
+        ViewContext::send_message(Message::Notify(
+            "No OpenAPI spec linked; set the `openapi` field in your \
+            collection"
+                .into(),
+        ));
+        return;
+    }
+    let Some(exchange) = ViewContext::with_database(|database| {
+        load_exchange(database, request_id)
+    })
+    .reported(&ViewContext::messages_tx()) else {
+        return;
+    };
+    let Some(violations) = validate_contract(&exchange) else {
+        return;
+    };
+
+    if violations.is_empty() {
+        ViewContext::send_message(Message::Notify(
+            "Response matches OpenAPI spec".into(),
+        ));
+    } else {
+        ViewContext::open_modal(ErrorListModal::new(
+            title(violations.len()),
+            violations,
+        ));
+    }
+}
+
+/// Describe a number of violations, e.g. "2 Contract Violations"
+pub fn title(count: usize) -> String {
+    let noun = if count == 1 {
+        "Violation"
+    } else {
+        "Violations"
+    };
+    format!("{count} Contract {noun}")
+}
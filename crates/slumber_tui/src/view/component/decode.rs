@@ -0,0 +1,217 @@
+//! Inspect encoded values (JWTs, base64, timestamps, etc.) from a response
+
+use crate::{
+    context::TuiContext,
+    message::Message,
+    view::{
+        common::{
+            modal::Modal,
+            text_window::{TextWindow, TextWindowProps},
+        },
+        component::Component,
+        draw::{Draw, DrawMetadata},
+        event::{Child, EventHandler},
+        ViewContext,
+    },
+};
+use ratatui::{
+    layout::Constraint,
+    text::{Line, Text},
+    Frame,
+};
+use slumber_config::Action;
+use slumber_core::http::{
+    decode::{decode, Decoded},
+    query::Query,
+    ResponseBody,
+};
+
+/// Decode the value selected by a body query, and show every way it could be
+/// decoded. Without a query, the entire body is decoded. If the body is shown
+/// with a display query, the query is applied to the transformed body, just
+/// like it is in the body view.
+pub fn decode_value(
+    body: &ResponseBody,
+    display_query: Option<&Query>,
+    query: Option<&Query>,
+) {
+    let transformed = body
+        .parsed()
+        .zip(display_query)
+        .map(|(parsed, display_query)| display_query.query_display(parsed));
+    let parsed = transformed.as_deref().or(body.parsed());
+    let value = match (parsed, query) {
+        (Some(parsed), Some(query)) => match query.query_to_string(parsed) {
+            Ok(value) => value,
+            Err(error) => {
+                let binding = TuiContext::get()
+                    .input_engine
+                    .binding_display(Action::Search);
+                ViewContext::send_message(Message::Notify(format!(
+                    "{error}; use '{binding}' to select a single value"
+                )));
+                return;
+            }
+        },
+        _ => body.text().unwrap_or_default().to_owned(),
+    };
+
+    let decoded = decode(&value);
+    if decoded.is_empty() {
+        ViewContext::send_message(Message::Notify(
+            "Value doesn't look like JWT, JSON, a timestamp, base64, or \
+            URL-encoded text"
+                .into(),
+        ));
+    } else {
+        ViewContext::open_modal(DecodeModal::new(decoded));
+    }
+}
+
+/// Modal listing each way a value can be decoded
+#[derive(Debug)]
+struct DecodeModal {
+    /// One section per decoding, each with the encoding as a header
+    text: Text<'static>,
+    text_window: Component<TextWindow>,
+}
+
+impl DecodeModal {
+    fn new(decoded: Vec<Decoded>) -> Self {
+        let styles = &TuiContext::get().styles;
+        let mut lines: Vec<Line<'static>> = Vec::new();
+        for (i, decoded) in decoded.into_iter().enumerate() {
+            if i > 0 {
+                lines.push(Line::default());
+            }
+            lines.push(Line::styled(
+                decoded.encoding.to_string(),
+                styles.text.title,
+            ));
+            lines.extend(
+                decoded.text.lines().map(|line| line.to_owned().into()),
+            );
+        }
+        Self {
+            text: lines.into(),
+            text_window: Component::default(),
+        }
+    }
+}
+
+impl Modal for DecodeModal {
+    fn title(&self) -> Line<'_> {
+        "Decoded Value".into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Percentage(60),
+            Constraint::Length(self.text.height().min(30) as u16),
+        )
+    }
+}
+
+impl EventHandler for DecodeModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.text_window.to_child_mut()]
+    }
+}
+
+impl Draw for DecodeModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        self.text_window.draw(
+            frame,
+            TextWindowProps {
+                text: &self.text,
+                marks: &[],
+                margins: Default::default(),
+                footer: None,
+            },
+            metadata.area(),
+            true,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{harness, TestHarness},
+        view::event::Event,
+    };
+    use indexmap::indexmap;
+    use rstest::rstest;
+    use slumber_core::{
+        assert_matches,
+        http::ResponseRecord,
+        test_util::{header_map, Factory},
+    };
+
+    /// Build a parsed JSON response
+    fn json_response(body: &str) -> ResponseRecord {
+        let response = ResponseRecord {
+            headers: header_map(
+                indexmap! {"content-type" => "application/json"},
+            ),
+            body: body.as_bytes().to_vec().into(),
+            ..ResponseRecord::factory(())
+        };
+        response.parse_body();
+        response
+    }
+
+    /// Decode a value selected with a query
+    #[rstest]
+    fn test_decode_query(_harness: TestHarness) {
+        let response =
+            json_response(r#"{"token": "aGVsbG8gd29ybGQ=", "id": 3}"#);
+        let query: Query = "$.token".parse().unwrap();
+        decode_value(&response.body, None, Some(&query));
+        let modal = assert_matches!(
+            ViewContext::pop_event(),
+            Some(Event::OpenModal(modal)) => modal,
+        );
+        assert_eq!(modal.title(), Line::from("Decoded Value"));
+    }
+
+    /// User's query is applied to the body as transformed by the display query
+    #[rstest]
+    fn test_decode_display_query(_harness: TestHarness) {
+        let response =
+            json_response(r#"{"data": {"token": "aGVsbG8gd29ybGQ="}}"#);
+        let display_query: Query = "$.data".parse().unwrap();
+        let query: Query = "$.token".parse().unwrap();
+        decode_value(&response.body, Some(&display_query), Some(&query));
+        assert_matches!(ViewContext::pop_event(), Some(Event::OpenModal(_)));
+    }
+
+    /// Query must select exactly one value
+    #[rstest]
+    fn test_decode_multiple(mut harness: TestHarness) {
+        let response = json_response("[1, 2]");
+        let query: Query = "$[*]".parse().unwrap();
+        decode_value(&response.body, None, Some(&query));
+        let message = assert_matches!(
+            harness.pop_message_now(),
+            Message::Notify(message) => message,
+        );
+        assert_eq!(
+            message,
+            "Expected exactly one result from query, but got 2; \
+            use '/' to select a single value"
+        );
+    }
+
+    /// Nothing to decode
+    #[rstest]
+    fn test_decode_plain(mut harness: TestHarness) {
+        let response = ResponseRecord {
+            body: b"hello".to_vec().into(),
+            ..ResponseRecord::factory(())
+        };
+        decode_value(&response.body, None, None);
+        assert_matches!(harness.pop_message_now(), Message::Notify(_));
+    }
+}
@@ -0,0 +1,152 @@
+//! Report problems with how a collection's items refer to each other
+
+use crate::{
+    context::TuiContext,
+    util::ResultReported,
+    view::{
+        common::{
+            modal::Modal,
+            text_window::{TextWindow, TextWindowProps},
+        },
+        component::Component,
+        draw::{Draw, DrawMetadata},
+        event::{Child, EventHandler},
+        ViewContext,
+    },
+};
+use ratatui::{
+    layout::Constraint,
+    text::{Line, Text},
+    Frame,
+};
+use slumber_core::collection::ReferenceIssue;
+
+/// Check the current collection for unused and dangling references, and show
+/// the results in a modal
+pub fn open_diagnostics() {
+    if let Some(issues) = ViewContext::collection()
+        .reference_issues()
+        .reported(&ViewContext::messages_tx())
+    {
+        ViewContext::open_modal(DiagnosticsModal::new(&issues));
+    }
+}
+
+/// List unused and dangling references in the collection. Dangling references
+/// are shown first, because they break requests.
+#[derive(Debug)]
+pub struct DiagnosticsModal {
+    text: Text<'static>,
+    text_window: Component<TextWindow>,
+}
+
+impl DiagnosticsModal {
+    fn new(issues: &[ReferenceIssue]) -> Self {
+        let styles = &TuiContext::get().styles;
+        let (errors, unused): (Vec<_>, Vec<_>) =
+            issues.iter().partition(|issue| issue.is_error());
+
+        let mut lines: Vec<Line<'static>> = Vec::new();
+        for (title, issues, style) in [
+            ("Dangling References", errors, styles.text.error),
+            ("Unused", unused, Default::default()),
+        ] {
+            if issues.is_empty() {
+                continue;
+            }
+            if !lines.is_empty() {
+                lines.push(Line::default());
+            }
+            lines.push(Line::styled(title, styles.text.title));
+            lines.extend(
+                issues
+                    .into_iter()
+                    .map(|issue| Line::styled(issue.to_string(), style)),
+            );
+        }
+        if lines.is_empty() {
+            lines.push("No unused or dangling references".into());
+        }
+
+        Self {
+            text: lines.into(),
+            text_window: Component::default(),
+        }
+    }
+}
+
+impl Modal for DiagnosticsModal {
+    fn title(&self) -> Line<'_> {
+        "Diagnostics".into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Percentage(60),
+            Constraint::Length((self.text.height() as u16).min(20)),
+        )
+    }
+}
+
+impl EventHandler for DiagnosticsModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.text_window.to_child_mut()]
+    }
+}
+
+impl Draw for DiagnosticsModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        self.text_window.draw(
+            frame,
+            TextWindowProps {
+                text: &self.text,
+                marks: &[],
+                margins: Default::default(),
+                footer: None,
+            },
+            metadata.area(),
+            true,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{harness, TestHarness};
+    use rstest::rstest;
+    use slumber_core::{collection::ReferenceLocation, template::TemplateKey};
+
+    /// Errors are grouped before unused items
+    #[rstest]
+    fn test_diagnostics(_harness: TestHarness) {
+        let issues = [
+            ReferenceIssue::UnusedChain {
+                chain: "token".into(),
+            },
+            ReferenceIssue::Dangling {
+                key: TemplateKey::Field("host".into()),
+                location: ReferenceLocation::Recipe("login".into()),
+            },
+        ];
+        let modal = DiagnosticsModal::new(&issues);
+        let lines: Vec<String> =
+            modal.text.lines.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Dangling References",
+                "Recipe `login` refers to unknown field `host`",
+                "",
+                "Unused",
+                "Chain `token` is never used",
+            ]
+        );
+
+        let modal = DiagnosticsModal::new(&[]);
+        assert_eq!(
+            modal.text.lines[0].to_string(),
+            "No unused or dangling references"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Show the environment that a request was built in, and the environment
+//! variables that the collection uses
+
+use crate::{
+    context::TuiContext,
+    view::{
+        common::{modal::Modal, table::Table},
+        draw::{Draw, DrawMetadata, Generate},
+        event::EventHandler,
+    },
+};
+use ratatui::{
+    layout::{Constraint, Layout},
+    text::{Line, Text},
+    Frame,
+};
+use slumber_core::{
+    collection::{Collection, EnvironmentVariable},
+    http::RequestRecord,
+};
+use std::env;
+
+/// Modal listing the profile, git revision, and profile field values that were
+/// used to build a request, and what triggered it
+#[derive(Debug)]
+pub struct EnvironmentModal {
+    /// Profile, git revision, and trigger
+    general: Vec<[Text<'static>; 2]>,
+    /// Profile field values, masked where sensitive
+    fields: Vec<[Text<'static>; 2]>,
+}
+
+impl EnvironmentModal {
+    pub fn new(request: &RequestRecord) -> Self {
+        let environment = &request.environment;
+        let optional = |value: Option<String>| {
+            Text::from(value.unwrap_or_else(|| "<none>".into()))
+        };
+        let general = vec![
+            [
+                "Profile".into(),
+                optional(request.profile_id.as_ref().map(ToString::to_string)),
+            ],
+            [
+                "Git Revision".into(),
+                optional(environment.git_revision.clone()),
+            ],
+            [
+                "Triggered By".into(),
+                optional(environment.trigger.as_ref().map(ToString::to_string)),
+            ],
+        ];
+        let fields = environment
+            .profile_data
+            .iter()
+            .map(|(field, value)| [field.clone().into(), value.clone().into()])
+            .collect();
+        Self { general, fields }
+    }
+}
+
+impl Modal for EnvironmentModal {
+    fn title(&self) -> Line<'_> {
+        "Environment".into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        // General table, spacer, then fields table with a header
+        let height = self.general.len() + 2 + self.fields.len();
+        (
+            Constraint::Percentage(60),
+            Constraint::Length(height.min(30) as u16),
+        )
+    }
+}
+
+impl EventHandler for EnvironmentModal {}
+
+impl Draw for EnvironmentModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let [general_area, _, fields_area] = Layout::vertical([
+            Constraint::Length(self.general.len() as u16),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .areas(metadata.area());
+
+        frame.render_widget(
+            Table {
+                rows: self.general.clone(),
+                column_widths: &[Constraint::Length(13), Constraint::Min(0)],
+                ..Default::default()
+            }
+            .generate(),
+            general_area,
+        );
+        frame.render_widget(
+            Table {
+                rows: self.fields.clone(),
+                header: Some(["Field", "Value"]),
+                alternate_row_style: true,
+                ..Default::default()
+            }
+            .generate(),
+            fields_area,
+        );
+    }
+}
+
+/// Modal listing every environment variable that the collection uses, with
+/// its current value. Sensitive values are masked.
+#[derive(Debug)]
+pub struct EnvironmentVariablesModal {
+    rows: Vec<[Text<'static>; 2]>,
+}
+
+impl EnvironmentVariablesModal {
+    /// Build the list of variables. Return `None` if the collection doesn't
+    /// use any
+    pub fn new(collection: &Collection) -> anyhow::Result<Option<Self>> {
+        let styles = &TuiContext::get().styles;
+        let rows: Vec<_> = collection
+            .environment_variables()?
+            .into_iter()
+            .map(|variable| {
+                let options = collection.environment.get(&variable);
+                let sensitive =
+                    options.is_some_and(|options| options.sensitive);
+                let value = match (env::var(&variable), options) {
+                    (Ok(_), _) if sensitive => "<sensitive>".into(),
+                    (Ok(value), _) => value.into(),
+                    (
+                        Err(_),
+                        Some(EnvironmentVariable {
+                            default: Some(default),
+                            ..
+                        }),
+                    ) => Text::styled(
+                        if sensitive {
+                            "<unset; using default>".into()
+                        } else {
+                            format!("<unset; using default `{default}`>")
+                        },
+                        styles.text.hint,
+                    ),
+                    (
+                        Err(_),
+                        Some(EnvironmentVariable {
+                            required: Some(_), ..
+                        }),
+                    ) => Text::styled("<unset; required>", styles.text.error),
+                    (Err(_), _) => Text::styled("<unset>", styles.text.hint),
+                };
+                [variable.into(), value]
+            })
+            .collect();
+        Ok((!rows.is_empty()).then_some(Self { rows }))
+    }
+}
+
+impl Modal for EnvironmentVariablesModal {
+    fn title(&self) -> Line<'_> {
+        "Environment Variables".into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        // Header, then one row per variable
+        let height = self.rows.len() + 1;
+        (
+            Constraint::Percentage(60),
+            Constraint::Length(height.min(30) as u16),
+        )
+    }
+}
+
+impl EventHandler for EnvironmentVariablesModal {}
+
+impl Draw for EnvironmentVariablesModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        frame.render_widget(
+            Table {
+                rows: self.rows.clone(),
+                header: Some(["Variable", "Value"]),
+                alternate_row_style: true,
+                ..Default::default()
+            }
+            .generate(),
+            metadata.area(),
+        );
+    }
+}
@@ -3,16 +3,22 @@ use crate::{
     view::{
         common::{tabs::Tabs, Pane},
         component::{
+            assertion::{run_assertions, AssertionResults},
+            contract::{self, validate_contract},
+            graphql::{self, GraphQlErrorsView, GraphQlErrorsViewProps},
             primary::PrimaryPane,
             request_view::{RequestView, RequestViewProps},
             response_view::{
                 ResponseBodyView, ResponseBodyViewProps, ResponseHeadersView,
                 ResponseHeadersViewProps,
             },
+            status_code::{StatusLink, StatusLinkProps},
+            timing::{TimingView, TimingViewProps},
             Component,
         },
         draw::{Draw, DrawMetadata, Generate},
         event::{Child, Event, EventHandler, Update},
+        state::StateCell,
         util::persistence::PersistedLazy,
         RequestState, ViewContext,
     },
@@ -21,14 +27,15 @@ use derive_more::Display;
 use persisted::SingletonKey;
 use ratatui::{
     layout::{Alignment, Constraint, Layout},
-    text::Line,
-    widgets::block::Title,
+    text::{Line, Span, Text},
+    widgets::{block::Title, Paragraph, Wrap},
     Frame,
 };
 use serde::{Deserialize, Serialize};
 use slumber_config::Action;
 use slumber_core::{
-    collection::RecipeNodeDiscriminants, http::RequestRecord,
+    collection::{RecipeNodeDiscriminants, ResponseExample},
+    http::{graphql::GraphQlResponse, RequestId, RequestRecord},
     util::format_byte_size,
 };
 use std::sync::Arc;
@@ -44,6 +51,20 @@ pub struct ExchangePane {
     request: Component<RequestView>,
     response_headers: Component<ResponseHeadersView>,
     response_body: Component<ResponseBodyView>,
+    graphql_errors: Component<GraphQlErrorsView>,
+    timing: Component<TimingView>,
+    /// Clickable status code of the selected response
+    status: Component<StatusLink>,
+    /// Number of OpenAPI contract violations for the selected response.
+    /// `None` if the collection has no spec. Cached so we only validate once
+    /// per response
+    contract_violations: StateCell<RequestId, Option<usize>>,
+    /// Results of the recipe's assertions for the selected response. Cached
+    /// so we only check once per response
+    assertion_results: StateCell<RequestId, AssertionResults>,
+    /// Parsed response for GraphQL requests; `None` for anything else. Cached
+    /// so we only parse once per response
+    graphql: StateCell<RequestId, Option<GraphQlResponse>>,
 }
 
 pub struct ExchangePaneProps<'a> {
@@ -51,6 +72,9 @@ pub struct ExchangePaneProps<'a> {
     /// placeholder
     pub selected_recipe_kind: Option<RecipeNodeDiscriminants>,
     pub request_state: Option<&'a RequestState>,
+    /// Example response of the selected recipe, shown in place of a response
+    /// if the recipe has no history
+    pub example: Option<&'a ResponseExample>,
 }
 
 #[derive(
@@ -69,7 +93,13 @@ enum Tab {
     Request,
     #[default]
     Body,
+    /// GraphQL only: the `data` field of the body
+    Data,
+    /// GraphQL only: the `errors` field of the body
+    Errors,
     Headers,
+    /// Breakdown of how long each phase of the request took
+    Timing,
 }
 
 impl EventHandler for ExchangePane {
@@ -89,6 +119,8 @@ impl EventHandler for ExchangePane {
         vec![
             self.request.to_child_mut(),
             self.response_body.to_child_mut(),
+            self.graphql_errors.to_child_mut(),
+            self.status.to_child_mut(),
             // Tabs last so the children get priority
             self.tabs.to_child_mut(),
         ]
@@ -136,6 +168,23 @@ impl<'a> Draw<ExchangePaneProps<'a>> for ExchangePane {
             Some(RecipeNodeDiscriminants::Recipe) => {}
         }
 
+        // Data and Errors replace Body for GraphQL responses
+        let graphql_response = match props.request_state {
+            Some(RequestState::Response { exchange }) => {
+                Some(self.graphql.get_or_update(&exchange.id, || {
+                    GraphQlResponse::parse(exchange)
+                }))
+            }
+            _ => None,
+        };
+        let graphql_response =
+            graphql_response.as_deref().and_then(Option::as_ref);
+        if graphql_response.is_some() {
+            self.tabs.data().set_hidden(&[Tab::Body]);
+        } else {
+            self.tabs.data().set_hidden(&[Tab::Data, Tab::Errors]);
+        }
+
         // Split out the areas we *may* need
         let [metadata_area, tabs_area, content_area] = Layout::vertical([
             Constraint::Length(1),
@@ -162,15 +211,77 @@ impl<'a> Draw<ExchangePaneProps<'a>> for ExchangePane {
             .request_state
             .and_then(RequestState::response_metadata)
         {
+            let mut spans = Vec::new();
+            // Flag any drift from the OpenAPI spec
+            if let Some(RequestState::Response { exchange }) =
+                props.request_state
+            {
+                let violations = self
+                    .contract_violations
+                    .get_or_update(&exchange.id, || {
+                        validate_contract(exchange).map(|v| v.len())
+                    });
+                if let Some(count @ 1..) = *violations {
+                    spans.push(Span::styled(
+                        contract::title(count),
+                        TuiContext::get().styles.text.error,
+                    ));
+                    spans.push(" ".into());
+                }
+
+                // GraphQL servers usually return errors with a 200, so they
+                // need to be called out separately
+                if let Some(count @ 1..) =
+                    graphql_response.map(|response| response.errors.len())
+                {
+                    spans.push(Span::styled(
+                        graphql::title(count),
+                        TuiContext::get().styles.text.error,
+                    ));
+                    spans.push(" ".into());
+                }
+
+                let results = self
+                    .assertion_results
+                    .get_or_update(&exchange.id, || run_assertions(exchange));
+                if results.total > 0 {
+                    let styles = &TuiContext::get().styles.status_code;
+                    let style = if results.failures.is_empty() {
+                        styles.success
+                    } else {
+                        styles.error
+                    };
+                    spans.push(Span::styled(results.title(), style));
+                    spans.push(" ".into());
+                }
+            }
+            // Status gets its own area so it can be clicked
+            let status = metadata.status.to_string();
+            let size = format!(" {}", format_byte_size(metadata.size));
+            let [spans_area, status_area, size_area] = Layout::horizontal([
+                Constraint::Min(0),
+                Constraint::Length(status.len() as u16),
+                Constraint::Length(size.len() as u16),
+            ])
+            .areas(metadata_area);
             frame.render_widget(
-                Line::from(vec![
-                    metadata.status.generate(),
-                    " ".into(),
-                    format_byte_size(metadata.size).into(),
-                ])
-                .alignment(Alignment::Right),
-                metadata_area,
+                Line::from(spans).alignment(Alignment::Right),
+                spans_area,
             );
+            if let Some(RequestState::Response { exchange }) =
+                props.request_state
+            {
+                self.status.draw(
+                    frame,
+                    StatusLinkProps {
+                        request_id: exchange.id,
+                        status: metadata.status,
+                    },
+                    status_area,
+                    true,
+                );
+            }
+            frame.render_widget(size, size_area);
         }
 
         // Render request/response based on state. Lambdas help with code dupe
@@ -187,22 +298,42 @@ impl<'a> Draw<ExchangePaneProps<'a>> for ExchangePane {
             )
         };
         match props.request_state {
-            None => frame.render_widget(
-                "No request history for this recipe & profile",
-                area,
+            None => match props.example {
+                Some(example) => frame.render_widget(
+                    Paragraph::new(example_text(example)).wrap(Wrap::default()),
+                    area,
+                ),
+                None => frame.render_widget(
+                    "No request history for this recipe & profile",
+                    area,
+                ),
+            },
+            Some(RequestState::Building { .. }) => frame.render_widget(
+                format!("Initializing request... {}", cancel_hint()),
+                content_area,
             ),
-            Some(RequestState::Building { .. }) => {
-                frame.render_widget("Initializing request...", content_area)
-            }
             Some(RequestState::BuildError { error, .. }) => {
                 frame.render_widget(error.generate(), content_area)
             }
-            Some(RequestState::Loading { request, .. }) => {
+            Some(RequestState::Loading {
+                request, progress, ..
+            }) => {
                 render_tabs(frame);
                 match selected_tab {
                     Tab::Request => render_request(frame, request),
-                    Tab::Body | Tab::Headers => {
-                        frame.render_widget("Loading...", content_area)
+                    Tab::Body
+                    | Tab::Data
+                    | Tab::Errors
+                    | Tab::Headers
+                    | Tab::Timing => {
+                        let text = match progress {
+                            Some(progress) => format!(
+                                "Downloading... {progress} {}",
+                                cancel_hint()
+                            ),
+                            None => format!("Loading... {}", cancel_hint()),
+                        };
+                        frame.render_widget(text, content_area)
                     }
                 }
             }
@@ -210,7 +341,7 @@ impl<'a> Draw<ExchangePaneProps<'a>> for ExchangePane {
                 render_tabs(frame);
                 match selected_tab {
                     Tab::Request => render_request(frame, &exchange.request),
-                    Tab::Body => {
+                    Tab::Body | Tab::Data => {
                         // Don't draw body if empty, so we don't have to set
                         // up state, and don't offer impossible actions
                         if !exchange.response.body.bytes().is_empty() {
@@ -220,6 +351,7 @@ impl<'a> Draw<ExchangePaneProps<'a>> for ExchangePane {
                                     request_id: exchange.id,
                                     recipe_id: &exchange.request.recipe_id,
                                     response: Arc::clone(&exchange.response),
+                                    graphql: graphql_response.is_some(),
                                 },
                                 content_area,
                                 true,
@@ -231,6 +363,19 @@ impl<'a> Draw<ExchangePaneProps<'a>> for ExchangePane {
                             );
                         }
                     }
+                    Tab::Errors => {
+                        if let Some(response) = graphql_response {
+                            self.graphql_errors.draw(
+                                frame,
+                                GraphQlErrorsViewProps {
+                                    request_id: exchange.id,
+                                    errors: &response.errors,
+                                },
+                                content_area,
+                                true,
+                            );
+                        }
+                    }
                     Tab::Headers => self.response_headers.draw(
                         frame,
                         ResponseHeadersViewProps {
@@ -239,13 +384,25 @@ impl<'a> Draw<ExchangePaneProps<'a>> for ExchangePane {
                         content_area,
                         true,
                     ),
+                    Tab::Timing => self.timing.draw(
+                        frame,
+                        TimingViewProps {
+                            timing: exchange.timing.as_ref(),
+                        },
+                        content_area,
+                        true,
+                    ),
                 }
             }
             Some(RequestState::RequestError { error }) => {
                 render_tabs(frame);
                 match selected_tab {
                     Tab::Request => render_request(frame, &error.request),
-                    Tab::Body | Tab::Headers => {
+                    Tab::Body
+                    | Tab::Data
+                    | Tab::Errors
+                    | Tab::Headers
+                    | Tab::Timing => {
                         frame.render_widget(error.generate(), content_area)
                     }
                 }
@@ -253,3 +410,40 @@ impl<'a> Draw<ExchangePaneProps<'a>> for ExchangePane {
         }
     }
 }
+
+/// Placeholder for a recipe with no history, showing its example response
+/// instead
+fn example_text(example: &ResponseExample) -> Text<'_> {
+    let styles = &TuiContext::get().styles;
+    let mut lines = vec![
+        Line::styled(
+            "No request history for this recipe & profile; showing example",
+            styles.text.hint,
+        ),
+        Line::default(),
+        Line::styled(example.status.to_string(), styles.text.title),
+    ];
+    lines.extend(
+        example
+            .headers
+            .iter()
+            .map(|(name, value)| Line::from(format!("{name}: {value}"))),
+    );
+    lines.push(Line::default());
+    if let Some(file) = &example.file {
+        lines.push(Line::styled(
+            format!("Body loaded from {}", file.display()),
+            styles.text.hint,
+        ));
+    }
+    lines.extend(example.body.lines().map(Line::from));
+    lines.into()
+}
+
+/// Tell the user how to cancel an in-progress request
+fn cancel_hint() -> String {
+    let binding = TuiContext::get()
+        .input_engine
+        .binding_display(Action::CancelRequest);
+    format!("({binding} to cancel)")
+}
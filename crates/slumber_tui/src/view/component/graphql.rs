@@ -0,0 +1,134 @@
+//! Display for the parts of a GraphQL response
+
+use crate::{
+    context::TuiContext,
+    view::{
+        common::text_window::{TextWindow, TextWindowProps},
+        component::Component,
+        draw::{Draw, DrawMetadata},
+        event::{Child, EventHandler},
+        state::StateCell,
+    },
+};
+use ratatui::{
+    text::{Line, Text},
+    Frame,
+};
+use slumber_core::http::{graphql::GraphQlError, RequestId};
+
+/// Summarize the errors in a GraphQL response, e.g. "2 GraphQL Errors"
+pub fn title(count: usize) -> String {
+    let noun = if count == 1 { "Error" } else { "Errors" };
+    format!("{count} GraphQL {noun}")
+}
+
+/// List the errors from a GraphQL response
+#[derive(Debug, Default)]
+pub struct GraphQlErrorsView {
+    /// Rendered errors. Update whenever the selected request changes
+    text: StateCell<RequestId, Text<'static>>,
+    text_window: Component<TextWindow>,
+}
+
+pub struct GraphQlErrorsViewProps<'a> {
+    pub request_id: RequestId,
+    pub errors: &'a [GraphQlError],
+}
+
+impl EventHandler for GraphQlErrorsView {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.text_window.to_child_mut()]
+    }
+}
+
+impl<'a> Draw<GraphQlErrorsViewProps<'a>> for GraphQlErrorsView {
+    fn draw(
+        &self,
+        frame: &mut Frame,
+        props: GraphQlErrorsViewProps<'a>,
+        metadata: DrawMetadata,
+    ) {
+        if props.errors.is_empty() {
+            frame.render_widget("No errors", metadata.area());
+            return;
+        }
+
+        let text = self
+            .text
+            .get_or_update(&props.request_id, || errors_text(props.errors));
+        self.text_window.draw(
+            frame,
+            TextWindowProps {
+                text: &text,
+                marks: &[],
+                margins: Default::default(),
+                footer: None,
+            },
+            metadata.area(),
+            true,
+        );
+    }
+}
+
+/// Render each error with its message highlighted, followed by whatever
+/// context the server provided
+fn errors_text(errors: &[GraphQlError]) -> Text<'static> {
+    let styles = &TuiContext::get().styles;
+    let mut lines: Vec<Line<'static>> = Vec::new();
+    for (i, error) in errors.iter().enumerate() {
+        if i > 0 {
+            lines.push(Line::default());
+        }
+        let text = error.to_string();
+        let mut error_lines = text.lines();
+        if let Some(message) = error_lines.next() {
+            lines.push(Line::styled(message.to_owned(), styles.text.error));
+        }
+        lines.extend(error_lines.map(|line| Line::from(line.to_owned())));
+    }
+    lines.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{harness, TestHarness};
+    use rstest::rstest;
+    use slumber_core::http::graphql::{Location, PathSegment};
+
+    #[rstest]
+    fn test_errors_text(_harness: TestHarness) {
+        let errors = [
+            GraphQlError {
+                message: "Name is private".into(),
+                path: vec![
+                    PathSegment::Field("users".into()),
+                    PathSegment::Index(0),
+                    PathSegment::Field("name".into()),
+                ],
+                locations: vec![Location {
+                    line: 1,
+                    column: 11,
+                }],
+                extensions: None,
+            },
+            GraphQlError {
+                message: "Rate limited".into(),
+                path: vec![],
+                locations: vec![],
+                extensions: None,
+            },
+        ];
+        let styles = &TuiContext::get().styles;
+        assert_eq!(
+            errors_text(&errors),
+            Text::from(vec![
+                Line::styled("Name is private", styles.text.error),
+                Line::from("  Path: users[0].name"),
+                Line::from("  Location: line 1, column 11"),
+                Line::default(),
+                Line::styled("Rate limited", styles.text.error),
+            ])
+        );
+    }
+}
@@ -1,27 +1,44 @@
 use crate::{
     context::TuiContext,
+    message::Message,
     util::ResultReported,
     view::{
         common::{list::List, modal::Modal},
-        component::Component,
+        component::{response_diff::compare_responses, Component},
         draw::{Draw, DrawMetadata, Generate},
-        event::{Child, Event, EventHandler},
+        event::{Child, Event, EventHandler, Update},
         state::{select::SelectState, RequestStateSummary},
         ViewContext,
     },
 };
+use chrono::{DateTime, Utc};
+use derive_more::Display;
 use ratatui::{
     layout::Constraint,
     text::{Line, Span},
     Frame,
 };
-use slumber_core::{collection::RecipeId, http::RequestId};
+use slumber_config::Action;
+use slumber_core::{
+    collection::RecipeId,
+    http::{failure::FailureKind, RequestId},
+    util::format_time,
+};
+use strum::IntoEnumIterator;
 
-/// Browse request/response history for a recipe
+/// Browse request/response history for a recipe. Two responses can be
+/// compared by marking one, then selecting the other. The list can be filtered
+/// to failed requests, or to one kind of failure.
 #[derive(Debug)]
 pub struct History {
     recipe_name: String,
+    /// Every request for the recipe, so the list can be re-filtered
+    requests: Vec<RequestStateSummary>,
+    filter: HistoryFilter,
     select: Component<SelectState<RequestStateSummary>>,
+    /// ID and time of the response marked to be compared against the next
+    /// selected one
+    compare: Option<(RequestId, DateTime<Utc>)>,
 }
 
 impl History {
@@ -38,44 +55,105 @@ impl History {
             .reported(&ViewContext::messages_tx())
             .map(|recipe| recipe.name().to_owned())
             .unwrap_or_else(|| recipe_id.to_string());
-        let select = SelectState::builder(requests)
-            .preselect_opt(selected_request_id.as_ref())
+        let filter = HistoryFilter::default();
+        let select =
+            Self::build_select(&requests, filter, selected_request_id.as_ref());
+        Self {
+            recipe_name,
+            requests,
+            filter,
+            select: select.into(),
+            compare: None,
+        }
+    }
+
+    fn build_select(
+        requests: &[RequestStateSummary],
+        filter: HistoryFilter,
+        selected_request_id: Option<&RequestId>,
+    ) -> SelectState<RequestStateSummary> {
+        let requests = requests
+            .iter()
+            .filter(|request| filter.matches(request))
+            .cloned()
+            .collect();
+        SelectState::builder(requests)
+            .preselect_opt(selected_request_id)
             // When an item is selected, load it up
             .on_select(|exchange| {
                 ViewContext::push_event(Event::HttpSelectRequest(Some(
                     exchange.id(),
                 )))
             })
-            .build();
+            .build()
+    }
 
-        Self {
-            recipe_name,
-            select: select.into(),
+    /// Switch to the next filter, keeping the selected request if it's still
+    /// in the list
+    fn cycle_filter(&mut self) {
+        self.filter = self.filter.next();
+        let selected =
+            self.select.data().selected().map(|request| request.id());
+        self.select =
+            Self::build_select(&self.requests, self.filter, selected.as_ref())
+                .into();
+    }
+
+    /// Mark the selected response for comparison. If one is already marked,
+    /// compare the two instead. Marking the same response again unmarks it.
+    fn toggle_compare(&mut self) {
+        let Some(selected) = self.select.data().selected() else {
+            return;
+        };
+        if !matches!(selected, RequestStateSummary::Response(_)) {
+            ViewContext::send_message(Message::Notify(
+                "Only completed responses can be compared".into(),
+            ));
+            return;
+        }
+        match self.compare.take() {
+            None => self.compare = Some((selected.id(), selected.time())),
+            Some((marked, _)) if marked == selected.id() => {}
+            Some((marked, _)) => compare_responses(marked, selected.id()),
         }
     }
 }
 
 impl Modal for History {
     fn title(&self) -> Line<'_> {
-        vec![
+        if let Some((_, time)) = &self.compare {
+            return format!("Compare with {}", format_time(time)).into();
+        }
+        let styles = &TuiContext::get().styles;
+        let mut spans = vec![
             "History for ".into(),
-            Span::styled(
-                self.recipe_name.as_str(),
-                TuiContext::get().styles.text.primary,
-            ),
-        ]
-        .into()
+            Span::styled(self.recipe_name.as_str(), styles.text.primary),
+        ];
+        if self.filter != HistoryFilter::All {
+            spans.push(format!(" ({})", self.filter).into());
+        }
+        spans.into()
     }
 
     fn dimensions(&self) -> (Constraint, Constraint) {
         (
-            Constraint::Length(40),
-            Constraint::Length(self.select.data().len().min(20) as u16),
+            Constraint::Length(50),
+            // Leave a line for the empty message
+            Constraint::Length(self.select.data().len().clamp(1, 20) as u16),
         )
     }
 }
 
 impl EventHandler for History {
+    fn update(&mut self, event: Event) -> Update {
+        match event.action() {
+            Some(Action::Toggle) => self.toggle_compare(),
+            Some(Action::Search) => self.cycle_filter(),
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+
     fn children(&mut self) -> Vec<Component<Child<'_>>> {
         vec![self.select.to_child_mut()]
     }
@@ -83,6 +161,16 @@ impl EventHandler for History {
 
 impl Draw for History {
     fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        if self.select.data().is_empty() {
+            frame.render_widget(
+                Span::styled(
+                    "No matching requests",
+                    TuiContext::get().styles.text.hint,
+                ),
+                metadata.area(),
+            );
+            return;
+        }
         self.select.draw(
             frame,
             List::from(self.select.data()),
@@ -109,11 +197,59 @@ impl Generate for &RequestStateSummary {
             RequestStateSummary::Response(exchange) => {
                 exchange.status.generate()
             }
-            RequestStateSummary::RequestError { .. } => {
-                Span::styled("Request error", styles.text.error)
+            RequestStateSummary::RequestError { kind, .. } => {
+                Span::styled(kind.description(), styles.text.error)
+            }
+        };
+        let mut spans = vec![self.time().generate(), " ".into(), description];
+        if let RequestStateSummary::Response(summary) = self {
+            // Flag requests that were sent automatically, so they aren't a
+            // mystery
+            if let Some(trigger) = &summary.trigger {
+                spans.push(Span::styled(
+                    format!(" via {}", trigger.chain_id),
+                    styles.text.hint,
+                ));
+            }
+            if let Some(note) = &summary.note {
+                spans.push(format!(" {note}").into());
             }
+        }
+        spans.into()
+    }
+}
+
+/// Which requests to show in the history list
+#[derive(Copy, Clone, Debug, Default, Display, PartialEq)]
+enum HistoryFilter {
+    #[default]
+    All,
+    /// Any request that didn't get a response
+    Failed,
+    /// Requests that failed for one particular reason
+    #[display("{}", _0.description())]
+    Kind(FailureKind),
+}
+
+impl HistoryFilter {
+    /// Get the next filter in the cycle: all, failed, then each kind of
+    /// failure
+    fn next(self) -> Self {
+        let mut kinds = FailureKind::iter();
+        let next_kind = match self {
+            Self::All => return Self::Failed,
+            Self::Failed => kinds.next(),
+            Self::Kind(kind) => kinds.skip_while(|other| *other != kind).nth(1),
         };
-        vec![self.time().generate(), " ".into(), description].into()
+        next_kind.map(Self::Kind).unwrap_or(Self::All)
+    }
+
+    fn matches(self, request: &RequestStateSummary) -> bool {
+        match self {
+            Self::All => true,
+            Self::Failed => request.failure_kind().is_some(),
+            Self::Kind(kind) => request.failure_kind() == Some(kind),
+        }
     }
 }
 
@@ -123,3 +259,142 @@ impl PartialEq<RequestStateSummary> for RequestId {
         self == &other.id()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{harness, terminal, TestHarness, TestTerminal},
+        view::test_util::TestComponent,
+    };
+    use crossterm::event::KeyCode;
+    use rstest::rstest;
+    use slumber_core::{
+        assert_matches,
+        http::{Exchange, ExchangeSummary, ResponseRecord},
+        test_util::Factory,
+    };
+
+    /// Mark one response, then select another to compare them
+    #[rstest]
+    fn test_compare(harness: TestHarness, terminal: TestTerminal) {
+        let recipe_id = RecipeId::factory(());
+        let old = Exchange::factory(recipe_id.clone());
+        let new = Exchange {
+            response: ResponseRecord {
+                body: b"changed".to_vec().into(),
+                ..ResponseRecord::factory(())
+            }
+            .into(),
+            ..Exchange::factory(recipe_id.clone())
+        };
+        for exchange in [&old, &new] {
+            harness.database.insert_exchange(exchange).unwrap();
+        }
+        let summaries = [&new, &old]
+            .map(|exchange| {
+                RequestStateSummary::Response(ExchangeSummary::from(exchange))
+            })
+            .into();
+
+        let mut component = TestComponent::new(
+            &terminal,
+            History::new(&recipe_id, summaries, None),
+            (),
+        );
+        component.send_key(KeyCode::Char(' ')).assert_empty();
+        assert_eq!(component.data().compare, Some((new.id, new.start_time)));
+
+        // Marking the same response again unmarks it
+        component.send_key(KeyCode::Char(' ')).assert_empty();
+        assert_eq!(component.data().compare, None);
+
+        component.send_key(KeyCode::Char(' ')).assert_empty();
+        assert_matches!(
+            component.send_key(KeyCode::Down).events(),
+            [Event::HttpSelectRequest(Some(id))] if *id == old.id
+        );
+        let events = component.send_key(KeyCode::Char(' '));
+        assert_matches!(events.events(), [Event::OpenModal(_)]);
+        assert_eq!(component.data().compare, None);
+    }
+
+    /// Cycle through filters to narrow the list to failed requests
+    #[rstest]
+    fn test_filter(_harness: TestHarness, terminal: TestTerminal) {
+        let recipe_id = RecipeId::factory(());
+        let exchange = Exchange::factory(recipe_id.clone());
+        let dns_id = RequestId::new();
+        let build_id = RequestId::new();
+        let summaries = vec![
+            RequestStateSummary::Response(ExchangeSummary::from(&exchange)),
+            RequestStateSummary::RequestError {
+                id: dns_id,
+                time: Utc::now(),
+                kind: FailureKind::Dns,
+            },
+            RequestStateSummary::BuildError {
+                id: build_id,
+                start_time: Utc::now(),
+                end_time: Utc::now(),
+            },
+        ];
+
+        let mut component = TestComponent::new(
+            &terminal,
+            History::new(&recipe_id, summaries, None),
+            (),
+        );
+        let ids = |component: &TestComponent<'_, History, ()>| {
+            component
+                .data()
+                .select
+                .data()
+                .items()
+                .map(RequestStateSummary::id)
+                .collect::<Vec<_>>()
+        };
+
+        // The first request that matches the filter is selected
+        assert_matches!(
+            component.send_key(KeyCode::Char('/')).events(),
+            [Event::HttpSelectRequest(Some(id))] if *id == dns_id
+        );
+        assert_eq!(component.data().filter, HistoryFilter::Failed);
+        assert_eq!(ids(&component), vec![dns_id, build_id]);
+
+        assert_matches!(
+            component.send_key(KeyCode::Char('/')).events(),
+            [Event::HttpSelectRequest(Some(id))] if *id == build_id
+        );
+        assert_eq!(
+            component.data().filter,
+            HistoryFilter::Kind(FailureKind::Build)
+        );
+        assert_eq!(ids(&component), vec![build_id]);
+
+        assert_matches!(
+            component.send_key(KeyCode::Char('/')).events(),
+            [Event::HttpSelectRequest(Some(id))] if *id == dns_id
+        );
+        assert_eq!(
+            component.data().filter,
+            HistoryFilter::Kind(FailureKind::Dns)
+        );
+        assert_eq!(ids(&component), vec![dns_id]);
+    }
+
+    #[test]
+    fn test_filter_cycle() {
+        let mut filter = HistoryFilter::All;
+        let mut filters = vec![];
+        loop {
+            filter = filter.next();
+            if filter == HistoryFilter::All {
+                break;
+            }
+            filters.push(filter);
+        }
+        assert_eq!(filters.len(), FailureKind::iter().count() + 1);
+    }
+}
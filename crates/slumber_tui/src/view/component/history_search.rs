@@ -0,0 +1,303 @@
+//! Full-text search over request history
+
+use crate::{
+    context::TuiContext,
+    util::ResultReported,
+    view::{
+        common::{list::List, modal::Modal, text_box::TextBox},
+        component::Component,
+        draw::{Draw, DrawMetadata, Generate},
+        event::{Child, Event, EventHandler, Update},
+        state::select::SelectState,
+        ViewContext,
+    },
+};
+use ratatui::{
+    layout::{Constraint, Layout},
+    text::{Line, Span},
+    widgets::{Paragraph, Wrap},
+    Frame,
+};
+use slumber_config::Action;
+use slumber_core::db::SearchMatch;
+
+/// Maximum number of matches to show for a search
+const SEARCH_LIMIT: usize = 100;
+
+/// Search request history across all recipes and profiles. Matches are listed
+/// newest first. Submitting one loads it into the exchange pane.
+#[derive(Debug)]
+pub struct HistorySearch {
+    text_box: Component<TextBox>,
+    /// Are we typing in the search box? If not, the result list has focus
+    text_box_focused: bool,
+    /// `None` until the first search is run
+    results: Option<Component<SelectState<SearchMatch>>>,
+}
+
+/// Local event to modify state from text box callbacks
+#[derive(Debug)]
+enum SearchCallback {
+    Submit,
+    Cancel,
+}
+
+impl HistorySearch {
+    pub fn new() -> Self {
+        let text_box = TextBox::default()
+            .placeholder("Search URLs, headers, and bodies")
+            .on_submit(|| {
+                ViewContext::push_event(Event::new_local(
+                    SearchCallback::Submit,
+                ))
+            })
+            .on_cancel(|| {
+                ViewContext::push_event(Event::new_local(
+                    SearchCallback::Cancel,
+                ))
+            });
+        Self {
+            text_box: text_box.into(),
+            text_box_focused: true,
+            results: None,
+        }
+    }
+
+    /// Run the search for the current query, and move focus to the results
+    /// if there are any
+    fn search(&mut self) {
+        let query = self.text_box.data().text();
+        let Some(matches) = ViewContext::with_database(|database| {
+            database.search_requests(query, SEARCH_LIMIT)
+        })
+        .reported(&ViewContext::messages_tx()) else {
+            return;
+        };
+        let select = SelectState::builder(matches)
+            .on_submit(|search_match| {
+                ViewContext::push_event(Event::CloseModal { submitted: true });
+                ViewContext::push_event(Event::HttpSelectRequest(Some(
+                    search_match.id,
+                )));
+            })
+            .build();
+        self.text_box_focused = select.is_empty();
+        self.results = Some(select.into());
+    }
+
+    fn has_results(&self) -> bool {
+        self.results
+            .as_ref()
+            .is_some_and(|results| !results.data().is_empty())
+    }
+}
+
+impl Default for HistorySearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Modal for HistorySearch {
+    fn title(&self) -> Line<'_> {
+        "Search History".into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(80), Constraint::Percentage(60))
+    }
+}
+
+impl EventHandler for HistorySearch {
+    fn update(&mut self, event: Event) -> Update {
+        if let Some(callback) = event.local::<SearchCallback>() {
+            match callback {
+                SearchCallback::Submit => self.search(),
+                // Leave the search box. If there's nothing else to look at,
+                // close the modal entirely
+                SearchCallback::Cancel if self.has_results() => {
+                    self.text_box_focused = false
+                }
+                SearchCallback::Cancel => {
+                    ViewContext::push_event(Event::CloseModal {
+                        submitted: false,
+                    })
+                }
+            }
+        } else if let Some(Action::Search) = event.action() {
+            self.text_box_focused = true;
+        } else {
+            return Update::Propagate(event);
+        }
+        Update::Consumed
+    }
+
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        // Only one of the two gets input at a time, otherwise the text box
+        // would eat keys meant for the list and vice versa
+        if self.text_box_focused {
+            vec![self.text_box.to_child_mut()]
+        } else {
+            self.results
+                .iter_mut()
+                .map(Component::to_child_mut)
+                .collect()
+        }
+    }
+}
+
+impl Draw for HistorySearch {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let styles = &TuiContext::get().styles;
+        let [text_box_area, list_area, detail_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(4),
+        ])
+        .spacing(1)
+        .areas(metadata.area());
+
+        self.text_box
+            .draw(frame, (), text_box_area, self.text_box_focused);
+
+        let Some(results) = &self.results else {
+            let binding = TuiContext::get()
+                .input_engine
+                .binding_display(Action::Submit);
+            frame.render_widget(
+                Span::styled(format!("{binding} to search"), styles.text.hint),
+                list_area,
+            );
+            return;
+        };
+        if results.data().is_empty() {
+            frame.render_widget("No matching requests", list_area);
+            return;
+        }
+        results.draw(
+            frame,
+            List::from(results.data()),
+            list_area,
+            !self.text_box_focused,
+        );
+
+        if let Some(search_match) = results.data().selected() {
+            frame.render_widget(
+                Paragraph::new(vec![
+                    format!("{} {}", search_match.method, search_match.url)
+                        .into(),
+                    Line::styled(
+                        search_match.snippet.as_str(),
+                        styles.text.hint,
+                    ),
+                ])
+                .wrap(Wrap::default()),
+                detail_area,
+            );
+        }
+    }
+}
+
+impl Generate for &SearchMatch {
+    type Output<'this> = Line<'this> where Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        let styles = &TuiContext::get().styles;
+        let mut spans = vec![
+            self.start_time.generate(),
+            " ".into(),
+            self.status.generate(),
+            " ".into(),
+            Span::styled(self.recipe_id.to_string(), styles.text.primary),
+        ];
+        if let Some(profile_id) = &self.profile_id {
+            spans.push(format!(" ({profile_id})").into());
+        }
+        spans.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{harness, terminal, TestHarness, TestTerminal},
+        view::test_util::TestComponent,
+    };
+    use crossterm::event::KeyCode;
+    use rstest::rstest;
+    use slumber_core::{
+        assert_matches,
+        http::{Exchange, RequestRecord, ResponseRecord},
+        test_util::Factory,
+    };
+
+    /// Type a query, submit it, then submit one of the results
+    #[rstest]
+    fn test_search(harness: TestHarness, terminal: TestTerminal) {
+        let exchange = |url: &str| {
+            Exchange::factory((
+                RequestRecord {
+                    url: url.parse().unwrap(),
+                    ..RequestRecord::factory((None, "recipe1".into()))
+                },
+                ResponseRecord::factory(()),
+            ))
+        };
+        let old = exchange("http://localhost/users/frodo");
+        let new = exchange("http://localhost/users/sam");
+        let other = exchange("http://localhost/fellowship");
+        for exchange in [&old, &new, &other] {
+            harness.database.insert_exchange(exchange).unwrap();
+        }
+
+        let mut component =
+            TestComponent::new(&terminal, HistorySearch::new(), ());
+        for c in "users".chars() {
+            component.send_key(KeyCode::Char(c)).assert_empty();
+        }
+        component.send_key(KeyCode::Enter).assert_empty();
+        // Focus moved to the results, newest first
+        let data = component.data();
+        assert!(!data.text_box_focused);
+        let ids: Vec<_> = data
+            .results
+            .as_ref()
+            .unwrap()
+            .data()
+            .items()
+            .map(|search_match| search_match.id)
+            .collect();
+        assert_eq!(ids, vec![new.id, old.id]);
+
+        component.send_key(KeyCode::Down).assert_empty();
+        assert_matches!(
+            component.send_key(KeyCode::Enter).events(),
+            &[
+                Event::CloseModal { submitted: true },
+                Event::HttpSelectRequest(Some(id))
+            ] if id == old.id
+        );
+    }
+
+    /// With no matches, focus stays in the search box
+    #[rstest]
+    fn test_search_no_matches(_harness: TestHarness, terminal: TestTerminal) {
+        let mut component =
+            TestComponent::new(&terminal, HistorySearch::new(), ());
+        component.send_key(KeyCode::Char('x')).assert_empty();
+        component.send_key(KeyCode::Enter).assert_empty();
+        let data = component.data();
+        assert!(data.text_box_focused);
+        assert!(data.results.as_ref().unwrap().data().is_empty());
+
+        // Cancel closes the modal since there's nothing to look at
+        assert_matches!(
+            component.send_key(KeyCode::Esc).events(),
+            &[Event::CloseModal { submitted: false }]
+        );
+    }
+}
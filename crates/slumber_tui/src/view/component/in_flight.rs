@@ -0,0 +1,192 @@
+//! Components for juggling multiple concurrent requests: a footer indicator
+//! of how many are in flight, and a list to switch between them
+
+use crate::{
+    context::TuiContext,
+    view::{
+        common::{list::List, modal::Modal},
+        draw::{Draw, DrawMetadata, Generate},
+        event::{Child, Event, EventHandler},
+        state::{select::SelectState, RequestState, RequestStateSummary},
+        Component, ViewContext,
+    },
+};
+use ratatui::{
+    layout::Constraint,
+    text::{Line, Span},
+    Frame,
+};
+use slumber_config::Action;
+use slumber_core::{collection::ProfileId, http::RequestId};
+
+/// Maximum number of requests to show in the list
+pub const IN_FLIGHT_LIMIT: usize = 20;
+
+/// Footer text showing how many requests are in flight, with a hint for the
+/// request list. Empty if nothing is in flight
+pub struct InFlightText(pub usize);
+
+impl Generate for InFlightText {
+    type Output<'this> = Line<'this>
+    where
+        Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        if self.0 == 0 {
+            return Line::default();
+        }
+        let tui_context = TuiContext::get();
+        let text = tui_context
+            .input_engine
+            .add_hint(format!("⟳ {} in flight", self.0), Action::InFlight);
+        Line::styled(text, tui_context.styles.text.hint)
+    }
+}
+
+/// Modal listing requests sent during this session, newest first. Requests
+/// stay in the list after they land, so the user can flip between results
+/// while others are still loading.
+#[derive(Debug)]
+pub struct InFlightModal {
+    in_flight: usize,
+    select: Component<SelectState<SessionRequest>>,
+}
+
+impl InFlightModal {
+    /// Parent is responsible for collecting the requests from the request
+    /// store
+    pub fn new(
+        requests: Vec<SessionRequest>,
+        selected_request_id: Option<RequestId>,
+    ) -> Self {
+        let in_flight = requests
+            .iter()
+            .filter(|request| request.is_in_flight())
+            .count();
+        let select = SelectState::builder(requests)
+            .preselect_opt(selected_request_id.as_ref())
+            // Load each request as it's highlighted, same as history
+            .on_select(|request| {
+                ViewContext::push_event(Event::HttpSelectRequest(Some(
+                    request.summary.id(),
+                )))
+            })
+            .on_submit(|_| {
+                ViewContext::push_event(Event::CloseModal { submitted: true })
+            })
+            .build();
+        Self {
+            in_flight,
+            select: select.into(),
+        }
+    }
+}
+
+impl Modal for InFlightModal {
+    fn title(&self) -> Line<'_> {
+        format!("Requests ({} in flight)", self.in_flight).into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Length(60),
+            // Leave a line for the empty message
+            Constraint::Length(
+                self.select.data().len().clamp(1, IN_FLIGHT_LIMIT) as u16,
+            ),
+        )
+    }
+}
+
+impl EventHandler for InFlightModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.select.to_child_mut()]
+    }
+}
+
+impl Draw for InFlightModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        if self.select.data().is_empty() {
+            frame.render_widget(
+                Span::styled(
+                    "No requests sent yet",
+                    TuiContext::get().styles.text.hint,
+                ),
+                metadata.area(),
+            );
+            return;
+        }
+        self.select.draw(
+            frame,
+            List::from(self.select.data()),
+            metadata.area(),
+            true,
+        );
+    }
+}
+
+/// A request sent during this session, for any recipe
+#[derive(Debug)]
+pub struct SessionRequest {
+    summary: RequestStateSummary,
+    recipe_name: String,
+    profile_id: Option<ProfileId>,
+}
+
+impl SessionRequest {
+    fn is_in_flight(&self) -> bool {
+        matches!(
+            self.summary,
+            RequestStateSummary::Building { .. }
+                | RequestStateSummary::Loading { .. }
+        )
+    }
+}
+
+impl From<&RequestState> for SessionRequest {
+    fn from(state: &RequestState) -> Self {
+        let recipe_id = state.recipe_id();
+        // Scratch requests, or recipes removed by a reload, won't be found
+        let recipe_name = ViewContext::collection()
+            .recipes
+            .get_recipe(recipe_id)
+            .map(|recipe| recipe.name().to_owned())
+            .unwrap_or_else(|| recipe_id.to_string());
+        Self {
+            summary: state.into(),
+            recipe_name,
+            profile_id: state.profile_id().cloned(),
+        }
+    }
+}
+
+/// Allow selection by ID
+impl PartialEq<SessionRequest> for RequestId {
+    fn eq(&self, other: &SessionRequest) -> bool {
+        self == &other.summary.id()
+    }
+}
+
+impl Generate for &SessionRequest {
+    type Output<'this> = Line<'this> where Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        let styles = &TuiContext::get().styles;
+        let mut line = self.summary.generate();
+        line.push_span(" ");
+        line.push_span(Span::styled(
+            self.recipe_name.as_str(),
+            styles.text.primary,
+        ));
+        if let Some(profile_id) = &self.profile_id {
+            line.push_span(format!(" ({profile_id})"));
+        }
+        line
+    }
+}
@@ -1,18 +1,22 @@
 //! Miscellaneous components. They have specific purposes and therefore aren't
 //! generic/utility, but don't fall into a clear category.
 
-use crate::view::{
-    common::{
-        button::ButtonGroup,
-        list::List,
-        modal::{IntoModal, Modal},
-        text_box::TextBox,
+use crate::{
+    context::TuiContext,
+    view::{
+        common::{
+            button::ButtonGroup,
+            list::List,
+            modal::{IntoModal, Modal},
+            text_box::TextBox,
+            text_window::{TextWindow, TextWindowProps},
+        },
+        component::Component,
+        draw::{Draw, DrawMetadata, Generate},
+        event::{Child, Event, EventHandler, Update},
+        state::{select::SelectState, Notification},
+        Confirm, ModalPriority, ViewContext,
     },
-    component::Component,
-    draw::{Draw, DrawMetadata, Generate},
-    event::{Child, Event, EventHandler, Update},
-    state::{select::SelectState, Notification},
-    Confirm, ModalPriority, ViewContext,
 };
 use derive_more::Display;
 use ratatui::{
@@ -21,7 +25,10 @@ use ratatui::{
     widgets::{Paragraph, Wrap},
     Frame,
 };
-use slumber_core::template::{Prompt, Select};
+use slumber_core::{
+    template::{Prompt, Select},
+    util::git::GitStatus,
+};
 use strum::{EnumCount, EnumIter};
 
 #[derive(Debug)]
@@ -242,6 +249,68 @@ impl IntoModal for Select {
     }
 }
 
+/// Show a list of problems, e.g. differences from a baseline, one per line
+#[derive(Debug)]
+pub struct ErrorListModal {
+    title: String,
+    count: usize,
+    text: Text<'static>,
+    text_window: Component<TextWindow>,
+}
+
+impl ErrorListModal {
+    pub fn new<T: ToString>(
+        title: String,
+        items: impl IntoIterator<Item = T>,
+    ) -> Self {
+        let lines: Vec<Line<'static>> = items
+            .into_iter()
+            .map(|item| Line::from(item.to_string()))
+            .collect();
+        Self {
+            title,
+            count: lines.len(),
+            text: lines.into(),
+            text_window: Default::default(),
+        }
+    }
+}
+
+impl Modal for ErrorListModal {
+    fn title(&self) -> Line<'_> {
+        Line::styled(self.title.as_str(), TuiContext::get().styles.text.error)
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Percentage(60),
+            Constraint::Length(self.count.min(20) as u16),
+        )
+    }
+}
+
+impl EventHandler for ErrorListModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.text_window.to_child_mut()]
+    }
+}
+
+impl Draw for ErrorListModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        self.text_window.draw(
+            frame,
+            TextWindowProps {
+                text: &self.text,
+                marks: &[],
+                margins: Default::default(),
+                footer: None,
+            },
+            metadata.area(),
+            true,
+        );
+    }
+}
+
 /// Inner state for the prompt modal
 #[derive(derive_more::Debug)]
 pub struct ConfirmModal {
@@ -350,3 +419,53 @@ impl Draw for NotificationText {
         );
     }
 }
+
+/// Banner shown above the panes when the collection file failed to reload.
+/// The error is flattened onto one line so it doesn't push the panes around.
+#[derive(Debug)]
+pub struct CollectionErrorText<'a>(pub &'a anyhow::Error);
+
+impl<'a> Generate for CollectionErrorText<'a> {
+    type Output<'this> = Line<'this>
+    where
+        Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        let error = format!("{:#}", self.0).replace('\n', " ");
+        Line::styled(
+            format!(
+                "Error reloading collection (showing last valid version): \
+                {error}"
+            ),
+            TuiContext::get().styles.text.error,
+        )
+    }
+}
+
+/// Footer indicator for the collection file's git status. Empty if the file is
+/// in sync with HEAD and upstream.
+#[derive(Debug)]
+pub struct GitStatusText<'a>(pub &'a GitStatus);
+
+impl<'a> Generate for GitStatusText<'a> {
+    type Output<'this> = Line<'this>
+    where
+        Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        let mut parts = Vec::new();
+        if self.0.uncommitted {
+            parts.push("● Uncommitted changes");
+        }
+        if self.0.upstream_differs {
+            parts.push("⇅ Differs from upstream");
+        }
+        Line::styled(parts.join(" "), TuiContext::get().styles.text.hint)
+    }
+}
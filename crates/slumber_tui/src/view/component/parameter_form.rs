@@ -0,0 +1,274 @@
+//! Form to collect values for a recipe's parameters before sending it
+
+use crate::{
+    message::{Message, RequestConfig},
+    view::{
+        common::{
+            form::{self, Form, FormField, SubmitForm},
+            modal::Modal,
+            text_box::TextBox,
+        },
+        component::Component,
+        draw::{Draw, DrawMetadata},
+        event::{Child, Event, EventHandler, Update},
+        util::persistence::DatabasePersistedStore,
+        ViewContext,
+    },
+};
+use indexmap::IndexMap;
+use persisted::PersistedStore;
+use ratatui::{layout::Constraint, text::Line, Frame};
+use serde::Serialize;
+use slumber_core::{
+    collection::{Recipe, RecipeId, RecipeParameter},
+    http::BuildOptions,
+};
+
+/// Send a request. If the recipe declares any parameters, open a form to
+/// collect their values first
+pub fn send_request(config: RequestConfig) {
+    let collection = ViewContext::collection();
+    match collection.recipes.get_recipe(&config.recipe_id) {
+        Some(recipe) if !recipe.parameters.is_empty() => {
+            ViewContext::open_modal(ParameterFormModal::new(recipe, config))
+        }
+        _ => ViewContext::send_message(Message::HttpBeginRequest(config)),
+    }
+}
+
+/// Modal with one text box per recipe parameter. Submitting sends the request
+/// with the given values. Empty fields fall back to the parameter's default.
+#[derive(Debug)]
+pub struct ParameterFormModal {
+    recipe_name: String,
+    /// Request to send once the form is submitted
+    config: Option<RequestConfig>,
+    parameters: IndexMap<String, RecipeParameter>,
+    /// One field per parameter, keyed by parameter name
+    form: Component<Form<String>>,
+    /// Validated values, stashed on submission so they can be used when the
+    /// modal closes
+    submission: Option<IndexMap<String, String>>,
+}
+
+/// Persistence key for the values most recently sent for a recipe's
+/// parameters, so the form can be prefilled with them next time
+#[derive(Debug, persisted::PersistedKey, Serialize)]
+#[persisted(IndexMap<String, String>)]
+struct ParametersKey(RecipeId);
+
+impl ParameterFormModal {
+    pub fn new(recipe: &Recipe, config: RequestConfig) -> Self {
+        let previous = DatabasePersistedStore::load_persisted(&ParametersKey(
+            recipe.id.clone(),
+        ))
+        .unwrap_or_default();
+        let fields = recipe
+            .parameters
+            .iter()
+            .map(|(name, parameter)| {
+                FormField::new(
+                    name.clone(),
+                    name.as_str(),
+                    text_box(parameter).default_value(
+                        previous.get(name).cloned().unwrap_or_default(),
+                    ),
+                )
+            })
+            .collect();
+        Self {
+            recipe_name: recipe.name().to_owned(),
+            config: Some(config),
+            parameters: recipe.parameters.clone(),
+            form: Form::new(fields).into(),
+            submission: None,
+        }
+    }
+
+    /// Validate the form and get the value of each parameter that was filled
+    /// in. Parameters left empty are omitted, so they use their default
+    fn build(&self) -> Result<IndexMap<String, String>, String> {
+        let mut values = IndexMap::new();
+        for (name, parameter) in &self.parameters {
+            let text = self.form.data().text(name).unwrap_or_default();
+            if text.is_empty() {
+                if parameter.default.is_none() {
+                    return Err(format!("`{name}` is required"));
+                }
+                continue;
+            }
+            parameter.kind.validate(text).map_err(|error| {
+                format!("Invalid value for `{name}`: {error}")
+            })?;
+            values.insert(name.clone(), text.to_owned());
+        }
+        Ok(values)
+    }
+}
+
+/// Build a text box for a parameter, with validation for its type
+fn text_box(parameter: &RecipeParameter) -> TextBox {
+    let kind = parameter.kind;
+    let placeholder = match (&parameter.description, &parameter.default) {
+        (Some(description), Some(default)) => {
+            format!("{description} (default: {})", default.display())
+        }
+        (Some(description), None) => description.clone(),
+        (None, Some(default)) => format!("Default: {}", default.display()),
+        (None, None) => format!("Required {kind}"),
+    };
+    TextBox::default()
+        .placeholder(placeholder)
+        .validator(move |text| {
+            let text = text.trim();
+            text.is_empty() || kind.validate(text).is_ok()
+        })
+}
+
+impl Modal for ParameterFormModal {
+    fn title(&self) -> Line<'_> {
+        format!("Send `{}`", self.recipe_name).into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Percentage(60),
+            Constraint::Length(self.form.data().height()),
+        )
+    }
+
+    fn on_close(mut self: Box<Self>, submitted: bool) {
+        let (Some(values), Some(config)) = (
+            self.submission.take().filter(|_| submitted),
+            self.config.take(),
+        ) else {
+            return;
+        };
+        DatabasePersistedStore::store_persisted(
+            &ParametersKey(config.recipe_id.clone()),
+            &values,
+        );
+        ViewContext::send_message(Message::HttpBeginRequest(RequestConfig {
+            options: BuildOptions {
+                parameters: values,
+                ..config.options
+            },
+            ..config
+        }));
+    }
+}
+
+impl EventHandler for ParameterFormModal {
+    fn update(&mut self, event: Event) -> Update {
+        if let Some(SubmitForm) = event.local() {
+            self.submission = form::submit(self.build());
+            Update::Consumed
+        } else {
+            Update::Propagate(event)
+        }
+    }
+
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.form.to_child_mut()]
+    }
+}
+
+impl Draw for ParameterFormModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        self.form.draw(frame, (), metadata.area(), true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{harness, TestHarness};
+    use indexmap::indexmap;
+    use rstest::rstest;
+    use slumber_core::{
+        assert_matches, collection::ParameterType, test_util::Factory,
+    };
+
+    fn recipe() -> Recipe {
+        Recipe {
+            parameters: indexmap! {
+                "id".into() => RecipeParameter {
+                    kind: ParameterType::Integer,
+                    default: None,
+                    description: Some("User ID".into()),
+                },
+                "verbose".into() => RecipeParameter {
+                    kind: ParameterType::Boolean,
+                    default: Some("false".into()),
+                    description: None,
+                },
+            },
+            ..Recipe::factory(())
+        }
+    }
+
+    fn config(recipe: &Recipe) -> RequestConfig {
+        RequestConfig {
+            profile_id: None,
+            recipe_id: recipe.id.clone(),
+            options: BuildOptions::default(),
+            note: Some("note".into()),
+        }
+    }
+
+    /// Set the text of a field in the form
+    fn set_text(modal: &mut ParameterFormModal, name: &str, text: &str) {
+        modal.form.data_mut().set_text(&name.to_owned(), text);
+    }
+
+    /// Required parameters must be filled in, and values must match the type
+    #[rstest]
+    fn test_validate(_harness: TestHarness) {
+        let recipe = recipe();
+        let mut modal = ParameterFormModal::new(&recipe, config(&recipe));
+        assert_eq!(modal.build().unwrap_err(), "`id` is required");
+        set_text(&mut modal, "id", "abc");
+        assert_eq!(
+            modal.build().unwrap_err(),
+            "Invalid value for `id`: Expected integer, got `abc`"
+        );
+        set_text(&mut modal, "id", "42");
+        // Empty optional parameters are left out, so they use their default
+        assert_eq!(
+            modal.build().unwrap(),
+            indexmap! {"id".into() => "42".to_owned()}
+        );
+    }
+
+    /// Submitting sends the request with the parameters, and remembers them
+    /// for next time
+    #[rstest]
+    fn test_submit(mut harness: TestHarness) {
+        let recipe = recipe();
+        let mut modal = ParameterFormModal::new(&recipe, config(&recipe));
+        set_text(&mut modal, "id", "42");
+        set_text(&mut modal, "verbose", "true");
+        harness.clear_messages();
+        modal.update(Event::new_local(SubmitForm));
+        assert_matches!(
+            ViewContext::pop_event(),
+            Some(Event::CloseModal { submitted: true })
+        );
+        Box::new(modal).on_close(true);
+
+        let expected = indexmap! {
+            "id".into() => "42".to_owned(),
+            "verbose".into() => "true".to_owned(),
+        };
+        let sent = assert_matches!(
+            harness.pop_message_now(),
+            Message::HttpBeginRequest(config) => config,
+        );
+        assert_eq!(sent.options.parameters, expected);
+        assert_eq!(sent.note.as_deref(), Some("note"));
+
+        // The next form starts with the same values
+        let modal = ParameterFormModal::new(&recipe, config(&recipe));
+        assert_eq!(modal.build().unwrap(), expected);
+    }
+}
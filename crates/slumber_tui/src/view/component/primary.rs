@@ -1,16 +1,31 @@
 //! Components for the "primary" view, which is the paned request/response view
 
 use crate::{
-    message::Message,
+    context::TuiContext,
+    message::{Message, RequestConfig},
     util::ResultReported,
     view::{
-        common::actions::ActionsModal,
+        common::{actions::ActionsModal, text_box::TextBox},
         component::{
+            batch::send_batch,
+            captured_requests::CapturedRequests,
+            connection_error::RetryRequest,
+            diagnostics::open_diagnostics,
             exchange_pane::{ExchangePane, ExchangePaneProps},
             help::HelpModal,
+            history_search::HistorySearch,
+            misc::TextBoxModal,
+            parameter_form::send_request,
+            profile_compare::select_profiles_to_compare,
             profile_select::ProfilePane,
+            recipe_diff::select_recipe_to_compare,
+            recipe_form::RecipeFormModal,
             recipe_list::RecipeListPane,
-            recipe_pane::{RecipeMenuAction, RecipePane, RecipePaneProps},
+            recipe_pane::{
+                select_draft, DraftRestored, RecipeMenuAction, RecipePane,
+                RecipePaneProps,
+            },
+            references::ReferencesModal,
         },
         draw::{Draw, DrawMetadata, ToStringGenerate},
         event::{Child, Event, EventHandler, Update},
@@ -24,14 +39,21 @@ use persisted::SingletonKey;
 use ratatui::{
     layout::Layout,
     prelude::{Constraint, Rect},
+    widgets::Tabs,
     Frame,
 };
 use serde::{Deserialize, Serialize};
 use slumber_config::Action;
 use slumber_core::collection::{
-    Collection, ProfileId, RecipeId, RecipeNodeDiscriminants,
+    self, Collection, CollectionChange, ProfileId, Recipe, RecipeId,
+    RecipeNodeDiscriminants, ReferenceLocation,
 };
-use strum::{EnumCount, EnumIter};
+use std::sync::{Arc, OnceLock};
+use strum::{EnumCount, EnumIter, IntoEnumIterator};
+
+/// Below this width, the panes don't fit side-by-side, so we switch to the
+/// compact layout: one pane at a time, with tabs to switch between them
+const COMPACT_WIDTH: u16 = 100;
 
 /// Primary TUI view, which shows request/response panes
 #[derive(Debug)]
@@ -68,11 +90,25 @@ pub struct PrimaryViewProps<'a> {
 )]
 pub enum PrimaryPane {
     #[default]
+    #[display("Recipes")]
     RecipeList,
+    #[display("Recipe")]
     Recipe,
+    #[display("Request / Response")]
     Exchange,
 }
 
+impl PrimaryPane {
+    /// Action to select this pane
+    fn action(self) -> Action {
+        match self {
+            Self::RecipeList => Action::SelectRecipeList,
+            Self::Recipe => Action::SelectRecipe,
+            Self::Exchange => Action::SelectResponse,
+        }
+    }
+}
+
 /// Panes that can be fullscreened. This is separate from [PrimaryPane] because
 /// it makes it easy to check when we should exit fullscreen mode.
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -101,6 +137,16 @@ enum MenuAction {
     #[default]
     #[display("Edit Collection")]
     EditCollection,
+    #[display("Clear Chain Cache")]
+    ClearChainCache,
+    #[display("View Captured Requests")]
+    ViewCapturedRequests,
+    #[display("Search History")]
+    SearchHistory,
+    #[display("View Diagnostics")]
+    ViewDiagnostics,
+    #[display("Find References")]
+    FindReferences,
 }
 impl ToStringGenerate for MenuAction {}
 
@@ -129,6 +175,37 @@ impl PrimaryView {
         }
     }
 
+    /// Update for a reloaded collection. Only panes that depend on a changed
+    /// part of the collection are rebuilt, so the rest keep their state. Their
+    /// selections are persisted, so those survive the rebuild as well. Pass
+    /// `None` for `changes` if they couldn't be determined, to rebuild
+    /// everything. The exchange pane is never rebuilt, because it only depends
+    /// on the selected request.
+    pub fn reload_collection(
+        &mut self,
+        collection: &Collection,
+        changes: Option<&[CollectionChange]>,
+    ) {
+        let changed = |section: &str| {
+            changes.map_or(true, |changes| {
+                changes.iter().any(|change| {
+                    change.path().split(['.', '[']).next() == Some(section)
+                })
+            })
+        };
+        if changed("profiles") {
+            self.profile_pane = ProfilePane::new(&collection.profiles).into();
+        }
+        if changed("requests") {
+            self.recipe_list_pane =
+                RecipeListPane::new(&collection.recipes).into();
+        }
+        // Recipe previews can depend on any part of the collection
+        if changes.map_or(true, |changes| !changes.is_empty()) {
+            self.recipe_pane.data_mut().reset();
+        }
+    }
+
     /// Which recipe in the recipe list is selected? `None` iff the list is
     /// empty OR a folder is selected.
     pub fn selected_recipe_id(&self) -> Option<&RecipeId> {
@@ -174,39 +251,119 @@ impl PrimaryView {
             recipes_area,
             self.is_selected(PrimaryPane::RecipeList),
         );
+        self.draw_recipe_pane(
+            frame,
+            recipe_area,
+            self.is_selected(PrimaryPane::Recipe),
+        );
+        self.draw_exchange_pane(
+            frame,
+            props,
+            request_response_area,
+            self.is_selected(PrimaryPane::Exchange),
+        );
+    }
 
-        let (selected_recipe_id, selected_recipe_kind) =
-            match self.recipe_list_pane.data().selected_node() {
-                Some((selected_recipe_id, selected_recipe_kind)) => {
-                    (Some(selected_recipe_id), Some(selected_recipe_kind))
-                }
-                None => (None, None),
-            };
+    /// Draw the compact view, for narrow terminals. Only the selected pane is
+    /// shown, with a row of tabs above it
+    fn draw_compact(
+        &self,
+        frame: &mut Frame,
+        props: PrimaryViewProps,
+        area: Rect,
+    ) {
+        let [profile_area, tabs_area, pane_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .areas(area);
+
+        self.profile_pane.draw(frame, (), profile_area, true);
+
+        let context = TuiContext::get();
+        let selected = self.selected_pane.selected();
+        let tabs = Tabs::new(PrimaryPane::iter().map(|pane| {
+            context
+                .input_engine
+                .add_hint(pane.to_string(), pane.action())
+        }))
+        .select(self.selected_pane.selected_index())
+        .highlight_style(context.styles.tab.highlight);
+        frame.render_widget(tabs, tabs_area);
+
+        // The recipe pane holds the request config, so it has to stay up to
+        // date even while hidden
+        if selected != PrimaryPane::Recipe {
+            let collection = ViewContext::collection();
+            self.recipe_pane
+                .data()
+                .update_state(&self.recipe_pane_props(&collection));
+        }
+        match selected {
+            PrimaryPane::RecipeList => {
+                self.recipe_list_pane.draw(frame, (), pane_area, true)
+            }
+            PrimaryPane::Recipe => {
+                self.draw_recipe_pane(frame, pane_area, true)
+            }
+            PrimaryPane::Exchange => {
+                self.draw_exchange_pane(frame, props, pane_area, true)
+            }
+        }
+    }
+
+    fn draw_recipe_pane(&self, frame: &mut Frame, area: Rect, has_focus: bool) {
         let collection = ViewContext::collection();
-        let selected_recipe_node = selected_recipe_id.and_then(|id| {
-            collection
-                .recipes
-                .try_get(id)
-                .reported(&ViewContext::messages_tx())
-        });
         self.recipe_pane.draw(
             frame,
-            RecipePaneProps {
-                selected_recipe_node,
-                selected_profile_id: self.selected_profile_id(),
-            },
-            recipe_area,
-            self.is_selected(PrimaryPane::Recipe),
+            self.recipe_pane_props(&collection),
+            area,
+            has_focus,
         );
+    }
+
+    fn recipe_pane_props<'a>(
+        &'a self,
+        collection: &'a Collection,
+    ) -> RecipePaneProps<'a> {
+        let selected_recipe_node = self
+            .recipe_list_pane
+            .data()
+            .selected_node()
+            .and_then(|(id, _)| {
+                collection
+                    .recipes
+                    .try_get(id)
+                    .reported(&ViewContext::messages_tx())
+            });
+        RecipePaneProps {
+            selected_recipe_node,
+            selected_profile_id: self.selected_profile_id(),
+        }
+    }
 
+    fn draw_exchange_pane(
+        &self,
+        frame: &mut Frame,
+        props: PrimaryViewProps,
+        area: Rect,
+        has_focus: bool,
+    ) {
+        let collection = ViewContext::collection();
+        let selected_node = self.recipe_list_pane.data().selected_node();
+        let example = selected_node
+            .and_then(|(id, _)| collection.recipes.get_recipe(id))
+            .and_then(|recipe| recipe.example.as_ref());
         self.exchange_pane.draw(
             frame,
             ExchangePaneProps {
-                selected_recipe_kind,
+                selected_recipe_kind: selected_node.map(|(_, kind)| kind),
                 request_state: props.selected_request,
+                example,
             },
-            request_response_area,
-            self.is_selected(PrimaryPane::Exchange),
+            area,
+            has_focus,
         );
     }
 
@@ -254,17 +411,123 @@ impl PrimaryView {
     /// Handle menu actions for recipe list or detail panes. We handle this here
     /// for code de-duplication, and because we have access to all the needed
     /// context.
-    fn handle_recipe_menu_action(&self, action: RecipeMenuAction) {
-        // If no recipes are available, we can't do anything
-        let Some(config) = self.recipe_pane.data().request_config() else {
-            return;
-        };
-
-        let message = match action {
-            RecipeMenuAction::EditCollection => Message::CollectionEdit,
-            RecipeMenuAction::CopyUrl => Message::CopyRequestUrl(config),
-            RecipeMenuAction::CopyBody => Message::CopyRequestBody(config),
-            RecipeMenuAction::CopyCurl => Message::CopyRequestCurl(config),
+    fn handle_recipe_menu_action(&mut self, action: RecipeMenuAction) {
+        let config = self.recipe_pane.data().request_config();
+        let message = match (action, config) {
+            (RecipeMenuAction::EditCollection, _) => Message::CollectionEdit,
+            (RecipeMenuAction::EditRecipe, _) => {
+                match self.recipe_list_pane.data().selected_node() {
+                    Some((id, _)) => Message::CollectionEditItem(
+                        ReferenceLocation::Recipe(id.clone()),
+                    ),
+                    None => Message::CollectionEdit,
+                }
+            }
+            (RecipeMenuAction::NewRecipe, _) => {
+                ViewContext::open_modal(RecipeFormModal::new_recipe());
+                return;
+            }
+            (RecipeMenuAction::ImportCurl, _) => {
+                open_import_curl();
+                return;
+            }
+            (RecipeMenuAction::EditRecipeFields, _) => {
+                let collection = ViewContext::collection();
+                if let Some(recipe) = self
+                    .selected_recipe_id()
+                    .and_then(|id| collection.recipes.get_recipe(id))
+                {
+                    ViewContext::open_modal(RecipeFormModal::edit_recipe(
+                        recipe,
+                    ));
+                }
+                return;
+            }
+            (RecipeMenuAction::ScratchRequest, _) => {
+                ViewContext::open_modal(RecipeFormModal::scratch(
+                    self.selected_profile_id().cloned(),
+                ));
+                return;
+            }
+            (RecipeMenuAction::SaveScratch, _) => {
+                match RecipeFormModal::save_scratch() {
+                    Some(modal) => ViewContext::open_modal(modal),
+                    None => ViewContext::send_message(Message::Notify(
+                        "No scratch request to save".into(),
+                    )),
+                }
+                return;
+            }
+            (RecipeMenuAction::ToggleUrlPreview, _) => {
+                self.recipe_pane.data_mut().toggle_url_preview();
+                return;
+            }
+            (RecipeMenuAction::ClearChainCache, _) => Message::ChainCacheClear,
+            (RecipeMenuAction::ViewCapturedRequests, _) => {
+                open_captured_requests();
+                return;
+            }
+            (RecipeMenuAction::SearchHistory, _) => {
+                ViewContext::open_modal(HistorySearch::new());
+                return;
+            }
+            (RecipeMenuAction::ViewDiagnostics, _) => {
+                open_diagnostics();
+                return;
+            }
+            (RecipeMenuAction::FindReferences, _) => {
+                ViewContext::open_modal(ReferencesModal::new());
+                return;
+            }
+            (RecipeMenuAction::SendMarked, _) => {
+                send_batch(
+                    self.recipe_list_pane.data().marked_recipes(),
+                    self.selected_profile_id().cloned(),
+                    false,
+                );
+                return;
+            }
+            (RecipeMenuAction::SendMarkedParallel, _) => {
+                send_batch(
+                    self.recipe_list_pane.data().marked_recipes(),
+                    self.selected_profile_id().cloned(),
+                    true,
+                );
+                return;
+            }
+            (RecipeMenuAction::CompareRecipe, _) => {
+                if let Some(recipe_id) = self.selected_recipe_id() {
+                    select_recipe_to_compare(
+                        recipe_id.clone(),
+                        self.selected_profile_id().cloned(),
+                    );
+                }
+                return;
+            }
+            (RecipeMenuAction::CompareProfiles, _) => {
+                if let Some(recipe_id) = self.selected_recipe_id() {
+                    select_profiles_to_compare(
+                        recipe_id.clone(),
+                        self.selected_profile_id(),
+                    );
+                }
+                return;
+            }
+            (RecipeMenuAction::RestoreDraft, Some(config)) => {
+                select_draft(config.recipe_id);
+                return;
+            }
+            (RecipeMenuAction::CopyUrl, Some(config)) => {
+                Message::CopyRequestUrl(config)
+            }
+            (RecipeMenuAction::CopyBody, Some(config)) => {
+                Message::CopyRequestBody(config)
+            }
+            (RecipeMenuAction::CopyCurl, Some(config)) => {
+                Message::CopyRequestCurl(config)
+            }
+            // If no recipes are available, we can't do anything
+            (_, None) => return,
         };
         ViewContext::send_message(message);
     }
@@ -285,9 +548,14 @@ impl EventHandler for PrimaryView {
                     if let Some(config) =
                         self.recipe_pane.data().request_config()
                     {
-                        ViewContext::send_message(Message::HttpBeginRequest(
-                            config,
-                        ));
+                        send_request(config);
+                    }
+                }
+                Action::SendWithNote => {
+                    if let Some(config) =
+                        self.recipe_pane.data().request_config()
+                    {
+                        send_with_note(config);
                     }
                 }
                 Action::OpenActions => {
@@ -338,6 +606,14 @@ impl EventHandler for PrimaryView {
             Event::Local(local) => {
                 if let Some(PaneChanged) = local.downcast_ref() {
                     self.maybe_exit_fullscreen();
+                } else if let Some(RetryRequest) = local.downcast_ref() {
+                    if let Some(config) =
+                        self.recipe_pane.data().request_config()
+                    {
+                        send_request(config);
+                    }
+                } else if let Some(DraftRestored) = local.downcast_ref() {
+                    self.recipe_pane.data_mut().reset();
                 } else if let Some(pane) = local.downcast_ref::<PrimaryPane>() {
                     // Children can select themselves by sending PrimaryPane
                     self.selected_pane.get_mut().select(pane);
@@ -351,6 +627,19 @@ impl EventHandler for PrimaryView {
                         MenuAction::EditCollection => {
                             ViewContext::send_message(Message::CollectionEdit)
                         }
+                        MenuAction::ClearChainCache => {
+                            ViewContext::send_message(Message::ChainCacheClear)
+                        }
+                        MenuAction::ViewCapturedRequests => {
+                            open_captured_requests()
+                        }
+                        MenuAction::SearchHistory => {
+                            ViewContext::open_modal(HistorySearch::new())
+                        }
+                        MenuAction::ViewDiagnostics => open_diagnostics(),
+                        MenuAction::FindReferences => {
+                            ViewContext::open_modal(ReferencesModal::new())
+                        }
                     }
                 } else {
                     return Update::Propagate(event);
@@ -379,56 +668,97 @@ impl<'a> Draw<PrimaryViewProps<'a>> for PrimaryView {
         props: PrimaryViewProps<'a>,
         metadata: DrawMetadata,
     ) {
+        let area = metadata.area();
         match *self.fullscreen_mode {
-            None => self.draw_all_panes(frame, props, metadata.area()),
-            Some(FullscreenMode::Recipe) => {
-                let collection = ViewContext::collection();
-                let selected_recipe_node =
-                    self.recipe_list_pane.data().selected_node().and_then(
-                        |(id, _)| {
-                            collection
-                                .recipes
-                                .try_get(id)
-                                .reported(&ViewContext::messages_tx())
-                        },
-                    );
-                self.recipe_pane.draw(
-                    frame,
-                    RecipePaneProps {
-                        selected_recipe_node,
-                        selected_profile_id: self.selected_profile_id(),
-                    },
-                    metadata.area(),
-                    self.is_selected(PrimaryPane::Recipe),
-                );
+            None if area.width < COMPACT_WIDTH => {
+                self.draw_compact(frame, props, area)
             }
-            Some(FullscreenMode::Exchange) => self.exchange_pane.draw(
+            None => self.draw_all_panes(frame, props, area),
+            Some(FullscreenMode::Recipe) => self.draw_recipe_pane(
                 frame,
-                ExchangePaneProps {
-                    selected_recipe_kind: self
-                        .recipe_list_pane
-                        .data()
-                        .selected_node()
-                        .map(|(_, kind)| kind),
-                    request_state: props.selected_request,
-                },
-                metadata.area(),
-                true,
+                area,
+                self.is_selected(PrimaryPane::Recipe),
             ),
+            Some(FullscreenMode::Exchange) => {
+                self.draw_exchange_pane(frame, props, area, true)
+            }
         }
     }
 }
 
+/// Load requests captured by `slumber listen` and show them in a modal
+fn open_captured_requests() {
+    if let Some(requests) =
+        ViewContext::with_database(|database| database.get_captured_requests())
+            .reported(&ViewContext::messages_tx())
+    {
+        ViewContext::open_modal(CapturedRequests::new(requests));
+    }
+}
+
+/// Ask the user for a note, then send the request with the note attached. If
+/// the note is left empty, the request is sent without one.
+fn send_with_note(config: RequestConfig) {
+    ViewContext::open_modal(TextBoxModal::new(
+        "Note".into(),
+        TextBox::default().placeholder("Describe this request"),
+        move |note| {
+            let note = note.trim();
+            send_request(RequestConfig {
+                note: (!note.is_empty()).then(|| note.to_owned()),
+                ..config
+            });
+        },
+    ));
+}
+
+/// Ask the user for a curl command, then add it to the collection file as a
+/// new recipe. The collection will be reloaded by the file watcher once the
+/// file is written.
+fn open_import_curl() {
+    ViewContext::open_modal(TextBoxModal::new(
+        "Paste a cURL command".into(),
+        TextBox::default()
+            .placeholder("curl https://example.com")
+            .validator(|command| Recipe::from_curl(command).is_ok()),
+        |command| {
+            // The ID is generated during the edit, so we need to get it back
+            // out for the notification
+            let id: Arc<OnceLock<RecipeId>> = Default::default();
+            let new_id = Arc::clone(&id);
+            // Recipes can come from imported files, so check against all of
+            // them
+            let loaded = ViewContext::collection();
+            ViewContext::send_message(Message::CollectionWrite {
+                edit: Box::new(move |source| {
+                    let (source, recipe_id) = collection::import_curl(
+                        source, &loaded, &command, None,
+                    )?;
+                    let _ = id.set(recipe_id);
+                    Ok(source)
+                }),
+                on_complete: Box::new(move |()| {
+                    if let Some(id) = new_id.get() {
+                        ViewContext::send_message(Message::Notify(format!(
+                            "Imported recipe `{id}`"
+                        )));
+                    }
+                }),
+            });
+        },
+    ));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        message::{Message, RequestConfig},
         test_util::{harness, terminal, TestHarness, TestTerminal},
         view::{
             test_util::TestComponent, util::persistence::DatabasePersistedStore,
         },
     };
+    use crossterm::event::KeyCode;
     use persisted::PersistedStore;
     use rstest::rstest;
     use slumber_core::{assert_matches, http::BuildOptions};
@@ -474,6 +804,29 @@ mod tests {
         );
     }
 
+    /// On narrow terminals, only the selected pane is drawn
+    #[rstest]
+    #[case::compact(terminal(80, 30), false)]
+    #[case::wide(terminal(120, 30), true)]
+    fn test_compact_layout(
+        mut harness: TestHarness,
+        #[case] terminal: TestTerminal,
+        #[case] expect_all: bool,
+    ) {
+        let mut component = create_component(&mut harness, &terminal);
+        let visible = |view: &PrimaryView| {
+            [
+                view.recipe_list_pane.is_visible(),
+                view.recipe_pane.is_visible(),
+                view.exchange_pane.is_visible(),
+            ]
+        };
+        assert_eq!(visible(component.data()), [true, expect_all, expect_all]);
+
+        component.send_key(KeyCode::Tab).assert_empty();
+        assert_eq!(visible(component.data()), [expect_all, true, expect_all]);
+    }
+
     /// Test "Copy URL" action, which is available via the Recipe List or Recipe
     /// panes
     #[rstest]
@@ -482,6 +835,7 @@ mod tests {
             recipe_id: harness.collection.first_recipe_id().clone(),
             profile_id: Some(harness.collection.first_profile_id().clone()),
             options: BuildOptions::default(),
+            note: None,
         };
         let mut component = create_component(&mut harness, &terminal);
         component
@@ -503,6 +857,7 @@ mod tests {
             recipe_id: harness.collection.first_recipe_id().clone(),
             profile_id: Some(harness.collection.first_profile_id().clone()),
             options: BuildOptions::default(),
+            note: None,
         };
         let mut component = create_component(&mut harness, &terminal);
         component
@@ -524,6 +879,7 @@ mod tests {
             recipe_id: harness.collection.first_recipe_id().clone(),
             profile_id: Some(harness.collection.first_profile_id().clone()),
             options: BuildOptions::default(),
+            note: None,
         };
         let mut component = create_component(&mut harness, &terminal);
         component
@@ -536,4 +892,40 @@ mod tests {
         );
         assert_eq!(request_config, expected_config);
     }
+
+    /// Test "Clear Chain Cache" action, which is available via the Recipe List
+    /// or Recipe panes
+    #[rstest]
+    fn test_clear_chain_cache(
+        mut harness: TestHarness,
+        terminal: TestTerminal,
+    ) {
+        let mut component = create_component(&mut harness, &terminal);
+        component
+            .update_draw(Event::new_local(RecipeMenuAction::ClearChainCache))
+            .assert_empty();
+
+        assert_matches!(harness.pop_message_now(), Message::ChainCacheClear);
+    }
+
+    /// Test "Edit Recipe" action, which opens the selected recipe in the
+    /// editor
+    #[rstest]
+    fn test_edit_recipe(mut harness: TestHarness, terminal: TestTerminal) {
+        let mut component = create_component(&mut harness, &terminal);
+        component
+            .update_draw(Event::new_local(RecipeMenuAction::EditRecipe))
+            .assert_empty();
+
+        let location = assert_matches!(
+            harness.pop_message_now(),
+            Message::CollectionEditItem(location) => location,
+        );
+        assert_eq!(
+            location,
+            ReferenceLocation::Recipe(
+                harness.collection.first_recipe_id().clone()
+            )
+        );
+    }
 }
@@ -0,0 +1,449 @@
+//! Send a recipe with several profiles at once, and compare the responses
+
+use crate::{
+    context::TuiContext,
+    message::{Message, RequestConfig},
+    view::{
+        common::{list::List, modal::Modal},
+        component::{
+            baseline::load_exchange,
+            batch::{BatchOutcomes, Outcome},
+            response_diff::{DiffMode, DiffView},
+            Component,
+        },
+        draw::{Draw, DrawMetadata, Generate},
+        event::{Child, Event, EventHandler, Update},
+        state::{select::SelectState, StateCell},
+        ViewContext,
+    },
+};
+use ratatui::{
+    layout::{Constraint, Layout},
+    text::{Line, Span, Text},
+    Frame,
+};
+use slumber_config::Action;
+use slumber_core::{
+    collection::{ProfileId, RecipeId},
+    http::{diff::ResponseDiff, BuildOptions},
+};
+
+/// Ask the user which profiles to send a recipe with, then send it with all
+/// of them at once and compare the responses. The current profile is selected
+/// to start.
+pub fn select_profiles_to_compare(
+    recipe_id: RecipeId,
+    selected_profile_id: Option<&ProfileId>,
+) {
+    let collection = ViewContext::collection();
+    if collection.profiles.len() < 2 {
+        ViewContext::send_message(Message::Notify(
+            "Comparing profiles requires at least two profiles".into(),
+        ));
+        return;
+    }
+    let profiles = collection
+        .profiles
+        .values()
+        .map(|profile| ProfileOption {
+            id: profile.id.clone(),
+            name: profile.name().to_owned(),
+            checked: Some(&profile.id) == selected_profile_id,
+        })
+        .collect();
+    ViewContext::open_modal(ProfileSelectModal::new(recipe_id, profiles));
+}
+
+/// Send a recipe with each profile in parallel, and open a modal to compare
+/// the responses once they're all in
+fn compare_profiles(recipe_id: RecipeId, profiles: Vec<ProfileOption>) {
+    let modal = ProfileDiffModal::new(
+        &recipe_id,
+        profiles
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect(),
+    );
+    ViewContext::send_message(Message::HttpBeginBatch {
+        requests: profiles
+            .into_iter()
+            .map(|profile| RequestConfig {
+                profile_id: Some(profile.id),
+                recipe_id: recipe_id.clone(),
+                options: BuildOptions::default(),
+                note: None,
+            })
+            .collect(),
+        parallel: true,
+        on_complete: modal.outcomes.on_complete(),
+    });
+    ViewContext::open_modal(modal);
+}
+
+/// Pick the profiles to compare a recipe across
+#[derive(Debug)]
+struct ProfileSelectModal {
+    recipe_id: RecipeId,
+    profiles: Component<SelectState<ProfileOption>>,
+}
+
+#[derive(Clone, Debug)]
+struct ProfileOption {
+    id: ProfileId,
+    name: String,
+    checked: bool,
+}
+
+impl ProfileOption {
+    fn toggle(&mut self) {
+        self.checked ^= true;
+    }
+}
+
+impl ProfileSelectModal {
+    fn new(recipe_id: RecipeId, profiles: Vec<ProfileOption>) -> Self {
+        Self {
+            recipe_id,
+            profiles: SelectState::builder(profiles)
+                .on_toggle(ProfileOption::toggle)
+                .on_submit(|_| {
+                    ViewContext::push_event(Event::CloseModal {
+                        submitted: true,
+                    })
+                })
+                .build()
+                .into(),
+        }
+    }
+}
+
+impl Modal for ProfileSelectModal {
+    fn title(&self) -> Line<'_> {
+        format!("Compare `{}` across profiles", self.recipe_id).into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Percentage(40),
+            // Leave room for the hint
+            Constraint::Length(self.profiles.data().len().min(20) as u16 + 1),
+        )
+    }
+
+    fn on_close(self: Box<Self>, submitted: bool) {
+        if !submitted {
+            return;
+        }
+        let profiles: Vec<ProfileOption> = self
+            .profiles
+            .data()
+            .items()
+            .filter(|profile| profile.checked)
+            .cloned()
+            .collect();
+        if profiles.len() < 2 {
+            ViewContext::send_message(Message::Notify(
+                "Select at least two profiles to compare".into(),
+            ));
+            return;
+        }
+        compare_profiles(self.recipe_id, profiles);
+    }
+}
+
+impl EventHandler for ProfileSelectModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.profiles.to_child_mut()]
+    }
+}
+
+impl Draw for ProfileSelectModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let tui_context = TuiContext::get();
+        let [list_area, hint_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)])
+                .areas(metadata.area());
+        self.profiles.draw(
+            frame,
+            List::from(self.profiles.data()),
+            list_area,
+            true,
+        );
+        let input_engine = &tui_context.input_engine;
+        frame.render_widget(
+            Span::styled(
+                format!(
+                    "{} to select, {} to send",
+                    input_engine.binding_display(Action::Toggle),
+                    input_engine.binding_display(Action::Submit),
+                ),
+                tui_context.styles.text.hint,
+            ),
+            hint_area,
+        );
+    }
+}
+
+impl Generate for &ProfileOption {
+    type Output<'this> = Line<'this>
+    where
+        Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        let checkbox = if self.checked { "[x]" } else { "[ ]" };
+        format!("{checkbox} {}", self.name).into()
+    }
+}
+
+/// Responses from each profile, compared against the first profile. With more
+/// than two profiles, the user can cycle through which one is compared.
+#[derive(Debug)]
+pub struct ProfileDiffModal {
+    title: String,
+    /// Display name of each profile. The first is the baseline that the others
+    /// are compared to
+    names: Vec<String>,
+    /// Outcome of each profile's request, parallel to `names`
+    outcomes: BatchOutcomes,
+    /// Index of the profile being compared to the baseline. Never 0
+    compared: usize,
+    /// Diff between the baseline and the compared profile, or an explanation
+    /// of why there isn't one. Built once every request has finished
+    diff: StateCell<usize, Result<Component<DiffView>, String>>,
+}
+
+impl ProfileDiffModal {
+    fn new(recipe_id: &RecipeId, names: Vec<String>) -> Self {
+        Self {
+            title: format!("Compare `{recipe_id}` across profiles"),
+            outcomes: BatchOutcomes::new(names.len()),
+            names,
+            compared: 1,
+            diff: StateCell::default(),
+        }
+    }
+
+    /// Cycle which profile is compared to the baseline
+    fn cycle(&mut self, forward: bool) {
+        // Index among the non-baseline profiles
+        let count = self.names.len() - 1;
+        let index = self.compared - 1;
+        let index = if forward {
+            (index + 1) % count
+        } else {
+            (index + count - 1) % count
+        };
+        self.compared = index + 1;
+    }
+
+    /// Diff the responses for the baseline and compared profiles
+    fn build_diff(
+        &self,
+        outcomes: &[Option<Outcome>],
+    ) -> Result<Component<DiffView>, String> {
+        let exchange = |index: usize| match &outcomes[index] {
+            Some(Ok(summary)) => ViewContext::with_database(|database| {
+                load_exchange(database, summary.id)
+            })
+            .map_err(|error| format!("{error:#}")),
+            Some(Err(error)) => Err(format!("{}: {error}", self.names[index])),
+            None => {
+                Err(format!("{}: request hasn't finished", self.names[index]))
+            }
+        };
+        let baseline = exchange(0)?;
+        let compared = exchange(self.compared)?;
+        let diff = ResponseDiff::new(&baseline.response, &compared.response);
+        if diff.is_empty() {
+            Err("Responses are identical".into())
+        } else {
+            Ok(DiffView::response(diff, DiffMode::SideBySide).into())
+        }
+    }
+}
+
+impl Modal for ProfileDiffModal {
+    fn title(&self) -> Line<'_> {
+        self.title.as_str().into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(90), Constraint::Percentage(80))
+    }
+}
+
+impl EventHandler for ProfileDiffModal {
+    fn update(&mut self, event: Event) -> Update {
+        match event.action() {
+            Some(Action::Left) if self.names.len() > 2 => self.cycle(false),
+            Some(Action::Right) if self.names.len() > 2 => self.cycle(true),
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        if let Some(Ok(diff)) = self.diff.get_mut() {
+            vec![diff.to_child_mut()]
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Draw for ProfileDiffModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let tui_context = TuiContext::get();
+        let styles = &tui_context.styles;
+        let [header_area, content_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)])
+                .areas(metadata.area());
+
+        let (done, total) = self.outcomes.progress();
+        if done < total {
+            frame.render_widget(
+                Span::styled(
+                    format!("Waiting for responses ({done}/{total})"),
+                    styles.text.hint,
+                ),
+                header_area,
+            );
+            return;
+        }
+
+        let mut header = vec![Span::styled(
+            format!("{} ↔ {}", self.names[0], self.names[self.compared]),
+            styles.text.title,
+        )];
+        if self.names.len() > 2 {
+            let input_engine = &tui_context.input_engine;
+            header.push(Span::styled(
+                format!(
+                    " ({}/{}; {}/{} to switch)",
+                    self.compared,
+                    self.names.len() - 1,
+                    input_engine.binding_display(Action::Left),
+                    input_engine.binding_display(Action::Right),
+                ),
+                styles.text.hint,
+            ));
+        }
+        frame.render_widget(Line::from(header), header_area);
+
+        let outcomes = self.outcomes.lock();
+        let diff = self
+            .diff
+            .get_or_update(&self.compared, || self.build_diff(&outcomes));
+        match &*diff {
+            Ok(diff) => diff.draw(frame, (), content_area, true),
+            Err(message) => frame.render_widget(
+                Text::styled(message.as_str(), styles.text.hint),
+                content_area,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{harness, terminal, TestHarness, TestTerminal},
+        view::test_util::TestComponent,
+    };
+    use crossterm::event::KeyCode;
+    use reqwest::StatusCode;
+    use rstest::rstest;
+    use slumber_core::{
+        assert_matches,
+        http::{Exchange, ExchangeSummary, RequestRecord, ResponseRecord},
+        test_util::Factory,
+    };
+
+    fn option(id: &str, checked: bool) -> ProfileOption {
+        ProfileOption {
+            id: id.into(),
+            name: id.to_owned(),
+            checked,
+        }
+    }
+
+    /// Only checked profiles are sent, and only if there are at least two
+    #[rstest]
+    fn test_select(mut harness: TestHarness) {
+        let recipe_id = RecipeId::factory(());
+        let mut staging = option("staging", false);
+        staging.toggle();
+        let profiles =
+            vec![option("dev", true), staging, option("prod", false)];
+        let modal = ProfileSelectModal::new(recipe_id.clone(), profiles);
+        Box::new(modal).on_close(true);
+
+        let (requests, parallel) = assert_matches!(
+            harness.pop_message_now(),
+            Message::HttpBeginBatch { requests, parallel, .. } =>
+                (requests, parallel),
+        );
+        assert!(parallel);
+        let profile_ids: Vec<_> = requests
+            .iter()
+            .map(|config| config.profile_id.clone().unwrap())
+            .collect();
+        assert_eq!(profile_ids, vec![ProfileId::from("dev"), "staging".into()]);
+        assert_matches!(ViewContext::pop_event(), Some(Event::OpenModal(_)));
+
+        // One profile isn't enough
+        let modal = ProfileSelectModal::new(
+            recipe_id,
+            vec![option("dev", true), option("staging", false)],
+        );
+        Box::new(modal).on_close(true);
+        assert_matches!(harness.pop_message_now(), Message::Notify(_));
+    }
+
+    /// The diff is shown once every response is in, and the compared profile
+    /// can be cycled
+    #[rstest]
+    fn test_diff(harness: TestHarness, #[with(60, 10)] terminal: TestTerminal) {
+        let recipe_id = RecipeId::factory(());
+        let exchanges = [StatusCode::OK, StatusCode::NOT_FOUND, StatusCode::OK]
+            .map(|status| {
+                Exchange::factory((
+                    RequestRecord::factory((None, recipe_id.clone())),
+                    ResponseRecord::factory(status),
+                ))
+            });
+        for exchange in &exchanges {
+            harness.database.insert_exchange(exchange).unwrap();
+        }
+
+        let modal = ProfileDiffModal::new(
+            &recipe_id,
+            vec!["dev".into(), "staging".into(), "prod".into()],
+        );
+        let on_complete = modal.outcomes.on_complete();
+        let mut component = TestComponent::new(&terminal, modal, ());
+        assert!(component.data().diff.get().is_none());
+
+        for (i, exchange) in exchanges.iter().enumerate() {
+            on_complete(i, Ok(ExchangeSummary::from(exchange)));
+        }
+        component.drain_draw().assert_empty();
+        assert_matches!(component.data().diff.get().as_deref(), Some(Ok(_)));
+
+        // Same status, same everything
+        component.send_key(KeyCode::Right).assert_empty();
+        assert_eq!(component.data().compared, 2);
+        assert_matches!(
+            component.data().diff.get().as_deref(),
+            Some(Err(message)) if message == "Responses are identical"
+        );
+
+        // Wrap around
+        component.send_key(KeyCode::Right).assert_empty();
+        assert_eq!(component.data().compared, 1);
+    }
+}
@@ -2,13 +2,15 @@
 
 use crate::{
     context::TuiContext,
+    message::Message,
     util::ResultReported,
     view::{
         common::{
-            list::List, modal::Modal, table::Table,
-            template_preview::TemplatePreview, Pane,
+            actions::ActionsModal, list::List, modal::Modal, table::Table,
+            template_preview::TemplatePreview, text_box::TextBox, Pane,
         },
-        draw::{Draw, DrawMetadata, Generate},
+        component::misc::TextBoxModal,
+        draw::{Draw, DrawMetadata, Generate, ToStringGenerate},
         event::{Child, Event, EventHandler, Update},
         state::{select::SelectState, StateCell},
         util::persistence::Persisted,
@@ -16,20 +18,22 @@ use crate::{
     },
 };
 use anyhow::anyhow;
+use derive_more::Display;
 use indexmap::IndexMap;
 use itertools::Itertools;
 use persisted::PersistedKey;
 use ratatui::{
     layout::{Constraint, Layout},
-    text::{Line, Text},
+    text::{Line, Span, Text},
     Frame,
 };
 use serde::Serialize;
 use slumber_config::Action;
 use slumber_core::{
-    collection::{HasId, Profile, ProfileId},
+    collection::{self, HasId, Profile, ProfileId, ReferenceLocation},
     util::doc_link,
 };
+use strum::{EnumCount, EnumIter};
 
 /// Minimal pane to show the current profile, and handle interaction to open the
 /// profile list modal
@@ -41,6 +45,9 @@ pub struct ProfilePane {
     /// necessarily the same: the user could highlight a profile without
     /// actually selecting it.
     selected_profile_id: Persisted<SelectedProfileKey>,
+    /// Profile that was highlighted in the list modal when the actions menu
+    /// was opened. This is the target of the clone action
+    action_profile_id: Option<ProfileId>,
 }
 
 /// Persisted key for the ID of the selected profile
@@ -68,6 +75,7 @@ impl ProfilePane {
 
         Self {
             selected_profile_id,
+            action_profile_id: None,
         }
     }
 
@@ -81,6 +89,54 @@ impl ProfilePane {
             self.selected_profile_id.as_ref(),
         ));
     }
+
+    /// Prompt the user for the ID of a new profile. If `from` is given, the
+    /// new profile will be a copy of it
+    fn open_create_modal(from: Option<ProfileId>) {
+        let title = match &from {
+            Some(from) => format!("ID for copy of {from}"),
+            None => "ID for new profile".into(),
+        };
+        ViewContext::open_modal(TextBoxModal::new(
+            title,
+            TextBox::default().validator(|id| {
+                !id.is_empty()
+                    && !ViewContext::collection()
+                        .profiles
+                        .contains_key(&ProfileId::from(id.to_owned()))
+            }),
+            move |id| {
+                ViewContext::push_event(Event::new_local(CreateProfile {
+                    from,
+                    id: id.into(),
+                }))
+            },
+        ));
+    }
+
+    /// Add a profile to the collection file, then select it. The collection
+    /// will be reloaded by the file watcher once the file is written.
+    fn create_profile(from: Option<ProfileId>, id: ProfileId) {
+        let new_id = id.clone();
+        // Profiles can come from imported files, so check against all of them
+        let loaded = ViewContext::collection();
+        ViewContext::send_message(Message::CollectionWrite {
+            edit: Box::new(move |source| match &from {
+                Some(from) => {
+                    collection::clone_profile(source, &loaded, from, &id)
+                }
+                None => collection::add_profile(source, &loaded, &id),
+            }),
+            on_complete: Box::new(move |()| {
+                ViewContext::send_message(Message::Notify(format!(
+                    "Created profile `{new_id}`"
+                )));
+                ViewContext::push_event(Event::new_local(SelectProfile(
+                    new_id,
+                )));
+            }),
+        });
+    }
 }
 
 impl EventHandler for ProfilePane {
@@ -92,6 +148,33 @@ impl EventHandler for ProfilePane {
             *self.selected_profile_id.get_mut() = Some(profile_id.clone());
             // Refresh template previews
             ViewContext::push_event(Event::HttpSelectRequest(None));
+        } else if let Some(OpenProfileActions(profile_id)) = event.local() {
+            // Remember which profile was highlighted, in case it gets cloned
+            self.action_profile_id = profile_id.clone();
+            let disabled = if profile_id.is_some() {
+                [].as_slice()
+            } else {
+                &[ProfileMenuAction::Clone, ProfileMenuAction::Edit]
+            };
+            ViewContext::open_modal(ActionsModal::new(disabled));
+        } else if let Some(action) = event.local::<ProfileMenuAction>() {
+            match action {
+                ProfileMenuAction::New => Self::open_create_modal(None),
+                ProfileMenuAction::Clone => {
+                    if let Some(profile_id) = self.action_profile_id.take() {
+                        Self::open_create_modal(Some(profile_id));
+                    }
+                }
+                ProfileMenuAction::Edit => {
+                    if let Some(profile_id) = self.action_profile_id.take() {
+                        ViewContext::send_message(Message::CollectionEditItem(
+                            ReferenceLocation::Profile(profile_id),
+                        ));
+                    }
+                }
+            }
+        } else if let Some(CreateProfile { from, id }) = event.local() {
+            Self::create_profile(from.clone(), id.clone());
         } else {
             return Update::Propagate(event);
         }
@@ -132,6 +215,37 @@ impl Draw for ProfilePane {
 #[derive(Debug)]
 struct SelectProfile(ProfileId);
 
+/// Local event to open the actions menu from the modal. The modal closes
+/// itself first, so the parent has to handle the menu. Includes the ID of the
+/// highlighted profile, if any
+#[derive(Debug)]
+struct OpenProfileActions(Option<ProfileId>);
+
+/// Local event to add a profile to the collection, after the user has entered
+/// its ID
+#[derive(Debug)]
+struct CreateProfile {
+    /// Profile to copy. If `None`, create an empty profile
+    from: Option<ProfileId>,
+    id: ProfileId,
+}
+
+/// Items in the actions popup menu for the profile list
+#[derive(
+    Copy, Clone, Debug, Default, Display, EnumCount, EnumIter, PartialEq,
+)]
+enum ProfileMenuAction {
+    #[default]
+    #[display("New Profile")]
+    New,
+    #[display("Clone Profile")]
+    Clone,
+    #[display("Edit Profile")]
+    Edit,
+}
+
+impl ToStringGenerate for ProfileMenuAction {}
+
 /// Modal to allow user to select a profile from a list and preview profile
 /// fields
 #[derive(Debug)]
@@ -171,7 +285,10 @@ impl ProfileListModal {
 
 impl Modal for ProfileListModal {
     fn title(&self) -> Line<'_> {
-        "Profiles".into()
+        TuiContext::get()
+            .input_engine
+            .add_hint("Profiles", Action::OpenActions)
+            .into()
     }
 
     fn dimensions(&self) -> (Constraint, Constraint) {
@@ -180,6 +297,23 @@ impl Modal for ProfileListModal {
 }
 
 impl EventHandler for ProfileListModal {
+    fn update(&mut self, event: Event) -> Update {
+        if let Some(Action::OpenActions) = event.action() {
+            // Only one modal can be shown at a time, so close this one and let
+            // the pane open the menu
+            ViewContext::push_event(Event::CloseModal { submitted: false });
+            ViewContext::push_event(Event::new_local(OpenProfileActions(
+                self.select
+                    .data()
+                    .selected()
+                    .map(|profile| profile.id.clone()),
+            )));
+            Update::Consumed
+        } else {
+            Update::Propagate(event)
+        }
+    }
+
     fn children(&mut self) -> Vec<Component<Child<'_>>> {
         vec![self.select.to_child_mut()]
     }
@@ -272,7 +406,16 @@ impl<'a> Generate for &'a ProfileListItem {
 /// Display the contents of a profile
 #[derive(Debug, Default)]
 struct ProfileDetail {
-    fields: StateCell<ProfileId, Vec<(String, TemplatePreview)>>,
+    fields: StateCell<ProfileId, Vec<ProfileField>>,
+}
+
+/// A single field in the profile detail
+#[derive(Debug)]
+struct ProfileField {
+    key: String,
+    /// If the value is inherited, the ID of the profile it comes from
+    inherited_from: Option<ProfileId>,
+    value: TemplatePreview,
 }
 
 struct ProfileDetailProps<'a> {
@@ -291,29 +434,44 @@ impl<'a> Draw<ProfileDetailProps<'a>> for ProfileDetail {
         let profile_id = props.profile_id;
         let fields = self.fields.get_or_update(profile_id, || {
             let collection = ViewContext::collection();
-            let Some(profile_data) = collection
+            if collection
                 .profiles
                 .get(profile_id)
                 // Failure is a logic error
                 .ok_or_else(|| anyhow!("No profile with ID `{profile_id}`"))
                 .reported(&ViewContext::messages_tx())
-                .map(|profile| &profile.data)
-            else {
+                .is_none()
+            {
                 return Default::default();
-            };
-            profile_data
-                .iter()
-                .map(|(key, template)| {
-                    (key.clone(), TemplatePreview::new(template.clone(), None))
+            }
+            // Include fields inherited from parent profiles
+            collection
+                .profile_data(profile_id)
+                .into_iter()
+                .map(|(key, (source_id, template))| ProfileField {
+                    key: key.to_owned(),
+                    inherited_from: (source_id != profile_id)
+                        .then(|| source_id.clone()),
+                    value: TemplatePreview::new(template.clone(), None),
                 })
                 .collect_vec()
         });
 
+        let styles = &TuiContext::get().styles;
         let table = Table {
             header: Some(["Field", "Value"]),
             rows: fields
                 .iter()
-                .map(|(key, value)| [key.as_str().into(), value.generate()])
+                .map(|field| {
+                    let mut key = Line::from(field.key.as_str());
+                    if let Some(source_id) = &field.inherited_from {
+                        key.push_span(Span::styled(
+                            format!(" (from {source_id})"),
+                            styles.text.hint,
+                        ));
+                    }
+                    [key.into(), field.value.generate()]
+                })
                 .collect_vec(),
             alternate_row_style: true,
             ..Default::default()
@@ -325,12 +483,19 @@ impl<'a> Draw<ProfileDetailProps<'a>> for ProfileDetail {
 #[cfg(test)]
 mod tests {
     use crate::{
-        test_util::{harness, TestHarness},
-        view::util::persistence::DatabasePersistedStore,
+        test_util::{harness, terminal, TestHarness, TestTerminal},
+        view::{
+            test_util::{TestComponent, WithModalQueue},
+            util::persistence::DatabasePersistedStore,
+        },
     };
+    use crossterm::event::KeyCode;
     use persisted::PersistedStore;
     use rstest::rstest;
-    use slumber_core::test_util::{by_id, Factory};
+    use slumber_core::{
+        assert_matches,
+        test_util::{by_id, Factory},
+    };
 
     use super::*;
 
@@ -363,4 +528,48 @@ mod tests {
         let component = ProfilePane::new(&profiles);
         assert_eq!(*component.selected_profile_id, expected);
     }
+
+    /// Clone a profile from the list modal. The copy is written to the
+    /// collection file, then selected
+    #[rstest]
+    fn test_clone_profile(mut harness: TestHarness, terminal: TestTerminal) {
+        let profile_id = harness.collection.profiles.first().unwrap().0.clone();
+        let mut component = TestComponent::new(
+            &terminal,
+            WithModalQueue::new(ProfilePane::new(&harness.collection.profiles)),
+            (),
+        );
+
+        // Open the list modal, then the actions menu, then clone
+        component.data().inner().open_modal();
+        component.drain_draw().assert_empty();
+        component.send_key(KeyCode::Char('x')).assert_empty();
+        component.send_key(KeyCode::Down).assert_empty();
+        component.send_key(KeyCode::Enter).assert_empty();
+        harness.clear_messages();
+        component.send_text("copy").assert_empty();
+        component.send_key(KeyCode::Enter).assert_empty();
+
+        let (edit, on_complete) = assert_matches!(
+            harness.pop_message_now(),
+            Message::CollectionWrite { edit, on_complete } => (edit, on_complete),
+        );
+        let source = format!("profiles:\n  {profile_id}:\n    data: {{}}\n");
+        assert_eq!(
+            edit(&source).unwrap(),
+            format!("{source}  copy:\n    data: {{}}\n")
+        );
+
+        // Once the file is written, the new profile is selected
+        on_complete(());
+        assert_matches!(
+            component.drain_draw().events(),
+            &[Event::HttpSelectRequest(None)]
+        );
+        assert_eq!(
+            component.data().inner().selected_profile_id(),
+            Some(&ProfileId::from("copy".to_owned()))
+        );
+        assert_matches!(harness.pop_message_now(), Message::Notify(_));
+    }
 }
@@ -15,19 +15,25 @@ use crate::{
     },
 };
 use anyhow::Context;
+use derive_more::Display;
 use persisted::PersistedContainer;
 use ratatui::{
     layout::{Constraint, Layout},
-    text::Text,
+    text::{Line, Text},
     Frame,
 };
 use serde_json_path::JsonPath;
 use slumber_config::Action;
 use slumber_core::{
-    http::{content_type::ContentType, query::Query, ResponseBody},
-    util::{MaybeStr, ResultTraced},
+    http::{
+        blob::{BlobRef, BlobStore},
+        content_type::ContentType,
+        query::Query,
+        ResponseBody,
+    },
+    util::{format_byte_size, MaybeStr, ResultTraced},
 };
-use std::cell::Cell;
+use std::{cell::Cell, ops::Range, str};
 
 /// Display response body as text, with a query box to filter it if the body has
 /// been parsed. The query state can be persisted by persisting this entire
@@ -35,11 +41,22 @@ use std::cell::Cell;
 #[derive(Debug)]
 pub struct QueryableBody {
     /// Visible text state. This needs to be in a cell because it's initialized
-    /// from the body passed in via props
-    filtered_text: StateCell<Option<Query>, Text<'static>>,
+    /// from the body passed in via props. Keyed by the display query, the
+    /// user's query, whether the full body has been loaded, and the visible
+    /// page of a streamed body, respectively
+    filtered_text:
+        StateCell<(Option<Query>, Option<Query>, bool, usize), BodyText>,
     /// Store whether the body can be queried. True only if it's a recognized
-    /// and parsed format
+    /// and parsed format, and the body isn't truncated
     query_available: Cell<bool>,
+    /// Bodies larger than this (in bytes) are truncated for display, because
+    /// rendering them can freeze the UI. Pulled from the config
+    truncate_size: usize,
+    /// Has the user asked to see the full body, even though it's large?
+    load_full: bool,
+    /// Streamed bodies are too large to load at all, so they're read from the
+    /// blob store one page at a time. Each page is `truncate_size` bytes
+    page: usize,
     /// Are we currently typing in the query box?
     query_focused: bool,
     /// Expression used to filter the content of the body down
@@ -60,6 +77,11 @@ pub struct QueryableBodyProps<'a> {
     /// a full response), it makes it easier to adapt in the future if we want
     /// to make request bodies queryable as well.
     pub body: &'a ResponseBody,
+    /// Query applied to the body before the user's query, to transform it for
+    /// display. The user's query is applied to the *transformed* body
+    pub display_query: Option<&'a Query>,
+    /// Lines (starting at 0) of the visible text to highlight, e.g. bookmarks
+    pub marks: &'a [usize],
 }
 
 impl QueryableBody {
@@ -86,6 +108,9 @@ impl QueryableBody {
         Self {
             filtered_text: Default::default(),
             query_available: Cell::new(false),
+            truncate_size: TuiContext::get().config.truncate_body_size,
+            load_full: false,
+            page: 0,
             query_focused: false,
             query: Default::default(),
             query_text_box: text_box.into(),
@@ -96,7 +121,58 @@ impl QueryableBody {
     /// Get visible body text. Return an owned value because that's what all
     /// consumers need anyway, and it makes the API simpler
     pub fn text(&self) -> Option<String> {
-        self.filtered_text.get().map(|text| text.to_string())
+        self.filtered_text.get().map(|text| text.text.to_string())
+    }
+
+    /// Is only part of the body visible, because it's too large? This doesn't
+    /// include a body that was cut off when it was received, because all of
+    /// that body is still visible
+    pub fn is_truncated(&self) -> bool {
+        self.filtered_text.get().is_some_and(|text| {
+            matches!(
+                text.truncation,
+                Some(Truncation::Prefix { .. } | Truncation::Page { .. })
+            )
+        })
+    }
+
+    /// Show the entire body, even if it's over the truncation size
+    pub fn load_full_body(&mut self) {
+        self.load_full = true;
+    }
+
+    /// Show the next or previous page of a streamed body. Stops at either end
+    pub fn turn_page(&mut self, body: &ResponseBody, forward: bool) {
+        let last_page = page_count(body.size(), self.truncate_size) - 1;
+        self.page = if forward {
+            (self.page + 1).min(last_page)
+        } else {
+            self.page.saturating_sub(1)
+        };
+        self.text_window.data_mut().scroll_to(0);
+    }
+
+    /// Get the applied query, if any
+    pub fn query(&self) -> Option<&Query> {
+        self.query.as_ref()
+    }
+
+    /// Get the text of the applied query. Empty if there is no query
+    pub fn query_text(&self) -> String {
+        self.query
+            .as_ref()
+            .map(Query::to_string)
+            .unwrap_or_default()
+    }
+
+    /// Get the index of the first visible line
+    pub fn first_line(&self) -> usize {
+        self.text_window.data().first_line()
+    }
+
+    /// Scroll the text so a line is as close to the top as possible
+    pub fn scroll_to(&mut self, line: usize) {
+        self.text_window.data_mut().scroll_to(line);
     }
 }
 
@@ -161,24 +237,77 @@ impl<'a> Draw<QueryableBodyProps<'a>> for QueryableBody {
         props: QueryableBodyProps,
         metadata: DrawMetadata,
     ) {
-        // Body can only be queried if it's been parsed
-        let query_available = props.body.parsed().is_some();
+        // Streamed bodies are always paged, even if the user asked for the
+        // full body
+        let blob = props.body.blob();
+        let truncated = blob.is_some()
+            || (!self.load_full && props.body.size() > self.truncate_size);
+        // Body can only be queried if it's been parsed. Truncated bodies are
+        // shown raw, so there's nothing to query
+        let query_available = props.body.parsed().is_some() && !truncated;
         self.query_available.set(query_available);
+        // The body may also have been cut off when it was received
+        let show_truncation = truncated || props.body.full_size().is_some();
 
-        let [body_area, query_area] = Layout::vertical([
+        let [truncation_area, body_area, query_area] = Layout::vertical([
+            Constraint::Length(show_truncation as u16),
             Constraint::Min(0),
             Constraint::Length(if query_available { 1 } else { 0 }),
         ])
         .areas(metadata.area());
 
         // Draw the body
-        let text = self.filtered_text.get_or_update(&self.query, || {
-            init_text(props.content_type, props.body, self.query.as_ref())
+        let key = (
+            props.display_query.cloned(),
+            self.query.clone(),
+            self.load_full,
+            self.page,
+        );
+        let text = self.filtered_text.get_or_update(&key, || {
+            if let Some(blob) = blob {
+                BodyText::page(
+                    props.content_type,
+                    blob,
+                    self.page,
+                    self.truncate_size,
+                )
+            } else if truncated {
+                BodyText::truncated(
+                    props.content_type,
+                    props.body.bytes(),
+                    self.truncate_size,
+                )
+            } else {
+                BodyText {
+                    text: init_text(
+                        props.content_type,
+                        props.body,
+                        props.display_query,
+                        self.query.as_ref(),
+                    ),
+                    truncation: props.body.full_size().map(|full_size| {
+                        Truncation::Captured {
+                            captured_size: props.body.size(),
+                            full_size: full_size as usize,
+                        }
+                    }),
+                }
+            }
         });
+        if let Some(truncation) = &text.truncation {
+            frame.render_widget(
+                Line::styled(
+                    truncation.to_string(),
+                    TuiContext::get().styles.text.hint,
+                ),
+                truncation_area,
+            );
+        }
         self.text_window.draw(
             frame,
             TextWindowProps {
-                text: &text,
+                text: &text.text,
+                marks: props.marks,
                 margins: ScrollbarMargins {
                     bottom: 2, // Extra margin to jump over the search box
                     ..Default::default()
@@ -217,10 +346,179 @@ enum QueryCallback {
     Submit,
 }
 
+/// Text to display for a body, and whether it's been truncated
+#[derive(Debug)]
+struct BodyText {
+    text: Text<'static>,
+    truncation: Option<Truncation>,
+}
+
+impl BodyText {
+    /// Show just the beginning of a large body. We skip prettification and
+    /// querying, because those require processing the entire body.
+    fn truncated(
+        content_type: Option<ContentType>,
+        body: &[u8],
+        size: usize,
+    ) -> Self {
+        let shown = truncate(body, size);
+        let text = highlight::highlight_if(
+            content_type,
+            format!("{:#}", MaybeStr(shown)).into(),
+        );
+        let truncation = Truncation::Prefix {
+            shown_size: shown.len(),
+            shown_lines: text.lines.len(),
+            total_size: body.len(),
+            total_lines: line_count(body),
+        };
+        Self {
+            text,
+            truncation: Some(truncation),
+        }
+    }
+
+    /// Show one page of a streamed body, read from the blob store. Like a
+    /// truncated body, the page is shown raw
+    fn page(
+        content_type: Option<ContentType>,
+        blob: &BlobRef,
+        page: usize,
+        page_size: usize,
+    ) -> Self {
+        let text = match read_page(blob, page, page_size).traced() {
+            Ok(data) => highlight::highlight_if(
+                content_type,
+                format!("{:#}", MaybeStr(&data)).into(),
+            ),
+            Err(error) => Line::styled(
+                format!("{error:#}"),
+                TuiContext::get().styles.text.error,
+            )
+            .into(),
+        };
+        let total_size = blob.size as usize;
+        Self {
+            text,
+            truncation: Some(Truncation::Page {
+                page: page + 1,
+                pages: page_count(total_size, page_size),
+                total_size,
+            }),
+        }
+    }
+}
+
+/// How much of a truncated body is visible
+#[derive(Debug, Display)]
+enum Truncation {
+    /// Only the beginning of the body is shown
+    #[display(
+        "Showing {} of {} ({shown_lines} of {total_lines} lines). Select \
+        \"Load Full Body\" from the actions menu to see the rest",
+        format_byte_size(*shown_size),
+        format_byte_size(*total_size),
+    )]
+    Prefix {
+        shown_size: usize,
+        shown_lines: usize,
+        total_size: usize,
+        total_lines: usize,
+    },
+    /// The body was streamed, so it's shown one page at a time
+    #[display(
+        "Showing page {page} of {pages} ({} total). Select \"Next Page\" or \
+        \"Previous Page\" from the actions menu to see more",
+        format_byte_size(*total_size),
+    )]
+    Page {
+        page: usize,
+        pages: usize,
+        total_size: usize,
+    },
+    /// The body was over the capture limit, so only the beginning was kept
+    #[display(
+        "Truncated: received {} of {}. Select \"Download Full Body\" from \
+        the actions menu to save all of it",
+        format_byte_size(*captured_size),
+        format_byte_size(*full_size),
+    )]
+    Captured {
+        captured_size: usize,
+        full_size: usize,
+    },
+}
+
+/// Get the first `size` bytes of a body. If that splits a UTF-8 character,
+/// the partial character is dropped so the text is still valid
+fn truncate(body: &[u8], size: usize) -> &[u8] {
+    let prefix = &body[..body.len().min(size)];
+    match str::from_utf8(prefix) {
+        // An incomplete character at the end has no error length. Any other
+        // error means the body isn't text, so leave it as-is
+        Err(error) if error.error_len().is_none() => {
+            &prefix[..error.valid_up_to()]
+        }
+        _ => prefix,
+    }
+}
+
+/// Number of pages needed to show a streamed body. There's always at least one
+fn page_count(size: usize, page_size: usize) -> usize {
+    size.div_ceil(page_size).max(1)
+}
+
+/// Read one page of a streamed body from the blob store
+fn read_page(
+    blob: &BlobRef,
+    page: usize,
+    page_size: usize,
+) -> anyhow::Result<Vec<u8>> {
+    // Read a few bytes past the end of the page, in case a character
+    // straddles the boundary
+    let data = BlobStore::global().read_range(
+        blob,
+        (page * page_size) as u64,
+        page_size + 3,
+    )?;
+    Ok(data[page_bounds(&data, page_size)].to_vec())
+}
+
+/// Get the range of a page's bytes to show, so a UTF-8 character that
+/// straddles the boundary between two pages is shown whole on the earlier
+/// page. `data` starts at the page's offset and may extend past its end.
+fn page_bounds(data: &[u8], page_size: usize) -> Range<usize> {
+    // Continuation bytes (0b10xxxxxx) never start a character, and there are
+    // at most 3 in a row
+    let next_boundary = |index: usize| {
+        let index = index.min(data.len());
+        index
+            + data[index..]
+                .iter()
+                .take(3)
+                .take_while(|&&byte| byte & 0xc0 == 0x80)
+                .count()
+    };
+    let end = next_boundary(page_size);
+    next_boundary(0).min(end)..end
+}
+
+/// Count lines in a body, without decoding it
+fn line_count(body: &[u8]) -> usize {
+    let newlines = body.iter().filter(|&&byte| byte == b'\n').count();
+    // Don't count a trailing newline as starting another line
+    if body.last().is_some_and(|&byte| byte != b'\n') {
+        newlines + 1
+    } else {
+        newlines
+    }
+}
+
 /// Calculate display text based on current body/query
 fn init_text(
     content_type: Option<ContentType>,
     body: &ResponseBody,
+    display_query: Option<&Query>,
     query: Option<&Query>,
 ) -> Text<'static> {
     // Query and prettify text if possible. This involves a lot of cloning
@@ -229,8 +527,11 @@ fn init_text(
     let body = body
         .parsed()
         .map(|parsed_body| {
-            // Body is a known content type so we parsed it - apply a query if
+            // Body is a known content type so we parsed it - apply queries if
             // necessary and prettify the output
+            let transformed =
+                display_query.map(|query| query.query_display(parsed_body));
+            let parsed_body = transformed.as_deref().unwrap_or(parsed_body);
             query
                 .map(|query| query.query(parsed_body).prettify())
                 .unwrap_or_else(|| parsed_body.prettify())
@@ -259,7 +560,9 @@ mod tests {
     use reqwest::StatusCode;
     use rstest::{fixture, rstest};
     use serde::Serialize;
-    use slumber_core::{http::ResponseRecord, test_util::header_map};
+    use slumber_core::{
+        assert_matches, http::ResponseRecord, test_util::header_map,
+    };
 
     const TEXT: &[u8] = b"{\"greeting\":\"hello\"}";
 
@@ -293,6 +596,8 @@ mod tests {
             QueryableBodyProps {
                 content_type: None,
                 body: &body,
+                display_query: None,
+                marks: &[],
             },
         );
 
@@ -326,6 +631,8 @@ mod tests {
             QueryableBodyProps {
                 content_type: None,
                 body: &json_response.body,
+                display_query: None,
+                marks: &[],
             },
         );
 
@@ -380,6 +687,157 @@ mod tests {
         ]);
     }
 
+    /// Display query is applied before the user's query
+    #[rstest]
+    fn test_display_query(
+        _harness: TestHarness,
+        #[with(32, 5)] terminal: TestTerminal,
+    ) {
+        let response = ResponseRecord {
+            status: StatusCode::OK,
+            headers: header_map([("Content-Type", "application/json")]),
+            body: ResponseBody::new(
+                b"{\"data\":{\"greeting\":\"hello\"}}".as_slice().into(),
+            ),
+        };
+        response.parse_body();
+        let display_query: Query = "$.data".parse().unwrap();
+        let mut component = TestComponent::new(
+            &terminal,
+            QueryableBody::new(),
+            QueryableBodyProps {
+                content_type: None,
+                body: &response.body,
+                display_query: Some(&display_query),
+                marks: &[],
+            },
+        );
+        assert_eq!(
+            component.data().text().as_deref(),
+            Some("{\n  \"greeting\": \"hello\"\n}")
+        );
+
+        component.send_key(KeyCode::Char('/')).assert_empty();
+        component.send_text("$.greeting").assert_empty();
+        component.send_key(KeyCode::Enter).assert_empty();
+        assert_eq!(
+            component.data().text().as_deref(),
+            Some("[\n  \"hello\"\n]")
+        );
+    }
+
+    /// Large bodies are truncated until the full body is loaded
+    #[rstest]
+    fn test_truncated(
+        _harness: TestHarness,
+        #[with(40, 4)] terminal: TestTerminal,
+        json_response: ResponseRecord,
+    ) {
+        let mut body = QueryableBody::new();
+        body.truncate_size = 12;
+        let mut component = TestComponent::new(
+            &terminal,
+            body,
+            QueryableBodyProps {
+                content_type: None,
+                body: &json_response.body,
+                display_query: None,
+                marks: &[],
+            },
+        );
+
+        let data = component.data();
+        assert!(data.is_truncated());
+        assert!(!data.query_available.get());
+        assert_eq!(data.text().as_deref(), Some("{\"greeting\":\n"));
+
+        component.data_mut().load_full_body();
+        component.drain_draw().assert_empty();
+        let data = component.data();
+        assert!(!data.is_truncated());
+        assert!(data.query_available.get());
+        assert_eq!(
+            data.text().as_deref(),
+            Some("{\n  \"greeting\": \"hello\"\n}")
+        );
+    }
+
+    /// A body cut off by the capture limit is shown in full, with a note
+    /// that there's more
+    #[rstest]
+    fn test_captured(
+        _harness: TestHarness,
+        #[with(40, 4)] terminal: TestTerminal,
+    ) {
+        let body = ResponseBody::new(TEXT.into()).truncated(1000);
+        let component = TestComponent::new(
+            &terminal,
+            QueryableBody::new(),
+            QueryableBodyProps {
+                content_type: None,
+                body: &body,
+                display_query: None,
+                marks: &[],
+            },
+        );
+
+        let data = component.data();
+        // Everything that was received is visible
+        assert!(!data.is_truncated());
+        assert_matches!(
+            &data.filtered_text.get().unwrap().truncation,
+            Some(Truncation::Captured {
+                captured_size: 20,
+                full_size: 1000
+            })
+        );
+    }
+
+    #[rstest]
+    #[case::ascii(b"hello", 3, b"hel")]
+    #[case::short(b"hello", 10, b"hello")]
+    #[case::split_char("héllo".as_bytes(), 2, b"h")]
+    #[case::binary(b"\xff\xfe\xfd", 2, b"\xff\xfe")]
+    fn test_truncate(
+        #[case] body: &[u8],
+        #[case] size: usize,
+        #[case] expected: &[u8],
+    ) {
+        assert_eq!(truncate(body, size), expected);
+    }
+
+    /// A character split between two pages is shown on the first one
+    #[rstest]
+    #[case::ascii(b"hello", 3, 0..3)]
+    #[case::short(b"he", 3, 0..2)]
+    #[case::split_end("heé!".as_bytes(), 3, 0..4)]
+    // Second half of "é"
+    #[case::split_start(b"\xa9!", 3, 1..2)]
+    fn test_page_bounds(
+        #[case] data: &[u8],
+        #[case] page_size: usize,
+        #[case] expected: Range<usize>,
+    ) {
+        assert_eq!(page_bounds(data, page_size), expected);
+    }
+
+    #[rstest]
+    #[case::empty(0, 1)]
+    #[case::exact(20, 2)]
+    #[case::partial(21, 3)]
+    fn test_page_count(#[case] size: usize, #[case] expected: usize) {
+        assert_eq!(page_count(size, 10), expected);
+    }
+
+    #[rstest]
+    #[case::empty(b"", 0)]
+    #[case::one_line(b"hello", 1)]
+    #[case::trailing_newline(b"hello\n", 1)]
+    #[case::multiple(b"a\nb\nc", 3)]
+    fn test_line_count(#[case] body: &[u8], #[case] expected: usize) {
+        assert_eq!(line_count(body), expected);
+    }
+
     /// Render a parsed body with query text box, and load initial query from
     /// the DB. This tests the `PersistedContainer` implementation
     #[rstest]
@@ -404,6 +862,8 @@ mod tests {
             QueryableBodyProps {
                 content_type: None,
                 body: &json_response.body,
+                display_query: None,
+                marks: &[],
             },
         );
         assert_eq!(component.data().query, Some("$.greeting".parse().unwrap()));
@@ -0,0 +1,265 @@
+//! Compare two recipes side by side
+
+use crate::{
+    context::TuiContext,
+    message::Message,
+    util::ResultReported,
+    view::{
+        common::{modal::Modal, tabs::Tabs},
+        component::{
+            misc::SelectListModal,
+            response_diff::{DiffMode, DiffView},
+            Component,
+        },
+        draw::{Draw, DrawMetadata},
+        event::{Child, EventHandler},
+        ViewContext,
+    },
+};
+use derive_more::Display;
+use itertools::Itertools;
+use ratatui::{
+    layout::{Constraint, Layout},
+    text::{Line, Span},
+    Frame,
+};
+use slumber_core::{
+    collection::{ProfileId, Recipe, RecipeId},
+    http::{
+        diff::{diff_lines, DiffLine, ResponseDiff},
+        Exchange,
+    },
+};
+use strum::{EnumCount, EnumIter};
+
+/// Ask the user for another recipe, then compare it to the given one
+pub fn select_recipe_to_compare(
+    recipe_id: RecipeId,
+    profile_id: Option<ProfileId>,
+) {
+    let options = ViewContext::collection()
+        .recipes
+        .recipe_ids()
+        .filter(|id| **id != recipe_id)
+        .map(RecipeId::to_string)
+        .collect_vec();
+    if options.is_empty() {
+        ViewContext::send_message(Message::Notify(
+            "No other recipes to compare with".into(),
+        ));
+        return;
+    }
+    ViewContext::open_modal(SelectListModal::new(
+        format!("Compare `{recipe_id}` with"),
+        options,
+        move |other| {
+            compare_recipes(&recipe_id, &other.into(), profile_id.as_ref())
+        },
+    ));
+}
+
+/// Show the differences between two recipes' definitions, as well as their
+/// most recent responses for the given profile
+fn compare_recipes(
+    first: &RecipeId,
+    second: &RecipeId,
+    profile_id: Option<&ProfileId>,
+) {
+    let collection = ViewContext::collection();
+    let Some((first, second)) = collection
+        .recipes
+        .try_get_recipe(first)
+        .and_then(|first| {
+            Ok((first, collection.recipes.try_get_recipe(second)?))
+        })
+        .reported(&ViewContext::messages_tx())
+    else {
+        return;
+    };
+    let Some((first_exchange, second_exchange)) =
+        ViewContext::with_database(|database| {
+            Ok::<_, anyhow::Error>((
+                database.get_latest_request(profile_id, &first.id)?,
+                database.get_latest_request(profile_id, &second.id)?,
+            ))
+        })
+        .reported(&ViewContext::messages_tx())
+    else {
+        return;
+    };
+    ViewContext::open_modal(RecipeDiffModal::new(
+        first,
+        second,
+        first_exchange.as_ref(),
+        second_exchange.as_ref(),
+    ));
+}
+
+/// Two recipes side by side. One tab shows their definitions, the other shows
+/// their latest responses. The first recipe is on the left.
+#[derive(Debug)]
+pub struct RecipeDiffModal {
+    title: String,
+    tabs: Component<Tabs<Tab>>,
+    definition: Component<DiffView>,
+    /// Diff of the latest responses, or an explanation of why there isn't one
+    response: Result<Component<DiffView>, String>,
+}
+
+#[derive(
+    Copy, Clone, Debug, Default, Display, EnumCount, EnumIter, PartialEq,
+)]
+enum Tab {
+    #[default]
+    Definition,
+    Response,
+}
+
+impl RecipeDiffModal {
+    fn new(
+        first: &Recipe,
+        second: &Recipe,
+        first_exchange: Option<&Exchange>,
+        second_exchange: Option<&Exchange>,
+    ) -> Self {
+        let definition = DiffView::new(
+            vec![("Recipe", definition_diff(first, second))],
+            DiffMode::SideBySide,
+        );
+        let response = match (first_exchange, second_exchange) {
+            (Some(first_exchange), Some(second_exchange)) => {
+                Ok(DiffView::response(
+                    ResponseDiff::new(
+                        &first_exchange.response,
+                        &second_exchange.response,
+                    ),
+                    DiffMode::SideBySide,
+                )
+                .into())
+            }
+            (None, _) => {
+                Err(format!("No response for `{}` in this profile", first.id))
+            }
+            (_, None) => {
+                Err(format!("No response for `{}` in this profile", second.id))
+            }
+        };
+        Self {
+            title: format!("{} ↔ {}", first.id, second.id),
+            tabs: Default::default(),
+            definition: definition.into(),
+            response,
+        }
+    }
+}
+
+impl Modal for RecipeDiffModal {
+    fn title(&self) -> Line<'_> {
+        self.title.as_str().into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(90), Constraint::Percentage(80))
+    }
+}
+
+impl EventHandler for RecipeDiffModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        let mut children = vec![self.tabs.to_child_mut()];
+        children.push(self.definition.to_child_mut());
+        if let Ok(response) = &mut self.response {
+            children.push(response.to_child_mut());
+        }
+        children
+    }
+}
+
+impl Draw for RecipeDiffModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let [tabs_area, content_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)])
+                .areas(metadata.area());
+        self.tabs.draw(frame, (), tabs_area, true);
+        match (self.tabs.data().selected(), &self.response) {
+            (Tab::Definition, _) => {
+                self.definition.draw(frame, (), content_area, true)
+            }
+            (Tab::Response, Ok(response)) => {
+                response.draw(frame, (), content_area, true)
+            }
+            (Tab::Response, Err(message)) => frame.render_widget(
+                Span::styled(
+                    message.as_str(),
+                    TuiContext::get().styles.text.hint,
+                ),
+                content_area,
+            ),
+        }
+    }
+}
+
+/// Diff the YAML definitions of two recipes. Empty fields are left out, so
+/// only what the user actually set shows up.
+fn definition_diff(first: &Recipe, second: &Recipe) -> Vec<DiffLine> {
+    // Recipes are always serializable, but don't panic just in case
+    let yaml = |recipe: &Recipe| {
+        serde_yaml::to_value(recipe)
+            .and_then(|mut value| {
+                if let serde_yaml::Value::Mapping(fields) = &mut value {
+                    fields.retain(|_, field| match field {
+                        serde_yaml::Value::Null
+                        | serde_yaml::Value::Bool(false) => false,
+                        serde_yaml::Value::Sequence(items) => !items.is_empty(),
+                        serde_yaml::Value::Mapping(items) => !items.is_empty(),
+                        _ => true,
+                    });
+                }
+                serde_yaml::to_string(&value)
+            })
+            .unwrap_or_else(|error| error.to_string())
+    };
+    diff_lines(&yaml(first), &yaml(second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{harness, terminal, TestHarness, TestTerminal},
+        view::test_util::TestComponent,
+    };
+    use crossterm::event::KeyCode;
+    use rstest::rstest;
+    use slumber_core::test_util::Factory;
+
+    /// Definitions are diffed, and the response tab explains when there's
+    /// nothing to compare
+    #[rstest]
+    fn test_recipe_diff(_harness: TestHarness, terminal: TestTerminal) {
+        let first = Recipe::factory("first");
+        let second = Recipe {
+            url: "http://localhost/other".into(),
+            ..Recipe::factory("second")
+        };
+        let exchange = Exchange::factory(first.id.clone());
+        let mut component = TestComponent::new(
+            &terminal,
+            RecipeDiffModal::new(&first, &second, Some(&exchange), None),
+            (),
+        );
+
+        assert_eq!(component.data().title, "first ↔ second");
+        let definition = definition_diff(&first, &second);
+        assert!(definition
+            .contains(&DiffLine::Removed("url: http://localhost/url".into())));
+        assert!(definition
+            .contains(&DiffLine::Added("url: http://localhost/other".into())));
+
+        component.send_key(KeyCode::Right).assert_empty();
+        assert_eq!(component.data().tabs.data().selected(), Tab::Response);
+        assert_eq!(
+            component.data().response.as_ref().unwrap_err(),
+            "No response for `second` in this profile"
+        );
+    }
+}
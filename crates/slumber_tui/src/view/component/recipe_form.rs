@@ -0,0 +1,566 @@
+//! Form to create a recipe, or edit the simple fields of an existing one,
+//! without leaving the TUI. The same form is used to compose one-off scratch
+//! requests.
+
+use crate::{
+    message::Message,
+    util::ResultReported,
+    view::{
+        common::{
+            form::{self, Form, FormField, SubmitForm},
+            modal::Modal,
+            text_box::TextBox,
+        },
+        component::Component,
+        draw::{Draw, DrawMetadata},
+        event::{Child, Event, EventHandler, Update},
+        util::persistence::DatabasePersistedStore,
+        ViewContext,
+    },
+};
+use derive_more::Display;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use persisted::PersistedStore;
+use ratatui::{layout::Constraint, text::Line, Frame};
+use serde::Serialize;
+use slumber_core::collection::{
+    self, Method, ProfileId, Recipe, RecipeFields, RecipeId,
+};
+use strum::{EnumIter, IntoEnumIterator};
+
+/// Modal with one text box per recipe field. Submitting writes the recipe to
+/// the collection file, which then gets reloaded by the file watcher. In
+/// scratch mode, the recipe is sent as a one-off request instead.
+#[derive(Debug)]
+pub struct RecipeFormModal {
+    mode: FormMode,
+    /// Initial values of the form. Fields that can't be edited in the form are
+    /// taken from here
+    original: Option<RecipeFields>,
+    form: Component<Form<Field>>,
+    /// Validated form contents, stashed on submission so they can be used
+    /// when the modal closes
+    submission: Option<(RecipeId, RecipeFields)>,
+}
+
+/// What happens to the form contents on submission
+#[derive(Debug)]
+enum FormMode {
+    /// Add a new recipe to the collection
+    New,
+    /// Update an existing recipe in the collection
+    Edit(RecipeId),
+    /// Send a one-off request, without touching the collection
+    Scratch { profile_id: Option<ProfileId> },
+}
+
+#[derive(Copy, Clone, Debug, Display, EnumIter, PartialEq)]
+enum Field {
+    #[display("ID")]
+    Id,
+    #[display("Name")]
+    Name,
+    #[display("Method")]
+    Method,
+    #[display("URL")]
+    Url,
+    #[display("Headers")]
+    Headers,
+    #[display("Body")]
+    Body,
+}
+
+/// Persistence key for the most recent scratch request, so it can be
+/// recalled or saved to the collection later
+#[derive(Debug, Default, persisted::PersistedKey, Serialize)]
+#[persisted(Option<RecipeFields>)]
+struct ScratchKey;
+
+impl RecipeFormModal {
+    /// Create a form for a new recipe
+    pub fn new_recipe() -> Self {
+        Self::new(FormMode::New, None, true)
+    }
+
+    /// Create a form to edit the simple fields of an existing recipe
+    pub fn edit_recipe(recipe: &Recipe) -> Self {
+        Self::new(
+            FormMode::Edit(recipe.id.clone()),
+            Some(RecipeFields::from_recipe(recipe)),
+            RecipeFields::is_body_editable(recipe),
+        )
+    }
+
+    /// Create a form to compose a one-off request, which is sent with the
+    /// given profile. The form is filled with the previous scratch request,
+    /// if any.
+    pub fn scratch(profile_id: Option<ProfileId>) -> Self {
+        Self::new(FormMode::Scratch { profile_id }, load_scratch(), true)
+    }
+
+    /// Create a form to add the most recent scratch request to the collection.
+    /// Return `None` if no scratch request has been sent.
+    pub fn save_scratch() -> Option<Self> {
+        Some(Self::new(FormMode::New, Some(load_scratch()?), true))
+    }
+
+    fn new(
+        mode: FormMode,
+        original: Option<RecipeFields>,
+        body_editable: bool,
+    ) -> Self {
+        let fields = Field::iter()
+            .filter(|field| mode.has_field(*field))
+            .map(|field| {
+                let (value, enabled) = match &original {
+                    Some(original) => {
+                        initial_value(field, original, body_editable)
+                    }
+                    // New recipes default to GET; everything else is empty
+                    None if field == Field::Method => {
+                        (Method::Get.to_string(), true)
+                    }
+                    None => (String::new(), true),
+                };
+                // Some values can't be represented in a single-line text
+                // box (e.g. multi-line or structured bodies). Those fields
+                // are shown but can't be selected, and are left unchanged.
+                FormField::new(
+                    field,
+                    field.to_string(),
+                    field.text_box(enabled).default_value(value),
+                )
+                .enabled(enabled)
+            })
+            .collect();
+        Self {
+            mode,
+            original,
+            form: Form::new(fields).into(),
+            submission: None,
+        }
+    }
+
+    /// Get the current text of a field. `None` if the field isn't in the form
+    /// or is disabled
+    fn text(&self, field: Field) -> Option<&str> {
+        self.form.data().text(&field)
+    }
+
+    /// Validate the form and convert it into recipe fields
+    fn build(&self) -> Result<(RecipeId, RecipeFields), String> {
+        let id = match &self.mode {
+            FormMode::New => match self.text(Field::Id) {
+                Some(id) if !id.is_empty() => id.to_owned().into(),
+                _ => return Err("ID is required".into()),
+            },
+            FormMode::Edit(id) => id.clone(),
+            FormMode::Scratch { .. } => RecipeId::scratch(),
+        };
+        let method = self
+            .text(Field::Method)
+            .unwrap_or_default()
+            .parse::<Method>()
+            .map_err(|_| "Invalid HTTP method".to_owned())?;
+        let url = match self.text(Field::Url) {
+            Some(url) => url.to_owned(),
+            None => self
+                .original
+                .as_ref()
+                .map(|original| original.url.clone())
+                .unwrap_or_default(),
+        };
+        if url.is_empty() {
+            return Err("URL is required".into());
+        }
+        let headers = match self.text(Field::Headers) {
+            Some(text) => parse_headers(text).ok_or_else(|| {
+                "Headers must be formatted as `name: value; name: value`"
+                    .to_owned()
+            })?,
+            // Disabled; keep the original
+            None => self
+                .original
+                .as_ref()
+                .map(|original| original.headers.clone())
+                .unwrap_or_default(),
+        };
+        let body = match self.text(Field::Body) {
+            Some(body) => (!body.is_empty()).then(|| body.to_owned()),
+            None => self
+                .original
+                .as_ref()
+                .and_then(|original| original.body.clone()),
+        };
+        let name = self
+            .text(Field::Name)
+            .filter(|name| !name.is_empty())
+            .map(String::from);
+        let fields = RecipeFields {
+            name,
+            method,
+            url,
+            headers,
+            body,
+        };
+        // Catch invalid templates now, so the user can fix them
+        fields
+            .to_recipe(id.clone())
+            .map_err(|error| format!("{error:#}"))?;
+        Ok((id, fields))
+    }
+}
+
+impl FormMode {
+    /// Should the given field be shown in the form?
+    fn has_field(&self, field: Field) -> bool {
+        match self {
+            Self::New => true,
+            // ID can't be changed here; that's what renaming is for
+            Self::Edit(_) => field != Field::Id,
+            // Scratch requests aren't stored in the collection, so there's
+            // nothing to identify
+            Self::Scratch { .. } => !matches!(field, Field::Id | Field::Name),
+        }
+    }
+}
+
+impl Field {
+    /// Build an empty text box for this field, with appropriate validation
+    fn text_box(self, enabled: bool) -> TextBox {
+        let text_box = TextBox::default();
+        if !enabled {
+            return text_box
+                .placeholder("Can't be edited here; edit the collection file");
+        }
+        match self {
+            Self::Id => text_box.validator(|id| {
+                ViewContext::collection()
+                    .recipes
+                    .get(&RecipeId::from(id.to_owned()))
+                    .is_none()
+            }),
+            Self::Name => text_box.placeholder("Optional"),
+            Self::Method => {
+                text_box.validator(|method| method.parse::<Method>().is_ok())
+            }
+            Self::Url => text_box.placeholder("{{host}}/path"),
+            Self::Headers => text_box
+                .placeholder("name: value; name: value")
+                .validator(|headers| parse_headers(headers).is_some()),
+            Self::Body => text_box.placeholder("Optional"),
+        }
+    }
+}
+
+/// Get the initial value for a field from an existing recipe, and whether the
+/// field can be edited in the form
+fn initial_value(
+    field: Field,
+    original: &RecipeFields,
+    body_editable: bool,
+) -> (String, bool) {
+    match field {
+        // ID is only shown for new recipes, so there's nothing to fill in
+        Field::Id => (String::new(), true),
+        Field::Name => (original.name.clone().unwrap_or_default(), true),
+        Field::Method => (original.method.to_string(), true),
+        Field::Url => (original.url.clone(), !original.url.contains('\n')),
+        Field::Headers => match format_headers(&original.headers) {
+            Some(headers) => (headers, true),
+            None => (String::new(), false),
+        },
+        Field::Body => match &original.body {
+            _ if !body_editable => (String::new(), false),
+            Some(body) if body.contains('\n') => (String::new(), false),
+            body => (body.clone().unwrap_or_default(), true),
+        },
+    }
+}
+
+/// Load the most recent scratch request
+fn load_scratch() -> Option<RecipeFields> {
+    DatabasePersistedStore::load_persisted(&ScratchKey).flatten()
+}
+
+/// Parse headers from the form's single-line format: `name: value; name:
+/// value`. Return `None` if any header is malformed
+fn parse_headers(text: &str) -> Option<IndexMap<String, String>> {
+    text.split(';')
+        .map(str::trim)
+        .filter(|header| !header.is_empty())
+        .map(|header| {
+            let (name, value) = header.split_once(':')?;
+            let name = name.trim();
+            (!name.is_empty())
+                .then(|| (name.to_owned(), value.trim().to_owned()))
+        })
+        .collect()
+}
+
+/// Format headers into a single line for the form. Return `None` if they
+/// can't be represented unambiguously
+fn format_headers(headers: &IndexMap<String, String>) -> Option<String> {
+    if headers
+        .iter()
+        .any(|(name, value)| name.contains([':', ';']) || value.contains(';'))
+    {
+        return None;
+    }
+    Some(
+        headers
+            .iter()
+            .map(|(name, value)| format!("{name}: {value}"))
+            .join("; "),
+    )
+}
+
+impl Modal for RecipeFormModal {
+    fn title(&self) -> Line<'_> {
+        match &self.mode {
+            FormMode::New => "New Recipe".into(),
+            FormMode::Edit(id) => format!("Edit Recipe `{id}`").into(),
+            FormMode::Scratch { .. } => "Scratch Request".into(),
+        }
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Percentage(60),
+            Constraint::Length(self.form.data().height()),
+        )
+    }
+
+    fn on_close(self: Box<Self>, submitted: bool) {
+        let Some((id, fields)) = self.submission.filter(|_| submitted) else {
+            return;
+        };
+        let is_new = match self.mode {
+            FormMode::New => true,
+            FormMode::Edit(_) => false,
+            FormMode::Scratch { profile_id } => {
+                send_scratch(profile_id, fields);
+                return;
+            }
+        };
+        let message_id = id.clone();
+        // Recipes can come from or extend imported files, so check against
+        // all of them
+        let loaded = ViewContext::collection();
+        ViewContext::send_message(Message::CollectionWrite {
+            edit: Box::new(move |source| {
+                if is_new {
+                    collection::add_recipe(source, &loaded, &id, &fields)
+                } else {
+                    collection::edit_recipe(source, &loaded, &id, &fields)
+                }
+            }),
+            on_complete: Box::new(move |()| {
+                let verb = if is_new { "Created" } else { "Updated" };
+                ViewContext::send_message(Message::Notify(format!(
+                    "{verb} recipe `{message_id}`"
+                )));
+            }),
+        });
+    }
+}
+
+/// Remember a scratch request so it can be recalled later, then send it
+fn send_scratch(profile_id: Option<ProfileId>, fields: RecipeFields) {
+    // Templates were already validated on submission
+    let Some(recipe) = fields
+        .to_recipe(RecipeId::scratch())
+        .reported(&ViewContext::messages_tx())
+    else {
+        return;
+    };
+    DatabasePersistedStore::store_persisted(&ScratchKey, &Some(fields));
+    ViewContext::send_message(Message::HttpBeginScratch {
+        profile_id,
+        recipe: recipe.into(),
+    });
+}
+
+impl EventHandler for RecipeFormModal {
+    fn update(&mut self, event: Event) -> Update {
+        if let Some(SubmitForm) = event.local() {
+            self.submission = form::submit(self.build());
+            Update::Consumed
+        } else {
+            Update::Propagate(event)
+        }
+    }
+
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.form.to_child_mut()]
+    }
+}
+
+impl Draw for RecipeFormModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        self.form.draw(frame, (), metadata.area(), true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{harness, TestHarness};
+    use rstest::rstest;
+    use slumber_core::{assert_matches, test_util::Factory};
+
+    /// Set the text of a field in the form
+    fn set_text(modal: &mut RecipeFormModal, field: Field, text: &str) {
+        modal.form.data_mut().set_text(&field, text);
+    }
+
+    /// Submit the form
+    fn submit(modal: &mut RecipeFormModal) {
+        modal.update(Event::new_local(SubmitForm));
+    }
+
+    /// Fill out the form for a new recipe, and write it to the collection
+    #[rstest]
+    fn test_new_recipe(mut harness: TestHarness) {
+        let mut modal = RecipeFormModal::new_recipe();
+        set_text(&mut modal, Field::Id, "users");
+        set_text(&mut modal, Field::Url, "{{host}}/users");
+        set_text(&mut modal, Field::Headers, "accept: application/json");
+        harness.clear_messages();
+        submit(&mut modal);
+        assert_matches!(
+            ViewContext::pop_event(),
+            Some(Event::CloseModal { submitted: true })
+        );
+
+        Box::new(modal).on_close(true);
+        let edit = assert_matches!(
+            harness.pop_message_now(),
+            Message::CollectionWrite { edit, .. } => edit,
+        );
+        let edited = edit("").unwrap();
+        assert!(edited.starts_with("requests:\n  users: !request\n"));
+        assert!(edited.contains("url: '{{host}}/users'"), "{edited}");
+        assert!(edited.contains("accept: application/json"), "{edited}");
+    }
+
+    /// Edit an existing recipe. Fields that can't be shown in the form are
+    /// disabled, and retain their original values
+    #[rstest]
+    fn test_edit_recipe(_harness: TestHarness) {
+        let recipe = Recipe {
+            url: "{{host}}/users".into(),
+            headers: IndexMap::from_iter([(
+                "cookie".into(),
+                "a=1; b=2".into(),
+            )]),
+            ..Recipe::factory(())
+        };
+        let mut modal = RecipeFormModal::edit_recipe(&recipe);
+        assert_eq!(
+            modal
+                .form
+                .data()
+                .fields()
+                .iter()
+                .map(|field| (*field.key(), field.is_enabled()))
+                .collect_vec(),
+            vec![
+                (Field::Name, true),
+                (Field::Method, true),
+                (Field::Url, true),
+                (Field::Headers, false),
+                (Field::Body, true),
+            ]
+        );
+
+        set_text(&mut modal, Field::Method, "post");
+        let (id, fields) = modal.build().unwrap();
+        assert_eq!(id, recipe.id);
+        assert_eq!(fields.method.to_string(), "POST");
+        assert_eq!(fields.url, "{{host}}/users");
+        assert_eq!(fields.headers["cookie"], "a=1; b=2");
+    }
+
+    /// Send a scratch request, then save it as a recipe
+    #[rstest]
+    fn test_scratch(mut harness: TestHarness) {
+        assert!(RecipeFormModal::save_scratch().is_none());
+
+        let profile_id = harness.collection.first_profile_id().clone();
+        let mut modal = RecipeFormModal::scratch(Some(profile_id.clone()));
+        assert_eq!(
+            modal
+                .form
+                .data()
+                .fields()
+                .iter()
+                .map(|field| *field.key())
+                .collect_vec(),
+            vec![Field::Method, Field::Url, Field::Headers, Field::Body]
+        );
+        set_text(&mut modal, Field::Method, "POST");
+        set_text(&mut modal, Field::Url, "{{host}}/login");
+        set_text(&mut modal, Field::Body, "{{credentials}}");
+        harness.clear_messages();
+        submit(&mut modal);
+        Box::new(modal).on_close(true);
+        let (sent_profile_id, recipe) = assert_matches!(
+            harness.pop_message_now(),
+            Message::HttpBeginScratch { profile_id, recipe } =>
+                (profile_id, recipe),
+        );
+        assert_eq!(sent_profile_id, Some(profile_id.clone()));
+        assert_eq!(recipe.id, RecipeId::scratch());
+        assert_eq!(recipe.url.display(), "{{host}}/login");
+
+        // The next scratch request starts where the last one left off
+        let modal = RecipeFormModal::scratch(Some(profile_id));
+        assert_eq!(modal.text(Field::Url), Some("{{host}}/login"));
+
+        // Saving it as a recipe requires an ID
+        let mut modal = RecipeFormModal::save_scratch().unwrap();
+        assert_eq!(modal.title(), Line::from("New Recipe"));
+        assert_eq!(modal.text(Field::Id), Some(""));
+        assert_eq!(modal.text(Field::Method), Some("POST"));
+        assert_eq!(modal.text(Field::Body), Some("{{credentials}}"));
+        assert_eq!(modal.build().unwrap_err(), "ID is required");
+        set_text(&mut modal, Field::Id, "login");
+        assert_eq!(modal.build().unwrap().0, RecipeId::from("login"));
+    }
+
+    /// Invalid templates are caught before the form closes
+    #[rstest]
+    fn test_invalid_template(_harness: TestHarness) {
+        let mut modal = RecipeFormModal::scratch(None);
+        set_text(&mut modal, Field::Url, "{{host");
+        let error = modal.build().unwrap_err();
+        assert!(error.starts_with("Invalid template for URL"), "{error}");
+    }
+
+    #[rstest]
+    #[case::empty("", Some(vec![]))]
+    #[case::multiple(
+        "accept: application/json; x-token: {{token}}",
+        Some(vec![("accept", "application/json"), ("x-token", "{{token}}")])
+    )]
+    #[case::colon_in_value(
+        "x-time: 12:00",
+        Some(vec![("x-time", "12:00")])
+    )]
+    #[case::missing_colon("accept", None)]
+    #[case::empty_name(": value", None)]
+    fn test_parse_headers(
+        #[case] text: &str,
+        #[case] expected: Option<Vec<(&str, &str)>>,
+    ) {
+        let expected = expected.map(|headers| {
+            headers
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                .collect::<IndexMap<_, _>>()
+        });
+        assert_eq!(parse_headers(text), expected);
+    }
+}
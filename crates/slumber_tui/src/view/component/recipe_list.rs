@@ -46,6 +46,10 @@ pub struct RecipeListPane {
     /// issue though, it just means it'll be pre-collapsed if the user ever
     /// adds the folder back. Not worth working around.
     collapsed: Persisted<SingletonKey<Collapsed>>,
+    /// Recipes marked to be sent together. Not persisted, since it's only
+    /// relevant for the current batch
+    /// Invariant: No folders, only recipes
+    marked: HashSet<RecipeId>,
 }
 
 /// Persisted key for the ID of the selected recipe
@@ -59,16 +63,28 @@ impl RecipeListPane {
         // recipes
         let collapsed: Persisted<SingletonKey<Collapsed>> =
             Persisted::default();
+        let marked = HashSet::new();
         let persistent = PersistedLazy::new(
             SelectedRecipeKey,
-            collapsed.build_select_state(recipes),
+            collapsed.build_select_state(recipes, &marked),
         );
         Self {
             select: persistent.into(),
             collapsed,
+            marked,
         }
     }
 
+    /// IDs of all marked recipes, in the order they appear in the tree
+    pub fn marked_recipes(&self) -> Vec<RecipeId> {
+        ViewContext::collection()
+            .recipes
+            .recipe_ids()
+            .filter(|id| self.marked.contains(*id))
+            .cloned()
+            .collect()
+    }
+
     /// ID and kind of whatever recipe/folder in the list is selected. `None`
     /// iff the list is empty
     pub fn selected_node(
@@ -108,9 +124,10 @@ impl RecipeListPane {
 
         // If we changed the set of what is visible, rebuild the list state
         if changed {
-            let mut new_select_state = self
-                .collapsed
-                .build_select_state(&ViewContext::collection().recipes);
+            let mut new_select_state = self.collapsed.build_select_state(
+                &ViewContext::collection().recipes,
+                &self.marked,
+            );
 
             // Carry over the selection
             if let Some(selected) = select.selected() {
@@ -121,6 +138,24 @@ impl RecipeListPane {
 
         changed
     }
+
+    /// Mark or unmark the selected recipe. If a folder is selected, do
+    /// nothing
+    fn toggle_selected_marked(&mut self) {
+        let mut select = self.select.data_mut().get_mut();
+        let Some(index) = select.selected_index() else {
+            return;
+        };
+        let item = &mut select.items_mut()[index].value;
+        if item.is_recipe() {
+            item.marked = !item.marked;
+            if item.marked {
+                self.marked.insert(item.id.clone());
+            } else {
+                self.marked.remove(&item.id);
+            }
+        }
+    }
 }
 
 impl EventHandler for RecipeListPane {
@@ -141,7 +176,10 @@ impl EventHandler for RecipeListPane {
                 self.set_selected_collapsed(CollapseState::Expand);
             }
             Action::Toggle => {
-                self.set_selected_collapsed(CollapseState::Toggle);
+                // Space collapses folders and marks recipes
+                if !self.set_selected_collapsed(CollapseState::Toggle) {
+                    self.toggle_selected_marked();
+                }
             }
             Action::OpenActions => {
                 let recipe = self
@@ -180,9 +218,12 @@ impl Draw for RecipeListPane {
     fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
         let context = TuiContext::get();
 
-        let title = context
+        let mut title = context
             .input_engine
             .add_hint("Recipes", Action::SelectRecipeList);
+        if !self.marked.is_empty() {
+            title.push_str(&format!(" ({} marked)", self.marked.len()));
+        }
         let block = Pane {
             title: &title,
             has_focus: metadata.has_focus(),
@@ -205,6 +246,7 @@ struct RecipeListItem {
     kind: RecipeNodeDiscriminants,
     depth: usize,
     collapsed: bool,
+    marked: bool,
 }
 
 impl RecipeListItem {
@@ -247,6 +289,7 @@ impl<'a> Generate for &'a RecipeListItem {
         let icon = match self.kind {
             RecipeNodeDiscriminants::Folder if self.collapsed => "▶",
             RecipeNodeDiscriminants::Folder => "▼",
+            RecipeNodeDiscriminants::Recipe if self.marked => "✓ ",
             RecipeNodeDiscriminants::Recipe => "",
         };
 
@@ -294,6 +337,7 @@ impl Collapsed {
     fn build_select_state(
         &self,
         recipes: &RecipeTree,
+        marked: &HashSet<RecipeId>,
     ) -> SelectState<RecipeListItem> {
         // When highlighting a new recipe, load it from the repo
         fn on_select(_: &mut RecipeListItem) {
@@ -310,6 +354,7 @@ impl Collapsed {
                 name: node.name().to_owned(),
                 kind: node.into(),
                 collapsed: self.is_collapsed(node.id()),
+                marked: marked.contains(node.id()),
                 depth: lookup_key.as_slice().len() - 1,
             })
             .collect();
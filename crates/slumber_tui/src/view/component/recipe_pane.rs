@@ -8,27 +8,37 @@ pub use persistence::RecipeOverrideStore;
 
 use crate::{
     context::TuiContext,
-    message::RequestConfig,
+    message::{Message, RequestConfig},
+    util::ResultReported,
     view::{
         common::{actions::ActionsModal, Pane},
-        component::{primary::PrimaryPane, recipe_pane::recipe::RecipeDisplay},
+        component::{
+            misc::SelectListModal,
+            primary::PrimaryPane,
+            recipe_pane::recipe::{RecipeDisplay, RecipeDisplayProps},
+        },
         draw::{Draw, DrawMetadata, Generate, ToStringGenerate},
         event::{Child, Event, EventHandler, Update},
         state::StateCell,
+        util::persistence::Persisted,
         Component, ViewContext,
     },
 };
 use derive_more::Display;
 use itertools::{Itertools, Position};
 use ratatui::{
+    layout::Alignment,
     text::{Line, Text},
+    widgets::block::Title,
     Frame,
 };
+use serde::Serialize;
 use slumber_config::Action;
 use slumber_core::{
     collection::{Folder, HasId, ProfileId, RecipeId, RecipeNode},
-    util::doc_link,
+    util::{doc_link, format_time},
 };
+use std::cell::Ref;
 use strum::{EnumCount, EnumIter};
 
 /// Display for the current recipe node, which could be a recipe, a folder, or
@@ -38,8 +48,19 @@ pub struct RecipePane {
     /// All UI state derived from the recipe is stored together, and reset when
     /// the recipe or profile changes
     recipe_state: StateCell<RecipeStateKey, Option<Component<RecipeDisplay>>>,
+    /// Number of drafts saved for the recipe by other sessions. Loaded from
+    /// the DB, so only refreshed when the recipe changes
+    draft_count: StateCell<RecipeId, usize>,
+    /// Show the raw URL template instead of the preview. This applies to all
+    /// recipes, so it's persisted outside the recipe state
+    show_raw_url: Persisted<ShowRawUrlKey>,
 }
 
+/// Persistence key for the raw/preview toggle on the URL
+#[derive(Debug, Default, persisted::PersistedKey, Serialize)]
+#[persisted(bool)]
+struct ShowRawUrlKey;
+
 #[derive(Clone)]
 pub struct RecipePaneProps<'a> {
     /// ID of the recipe *or* folder selected
@@ -60,8 +81,58 @@ impl RecipePane {
             recipe_id,
             profile_id,
             options,
+            note: None,
         })
     }
+
+    /// Throw away all recipe state, so it's rebuilt on the next draw. Call this
+    /// after overrides are changed externally, e.g. by restoring a draft.
+    pub fn reset(&mut self) {
+        self.recipe_state = StateCell::default();
+        self.draft_count = StateCell::default();
+    }
+
+    /// Switch the URL between the raw template and the rendered preview
+    pub fn toggle_url_preview(&mut self) {
+        let mut show_raw_url = self.show_raw_url.get_mut();
+        *show_raw_url = !*show_raw_url;
+    }
+
+    /// Rebuild the recipe state if the selected recipe or profile has changed.
+    /// This happens automatically on draw, but needs to be called explicitly
+    /// while the pane is hidden so [Self::request_config] stays current.
+    pub fn update_state(&self, props: &RecipePaneProps) {
+        self.get_state(props);
+    }
+
+    fn get_state(
+        &self,
+        props: &RecipePaneProps,
+    ) -> Ref<'_, Option<Component<RecipeDisplay>>> {
+        // Whenever the recipe or profile changes, generate a preview for
+        // each templated value. Almost anything that could change the
+        // preview will either involve changing one of those two things, or
+        // reloading the collection, which resets this state.
+        self.recipe_state.get_or_update(
+            &RecipeStateKey {
+                selected_profile_id: props.selected_profile_id.cloned(),
+                recipe_id: props
+                    .selected_recipe_node
+                    .map(RecipeNode::id)
+                    .cloned(),
+            },
+            || match props.selected_recipe_node {
+                Some(RecipeNode::Recipe(recipe)) => Some(
+                    RecipeDisplay::new(
+                        recipe,
+                        &ViewContext::collection().recipe_defaults(&recipe.id),
+                    )
+                    .into(),
+                ),
+                Some(RecipeNode::Folder(_)) | None => None,
+            },
+        )
+    }
 }
 
 impl EventHandler for RecipePane {
@@ -116,34 +187,44 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
             },
             Action::SelectRecipe,
         );
-        let block = Pane {
+        let mut block = Pane {
             title: &title,
             has_focus: metadata.has_focus(),
-        };
-        let block = block.generate();
+        }
+        .generate();
+        // Make it obvious when a recipe won't verify the server's certificate
+        if let Some(RecipeNode::Recipe(recipe)) = props.selected_recipe_node {
+            if recipe.danger_skip_tls_verify {
+                block = block.title(
+                    Title::from(Line::styled(
+                        "TLS verification disabled",
+                        TuiContext::get().styles.text.error,
+                    ))
+                    .alignment(Alignment::Right),
+                );
+            }
+            // Let the user know there's something to restore
+            let draft_count =
+                *self.draft_count.get_or_update(&recipe.id, || {
+                    RecipeOverrideStore::load_drafts(&recipe.id)
+                        .map(|drafts| drafts.len())
+                        .unwrap_or_default()
+                });
+            if draft_count > 0 {
+                let label = if draft_count == 1 { "draft" } else { "drafts" };
+                block = block.title(
+                    Title::from(Line::styled(
+                        format!("{draft_count} saved {label}"),
+                        TuiContext::get().styles.text.hint,
+                    ))
+                    .alignment(Alignment::Right),
+                );
+            }
+        }
         let inner_area = block.inner(metadata.area());
         frame.render_widget(block, metadata.area());
 
-        // Whenever the recipe or profile changes, generate a preview for
-        // each templated value. Almost anything that could change the
-        // preview will either involve changing one of those two things, or
-        // would require reloading the whole collection which will reset
-        // UI state.
-        let recipe_state = self.recipe_state.get_or_update(
-            &RecipeStateKey {
-                selected_profile_id: props.selected_profile_id.cloned(),
-                recipe_id: props
-                    .selected_recipe_node
-                    .map(RecipeNode::id)
-                    .cloned(),
-            },
-            || match props.selected_recipe_node {
-                Some(RecipeNode::Recipe(recipe)) => {
-                    Some(RecipeDisplay::new(recipe).into())
-                }
-                Some(RecipeNode::Folder(_)) | None => None,
-            },
-        );
+        let recipe_state = self.get_state(&props);
 
         match props.selected_recipe_node {
             None => frame.render_widget(
@@ -158,10 +239,14 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
             }
             Some(RecipeNode::Recipe(_)) => {
                 // Unwrap is safe because we just initialized state above
-                recipe_state
-                    .as_ref()
-                    .unwrap()
-                    .draw(frame, (), inner_area, true)
+                recipe_state.as_ref().unwrap().draw(
+                    frame,
+                    RecipeDisplayProps {
+                        show_raw_url: *self.show_raw_url,
+                    },
+                    inner_area,
+                    true,
+                )
             }
         };
     }
@@ -183,12 +268,46 @@ pub enum RecipeMenuAction {
     #[default]
     #[display("Edit Collection")]
     EditCollection,
+    #[display("Edit Recipe")]
+    EditRecipe,
+    #[display("New Recipe")]
+    NewRecipe,
+    #[display("Import cURL")]
+    ImportCurl,
+    #[display("Edit Recipe Fields")]
+    EditRecipeFields,
+    #[display("Scratch Request")]
+    ScratchRequest,
+    #[display("Save Scratch as Recipe")]
+    SaveScratch,
     #[display("Copy URL")]
     CopyUrl,
     #[display("Copy Body")]
     CopyBody,
     #[display("Copy as cURL")]
     CopyCurl,
+    #[display("Toggle URL Preview")]
+    ToggleUrlPreview,
+    #[display("Compare With Recipe")]
+    CompareRecipe,
+    #[display("Compare Across Profiles")]
+    CompareProfiles,
+    #[display("Restore Draft")]
+    RestoreDraft,
+    #[display("Send Marked Recipes")]
+    SendMarked,
+    #[display("Send Marked in Parallel")]
+    SendMarkedParallel,
+    #[display("Clear Chain Cache")]
+    ClearChainCache,
+    #[display("View Captured Requests")]
+    ViewCapturedRequests,
+    #[display("Search History")]
+    SearchHistory,
+    #[display("View Diagnostics")]
+    ViewDiagnostics,
+    #[display("Find References")]
+    FindReferences,
 }
 
 impl RecipeMenuAction {
@@ -203,13 +322,66 @@ impl RecipeMenuAction {
                 &[Self::CopyBody]
             }
         } else {
-            &[Self::CopyUrl, Self::CopyBody, Self::CopyCurl]
+            &[
+                Self::EditRecipeFields,
+                Self::CopyUrl,
+                Self::CopyBody,
+                Self::CopyCurl,
+                Self::ToggleUrlPreview,
+                Self::CompareRecipe,
+                Self::CompareProfiles,
+                Self::RestoreDraft,
+            ]
         }
     }
 }
 
 impl ToStringGenerate for RecipeMenuAction {}
 
+/// Event emitted after a draft is restored into the override store, so the
+/// recipe pane can rebuild its state with the restored overrides
+#[derive(Debug)]
+pub struct DraftRestored;
+
+/// Show the drafts saved for a recipe by other sessions, and restore whichever
+/// one the user selects
+pub fn select_draft(recipe_id: RecipeId) {
+    let Some(mut drafts) = RecipeOverrideStore::load_drafts(&recipe_id)
+        .reported(&ViewContext::messages_tx())
+    else {
+        return;
+    };
+    if drafts.is_empty() {
+        ViewContext::send_message(Message::Notify(format!(
+            "No saved drafts for recipe `{recipe_id}`"
+        )));
+        return;
+    }
+    let options = drafts
+        .iter()
+        .map(|draft| {
+            format!(
+                "{} (last saved {})",
+                draft.name,
+                format_time(&draft.updated_at)
+            )
+        })
+        .collect_vec();
+    ViewContext::open_modal(SelectListModal::new(
+        format!("Restore draft for `{recipe_id}`"),
+        options.clone(),
+        move |selected| {
+            let Some(index) = options.iter().position(|o| *o == selected)
+            else {
+                return;
+            };
+            let draft = drafts.swap_remove(index);
+            RecipeOverrideStore::restore_draft(&recipe_id, draft);
+            ViewContext::push_event(Event::new_local(DraftRestored));
+        },
+    ));
+}
+
 /// Render folder as a tree
 impl<'a> Generate for &'a Folder {
     type Output<'this> = Text<'this>
@@ -271,7 +443,13 @@ impl<'a> Generate for &'a Folder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        test_util::{harness, TestHarness},
+        view::util::persistence::DatabasePersistedStore,
+    };
+    use persisted::PersistedStore;
     use pretty_assertions::assert_eq;
+    use rstest::rstest;
     use slumber_core::{
         collection::Recipe,
         test_util::{by_id, Factory},
@@ -282,6 +460,7 @@ mod tests {
         let folder = Folder {
             id: "1f".into(),
             name: None,
+            defaults: Default::default(),
             children: by_id([
                 RecipeNode::Recipe(Recipe::factory("1.1r")),
                 RecipeNode::Recipe(Recipe::factory("1.2r")),
@@ -289,6 +468,7 @@ mod tests {
                 RecipeNode::Folder(Folder {
                     id: "1.3f".into(),
                     name: None,
+                    defaults: Default::default(),
                     children: by_id([RecipeNode::Recipe(Recipe::factory(
                         "1.3.1r",
                     ))]),
@@ -297,6 +477,7 @@ mod tests {
                 RecipeNode::Folder(Folder {
                     id: "1.4f".into(),
                     name: None,
+                    defaults: Default::default(),
                     children: Default::default(),
                 }),
                 // End with a nested folder to make sure the leftmost
@@ -304,11 +485,13 @@ mod tests {
                 RecipeNode::Folder(Folder {
                     id: "1.5f".into(),
                     name: None,
+                    defaults: Default::default(),
                     children: by_id([
                         RecipeNode::Recipe(Recipe::factory("1.5.1r")),
                         RecipeNode::Folder(Folder {
                             id: "1.5.2f".into(),
                             name: None,
+                            defaults: Default::default(),
                             children: by_id([RecipeNode::Recipe(
                                 Recipe::factory("1.5.2.1r"),
                             )]),
@@ -331,4 +514,20 @@ mod tests {
     └─1.5.2.1r";
         assert_eq!(folder.generate().to_string(), expected);
     }
+
+    /// URL preview toggle is persisted, so it applies to every recipe and
+    /// survives restarts
+    #[rstest]
+    fn test_toggle_url_preview(_harness: TestHarness) {
+        let mut pane = RecipePane::default();
+        assert!(!*pane.show_raw_url);
+
+        pane.toggle_url_preview();
+        assert!(*pane.show_raw_url);
+        assert_eq!(
+            DatabasePersistedStore::load_persisted(&ShowRawUrlKey),
+            Some(true)
+        );
+        assert!(*RecipePane::default().show_raw_url);
+    }
 }
@@ -2,7 +2,9 @@ use crate::{
     context::TuiContext,
     util::ResultReported,
     view::{
-        common::{table::Table, text_box::TextBox},
+        common::{
+            table::Table, template_preview::TemplatePreview, text_box::TextBox,
+        },
         component::{
             misc::TextBoxModal,
             recipe_pane::persistence::{RecipeOverrideKey, RecipeTemplate},
@@ -61,6 +63,27 @@ impl AuthenticationDisplay {
                     None,
                 ),
             },
+            Authentication::AwsSigv4 {
+                region,
+                service,
+                access_key_id,
+                profile,
+                ..
+            } => State::AwsSigv4 {
+                region: region.into(),
+                service: service.into(),
+                // Never show the secret key. The access key ID identifies the
+                // credentials well enough
+                credentials: match (access_key_id, profile) {
+                    (Some(access_key_id), _) => {
+                        ("Access Key:", access_key_id.into())
+                    }
+                    (None, Some(profile)) => ("Profile:", profile.into()),
+                    (None, None) => {
+                        ("Profile:", Template::raw("default".into()).into())
+                    }
+                },
+            },
         };
         Self(inner)
     }
@@ -69,18 +92,20 @@ impl AuthenticationDisplay {
     /// override value. Return `None` to use the recipe's stock auth.
     pub fn override_value(&self) -> Option<Authentication> {
         if self.0.is_overridden() {
-            Some(match &self.0 {
+            match &self.0 {
                 State::Basic {
                     username, password, ..
-                } => Authentication::Basic {
+                } => Some(Authentication::Basic {
                     username: username.template().clone(),
                     // See note on field def for why we always use Some
                     password: Some(password.template().clone()),
-                },
+                }),
                 State::Bearer { token, .. } => {
-                    Authentication::Bearer(token.template().clone())
+                    Some(Authentication::Bearer(token.template().clone()))
                 }
-            })
+                // Not editable, so never overridden
+                State::AwsSigv4 { .. } => None,
+            }
         } else {
             None
         }
@@ -104,7 +129,7 @@ impl EventHandler for AuthenticationDisplay {
             State::Basic { selected_field, .. } => {
                 vec![selected_field.to_child_mut()]
             }
-            State::Bearer { .. } => vec![],
+            State::Bearer { .. } | State::AwsSigv4 { .. } => vec![],
         }
     }
 }
@@ -142,6 +167,26 @@ impl Draw for AuthenticationDisplay {
                 frame.render_widget(token.preview().generate(), content_area);
                 "Bearer"
             }
+            State::AwsSigv4 {
+                region,
+                service,
+                credentials: (credentials_label, credentials),
+            } => {
+                let table = Table {
+                    rows: vec![
+                        ["Region:".into(), region.generate()],
+                        ["Service:".into(), service.generate()],
+                        [(*credentials_label).into(), credentials.generate()],
+                    ],
+                    column_widths: &[
+                        Constraint::Length(12),
+                        Constraint::Min(0),
+                    ],
+                    ..Default::default()
+                };
+                frame.render_widget(table.generate(), content_area);
+                "AWS SigV4"
+            }
         };
 
         let mut title: Line = Span::styled(
@@ -172,6 +217,14 @@ enum State {
     Bearer {
         token: RecipeTemplate,
     },
+    /// SigV4 has too many fields to make editing worthwhile, so it's
+    /// display-only
+    AwsSigv4 {
+        region: TemplatePreview,
+        service: TemplatePreview,
+        /// Label and value for whichever credentials source is in use
+        credentials: (&'static str, TemplatePreview),
+    },
 }
 
 impl State {
@@ -182,6 +235,7 @@ impl State {
                 username, password, ..
             } => username.is_overridden() || password.is_overridden(),
             Self::Bearer { token } => token.is_overridden(),
+            Self::AwsSigv4 { .. } => false,
         }
     }
 
@@ -204,6 +258,7 @@ impl State {
             Self::Bearer { token, .. } => {
                 ("bearer token", token.template().display())
             }
+            Self::AwsSigv4 { .. } => return,
         };
         ViewContext::open_modal(TextBoxModal::new(
             format!("Edit {label}"),
@@ -246,6 +301,8 @@ impl State {
             Self::Bearer { token } => {
                 token.set_override(template);
             }
+            // Not editable
+            Self::AwsSigv4 { .. } => {}
         }
     }
 }
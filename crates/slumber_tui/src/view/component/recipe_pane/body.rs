@@ -58,6 +58,7 @@ impl RecipeBodyDisplay {
                                 recipe_id: recipe_id.clone(),
                                 field: field.clone(),
                             },
+                            false,
                         )
                     }),
                 );
@@ -225,6 +226,7 @@ impl Draw for RawBody {
                 // Do *not* call generate, because that clones the text and
                 // we only need a reference
                 text: &self.body.preview().text(),
+                marks: &[],
                 margins: Default::default(),
                 footer: if self.body.is_overridden() {
                     Some("(edited)".set_style(styles.text.hint).into())
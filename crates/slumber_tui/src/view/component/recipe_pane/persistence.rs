@@ -1,28 +1,112 @@
 //! Single-session persistence for recipe overrides
 
 use crate::view::{common::template_preview::TemplatePreview, ViewContext};
+use chrono::Utc;
+use itertools::Itertools;
 use persisted::{PersistedContainer, PersistedLazy, PersistedStore};
+use serde::{Deserialize, Serialize};
 use slumber_core::{
-    collection::RecipeId, http::content_type::ContentType, template::Template,
+    collection::RecipeId, db::RecipeDraft, http::content_type::ContentType,
+    template::Template, util::format_time,
 };
 use std::{collections::HashMap, fmt::Debug};
 use tracing::debug;
 
 /// Special single-session [PersistedStore] just for edited recipe templates.
-/// We don't want to store recipe overrides across sessions, because they could
-/// be very large and conflict with changes in the recipe. Using a dedicated
-/// type for this makes the generic bounds stricter which is nice.
+/// We don't want to restore recipe overrides across sessions automatically,
+/// because they could be very large and conflict with changes in the recipe.
+/// Using a dedicated type for this makes the generic bounds stricter which is
+/// nice.
+///
+/// Whenever an override is stored, all overrides for that recipe are also
+/// saved to a draft in the database, named after the start of the session. If
+/// the app exits unexpectedly, the user can restore the draft manually.
 ///
 /// To persist something in this store, you probably want to use
 /// [RecipeTemplate] for your component/state field.
-#[derive(Debug, Default)]
-pub struct RecipeOverrideStore(HashMap<RecipeOverrideKey, Template>);
+#[derive(Debug)]
+pub struct RecipeOverrideStore {
+    overrides: HashMap<RecipeOverrideKey, Template>,
+    /// Name of the draft that this session saves to
+    draft_name: String,
+}
+
+impl RecipeOverrideStore {
+    /// Get saved drafts for a recipe from other sessions, newest first
+    pub fn load_drafts(
+        recipe_id: &RecipeId,
+    ) -> anyhow::Result<Vec<RecipeDraft<RecipeOverrideDraft>>> {
+        let draft_name =
+            ViewContext::with_override_store(|store| store.draft_name.clone());
+        let drafts = ViewContext::with_database(|database| {
+            database.get_drafts::<RecipeOverrideDraft>(recipe_id)
+        })?;
+        Ok(drafts
+            .into_iter()
+            .filter(|draft| draft.name != draft_name)
+            .collect())
+    }
+
+    /// Move a draft from another session into this session. Its overrides
+    /// are added to the current overrides for the recipe, and it's deleted
+    /// from the database. The recipe's components must be rebuilt to pick up
+    /// the restored values.
+    pub fn restore_draft(
+        recipe_id: &RecipeId,
+        draft: RecipeDraft<RecipeOverrideDraft>,
+    ) {
+        debug!(%recipe_id, name = %draft.name, "Restoring recipe draft");
+        ViewContext::with_override_store_mut(|store| {
+            for (kind, template) in draft.value {
+                let key = RecipeOverrideKey {
+                    kind,
+                    recipe_id: recipe_id.clone(),
+                };
+                store.overrides.insert(key, template);
+            }
+        });
+        ViewContext::with_database(|database| {
+            database.delete_draft(recipe_id, &draft.name)
+        })
+        // Error is already traced in the DB, nothing to do with it here
+        .ok();
+        Self::save_draft(recipe_id);
+    }
+
+    /// Save all overrides for a recipe to this session's draft
+    fn save_draft(recipe_id: &RecipeId) {
+        let (draft_name, draft) = ViewContext::with_override_store(|store| {
+            let draft: RecipeOverrideDraft = store
+                .overrides
+                .iter()
+                .filter(|(key, _)| &key.recipe_id == recipe_id)
+                .map(|(key, template)| (key.kind.clone(), template.clone()))
+                .sorted_by_key(|(kind, _)| kind.clone())
+                .collect();
+            (store.draft_name.clone(), draft)
+        });
+        ViewContext::with_database(|database| {
+            database.set_draft(recipe_id, &draft_name, draft)
+        })
+        // Error is already traced in the DB, nothing to do with it here
+        .ok();
+    }
+}
+
+impl Default for RecipeOverrideStore {
+    fn default() -> Self {
+        Self {
+            overrides: HashMap::new(),
+            draft_name: format_time(&Utc::now()).to_string(),
+        }
+    }
+}
 
 impl PersistedStore<RecipeOverrideKey> for RecipeOverrideStore {
     fn load_persisted(key: &RecipeOverrideKey) -> Option<RecipeOverrideValue> {
-        if let Some(template) =
-            ViewContext::with_override_store(|store| store.0.get(key).cloned())
-        {
+        if let Some(template) = ViewContext::with_override_store(|store| {
+            store.overrides.get(key).cloned()
+        }) {
             // Only overridden values are persisted
             debug!(?key, ?template, "Loaded persisted recipe override");
             Some(RecipeOverrideValue::Override(template))
@@ -37,8 +121,9 @@ impl PersistedStore<RecipeOverrideKey> for RecipeOverrideStore {
         if let RecipeOverrideValue::Override(template) = value {
             debug!(?key, ?template, "Persisting recipe override");
             ViewContext::with_override_store_mut(|store| {
-                store.0.insert(key.clone(), template.clone());
-            })
+                store.overrides.insert(key.clone(), template.clone());
+            });
+            Self::save_draft(&key.recipe_id);
         }
     }
 }
@@ -211,10 +296,18 @@ impl RecipeOverrideKey {
     }
 }
 
+/// All overrides for one recipe, as saved in a draft. Fields are identified by
+/// index, so if the recipe changes between sessions, a restored override may
+/// land on a different field than it was made for.
+pub type RecipeOverrideDraft = Vec<(RecipeOverrideKeyKind, Template)>;
+
 /// Different kinds of recipe fields that can be persisted. This is exposed only
 /// through methods on [RecipeOverrideKey] to make usage a bit terser.
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
-enum RecipeOverrideKeyKind {
+#[derive(
+    Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum RecipeOverrideKeyKind {
     Body,
     AuthenticationBasicUsername,
     AuthenticationBasicPassword,
@@ -223,3 +316,74 @@ enum RecipeOverrideKeyKind {
     Header(usize),
     FormField(usize),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{harness, TestHarness};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use slumber_core::test_util::Factory;
+
+    /// Storing an override saves all of the recipe's overrides to this
+    /// session's draft
+    #[rstest]
+    fn test_save_draft(harness: TestHarness) {
+        let recipe_id = RecipeId::factory(());
+        for (key, value) in [
+            (RecipeOverrideKey::header(recipe_id.clone(), 1), "header"),
+            (RecipeOverrideKey::body(recipe_id.clone()), "body"),
+        ] {
+            RecipeOverrideStore::store_persisted(
+                &key,
+                &RecipeOverrideValue::Override(value.into()),
+            );
+        }
+
+        let drafts = harness
+            .database
+            .get_drafts::<RecipeOverrideDraft>(&recipe_id)
+            .unwrap();
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(
+            drafts[0].value,
+            vec![
+                (RecipeOverrideKeyKind::Body, "body".into()),
+                (RecipeOverrideKeyKind::Header(1), "header".into()),
+            ]
+        );
+        // This session's draft isn't offered for restoring
+        assert_eq!(RecipeOverrideStore::load_drafts(&recipe_id).unwrap(), []);
+    }
+
+    /// Restoring a draft from another session loads its overrides into the
+    /// store, and moves it into this session's draft
+    #[rstest]
+    fn test_restore_draft(harness: TestHarness) {
+        let recipe_id = RecipeId::factory(());
+        let draft: RecipeOverrideDraft =
+            vec![(RecipeOverrideKeyKind::QueryParam(0), "restored".into())];
+        harness
+            .database
+            .set_draft(&recipe_id, "old session", &draft)
+            .unwrap();
+
+        let mut drafts = RecipeOverrideStore::load_drafts(&recipe_id).unwrap();
+        assert_eq!(drafts.len(), 1);
+        RecipeOverrideStore::restore_draft(&recipe_id, drafts.remove(0));
+
+        assert_eq!(
+            RecipeOverrideStore::load_persisted(
+                &RecipeOverrideKey::query_param(recipe_id.clone(), 0)
+            ),
+            Some(RecipeOverrideValue::Override("restored".into()))
+        );
+        let drafts = harness
+            .database
+            .get_drafts::<RecipeOverrideDraft>(&recipe_id)
+            .unwrap();
+        assert_eq!(drafts.len(), 1);
+        assert_ne!(drafts[0].name, "old session");
+        assert_eq!(drafts[0].value, draft);
+    }
+}
@@ -26,7 +26,7 @@ use ratatui::{
 use serde::{Deserialize, Serialize};
 use slumber_config::Action;
 use slumber_core::{
-    collection::{Method, Recipe, RecipeId},
+    collection::{Method, Recipe, RecipeDefaults, RecipeId},
     http::BuildOptions,
 };
 use strum::{EnumCount, EnumIter};
@@ -43,16 +43,21 @@ pub struct RecipeDisplay {
     headers: Component<RecipeFieldTable<HeaderRowKey, HeaderRowToggleKey>>,
     body: Option<Component<RecipeBodyDisplay>>,
     authentication: Option<Component<AuthenticationDisplay>>,
+    /// Is the authentication inherited from defaults, rather than set on the
+    /// recipe itself?
+    authentication_inherited: bool,
 }
 
 impl RecipeDisplay {
     /// Initialize new recipe state. Should be called whenever the recipe or
-    /// profile changes
-    pub fn new(recipe: &Recipe) -> Self {
+    /// profile changes. Values inherited from the given defaults are shown
+    /// alongside the recipe's own.
+    pub fn new(recipe: &Recipe, defaults: &RecipeDefaults) -> Self {
+        let authentication = defaults.authentication(recipe);
         Self {
             tabs: Default::default(),
             method: recipe.method,
-            url: TemplatePreview::new(recipe.url.clone(), None),
+            url: TemplatePreview::new(defaults.url(recipe).into_owned(), None),
             query: RecipeFieldTable::new(
                 QueryRowKey(recipe.id.clone()),
                 recipe.query.iter().enumerate().map(|(i, (param, value))| {
@@ -64,14 +69,26 @@ impl RecipeDisplay {
                             recipe_id: recipe.id.clone(),
                             param: param.clone(),
                         },
+                        false,
                     )
                 }),
             )
             .into(),
             headers: RecipeFieldTable::new(
                 HeaderRowKey(recipe.id.clone()),
-                recipe.headers.iter().enumerate().map(
-                    |(i, (header, value))| {
+                // Inherited headers go after the recipe's own, to match the
+                // override indexes used when building the request
+                recipe
+                    .headers
+                    .iter()
+                    .map(|header| (header, false))
+                    .chain(
+                        defaults
+                            .inherited_headers(recipe)
+                            .map(|header| (header, true)),
+                    )
+                    .enumerate()
+                    .map(|(i, ((header, value), inherited))| {
                         (
                             header.clone(),
                             value.clone(),
@@ -80,24 +97,24 @@ impl RecipeDisplay {
                                 recipe_id: recipe.id.clone(),
                                 header: header.clone(),
                             },
+                            inherited,
                         )
-                    },
-                ),
+                    }),
             )
             .into(),
             body: recipe.body.as_ref().map(|body| {
                 RecipeBodyDisplay::new(body, recipe.id.clone()).into()
             }),
             // Map authentication type
-            authentication: recipe.authentication.as_ref().map(
-                |authentication| {
-                    AuthenticationDisplay::new(
-                        recipe.id.clone(),
-                        authentication.clone(),
-                    )
-                    .into()
-                },
-            ),
+            authentication: authentication.map(|authentication| {
+                AuthenticationDisplay::new(
+                    recipe.id.clone(),
+                    authentication.clone(),
+                )
+                .into()
+            }),
+            authentication_inherited: recipe.authentication.is_none()
+                && authentication.is_some(),
         }
     }
 
@@ -128,6 +145,7 @@ impl RecipeDisplay {
             query_parameters: self.query.data().to_build_overrides(),
             form_fields,
             body,
+            ..Default::default()
         }
     }
 
@@ -152,8 +170,18 @@ impl EventHandler for RecipeDisplay {
     }
 }
 
-impl Draw for RecipeDisplay {
-    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+pub struct RecipeDisplayProps {
+    /// Show the URL template as written, instead of its rendered preview
+    pub show_raw_url: bool,
+}
+
+impl Draw<RecipeDisplayProps> for RecipeDisplay {
+    fn draw(
+        &self,
+        frame: &mut Frame,
+        props: RecipeDisplayProps,
+        metadata: DrawMetadata,
+    ) {
         let tui_context = TuiContext::get();
 
         // Render request contents
@@ -176,7 +204,11 @@ impl Draw for RecipeDisplay {
 
         // First line: Method + URL
         frame.render_widget(Paragraph::new(method), method_area);
-        frame.render_widget(&self.url, url_area);
+        if props.show_raw_url {
+            frame.render_widget(self.url.raw(), url_area);
+        } else {
+            frame.render_widget(&self.url, url_area);
+        }
 
         // Navigation tabs
         self.tabs.draw(frame, (), tabs_area, true);
@@ -221,7 +253,22 @@ impl Draw for RecipeDisplay {
             ),
             Tab::Authentication => {
                 if let Some(authentication) = &self.authentication {
-                    authentication.draw(frame, (), content_area, true)
+                    let [inherited_area, authentication_area] =
+                        Layout::vertical([
+                            Constraint::Length(
+                                self.authentication_inherited.into(),
+                            ),
+                            Constraint::Min(0),
+                        ])
+                        .areas(content_area);
+                    frame.render_widget(
+                        Span::styled(
+                            "Inherited from defaults",
+                            tui_context.styles.text.hint,
+                        ),
+                        inherited_area,
+                    );
+                    authentication.draw(frame, (), authentication_area, true)
                 }
             }
         }
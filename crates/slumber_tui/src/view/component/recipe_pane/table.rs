@@ -59,25 +59,32 @@ where
     RowSelectKey: PersistedKey<Value = Option<String>>,
     RowToggleKey: 'static + PersistedKey<Value = bool>,
 {
+    /// Create a new table. The last field of each row indicates whether the
+    /// value is inherited from defaults, rather than set on the recipe
     pub fn new(
         select_key: RowSelectKey,
         rows: impl IntoIterator<
-            Item = (String, Template, RecipeOverrideKey, RowToggleKey),
+            Item = (String, Template, RecipeOverrideKey, RowToggleKey, bool),
         >,
     ) -> Self {
         let items = rows
             .into_iter()
             .enumerate()
-            .map(|(i, (key, template, override_key, toggle_key))| RowState {
-                index: i, // This will be the unique ID for the row
-                key,
-                value: RecipeTemplate::new(
-                    override_key,
-                    template.clone(),
-                    None,
-                ),
-                enabled: Persisted::new(toggle_key, true),
-            })
+            .map(
+                |(i, (key, template, override_key, toggle_key, inherited))| {
+                    RowState {
+                        index: i, // This will be the unique ID for the row
+                        key,
+                        value: RecipeTemplate::new(
+                            override_key,
+                            template.clone(),
+                            None,
+                        ),
+                        enabled: Persisted::new(toggle_key, true),
+                        inherited,
+                    }
+                },
+            )
             .collect();
         let select = SelectState::builder(items)
             .on_toggle(RowState::toggle)
@@ -194,6 +201,9 @@ struct RowState<K: PersistedKey<Value = bool>> {
     /// by index (the actual unique key), then adding/removing any field to the
     /// table will mess with persistence.
     enabled: Persisted<K>,
+    /// Did this row come from defaults on a folder or the collection, rather
+    /// than the recipe itself?
+    inherited: bool,
 }
 
 impl<K: PersistedKey<Value = bool>> Generate for &RowState<K> {
@@ -210,6 +220,10 @@ impl<K: PersistedKey<Value = bool>> Generate for &RowState<K> {
         if self.value.is_overridden() {
             preview_text.push_span(Span::styled(" (edited)", styles.text.hint));
         }
+        if self.inherited {
+            preview_text
+                .push_span(Span::styled(" (inherited)", styles.text.hint));
+        }
         ToggleRow::new([self.key.as_str().into(), preview_text], *self.enabled)
             .generate()
     }
@@ -339,6 +353,7 @@ mod tests {
                     recipe_id: recipe_id.clone(),
                     key: "row0".into(),
                 },
+                false,
             ),
             (
                 "row1".into(),
@@ -348,6 +363,7 @@ mod tests {
                     recipe_id: recipe_id.clone(),
                     key: "row1".into(),
                 },
+                false,
             ),
         ];
         let mut component = TestComponent::new(
@@ -399,6 +415,7 @@ mod tests {
                     recipe_id: recipe_id.clone(),
                     key: "row0".into(),
                 },
+                false,
             ),
             (
                 "row1".into(),
@@ -408,6 +425,7 @@ mod tests {
                     recipe_id: recipe_id.clone(),
                     key: "row1".into(),
                 },
+                false,
             ),
         ];
         let mut component = TestComponent::new(
@@ -469,6 +487,7 @@ mod tests {
                     recipe_id: recipe_id.clone(),
                     key: "row0".into(),
                 },
+                false,
             ),
             (
                 "row1".into(),
@@ -478,6 +497,7 @@ mod tests {
                     recipe_id: recipe_id.clone(),
                     key: "row1".into(),
                 },
+                false,
             ),
         ];
         let component = TestComponent::new(
@@ -0,0 +1,205 @@
+//! Find every template that refers to a profile field or chain
+
+use crate::{
+    context::TuiContext,
+    util::ResultReported,
+    view::{
+        common::{list::List, modal::Modal},
+        component::Component,
+        draw::{Draw, DrawMetadata, Generate},
+        event::{Child, EventHandler},
+        state::{select::SelectState, StateCell},
+        ViewContext,
+    },
+};
+use ratatui::{
+    layout::{Constraint, Layout},
+    text::{Line, Span, Text},
+    widgets::Paragraph,
+    Frame,
+};
+use slumber_core::collection::{
+    TemplateKeyInfo, TemplateKeyKind, TemplateReference,
+};
+
+/// Pick a profile field or chain, and list every template that uses it. This
+/// shows what would be affected by changing it.
+#[derive(Debug)]
+pub struct ReferencesModal {
+    select: Component<SelectState<TemplateKeyInfo>>,
+    /// References to the selected key. Cached because finding them requires
+    /// walking the entire collection
+    references: StateCell<String, Vec<TemplateReference>>,
+}
+
+impl ReferencesModal {
+    pub fn new() -> Self {
+        let keys = ViewContext::collection()
+            .template_keys(None)
+            .reported(&ViewContext::messages_tx())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|info| {
+                matches!(
+                    info.kind,
+                    TemplateKeyKind::Field | TemplateKeyKind::Chain
+                )
+            })
+            .collect();
+        Self {
+            select: SelectState::builder(keys).build().into(),
+            references: Default::default(),
+        }
+    }
+}
+
+impl Default for ReferencesModal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Modal for ReferencesModal {
+    fn title(&self) -> Line<'_> {
+        "Find References".into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(80), Constraint::Percentage(60))
+    }
+}
+
+impl EventHandler for ReferencesModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.select.to_child_mut()]
+    }
+}
+
+impl Draw for ReferencesModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let select = self.select.data();
+        if select.is_empty() {
+            frame.render_widget(
+                "No profile fields or chains defined",
+                metadata.area(),
+            );
+            return;
+        }
+
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Length(30), Constraint::Min(0)])
+                .spacing(1)
+                .areas(metadata.area());
+        self.select.draw(frame, List::from(select), list_area, true);
+
+        let Some(selected) = select.selected() else {
+            return;
+        };
+        let references = self.references.get_or_update(&selected.key, || {
+            ViewContext::collection()
+                .find_references(&selected.key)
+                .reported(&ViewContext::messages_tx())
+                .unwrap_or_default()
+        });
+        let text: Text = if references.is_empty() {
+            "Not used by any template".into()
+        } else {
+            references
+                .iter()
+                .map(|reference| Line::from(reference.to_string()))
+                .collect::<Vec<_>>()
+                .into()
+        };
+        frame.render_widget(Paragraph::new(text), detail_area);
+    }
+}
+
+impl Generate for &TemplateKeyInfo {
+    type Output<'this> = Line<'this> where Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        if self.used {
+            self.key.as_str().into()
+        } else {
+            vec![
+                self.key.as_str().into(),
+                Span::styled(" (unused)", TuiContext::get().styles.text.hint),
+            ]
+            .into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{harness, terminal, TestHarness, TestTerminal},
+        view::test_util::TestComponent,
+    };
+    use crossterm::event::KeyCode;
+    use indexmap::indexmap;
+    use rstest::rstest;
+    use slumber_core::{
+        collection::{Chain, Collection, Profile, Recipe},
+        test_util::{by_id, Factory},
+    };
+
+    /// Fields and chains are listed, and references are loaded for whichever
+    /// is selected
+    #[rstest]
+    fn test_references(harness: TestHarness, terminal: TestTerminal) {
+        let collection = Collection {
+            profiles: by_id([Profile {
+                data: indexmap! {"host".into() => "http://localhost".into()},
+                ..Profile::factory(())
+            }]),
+            chains: by_id([Chain {
+                id: "token".into(),
+                ..Chain::factory(())
+            }]),
+            recipes: by_id([Recipe {
+                id: "login".into(),
+                url: "{{host}}/login".into(),
+                ..Recipe::factory(())
+            }])
+            .into(),
+            ..Collection::factory(())
+        };
+        ViewContext::init(
+            collection.into(),
+            harness.database.clone(),
+            harness.messages_tx().clone(),
+        );
+
+        let mut component =
+            TestComponent::new(&terminal, ReferencesModal::new(), ());
+        let keys: Vec<(String, bool)> = component
+            .data()
+            .select
+            .data()
+            .items()
+            .map(|info| (info.key.clone(), info.used))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![("host".into(), true), ("chains.token".into(), false)]
+        );
+        let references = |modal: &ReferencesModal| -> Vec<String> {
+            modal
+                .references
+                .get()
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+        assert_eq!(references(component.data()), vec!["Recipe `login`: url"]);
+
+        component.send_key(KeyCode::Down).assert_empty();
+        assert_eq!(references(component.data()), Vec::<String>::new());
+    }
+}
@@ -7,6 +7,7 @@ use crate::{
             header_table::HeaderTable,
             text_window::{TextWindow, TextWindowProps},
         },
+        component::environment::EnvironmentModal,
         draw::{Draw, DrawMetadata, Generate, ToStringGenerate},
         event::{Child, Event, EventHandler, Update},
         state::StateCell,
@@ -15,7 +16,12 @@ use crate::{
     },
 };
 use derive_more::Display;
-use ratatui::{layout::Layout, prelude::Constraint, text::Text, Frame};
+use ratatui::{
+    layout::Layout,
+    prelude::Constraint,
+    text::{Line, Span, Text},
+    Frame,
+};
 use slumber_config::Action;
 use slumber_core::{
     http::{content_type::ContentType, RequestId, RequestRecord},
@@ -58,6 +64,8 @@ enum MenuAction {
     CopyUrl,
     #[display("Copy Body")]
     CopyBody,
+    #[display("View Environment")]
+    ViewEnvironment,
 }
 
 impl ToStringGenerate for MenuAction {}
@@ -98,6 +106,13 @@ impl EventHandler for RequestView {
                         ViewContext::send_message(Message::CopyText(body));
                     }
                 }
+                MenuAction::ViewEnvironment => {
+                    if let Some(state) = self.state.get() {
+                        ViewContext::open_modal(EnvironmentModal::new(
+                            &state.request,
+                        ));
+                    }
+                }
             }
         } else {
             return Update::Propagate(event);
@@ -122,13 +137,24 @@ impl Draw<RequestViewProps> for RequestView {
             body: init_body(&props.request),
         });
 
-        let [url_area, headers_area, body_area] = Layout::vertical([
-            Constraint::Length(2),
-            Constraint::Length(props.request.headers.len() as u16 + 2),
-            Constraint::Min(0),
-        ])
-        .areas(metadata.area());
+        let [note_area, url_area, headers_area, body_area] =
+            Layout::vertical([
+                Constraint::Length(props.request.note.is_some() as u16),
+                Constraint::Length(2),
+                Constraint::Length(props.request.headers.len() as u16 + 2),
+                Constraint::Min(0),
+            ])
+            .areas(metadata.area());
 
+        if let Some(note) = &props.request.note {
+            frame.render_widget(
+                Line::from(vec![
+                    Span::styled("Note: ", TuiContext::get().styles.text.title),
+                    note.as_str().into(),
+                ]),
+                note_area,
+            );
+        }
         // This can get cut off which is jank but there isn't a good fix. User
         // can copy the URL to see the full thing
         frame.render_widget(props.request.url.to_string(), url_area);
@@ -144,6 +170,7 @@ impl Draw<RequestViewProps> for RequestView {
                 frame,
                 TextWindowProps {
                     text: body,
+                    marks: &[],
                     margins: Default::default(),
                     footer: None,
                 },
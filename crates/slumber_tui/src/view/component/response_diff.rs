@@ -0,0 +1,382 @@
+//! Compare two responses from history, and generic diff rendering
+
+use crate::{
+    context::TuiContext,
+    message::Message,
+    util::ResultReported,
+    view::{
+        common::{
+            modal::Modal,
+            text_window::{TextWindow, TextWindowProps},
+        },
+        component::{baseline::load_exchange, Component},
+        draw::{Draw, DrawMetadata},
+        event::{Child, Event, EventHandler, Update},
+        state::StateCell,
+        ViewContext,
+    },
+};
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::Style,
+    text::{Line, Span, Text},
+    Frame,
+};
+use slumber_config::Action;
+use slumber_core::{
+    http::{
+        diff::{DiffLine, ResponseDiff},
+        RequestId,
+    },
+    util::format_time,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Load two requests from history and show the differences between their
+/// responses in a modal. The older of the two is treated as the "before"
+/// version.
+pub fn compare_responses(first: RequestId, second: RequestId) {
+    let Some((old, new)) = ViewContext::with_database(|database| {
+        Ok::<_, anyhow::Error>((
+            load_exchange(database, first)?,
+            load_exchange(database, second)?,
+        ))
+    })
+    .reported(&ViewContext::messages_tx()) else {
+        return;
+    };
+    let (old, new) = if old.start_time <= new.start_time {
+        (old, new)
+    } else {
+        (new, old)
+    };
+
+    let diff = ResponseDiff::new(&old.response, &new.response);
+    if diff.is_empty() {
+        ViewContext::send_message(Message::Notify(
+            "Responses are identical".into(),
+        ));
+    } else {
+        ViewContext::open_modal(ResponseDiffModal::new(
+            format!(
+                "{} → {}",
+                format_time(&old.start_time),
+                format_time(&new.start_time)
+            ),
+            diff,
+        ));
+    }
+}
+
+/// Show the differences between two responses, either as a unified diff or
+/// side-by-side
+#[derive(Debug)]
+pub struct ResponseDiffModal {
+    title: String,
+    view: Component<DiffView>,
+}
+
+impl ResponseDiffModal {
+    fn new(title: String, diff: ResponseDiff) -> Self {
+        Self {
+            title,
+            view: DiffView::response(diff, DiffMode::default()).into(),
+        }
+    }
+}
+
+impl Modal for ResponseDiffModal {
+    fn title(&self) -> Line<'_> {
+        self.title.as_str().into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(80), Constraint::Percentage(80))
+    }
+}
+
+impl EventHandler for ResponseDiffModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.view.to_child_mut()]
+    }
+}
+
+impl Draw for ResponseDiffModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        self.view.draw(frame, (), metadata.area(), true);
+    }
+}
+
+/// A diff made up of titled sections, rendered either as a unified diff or
+/// side-by-side. The user can toggle between the two.
+#[derive(Debug)]
+pub struct DiffView {
+    sections: Vec<(&'static str, Vec<DiffLine>)>,
+    mode: DiffMode,
+    /// Rendered diff. Side-by-side rendering depends on the available width,
+    /// so that's part of the key
+    text: StateCell<(DiffMode, u16), Text<'static>>,
+    text_window: Component<TextWindow>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum DiffMode {
+    #[default]
+    Unified,
+    SideBySide,
+}
+
+impl DiffView {
+    pub fn new(
+        sections: Vec<(&'static str, Vec<DiffLine>)>,
+        mode: DiffMode,
+    ) -> Self {
+        Self {
+            sections,
+            mode,
+            text: StateCell::default(),
+            text_window: Component::default(),
+        }
+    }
+
+    /// Show the differences between two responses
+    pub fn response(diff: ResponseDiff, mode: DiffMode) -> Self {
+        let (old_status, new_status) = diff.status;
+        // Status codes are shown as diff lines so they can be rendered like
+        // everything else
+        let status = if old_status == new_status {
+            vec![DiffLine::Equal(old_status.to_string())]
+        } else {
+            vec![
+                DiffLine::Removed(old_status.to_string()),
+                DiffLine::Added(new_status.to_string()),
+            ]
+        };
+        Self::new(
+            vec![
+                ("Status", status),
+                ("Headers", diff.headers),
+                ("Body", diff.body),
+            ],
+            mode,
+        )
+    }
+}
+
+impl EventHandler for DiffView {
+    fn update(&mut self, event: Event) -> Update {
+        if let Some(Action::Toggle) = event.action() {
+            self.mode = match self.mode {
+                DiffMode::Unified => DiffMode::SideBySide,
+                DiffMode::SideBySide => DiffMode::Unified,
+            };
+            Update::Consumed
+        } else {
+            Update::Propagate(event)
+        }
+    }
+
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.text_window.to_child_mut()]
+    }
+}
+
+impl Draw for DiffView {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        let tui_context = TuiContext::get();
+        let [hint_area, text_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)])
+                .areas(metadata.area());
+
+        let binding = tui_context.input_engine.binding_display(Action::Toggle);
+        let hint = match self.mode {
+            DiffMode::Unified => {
+                format!("Unified ({binding} for side-by-side)")
+            }
+            DiffMode::SideBySide => {
+                format!("Side-by-side ({binding} for unified)")
+            }
+        };
+        frame.render_widget(
+            Span::styled(hint, tui_context.styles.text.hint),
+            hint_area,
+        );
+
+        let text =
+            self.text.get_or_update(&(self.mode, text_area.width), || {
+                let sections = self
+                    .sections
+                    .iter()
+                    .map(|(title, lines)| (*title, lines.iter().collect()))
+                    .collect::<Vec<_>>();
+                match self.mode {
+                    DiffMode::Unified => unified(&sections),
+                    DiffMode::SideBySide => {
+                        side_by_side(&sections, text_area.width)
+                    }
+                }
+            });
+        self.text_window.draw(
+            frame,
+            TextWindowProps {
+                text: &text,
+                marks: &[],
+                margins: Default::default(),
+                footer: None,
+            },
+            text_area,
+            true,
+        );
+    }
+}
+
+/// Render sections as a unified diff, with `-`/`+` markers
+fn unified(sections: &[(&'static str, Vec<&DiffLine>)]) -> Text<'static> {
+    let styles = &TuiContext::get().styles;
+    let mut lines: Vec<Line<'static>> = Vec::new();
+    for (i, (title, section)) in sections.iter().enumerate() {
+        if i > 0 {
+            lines.push(Line::default());
+        }
+        lines.push(Line::styled(*title, styles.text.title));
+        lines.extend(section.iter().map(|line| match line {
+            DiffLine::Equal(line) => Line::from(format!("  {line}")),
+            DiffLine::Removed(line) => {
+                Line::styled(format!("- {line}"), styles.diff.removed)
+            }
+            DiffLine::Added(line) => {
+                Line::styled(format!("+ {line}"), styles.diff.added)
+            }
+        }));
+    }
+    lines.into()
+}
+
+/// Render sections as two columns, old on the left and new on the right.
+/// Removed and added lines are paired up so changes line up across columns.
+fn side_by_side(
+    sections: &[(&'static str, Vec<&DiffLine>)],
+    width: u16,
+) -> Text<'static> {
+    const SEPARATOR: &str = " │ ";
+
+    let styles = &TuiContext::get().styles;
+    // Leave room for the text window's gutter and scrollbar. The gutter
+    // width depends on the line count, so this is an estimate
+    let column_width =
+        (usize::from(width).saturating_sub(SEPARATOR.width() + 6) / 2).max(1);
+    let row = |left: Option<(&str, Style)>, right: Option<(&str, Style)>| {
+        let cell = |cell: Option<(&str, Style)>| match cell {
+            Some((text, style)) => Span::styled(fit(text, column_width), style),
+            None => Span::raw(" ".repeat(column_width)),
+        };
+        Line::from(vec![cell(left), SEPARATOR.into(), cell(right)])
+    };
+
+    let mut lines: Vec<Line<'static>> = Vec::new();
+    for (i, (title, section)) in sections.iter().enumerate() {
+        if i > 0 {
+            lines.push(Line::default());
+        }
+        lines.push(Line::styled(*title, styles.text.title));
+
+        let mut removed: Vec<&str> = Vec::new();
+        let mut added: Vec<&str> = Vec::new();
+        let flush = |removed: &mut Vec<&str>,
+                     added: &mut Vec<&str>,
+                     lines: &mut Vec<Line<'static>>| {
+            for i in 0..removed.len().max(added.len()) {
+                lines.push(row(
+                    removed.get(i).map(|line| (*line, styles.diff.removed)),
+                    added.get(i).map(|line| (*line, styles.diff.added)),
+                ));
+            }
+            removed.clear();
+            added.clear();
+        };
+        for line in section {
+            match line {
+                DiffLine::Equal(line) => {
+                    flush(&mut removed, &mut added, &mut lines);
+                    lines.push(row(
+                        Some((line, Style::default())),
+                        Some((line, Style::default())),
+                    ));
+                }
+                DiffLine::Removed(line) => removed.push(line),
+                DiffLine::Added(line) => added.push(line),
+            }
+        }
+        flush(&mut removed, &mut added, &mut lines);
+    }
+    lines.into()
+}
+
+/// Truncate or pad text to exactly the given display width
+fn fit(text: &str, width: usize) -> String {
+    let mut fitted = String::with_capacity(width);
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width > width {
+            break;
+        }
+        fitted.push(c);
+        used += char_width;
+    }
+    fitted.extend(std::iter::repeat(' ').take(width - used));
+    fitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{harness, TestHarness};
+    use rstest::rstest;
+    use slumber_core::http::diff::diff_lines;
+
+    /// Get the text content of each rendered line
+    fn contents(text: Text) -> Vec<String> {
+        text.lines.iter().map(ToString::to_string).collect()
+    }
+
+    #[rstest]
+    fn test_unified(_harness: TestHarness) {
+        let lines = diff_lines("a\nb\nc", "a\nx\ny\nc");
+        let text = unified(&[("Body", lines.iter().collect())]);
+        assert_eq!(
+            contents(text),
+            vec!["Body", "  a", "- b", "+ x", "+ y", "  c"]
+        );
+    }
+
+    /// Removed and added lines are paired up across the two columns
+    #[rstest]
+    fn test_side_by_side(_harness: TestHarness) {
+        let lines = diff_lines("a\nb\nc", "a\nx\ny\nc");
+        // 2 columns of 4, plus separator and gutter/scrollbar allowance
+        let text = side_by_side(&[("Body", lines.iter().collect())], 17);
+        assert_eq!(
+            contents(text),
+            vec![
+                "Body",
+                "a    │ a   ",
+                "b    │ x   ",
+                "     │ y   ",
+                "c    │ c   ",
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::pad("ab", 4, "ab  ")]
+    #[case::truncate("abcdef", 4, "abcd")]
+    #[case::wide_char("日本語", 5, "日本 ")]
+    fn test_fit(
+        #[case] text: &str,
+        #[case] width: usize,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(fit(text, width), expected);
+    }
+}
@@ -2,13 +2,22 @@
 
 use crate::{
     message::Message,
+    util::ResultReported,
     view::{
         common::{actions::ActionsModal, header_table::HeaderTable},
-        component::queryable_body::{QueryableBody, QueryableBodyProps},
+        component::{
+            assertion::check_assertions,
+            baseline::{compare_baseline, load_exchange, pin_baseline},
+            certificate::view_certificate,
+            contract::check_contract,
+            decode::decode_value,
+            queryable_body::{QueryableBody, QueryableBodyProps},
+            status_code::explain_status,
+        },
         draw::{Draw, DrawMetadata, Generate, ToStringGenerate},
         event::{Child, Event, EventHandler, Update},
         state::StateCell,
-        util::persistence::PersistedLazy,
+        util::persistence::{Persisted, PersistedLazy},
         Component, ViewContext,
     },
 };
@@ -19,9 +28,9 @@ use serde::Serialize;
 use slumber_config::Action;
 use slumber_core::{
     collection::RecipeId,
-    http::{RequestId, ResponseRecord},
+    http::{query::Query, RequestId, ResponseRecord},
 };
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 use strum::{EnumCount, EnumIter};
 
 /// Display response body
@@ -30,6 +39,9 @@ pub struct ResponseBodyView {
     /// Persist the response body to track view state. Update whenever the
     /// loaded request changes
     state: StateCell<RequestId, State>,
+    /// Ignore the recipe's display query and show the full body. This applies
+    /// to all responses, so it sticks when switching between requests
+    show_raw: bool,
 }
 
 #[derive(Clone)]
@@ -37,6 +49,9 @@ pub struct ResponseBodyViewProps<'a> {
     pub request_id: RequestId,
     pub recipe_id: &'a RecipeId,
     pub response: Arc<ResponseRecord>,
+    /// Is this the response to a GraphQL request? If so, only the `data`
+    /// field is shown by default
+    pub graphql: bool,
 }
 
 /// Items in the actions popup menu for the Body
@@ -51,6 +66,30 @@ enum BodyMenuAction {
     CopyBody,
     #[display("Save Body as File")]
     SaveBody,
+    #[display("Decode Value")]
+    DecodeValue,
+    #[display("Toggle Raw Body")]
+    ToggleRawBody,
+    #[display("Pin as Baseline")]
+    PinBaseline,
+    #[display("Compare to Baseline")]
+    CompareBaseline,
+    #[display("Check Contract")]
+    CheckContract,
+    #[display("Check Assertions")]
+    CheckAssertions,
+    #[display("Explain Status Code")]
+    ExplainStatus,
+    #[display("View TLS Certificate")]
+    ViewCertificate,
+    #[display("Load Full Body")]
+    LoadFullBody,
+    #[display("Next Page")]
+    NextPage,
+    #[display("Previous Page")]
+    PreviousPage,
+    #[display("Download Full Body")]
+    DownloadFullBody,
 }
 
 impl ToStringGenerate for BodyMenuAction {}
@@ -58,12 +97,18 @@ impl ToStringGenerate for BodyMenuAction {}
 /// Internal state
 #[derive(Debug)]
 struct State {
+    /// Recipe that the response came from, to look up its display query
+    recipe_id: RecipeId,
+    /// Is this the response to a GraphQL request?
+    graphql: bool,
     /// Use Arc so we're not cloning large responses
     response: Arc<ResponseRecord>,
     /// The presentable version of the response body, which may or may not
     /// match the response body. We apply transformations such as filter,
     /// prettification, or in the case of binary responses, a hex dump.
     body: Component<PersistedLazy<ResponseQueryPersistedKey, QueryableBody>>,
+    /// Bookmarked lines of the body
+    bookmarks: Persisted<ResponseBookmarksKey>,
 }
 
 /// Persisted key for response body JSONPath query text box
@@ -71,12 +116,130 @@ struct State {
 #[persisted(String)]
 struct ResponseQueryPersistedKey(RecipeId);
 
+/// Persisted key for the bookmarked lines of a single response body. Each
+/// query shows different text, so lines are grouped by the query that was
+/// applied when they were bookmarked (empty for no query).
+#[derive(Debug, Serialize, PersistedKey)]
+#[persisted(BTreeMap<String, Vec<usize>>)]
+struct ResponseBookmarksKey(RequestId);
+
+impl State {
+    /// Get the query that the recipe applies to its responses for display.
+    /// This is looked up each time so it reflects collection reloads. GraphQL
+    /// responses default to showing just the data, because errors are shown
+    /// in their own tab.
+    fn display_query(&self) -> Option<Query> {
+        ViewContext::collection()
+            .recipes
+            .get_recipe(&self.recipe_id)
+            .and_then(|recipe| recipe.display_query.clone())
+            .or_else(|| {
+                self.graphql
+                    .then(|| "$.data".parse().expect("Query is valid"))
+            })
+    }
+
+    /// Bookmark the line at the top of the body window, or remove the bookmark
+    /// if it's already there
+    fn toggle_bookmark(&mut self) {
+        let body = self.body.data();
+        let line = body.first_line();
+        let query = body.query_text();
+        let mut bookmarks = self.bookmarks.get_mut();
+        let lines = bookmarks.entry(query.clone()).or_default();
+        match lines.binary_search(&line) {
+            Ok(index) => {
+                lines.remove(index);
+                if lines.is_empty() {
+                    bookmarks.remove(&query);
+                }
+            }
+            Err(index) => lines.insert(index, line),
+        }
+    }
+
+    /// Scroll to the nearest bookmark below the top of the body window, or
+    /// above it if going backward. Wraps around at either end.
+    fn jump_to_bookmark(&mut self, forward: bool) {
+        let body = self.body.data();
+        let current = body.first_line();
+        let Some(lines) = (*self.bookmarks).get(&body.query_text()) else {
+            ViewContext::send_message(Message::Notify(
+                "No bookmarks in this response".into(),
+            ));
+            return;
+        };
+        let target = if forward {
+            lines.iter().find(|&&line| line > current).or(lines.first())
+        } else {
+            lines
+                .iter()
+                .rev()
+                .find(|&&line| line < current)
+                .or(lines.last())
+        };
+        if let Some(&line) = target {
+            self.body.data_mut().get_mut().scroll_to(line);
+        }
+    }
+
+    /// Get bookmarked lines for the current query
+    fn marks(&self) -> &[usize] {
+        (*self.bookmarks)
+            .get(&self.body.data().query_text())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
 impl EventHandler for ResponseBodyView {
     fn update(&mut self, event: Event) -> Update {
         if let Some(Action::OpenActions) = event.action() {
-            ViewContext::open_modal::<ActionsModal<BodyMenuAction>>(
-                Default::default(),
-            );
+            let state = self.state.get();
+            let has_display_query = state
+                .as_ref()
+                .is_some_and(|state| state.display_query().is_some());
+            let is_truncated = state
+                .as_ref()
+                .is_some_and(|state| state.body.data().is_truncated());
+            // Streamed bodies can't be loaded in full, only paged through
+            let is_streamed = state
+                .as_ref()
+                .is_some_and(|state| state.response.body.blob().is_some());
+            // Only a body cut off by the capture limit has more to download
+            let is_captured = state
+                .as_ref()
+                .is_some_and(|state| state.response.body.full_size().is_some());
+            let mut disabled = Vec::new();
+            if !has_display_query {
+                disabled.push(BodyMenuAction::ToggleRawBody);
+            }
+            if !is_truncated || is_streamed {
+                disabled.push(BodyMenuAction::LoadFullBody);
+            }
+            if !is_streamed {
+                disabled.extend([
+                    BodyMenuAction::NextPage,
+                    BodyMenuAction::PreviousPage,
+                ]);
+            }
+            if !is_captured {
+                disabled.push(BodyMenuAction::DownloadFullBody);
+            }
+            ViewContext::open_modal(ActionsModal::new(&disabled));
+        } else if let Some(
+            action @ (Action::ToggleBookmark
+            | Action::NextBookmark
+            | Action::PreviousBookmark),
+        ) = event.action()
+        {
+            if let Some(state) = self.state.get_mut() {
+                match action {
+                    Action::ToggleBookmark => state.toggle_bookmark(),
+                    Action::NextBookmark => state.jump_to_bookmark(true),
+                    _ => state.jump_to_bookmark(false),
+                }
+            }
         } else if let Some(action) = event.local::<BodyMenuAction>() {
             match action {
                 BodyMenuAction::EditCollection => {
@@ -102,22 +265,94 @@ impl EventHandler for ResponseBodyView {
                         // If we need to optimize this, we would have to shove
                         // all querying to the main data storage, so the main
                         // loop can access it directly to be written.
-                        let data = if state.response.body.parsed().is_some() {
+                        // A truncated body isn't the full text, so fall back
+                        // to the raw bytes. A streamed body has to be loaded
+                        // from the blob store
+                        let body = state.body.data();
+                        let data = if state.response.body.parsed().is_some()
+                            && !body.is_truncated()
+                        {
+                            Some(body.text().unwrap_or_default().into_bytes())
+                        } else {
                             state
+                                .response
                                 .body
-                                .data()
-                                .text()
-                                .unwrap_or_default()
-                                .into_bytes()
-                        } else {
-                            state.response.body.bytes().to_vec()
+                                .load_bytes()
+                                .reported(&ViewContext::messages_tx())
+                                .map(|bytes| bytes.to_vec())
                         };
 
                         // This will trigger a modal to ask the user for a path
-                        ViewContext::send_message(Message::SaveFile {
-                            default_path: state.response.file_name(),
-                            data,
-                        });
+                        if let Some(data) = data {
+                            ViewContext::send_message(Message::SaveFile {
+                                default_path: state.response.file_name(),
+                                data,
+                            });
+                        }
+                    }
+                }
+                BodyMenuAction::DecodeValue => {
+                    if let Some(state) = self.state.get() {
+                        let display_query = if self.show_raw {
+                            None
+                        } else {
+                            state.display_query()
+                        };
+                        decode_value(
+                            &state.response.body,
+                            display_query.as_ref(),
+                            state.body.data().query(),
+                        );
+                    }
+                }
+                BodyMenuAction::ToggleRawBody => self.show_raw = !self.show_raw,
+                BodyMenuAction::PinBaseline => {
+                    if let Some(request_id) = self.state.get_key() {
+                        pin_baseline(*request_id);
+                    }
+                }
+                BodyMenuAction::CompareBaseline => {
+                    if let Some(request_id) = self.state.get_key() {
+                        compare_baseline(*request_id);
+                    }
+                }
+                BodyMenuAction::CheckContract => {
+                    if let Some(request_id) = self.state.get_key() {
+                        check_contract(*request_id);
+                    }
+                }
+                BodyMenuAction::CheckAssertions => {
+                    if let Some(request_id) = self.state.get_key() {
+                        check_assertions(*request_id);
+                    }
+                }
+                BodyMenuAction::ExplainStatus => {
+                    if let Some(request_id) = self.state.get_key() {
+                        explain_status(*request_id);
+                    }
+                }
+                BodyMenuAction::ViewCertificate => {
+                    if let Some(request_id) = self.state.get_key() {
+                        view_certificate(*request_id);
+                    }
+                }
+                BodyMenuAction::LoadFullBody => {
+                    if let Some(state) = self.state.get_mut() {
+                        state.body.data_mut().get_mut().load_full_body();
+                    }
+                }
+                action @ (BodyMenuAction::NextPage
+                | BodyMenuAction::PreviousPage) => {
+                    if let Some(state) = self.state.get_mut() {
+                        state.body.data_mut().get_mut().turn_page(
+                            &state.response.body,
+                            *action == BodyMenuAction::NextPage,
+                        );
+                    }
+                }
+                BodyMenuAction::DownloadFullBody => {
+                    if let Some(request_id) = self.state.get_key() {
+                        download_full_body(*request_id);
                     }
                 }
             }
@@ -136,6 +371,23 @@ impl EventHandler for ResponseBodyView {
     }
 }
 
+/// Send a request from history again, and save the full response body to a
+/// file. The request is loaded from the database because the view only holds
+/// the response
+fn download_full_body(request_id: RequestId) {
+    let Some(exchange) = ViewContext::with_database(|database| {
+        load_exchange(database, request_id)
+    })
+    .reported(&ViewContext::messages_tx()) else {
+        return;
+    };
+    // This will trigger a modal to ask the user for a path
+    ViewContext::send_message(Message::DownloadFile {
+        default_path: exchange.response.file_name(),
+        request: exchange.request,
+    });
+}
+
 impl<'a> Draw<ResponseBodyViewProps<'a>> for ResponseBodyView {
     fn draw(
         &self,
@@ -145,19 +397,31 @@ impl<'a> Draw<ResponseBodyViewProps<'a>> for ResponseBodyView {
     ) {
         let response = &props.response;
         let state = self.state.get_or_update(&props.request_id, || State {
+            recipe_id: props.recipe_id.clone(),
+            graphql: props.graphql,
             response: Arc::clone(&props.response),
             body: PersistedLazy::new(
                 ResponseQueryPersistedKey(props.recipe_id.clone()),
                 QueryableBody::new(),
             )
             .into(),
+            bookmarks: Persisted::new_default(ResponseBookmarksKey(
+                props.request_id,
+            )),
         });
 
+        let display_query = if self.show_raw {
+            None
+        } else {
+            state.display_query()
+        };
         state.body.draw(
             frame,
             QueryableBodyProps {
                 content_type: response.content_type(),
                 body: &response.body,
+                display_query: display_query.as_ref(),
+                marks: state.marks(),
             },
             metadata.area(),
             true,
@@ -194,15 +458,22 @@ mod tests {
     use super::*;
     use crate::{
         test_util::{harness, terminal, TestHarness, TestTerminal},
-        view::test_util::TestComponent,
+        view::{
+            test_util::TestComponent, util::persistence::DatabasePersistedStore,
+        },
     };
+    use crossterm::event::KeyCode;
     use indexmap::indexmap;
+    use itertools::Itertools;
+    use persisted::PersistedStore;
     use rstest::rstest;
     use slumber_core::{
         assert_matches,
-        http::Exchange,
-        test_util::{header_map, Factory},
+        collection::{Assertion, Collection, Recipe},
+        http::{Exchange, ResponseBody},
+        test_util::{by_id, header_map, temp_dir, Factory, TempDir},
     };
+    use std::{fs, time::Duration};
 
     /// Test "Copy Body" menu action
     #[rstest]
@@ -244,6 +515,7 @@ mod tests {
                 request_id: exchange.id,
                 recipe_id: &exchange.request.recipe_id,
                 response: exchange.response,
+                graphql: false,
             },
         );
 
@@ -258,6 +530,301 @@ mod tests {
         assert_eq!(body, expected_body);
     }
 
+    /// Bookmark a line, then jump back to it
+    #[rstest]
+    fn test_bookmarks(
+        _harness: TestHarness,
+        #[with(20, 5)] terminal: TestTerminal,
+    ) {
+        let response = ResponseRecord {
+            body: (1..=10).join("\n").into_bytes().into(),
+            ..ResponseRecord::factory(())
+        };
+        let exchange = Exchange {
+            response: response.into(),
+            ..Exchange::factory(())
+        };
+        let mut component = TestComponent::new(
+            &terminal,
+            ResponseBodyView::default(),
+            ResponseBodyViewProps {
+                request_id: exchange.id,
+                recipe_id: &exchange.request.recipe_id,
+                response: exchange.response,
+                graphql: false,
+            },
+        );
+        let first_line = |component: &ResponseBodyView| {
+            component.state.get().unwrap().body.data().first_line()
+        };
+
+        component.send_key(KeyCode::Down).assert_empty();
+        component.send_key(KeyCode::Down).assert_empty();
+        component.send_key(KeyCode::Char('b')).assert_empty();
+        assert_eq!(
+            DatabasePersistedStore::load_persisted(&ResponseBookmarksKey(
+                exchange.id
+            )),
+            Some(BTreeMap::from([(String::new(), vec![2])]))
+        );
+
+        component.send_key(KeyCode::Home).assert_empty();
+        assert_eq!(first_line(component.data()), 0);
+        component.send_key(KeyCode::Char(']')).assert_empty();
+        assert_eq!(first_line(component.data()), 2);
+
+        // Toggling again removes it
+        component.send_key(KeyCode::Char('b')).assert_empty();
+        assert_eq!(
+            DatabasePersistedStore::load_persisted(&ResponseBookmarksKey(
+                exchange.id
+            )),
+            Some(BTreeMap::new())
+        );
+    }
+
+    /// Test "Pin as Baseline" and "Compare to Baseline" menu actions
+    #[rstest]
+    fn test_baseline(mut harness: TestHarness, terminal: TestTerminal) {
+        let recipe_id: RecipeId = "recipe1".into();
+        let baseline = Exchange::factory(recipe_id.clone());
+        let exchange = Exchange::factory(recipe_id.clone());
+        harness.database.insert_exchange(&baseline).unwrap();
+        harness.database.insert_exchange(&exchange).unwrap();
+        let create_component = |exchange: &Exchange| {
+            TestComponent::new(
+                &terminal,
+                ResponseBodyView::default(),
+                ResponseBodyViewProps {
+                    request_id: exchange.id,
+                    recipe_id: &recipe_id,
+                    response: Arc::clone(&exchange.response),
+                    graphql: false,
+                },
+            )
+        };
+
+        create_component(&baseline)
+            .update_draw(Event::new_local(BodyMenuAction::PinBaseline))
+            .assert_empty();
+        let message = assert_matches!(
+            harness.pop_message_now(),
+            Message::Notify(message) => message,
+        );
+        assert_eq!(message, "Pinned response as baseline");
+
+        create_component(&exchange)
+            .update_draw(Event::new_local(BodyMenuAction::CompareBaseline))
+            .assert_empty();
+        let message = assert_matches!(
+            harness.pop_message_now(),
+            Message::Notify(message) => message,
+        );
+        assert_eq!(message, "Response matches baseline");
+    }
+
+    /// Test "Download Full Body" menu action
+    #[rstest]
+    fn test_download_full_body(
+        mut harness: TestHarness,
+        terminal: TestTerminal,
+    ) {
+        let exchange = Exchange {
+            response: ResponseRecord {
+                body: ResponseBody::new("trunc".into()).truncated(1000),
+                ..ResponseRecord::factory(())
+            }
+            .into(),
+            ..Exchange::factory(())
+        };
+        harness.database.insert_exchange(&exchange).unwrap();
+        let mut component = TestComponent::new(
+            &terminal,
+            ResponseBodyView::default(),
+            ResponseBodyViewProps {
+                request_id: exchange.id,
+                recipe_id: &exchange.request.recipe_id,
+                response: Arc::clone(&exchange.response),
+                graphql: false,
+            },
+        );
+
+        component
+            .update_draw(Event::new_local(BodyMenuAction::DownloadFullBody))
+            .assert_empty();
+        let request = assert_matches!(
+            harness.pop_message_now(),
+            Message::DownloadFile { request, .. } => request,
+        );
+        assert_eq!(request.id, exchange.id);
+    }
+
+    /// Test "Check Contract" menu action
+    #[rstest]
+    fn test_check_contract(
+        mut harness: TestHarness,
+        terminal: TestTerminal,
+        temp_dir: TempDir,
+    ) {
+        let exchange = Exchange::factory(());
+        harness.database.insert_exchange(&exchange).unwrap();
+        let check = |harness: &mut TestHarness| {
+            TestComponent::new(
+                &terminal,
+                ResponseBodyView::default(),
+                ResponseBodyViewProps {
+                    request_id: exchange.id,
+                    recipe_id: &exchange.request.recipe_id,
+                    response: Arc::clone(&exchange.response),
+                    graphql: false,
+                },
+            )
+            .update_draw(Event::new_local(BodyMenuAction::CheckContract))
+            .assert_empty();
+            assert_matches!(
+                harness.pop_message_now(),
+                Message::Notify(message) => message,
+            )
+        };
+
+        // No spec linked
+        assert_eq!(
+            check(&mut harness),
+            "No OpenAPI spec linked; set the `openapi` field in your \
+            collection"
+        );
+
+        let path = temp_dir.join("openapi.yml");
+        fs::write(
+            &path,
+            r#"
+openapi: 3.0.0
+info: {title: Test, version: "1"}
+paths:
+  /url:
+    get:
+      responses:
+        "200": {description: OK}
+"#,
+        )
+        .unwrap();
+        let collection = Collection {
+            openapi: Some(path),
+            ..Collection::factory(())
+        };
+        ViewContext::init(
+            collection.into(),
+            harness.database.clone(),
+            harness.messages_tx().clone(),
+        );
+        assert_eq!(check(&mut harness), "Response matches OpenAPI spec");
+    }
+
+    /// Test "Check Assertions" menu action
+    #[rstest]
+    fn test_check_assertions(mut harness: TestHarness, terminal: TestTerminal) {
+        let exchange = Exchange::factory(());
+        harness.database.insert_exchange(&exchange).unwrap();
+        let check = |harness: &mut TestHarness| {
+            TestComponent::new(
+                &terminal,
+                ResponseBodyView::default(),
+                ResponseBodyViewProps {
+                    request_id: exchange.id,
+                    recipe_id: &exchange.request.recipe_id,
+                    response: Arc::clone(&exchange.response),
+                    graphql: false,
+                },
+            )
+            .update_draw(Event::new_local(BodyMenuAction::CheckAssertions))
+            .assert_empty();
+            assert_matches!(
+                harness.pop_message_now(),
+                Message::Notify(message) => message,
+            )
+        };
+
+        // Recipe isn't in the collection
+        assert_eq!(
+            check(&mut harness),
+            "Recipe has no assertions; set the `assertions` field in your \
+            collection"
+        );
+
+        let recipe = Recipe {
+            id: exchange.request.recipe_id.clone(),
+            assertions: vec![
+                Assertion::Status(exchange.response.status.as_u16()),
+                Assertion::Latency(Duration::from_secs(60)),
+            ],
+            ..Recipe::factory(())
+        };
+        let collection = Collection {
+            recipes: by_id([recipe]).into(),
+            ..Collection::factory(())
+        };
+        ViewContext::init(
+            collection.into(),
+            harness.database.clone(),
+            harness.messages_tx().clone(),
+        );
+        assert_eq!(check(&mut harness), "2 Assertions Passed");
+    }
+
+    /// Recipe's display query is applied to the body, and "Toggle Raw Body"
+    /// shows the full body
+    #[rstest]
+    fn test_display_query(harness: TestHarness, terminal: TestTerminal) {
+        let response = ResponseRecord {
+            headers: header_map(
+                indexmap! {"content-type" => "application/json"},
+            ),
+            body: br#"{"data":{"a":1}}"#.to_vec().into(),
+            ..ResponseRecord::factory(())
+        };
+        response.parse_body();
+        let exchange = Exchange {
+            response: response.into(),
+            ..Exchange::factory(())
+        };
+        let recipe = Recipe {
+            id: exchange.request.recipe_id.clone(),
+            display_query: Some("$.data".parse().unwrap()),
+            ..Recipe::factory(())
+        };
+        let collection = Collection {
+            recipes: by_id([recipe]).into(),
+            ..Collection::factory(())
+        };
+        ViewContext::init(
+            collection.into(),
+            harness.database.clone(),
+            harness.messages_tx().clone(),
+        );
+        let mut component = TestComponent::new(
+            &terminal,
+            ResponseBodyView::default(),
+            ResponseBodyViewProps {
+                request_id: exchange.id,
+                recipe_id: &exchange.request.recipe_id,
+                response: exchange.response,
+                graphql: false,
+            },
+        );
+        let text = |component: &ResponseBodyView| {
+            component.state.get().unwrap().body.data().text()
+        };
+
+        assert_eq!(text(component.data()).as_deref(), Some("{\n  \"a\": 1\n}"));
+        component
+            .update_draw(Event::new_local(BodyMenuAction::ToggleRawBody))
+            .assert_empty();
+        assert_eq!(
+            text(component.data()).as_deref(),
+            Some("{\n  \"data\": {\n    \"a\": 1\n  }\n}")
+        );
+    }
+
     /// Test "Save Body as File" menu action
     #[rstest]
     #[case::json_body(
@@ -311,6 +878,7 @@ mod tests {
                 request_id: exchange.id,
                 recipe_id: &exchange.request.recipe_id,
                 response: exchange.response,
+                graphql: false,
             },
         );
 
@@ -4,10 +4,16 @@ use crate::{
     view::{
         common::modal::ModalQueue,
         component::{
+            connection_error::ConnectionErrorModal,
+            environment::EnvironmentVariablesModal,
             help::HelpFooter,
             history::History,
-            misc::NotificationText,
+            in_flight::{
+                InFlightModal, InFlightText, SessionRequest, IN_FLIGHT_LIMIT,
+            },
+            misc::{CollectionErrorText, GitStatusText, NotificationText},
             primary::{PrimaryView, PrimaryViewProps},
+            workspace::{WorkspaceModal, WorkspaceText},
         },
         draw::{Draw, DrawMetadata, Generate},
         event::{Child, Event, EventHandler, Update},
@@ -24,9 +30,13 @@ use ratatui::{layout::Layout, prelude::Constraint, Frame};
 use serde::Serialize;
 use slumber_config::Action;
 use slumber_core::{
-    collection::{Collection, ProfileId},
+    collection::{
+        Collection, CollectionChange, ProfileId, RecipeId, Workspace,
+    },
     http::RequestId,
+    util::{doc_link, git::GitStatus},
 };
+use std::sync::Arc;
 
 /// The root view component
 #[derive(Debug)]
@@ -34,8 +44,20 @@ pub struct Root {
     // ===== Own State =====
     /// Track and cache in-progress and completed requests
     request_store: RequestStore,
+    /// Requests sent during this session, in the order they were launched.
+    /// Used to list them so the user can switch between concurrent requests
+    sent_requests: Vec<RequestId>,
     /// Which request are we showing in the request/response panel?
     selected_request: PersistedLazy<SelectedRequestKey, SelectedRequestId>,
+    /// Git status of the collection file. `None` if it's not in a repository
+    /// or we haven't checked yet
+    git_status: Option<GitStatus>,
+    /// Workspace that the collection belongs to, and the label of the
+    /// collection's member within it. `None` if not in a workspace
+    workspace: Option<(Arc<Workspace>, String)>,
+    /// Error from the most recent collection reload. Cleared by the next
+    /// successful reload
+    collection_error: Option<anyhow::Error>,
 
     // ==== Children =====
     primary_view: Component<PrimaryView>,
@@ -52,7 +74,11 @@ impl Root {
         Self {
             // State
             request_store: RequestStore::default(),
+            sent_requests: Vec::new(),
             selected_request,
+            git_status: None,
+            workspace: None,
+            collection_error: None,
 
             // Children
             primary_view: primary_view.into(),
@@ -66,6 +92,47 @@ impl Root {
         self.primary_view.data().selected_profile_id()
     }
 
+    /// Update the collection file's git status
+    pub fn set_git_status(&mut self, status: Option<GitStatus>) {
+        self.git_status = status;
+    }
+
+    /// Set the workspace that the collection belongs to
+    pub fn set_workspace(&mut self, workspace: Arc<Workspace>, member: String) {
+        self.workspace = Some((workspace, member));
+    }
+
+    /// Update for a reloaded collection. See
+    /// [PrimaryView::reload_collection] for the meaning of `changes`.
+    pub fn reload_collection(
+        &mut self,
+        collection: &Collection,
+        changes: Option<&[CollectionChange]>,
+    ) {
+        self.collection_error = None;
+        self.primary_view
+            .data_mut()
+            .reload_collection(collection, changes);
+    }
+
+    /// Show an error from reloading the collection, in place of whatever
+    /// error was there before
+    pub fn set_collection_error(&mut self, error: anyhow::Error) {
+        self.collection_error = Some(error);
+    }
+
+    /// Open the modal to switch to another collection in the workspace
+    fn open_workspace(&self) {
+        if let Some((workspace, member)) = &self.workspace {
+            ViewContext::open_modal(WorkspaceModal::new(workspace, member));
+        } else {
+            ViewContext::send_message(Message::Notify(format!(
+                "Not in a workspace; see {}",
+                doc_link("api/workspace")
+            )));
+        }
+    }
+
     /// Select the given request. This will ensure the request data is loaded
     /// in memory.
     fn select_request(
@@ -130,6 +197,38 @@ impl Root {
         }
         Ok(())
     }
+
+    /// Open a list of the environment variables the collection uses
+    fn open_environment_variables(&self) {
+        let collection = ViewContext::collection();
+        match EnvironmentVariablesModal::new(&collection)
+            .reported(&ViewContext::messages_tx())
+        {
+            Some(Some(modal)) => ViewContext::open_modal(modal),
+            Some(None) => ViewContext::send_message(Message::Notify(
+                "Collection doesn't use any environment variables".into(),
+            )),
+            // Error was already reported
+            None => {}
+        }
+    }
+
+    /// Open a list of requests sent during this session, to switch between
+    /// them while they're in flight or as they land
+    fn open_in_flight(&self) {
+        let requests = self
+            .sent_requests
+            .iter()
+            .rev()
+            .filter_map(|id| self.request_store.get(*id))
+            .take(IN_FLIGHT_LIMIT)
+            .map(SessionRequest::from)
+            .collect();
+        ViewContext::open_modal(InFlightModal::new(
+            requests,
+            self.selected_request.0,
+        ));
+    }
 }
 
 impl EventHandler for Root {
@@ -143,11 +242,37 @@ impl EventHandler for Root {
             // Update state of in-progress HTTP request
             Event::HttpSetState(state) => {
                 let id = state.id();
-                // If this request is *new*, select it
+                // Requests can be sent for other recipes (e.g. in a batch),
+                // and those shouldn't replace what's on screen. Scratch
+                // requests don't belong to any recipe, so they're always shown
+                let primary_view = self.primary_view.data();
+                let is_current = (primary_view.selected_recipe_id()
+                    == Some(state.recipe_id())
+                    && primary_view.selected_profile_id()
+                        == state.profile_id())
+                    || state.recipe_id() == &RecipeId::scratch();
+                // If the request couldn't reach the server, help the user
+                // figure out why
+                if let RequestState::RequestError { error } = &state {
+                    if is_current && error.kind().is_connection() {
+                        ViewContext::open_modal(ConnectionErrorModal::new(
+                            error,
+                        ));
+                    }
+                }
+                // If this request is *new*, track it and select it. Other
+                // requests may still be in flight; they stay in the store and
+                // can be switched back to
                 if self.request_store.update(state) {
-                    *self.selected_request.get_mut() = Some(id).into();
+                    self.sent_requests.push(id);
+                    if is_current {
+                        *self.selected_request.get_mut() = Some(id).into();
+                    }
                 }
             }
+            Event::HttpSetProgress { id, progress } => {
+                self.request_store.set_progress(id, progress)
+            }
 
             Event::Notify(notification) => {
                 self.notification_text =
@@ -166,6 +291,22 @@ impl EventHandler for Root {
                 Action::ReloadCollection => {
                     ViewContext::send_message(Message::CollectionStartReload)
                 }
+                Action::SwitchCollection => self.open_workspace(),
+                Action::InFlight => self.open_in_flight(),
+                Action::EnvironmentVariables => {
+                    self.open_environment_variables()
+                }
+                // Only requests that haven't finished can be cancelled
+                Action::CancelRequest => {
+                    if let Some(state) = self
+                        .selected_request()
+                        .filter(|state| state.is_in_flight())
+                    {
+                        ViewContext::send_message(Message::HttpCancel(
+                            state.id(),
+                        ));
+                    }
+                }
                 _ => return Update::Propagate(event),
             },
 
@@ -190,9 +331,24 @@ impl EventHandler for Root {
 impl Draw for Root {
     fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
         // Create layout
-        let [main_area, footer_area] =
-            Layout::vertical([Constraint::Min(0), Constraint::Length(1)])
-                .areas(metadata.area());
+        let banner_height = if self.collection_error.is_some() {
+            1
+        } else {
+            0
+        };
+        let [banner_area, main_area, footer_area] = Layout::vertical([
+            Constraint::Length(banner_height),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(metadata.area());
+
+        if let Some(error) = &self.collection_error {
+            frame.render_widget(
+                CollectionErrorText(error).generate(),
+                banner_area,
+            );
+        }
 
         // Main content
         self.primary_view.draw(
@@ -206,14 +362,35 @@ impl Draw for Root {
 
         // Footer
         let footer = HelpFooter.generate();
-        let [notification_area, help_area] = Layout::horizontal([
-            Constraint::Min(10),
-            Constraint::Length(footer.width() as u16),
-        ])
-        .areas(footer_area);
+        let git_status = self
+            .git_status
+            .as_ref()
+            .map(|status| GitStatusText(status).generate())
+            .unwrap_or_default();
+        let workspace = self
+            .workspace
+            .as_ref()
+            .and_then(|(workspace, member)| workspace.members.get(member))
+            .map(|member| WorkspaceText(member).generate())
+            .unwrap_or_default();
+        let in_flight =
+            InFlightText(self.request_store.count_in_flight()).generate();
+        let [notify_area, in_flight_area, workspace_area, git_area, help_area] =
+            Layout::horizontal([
+                Constraint::Min(10),
+                Constraint::Length(in_flight.width() as u16),
+                Constraint::Length(workspace.width() as u16),
+                Constraint::Length(git_status.width() as u16),
+                Constraint::Length(footer.width() as u16),
+            ])
+            .spacing(1)
+            .areas(footer_area);
         if let Some(notification_text) = &self.notification_text {
-            notification_text.draw(frame, (), notification_area, false);
+            notification_text.draw(frame, (), notify_area, false);
         }
+        frame.render_widget(in_flight, in_flight_area);
+        frame.render_widget(workspace, workspace_area);
+        frame.render_widget(git_status, git_area);
         frame.render_widget(footer, help_area);
 
         // Render modals last so they go on top
@@ -252,13 +429,21 @@ mod tests {
     use crate::{
         test_util::{harness, terminal, TestHarness, TestTerminal},
         view::{
-            test_util::TestComponent, util::persistence::DatabasePersistedStore,
+            test_util::TestComponent,
+            util::persistence::DatabasePersistedStore, IntoModal,
         },
     };
-    use crossterm::event::KeyCode;
+    use anyhow::anyhow;
+    use chrono::Utc;
+    use crossterm::event::{KeyCode, KeyModifiers};
     use persisted::PersistedStore;
     use rstest::rstest;
-    use slumber_core::{assert_matches, http::Exchange, test_util::Factory};
+    use slumber_core::{
+        assert_matches,
+        http::Exchange,
+        test_util::{temp_dir, Factory, TempDir},
+    };
+    use std::fs;
 
     /// Test that, on first render, the view loads the most recent historical
     /// request for the first recipe+profile
@@ -328,6 +513,41 @@ mod tests {
         );
     }
 
+    /// Reloading the collection keeps open modals and the selected request,
+    /// and clears the previous reload error
+    #[rstest]
+    fn test_reload_collection(harness: TestHarness, terminal: TestTerminal) {
+        let collection = Collection::factory(());
+        let exchange = Exchange::factory((
+            Some(collection.first_profile_id().clone()),
+            collection.first_recipe_id().clone(),
+        ));
+        harness.database.insert_exchange(&exchange).unwrap();
+        let mut component =
+            TestComponent::new(&terminal, Root::new(&collection), ());
+
+        component
+            .data_mut()
+            .set_collection_error(anyhow!("Invalid YAML"));
+        component
+            .update_draw(Event::OpenModal(Box::new(
+                anyhow!("Still here").into_modal(),
+            )))
+            .assert_empty();
+        assert!(component.data().collection_error.is_some());
+
+        // Rebuild everything, as if the changes couldn't be determined
+        component.data_mut().reload_collection(&collection, None);
+        component.drain_draw().assert_empty();
+        let root = component.data();
+        assert!(root.collection_error.is_none());
+        assert!(root.modal_queue.data().is_open());
+        assert_eq!(
+            root.selected_request().map(RequestState::id),
+            Some(exchange.id)
+        );
+    }
+
     /// Test that if the persisted request ID isn't in the DB, we'll fall back
     /// to selecting the most recent request
     #[rstest]
@@ -364,6 +584,80 @@ mod tests {
         );
     }
 
+    /// The cancel key cancels the selected request, but only while it's in
+    /// progress
+    #[rstest]
+    fn test_cancel_request(mut harness: TestHarness, terminal: TestTerminal) {
+        let collection = Arc::clone(&harness.collection);
+        let exchange = Exchange::factory((
+            Some(collection.first_profile_id().clone()),
+            collection.first_recipe_id().clone(),
+        ));
+        harness.database.insert_exchange(&exchange).unwrap();
+        let mut component =
+            TestComponent::new(&terminal, Root::new(&collection), ());
+        harness.clear_messages();
+
+        // Completed requests can't be cancelled. If this sent a message, it
+        // would be popped below instead of the one we expect
+        component
+            .send_key_modifiers(KeyCode::Char('k'), KeyModifiers::CONTROL)
+            .assert_empty();
+
+        let id = RequestId::new();
+        component
+            .update_draw(Event::HttpSetState(RequestState::Building {
+                id,
+                start_time: Utc::now(),
+                profile_id: Some(collection.first_profile_id().clone()),
+                recipe_id: collection.first_recipe_id().clone(),
+            }))
+            .assert_empty();
+        component
+            .send_key_modifiers(KeyCode::Char('k'), KeyModifiers::CONTROL)
+            .assert_empty();
+        assert_matches!(
+            harness.pop_message_now(),
+            Message::HttpCancel(cancelled) if cancelled == id
+        );
+    }
+
+    /// Multiple requests can be in flight at once, and the user can switch
+    /// between them
+    #[rstest]
+    fn test_in_flight(harness: TestHarness, terminal: TestTerminal) {
+        let collection = Arc::clone(&harness.collection);
+        let mut component =
+            TestComponent::new(&terminal, Root::new(&collection), ());
+        let building = |id| {
+            Event::HttpSetState(RequestState::Building {
+                id,
+                start_time: Utc::now(),
+                profile_id: Some(collection.first_profile_id().clone()),
+                recipe_id: collection.first_recipe_id().clone(),
+            })
+        };
+
+        let first = RequestId::new();
+        let second = RequestId::new();
+        component.update_draw(building(first)).assert_empty();
+        component.update_draw(building(second)).assert_empty();
+        // The newest request is shown, but the first is still tracked
+        let root = component.data();
+        assert_eq!(root.request_store.count_in_flight(), 2);
+        assert_eq!(root.selected_request().map(RequestState::id), Some(second));
+
+        // Open the list and move down to the older request
+        component.send_key(KeyCode::Char('i')).assert_empty();
+        component.send_key(KeyCode::Down).assert_empty();
+        assert_eq!(
+            component.data().selected_request().map(RequestState::id),
+            Some(first)
+        );
+        component.send_key(KeyCode::Enter).assert_empty();
+        assert!(!component.data().modal_queue.data().is_open());
+    }
+
     #[rstest]
     fn test_edit_collection(mut harness: TestHarness, terminal: TestTerminal) {
         let root = Root::new(&harness.collection);
@@ -378,4 +672,37 @@ mod tests {
         component.send_key(KeyCode::Enter).assert_empty();
         assert_matches!(harness.pop_message_now(), Message::CollectionEdit);
     }
+
+    /// Switching collections should open the member list, and submitting
+    /// should send a message to load the selected member
+    #[rstest]
+    fn test_switch_collection(
+        mut harness: TestHarness,
+        #[with(60, 20)] terminal: TestTerminal,
+        temp_dir: TempDir,
+    ) {
+        let path = temp_dir.join("slumber-workspace.yml");
+        fs::write(
+            &path,
+            "members: {users: {path: users}, billing: {path: billing}}",
+        )
+        .unwrap();
+        let workspace = Workspace::load(&path).unwrap();
+        let mut root = Root::new(&harness.collection);
+        root.set_workspace(workspace.into(), "users".into());
+        let mut component = TestComponent::new(&terminal, root, ());
+
+        harness.clear_messages(); // Clear init junk
+
+        // Open the switcher with the current member preselected, then pick the
+        // next one
+        component.send_key(KeyCode::Char('o')).assert_empty();
+        component.send_key(KeyCode::Down).assert_empty();
+        component.send_key(KeyCode::Enter).assert_empty();
+        let member = assert_matches!(
+            harness.pop_message_now(),
+            Message::CollectionStartSwitch(member) => member,
+        );
+        assert_eq!(member, "billing");
+    }
 }
@@ -0,0 +1,228 @@
+//! Explain the status code of a response, with a reference from the spec and
+//! how often the recipe has returned that code recently
+
+use crate::{
+    context::TuiContext,
+    util::ResultReported,
+    view::{
+        common::{
+            modal::Modal,
+            text_window::{TextWindow, TextWindowProps},
+        },
+        component::{baseline::load_exchange, Component},
+        draw::{Draw, DrawMetadata, Generate},
+        event::{Child, Event, EventHandler, Update},
+        ViewContext,
+    },
+};
+use derive_more::Display;
+use ratatui::{
+    layout::Constraint,
+    text::{Line, Text},
+    Frame,
+};
+use reqwest::StatusCode;
+use slumber_config::Action;
+use slumber_core::http::{
+    status::status_reference, ExchangeSummary, RequestId,
+};
+use std::cell::Cell;
+
+/// Maximum number of past requests to count when calculating how often a
+/// status code occurs
+const HISTORY_LIMIT: usize = 50;
+
+/// Show a reference for the status code of a request from history, alongside
+/// how often its recipe has returned that code
+pub fn explain_status(request_id: RequestId) {
+    let Some((status, frequency)) = ViewContext::with_database(|database| {
+        let exchange = load_exchange(database, request_id)?;
+        let history = database.get_all_requests(
+            exchange.request.profile_id.as_ref(),
+            &exchange.request.recipe_id,
+        )?;
+        let status = exchange.response.status;
+        anyhow::Ok((status, StatusFrequency::new(status, &history)))
+    })
+    .reported(&ViewContext::messages_tx()) else {
+        return;
+    };
+    ViewContext::open_modal(StatusModal::new(status, frequency));
+}
+
+/// A response's status code, which opens its explanation when clicked
+#[derive(Debug, Default)]
+pub struct StatusLink {
+    /// Response that was most recently drawn. Stored during draw so the click
+    /// handler knows which request to explain
+    request_id: Cell<Option<RequestId>>,
+}
+
+pub struct StatusLinkProps {
+    pub request_id: RequestId,
+    pub status: StatusCode,
+}
+
+impl EventHandler for StatusLink {
+    fn update(&mut self, event: Event) -> Update {
+        match (event.action(), self.request_id.get()) {
+            (Some(Action::LeftClick), Some(request_id)) => {
+                explain_status(request_id)
+            }
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+}
+
+impl Draw<StatusLinkProps> for StatusLink {
+    fn draw(
+        &self,
+        frame: &mut Frame,
+        props: StatusLinkProps,
+        metadata: DrawMetadata,
+    ) {
+        self.request_id.set(Some(props.request_id));
+        frame.render_widget(props.status.generate(), metadata.area());
+    }
+}
+
+/// How often a status code appears in a recipe's recent history
+#[derive(Debug, Display, PartialEq)]
+#[display(
+    "{count} of the last {total} requests for this recipe & profile \
+    returned {}",
+    status.as_u16()
+)]
+struct StatusFrequency {
+    status: StatusCode,
+    count: usize,
+    total: usize,
+}
+
+impl StatusFrequency {
+    /// Count occurrences of a status in history, which is ordered newest
+    /// first
+    fn new(status: StatusCode, history: &[ExchangeSummary]) -> Self {
+        let recent = &history[..history.len().min(HISTORY_LIMIT)];
+        Self {
+            status,
+            count: recent
+                .iter()
+                .filter(|exchange| exchange.status == status)
+                .count(),
+            total: recent.len(),
+        }
+    }
+}
+
+/// Modal with the description and spec link for a status code
+#[derive(Debug)]
+struct StatusModal {
+    status: StatusCode,
+    text: Text<'static>,
+    text_window: Component<TextWindow>,
+}
+
+impl StatusModal {
+    fn new(status: StatusCode, frequency: StatusFrequency) -> Self {
+        let styles = &TuiContext::get().styles;
+        let mut lines: Vec<Line<'static>> = Vec::new();
+        if let Some(reference) = status_reference(status) {
+            lines.push(reference.description.into());
+            lines.push(Line::default());
+            lines.push(Line::styled(reference.spec, styles.text.hint));
+            lines.push(Line::default());
+        }
+        lines.push(frequency.to_string().into());
+        Self {
+            status,
+            text: lines.into(),
+            text_window: Component::default(),
+        }
+    }
+}
+
+impl Modal for StatusModal {
+    fn title(&self) -> Line<'_> {
+        self.status.to_string().into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Percentage(60),
+            Constraint::Length(self.text.height() as u16),
+        )
+    }
+}
+
+impl EventHandler for StatusModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.text_window.to_child_mut()]
+    }
+}
+
+impl Draw for StatusModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        self.text_window.draw(
+            frame,
+            TextWindowProps {
+                text: &self.text,
+                marks: &[],
+                margins: Default::default(),
+                footer: None,
+            },
+            metadata.area(),
+            true,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{harness, TestHarness};
+    use rstest::rstest;
+    use slumber_core::{
+        assert_matches,
+        collection::RecipeId,
+        http::{Exchange, RequestRecord, ResponseRecord},
+        test_util::Factory,
+    };
+
+    /// Frequency only counts the recipe's own history
+    #[rstest]
+    fn test_explain_status(harness: TestHarness) {
+        let recipe_id = RecipeId::factory(());
+        let exchange = |recipe_id: &RecipeId, status| {
+            Exchange::factory((
+                RequestRecord::factory((None, recipe_id.clone())),
+                ResponseRecord::factory(status),
+            ))
+        };
+        let exchanges = [
+            exchange(&recipe_id, StatusCode::NOT_FOUND),
+            exchange(&recipe_id, StatusCode::OK),
+            exchange(&recipe_id, StatusCode::NOT_FOUND),
+            exchange(&RecipeId::factory(()), StatusCode::NOT_FOUND),
+        ];
+        for exchange in &exchanges {
+            harness.database.insert_exchange(exchange).unwrap();
+        }
+
+        let history =
+            harness.database.get_all_requests(None, &recipe_id).unwrap();
+        let frequency = StatusFrequency::new(StatusCode::NOT_FOUND, &history);
+        assert_eq!(
+            frequency.to_string(),
+            "2 of the last 3 requests for this recipe & profile returned 404"
+        );
+
+        explain_status(exchanges[0].id);
+        let modal = assert_matches!(
+            ViewContext::pop_event(),
+            Some(Event::OpenModal(modal)) => modal,
+        );
+        assert_eq!(modal.title(), Line::from("404 Not Found"));
+    }
+}
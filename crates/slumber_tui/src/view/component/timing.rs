@@ -0,0 +1,240 @@
+use crate::{
+    context::TuiContext,
+    view::{
+        draw::{Draw, DrawMetadata},
+        event::EventHandler,
+    },
+};
+use ratatui::{
+    text::{Line, Span, Text},
+    Frame,
+};
+use slumber_core::{http::ExchangeTiming, util::format_duration};
+use std::time::Duration;
+
+/// Width of the phase label column, including padding
+const LABEL_WIDTH: usize = 10;
+/// Width of the duration column, including padding
+const DURATION_WIDTH: usize = 9;
+
+/// Waterfall of how long each phase of an exchange took. Each phase gets a
+/// bar, offset by the phases before it, so the bars line up end to end
+#[derive(Debug, Default)]
+pub struct TimingView;
+
+#[derive(Clone)]
+pub struct TimingViewProps<'a> {
+    /// `None` if the exchange wasn't timed
+    pub timing: Option<&'a ExchangeTiming>,
+}
+
+impl EventHandler for TimingView {}
+
+impl<'a> Draw<TimingViewProps<'a>> for TimingView {
+    fn draw(
+        &self,
+        frame: &mut Frame,
+        props: TimingViewProps<'a>,
+        metadata: DrawMetadata,
+    ) {
+        let Some(timing) = props.timing else {
+            frame.render_widget(
+                "No timing breakdown for this request",
+                metadata.area(),
+            );
+            return;
+        };
+        let styles = &TuiContext::get().styles;
+        let bar_width = (metadata.area().width as usize)
+            .saturating_sub(LABEL_WIDTH + DURATION_WIDTH);
+        let total = timing.total();
+        // Convert a point in time to a column in the bar area
+        let column = |elapsed: Duration| {
+            if total.is_zero() {
+                0
+            } else {
+                (elapsed.as_secs_f64() / total.as_secs_f64() * bar_width as f64)
+                    .round() as usize
+            }
+        };
+
+        let phases = [
+            ("DNS", timing.dns),
+            ("Connect", timing.connect),
+            ("TLS", timing.tls),
+            ("Waiting", Some(timing.wait)),
+            ("Download", Some(timing.download)),
+        ];
+        let mut elapsed = Duration::ZERO;
+        let mut lines: Vec<Line> = phases
+            .into_iter()
+            .map(|(label, duration)| {
+                let Some(duration) = duration else {
+                    // Phase didn't happen, e.g. connecting on a reused
+                    // connection
+                    return Line::from(vec![
+                        format!("{label:<LABEL_WIDTH$}").into(),
+                        Span::styled(
+                            format!("{:>w$}", "-", w = DURATION_WIDTH - 1),
+                            styles.text.hint,
+                        ),
+                    ]);
+                };
+                let start = column(elapsed);
+                elapsed += duration;
+                // Anything that took time gets at least one cell, so it
+                // doesn't disappear next to a slow phase
+                let end = column(elapsed).max(start + 1).min(bar_width);
+                Line::from(vec![
+                    format!("{label:<LABEL_WIDTH$}").into(),
+                    format!(
+                        "{:>w$} ",
+                        display_duration(duration),
+                        w = DURATION_WIDTH - 1
+                    )
+                    .into(),
+                    " ".repeat(start).into(),
+                    Span::styled(
+                        "█".repeat(end.saturating_sub(start)),
+                        styles.text.primary,
+                    ),
+                ])
+            })
+            .collect();
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{:<LABEL_WIDTH$}", "Total"),
+                styles.text.title,
+            ),
+            Span::styled(
+                format!(
+                    "{:>w$}",
+                    display_duration(total),
+                    w = DURATION_WIDTH - 1
+                ),
+                styles.text.title,
+            ),
+        ]));
+        frame.render_widget(Text::from(lines), metadata.area());
+    }
+}
+
+/// Format a duration the same way as everywhere else in the TUI
+fn display_duration(duration: Duration) -> String {
+    format_duration(&chrono::Duration::from_std(duration).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{harness, terminal, TestHarness, TestTerminal},
+        view::test_util::TestComponent,
+    };
+    use rstest::rstest;
+
+    /// Each phase's bar starts where the previous one ended
+    #[rstest]
+    fn test_waterfall(
+        #[with(29, 6)] terminal: TestTerminal,
+        _harness: TestHarness,
+    ) {
+        let timing = ExchangeTiming {
+            dns: Some(Duration::from_millis(20)),
+            connect: Some(Duration::from_millis(10)),
+            tls: Some(Duration::from_millis(10)),
+            wait: Duration::from_millis(40),
+            download: Duration::from_millis(20),
+        };
+        TestComponent::new(
+            &terminal,
+            TimingView,
+            TimingViewProps {
+                timing: Some(&timing),
+            },
+        );
+
+        let styles = &TuiContext::get().styles;
+        let bar = |text: &'static str| Span::styled(text, styles.text.primary);
+        terminal.assert_buffer_lines([
+            Line::from(vec![
+                "DNS           20ms ".into(),
+                bar("██"),
+                "        ".into(),
+            ]),
+            Line::from(vec![
+                "Connect       10ms   ".into(),
+                bar("█"),
+                "       ".into(),
+            ]),
+            Line::from(vec![
+                "TLS           10ms    ".into(),
+                bar("█"),
+                "      ".into(),
+            ]),
+            Line::from(vec![
+                "Waiting       40ms     ".into(),
+                bar("████"),
+                "  ".into(),
+            ]),
+            Line::from(vec!["Download      20ms         ".into(), bar("██")]),
+            Line::from(vec![
+                Span::styled("Total        100ms", styles.text.title),
+                "           ".into(),
+            ]),
+        ]);
+    }
+
+    /// A reused connection doesn't need a DNS lookup, connecting, or a TLS
+    /// handshake
+    #[rstest]
+    fn test_reused_connection(
+        #[with(29, 6)] terminal: TestTerminal,
+        _harness: TestHarness,
+    ) {
+        let timing = ExchangeTiming {
+            dns: None,
+            connect: None,
+            tls: None,
+            wait: Duration::from_millis(50),
+            download: Duration::from_millis(50),
+        };
+        TestComponent::new(
+            &terminal,
+            TimingView,
+            TimingViewProps {
+                timing: Some(&timing),
+            },
+        );
+
+        let styles = &TuiContext::get().styles;
+        let bar = |text: &'static str| Span::styled(text, styles.text.primary);
+        terminal.assert_buffer_lines([
+            Line::from(vec![
+                "DNS       ".into(),
+                Span::styled("       -", styles.text.hint),
+                "           ".into(),
+            ]),
+            Line::from(vec![
+                "Connect   ".into(),
+                Span::styled("       -", styles.text.hint),
+                "           ".into(),
+            ]),
+            Line::from(vec![
+                "TLS       ".into(),
+                Span::styled("       -", styles.text.hint),
+                "           ".into(),
+            ]),
+            Line::from(vec![
+                "Waiting       50ms ".into(),
+                bar("█████"),
+                "     ".into(),
+            ]),
+            Line::from(vec!["Download      50ms      ".into(), bar("█████")]),
+            Line::from(vec![
+                Span::styled("Total        100ms", styles.text.title),
+                "           ".into(),
+            ]),
+        ]);
+    }
+}
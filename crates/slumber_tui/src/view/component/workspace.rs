@@ -0,0 +1,130 @@
+//! Components for switching between collections in a workspace
+
+use crate::{
+    context::TuiContext,
+    message::Message,
+    view::{
+        common::{list::List, modal::Modal},
+        draw::{Draw, DrawMetadata, Generate},
+        event::{Child, Event, EventHandler},
+        state::select::SelectState,
+        Component, ViewContext,
+    },
+};
+use ratatui::{
+    layout::Constraint,
+    text::{Line, Text},
+    Frame,
+};
+use slumber_config::Action;
+use slumber_core::collection::{Workspace, WorkspaceMember};
+
+/// Footer text showing the current workspace member, with a hint for the
+/// switcher
+pub struct WorkspaceText<'a>(pub &'a WorkspaceMember);
+
+impl<'a> Generate for WorkspaceText<'a> {
+    type Output<'this> = Line<'this>
+    where
+        Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        let tui_context = TuiContext::get();
+        let text = tui_context
+            .input_engine
+            .add_hint(self.0.name(), Action::SwitchCollection);
+        Line::styled(text, tui_context.styles.text.hint)
+    }
+}
+
+/// Modal to pick a collection from the workspace
+#[derive(Debug)]
+pub struct WorkspaceModal {
+    select: Component<SelectState<MemberListItem>>,
+}
+
+impl WorkspaceModal {
+    pub fn new(workspace: &Workspace, selected_member: &str) -> Self {
+        fn on_submit(member: &mut MemberListItem) {
+            ViewContext::push_event(Event::CloseModal { submitted: true });
+            ViewContext::send_message(Message::CollectionStartSwitch(
+                member.id.clone(),
+            ));
+        }
+
+        let members = workspace
+            .members
+            .values()
+            .map(|member| MemberListItem {
+                id: member.id.clone(),
+                name: member.name().to_owned(),
+            })
+            .collect();
+        let select = SelectState::builder(members)
+            .preselect(&selected_member)
+            .on_submit(on_submit)
+            .build();
+        Self {
+            select: select.into(),
+        }
+    }
+}
+
+impl Modal for WorkspaceModal {
+    fn title(&self) -> Line<'_> {
+        "Collections".into()
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Length(40),
+            Constraint::Length(self.select.data().len().min(20) as u16),
+        )
+    }
+}
+
+impl EventHandler for WorkspaceModal {
+    fn children(&mut self) -> Vec<Component<Child<'_>>> {
+        vec![self.select.to_child_mut()]
+    }
+}
+
+impl Draw for WorkspaceModal {
+    fn draw(&self, frame: &mut Frame, _: (), metadata: DrawMetadata) {
+        self.select.draw(
+            frame,
+            List::from(self.select.data()),
+            metadata.area(),
+            true,
+        );
+    }
+}
+
+/// A workspace member in the switcher list
+#[derive(Debug)]
+struct MemberListItem {
+    id: String,
+    name: String,
+}
+
+impl PartialEq<MemberListItem> for &str {
+    fn eq(&self, item: &MemberListItem) -> bool {
+        *self == item.id
+    }
+}
+
+impl<'a> Generate for &'a MemberListItem {
+    type Output<'this> = Text<'this>
+    where
+        Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        self.name.as_str().into()
+    }
+}
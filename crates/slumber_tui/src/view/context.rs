@@ -21,8 +21,8 @@ use tracing::debug;
 /// view code. We're leaning heavily on the fact that the view is
 /// single-threaded here.
 pub struct ViewContext {
-    /// The request collection. This is immutable through the lifespan of the
-    /// view; the entire view is rebuilt when the collection reloads.
+    /// The request collection. This is replaced when the collection file is
+    /// reloaded; see [Self::set_collection].
     collection: Arc<Collection>,
     /// Persistence database. The TUI only ever needs to run DB ops related to
     /// our collection, so we can use a collection-restricted DB handle
@@ -91,6 +91,12 @@ impl ViewContext {
         })
     }
 
+    /// Replace the request collection after a reload. Everything else in the
+    /// context, including queued events and recipe overrides, is retained.
+    pub fn set_collection(collection: Arc<Collection>) {
+        Self::with_mut(|context| context.collection = collection)
+    }
+
     /// Get a pointer to the request collection
     pub fn collection() -> Arc<Collection> {
         Self::with(|context| Arc::clone(&context.collection))
@@ -8,7 +8,7 @@ use crate::view::{
 };
 use persisted::{PersistedContainer, PersistedLazyRefMut, PersistedStore};
 use slumber_config::Action;
-use slumber_core::http::RequestId;
+use slumber_core::http::{DownloadProgress, RequestId};
 use std::{
     any::Any,
     collections::VecDeque,
@@ -191,6 +191,11 @@ pub enum Event {
     HttpSelectRequest(Option<RequestId>),
     /// Update the state of an in-progress HTTP request
     HttpSetState(RequestState),
+    /// Update download progress of an in-flight HTTP request
+    HttpSetProgress {
+        id: RequestId,
+        progress: DownloadProgress,
+    },
 
     /// Show a modal to the user
     OpenModal(Box<dyn Modal>),
@@ -9,9 +9,10 @@ use derive_more::Deref;
 use reqwest::StatusCode;
 use slumber_core::{
     collection::{ProfileId, RecipeId},
+    db::FailureSummary,
     http::{
-        Exchange, ExchangeSummary, RequestBuildError, RequestError, RequestId,
-        RequestRecord,
+        failure::FailureKind, DownloadProgress, Exchange, ExchangeSummary,
+        RequestBuildError, RequestError, RequestId, RequestRecord,
     },
 };
 use std::{
@@ -97,8 +98,8 @@ impl<K, V> Default for StateCell<K, V> {
 }
 
 /// State of an HTTP response, which can be in various states of
-/// completion/failure. Each request *recipe* should have one request state
-/// stored in the view at a time.
+/// completion/failure. Multiple requests for the same recipe can be in flight
+/// at once, so each state is tracked under its own request ID.
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub enum RequestState {
@@ -122,6 +123,9 @@ pub enum RequestState {
         /// pointer to the request as well
         request: Arc<RequestRecord>,
         start_time: DateTime<Utc>,
+        /// How much of the response body has downloaded. Only set once the
+        /// download has been going for long enough to be worth showing
+        progress: Option<DownloadProgress>,
     },
 
     /// A resolved HTTP response, with all content loaded and ready to be
@@ -190,6 +194,11 @@ impl RequestState {
         }
     }
 
+    /// Is the request still building or loading?
+    pub fn is_in_flight(&self) -> bool {
+        matches!(self, Self::Building { .. } | Self::Loading { .. })
+    }
+
     /// Get metadata about a request. Return `None` if the request hasn't been
     /// successfully built (yet)
     pub fn request_metadata(&self) -> RequestMetadata {
@@ -251,6 +260,7 @@ impl RequestState {
         Self::Loading {
             request,
             start_time: Utc::now(),
+            progress: None,
         }
     }
 
@@ -268,7 +278,7 @@ impl RequestState {
 /// A simplified version of [RequestState], which only stores metadata. This is
 /// useful when you want to show a list of requests and don't need the entire
 /// request/response data for each one.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum RequestStateSummary {
     Building {
         id: RequestId,
@@ -287,6 +297,7 @@ pub enum RequestStateSummary {
     RequestError {
         id: RequestId,
         time: DateTime<Utc>,
+        kind: FailureKind,
     },
 }
 
@@ -318,6 +329,17 @@ impl RequestStateSummary {
             Self::Response(exchange) => exchange.start_time,
         }
     }
+
+    /// Why did the request fail? `None` if it hasn't failed (yet)
+    pub fn failure_kind(&self) -> Option<FailureKind> {
+        match self {
+            Self::BuildError { .. } => Some(FailureKind::Build),
+            Self::RequestError { kind, .. } => Some(*kind),
+            Self::Building { .. }
+            | Self::Loading { .. }
+            | Self::Response(_) => None,
+        }
+    }
 }
 
 impl From<&RequestState> for RequestStateSummary {
@@ -327,10 +349,19 @@ impl From<&RequestState> for RequestStateSummary {
                 id: *id,
                 start_time: *start_time,
             },
-            RequestState::BuildError { error } => Self::BuildError {
-                id: error.id,
-                start_time: error.start_time,
-                end_time: error.end_time,
+            RequestState::BuildError { error } => match error.kind() {
+                FailureKind::Build => Self::BuildError {
+                    id: error.id,
+                    start_time: error.start_time,
+                    end_time: error.end_time,
+                },
+                // The build was cancelled, which is stored the same way as a
+                // cancelled send
+                kind => Self::RequestError {
+                    id: error.id,
+                    time: error.start_time,
+                    kind,
+                },
             },
             RequestState::Loading {
                 request,
@@ -346,6 +377,26 @@ impl From<&RequestState> for RequestStateSummary {
             RequestState::RequestError { error } => Self::RequestError {
                 id: error.request.id,
                 time: error.start_time,
+                kind: error.kind(),
+            },
+        }
+    }
+}
+
+/// Failures from a previous session are stored in the DB without their full
+/// request, so they're only available as summaries
+impl From<FailureSummary> for RequestStateSummary {
+    fn from(failure: FailureSummary) -> Self {
+        match failure.kind {
+            FailureKind::Build => Self::BuildError {
+                id: failure.id,
+                start_time: failure.start_time,
+                end_time: failure.end_time,
+            },
+            kind => Self::RequestError {
+                id: failure.id,
+                time: failure.start_time,
+                kind,
             },
         }
     }
@@ -4,7 +4,7 @@ use crate::view::{
 use itertools::Itertools;
 use slumber_core::{
     collection::{ProfileId, RecipeId},
-    http::RequestId,
+    http::{DownloadProgress, RequestId},
 };
 use std::collections::{hash_map::Entry, HashMap};
 
@@ -37,6 +37,25 @@ impl RequestStore {
         self.requests.insert(state.id(), state).is_none()
     }
 
+    /// Update download progress of an in-flight request. If the request is no
+    /// longer loading (e.g. the progress message arrived late), do nothing
+    pub fn set_progress(&mut self, id: RequestId, progress: DownloadProgress) {
+        if let Some(RequestState::Loading {
+            progress: current, ..
+        }) = self.requests.get_mut(&id)
+        {
+            *current = Some(progress);
+        }
+    }
+
+    /// Number of requests that are still building or loading
+    pub fn count_in_flight(&self) -> usize {
+        self.requests
+            .values()
+            .filter(|state| state.is_in_flight())
+            .count()
+    }
+
     /// Load a request from the database by ID. If already present in the store,
     /// do *not* update it. Only go to the DB if it's missing. Return the loaded
     /// request. Return `None` only if the ID is not present in the store *or*
@@ -102,8 +121,11 @@ impl RequestStore {
     ) -> anyhow::Result<impl 'a + Iterator<Item = RequestStateSummary>> {
         // Load summaries from the DB. We do *not* want to insert these into the
         // store, because they don't include request/response data
-        let loaded = ViewContext::with_database(|database| {
-            database.get_all_requests(profile_id, recipe_id)
+        let (loaded, failures) = ViewContext::with_database(|database| {
+            anyhow::Ok((
+                database.get_all_requests(profile_id, recipe_id)?,
+                database.get_failures(profile_id, recipe_id, None)?,
+            ))
         })?;
 
         // Find what we have in memory already
@@ -117,6 +139,7 @@ impl RequestStore {
             .map(RequestStateSummary::from)
             // Add what we loaded from the DB
             .chain(loaded.into_iter().map(RequestStateSummary::Response))
+            .chain(failures.into_iter().map(RequestStateSummary::from))
             // Sort descending
             .sorted_by_key(RequestStateSummary::time)
             .rev()
@@ -135,7 +158,10 @@ mod tests {
     use rstest::rstest;
     use slumber_core::{
         assert_matches,
-        http::{Exchange, RequestBuildError, RequestError, RequestRecord},
+        http::{
+            failure::FailureKind, Exchange, RequestBuildError, RequestError,
+            RequestRecord,
+        },
         test_util::Factory,
     };
     use std::sync::Arc;
@@ -173,11 +199,26 @@ mod tests {
         assert!(!store.update(RequestState::Loading {
             request: Arc::clone(&exchange.request),
             start_time: exchange.start_time,
+            progress: None,
         }));
         assert_matches!(store.get(id), Some(RequestState::Loading { .. }));
 
+        let progress = DownloadProgress {
+            downloaded: 1000,
+            total: Some(2000),
+        };
+        store.set_progress(id, progress);
+        assert_matches!(
+            store.get(id),
+            Some(RequestState::Loading { progress: Some(p), .. })
+                if *p == progress
+        );
+
+        assert_eq!(store.count_in_flight(), 1);
+
         assert!(!store.update(RequestState::response(exchange)));
         assert_matches!(store.get(id), Some(RequestState::Response { .. }));
+        assert_eq!(store.count_in_flight(), 0);
 
         // Insert a new request, just to make sure it's independent
         let exchange2 = Exchange::factory(());
@@ -190,6 +231,7 @@ mod tests {
         }));
         assert_matches!(store.get(id), Some(RequestState::Response { .. }));
         assert_matches!(store.get(id2), Some(RequestState::Building { .. }));
+        assert_eq!(store.count_in_flight(), 1);
     }
 
     #[rstest]
@@ -339,6 +381,7 @@ mod tests {
         store.update(RequestState::Loading {
             request: request.into(),
             start_time: Utc::now(),
+            progress: None,
         });
 
         let request = RequestRecord::factory((
@@ -409,6 +452,50 @@ mod tests {
         );
     }
 
+    /// Failures from previous sessions are loaded from the DB, but a failure
+    /// from this session is only listed once
+    #[rstest]
+    fn test_load_summaries_failures(harness: TestHarness) {
+        let recipe_id = RecipeId::factory(());
+        let failure = || RequestError {
+            error: anyhow!("oh no!"),
+            request: RequestRecord::factory((None, recipe_id.clone())).into(),
+            start_time: Utc::now(),
+            end_time: Utc::now(),
+        };
+        let old_failure = failure();
+        let new_failure = failure();
+        let new_id = new_failure.request.id;
+        harness.database.insert_failure(&old_failure).unwrap();
+        harness.database.insert_failure(&new_failure).unwrap();
+        let build_error = RequestBuildError {
+            profile_id: None,
+            recipe_id: recipe_id.clone(),
+            id: RequestId::new(),
+            start_time: Utc::now(),
+            end_time: Utc::now(),
+            error: anyhow!("oh no!"),
+        };
+        harness.database.insert_build_failure(&build_error).unwrap();
+
+        let mut store = RequestStore::default();
+        store.update(RequestState::RequestError { error: new_failure });
+
+        let loaded = store
+            .load_summaries(None, &recipe_id)
+            .unwrap()
+            .map(|summary| (summary.id(), summary.failure_kind()))
+            .collect_vec();
+        assert_eq!(
+            loaded,
+            &[
+                (build_error.id, Some(FailureKind::Build)),
+                (new_id, Some(FailureKind::Other)),
+                (old_failure.request.id, Some(FailureKind::Other)),
+            ]
+        );
+    }
+
     /// Create a exchange with the given profile+recipe ID (or random if
     /// None), and insert it into the DB
     fn create_exchange(
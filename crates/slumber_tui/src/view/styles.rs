@@ -10,6 +10,7 @@ use slumber_config::Theme;
 /// there. Styles are grouped into sub-structs generally by component.
 #[derive(Debug)]
 pub struct Styles {
+    pub diff: DiffStyles,
     pub list: ListStyles,
     pub modal: ModalStyles,
     pub pane: PaneStyles,
@@ -22,6 +23,15 @@ pub struct Styles {
     pub text_window: TextWindowStyle,
 }
 
+/// Styles for diffs between two versions of something
+#[derive(Debug)]
+pub struct DiffStyles {
+    /// Line only in the new version
+    pub added: Style,
+    /// Line only in the old version
+    pub removed: Style,
+}
+
 /// Styles for List component
 #[derive(Debug)]
 pub struct ListStyles {
@@ -124,11 +134,17 @@ pub struct TextBoxStyle {
 pub struct TextWindowStyle {
     /// Line numbers on large text areas
     pub gutter: Style,
+    /// Line numbers of marked lines, e.g. bookmarks
+    pub mark: Style,
 }
 
 impl Styles {
     pub fn new(theme: &Theme) -> Self {
         Self {
+            diff: DiffStyles {
+                added: Style::default().fg(theme.success_color),
+                removed: Style::default().fg(theme.error_color),
+            },
             list: ListStyles {
                 highlight: Style::default()
                     .bg(theme.primary_color)
@@ -199,6 +215,9 @@ impl Styles {
             },
             text_window: TextWindowStyle {
                 gutter: Style::default().fg(Color::DarkGray),
+                mark: Style::default()
+                    .fg(theme.primary_text_color)
+                    .bg(theme.primary_color),
             },
         }
     }
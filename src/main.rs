@@ -2,7 +2,7 @@
 #![deny(clippy::all)]
 
 use anyhow::Context;
-use slumber_cli::Args;
+use slumber_cli::{prompt_passphrase, Args};
 use slumber_core::util::{DataDirectory, ResultTraced};
 use slumber_tui::Tui;
 use std::{
@@ -26,7 +26,12 @@ async fn main() -> anyhow::Result<ExitCode> {
         // Run the TUI
         None => {
             // This should return the error so we get a full stack trac
-            Tui::start(args.global.file).await?;
+            Tui::start(
+                args.global.file,
+                args.global.offline,
+                prompt_passphrase,
+            )
+            .await?;
             Ok(ExitCode::SUCCESS)
         }
 